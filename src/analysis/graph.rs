@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use petgraph::{Graph, Direction};
-use petgraph::graph::NodeIndex;
+use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::visit::EdgeRef;
 use crate::sql::{QualifiedIdent, SqlObject, ObjectType};
 use crate::builtin_catalog::BuiltinCatalog;
+use crate::analysis::SearchPath;
+use crate::db::scanner::normalize_path;
 use tracing::debug;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -12,7 +15,7 @@ pub struct ObjectRef {
     pub qualified_name: QualifiedIdent,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DependencyType {
     /// Structural dependency - dependent must be recreated when dependency changes
     /// Examples: views depending on tables, triggers depending on functions
@@ -24,6 +27,99 @@ pub enum DependencyType {
     Soft,
 }
 
+/// A dependency edge that is part of a cycle: `dependent` depends on `dependency`
+#[derive(Debug, Clone)]
+pub struct CycleEdge {
+    pub dependency: ObjectRef,
+    pub dependent: ObjectRef,
+    pub kind: DependencyType,
+}
+
+/// A circular chain of dependencies, in edge order (each edge's dependent is
+/// the next edge's dependency, and the last dependent is the first dependency)
+#[derive(Debug, Clone)]
+pub struct DependencyCycle {
+    pub edges: Vec<CycleEdge>,
+}
+
+impl DependencyCycle {
+    /// The objects in the cycle, each a dependency of the next and the last
+    /// of the first
+    pub fn names(&self) -> Vec<String> {
+        self.edges.iter()
+            .map(|edge| display_name(&edge.dependency))
+            .collect()
+    }
+
+    /// Short form of the cycle, e.g. `api.a -> api.b -> api.a`
+    pub fn path(&self) -> String {
+        let mut names = self.names();
+        if let Some(first) = names.first().cloned() {
+            names.push(first);
+        }
+        names.join(" -> ")
+    }
+
+    /// Soft edges in the cycle. Removing any one of them breaks the cycle.
+    pub fn soft_edges(&self) -> Vec<&CycleEdge> {
+        self.edges.iter()
+            .filter(|edge| matches!(edge.kind, DependencyType::Soft))
+            .collect()
+    }
+
+    /// Full diagnostic: every edge with the source location of the statement
+    /// that creates it, followed by a suggestion for breaking the cycle
+    pub fn describe(&self, objects: &[SqlObject]) -> String {
+        let mut output = format!("Circular dependency detected: {}\n", self.path());
+
+        for edge in &self.edges {
+            output.push_str(&format!(
+                "\n  {} ({}) depends on {} ({}) [{}]",
+                display_name(&edge.dependent),
+                format!("{:?}", edge.dependent.object_type).to_lowercase(),
+                display_name(&edge.dependency),
+                format!("{:?}", edge.dependency.object_type).to_lowercase(),
+                match edge.kind {
+                    DependencyType::Hard => "hard",
+                    DependencyType::Soft => "soft",
+                }
+            ));
+
+            let source = objects.iter().find(|obj|
+                obj.object_type == edge.dependent.object_type &&
+                obj.qualified_name == edge.dependent.qualified_name
+            );
+            if let Some(obj) = source {
+                if let Some(file) = &obj.source_file {
+                    output.push_str(&format!("\n    at {}:{}", file.display(), obj.start_line.unwrap_or(1)));
+                }
+                let referenced = edge.dependency.qualified_name.name.to_lowercase();
+                if let Some(line) = obj.ddl_statement.lines().find(|line| line.to_lowercase().contains(&referenced)) {
+                    output.push_str(&format!("\n    {}", line.trim()));
+                }
+            }
+        }
+
+        let soft_edges = self.soft_edges();
+        if soft_edges.is_empty() {
+            output.push_str("\n\nAll dependencies in this cycle are structural (hard). One of these objects \
+                must be restructured, e.g. by moving the shared part into a separate view or function.");
+        } else {
+            output.push_str("\n\nThe cycle can be broken at one of these soft dependencies (runtime function calls):");
+            for edge in soft_edges {
+                output.push_str(&format!(
+                    "\n  - {} calls {}",
+                    display_name(&edge.dependent),
+                    display_name(&edge.dependency)
+                ));
+            }
+            output.push_str("\nConsider calling the function dynamically (EXECUTE) or moving the call into a separate function.");
+        }
+
+        output
+    }
+}
+
 #[derive(Debug)]
 pub struct DependencyGraph {
     graph: Graph<ObjectRef, DependencyType>,
@@ -42,6 +138,16 @@ impl DependencyGraph {
     pub fn build_from_objects(
         objects: &[SqlObject],
         builtin_catalog: &BuiltinCatalog
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::build_with_search_path(objects, builtin_catalog, &SearchPath::default())
+    }
+
+    /// Build dependency graph from SQL objects, resolving unqualified
+    /// references through `search_path`
+    pub fn build_with_search_path(
+        objects: &[SqlObject],
+        builtin_catalog: &BuiltinCatalog,
+        search_path: &SearchPath,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let mut graph = Self::new();
 
@@ -77,10 +183,9 @@ impl DependencyGraph {
             // Add edges for relation dependencies
             for dep in &filtered_deps.relations {
                 // Relations could be tables, views, or materialized views
-                if let Some(dep_obj) = objects.iter().find(|o|
-                    &o.qualified_name == dep &&
+                if let Some(dep_obj) = search_path.resolve(dep, objects.iter().filter(|o|
                     matches!(o.object_type, ObjectType::Table | ObjectType::View | ObjectType::MaterializedView)
-                ) {
+                )) {
                     let dep_ref = ObjectRef {
                         object_type: dep_obj.object_type.clone(),
                         qualified_name: dep_obj.qualified_name.clone(),
@@ -97,11 +202,11 @@ impl DependencyGraph {
             
             // Add edges for function dependencies
             for dep in &filtered_deps.functions {
-                // Function dependencies can match both functions and procedures
-                if let Some(dep_obj) = objects.iter().find(|o| 
-                    &o.qualified_name == dep && 
-                    matches!(o.object_type, ObjectType::Function | ObjectType::Procedure)
-                ) {
+                // Function dependencies can match both functions and procedures, and the
+                // operators an operator class or family is made of
+                if let Some(dep_obj) = search_path.resolve(dep, objects.iter().filter(|o|
+                    matches!(o.object_type, ObjectType::Function | ObjectType::Procedure | ObjectType::Operator)
+                )) {
                     let dep_ref = ObjectRef {
                         object_type: dep_obj.object_type.clone(),
                         qualified_name: dep_obj.qualified_name.clone(),
@@ -122,11 +227,13 @@ impl DependencyGraph {
             // Add edges for type dependencies
             for dep in &filtered_deps.types {
                 // Type dependencies can be satisfied by types, domains, views, materialized views, or tables
-                // (all of these create implicit row types in PostgreSQL)
-                if let Some(dep_obj) = objects.iter().find(|o|
-                    &o.qualified_name == dep &&
-                    matches!(o.object_type, ObjectType::Type | ObjectType::Domain | ObjectType::View | ObjectType::MaterializedView | ObjectType::Table)
-                ) {
+                // (all of these create implicit row types in PostgreSQL), and collations and text search
+                // configurations and dictionaries, and operator families, which are named the same way
+                if let Some(dep_obj) = search_path.resolve(dep, objects.iter().filter(|o|
+                    matches!(o.object_type, ObjectType::Type | ObjectType::Domain | ObjectType::View | ObjectType::MaterializedView | ObjectType::Table
+                        | ObjectType::Collation | ObjectType::TextSearchConfiguration | ObjectType::TextSearchDictionary
+                        | ObjectType::OperatorFamily)
+                )) {
                     let dep_ref = ObjectRef {
                         object_type: dep_obj.object_type.clone(),
                         qualified_name: dep_obj.qualified_name.clone(),
@@ -134,11 +241,57 @@ impl DependencyGraph {
                     graph.add_edge(dep_ref, obj_ref.clone(), DependencyType::Hard)?;
                 }
             }
+
+            // Objects using an extension's functions or types are created after
+            // it. Updating an extension doesn't recreate them, so the edges are soft.
+            for extension in extensions_used(obj, &filtered_deps, objects, builtin_catalog, search_path) {
+                graph.add_edge(ObjectRef::from(extension), obj_ref.clone(), DependencyType::Soft)?;
+            }
         }
-        
+
+        graph.add_apply_after_edges(objects)?;
+
         Ok(graph)
     }
 
+    /// Add ordering edges for `-- pgmg:apply-after` hints. They only order
+    /// creation, so they're soft. A hint that closes a cycle is an error.
+    fn add_apply_after_edges(&mut self, objects: &[SqlObject]) -> Result<(), Box<dyn std::error::Error>> {
+        let had_cycles = self.has_cycles();
+        let source_files: Vec<Option<PathBuf>> = objects.iter()
+            .map(|obj| obj.source_file.as_deref().map(normalize_path))
+            .collect();
+
+        for (obj, source_file) in objects.iter().zip(&source_files) {
+            if obj.apply_after.is_empty() {
+                continue;
+            }
+            let obj_ref = ObjectRef::from(obj);
+
+            for hint in &obj.apply_after {
+                for (dep_obj, dep_file) in objects.iter().zip(&source_files) {
+                    if dep_file.as_ref() != Some(hint) || dep_file == source_file {
+                        continue;
+                    }
+                    self.add_edge(ObjectRef::from(dep_obj), obj_ref.clone(), DependencyType::Soft)?;
+                }
+
+                if !had_cycles {
+                    if let Some(cycle) = self.find_cycle() {
+                        return Err(format!(
+                            "The apply-after hint on {} in {} creates a dependency cycle: {}",
+                            hint.display(),
+                            source_file.as_deref().unwrap_or(hint).display(),
+                            cycle.path()
+                        ).into());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Add a node to the graph
     pub fn add_node(&mut self, object_ref: ObjectRef) -> NodeIndex {
         if let Some(&node_id) = self.node_map.get(&object_ref) {
@@ -169,20 +322,121 @@ impl DependencyGraph {
         petgraph::algo::is_cyclic_directed(&self.graph)
     }
 
+    /// Find a dependency cycle, if there is one. The shortest cycle through the
+    /// first strongly connected component that contains one is returned.
+    pub fn find_cycle(&self) -> Option<DependencyCycle> {
+        for component in petgraph::algo::tarjan_scc(&self.graph) {
+            let members: HashSet<NodeIndex> = component.iter().copied().collect();
+            let start = component[0];
+
+            // Breadth-first search within the component for a path back to `start`
+            let mut parents: HashMap<NodeIndex, (NodeIndex, EdgeIndex)> = HashMap::new();
+            let mut visited: HashSet<NodeIndex> = HashSet::from([start]);
+            let mut queue: VecDeque<NodeIndex> = VecDeque::from([start]);
+
+            while let Some(node_id) = queue.pop_front() {
+                for edge in self.graph.edges_directed(node_id, Direction::Outgoing) {
+                    let target = edge.target();
+                    if !members.contains(&target) {
+                        continue;
+                    }
+
+                    if target == start {
+                        let mut edge_ids = vec![edge.id()];
+                        let mut current = node_id;
+                        while current != start {
+                            let (parent, parent_edge) = parents[&current];
+                            edge_ids.push(parent_edge);
+                            current = parent;
+                        }
+                        edge_ids.reverse();
+
+                        return Some(DependencyCycle {
+                            edges: edge_ids.into_iter()
+                                .map(|edge_id| {
+                                    let (source, target) = self.graph.edge_endpoints(edge_id)
+                                        .expect("edge from this graph");
+                                    CycleEdge {
+                                        dependency: self.graph[source].clone(),
+                                        dependent: self.graph[target].clone(),
+                                        kind: self.graph[edge_id].clone(),
+                                    }
+                                })
+                                .collect(),
+                        });
+                    }
+
+                    if visited.insert(target) {
+                        parents.insert(target, (node_id, edge.id()));
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
     /// Get topologically sorted order for creation (dependencies first)
+    ///
+    /// Objects are grouped into ranks by their longest dependency path. Within a
+    /// rank the order is deterministic: objects are grouped by schema, then by the
+    /// table they lock, so that e.g. a table's indexes and triggers are applied
+    /// back-to-back instead of being interleaved with unrelated objects.
     pub fn creation_order(&self) -> Result<Vec<ObjectRef>, Box<dyn std::error::Error>> {
-        if self.has_cycles() {
-            return Err("Dependency graph has cycles".into());
+        if let Some(cycle) = self.find_cycle() {
+            return Err(format!("Dependency graph has cycles: {}", cycle.path()).into());
         }
 
         let sorted_nodes = petgraph::algo::toposort(&self.graph, None)
             .map_err(|_| "Failed to perform topological sort")?;
 
-        Ok(sorted_nodes.into_iter()
-            .map(|node_id| self.graph[node_id].clone())
+        // Rank each node by the length of its longest incoming dependency path
+        let mut ranks: HashMap<NodeIndex, usize> = HashMap::new();
+        for &node_id in &sorted_nodes {
+            let rank = self.graph.neighbors_directed(node_id, Direction::Incoming)
+                .map(|dep_node| ranks.get(&dep_node).copied().unwrap_or(0) + 1)
+                .max()
+                .unwrap_or(0);
+            ranks.insert(node_id, rank);
+        }
+
+        let mut keyed: Vec<_> = sorted_nodes.into_iter()
+            .map(|node_id| {
+                let obj_ref = &self.graph[node_id];
+                let key = (
+                    ranks[&node_id],
+                    obj_ref.qualified_name.schema.clone().unwrap_or_else(|| "public".to_string()),
+                    self.lock_target(node_id),
+                    object_type_rank(&obj_ref.object_type),
+                    obj_ref.qualified_name.name.clone(),
+                );
+                (key, node_id)
+            })
+            .collect();
+        keyed.sort();
+
+        Ok(keyed.into_iter()
+            .map(|(_, node_id)| self.graph[node_id].clone())
             .collect())
     }
 
+    /// The table whose lock an object contends on: the table itself, or the
+    /// first table it directly depends on (e.g. the table an index or trigger is on)
+    fn lock_target(&self, node_id: NodeIndex) -> String {
+        let obj_ref = &self.graph[node_id];
+        if obj_ref.object_type == ObjectType::Table {
+            return obj_ref.qualified_name.name.clone();
+        }
+
+        self.graph.neighbors_directed(node_id, Direction::Incoming)
+            .map(|dep_node| &self.graph[dep_node])
+            .filter(|dep| dep.object_type == ObjectType::Table)
+            .map(|dep| dep.qualified_name.name.clone())
+            .min()
+            .unwrap_or_default()
+    }
+
     /// Get reverse topological order for deletion (dependents first)
     pub fn deletion_order(&self) -> Result<Vec<ObjectRef>, Box<dyn std::error::Error>> {
         let mut creation_order = self.creation_order()?;
@@ -266,7 +520,7 @@ impl DependencyGraph {
     }
     
     /// Get dependents of a specific object that have HARD dependencies only
-    fn hard_dependents_of(&self, object_ref: &ObjectRef) -> Vec<ObjectRef> {
+    pub fn hard_dependents_of(&self, object_ref: &ObjectRef) -> Vec<ObjectRef> {
         if let Some(&node_id) = self.node_map.get(object_ref) {
             // Get all outgoing edges and filter for Hard dependencies
             self.graph.edges_directed(node_id, Direction::Outgoing)
@@ -306,6 +560,152 @@ impl DependencyGraph {
         self.graph.edge_count()
     }
 
+    /// All edges as (dependency, dependent, kind)
+    pub fn edges(&self) -> Vec<(ObjectRef, ObjectRef, DependencyType)> {
+        self.graph.edge_references()
+            .map(|edge| (
+                self.graph[edge.source()].clone(),
+                self.graph[edge.target()].clone(),
+                edge.weight().clone(),
+            ))
+            .collect()
+    }
+
+    /// Find all objects matching a (possibly unqualified) name.
+    /// Unqualified names match objects in any schema.
+    pub fn find_by_name(&self, name: &QualifiedIdent) -> Vec<ObjectRef> {
+        self.graph.node_indices()
+            .map(|node_id| &self.graph[node_id])
+            .filter(|obj_ref| {
+                obj_ref.qualified_name.name == name.name && match &name.schema {
+                    Some(schema) => obj_ref.qualified_name.schema.as_deref().unwrap_or("public") == schema,
+                    None => true,
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Objects within `depth` hops of the given roots, following edges in both
+    /// directions (dependencies and dependents). `None` means unlimited depth.
+    pub fn neighborhood(&self, roots: &[ObjectRef], depth: Option<usize>) -> HashSet<ObjectRef> {
+        let mut visited: HashSet<NodeIndex> = HashSet::new();
+        let mut frontier: Vec<NodeIndex> = roots.iter()
+            .filter_map(|obj_ref| self.node_map.get(obj_ref).copied())
+            .collect();
+        visited.extend(frontier.iter().copied());
+
+        let mut hops = 0;
+        while !frontier.is_empty() && depth.map_or(true, |d| hops < d) {
+            let mut next = Vec::new();
+            for node_id in frontier {
+                for neighbor in self.graph.neighbors_undirected(node_id) {
+                    if visited.insert(neighbor) {
+                        next.push(neighbor);
+                    }
+                }
+            }
+            frontier = next;
+            hops += 1;
+        }
+
+        visited.into_iter()
+            .map(|node_id| self.graph[node_id].clone())
+            .collect()
+    }
+
+    /// Build a new graph containing only the objects accepted by `keep`
+    /// and the edges between them
+    pub fn subgraph<F>(&self, keep: F) -> DependencyGraph
+    where
+        F: Fn(&ObjectRef) -> bool,
+    {
+        let mut subgraph = DependencyGraph::new();
+
+        for node_id in self.graph.node_indices() {
+            let obj_ref = &self.graph[node_id];
+            if keep(obj_ref) {
+                subgraph.add_node(obj_ref.clone());
+            }
+        }
+
+        for edge in self.graph.edge_references() {
+            let source = &self.graph[edge.source()];
+            let target = &self.graph[edge.target()];
+            if subgraph.node_map.contains_key(source) && subgraph.node_map.contains_key(target) {
+                let from = subgraph.node_map[source];
+                let to = subgraph.node_map[target];
+                subgraph.graph.add_edge(from, to, edge.weight().clone());
+            }
+        }
+
+        subgraph
+    }
+
+    /// Output the dependency graph as a Mermaid flowchart (for embedding in markdown)
+    pub fn to_mermaid(&self) -> String {
+        let mut output = String::new();
+        output.push_str("graph LR\n");
+
+        for node_index in self.graph.node_indices() {
+            let obj_ref = &self.graph[node_index];
+            output.push_str(&format!(
+                "  n{}[\"{}<br/>({})\"]\n",
+                node_index.index(),
+                display_name(obj_ref).replace('"', "#quot;"),
+                format!("{:?}", obj_ref.object_type).to_lowercase()
+            ));
+        }
+
+        for edge in self.graph.edge_references() {
+            let arrow = match edge.weight() {
+                DependencyType::Hard => "-->",
+                DependencyType::Soft => "-.->",
+            };
+            output.push_str(&format!(
+                "  n{} {} n{}\n",
+                edge.source().index(),
+                arrow,
+                edge.target().index()
+            ));
+        }
+
+        output
+    }
+
+    /// Output the dependency graph as a JSON adjacency list. Each object lists
+    /// the objects it depends on, along with the kind of dependency.
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<serde_json::Value> = self.graph.node_indices()
+            .map(|node_id| {
+                let obj_ref = &self.graph[node_id];
+                let depends_on: Vec<serde_json::Value> = self.graph
+                    .edges_directed(node_id, Direction::Incoming)
+                    .map(|edge| {
+                        let dep = &self.graph[edge.source()];
+                        serde_json::json!({
+                            "type": format!("{:?}", dep.object_type).to_lowercase(),
+                            "name": display_name(dep),
+                            "kind": match edge.weight() {
+                                DependencyType::Hard => "hard",
+                                DependencyType::Soft => "soft",
+                            },
+                        })
+                    })
+                    .collect();
+
+                serde_json::json!({
+                    "type": format!("{:?}", obj_ref.object_type).to_lowercase(),
+                    "name": display_name(obj_ref),
+                    "depends_on": depends_on,
+                })
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&serde_json::json!({ "objects": nodes }))
+            .unwrap_or_else(|_| "{}".to_string())
+    }
+
     /// Output the dependency graph in Graphviz DOT format
     pub fn to_graphviz(&self) -> String {
         let mut output = String::new();
@@ -335,6 +735,16 @@ impl DependencyGraph {
                 ObjectType::CronJob => ("orange", "octagon"),
                 ObjectType::Aggregate => ("lightsteelblue", "triangle"),
                 ObjectType::Operator => ("lightsalmon", "invhouse"),
+                ObjectType::SecurityLabel => ("thistle", "note"),
+                ObjectType::Statistics => ("wheat", "parallelogram"),
+                ObjectType::Extension => ("lightgrey", "component"),
+                ObjectType::Publication => ("lightcyan", "cds"),
+                ObjectType::Collation => ("beige", "box3d"),
+                ObjectType::TextSearchConfiguration => ("honeydew", "folder"),
+                ObjectType::TextSearchDictionary => ("mintcream", "tab"),
+                ObjectType::OperatorClass => ("peachpuff", "house"),
+                ObjectType::OperatorFamily => ("papayawhip", "invtrapezium"),
+                ObjectType::Cast => ("lavenderblush", "septagon"),
             };
 
             // Create unique node ID that includes object type to avoid conflicts
@@ -390,6 +800,86 @@ impl DependencyGraph {
     }
 }
 
+/// Display name for an object: `schema.name`, or just `name` when unqualified
+/// The managed extensions an object uses functions or types of. Only the
+/// members of installed extensions are known, so a function or type nothing
+/// else provides is taken to come from the managed extensions that aren't
+/// installed yet.
+fn extensions_used<'a>(
+    obj: &SqlObject,
+    deps: &crate::sql::Dependencies,
+    objects: &'a [SqlObject],
+    builtin_catalog: &BuiltinCatalog,
+    search_path: &SearchPath,
+) -> Vec<&'a SqlObject> {
+    let extensions: Vec<&SqlObject> = objects.iter()
+        .filter(|o| o.object_type == ObjectType::Extension)
+        .collect();
+    if extensions.is_empty() || obj.object_type == ObjectType::Extension {
+        return Vec::new();
+    }
+
+    let mut used: Vec<&SqlObject> = Vec::new();
+    for dep in deps.functions.iter().chain(&deps.types) {
+        if search_path.resolve(dep, objects).is_some() {
+            continue;
+        }
+        let providers = match builtin_catalog.extension_members.get(dep) {
+            Some(name) => extensions.iter()
+                .filter(|extension| &extension.qualified_name.name == name)
+                .copied()
+                .collect::<Vec<_>>(),
+            None => extensions.iter()
+                .filter(|extension| !builtin_catalog.extensions.contains(&extension.qualified_name.name))
+                .copied()
+                .collect(),
+        };
+        for provider in providers {
+            if !used.iter().any(|extension| extension.qualified_name == provider.qualified_name) {
+                used.push(provider);
+            }
+        }
+    }
+    used
+}
+
+fn display_name(obj_ref: &ObjectRef) -> String {
+    match &obj_ref.qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, obj_ref.qualified_name.name),
+        None => obj_ref.qualified_name.name.clone(),
+    }
+}
+
+/// Tie-break order for object types that share a rank: objects that take
+/// heavier locks on a table come before the ones that hang off of it
+fn object_type_rank(object_type: &ObjectType) -> u8 {
+    match object_type {
+        ObjectType::Table => 0,
+        ObjectType::Type => 1,
+        ObjectType::Domain => 2,
+        ObjectType::Function => 3,
+        ObjectType::Procedure => 4,
+        ObjectType::Aggregate => 5,
+        ObjectType::Operator => 6,
+        ObjectType::View => 7,
+        ObjectType::MaterializedView => 8,
+        ObjectType::Index => 9,
+        ObjectType::Trigger => 10,
+        ObjectType::Comment => 11,
+        ObjectType::CronJob => 12,
+        ObjectType::SecurityLabel => 13,
+        ObjectType::Statistics => 14,
+        ObjectType::Extension => 15,
+        ObjectType::Publication => 16,
+        ObjectType::Collation => 17,
+        ObjectType::TextSearchConfiguration => 18,
+        ObjectType::TextSearchDictionary => 19,
+        ObjectType::OperatorClass => 20,
+        ObjectType::OperatorFamily => 21,
+        ObjectType::Cast => 22,
+    }
+}
+
 impl ObjectRef {
     pub fn new(object_type: ObjectType, qualified_name: QualifiedIdent) -> Self {
         Self {
@@ -446,12 +936,14 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
 
         let mut view_deps = Dependencies {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         // View depends on users table
         view_deps.relations.insert(QualifiedIdent::from_name("users".to_string()));
@@ -487,12 +979,14 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(), 
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
 
         let mut view1_deps = Dependencies {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         view1_deps.relations.insert(QualifiedIdent::from_name("users".to_string()));
 
@@ -500,6 +994,7 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         view2_deps.relations.insert(QualifiedIdent::from_name("user_stats".to_string()));
 
@@ -532,12 +1027,14 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
 
         let mut func2_deps = Dependencies {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         // func2 calls func1
         func2_deps.functions.insert(QualifiedIdent::from_name("func1".to_string()));
@@ -546,6 +1043,7 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         // view uses func1
         view_deps.functions.insert(QualifiedIdent::from_name("func1".to_string()));
@@ -589,12 +1087,14 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
 
         let mut view_deps = Dependencies {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         view_deps.relations.insert(QualifiedIdent::from_name("users".to_string()));
 
@@ -625,12 +1125,14 @@ mod tests {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
 
         let mut type_deps = Dependencies {
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         };
         // Composite type depends on materialized view's implicit row type
         type_deps.types.insert(QualifiedIdent::new(Some("core".to_string()), "seller_stats".to_string()));
@@ -679,4 +1181,252 @@ mod tests {
         let mv_pos_create = creation_order.iter().position(|obj| obj.qualified_name.name == "seller_stats").unwrap();
         assert!(mv_pos_create < type_pos_create, "Materialized view should be created before composite type");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_creation_order_groups_objects_by_table() {
+        let on_table = |table: &str| {
+            let mut deps = Dependencies::default();
+            deps.relations.insert(QualifiedIdent::from_name(table.to_string()));
+            deps
+        };
+
+        let objects = vec![
+            create_test_object(ObjectType::Index, "idx_users_email", None, on_table("users")),
+            create_test_object(ObjectType::Trigger, "trg_orders_audit", None, on_table("orders")),
+            create_test_object(ObjectType::View, "active_users", None, on_table("users")),
+            create_test_object(ObjectType::Table, "users", None, Dependencies::default()),
+            create_test_object(ObjectType::Index, "idx_orders_user", None, on_table("orders")),
+            create_test_object(ObjectType::Table, "orders", None, Dependencies::default()),
+        ];
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+        let names: Vec<String> = graph.creation_order().unwrap().into_iter()
+            .map(|obj| obj.qualified_name.name)
+            .collect();
+
+        assert_eq!(names, vec![
+            "orders", "users",
+            "idx_orders_user", "trg_orders_audit",
+            "active_users", "idx_users_email",
+        ]);
+
+        // Input order must not affect the result
+        let reversed: Vec<SqlObject> = objects.into_iter().rev().collect();
+        let graph = DependencyGraph::build_from_objects(&reversed, &builtin_catalog).unwrap();
+        let reversed_names: Vec<String> = graph.creation_order().unwrap().into_iter()
+            .map(|obj| obj.qualified_name.name)
+            .collect();
+        assert_eq!(names, reversed_names);
+    }
+
+    #[test]
+    fn test_neighborhood_and_subgraph() {
+        let depends_on = |relation: &str| {
+            let mut deps = Dependencies::default();
+            deps.relations.insert(QualifiedIdent::from_name(relation.to_string()));
+            deps
+        };
+
+        let objects = vec![
+            create_test_object(ObjectType::Table, "users", None, Dependencies::default()),
+            create_test_object(ObjectType::View, "active_users", None, depends_on("users")),
+            create_test_object(ObjectType::View, "active_user_emails", None, depends_on("active_users")),
+            create_test_object(ObjectType::Table, "products", None, Dependencies::default()),
+        ];
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+
+        let roots = graph.find_by_name(&QualifiedIdent::from_name("active_users".to_string()));
+        assert_eq!(roots.len(), 1);
+
+        let one_hop = graph.neighborhood(&roots, Some(1));
+        assert_eq!(one_hop.len(), 3);
+        assert!(!one_hop.iter().any(|obj| obj.qualified_name.name == "products"));
+
+        let unlimited = graph.neighborhood(&roots, None);
+        assert_eq!(unlimited.len(), 3);
+
+        let views_only = graph.subgraph(|obj| obj.object_type == ObjectType::View);
+        assert_eq!(views_only.node_count(), 2);
+        assert_eq!(views_only.edge_count(), 1);
+
+        let mermaid = views_only.to_mermaid();
+        assert!(mermaid.starts_with("graph LR\n"));
+        assert!(mermaid.contains("active_user_emails<br/>(view)"));
+        assert!(mermaid.contains("-->"));
+
+        let json: serde_json::Value = serde_json::from_str(&views_only.to_json()).unwrap();
+        let json_objects = json["objects"].as_array().unwrap();
+        assert_eq!(json_objects.len(), 2);
+        let emails = json_objects.iter()
+            .find(|obj| obj["name"] == "active_user_emails")
+            .unwrap();
+        assert_eq!(emails["depends_on"][0]["name"], "active_users");
+        assert_eq!(emails["depends_on"][0]["kind"], "hard");
+    }
+
+    #[test]
+    fn test_find_cycle_reports_path_and_soft_edges() {
+        let mut view_deps = Dependencies::default();
+        view_deps.functions.insert(QualifiedIdent::from_name("order_total".to_string()));
+
+        let mut total_deps = Dependencies::default();
+        total_deps.functions.insert(QualifiedIdent::from_name("order_tax".to_string()));
+
+        let mut tax_deps = Dependencies::default();
+        tax_deps.relations.insert(QualifiedIdent::from_name("order_summary".to_string()));
+
+        let objects = vec![
+            create_test_object(ObjectType::View, "order_summary", None, view_deps),
+            create_test_object(ObjectType::Function, "order_total", None, total_deps),
+            create_test_object(ObjectType::Function, "order_tax", None, tax_deps),
+            create_test_object(ObjectType::View, "unrelated", None, Dependencies::default()),
+        ];
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+
+        let cycle = graph.find_cycle().expect("cycle should be detected");
+        assert_eq!(cycle.edges.len(), 3);
+        assert!(cycle.path().contains("order_summary"));
+        assert!(cycle.path().contains("order_tax"));
+
+        // Only order_total -> order_tax is a routine calling a routine
+        let soft: Vec<_> = cycle.soft_edges();
+        assert_eq!(soft.len(), 1);
+        assert_eq!(soft[0].dependent.qualified_name.name, "order_total");
+        assert_eq!(soft[0].dependency.qualified_name.name, "order_tax");
+
+        let description = cycle.describe(&objects);
+        assert!(description.contains("Circular dependency detected"));
+        assert!(description.contains("order_total calls order_tax"));
+
+        let err = graph.creation_order().unwrap_err();
+        assert!(err.to_string().contains("order_summary"));
+    }
+
+    #[test]
+    fn test_apply_after_hints_order_objects() {
+        let in_file = |object_type, name, file: &str| {
+            let mut object = create_test_object(object_type, name, None, Dependencies::default());
+            object.source_file = Some(PathBuf::from(file));
+            object
+        };
+
+        let mut dispatcher = in_file(ObjectType::Function, "dispatch", "sql/api/dispatch.sql");
+        dispatcher.apply_after = vec![PathBuf::from("sql/handlers/handlers.sql")];
+        let objects = vec![
+            dispatcher,
+            in_file(ObjectType::Function, "handle_create", "./sql/handlers/handlers.sql"),
+            in_file(ObjectType::Function, "handle_delete", "./sql/handlers/handlers.sql"),
+        ];
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+
+        let order: Vec<String> = graph.creation_order().unwrap().into_iter()
+            .map(|obj_ref| obj_ref.qualified_name.name)
+            .collect();
+        assert_eq!(order.last().map(String::as_str), Some("dispatch"));
+        assert!(graph.edges().iter().all(|(_, _, kind)| *kind == DependencyType::Soft));
+
+        // A hint pointing back the other way closes a cycle
+        let mut objects = objects;
+        objects[1].apply_after = vec![PathBuf::from("sql/api/dispatch.sql")];
+        let err = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap_err();
+        assert!(err.to_string().contains("creates a dependency cycle"), "{}", err);
+    }
+
+    #[test]
+    fn test_depends_on_directives_order_objects() {
+        let (directives, _) = crate::sql::parse_object_directives(
+            "-- pgmg:depends-on api.totals, api.refresh()\nCREATE FUNCTION api.report() RETURNS void"
+        );
+        let report = create_test_object(ObjectType::Function, "report", Some("api"), directives.dependencies());
+        let objects = vec![
+            report,
+            create_test_object(ObjectType::Function, "refresh", Some("api"), Dependencies::default()),
+            create_test_object(ObjectType::View, "totals", Some("api"), Dependencies::default()),
+        ];
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+
+        let order: Vec<String> = graph.creation_order().unwrap().into_iter()
+            .map(|obj_ref| obj_ref.qualified_name.name)
+            .collect();
+        assert_eq!(order.last().map(String::as_str), Some("report"));
+
+        // Like a parsed call, a hinted function is a soft dependency of a function
+        let report = ObjectRef::from(&objects[0]);
+        let refresh = ObjectRef::from(&objects[1]);
+        assert!(graph.edges().iter().any(|(from, to, kind)| from == &refresh && to == &report && *kind == DependencyType::Soft));
+    }
+
+    #[test]
+    fn test_objects_using_extensions_are_created_after_them() {
+        let uses = |name: &str, function: &str| {
+            let mut dependencies = Dependencies::default();
+            dependencies.functions.insert(QualifiedIdent::from_name(function.to_string()));
+            create_test_object(ObjectType::View, name, Some("api"), dependencies)
+        };
+        let objects = vec![
+            uses("fuzzy_search", "similarity"),
+            uses("new_ids", "uuid_generate_v4"),
+            create_test_object(ObjectType::Extension, "pg_trgm", None, Dependencies::default()),
+            create_test_object(ObjectType::Extension, "uuid-ossp", None, Dependencies::default()),
+        ];
+
+        // pg_trgm is installed, so its functions are known
+        let mut builtin_catalog = BuiltinCatalog::new();
+        builtin_catalog.extensions.insert("pg_trgm".to_string());
+        builtin_catalog.extension_members.insert(QualifiedIdent::from_name("similarity".to_string()), "pg_trgm".to_string());
+
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+        let edges: HashSet<(String, String)> = graph.edges().into_iter()
+            .map(|(from, to, kind)| {
+                assert_eq!(kind, DependencyType::Soft);
+                (from.qualified_name.name, to.qualified_name.name)
+            })
+            .collect();
+
+        assert_eq!(edges, HashSet::from([
+            ("pg_trgm".to_string(), "fuzzy_search".to_string()),
+            // Not installed yet, so it may provide what nothing else does
+            ("uuid-ossp".to_string(), "new_ids".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn test_references_resolve_through_search_path() {
+        let selects_from = |name: &str, relation: QualifiedIdent| {
+            let mut dependencies = Dependencies::default();
+            dependencies.relations.insert(relation);
+            create_test_object(ObjectType::View, name, Some("reports"), dependencies)
+        };
+        let objects = vec![
+            create_test_object(ObjectType::Table, "users", Some("api"), Dependencies::default()),
+            create_test_object(ObjectType::Table, "users", None, Dependencies::default()),
+            selects_from("active_users", QualifiedIdent::from_name("users".to_string())),
+            selects_from("all_users", QualifiedIdent::new(Some("public".to_string()), "users".to_string())),
+        ];
+        let builtin_catalog = BuiltinCatalog::new();
+        let dependency_of = |graph: &DependencyGraph, view: &str| -> Vec<Option<String>> {
+            graph.dependencies_of(&ObjectRef::from(objects.iter().find(|obj| obj.qualified_name.name == view).unwrap()))
+                .into_iter()
+                .map(|obj_ref| obj_ref.qualified_name.schema)
+                .collect()
+        };
+
+        let graph = DependencyGraph::build_from_objects(&objects, &builtin_catalog).unwrap();
+        assert_eq!(dependency_of(&graph, "active_users"), vec![None]);
+        assert_eq!(dependency_of(&graph, "all_users"), vec![None]);
+
+        let search_path = SearchPath::new(vec!["api".to_string(), "public".to_string()]);
+        let graph = DependencyGraph::build_with_search_path(&objects, &builtin_catalog, &search_path).unwrap();
+        assert_eq!(dependency_of(&graph, "active_users"), vec![Some("api".to_string())]);
+        assert_eq!(dependency_of(&graph, "all_users"), vec![None]);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod graph;
+pub mod search_path;
 
-pub use graph::{DependencyGraph, ObjectRef, DependencyType};
\ No newline at end of file
+pub use graph::{DependencyGraph, ObjectRef, DependencyType, DependencyCycle, CycleEdge};
+pub use search_path::SearchPath;
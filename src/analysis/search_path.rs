@@ -0,0 +1,189 @@
+//! Resolving the names objects reference to the managed objects they mean,
+//! the way PostgreSQL's search_path does, so `users` and `api.users` can be
+//! the same table.
+
+use crate::sql::{QualifiedIdent, SqlObject};
+use tracing::warn;
+
+/// Schemas unqualified references are looked up in, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchPath {
+    schemas: Vec<String>,
+}
+
+impl Default for SearchPath {
+    /// Just public, as in a database with the default search_path and no
+    /// schema named after the user
+    fn default() -> Self {
+        Self { schemas: vec!["public".to_string()] }
+    }
+}
+
+impl SearchPath {
+    /// The default path if `schemas` is empty
+    pub fn new(schemas: Vec<String>) -> Self {
+        if schemas.is_empty() {
+            Self::default()
+        } else {
+            Self { schemas }
+        }
+    }
+
+    pub fn schemas(&self) -> &[String] {
+        &self.schemas
+    }
+
+    /// The object among `candidates` that `reference` means. A qualified
+    /// reference matches the object in that schema, with unqualified objects
+    /// taken to be in public. An unqualified one matches the object in the
+    /// first schema on the path that has one.
+    ///
+    /// Unqualified references to objects in no schema on the path, e.g. from
+    /// a function with its own `SET search_path`, match the object of that
+    /// name if there's only one. With several, the one in the first schema
+    /// by name is used and a warning logged.
+    pub fn resolve<'a>(
+        &self,
+        reference: &QualifiedIdent,
+        candidates: impl IntoIterator<Item = &'a SqlObject>,
+    ) -> Option<&'a SqlObject> {
+        self.resolve_by(reference, candidates, |obj| &obj.qualified_name)
+    }
+
+    /// The `schema.name` a name given on the command line or stored as a
+    /// dependency means, among the names of the objects pgmg knows. A name
+    /// nothing is known by is taken to be in the first schema on the path.
+    /// Names of objects outside schemas, like casts and security labels,
+    /// are kept as they are.
+    pub fn qualify<'a>(&self, name: &str, known: impl IntoIterator<Item = &'a QualifiedIdent>) -> String {
+        if is_schemaless(name) {
+            return name.to_string();
+        }
+        let reference = QualifiedIdent::from_qualified_name(name);
+        if reference.schema.is_some() {
+            return object_key(&reference);
+        }
+        match self.resolve_by(&reference, known, |name| name) {
+            Some(found) => object_key(found),
+            None => format!("{}.{}", self.schemas[0], reference.name),
+        }
+    }
+
+    fn resolve_by<'a, T>(
+        &self,
+        reference: &QualifiedIdent,
+        candidates: impl IntoIterator<Item = &'a T>,
+        name_of: impl Fn(&T) -> &QualifiedIdent,
+    ) -> Option<&'a T> {
+        let named: Vec<&T> = candidates.into_iter()
+            .filter(|obj| name_of(obj).name == reference.name)
+            .collect();
+        let in_schema = |schema: &str| named.iter().copied().find(|obj| schema_of(name_of(obj)) == schema);
+
+        if let Some(schema) = &reference.schema {
+            return in_schema(schema);
+        }
+        if let Some(obj) = self.schemas.iter().find_map(|schema| in_schema(schema)) {
+            return Some(obj);
+        }
+
+        let mut schemas: Vec<&str> = named.iter().map(|obj| schema_of(name_of(obj))).collect();
+        schemas.sort();
+        schemas.dedup();
+        let first = *schemas.first()?;
+        if schemas.len() > 1 {
+            warn!(
+                "`{}` is in schemas {}, none of them on the search path ({}); taking it to mean {}.{}",
+                reference.name,
+                schemas.join(", "),
+                self.schemas.join(", "),
+                first,
+                reference.name
+            );
+        }
+        in_schema(first)
+    }
+}
+
+fn schema_of(name: &QualifiedIdent) -> &str {
+    name.schema.as_deref().unwrap_or("public")
+}
+
+/// An object's name as `schema.name`, with objects defined without a schema
+/// in public. Casts and security labels, which aren't in a schema, keep their
+/// name.
+pub fn object_key(name: &QualifiedIdent) -> String {
+    if name.schema.is_none() && is_schemaless(&name.name) {
+        return name.name.clone();
+    }
+    format!("{}.{}", schema_of(name), name.name)
+}
+
+/// Whether the name is a cast's `(source AS target)` or a security label's
+/// `provider:object`
+fn is_schemaless(name: &str) -> bool {
+    name.starts_with('(') || name.contains(':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::ObjectType;
+
+    fn object(schema: Option<&str>, name: &str) -> SqlObject {
+        SqlObject::new(
+            ObjectType::Table,
+            QualifiedIdent::new(schema.map(str::to_string), name.to_string()),
+            format!("CREATE TABLE {}{} ()", schema.map(|schema| format!("{}.", schema)).unwrap_or_default(), name),
+            Default::default(),
+            None,
+        )
+    }
+
+    fn resolved(path: &SearchPath, reference: QualifiedIdent, objects: &[SqlObject]) -> Option<String> {
+        path.resolve(&reference, objects).map(|obj| format!("{}.{}", schema_of(&obj.qualified_name), obj.qualified_name.name))
+    }
+
+    #[test]
+    fn test_resolve_follows_search_path() {
+        let objects = [object(Some("api"), "users"), object(None, "users"), object(Some("internal"), "orders")];
+        let users = QualifiedIdent::from_name("users".to_string());
+
+        assert_eq!(resolved(&SearchPath::default(), users.clone(), &objects).as_deref(), Some("public.users"));
+        let api_first = SearchPath::new(vec!["api".to_string(), "public".to_string()]);
+        assert_eq!(resolved(&api_first, users.clone(), &objects).as_deref(), Some("api.users"));
+
+        // Qualified references and unqualified objects meet in public
+        let public_users = QualifiedIdent::new(Some("public".to_string()), "users".to_string());
+        assert_eq!(resolved(&api_first, public_users, &objects).as_deref(), Some("public.users"));
+        assert_eq!(resolved(&api_first, QualifiedIdent::new(Some("other".to_string()), "users".to_string()), &objects), None);
+
+        // Off the path, but there's only one
+        assert_eq!(resolved(&api_first, QualifiedIdent::from_name("orders".to_string()), &objects).as_deref(), Some("internal.orders"));
+    }
+
+    #[test]
+    fn test_qualify_names() {
+        let known = [QualifiedIdent::new(Some("api".to_string()), "users".to_string()), QualifiedIdent::from_name("orders".to_string())];
+        let api_first = SearchPath::new(vec!["api".to_string(), "public".to_string()]);
+
+        assert_eq!(api_first.qualify("users", &known), "api.users");
+        assert_eq!(api_first.qualify("orders", &known), "public.orders");
+        assert_eq!(api_first.qualify("public.users", &known), "public.users");
+        // Nothing by that name yet
+        assert_eq!(api_first.qualify("invoices", &known), "api.invoices");
+        assert_eq!(SearchPath::default().qualify("invoices", &known), "public.invoices");
+        assert_eq!(api_first.qualify("selinux:users", &known), "selinux:users");
+        assert_eq!(api_first.qualify("(text AS int4)", &known), "(text AS int4)");
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_reference_deterministically() {
+        let objects = [object(Some("sales"), "orders"), object(Some("billing"), "orders")];
+        let orders = QualifiedIdent::from_name("orders".to_string());
+
+        assert_eq!(resolved(&SearchPath::default(), orders.clone(), &objects).as_deref(), Some("billing.orders"));
+        let sales = SearchPath::new(vec!["sales".to_string()]);
+        assert_eq!(resolved(&sales, orders, &objects).as_deref(), Some("sales.orders"));
+    }
+}
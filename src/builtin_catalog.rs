@@ -1,13 +1,36 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tokio_postgres::Client;
 use crate::QualifiedIdent;
 
+/// The PostgreSQL major version whose catalog is used when there's no
+/// database to ask
+pub const DEFAULT_POSTGRES_VERSION: u32 = 15;
+
+/// Catalogs of a freshly created database of each supported major version,
+/// as written by `regenerate_embedded_catalog` below
+const EMBEDDED_CATALOGS: &[(u32, &str)] = &[
+    (15, include_str!("../catalogs/postgres-15.json")),
+];
+
 /// Catalog of built-in PostgreSQL objects that should be excluded from dependency analysis
-#[derive(Debug, Clone)]
+///
+/// Serializes in sorted order, so the same catalog always serializes the same.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuiltinCatalog {
+    #[serde(serialize_with = "sorted")]
     pub functions: HashSet<QualifiedIdent>,
+    #[serde(serialize_with = "sorted")]
     pub types: HashSet<QualifiedIdent>,
+    #[serde(serialize_with = "sorted")]
     pub relations: HashSet<QualifiedIdent>,
+    /// Names of the extensions installed in the database
+    #[serde(serialize_with = "sorted")]
+    pub extensions: HashSet<String>,
+    /// Functions and types installed by extensions, with the extension's name
+    #[serde(serialize_with = "sorted_pairs", deserialize_with = "pairs")]
+    pub extension_members: HashMap<QualifiedIdent, String>,
 }
 
 impl BuiltinCatalog {
@@ -17,9 +40,57 @@ impl BuiltinCatalog {
             functions: HashSet::new(),
             types: HashSet::new(),
             relations: HashSet::new(),
+            extensions: HashSet::new(),
+            extension_members: HashMap::new(),
         }
     }
 
+    /// The built-in objects of a fresh database of PostgreSQL `major_version`,
+    /// without connecting to one. Versions without a catalog of their own get
+    /// the one for the nearest older version, or the oldest there is.
+    pub fn embedded(major_version: u32) -> Self {
+        let (_, json) = EMBEDDED_CATALOGS.iter()
+            .rev()
+            .find(|(version, _)| *version <= major_version)
+            .unwrap_or(&EMBEDDED_CATALOGS[0]);
+        serde_json::from_str(json).expect("embedded builtin catalog is valid")
+    }
+
+    /// The embedded catalog of exactly PostgreSQL `major_version`, if there
+    /// is one
+    fn embedded_exact(major_version: u32) -> Option<Self> {
+        EMBEDDED_CATALOGS.iter()
+            .find(|(version, _)| *version == major_version)
+            .map(|(_, json)| serde_json::from_str(json).expect("embedded builtin catalog is valid"))
+    }
+
+    /// The built-in objects of a live database. The embedded catalog of the
+    /// server's version is refined with what the database itself has, e.g.
+    /// its extensions. Other versions only use the database's own, as another
+    /// version's catalog would have built-ins this server doesn't.
+    pub async fn for_database(client: &Client) -> Result<Self, Box<dyn std::error::Error>> {
+        let version_num: i32 = client.query_one("SELECT current_setting('server_version_num')::int", &[]).await?.get(0);
+        match Self::embedded_exact((version_num / 10000) as u32) {
+            Some(mut catalog) => {
+                catalog.refine(client).await?;
+                Ok(catalog)
+            }
+            None => Self::from_database(client).await,
+        }
+    }
+
+    /// Add the built-in objects and extensions of a live database to the
+    /// catalog
+    pub async fn refine(&mut self, client: &Client) -> Result<(), Box<dyn std::error::Error>> {
+        let database = Self::from_database(client).await?;
+        self.functions.extend(database.functions);
+        self.types.extend(database.types);
+        self.relations.extend(database.relations);
+        self.extensions.extend(database.extensions);
+        self.extension_members.extend(database.extension_members);
+        Ok(())
+    }
+
     /// Load built-in objects from a PostgreSQL database
     pub async fn from_database(client: &Client) -> Result<Self, Box<dyn std::error::Error>> {
         let mut catalog = Self::new();
@@ -32,6 +103,9 @@ impl BuiltinCatalog {
         
         // Query built-in relations (tables and views)
         catalog.load_builtin_relations(client).await?;
+
+        // Query installed extensions and what they provide
+        catalog.load_extensions(client).await?;
         
         Ok(catalog)
     }
@@ -186,10 +260,143 @@ impl BuiltinCatalog {
         
         Ok(())
     }
+
+    async fn load_extensions(&mut self, client: &Client) -> Result<(), Box<dyn std::error::Error>> {
+        let rows = client.query("SELECT extname::text FROM pg_extension", &[]).await?;
+        self.extensions.extend(rows.iter().map(|row| row.get::<_, String>(0)));
+
+        // Functions and types recorded as members of an extension
+        let query = r#"
+            SELECT e.extname::text, n.nspname::text, p.proname::text
+            FROM pg_depend d
+            JOIN pg_extension e ON e.oid = d.refobjid
+            JOIN pg_proc p ON p.oid = d.objid
+            JOIN pg_namespace n ON n.oid = p.pronamespace
+            WHERE d.classid = 'pg_proc'::regclass
+              AND d.refclassid = 'pg_extension'::regclass
+              AND d.deptype = 'e'
+            UNION
+            SELECT e.extname::text, n.nspname::text, t.typname::text
+            FROM pg_depend d
+            JOIN pg_extension e ON e.oid = d.refobjid
+            JOIN pg_type t ON t.oid = d.objid
+            JOIN pg_namespace n ON n.oid = t.typnamespace
+            WHERE d.classid = 'pg_type'::regclass
+              AND d.refclassid = 'pg_extension'::regclass
+              AND d.deptype = 'e'
+        "#;
+
+        let rows = client.query(query, &[]).await?;
+
+        for row in rows {
+            let extension: String = row.get(0);
+            let schema: String = row.get(1);
+            let name: String = row.get(2);
+
+            // Extension objects are usually referenced without their schema
+            self.extension_members.insert(QualifiedIdent::from_name(name.clone()), extension.clone());
+            self.extension_members.insert(QualifiedIdent::new(Some(schema), name), extension);
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for BuiltinCatalog {
     fn default() -> Self {
         Self::new()
     }
+}
+
+fn sorted<T: Ord + Serialize, S: Serializer>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut items: Vec<&T> = set.iter().collect();
+    items.sort();
+    items.serialize(serializer)
+}
+
+/// Maps are written as a list of pairs, as JSON keys can only be strings
+fn sorted_pairs<K: Ord + Serialize, V: Ord + Serialize, S: Serializer>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut pairs: Vec<(&K, &V)> = map.iter().collect();
+    pairs.sort();
+    pairs.serialize(serializer)
+}
+
+fn pairs<'de, K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<K, V>, D::Error> {
+    Ok(Vec::<(K, V)>::deserialize(deserializer)?.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_round_trips_through_json() {
+        let mut catalog = BuiltinCatalog::new();
+        catalog.functions.insert(QualifiedIdent::from_name("now".to_string()));
+        catalog.functions.insert(QualifiedIdent::new(Some("pg_catalog".to_string()), "now".to_string()));
+        catalog.extensions.insert("citext".to_string());
+        catalog.extension_members.insert(QualifiedIdent::from_name("citext".to_string()), "citext".to_string());
+
+        let json = serde_json::to_string(&catalog).unwrap();
+        assert!(json.contains(r#""functions":[{"schema":null,"name":"now"},{"schema":"pg_catalog","name":"now"}]"#));
+        assert!(json.contains(r#""extension_members":[[{"schema":null,"name":"citext"},"citext"]]"#));
+
+        let loaded: BuiltinCatalog = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.functions, catalog.functions);
+        assert_eq!(loaded.extensions, catalog.extensions);
+        assert_eq!(loaded.extension_members, catalog.extension_members);
+    }
+
+    #[test]
+    fn test_embedded_catalog() {
+        let catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+        assert!(catalog.functions.contains(&QualifiedIdent::from_name("now".to_string())));
+        assert!(catalog.functions.contains(&QualifiedIdent::new(Some("pg_catalog".to_string()), "jsonb_build_object".to_string())));
+        assert!(catalog.types.contains(&QualifiedIdent::from_name("int4".to_string())));
+        assert!(catalog.relations.contains(&QualifiedIdent::new(Some("information_schema".to_string()), "columns".to_string())));
+        assert_eq!(catalog.extensions, HashSet::from(["plpgsql".to_string()]));
+
+        // Versions without their own catalog fall back to the nearest one
+        assert_eq!(BuiltinCatalog::embedded(17).functions, catalog.functions);
+        assert_eq!(BuiltinCatalog::embedded(12).functions, catalog.functions);
+
+        // But a live database of such a version isn't given them
+        assert!(BuiltinCatalog::embedded_exact(DEFAULT_POSTGRES_VERSION).is_some());
+        assert!(BuiltinCatalog::embedded_exact(12).is_none());
+    }
+
+    #[test]
+    fn test_embedded_catalogs_are_sorted() {
+        // So regenerating one only shows what changed between versions
+        for (version, json) in EMBEDDED_CATALOGS {
+            let catalog: BuiltinCatalog = serde_json::from_str(json).unwrap();
+            let written = serde_json::to_string_pretty(&catalog).unwrap() + "\n";
+            assert!(written == *json, "catalogs/postgres-{}.json isn't as serialized", version);
+        }
+    }
+
+    /// Write catalogs/postgres-<major>.json from a freshly created database of
+    /// that version:
+    ///
+    /// PGMG_CATALOG_DATABASE_URL=postgres://... cargo test --lib regenerate_embedded_catalog -- --ignored
+    ///
+    /// then add it to `EMBEDDED_CATALOGS`.
+    #[tokio::test]
+    #[ignore]
+    async fn regenerate_embedded_catalog() {
+        let url = std::env::var("PGMG_CATALOG_DATABASE_URL").expect("PGMG_CATALOG_DATABASE_URL is set");
+        let (client, connection) = crate::db::connect_with_url(&url).await.unwrap();
+        connection.spawn();
+
+        let version_num: i32 = client.query_one("SELECT current_setting('server_version_num')::int", &[]).await.unwrap().get(0);
+        let catalog = BuiltinCatalog::from_database(&client).await.unwrap();
+
+        let path = format!("{}/catalogs/postgres-{}.json", env!("CARGO_MANIFEST_DIR"), version_num / 10000);
+        std::fs::write(&path, serde_json::to_string_pretty(&catalog).unwrap() + "\n").unwrap();
+    }
 }
\ No newline at end of file
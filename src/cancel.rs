@@ -0,0 +1,85 @@
+//! Cancelling a running apply, test run or watch from another task, e.g. on
+//! Ctrl-C or when the host application shuts down.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Asks a running command to stop. Clones share the same state, so a clone
+/// kept by the caller cancels the command it was passed to.
+///
+/// A cancelled apply rolls back its open transaction, releases the apply
+/// lock and fails with [`PgmgError::Cancelled`](crate::PgmgError::Cancelled).
+///
+/// # Example
+/// ```no_run
+/// use pgmg::{CancellationToken, Pgmg};
+///
+/// async fn apply_until_ctrl_c(pgmg: Pgmg, cancel: CancellationToken) -> pgmg::Result<()> {
+///     let on_ctrl_c = cancel.clone();
+///     tokio::spawn(async move {
+///         let _ = tokio::signal::ctrl_c().await;
+///         on_ctrl_c.cancel();
+///     });
+///     pgmg.apply().await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    /// Ask whatever holds this token, or a clone of it, to stop. Cancelling
+    /// more than once has no further effect.
+    pub fn cancel(&self) {
+        self.cancelled.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Wait until the token is cancelled, returning at once if it already is
+    pub async fn cancelled(&self) {
+        let mut receiver = self.cancelled.subscribe();
+        // The sender lives as long as `self`, so this can't fail
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_wakes_clones() {
+        let token = CancellationToken::new();
+        let waiting = {
+            let token = token.clone();
+            tokio::spawn(async move { token.cancelled().await })
+        };
+        assert!(!token.is_cancelled());
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        token.clone().cancel();
+
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert!(token.is_cancelled());
+        // Already cancelled, so there's nothing to wait for
+        tokio::time::timeout(Duration::from_millis(10), token.cancelled()).await.unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+use crate::cancel::CancellationToken;
+use crate::commands::apply::{apply_migrations_until_cancelled, ApplyResult};
+use crate::commands::plan::{execute_plan_with_options, PlanOptions, PlanResult};
+use crate::commands::seed::{execute_seed, SeedOptions, SeedResult};
+use crate::commands::test::{execute_test_with_options, TestDatabaseMode, TestResult};
+use crate::config::PgmgConfig;
+use crate::error::{PgmgError, Result};
+
+/// A database and the files to manage it with, for applications that run
+/// pgmg themselves, e.g. applying migrations at startup.
+///
+/// # Example
+/// ```no_run
+/// use pgmg::Pgmg;
+///
+/// async fn on_startup(database_url: &str) -> pgmg::Result<()> {
+///     let pgmg = Pgmg::builder()
+///         .connection(database_url)
+///         .migrations_dir("migrations")
+///         .code_dir("sql")
+///         .build()?;
+///
+///     if pgmg.plan().await?.has_changes() {
+///         pgmg.apply().await?;
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pgmg {
+    connection_string: String,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    seed_dir: Option<PathBuf>,
+    config: PgmgConfig,
+    cancellation: CancellationToken,
+}
+
+/// Builds a [`Pgmg`]. Anything not set is taken from the [`PgmgConfig`]
+/// given to [`config`](PgmgBuilder::config), if any.
+#[derive(Debug, Clone, Default)]
+pub struct PgmgBuilder {
+    connection_string: Option<String>,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    seed_dir: Option<PathBuf>,
+    config: PgmgConfig,
+    cancellation: CancellationToken,
+}
+
+impl PgmgBuilder {
+    /// The `postgres://` or `postgresql://` URL of the database
+    pub fn connection(mut self, connection_string: impl Into<String>) -> Self {
+        self.connection_string = Some(connection_string.into());
+        self
+    }
+
+    pub fn migrations_dir(mut self, migrations_dir: impl Into<PathBuf>) -> Self {
+        self.migrations_dir = Some(migrations_dir.into());
+        self
+    }
+
+    pub fn code_dir(mut self, code_dir: impl Into<PathBuf>) -> Self {
+        self.code_dir = Some(code_dir.into());
+        self
+    }
+
+    pub fn seed_dir(mut self, seed_dir: impl Into<PathBuf>) -> Self {
+        self.seed_dir = Some(seed_dir.into());
+        self
+    }
+
+    /// Settings as they would be read from pgmg.toml, e.g. from
+    /// [`PgmgConfig::load_from_file`]
+    pub fn config(mut self, config: PgmgConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Lets [`apply`](Pgmg::apply) and [`test`](Pgmg::test) be cancelled from
+    /// elsewhere, e.g. on shutdown, by cancelling a clone of `cancellation`
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Check the connection string and that the directories exist. Nothing
+    /// connects to the database until a command is run.
+    pub fn build(self) -> Result<Pgmg> {
+        let mut config = self.config;
+
+        let connection_string = self.connection_string
+            .or_else(|| config.connection_string.clone())
+            .ok_or_else(|| PgmgError::Configuration("No connection string provided".to_string()))?;
+        if !connection_string.starts_with("postgres://") && !connection_string.starts_with("postgresql://") {
+            return Err(PgmgError::InvalidConnectionString(connection_string));
+        }
+        config.connection_string = Some(connection_string.clone());
+
+        let migrations_dir = self.migrations_dir.or_else(|| config.migrations_dir.clone());
+        let code_dir = self.code_dir.or_else(|| config.code_dir.clone());
+        let seed_dir = self.seed_dir.or_else(|| config.seed_dir.clone());
+        for dir in [&migrations_dir, &code_dir, &seed_dir].into_iter().flatten() {
+            if !dir.is_dir() {
+                return Err(PgmgError::DirectoryNotFound(dir.clone()));
+            }
+        }
+
+        Ok(Pgmg { connection_string, migrations_dir, code_dir, seed_dir, config, cancellation: self.cancellation })
+    }
+}
+
+impl Pgmg {
+    pub fn builder() -> PgmgBuilder {
+        PgmgBuilder::default()
+    }
+
+    /// The changes [`apply`](Pgmg::apply) would make. Read-only, apart from
+    /// creating pgmg's state tables if they don't exist.
+    pub async fn plan(&self) -> Result<PlanResult> {
+        let options = PlanOptions::from_config(&self.config);
+        execute_plan_with_options(
+            self.migrations_dir.clone(),
+            self.code_dir.clone(),
+            self.connection_string.clone(),
+            None,
+            &options,
+        ).await
+    }
+
+    /// Apply pending migrations and code changes, holding the apply lock so
+    /// only one of several instances starting at once does. Problems that
+    /// don't stop the apply, such as PL/pgSQL check errors, are reported in
+    /// the result rather than failing it.
+    pub async fn apply(&self) -> Result<ApplyResult> {
+        apply_migrations_until_cancelled(&self.config, self.migrations_dir.clone(), self.code_dir.clone(), &self.cancellation).await
+    }
+
+    /// Run the `_test.sql` files in the code directory, or under the current
+    /// directory without one, against a test database cloned from a
+    /// template built from the migrations and code
+    pub async fn test(&self) -> Result<TestResult> {
+        execute_test_with_options(
+            self.code_dir.clone(),
+            self.connection_string.clone(),
+            false,
+            false,
+            true,
+            1,
+            &TestDatabaseMode::Template,
+            &self.config,
+            &self.cancellation,
+        ).await
+    }
+
+    /// Run the files in the seed directory, all in one transaction
+    pub async fn seed(&self) -> Result<SeedResult> {
+        let seed_dir = self.seed_dir.clone()
+            .ok_or_else(|| PgmgError::Configuration("No seed directory provided".to_string()))?;
+
+        execute_seed(seed_dir, self.connection_string.clone(), &SeedOptions::default()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_checks_connection_and_directories() {
+        assert!(matches!(Pgmg::builder().build(), Err(PgmgError::Configuration(_))));
+        assert!(matches!(
+            Pgmg::builder().connection("mysql://localhost/app").build(),
+            Err(PgmgError::InvalidConnectionString(_))
+        ));
+        assert!(matches!(
+            Pgmg::builder().connection("postgres://localhost/app").migrations_dir("/nonexistent/migrations").build(),
+            Err(PgmgError::DirectoryNotFound(dir)) if dir == PathBuf::from("/nonexistent/migrations")
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = PgmgConfig {
+            connection_string: Some("postgres://localhost/app".to_string()),
+            code_dir: Some(dir.path().to_path_buf()),
+            ..PgmgConfig::default()
+        };
+        let pgmg = Pgmg::builder().config(config).build().unwrap();
+        assert_eq!(pgmg.connection_string, "postgres://localhost/app");
+        assert_eq!(pgmg.code_dir.as_deref(), Some(dir.path()));
+        assert_eq!(pgmg.migrations_dir, None);
+
+        // What the builder sets wins over the config
+        let pgmg = Pgmg::builder()
+            .config(PgmgConfig { connection_string: Some("postgres://localhost/app".to_string()), ..PgmgConfig::default() })
+            .connection("postgresql://localhost/other")
+            .build()
+            .unwrap();
+        assert_eq!(pgmg.connection_string, "postgresql://localhost/other");
+        assert_eq!(pgmg.config.connection_string.as_deref(), Some("postgresql://localhost/other"));
+    }
+}
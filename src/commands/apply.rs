@@ -1,21 +1,26 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use std::collections::HashSet;
-use crate::db::{StateManager, connect_to_database, DatabaseConfig, AdvisoryLockManager, AdvisoryLockError};
-use crate::sql::{SqlObject, ObjectType, objects::{calculate_ddl_hash, extract_trigger_table}, splitter::split_sql_file, migration_analyzer::extract_enum_add_value_statements};
-use crate::commands::plan::{execute_plan, ChangeOperation, PlanResult};
+use std::collections::{HashMap, HashSet};
+use crate::db::{StateManager, NewRun, RunChanges, migration_content_hash, operator_signature, connect_to_database, DatabaseConfig, AdvisoryLockManager, AdvisoryLockError, get_replicated_tables, find_replication_conflicts, connect_with_url, collect_replica_expectation, wait_for_replica, ReplicaVerification, LockRetry};
+use crate::sql::{SqlObject, ObjectType, RefreshStrategy, SchemaFilter, SkipRules, SqlVars, substitute_vars, quote::{quote_dotted, quote_ident, quote_literal, quote_qualified_ident, quote_signature}, objects::{extract_extension_version, extract_trigger_table, split_security_label_name, is_create_or_replace, runs_outside_transaction}, splitter::split_sql_file, migration_analyzer::{check_migration_syntax, extract_enum_add_value_statements, extract_replication_hazards, is_no_transaction_migration}};
+use crate::commands::plan::{execute_plan_with_client, ChangeOperation, PlanOptions, PlanResult};
 use crate::config::PgmgConfig;
 use crate::analysis::ObjectRef;
-use crate::notify::{ObjectLoadedNotification, emit_object_loaded_notification};
-use crate::plpgsql_check::{check_modified_functions, check_soft_dependent_functions, display_check_errors};
-use crate::error::format_postgres_error_with_details;
+use crate::cancel::CancellationToken;
+use crate::notify::{ObjectLoadedNotification, emit_object_loaded_notification, record_apply_completed};
+use crate::integrations::IntegrationAction;
+use crate::hooks::{HookContext, HookEvent, Hooks};
+use crate::git::{git_head, GitHead};
+use crate::notify::webhook::{send_apply_summary, Webhook};
+use crate::slack::ApplySummaryContext;
+use crate::plpgsql_check::{check_modified_functions, check_soft_dependent_functions, display_check_errors, find_source_object};
+use crate::error::{format_postgres_error_with_details, PgmgError};
+use crate::logging::output::{self, Change};
 use tracing::{info, warn, debug, error};
-use tokio_postgres::GenericClient;
+use tokio_postgres::{GenericClient, NoTls};
+use tokio::sync::{mpsc, oneshot};
 
-#[cfg(feature = "cli")]
-use owo_colors::OwoColorize;
-
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ApplyResult {
     pub migrations_applied: Vec<String>,
     pub objects_created: Vec<String>,
@@ -24,6 +29,117 @@ pub struct ApplyResult {
     pub errors: Vec<String>,
     pub plpgsql_errors_found: usize,
     pub plpgsql_warnings_found: usize,
+    /// Statements that were deliberately not run
+    pub skipped: Vec<SkippedStatement>,
+    /// What happened to the data of each recreated materialized view
+    pub matview_refreshes: Vec<MatviewRefresh>,
+    /// The dump of the tables and materialized views the plan dropped, with
+    /// `backup_before_destructive`
+    pub backup_path: Option<PathBuf>,
+}
+
+/// A statement apply left out on purpose, e.g. pg_cron calls in test mode
+#[derive(Debug, Clone)]
+pub struct SkippedStatement {
+    pub reason: String,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    /// First line of the statement
+    pub statement: String,
+}
+
+/// The data of a materialized view apply dropped and recreated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatviewRefresh {
+    pub name: String,
+    pub status: MatviewRefreshStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatviewRefreshStatus {
+    /// Created WITH DATA, so it was filled when it was created
+    Populated,
+    /// Created WITH NO DATA and refreshed before the apply committed
+    Refreshed,
+    /// Refreshed again with REFRESH ... CONCURRENTLY after the apply committed
+    RefreshedConcurrently,
+    /// Created WITH NO DATA and left empty, as no refresh was asked for
+    LeftEmpty,
+    /// The concurrent refresh after the commit failed; the view keeps the
+    /// data it was created with
+    Failed(String),
+}
+
+impl SkippedStatement {
+    fn new(reason: &str, file: Option<&Path>, line: Option<usize>, sql: &str) -> Self {
+        Self {
+            reason: reason.to_string(),
+            file: file.map(Path::to_path_buf),
+            line,
+            statement: sql.lines().next().unwrap_or("").trim().to_string(),
+        }
+    }
+}
+
+/// Points in an apply run where [`ApplyHooks`] can pause it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApplyPhase {
+    /// About to wait for the advisory lock
+    WaitingForLock,
+    /// The advisory lock is held and nothing has been planned yet
+    LockAcquired,
+    /// The plan is built and nothing has been changed yet
+    Planned,
+    /// All changes have run. In transactional mode, the transaction is
+    /// still open.
+    ChangesApplied,
+    /// About to release the advisory lock, after success or failure
+    ReleasingLock,
+}
+
+/// An apply run paused at `phase`, waiting for the controller
+#[derive(Debug)]
+pub struct ApplyPause {
+    pub phase: ApplyPhase,
+    resume: oneshot::Sender<()>,
+}
+
+impl ApplyPause {
+    /// Let the apply carry on
+    pub fn resume(self) {
+        let _ = self.resume.send(());
+    }
+
+    /// Make the apply fail at this point, as if the process had stopped.
+    /// Open transactions are rolled back and the lock is released.
+    pub fn abort(self) {}
+}
+
+/// Sync points for reproducing interleavings of concurrent applies. An apply
+/// given hooks from [`ApplyHooks::channel`] sends an [`ApplyPause`] at each
+/// [`ApplyPhase`] and waits until it's resumed. The default hooks never pause.
+#[derive(Debug, Clone, Default)]
+pub struct ApplyHooks {
+    pauses: Option<mpsc::UnboundedSender<ApplyPause>>,
+}
+
+impl ApplyHooks {
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ApplyPause>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { pauses: Some(sender) }, receiver)
+    }
+
+    async fn reach(&self, phase: ApplyPhase) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(pauses) = &self.pauses else {
+            return Ok(());
+        };
+        let (resume, resumed) = oneshot::channel();
+        // Nobody listening means nobody to wait for
+        if pauses.send(ApplyPause { phase, resume }).is_err() {
+            return Ok(());
+        }
+        resumed.await.map_err(|_| format!("Apply aborted at {:?}", phase).into())
+    }
 }
 
 pub async fn execute_apply(
@@ -31,10 +147,25 @@ pub async fn execute_apply(
     code_dir: Option<PathBuf>,
     connection_string: String,
     config: &PgmgConfig,
-) -> Result<ApplyResult, Box<dyn std::error::Error>> {
+) -> crate::error::Result<ApplyResult> {
     execute_apply_with_test_mode(migrations_dir, code_dir, connection_string, config, false).await
 }
 
+/// Execute apply until `cancel` is cancelled. A cancelled apply stops the
+/// statement it is running, rolls back its transaction, releases the lock
+/// and fails with [`PgmgError::Cancelled`]. Changes made outside the
+/// transaction, e.g. by no-transaction migrations, stay.
+pub async fn execute_apply_with_cancellation(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    connection_string: String,
+    config: &PgmgConfig,
+    cancel: &CancellationToken,
+) -> crate::error::Result<ApplyResult> {
+    Ok(execute_apply_with_lock_management(migrations_dir, code_dir, connection_string, config, false,
+                                          config.apply_lock_timeout(), &ApplyHooks::default(), cancel).await?)
+}
+
 /// Execute apply with test mode support
 pub async fn execute_apply_with_test_mode(
     migrations_dir: Option<PathBuf>,
@@ -42,8 +173,23 @@ pub async fn execute_apply_with_test_mode(
     connection_string: String,
     config: &PgmgConfig,
     test_mode: bool,
-) -> Result<ApplyResult, Box<dyn std::error::Error>> {
-    execute_apply_with_lock_management(migrations_dir, code_dir, connection_string, config, test_mode).await
+) -> crate::error::Result<ApplyResult> {
+    Ok(execute_apply_with_lock_management(migrations_dir, code_dir, connection_string, config, test_mode,
+                                          config.apply_lock_timeout(), &ApplyHooks::default(), &CancellationToken::new()).await?)
+}
+
+/// Execute apply, pausing at each [`ApplyPhase`] for `hooks`. Used to test
+/// how concurrent applies interleave.
+pub async fn execute_apply_with_hooks(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    connection_string: String,
+    config: &PgmgConfig,
+    lock_timeout: Duration,
+    hooks: &ApplyHooks,
+) -> crate::error::Result<ApplyResult> {
+    Ok(execute_apply_with_lock_management(migrations_dir, code_dir, connection_string, config, false,
+                                          lock_timeout, hooks, &CancellationToken::new()).await?)
 }
 
 /// Library-friendly version of execute_apply
@@ -64,7 +210,7 @@ pub async fn execute_apply_with_test_mode(
 /// ```
 pub async fn apply_migrations(
     config: &PgmgConfig,
-) -> Result<ApplyResult, Box<dyn std::error::Error>> {
+) -> crate::error::Result<ApplyResult> {
     apply_migrations_with_options(config, None, None).await
 }
 
@@ -73,12 +219,22 @@ pub async fn apply_migrations_with_options(
     config: &PgmgConfig,
     migrations_dir: Option<PathBuf>,
     code_dir: Option<PathBuf>,
-) -> Result<ApplyResult, Box<dyn std::error::Error>> {
+) -> crate::error::Result<ApplyResult> {
+    apply_migrations_until_cancelled(config, migrations_dir, code_dir, &CancellationToken::new()).await
+}
+
+/// [`apply_migrations_with_options`], stopping early once `cancel` is cancelled
+pub(crate) async fn apply_migrations_until_cancelled(
+    config: &PgmgConfig,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    cancel: &CancellationToken,
+) -> crate::error::Result<ApplyResult> {
     use tracing::{info_span, Instrument};
     
     // Get connection string from config
     let connection_string = config.connection_string.clone()
-        .ok_or("No database connection string configured")?;
+        .ok_or_else(|| PgmgError::Configuration("No database connection string configured".to_string()))?;
     
     info!("Starting database migrations");
     debug!(?migrations_dir, ?code_dir, "Migration directories");
@@ -95,6 +251,9 @@ pub async fn apply_migrations_with_options(
         connection_string,
         config,
         false, // test_mode = false for normal apply
+        config.apply_lock_timeout(),
+        &ApplyHooks::default(),
+        cancel,
     ).instrument(span).await?;
     
     // Log summary information
@@ -143,12 +302,16 @@ pub async fn apply_migrations_with_options(
 }
 
 /// Execute apply with advisory lock management
+#[allow(clippy::too_many_arguments)]
 async fn execute_apply_with_lock_management(
     migrations_dir: Option<PathBuf>,
     code_dir: Option<PathBuf>,
     connection_string: String,
     config: &PgmgConfig,
     test_mode: bool,
+    lock_timeout: Duration,
+    hooks: &ApplyHooks,
+    cancel: &CancellationToken,
 ) -> Result<ApplyResult, Box<dyn std::error::Error>> {
     // Parse base config from URL
     let mut db_config = DatabaseConfig::from_url(&connection_string)?;
@@ -159,79 +322,228 @@ async fn execute_apply_with_lock_management(
     }
 
     // Connect with merged TLS config
-    let (client, connection) = connect_to_database(&db_config).await?;
+    let (mut client, connection) = connect_to_database(&db_config).await?;
 
     // Spawn connection handler
     connection.spawn();
 
     // Pass test_mode through to the inner function
-    execute_apply_inner(client, migrations_dir, code_dir, connection_string, config, test_mode).await
+    let lock_manager = config.apply_lock_manager(&connection_string);
+    execute_apply_inner(&mut client, lock_manager, lock_timeout, Some(&connection_string), migrations_dir, code_dir, config, test_mode, hooks, cancel).await
 }
 
-async fn execute_apply_inner(
-    mut client: tokio_postgres::Client,
+/// Run apply on an open connection, holding `lock_manager`'s advisory lock
+/// for the duration
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn execute_apply_inner(
+    client: &mut tokio_postgres::Client,
+    mut lock_manager: AdvisoryLockManager,
+    lock_timeout: Duration,
+    connection_string: Option<&str>,
     migrations_dir: Option<PathBuf>,
     code_dir: Option<PathBuf>,
-    connection_string: String,
     config: &PgmgConfig,
     test_mode: bool,
+    hooks: &ApplyHooks,
+    cancel: &CancellationToken,
 ) -> Result<ApplyResult, Box<dyn std::error::Error>> {
+    let started = std::time::Instant::now();
+    let (webhooks, git) = if test_mode {
+        (Vec::new(), None)
+    } else {
+        let dirs: Vec<PathBuf> = migrations_dir.iter().chain(&code_dir).cloned().collect();
+        (config.webhooks()?, git_head(&dirs))
+    };
+    hooks.reach(ApplyPhase::WaitingForLock).await?;
 
     // Acquire advisory lock to prevent concurrent apply operations
-    let mut lock_manager = AdvisoryLockManager::new(&connection_string);
-    
-    // Try to acquire lock with 30-second timeout
-    match lock_manager.acquire_lock(&client, Duration::from_secs(30)).await {
+    match lock_manager.acquire_lock_unless_cancelled(client, lock_timeout, cancel).await {
         Ok(()) => {
             info!("Acquired concurrency lock for apply operation");
         }
-        Err(AdvisoryLockError::Timeout { timeout_seconds }) => {
-            return Err(format!(
-                "Could not acquire lock for apply operation after {} seconds.\n\
-                Another pgmg apply process may be running against this database.\n\
-                If you're sure no other process is running, the lock may be stale and will be cleaned up when that session ends.",
-                timeout_seconds
-            ).into());
+        Err(AdvisoryLockError::Cancelled) => {
+            return Err(PgmgError::Cancelled.into());
+        }
+        Err(AdvisoryLockError::Timeout { .. }) => {
+            let holders = lock_manager.holders(client).await
+                .map(|holders| holders.iter().map(|holder| holder.describe()).collect())
+                .unwrap_or_default();
+            return Err(PgmgError::LockTimeout { timeout: lock_timeout, holders }.into());
         }
         Err(e) => {
-            return Err(format!("Failed to acquire advisory lock: {}", e).into());
+            return Err(PgmgError::LockFailed(e.to_string()).into());
         }
     }
 
-    // Execute the apply operation
-    let apply_result = execute_apply_internal(
-        migrations_dir,
-        code_dir,
-        connection_string,
-        config,
-        &mut client,
-        test_mode,
-    ).await;
+    // Stamped on the pgmg_migrations and pgmg_state rows this apply writes
+    if let Err(e) = set_git_head(&*client, git.as_ref()).await {
+        warn!(error = %e, "Failed to record the git commit being applied");
+    }
+
+    // Execute the apply operation. The backup is kept out here so the run
+    // records it even when the apply fails after taking it.
+    let mut backup_path = None;
+    let apply_result = match hooks.reach(ApplyPhase::LockAcquired).await {
+        Ok(()) => {
+            let cancel_query = client.cancel_token();
+            let apply = execute_apply_internal(
+                migrations_dir,
+                code_dir,
+                config,
+                connection_string,
+                client,
+                test_mode,
+                hooks,
+                &mut backup_path,
+            );
+            tokio::pin!(apply);
+            tokio::select! {
+                result = &mut apply => result,
+                _ = cancel.cancelled() => {
+                    warn!("Apply cancelled, rolling back");
+                    // Sent before the apply is dropped, which rolls back its
+                    // transaction, so the rollback doesn't wait for the
+                    // running statement to finish. Connections that require
+                    // TLS refuse the request, and the statement runs out.
+                    if let Err(e) = cancel_query.cancel_query(NoTls).await {
+                        debug!(error = %e, "Failed to cancel the running statement");
+                    }
+                    Err(PgmgError::Cancelled.into())
+                }
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    // Recorded while the lock is still held, so pgmg_history lists applies
+    // in the order they ran
+    if let (Ok(result), false) = (&apply_result, test_mode) {
+        let objects_changed = result.objects_created.len() + result.objects_updated.len() + result.objects_deleted.len();
+        if let Err(e) = record_apply_completed(&*client, result.migrations_applied.len(), objects_changed).await {
+            warn!(error = %e, "Failed to record finished apply");
+        }
+    }
+    if !test_mode {
+        record_run(&*client, &apply_result, backup_path.as_deref(), config, started.elapsed(), git).await;
+    }
 
     // Always attempt to release the lock
-    if let Err(e) = lock_manager.release_lock(&client).await {
+    let _ = hooks.reach(ApplyPhase::ReleasingLock).await;
+    if let Err(e) = lock_manager.release_lock(client).await {
         warn!("Failed to release advisory lock: {}", e);
         // Don't fail the operation if lock release fails - it will be cleaned up by PostgreSQL
     }
 
+    // Sent once the lock is released, so a slow webhook doesn't hold up the
+    // next apply
+    if !webhooks.is_empty() {
+        send_webhooks(&webhooks, &apply_result, config, started.elapsed()).await;
+    }
+
     apply_result
 }
 
+/// Record the apply in pgmg_runs, whether it succeeded or not, along with
+/// the backup taken before it. A run that can't be recorded is logged and
+/// doesn't fail the apply.
+async fn record_run(
+    client: &tokio_postgres::Client,
+    apply_result: &Result<ApplyResult, Box<dyn std::error::Error>>,
+    backup_path: Option<&Path>,
+    config: &PgmgConfig,
+    duration: Duration,
+    git: Option<GitHead>,
+) {
+    let (changes, errors) = match apply_result {
+        Ok(result) => (RunChanges {
+            migrations_applied: result.migrations_applied.clone(),
+            objects_created: result.objects_created.clone(),
+            objects_updated: result.objects_updated.clone(),
+            objects_deleted: result.objects_deleted.clone(),
+        }, result.errors.clone()),
+        Err(e) => (RunChanges::default(), vec![e.to_string()]),
+    };
+
+    let run = NewRun {
+        duration,
+        os_user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok(),
+        git_dirty: git.as_ref().map(|head| head.dirty),
+        git_commit: git.map(|head| head.commit),
+        environment: config.environment_name(),
+        succeeded: errors.is_empty(),
+        changes,
+        errors,
+        backup_path: backup_path.map(|path| path.display().to_string()),
+    };
+    if let Err(e) = StateManager::new(client).record_run(&run).await {
+        warn!(error = %e, "Failed to record apply run");
+    }
+}
+
+/// Set the commit being applied for the session, where the rows written to
+/// pgmg_migrations and pgmg_state pick it up. Cleared when there's none, so
+/// a reused connection doesn't stamp rows with an earlier apply's commit.
+async fn set_git_head(client: &tokio_postgres::Client, git: Option<&GitHead>) -> Result<(), tokio_postgres::Error> {
+    let commit = git.map(|head| head.commit.clone()).unwrap_or_default();
+    let dirty = git.map(|head| head.dirty.to_string()).unwrap_or_default();
+    client.execute(
+        "SELECT set_config('pgmg.git_commit', $1, false), set_config('pgmg.git_dirty', $2, false)",
+        &[&commit, &dirty],
+    ).await?;
+    Ok(())
+}
+
+/// Send the summary of an apply that changed something or failed to the
+/// configured webhooks
+async fn send_webhooks(
+    webhooks: &[Webhook],
+    apply_result: &Result<ApplyResult, Box<dyn std::error::Error>>,
+    config: &PgmgConfig,
+    duration: Duration,
+) {
+    let failed;
+    let result = match apply_result {
+        Ok(result) => result,
+        Err(e) => {
+            failed = ApplyResult { errors: vec![e.to_string()], ..ApplyResult::default() };
+            &failed
+        }
+    };
+    if !changed_anything(result) && result.errors.is_empty() {
+        return;
+    }
+
+    let context = ApplySummaryContext { environment: config.environment_name(), duration: Some(duration) };
+    send_apply_summary(webhooks, result, &context).await;
+}
+
 
-/// Internal apply function that runs with the lock already acquired
+/// Internal apply function that runs with the lock already acquired. The
+/// path of the backup taken before destructive changes is set in
+/// `backup_path`, as well as in the result.
+#[allow(clippy::too_many_arguments)]
 async fn execute_apply_internal(
     migrations_dir: Option<PathBuf>,
     code_dir: Option<PathBuf>,
-    connection_string: String,
     config: &PgmgConfig,
+    connection_string: Option<&str>,
     client: &mut tokio_postgres::Client,
     test_mode: bool,
+    hooks: &ApplyHooks,
+    backup_path: &mut Option<PathBuf>,
 ) -> Result<ApplyResult, Box<dyn std::error::Error>> {
 
     // Initialize state tracking
     let state_manager = StateManager::new(client);
     state_manager.initialize().await?;
 
+    let skip_rules = skip_rules_for_environment(&*client, config, test_mode).await?;
+    let vars = config.sql_vars();
+    let config_hooks = configured_hooks(config, test_mode);
+    let integration_actions = config.integration_actions()?;
+    let integration_schemas = config.postgrest_schemas();
+    let size_limit = config.destructive_size_limit_bytes()?;
+
     let mut apply_result = ApplyResult {
         migrations_applied: Vec::new(),
         objects_created: Vec::new(),
@@ -240,27 +552,84 @@ async fn execute_apply_internal(
         errors: Vec::new(),
         plpgsql_errors_found: 0,
         plpgsql_warnings_found: 0,
+        skipped: Vec::new(),
+        matview_refreshes: Vec::new(),
+        backup_path: None,
     };
 
     // Step 1: Get the plan to understand what needs to be applied
-    let plan_result = execute_plan(
+    let plan_result = execute_plan_with_client(
+        client,
         migrations_dir.clone(),
         code_dir.clone(),
-        connection_string.clone(),
         None, // No graph output for apply
+        &PlanOptions { partition_removal: config.partition_removal()?, ..PlanOptions::from_config(config) },
     ).await?;
 
-    if plan_result.changes.is_empty() && plan_result.new_migrations.is_empty() {
+    // Code whose formatting alone changed is left as it is in the database,
+    // but its new source is recorded so the change isn't reported again
+    for object in &plan_result.formatting_only {
+        update_object_hash(&*client, &object.object_type, &object.qualified_name, &object.ddl_hash, &object.ddl_source(), &object.directives.tags).await?;
+    }
+    if !plan_result.formatting_only.is_empty() {
+        info!(count = plan_result.formatting_only.len(), "Recorded formatting-only changes without recreating objects");
+    }
+
+    if plan_result.changes.is_empty() && plan_result.new_migrations.is_empty()
+        && plan_result.enum_additions.is_empty() && plan_result.publication_changes.is_empty()
+        && plan_result.cron_job_changes.is_empty() {
         info!("No changes to apply. Database is up to date.");
         return Ok(apply_result);
     }
 
-    // Step 2: Determine if we should use transaction mode
+    // Reject broken migrations before any of them runs
+    check_pending_migrations_parse(&plan_result)?;
+
     // Use auto-commit mode for fresh builds and test mode
     // This allows ALTER TYPE ADD VALUE and other non-transactional DDL
     let is_fresh_build = state_manager.is_empty().await?;
     let use_transaction = !test_mode && !is_fresh_build;
 
+    // Migrations with a `-- pgmg:no-transaction` header commit on their own, so
+    // they can only run before or after the transactional ones
+    let no_transaction = split_no_transaction_migrations(&migrations_dir, &plan_result.new_migrations);
+    if use_transaction && !no_transaction.interleaved.is_empty() {
+        return Err(format!(
+            "No-transaction migration(s) {} come between transactional migrations. Apply the \
+             migrations before them first, or rename them so they sort before or after all \
+             other pending migrations.",
+            no_transaction.interleaved.join(", ")
+        ).into());
+    }
+
+    // Refuse to guess an order for objects that depend on each other in a cycle
+    if let Some(cycle) = plan_result.dependency_graph.as_ref().and_then(|graph| graph.find_cycle()) {
+        return Err(PgmgError::DependencyCycle {
+            path: cycle.names(),
+            description: cycle.describe(&plan_result.file_objects),
+        }.into());
+    }
+
+    // Check pending migrations against logical replication before touching anything
+    if config.replication_safe.unwrap_or(false) && !plan_result.new_migrations.is_empty() {
+        check_replication_safety(client, &plan_result, config.force.unwrap_or(false)).await?;
+    }
+
+    // Refuse to drop or rewrite relations over destructive_size_limit
+    if let Some(limit) = size_limit {
+        check_destructive_size_limit(&plan_result, limit, config.force.unwrap_or(false))?;
+    }
+
+    // Dump the tables and materialized views about to be dropped
+    if !test_mode && config.backup_before_destructive.unwrap_or(false) {
+        *backup_path = backup_dropped_relations(&*client, &plan_result, config, connection_string).await?;
+        apply_result.backup_path = backup_path.clone();
+    }
+
+    config_hooks.run(HookEvent::PreApply, &*client, &HookContext::planned(&plan_result)).await?;
+    hooks.reach(ApplyPhase::Planned).await?;
+
+    // Step 2: Report whether the changes run in a transaction
     if !test_mode {
         if use_transaction {
             debug!("Running in transactional mode (safe rollback on error)");
@@ -281,6 +650,7 @@ async fn execute_apply_internal(
             for migration_name in &plan_result.new_migrations {
                 let migration_path = migrations_dir.join(format!("{}.sql", migration_name));
                 if let Ok(content) = std::fs::read_to_string(&migration_path) {
+                    let content = substitute_vars(vars.as_ref(), &migration_path, content)?;
                     if let Ok(enum_stmts) = extract_enum_add_value_statements(&content) {
                         for (original, rewritten) in &enum_stmts {
                             match client.execute(rewritten.as_str(), &[]).await {
@@ -309,25 +679,222 @@ async fn execute_apply_internal(
         }
     }
 
+    // Values added to enum types in code are committed before migrations and
+    // objects that may use them, like the ADD VALUE statements above
+    add_enum_values(&*client, &mut apply_result, &plan_result, test_mode).await?;
+
     // Step 3: Execute changes in either transaction or auto-commit mode
     if use_transaction {
-        let transaction = client.transaction().await?;
-        execute_all_changes(&transaction, &mut apply_result, &plan_result,
-                           &migrations_dir, &code_dir, config, test_mode,
-                           &pre_committed_enum_stmts).await?;
-        transaction.commit().await?;
+        // CREATE INDEX CONCURRENTLY and objects marked `-- pgmg:no-transaction`
+        // are left for after the commit
+        let mut outside = outside_transaction_objects(&plan_result);
+        outside.migrations_before = no_transaction.before;
+        outside.migrations_after = no_transaction.after;
+        let privileges = PreservedPrivileges::for_plan(&*client, &plan_result, config).await?;
+
+        // No-transaction migrations commit on their own, so the rest is split
+        // into transactions around them: managed objects are dropped before
+        // any migration runs and created after all of them
+        let stages = if outside.migrations_before.is_empty() && outside.migrations_after.is_empty() {
+            vec![(ApplyStage::All, &[][..])]
+        } else {
+            vec![
+                (ApplyStage::Drop, &outside.migrations_before[..]),
+                (ApplyStage::Migrations, &outside.migrations_after[..]),
+                (ApplyStage::Objects, &[][..]),
+            ]
+        };
+
+        for (stage, migrations_after_stage) in stages {
+            let transaction = client.transaction().await?;
+            execute_all_changes(&transaction, &mut apply_result, &plan_result,
+                               &migrations_dir, &code_dir, config, test_mode, &skip_rules,
+                               &pre_committed_enum_stmts, true, &outside, stage, &privileges).await?;
+            if stage.creates() {
+                hooks.reach(ApplyPhase::ChangesApplied).await?;
+            }
+            transaction.commit().await?;
+
+            for migration_name in migrations_after_stage {
+                apply_migration_outside_transaction(client, &mut apply_result, &migrations_dir,
+                                                    migration_name, &pre_committed_enum_stmts, &skip_rules, vars.as_ref(), &config_hooks).await?;
+            }
+        }
+
+        if !outside.objects.is_empty() {
+            apply_outside_transaction(client, &mut apply_result, &outside, config, &skip_rules).await?;
+        }
+        refresh_matviews_concurrently(&*client, &mut apply_result, &plan_result, config).await;
+        run_integration_actions(&*client, &apply_result, &plan_result, &integration_actions, &integration_schemas, test_mode).await;
+        run_post_apply_hooks(&*client, &apply_result, &config_hooks).await;
         print_apply_success_message(&apply_result, test_mode);
     } else {
+        let privileges = PreservedPrivileges::for_plan(&*client, &plan_result, config).await?;
         execute_all_changes(client, &mut apply_result, &plan_result,
-                           &migrations_dir, &code_dir, config, test_mode,
-                           &pre_committed_enum_stmts).await?;
+                           &migrations_dir, &code_dir, config, test_mode, &skip_rules,
+                           &pre_committed_enum_stmts, false, &OutsideTransaction::default(), ApplyStage::All, &privileges).await?;
+        refresh_matviews_concurrently(&*client, &mut apply_result, &plan_result, config).await;
+        hooks.reach(ApplyPhase::ChangesApplied).await?;
+        run_integration_actions(&*client, &apply_result, &plan_result, &integration_actions, &integration_schemas, test_mode).await;
+        run_post_apply_hooks(&*client, &apply_result, &config_hooks).await;
         print_apply_success_message(&apply_result, test_mode);
     }
 
     Ok(apply_result)
 }
 
+/// Parse every pending migration, so a syntax error in any of them fails the
+/// apply up front instead of partway through, after earlier statements ran
+fn check_pending_migrations_parse(plan_result: &PlanResult) -> Result<(), Box<dyn std::error::Error>> {
+    let errors: Vec<(&String, String)> = plan_result.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::ApplyMigration { name, content } => check_migration_syntax(content).err()
+                .map(|e| (name, format!("Migration {} does not parse, {}", name, e))),
+            _ => None,
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(PgmgError::MigrationParse {
+            names: errors.iter().map(|(name, _)| name.to_string()).collect(),
+            message: errors.into_iter().map(|(_, message)| message).collect::<Vec<_>>().join("\n"),
+        }.into())
+    }
+}
+
+/// Statements to leave out of an apply: pg_cron in test mode, plpgsql_check
+/// on RDS, and whatever pgmg.toml configures. The environment profile, if
+/// any, says whether the database is on RDS and which extensions exist.
+async fn skip_rules_for_environment<C: GenericClient>(
+    client: &C,
+    config: &PgmgConfig,
+    test_mode: bool,
+) -> Result<SkipRules, Box<dyn std::error::Error>> {
+    let profile = config.environment_profile()?;
+    let is_rds = match profile.as_ref().and_then(|(_, profile)| profile.rds) {
+        Some(rds) => rds,
+        None => {
+            let is_rds = is_aws_rds(client).await;
+            if is_rds {
+                info!("Detected AWS RDS environment - will skip plpgsql_check related statements");
+            }
+            is_rds
+        }
+    };
+    let extensions = profile.as_ref()
+        .and_then(|(_, profile)| profile.extensions.clone())
+        .unwrap_or_default();
+    if let Some((name, _)) = &profile {
+        debug!(environment = %name, is_rds, ?extensions, "Using environment profile");
+    }
+    Ok(SkipRules::for_profile(config.skip_rules()?, test_mode, is_rds, &extensions))
+}
+
+/// Apply the pending migrations and code changes inside a transaction the
+/// caller has opened and will roll back, e.g. to check functions as they'd be
+/// after a deploy. Runs as in test mode, without NOTIFY events or
+/// plpgsql_check, and leaves out statements that can't run in a transaction
+/// block (CREATE INDEX CONCURRENTLY, no-transaction migrations). Returns the
+/// plan along with the result, for its parsed file objects.
+pub(crate) async fn apply_uncommitted(
+    client: &tokio_postgres::Client,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    config: &PgmgConfig,
+) -> Result<(ApplyResult, PlanResult), Box<dyn std::error::Error>> {
+    let plan_result = execute_plan_with_client(
+        client,
+        migrations_dir.clone(),
+        code_dir.clone(),
+        None,
+        &PlanOptions::from_config(config),
+    ).await?;
+
+    let mut apply_result = ApplyResult {
+        migrations_applied: Vec::new(),
+        objects_created: Vec::new(),
+        objects_updated: Vec::new(),
+        objects_deleted: Vec::new(),
+        errors: Vec::new(),
+        plpgsql_errors_found: 0,
+        plpgsql_warnings_found: 0,
+        skipped: Vec::new(),
+        matview_refreshes: Vec::new(),
+        backup_path: None,
+    };
+    if plan_result.changes.is_empty() && plan_result.new_migrations.is_empty()
+        && plan_result.enum_additions.is_empty() && plan_result.publication_changes.is_empty()
+        && plan_result.cron_job_changes.is_empty() {
+        return Ok((apply_result, plan_result));
+    }
+
+    check_pending_migrations_parse(&plan_result)?;
+
+    if let Some(cycle) = plan_result.dependency_graph.as_ref().and_then(|graph| graph.find_cycle()) {
+        return Err(PgmgError::DependencyCycle {
+            path: cycle.names(),
+            description: cycle.describe(&plan_result.file_objects),
+        }.into());
+    }
+
+    let skip_rules = skip_rules_for_environment(client, config, true).await?;
+    let config = PgmgConfig { development_mode: Some(false), ..config.clone() };
+
+    let no_transaction = split_no_transaction_migrations(&migrations_dir, &plan_result.new_migrations);
+    let mut outside = outside_transaction_objects(&plan_result);
+    outside.migrations_before = no_transaction.before;
+    outside.migrations_after = no_transaction.after;
+    for (object, _) in &outside.objects {
+        debug!(object_name = %format_object_name(object), "Leaving out object that can't be created in a transaction");
+    }
+
+    add_enum_values(client, &mut apply_result, &plan_result, true).await?;
+    let privileges = PreservedPrivileges::for_plan(client, &plan_result, &config).await?;
+    let applied = execute_all_changes(client, &mut apply_result, &plan_result,
+                                      &migrations_dir, &code_dir, &config, true, &skip_rules,
+                                      &HashSet::new(), true, &outside, ApplyStage::All, &privileges).await;
+    if let Err(e) = applied {
+        if apply_result.errors.is_empty() {
+            return Err(e);
+        }
+        return Err(apply_result.errors.join("\n").into());
+    }
+
+    Ok((apply_result, plan_result))
+}
+
+/// The parts of an apply `execute_all_changes` runs. An apply with
+/// no-transaction migrations runs each part in a transaction of its own, with
+/// those migrations committed in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ApplyStage {
+    All,
+    /// Drop the managed objects that are recreated or deleted
+    Drop,
+    /// Run the transactional migrations
+    Migrations,
+    /// Create and recreate managed objects, and everything after that
+    Objects,
+}
+
+impl ApplyStage {
+    fn drops(self) -> bool {
+        matches!(self, ApplyStage::All | ApplyStage::Drop)
+    }
+
+    fn migrates(self) -> bool {
+        matches!(self, ApplyStage::All | ApplyStage::Migrations)
+    }
+
+    fn creates(self) -> bool {
+        matches!(self, ApplyStage::All | ApplyStage::Objects)
+    }
+}
+
 // Helper function to execute all changes using GenericClient (works with both Transaction and Client)
+#[allow(clippy::too_many_arguments)]
 async fn execute_all_changes<C: GenericClient>(
     client: &C,
     apply_result: &mut ApplyResult,
@@ -336,13 +903,35 @@ async fn execute_all_changes<C: GenericClient>(
     _code_dir: &Option<PathBuf>,
     config: &PgmgConfig,
     test_mode: bool,
+    skip_rules: &SkipRules,
     pre_committed_enum_stmts: &HashSet<String>,
+    in_transaction: bool,
+    outside: &OutsideTransaction<'_>,
+    stage: ApplyStage,
+    privileges: &PreservedPrivileges,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let lock_retry = LockRetry::new(config.lock_retry_policy(), in_transaction);
+    let vars = config.sql_vars();
+    let config_hooks = configured_hooks(config, test_mode);
+
+    // Nothing has been dropped or replaced yet, so the overloads to drop can be
+    // looked up for all objects at once
+    let existing_signatures = ExistingSignatures::load(client, plan_result).await?;
+
+    // Step 2.25: Replace views and routines in place where possible. Each attempt
+    // runs under a savepoint, so this needs a transaction. With pending migrations
+    // objects may have to be out of the way of ALTER TABLE, so always drop then.
+    let in_place = if in_transaction && stage == ApplyStage::All && plan_result.new_migrations.is_empty() {
+        replace_in_place(client, apply_result, plan_result, test_mode, skip_rules, &lock_retry, &existing_signatures).await?
+    } else {
+        InPlaceReplacement::default()
+    };
+
     // Step 2.5: Pre-drop managed objects if there are migrations
     // This unblocks migrations that would otherwise be blocked by dependent objects
     let mut pre_dropped_objects: HashSet<String> = HashSet::new();
 
-    if !plan_result.changes.is_empty() {
+    if stage.drops() && !plan_result.changes.is_empty() {
         // Collect all objects that need dropping (both updates and deletes)
         // These must be combined and sorted together by dependency order because
         // dependencies can cross between the two groups
@@ -367,9 +956,18 @@ async fn execute_all_changes<C: GenericClient>(
 
             for change in ordered_drops {
                 match change {
+                    ChangeOperation::UpdateObject { object, .. } if in_place.skips(object) => {
+                        debug!(object_name = %format_object_name(object), "Not dropping object replaced in place");
+                    }
+                    ChangeOperation::UpdateObject { object, .. } if object.object_type == ObjectType::Extension => {
+                        debug!(object_name = %format_object_name(object), "Not dropping extension, which is updated in place");
+                    }
+                    ChangeOperation::UpdateObject { object, .. } if outside.drop_later.contains(&object_key(object)) => {
+                        debug!(object_name = %format_object_name(object), "Dropping object after the transaction");
+                    }
                     ChangeOperation::UpdateObject { object, .. } => {
                         // Pre-drop for update (will be recreated after migrations)
-                        match apply_drop_for_update(client, object).await {
+                        match apply_drop_for_update(client, object, &lock_retry, &existing_signatures).await {
                             Ok(_) => {
                                 pre_dropped_objects.insert(format!("{:?}:{}",
                                     object.object_type,
@@ -398,15 +996,53 @@ async fn execute_all_changes<C: GenericClient>(
                             }
                         }
                     }
+                    ChangeOperation::DeleteObject { object_type, object_name, no_drop: true, .. } => {
+                        // Left in the database, only no longer tracked
+                        let qualified_name = crate::sql::QualifiedIdent::from_qualified_name(object_name);
+                        remove_object_from_state(client, object_type, &qualified_name).await?;
+                        if !test_mode {
+                            warn!(
+                                object_type = %object_type.to_string().to_lowercase(),
+                                object_name = %object_name,
+                                "Object removed from code is marked no-drop: left in the database and no longer tracked"
+                            );
+                        }
+                    }
+                    ChangeOperation::DeleteObject { object_type, object_name, detach_from: Some(parent), .. } => {
+                        // The partition and its rows stay, as a table of its own
+                        let statement = format!("ALTER TABLE {} DETACH PARTITION {}", quote_dotted(parent), quote_dotted(object_name));
+                        if let Err(e) = client.execute(&statement, &[]).await {
+                            let error_msg = format_db_error_details(&e.into());
+                            apply_result.errors.push(format!("Failed to detach {} from {}: {}", object_name, parent, error_msg));
+                            if !test_mode {
+                                error!(
+                                    object_name = %object_name,
+                                    error = %error_msg,
+                                    "Failed to detach partition"
+                                );
+                            }
+                            return Err("Pre-drop failed".into());
+                        }
+                        let qualified_name = crate::sql::QualifiedIdent::from_qualified_name(object_name);
+                        remove_object_from_state(client, object_type, &qualified_name).await?;
+                        if !test_mode {
+                            warn!(
+                                object_name = %object_name,
+                                parent = %parent,
+                                "Partition removed from code was detached: left in the database and no longer tracked"
+                            );
+                        }
+                    }
                     ChangeOperation::DeleteObject { object_type, object_name, .. } => {
                         // Permanent deletion
-                        match apply_delete_object(client, object_type, object_name).await {
+                        match apply_delete_object(client, object_type, object_name, &lock_retry, &existing_signatures).await {
                             Ok(_) => {
                                 pre_dropped_objects.insert(format!("{:?}:{}", object_type, object_name));
                                 apply_result.objects_deleted.push(object_name.clone());
 
                                 if !test_mode {
-                                    info!(
+                                    output::change(Change::Deleted, object_type.to_string().to_lowercase(), object_name);
+                                    debug!(
                                         object_type = %format!("{:?}", object_type).to_lowercase(),
                                         object_name = %object_name,
                                         "Deleted object"
@@ -438,24 +1074,31 @@ async fn execute_all_changes<C: GenericClient>(
     }
 
     // Step 3: Apply migrations first (they need to be applied in order)
-    if !plan_result.new_migrations.is_empty() {
+    let mut migration_error = None;
+    if stage.migrates() && !plan_result.new_migrations.is_empty() {
         if !test_mode {
             info!(count = plan_result.new_migrations.len(), "Applying new migrations");
         }
         
         if let Some(ref migrations_dir) = migrations_dir {
             for migration_name in &plan_result.new_migrations {
-                match apply_migration(client, migrations_dir, migration_name, test_mode, pre_committed_enum_stmts).await {
+                if outside.has_migration(migration_name) {
+                    continue;
+                }
+
+                match apply_migration(client, migrations_dir, migration_name, skip_rules, vars.as_ref(), &config_hooks, pre_committed_enum_stmts, &mut apply_result.skipped).await {
                     Ok(_) => {
                         apply_result.migrations_applied.push(migration_name.clone());
                         if !test_mode {
-                            info!(migration = %migration_name, "Applied migration");
+                            output::change(Change::Migrated, "migration", migration_name);
+                            debug!(migration = %migration_name, "Applied migration");
                         }
                     }
                     Err(e) => {
-                        // The error from apply_migration already contains detailed formatting
-                        apply_result.errors.push(e.to_string());
-                        error!(migration = %migration_name, error = %e, "Failed migration");
+                        let report = migration_error_report(&*e);
+                        error!(migration = %migration_name, error = %report, "Failed migration");
+                        apply_result.errors.push(report);
+                        migration_error = Some(e);
                         break; // Stop processing migrations on first error
                     }
                 }
@@ -464,16 +1107,24 @@ async fn execute_all_changes<C: GenericClient>(
     }
 
     // Check for migration errors before proceeding to object changes
-    if !apply_result.errors.is_empty() {
+    if let Some(e) = migration_error {
         error!(error_count = apply_result.errors.len(), "Failed due to migration errors");
         for err in &apply_result.errors {
             error!(error = %err, "Migration error");
         }
-        return Err("Migration failed".into());
+        return Err(e);
+    }
+
+    if !stage.creates() {
+        return Ok(());
     }
 
     // Track modified objects for plpgsql_check
-    let mut modified_objects: Vec<&SqlObject> = Vec::new();
+    let mut modified_objects: Vec<&SqlObject> = in_place.replaced.clone();
+    // Objects created or recreated, for NOTIFY events
+    let mut loaded_objects: Vec<&SqlObject> = Vec::new();
+    // Materialized views dropped and recreated, which may be left empty
+    let mut recreated_matviews: Vec<&SqlObject> = Vec::new();
     
     // Step 4: Apply object changes based on dependency order
     if !plan_result.changes.is_empty() {
@@ -491,14 +1142,14 @@ async fn execute_all_changes<C: GenericClient>(
         let (updates, _deletes): (Vec<_>, Vec<_>) = non_creates.into_iter()
             .partition(|change| matches!(change, ChangeOperation::UpdateObject { .. }));
 
-        // Get creation order if available (deletion order is handled in the unified drop phase above)
-        let creation_order = if let Some(ref dependency_graph) = plan_result.dependency_graph {
+        // Get creation order if available, preferring the order recorded in the plan
+        // (deletion order is handled in the unified drop phase above)
+        let creation_order = if !plan_result.apply_order.is_empty() {
+            Some(plan_result.apply_order.clone())
+        } else if let Some(ref dependency_graph) = plan_result.dependency_graph {
             match dependency_graph.creation_order() {
                 Ok(create_ord) => Some(create_ord),
-                _ => {
-                    warn!("Could not determine dependency order. Applying changes in original order.");
-                    None
-                }
+                Err(e) => return Err(format!("Could not determine dependency order: {}", e).into()),
             }
         } else {
             None
@@ -519,14 +1170,18 @@ async fn execute_all_changes<C: GenericClient>(
             // Add regular creates
             for change in &creates {
                 if let ChangeOperation::CreateObject { object, .. } = change {
-                    all_creates.push((object, false));
+                    if !outside.contains(object) {
+                        all_creates.push((object, false));
+                    }
                 }
             }
             
-            // Add updates (which need recreation)
+            // Add updates (which need recreation), unless already replaced in place
             for change in &updates {
                 if let ChangeOperation::UpdateObject { object, .. } = change {
-                    all_creates.push((object, true));
+                    if !in_place.skips(object) && !outside.contains(object) {
+                        all_creates.push((object, true));
+                    }
                 }
             }
             
@@ -543,15 +1198,24 @@ async fn execute_all_changes<C: GenericClient>(
             for (object, is_update) in all_creates {
                 if transaction_aborted { break; }
                 
-                match apply_create_object(client, object, config, test_mode).await {
+                let created = match apply_create_object(client, object, skip_rules, &lock_retry, &mut apply_result.skipped).await {
+                    Ok(_) if is_update => privileges.reapply(client, object).await,
+                    created => created,
+                };
+                match created {
                     Ok(_) => {
                         // Track modified objects for plpgsql_check
                         modified_objects.push(object);
+                        loaded_objects.push(object);
 
                         if is_update {
+                            if object.object_type == ObjectType::MaterializedView {
+                                recreated_matviews.push(object);
+                            }
                             apply_result.objects_updated.push(format_object_name(object));
                             if !test_mode {
-                                info!(
+                                output::change(Change::Updated, object.object_type.to_string().to_lowercase(), format_object_name(object));
+                                debug!(
                                     object_type = %format!("{:?}", object.object_type).to_lowercase(),
                                     object_name = %format_object_name(object),
                                     "Recreated object (updated)"
@@ -560,7 +1224,8 @@ async fn execute_all_changes<C: GenericClient>(
                         } else {
                             apply_result.objects_created.push(format_object_name(object));
                             if !test_mode {
-                                info!(
+                                output::change(Change::Created, object.object_type.to_string().to_lowercase(), format_object_name(object));
+                                debug!(
                                     object_type = %format!("{:?}", object.object_type).to_lowercase(),
                                     object_name = %format_object_name(object),
                                     "Created object"
@@ -601,6 +1266,15 @@ async fn execute_all_changes<C: GenericClient>(
         }
         return Err("Apply operation failed".into());
     }
+
+    // Tables added to publications are created by now
+    alter_publications(client, apply_result, plan_result, test_mode).await?;
+    alter_cron_jobs(client, apply_result, plan_result, test_mode, skip_rules).await?;
+
+    refresh_recreated_matviews(client, apply_result, &recreated_matviews, config).await?;
+
+    // Emit NOTIFY events if in development mode
+    emit_loaded_notifications(client, &loaded_objects, config).await;
     
     // Step 4.5: Run plpgsql_check on modified functions if in development mode
     // IMPORTANT: Run plpgsql_check WITHIN the transaction before committing
@@ -613,18 +1287,7 @@ async fn execute_all_changes<C: GenericClient>(
         
         // Check the modified functions themselves using the transaction
         match check_modified_functions(client, &modified_objects).await {
-            Ok(mut check_errors) => {
-                for error in &check_errors {
-                    if let Some(level) = &error.check_result.level {
-                        match level.as_str() {
-                            "error" => apply_result.plpgsql_errors_found += 1,
-                            "warning" => apply_result.plpgsql_warnings_found += 1,
-                            _ => {}
-                        }
-                    }
-                }
-                all_plpgsql_errors.append(&mut check_errors);
-            }
+            Ok(mut check_errors) => all_plpgsql_errors.append(&mut check_errors),
             Err(e) => {
                 // Log but don't fail the operation
                 warn!(error = %e, "Failed to run plpgsql_check");
@@ -639,18 +1302,7 @@ async fn execute_all_changes<C: GenericClient>(
                 &modified_objects,
                 &plan_result.file_objects
             ).await {
-                Ok(mut check_errors) => {
-                    for error in &check_errors {
-                        if let Some(level) = &error.check_result.level {
-                            match level.as_str() {
-                                "error" => apply_result.plpgsql_errors_found += 1,
-                                "warning" => apply_result.plpgsql_warnings_found += 1,
-                                _ => {}
-                            }
-                        }
-                    }
-                    all_plpgsql_errors.append(&mut check_errors);
-                }
+                Ok(mut check_errors) => all_plpgsql_errors.append(&mut check_errors),
                 Err(e) => {
                     // Log but don't fail the operation
                     warn!(error = %e, "Failed to check dependent functions");
@@ -658,15 +1310,32 @@ async fn execute_all_changes<C: GenericClient>(
             }
         }
         
+        // Leave out the codes pgmg.toml or the function itself ignores
+        let policy = config.check_policy()?;
+        all_plpgsql_errors.retain(|error| !policy.is_ignored(
+            &error.check_result,
+            find_source_object(&plan_result.file_objects, &error.function_name),
+        ));
+        for error in &all_plpgsql_errors {
+            match error.check_result.level.as_deref() {
+                Some(level) if level.starts_with("error") => apply_result.plpgsql_errors_found += 1,
+                Some(level) if level.starts_with("warning") => apply_result.plpgsql_warnings_found += 1,
+                _ => {}
+            }
+        }
+        
         // Display all plpgsql_check errors at once (sorted by severity)
         if !all_plpgsql_errors.is_empty() {
             display_check_errors(&all_plpgsql_errors);
         }
         
-        // If there are plpgsql_check errors, fail
-        if apply_result.plpgsql_errors_found > 0 {
+        // If there are plpgsql_check errors (or warnings, with fail_on = "warning"), fail
+        let failures = all_plpgsql_errors.iter()
+            .filter(|error| policy.is_failure(&error.check_result))
+            .count();
+        if failures > 0 {
             error!(
-                error_count = apply_result.plpgsql_errors_found,
+                error_count = failures,
                 "Apply blocked due to PL/pgSQL errors. Fix the errors above and try again."
             );
             return Err("Apply operation blocked due to PL/pgSQL compilation errors".into());
@@ -677,38 +1346,1039 @@ async fn execute_all_changes<C: GenericClient>(
 }
 
 
-async fn apply_migration<C: GenericClient>(
+/// Add the values planned for enum types, and record the types' new source.
+/// The type's settings, such as its owner, are run again in case they changed
+/// along with the values.
+async fn add_enum_values<C: GenericClient>(
     client: &C,
-    migrations_dir: &PathBuf,
-    migration_name: &str,
+    apply_result: &mut ApplyResult,
+    plan_result: &PlanResult,
     test_mode: bool,
-    pre_committed_enum_stmts: &HashSet<String>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let migration_path = migrations_dir.join(format!("{}.sql", migration_name));
-    let migration_content = std::fs::read_to_string(&migration_path)?;
-    
-    // Split migration into statements and execute each one
-    let statements = split_sql_file(&migration_content)?;
-    
-    // Check if we're on AWS RDS once at the beginning
-    let is_rds = is_aws_rds(client).await;
-    if is_rds {
-        info!("Detected AWS RDS environment - will skip plpgsql_check related statements");
-    }
-    
-    for (idx, statement) in statements.iter().enumerate() {
-        if !statement.sql.trim().is_empty() {
-            // Skip pg_cron related statements in test mode
-            if test_mode && should_skip_in_test_mode(&statement.sql) {
-                debug!("Skipping pg_cron statement in test mode: {}", statement.sql.lines().next().unwrap_or(""));
-                continue;
+    for addition in &plan_result.enum_additions {
+        let object = &addition.object;
+        for statement in addition.statements.iter().chain(&object.settings) {
+            if let Err(e) = client.execute(statement.as_str(), &[]).await {
+                return Err(format!(
+                    "Failed to add enum values to {}: {}\nSQL: {}",
+                    format_object_name(object), e, statement
+                ).into());
             }
-            
-            // Skip plpgsql_check related statements on RDS
-            if is_rds && should_skip_plpgsql_check_on_rds(&statement.sql) {
-                debug!("Skipping plpgsql_check statement on RDS: {}", statement.sql.lines().next().unwrap_or(""));
-                warn!("Skipping plpgsql_check statement (not available on AWS RDS)");
-                continue;
+        }
+        update_object_hash(client, &object.object_type, &object.qualified_name, &object.ddl_hash, &object.ddl_source(), &object.directives.tags).await?;
+
+        apply_result.objects_updated.push(format_object_name(object));
+        if !test_mode {
+            output::change(Change::Updated, object.object_type.to_string().to_lowercase(), format_object_name(object));
+            debug!(
+                object_name = %format_object_name(object),
+                count = addition.statements.len(),
+                "Added enum values"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Change the tables and schemas publications publish, and record the
+/// publications' new source. Altering a publication keeps the subscriptions
+/// reading from it, which recreating it would break.
+async fn alter_publications<C: GenericClient>(
+    client: &C,
+    apply_result: &mut ApplyResult,
+    plan_result: &PlanResult,
+    test_mode: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for change in &plan_result.publication_changes {
+        let object = &change.object;
+        for statement in change.statements.iter().chain(&object.settings) {
+            if let Err(e) = client.execute(statement.as_str(), &[]).await {
+                let message = format!(
+                    "Failed to alter publication {}: {}\nSQL: {}",
+                    format_object_name(object), e, statement
+                );
+                apply_result.errors.push(message.clone());
+                return Err(message.into());
+            }
+        }
+        update_object_hash(client, &object.object_type, &object.qualified_name, &object.ddl_hash, &object.ddl_source(), &object.directives.tags).await?;
+
+        apply_result.objects_updated.push(format_object_name(object));
+        if !test_mode {
+            output::change(Change::Updated, object.object_type.to_string().to_lowercase(), format_object_name(object));
+            debug!(
+                object_name = %format_object_name(object),
+                count = change.statements.len(),
+                "Altered publication"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Change the schedule or command of cron jobs with `cron.alter_job`, and
+/// record the jobs' new source. The job keeps its id and run history, which
+/// unscheduling and scheduling it again would lose.
+async fn alter_cron_jobs<C: GenericClient>(
+    client: &C,
+    apply_result: &mut ApplyResult,
+    plan_result: &PlanResult,
+    test_mode: bool,
+    skip_rules: &SkipRules,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for change in &plan_result.cron_job_changes {
+        let object = &change.object;
+        if skip_statement(skip_rules, &object.ddl_statement, object.source_file.as_deref(), object.start_line, &mut apply_result.skipped) {
+            continue;
+        }
+        for statement in &change.statements {
+            if let Err(e) = client.execute(statement.as_str(), &[]).await {
+                let message = format!(
+                    "Failed to alter cron job {}: {}\nSQL: {}",
+                    format_object_name(object), e, statement
+                );
+                apply_result.errors.push(message.clone());
+                return Err(message.into());
+            }
+        }
+        update_object_hash(client, &object.object_type, &object.qualified_name, &object.ddl_hash, &object.ddl_source(), &object.directives.tags).await?;
+
+        if change.statements.is_empty() {
+            continue;
+        }
+        apply_result.objects_updated.push(format_object_name(object));
+        if !test_mode {
+            output::change(Change::Updated, object.object_type.to_string().to_lowercase(), format_object_name(object));
+            debug!(object_name = %format_object_name(object), "Altered cron job");
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of trying to replace changed objects in place
+#[derive(Default)]
+struct InPlaceReplacement<'a> {
+    /// Objects replaced with their CREATE OR REPLACE definition
+    replaced: Vec<&'a SqlObject>,
+    /// Keys (`Type:name`) of updated objects that need no drop or recreate
+    skip: HashSet<String>,
+}
+
+impl InPlaceReplacement<'_> {
+    fn skips(&self, object: &SqlObject) -> bool {
+        self.skip.contains(&object_key(object))
+    }
+}
+
+/// Overloads of the routines and operators that are about to be replaced or
+/// dropped, looked up with one query per object type before any of them is
+/// changed rather than once per object.
+#[derive(Default)]
+struct ExistingSignatures {
+    by_object: HashMap<(ObjectType, String), Vec<String>>,
+}
+
+impl ExistingSignatures {
+    async fn load<C: GenericClient>(client: &C, plan_result: &PlanResult) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut keys_by_type: HashMap<ObjectType, Vec<String>> = HashMap::new();
+        for change in &plan_result.changes {
+            let (object_type, key) = match change {
+                ChangeOperation::UpdateObject { object, .. } => (&object.object_type, catalog_key(&object.qualified_name)),
+                ChangeOperation::DeleteObject { object_type, object_name, .. } => (
+                    object_type,
+                    catalog_key(&crate::sql::QualifiedIdent::from_qualified_name(object_name)),
+                ),
+                _ => continue,
+            };
+            if matches!(object_type, ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate | ObjectType::Operator
+                | ObjectType::OperatorClass | ObjectType::OperatorFamily)
+            {
+                keys_by_type.entry(object_type.clone()).or_default().push(key);
+            }
+        }
+
+        let mut by_object: HashMap<(ObjectType, String), Vec<String>> = HashMap::new();
+        for (object_type, keys) in keys_by_type {
+            // Objects without any overloads are known not to exist
+            for key in &keys {
+                by_object.entry((object_type.clone(), key.clone())).or_default();
+            }
+            for (key, signature) in query_existing_signatures(client, &object_type, &keys).await? {
+                by_object.entry((object_type.clone(), key)).or_default().push(signature);
+            }
+        }
+
+        Ok(ExistingSignatures { by_object })
+    }
+
+    /// Signatures of the object's overloads, queried individually for objects
+    /// that were not looked up up front
+    async fn get<C: GenericClient>(
+        &self,
+        client: &C,
+        object_type: &ObjectType,
+        qualified_name: &crate::sql::QualifiedIdent,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self.by_object.get(&(object_type.clone(), catalog_key(qualified_name))) {
+            Some(signatures) => Ok(signatures.clone()),
+            None => get_existing_function_signatures(client, object_type, qualified_name).await,
+        }
+    }
+}
+
+/// Privileges granted on objects that are dropped and recreated, captured
+/// from their ACLs before the drop. Replacing an object in place keeps its
+/// privileges, so only recreated objects need them granted again.
+#[derive(Default)]
+struct PreservedPrivileges {
+    by_object: HashMap<(ObjectType, String), Vec<ObjectAcl>>,
+}
+
+/// The ACL of one existing object, or one overload of a routine
+struct ObjectAcl {
+    /// The object as written in GRANT, with arguments for routines
+    target: String,
+    /// `(grantee, privilege, with grant option)`, with the grantee quoted or
+    /// PUBLIC. Privileges the owner holds implicitly are left out.
+    grants: Vec<(String, String, bool)>,
+}
+
+impl PreservedPrivileges {
+    /// The privileges on the objects the plan recreates, unless
+    /// `preserve_privileges` is off
+    async fn for_plan<C: GenericClient>(
+        client: &C,
+        plan_result: &PlanResult,
+        config: &PgmgConfig,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if !config.preserve_privileges.unwrap_or(true) {
+            return Ok(Self::default());
+        }
+        Self::load(client, plan_result.changes.iter().filter_map(|change| match change {
+            ChangeOperation::UpdateObject { object, .. } => Some(object),
+            _ => None,
+        })).await
+    }
+
+    async fn load<'a, C: GenericClient>(
+        client: &C,
+        objects: impl IntoIterator<Item = &'a SqlObject>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut keys_by_type: HashMap<ObjectType, Vec<String>> = HashMap::new();
+        for object in objects {
+            if acl_query(&object.object_type).is_some() {
+                keys_by_type.entry(object.object_type.clone()).or_default().push(catalog_key(&object.qualified_name));
+            }
+        }
+
+        let mut by_object: HashMap<(ObjectType, String), Vec<ObjectAcl>> = HashMap::new();
+        for (object_type, keys) in keys_by_type {
+            let Some(query) = acl_query(&object_type) else { continue };
+            let rows = client.query(query, &[&keys, &acl_kind(&object_type)]).await?;
+
+            for row in rows {
+                let acls = by_object.entry((object_type.clone(), row.get(0))).or_default();
+                let target: String = row.get(1);
+                let index = match acls.iter().position(|acl| acl.target == target) {
+                    Some(index) => index,
+                    None => {
+                        acls.push(ObjectAcl { target, grants: Vec::new() });
+                        acls.len() - 1
+                    }
+                };
+                // Without a grant beyond the owner's, the row only marks the ACL as set
+                if let (Some(grantee), Some(privilege)) = (row.get::<_, Option<String>>(2), row.get::<_, Option<String>>(3)) {
+                    acls[index].grants.push((grantee, privilege, row.get(4)));
+                }
+            }
+        }
+
+        Ok(PreservedPrivileges { by_object })
+    }
+
+    /// Grant the privileges captured for `object` on its new definition.
+    /// PUBLIC is revoked first, so a revoked default such as EXECUTE on a
+    /// function stays revoked.
+    async fn reapply<C: GenericClient>(&self, client: &C, object: &SqlObject) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(acls) = self.by_object.get(&(object.object_type.clone(), catalog_key(&object.qualified_name))) else {
+            return Ok(());
+        };
+        let keyword = acl_object_keyword(&object.object_type);
+
+        for acl in acls {
+            // An overload whose arguments changed no longer exists under this signature
+            if matches!(object.object_type, ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate) {
+                let exists: bool = client.query_one("SELECT to_regprocedure($1) IS NOT NULL", &[&acl.target]).await?.get(0);
+                if !exists {
+                    warn!(object = %acl.target, "Not granting privileges again as the signature no longer exists");
+                    continue;
+                }
+            }
+
+            let mut statements = vec![format!("REVOKE ALL ON {} {} FROM PUBLIC", keyword, acl.target)];
+            for (grantee, privilege, grantable) in &acl.grants {
+                statements.push(format!(
+                    "GRANT {} ON {} {} TO {}{}",
+                    privilege,
+                    keyword,
+                    acl.target,
+                    grantee,
+                    if *grantable { " WITH GRANT OPTION" } else { "" }
+                ));
+            }
+            client.batch_execute(&statements.join(";\n")).await?;
+            debug!(object = %acl.target, grants = acl.grants.len(), "Granted privileges again");
+        }
+
+        Ok(())
+    }
+}
+
+/// The ACLs of the objects of one type, one row per grant, for every
+/// `schema.name` in `$1` of the catalog kind `$2`
+fn acl_query(object_type: &ObjectType) -> Option<&'static str> {
+    let query = match object_type {
+        ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate => r#"
+            SELECT
+                n.nspname || '.' || p.proname,
+                quote_ident(n.nspname) || '.' || quote_ident(p.proname) || '(' || pg_get_function_identity_arguments(p.oid) || ')',
+                CASE WHEN a.grantee = 0 THEN 'PUBLIC' ELSE quote_ident(pg_get_userbyid(a.grantee)) END,
+                a.privilege_type,
+                a.is_grantable
+            FROM pg_proc p
+            JOIN pg_namespace n ON n.oid = p.pronamespace
+            LEFT JOIN LATERAL aclexplode(p.proacl) a ON a.grantee <> p.proowner
+            WHERE p.proacl IS NOT NULL
+              AND n.nspname || '.' || p.proname = ANY($1)
+              AND p.prokind = $2::char
+        "#,
+        ObjectType::View | ObjectType::MaterializedView => r#"
+            SELECT
+                n.nspname || '.' || c.relname,
+                quote_ident(n.nspname) || '.' || quote_ident(c.relname),
+                CASE WHEN a.grantee = 0 THEN 'PUBLIC' ELSE quote_ident(pg_get_userbyid(a.grantee)) END,
+                a.privilege_type,
+                a.is_grantable
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            LEFT JOIN LATERAL aclexplode(c.relacl) a ON a.grantee <> c.relowner
+            WHERE c.relacl IS NOT NULL
+              AND n.nspname || '.' || c.relname = ANY($1)
+              AND c.relkind = $2::char
+        "#,
+        ObjectType::Type | ObjectType::Domain => r#"
+            SELECT
+                n.nspname || '.' || t.typname,
+                quote_ident(n.nspname) || '.' || quote_ident(t.typname),
+                CASE WHEN a.grantee = 0 THEN 'PUBLIC' ELSE quote_ident(pg_get_userbyid(a.grantee)) END,
+                a.privilege_type,
+                a.is_grantable
+            FROM pg_type t
+            JOIN pg_namespace n ON n.oid = t.typnamespace
+            LEFT JOIN LATERAL aclexplode(t.typacl) a ON a.grantee <> t.typowner
+            WHERE t.typacl IS NOT NULL
+              AND n.nspname || '.' || t.typname = ANY($1)
+              AND (t.typtype = 'd') = ($2 = 'd')
+        "#,
+        _ => return None,
+    };
+    Some(query)
+}
+
+/// prokind, relkind or typtype of the object type, as used by `acl_query`
+fn acl_kind(object_type: &ObjectType) -> &'static str {
+    match object_type {
+        ObjectType::Procedure => "p",
+        ObjectType::Aggregate => "a",
+        ObjectType::View => "v",
+        ObjectType::MaterializedView => "m",
+        ObjectType::Domain => "d",
+        _ => "f",
+    }
+}
+
+/// The object class written in GRANT and REVOKE
+fn acl_object_keyword(object_type: &ObjectType) -> &'static str {
+    match object_type {
+        ObjectType::Procedure => "PROCEDURE",
+        ObjectType::View | ObjectType::MaterializedView => "TABLE",
+        ObjectType::Type => "TYPE",
+        ObjectType::Domain => "DOMAIN",
+        _ => "FUNCTION",
+    }
+}
+
+/// Replace changed views and routines in place with their CREATE OR REPLACE
+/// definition instead of dropping and recreating them and everything that
+/// depends on them.
+///
+/// Each replacement is tried under a savepoint. PostgreSQL rejects views that
+/// drop, rename or retype columns and routines with a different return type;
+/// a routine with different arguments would be added as a new overload. Those
+/// are rolled back and go through the usual drop/recreate path.
+async fn replace_in_place<'a, C: GenericClient>(
+    client: &C,
+    apply_result: &mut ApplyResult,
+    plan_result: &'a PlanResult,
+    test_mode: bool,
+    skip_rules: &SkipRules,
+    lock_retry: &LockRetry,
+    existing_signatures: &ExistingSignatures,
+) -> Result<InPlaceReplacement<'a>, Box<dyn std::error::Error>> {
+    let Some(graph) = plan_result.dependency_graph.as_ref() else {
+        return Ok(InPlaceReplacement::default());
+    };
+
+    let object_ref = |object: &SqlObject| ObjectRef {
+        object_type: object.object_type.clone(),
+        qualified_name: object.qualified_name.clone(),
+    };
+
+    let updates: Vec<(&SqlObject, bool)> = plan_result.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::UpdateObject { object, cascade, .. } => Some((object, *cascade)),
+            _ => None,
+        })
+        .collect();
+
+    // Objects whose own definition changed and that can be replaced in place.
+    // The rest were only queued because something they depend on changed.
+    let mut candidates: Vec<&SqlObject> = updates.iter()
+        .filter(|(object, cascade)| {
+            !*cascade &&
+            matches!(object.object_type, ObjectType::View | ObjectType::Function | ObjectType::Procedure) &&
+            is_create_or_replace(&object.ddl_statement) &&
+            // Left to the regular path, which records the skip
+            skip_rules.matching(&object.ddl_statement).is_none()
+        })
+        .map(|(object, _)| *object)
+        .collect();
+
+    if candidates.is_empty() {
+        return Ok(InPlaceReplacement::default());
+    }
+
+    if let Ok(order) = graph.creation_order() {
+        candidates.sort_by_key(|object| order.iter().position(|r| *r == object_ref(object)).unwrap_or(usize::MAX));
+    }
+
+    let mut keep: HashSet<ObjectRef> = HashSet::new();
+    for object in candidates {
+        let is_routine = object.object_type != ObjectType::View;
+        if is_routine && existing_signatures.get(client, &object.object_type, &object.qualified_name).await?.len() != 1 {
+            continue;
+        }
+
+        client.execute("SAVEPOINT replace_in_place", &[]).await?;
+        // CREATE OR REPLACE resets the options set on the object, so they are
+        // re-applied as part of the replacement
+        let replaced = match replace_object(client, object, lock_retry).await {
+            Ok(_) if is_routine => {
+                get_existing_function_signatures(client, &object.object_type, &object.qualified_name).await?.len() == 1
+            }
+            Ok(_) => true,
+            Err(e) => {
+                debug!(object_name = %format_object_name(object), error = %e, "Cannot replace object in place");
+                false
+            }
+        };
+
+        if replaced {
+            client.execute("RELEASE SAVEPOINT replace_in_place", &[]).await?;
+            keep.insert(object_ref(object));
+        } else {
+            client.execute("ROLLBACK TO SAVEPOINT replace_in_place", &[]).await?;
+        }
+    }
+
+    // Objects that were only queued because a dependency changed don't need
+    // recreating if that dependency was replaced in place
+    keep.extend(updates.iter()
+        .filter(|(_, cascade)| *cascade)
+        .map(|(object, _)| object_ref(object)));
+
+    // Anything depending on an object that is still dropped has to be dropped
+    // with it, even if it was replaced in place already
+    let changing: Vec<ObjectRef> = plan_result.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::UpdateObject { object, .. } => Some(object_ref(object)),
+            ChangeOperation::DeleteObject { object_type, object_name, no_drop: false, detach_from: None, .. } => Some(ObjectRef {
+                object_type: object_type.clone(),
+                qualified_name: crate::sql::QualifiedIdent::from_qualified_name(object_name),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    loop {
+        let dropped: Vec<ObjectRef> = changing.iter()
+            .filter(|obj_ref| !keep.contains(obj_ref))
+            .cloned()
+            .collect();
+        let must_drop: HashSet<ObjectRef> = graph.affected_by_changes(&dropped).into_iter()
+            .chain(dropped)
+            .collect();
+
+        let before = keep.len();
+        keep.retain(|obj_ref| !must_drop.contains(obj_ref));
+        if keep.len() == before {
+            break;
+        }
+    }
+
+    let mut result = InPlaceReplacement::default();
+    for (object, cascade) in updates {
+        if !keep.contains(&object_ref(object)) {
+            continue;
+        }
+        result.skip.insert(object_key(object));
+
+        if cascade {
+            continue;
+        }
+
+        update_object_hash(client, &object.object_type, &object.qualified_name, &object.ddl_hash, &object.ddl_source(), &object.directives.tags).await?;
+        store_object_dependencies(client, &object.object_type, &object.qualified_name, &object.dependencies).await?;
+
+        apply_result.objects_updated.push(format_object_name(object));
+        result.replaced.push(object);
+        if !test_mode {
+            output::change(Change::Updated, object.object_type.to_string().to_lowercase(), format_object_name(object));
+            debug!(object_name = %format_object_name(object), "Replaced object in place");
+        }
+    }
+
+    Ok(result)
+}
+
+async fn replace_object<C: GenericClient>(
+    client: &C,
+    object: &SqlObject,
+    lock_retry: &LockRetry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    lock_retry.execute(client, &object.ddl_statement, &locked_relations(object)).await?;
+    apply_object_settings(client, object, lock_retry).await
+}
+
+/// Run the ALTER statements that set options on a freshly created object
+async fn apply_object_settings<C: GenericClient>(
+    client: &C,
+    object: &SqlObject,
+    lock_retry: &LockRetry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for setting in &object.settings {
+        lock_retry.execute(client, setting, &locked_relations(object)).await?;
+    }
+    Ok(())
+}
+
+/// Objects that are created after the apply transaction commits
+#[derive(Default)]
+struct OutsideTransaction<'a> {
+    /// Objects to create, in creation order, and whether each is an update
+    objects: Vec<(&'a SqlObject, bool)>,
+    /// Keys of updated objects whose old version is also dropped after the
+    /// commit, so e.g. an index isn't locked for the whole transaction
+    drop_later: HashSet<String>,
+    /// No-transaction migrations to apply before the transaction starts
+    migrations_before: Vec<String>,
+    /// No-transaction migrations to apply after the transaction commits
+    migrations_after: Vec<String>,
+}
+
+impl OutsideTransaction<'_> {
+    fn has_migration(&self, name: &str) -> bool {
+        self.migrations_before.iter().chain(&self.migrations_after).any(|migration| migration == name)
+    }
+
+    fn contains(&self, object: &SqlObject) -> bool {
+        self.objects.iter().any(|(obj, _)| obj.object_type == object.object_type && obj.qualified_name == object.qualified_name)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct NoTransactionMigrations {
+    before: Vec<String>,
+    after: Vec<String>,
+    /// No-transaction migrations between transactional ones
+    interleaved: Vec<String>,
+}
+
+/// Split off the pending migrations marked `-- pgmg:no-transaction` that lead
+/// or trail the transactional ones
+fn split_no_transaction_migrations(migrations_dir: &Option<PathBuf>, pending: &[String]) -> NoTransactionMigrations {
+    let Some(migrations_dir) = migrations_dir else {
+        return NoTransactionMigrations::default();
+    };
+
+    let flags: Vec<bool> = pending.iter()
+        .map(|name| {
+            std::fs::read_to_string(migrations_dir.join(format!("{}.sql", name)))
+                .map(|content| is_no_transaction_migration(&content))
+                .unwrap_or(false)
+        })
+        .collect();
+
+    group_no_transaction_migrations(pending, &flags)
+}
+
+fn group_no_transaction_migrations(pending: &[String], no_transaction: &[bool]) -> NoTransactionMigrations {
+    let leading = no_transaction.iter().take_while(|flag| **flag).count();
+    if leading == pending.len() {
+        return NoTransactionMigrations {
+            before: pending.to_vec(),
+            ..Default::default()
+        };
+    }
+
+    let trailing = no_transaction.iter().rev().take_while(|flag| **flag).count();
+    let middle = leading..pending.len() - trailing;
+
+    NoTransactionMigrations {
+        before: pending[..leading].to_vec(),
+        after: pending[pending.len() - trailing..].to_vec(),
+        interleaved: pending[middle.clone()].iter()
+            .zip(&no_transaction[middle])
+            .filter(|(_, flag)| **flag)
+            .map(|(name, _)| name.clone())
+            .collect(),
+    }
+}
+
+/// Apply a no-transaction migration in auto-commit mode. Statements are
+/// committed one by one and the migration is only recorded once all succeed.
+async fn apply_migration_outside_transaction(
+    client: &tokio_postgres::Client,
+    apply_result: &mut ApplyResult,
+    migrations_dir: &Option<PathBuf>,
+    migration_name: &str,
+    pre_committed_enum_stmts: &HashSet<String>,
+    skip_rules: &SkipRules,
+    vars: Option<&SqlVars>,
+    config_hooks: &Hooks,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(migrations_dir) = migrations_dir else {
+        return Ok(());
+    };
+
+    if let Err(e) = apply_migration(client, migrations_dir, migration_name, skip_rules, vars, config_hooks, pre_committed_enum_stmts, &mut apply_result.skipped).await {
+        let report = migration_error_report(&*e);
+        error!(migration = %migration_name, error = %report, "Failed migration");
+        apply_result.errors.push(report);
+        output::warning(format!(
+            "Migration {} runs outside the apply transaction: statements before the failing one \
+            stay committed and it is not recorded as applied. Make its statements idempotent \
+            (e.g. IF NOT EXISTS) so it can be run again.",
+            migration_name
+        ));
+        return Err(e);
+    }
+
+    apply_result.migrations_applied.push(migration_name.to_string());
+    output::change(Change::Migrated, "migration", migration_name);
+    debug!(migration = %migration_name, "Applied migration outside the transaction");
+
+    Ok(())
+}
+
+/// Find created or updated objects that can't run inside a transaction block,
+/// together with any changed objects that depend on them (e.g. a comment on a
+/// concurrently built index)
+fn outside_transaction_objects(plan_result: &PlanResult) -> OutsideTransaction<'_> {
+    let changed: Vec<(&SqlObject, bool, &str)> = plan_result.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::CreateObject { object, reason } => Some((object, false, reason.as_str())),
+            ChangeOperation::UpdateObject { object, reason, .. } => Some((object, true, reason.as_str())),
+            _ => None,
+        })
+        .collect();
+
+    let object_ref = |object: &SqlObject| ObjectRef {
+        object_type: object.object_type.clone(),
+        qualified_name: object.qualified_name.clone(),
+    };
+
+    let direct: Vec<ObjectRef> = changed.iter()
+        .filter(|(object, _, _)| runs_outside_transaction(&object.ddl_statement))
+        .map(|(object, _, _)| object_ref(object))
+        .collect();
+
+    if direct.is_empty() {
+        return OutsideTransaction::default();
+    }
+
+    let mut selected: HashSet<ObjectRef> = direct.iter().cloned().collect();
+    if let Some(graph) = &plan_result.dependency_graph {
+        selected.extend(graph.affected_by_changes(&direct));
+    }
+
+    let mut outside = OutsideTransaction::default();
+    for (object, is_update, reason) in changed {
+        if !selected.contains(&object_ref(object)) {
+            continue;
+        }
+
+        // Objects recreated because of a migration or a recreated dependency
+        // still have to be dropped inside the transaction, before those run
+        if is_update && reason == "DDL content has changed" {
+            outside.drop_later.insert(object_key(object));
+        }
+        outside.objects.push((object, is_update));
+    }
+
+    let creation_order = if !plan_result.apply_order.is_empty() {
+        Some(plan_result.apply_order.clone())
+    } else {
+        plan_result.dependency_graph.as_ref().and_then(|graph| graph.creation_order().ok())
+    };
+    if let Some(order) = creation_order {
+        outside.objects.sort_by_key(|(object, _)| order.iter().position(|r| *r == object_ref(object)).unwrap_or(usize::MAX));
+    }
+
+    outside
+}
+
+/// Create objects that can't run inside the apply transaction, one statement
+/// at a time in auto-commit mode.
+///
+/// Everything else has been committed by now. If a statement fails, the
+/// object's state isn't updated, so the next apply picks it up again. A failed
+/// CREATE INDEX CONCURRENTLY leaves an invalid index behind, which is dropped
+/// before retrying.
+async fn apply_outside_transaction(
+    client: &tokio_postgres::Client,
+    apply_result: &mut ApplyResult,
+    outside: &OutsideTransaction<'_>,
+    config: &PgmgConfig,
+    skip_rules: &SkipRules,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!(count = outside.objects.len(), "Applying objects outside the transaction");
+    let lock_retry = LockRetry::new(config.lock_retry_policy(), false);
+
+    let failed = |object: &SqlObject, action: &str, e: Box<dyn std::error::Error>| -> Box<dyn std::error::Error> {
+        let detailed_error = match e.downcast_ref::<tokio_postgres::Error>() {
+            Some(pg_err) => format_postgres_error_with_details(
+                &format_object_name(object),
+                object.source_file.as_deref(),
+                object.start_line,
+                &object.ddl_statement,
+                pg_err
+            ),
+            None => format!("Failed to {} {}: {}", action, format_object_name(object), e),
+        };
+        format!(
+            "{}\nAll other changes were committed. Run apply again to retry the remaining objects.",
+            detailed_error
+        ).into()
+    };
+
+    let privileges = if config.preserve_privileges.unwrap_or(true) {
+        let recreated = outside.objects.iter()
+            .filter(|(object, _)| outside.drop_later.contains(&object_key(object)))
+            .map(|(object, _)| *object);
+        PreservedPrivileges::load(client, recreated).await?
+    } else {
+        PreservedPrivileges::default()
+    };
+
+    // Drop old versions, dependents first
+    for (object, _) in outside.objects.iter().rev() {
+        if !outside.drop_later.contains(&object_key(object)) {
+            continue;
+        }
+
+        let result = if object.object_type == ObjectType::Index {
+            let drop_statement = generate_drop_statement(&object.object_type, &object.qualified_name)
+                .replacen("DROP INDEX", "DROP INDEX CONCURRENTLY", 1);
+            client.execute(&drop_statement, &[]).await.map(|_| ()).map_err(|e| e.into())
+        } else {
+            // The transaction has committed, so look the overloads up again
+            apply_drop_for_update(client, object, &lock_retry, &ExistingSignatures::default()).await
+        };
+        result.map_err(|e| failed(object, "drop", e))?;
+    }
+
+    for (object, is_update) in &outside.objects {
+        if object.object_type == ObjectType::Index {
+            drop_invalid_index(client, object).await?;
+        }
+
+        let action = if *is_update { "recreate" } else { "create" };
+        apply_create_object(client, object, skip_rules, &lock_retry, &mut apply_result.skipped).await
+            .map_err(|e| failed(object, action, e))?;
+        if *is_update {
+            privileges.reapply(client, object).await.map_err(|e| failed(object, action, e))?;
+        }
+        emit_loaded_notifications(client, &[*object], config).await;
+
+        if *is_update {
+            apply_result.objects_updated.push(format_object_name(object));
+            output::change(Change::Updated, object.object_type.to_string().to_lowercase(), format_object_name(object));
+        } else {
+            apply_result.objects_created.push(format_object_name(object));
+            output::change(Change::Created, object.object_type.to_string().to_lowercase(), format_object_name(object));
+        }
+        debug!(object_name = %format_object_name(object), "Applied object outside the transaction");
+    }
+
+    Ok(())
+}
+
+/// Drop an invalid index left behind by an interrupted CREATE INDEX CONCURRENTLY
+async fn drop_invalid_index(
+    client: &tokio_postgres::Client,
+    object: &SqlObject,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rows = client.query(
+        "SELECT quote_ident(n.nspname) || '.' || quote_ident(c.relname)
+         FROM pg_index i
+         JOIN pg_class c ON c.oid = i.indexrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE c.relname = $1
+           AND (n.nspname = $2 OR ($2::text IS NULL AND n.nspname = ANY(current_schemas(false))))
+           AND NOT i.indisvalid",
+        &[&object.qualified_name.name, &object.qualified_name.schema],
+    ).await?;
+
+    for row in rows {
+        let index_name: String = row.get(0);
+        warn!(index = %index_name, "Dropping invalid index left by an earlier failed build");
+        client.execute(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", index_name), &[]).await?;
+    }
+
+    Ok(())
+}
+
+/// Wait until a replica shows everything a successful apply changed: the
+/// applied migrations, and the state rows and catalog entries of created or
+/// updated objects.
+pub async fn verify_replica(
+    primary_url: &str,
+    replica_url: &str,
+    result: &ApplyResult,
+    timeout: Duration,
+) -> Result<ReplicaVerification, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(primary_url).await?;
+    connection.spawn();
+
+    let object_names: Vec<String> = result.objects_created.iter()
+        .chain(result.objects_updated.iter())
+        .cloned()
+        .collect();
+    let expectation = collect_replica_expectation(&client, &result.migrations_applied, &object_names).await?;
+    debug!(
+        "Waiting for replica to show {} migrations and {} objects",
+        expectation.migrations.len(),
+        expectation.objects.len()
+    );
+
+    wait_for_replica(replica_url, &expectation, timeout, Duration::from_secs(1)).await
+}
+
+/// Fail if pending migrations change tables that take part in logical replication,
+/// unless `force` is set, in which case the conflicts are only logged
+async fn check_replication_safety(
+    client: &tokio_postgres::Client,
+    plan_result: &PlanResult,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut hazards = Vec::new();
+    for change in &plan_result.changes {
+        if let ChangeOperation::ApplyMigration { name, content } = change {
+            match extract_replication_hazards(content) {
+                Ok(found) => hazards.extend(found.into_iter().map(|hazard| (name.clone(), hazard))),
+                Err(e) => warn!("Could not analyze migration {} for replication safety: {}", name, e),
+            }
+        }
+    }
+
+    if hazards.is_empty() {
+        return Ok(());
+    }
+
+    let replicated = get_replicated_tables(client).await?;
+    let conflicts = find_replication_conflicts(&hazards, &replicated);
+    if conflicts.is_empty() {
+        debug!("No pending migrations affect replicated tables");
+        return Ok(());
+    }
+
+    if force {
+        for conflict in &conflicts {
+            warn!("Replication conflict (forced): {}", conflict.describe());
+        }
+        return Ok(());
+    }
+
+    let details: Vec<String> = conflicts.iter()
+        .map(|conflict| format!("  - {}", conflict.describe()))
+        .collect();
+    Err(format!(
+        "Pending migrations would affect logically replicated tables:\n{}\n\
+        Coordinate the change with subscribers, or re-run with --force to apply anyway.",
+        details.join("\n")
+    ).into())
+}
+
+/// Fail if the plan drops or rewrites relations larger than `limit` bytes,
+/// unless `force` is set, in which case they are only logged
+fn check_destructive_size_limit(
+    plan_result: &PlanResult,
+    limit: u64,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let oversized: Vec<_> = plan_result.relation_sizes.iter()
+        .filter(|size| size.bytes > limit)
+        .collect();
+    if oversized.is_empty() {
+        return Ok(());
+    }
+
+    if force {
+        for size in &oversized {
+            warn!("Relation over destructive_size_limit (forced): {}", size.describe());
+        }
+        return Ok(());
+    }
+
+    let details: Vec<String> = oversized.iter()
+        .map(|size| format!("  - {}", size.describe()))
+        .collect();
+    Err(format!(
+        "The plan drops or rewrites relations larger than destructive_size_limit ({}):\n{}\n\
+        Make the change in a maintenance window, or re-run with --force to apply anyway.",
+        crate::logging::format_size(limit),
+        details.join("\n")
+    ).into())
+}
+
+/// Dump the tables and materialized views the plan drops, whether removed
+/// from code or recreated, with their data to a new file in `backup_dir`
+/// that `pg_restore` can load. `None` if the plan drops none that exist.
+async fn backup_dropped_relations(
+    client: &tokio_postgres::Client,
+    plan_result: &PlanResult,
+    config: &PgmgConfig,
+    connection_string: Option<&str>,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let is_relation = |object_type: &ObjectType| matches!(object_type, ObjectType::Table | ObjectType::MaterializedView);
+    let mut relations = Vec::new();
+    for change in &plan_result.changes {
+        let relation = match change {
+            ChangeOperation::DeleteObject { object_type, object_name, no_drop: false, detach_from: None, .. } if is_relation(object_type) => {
+                quote_dotted(object_name)
+            }
+            ChangeOperation::UpdateObject { object, .. } if is_relation(&object.object_type) => {
+                quote_qualified_ident(&object.qualified_name)
+            }
+            _ => continue,
+        };
+        // pg_dump reads --table as a pattern, where `*`, `?` and `.` have a
+        // meaning and unqualified names match in every schema. Quoted parts
+        // are taken literally, so the relation is named by schema and name,
+        // each quoted.
+        let pattern = client.query_opt(
+            "SELECT format('\"%s\".\"%s\"', replace(n.nspname, '\"', '\"\"'), replace(c.relname, '\"', '\"\"'))
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.oid = to_regclass($1)",
+            &[&relation],
+        ).await?;
+        if let Some(row) = pattern {
+            relations.push(row.get::<_, String>(0));
+        }
+    }
+    if relations.is_empty() {
+        return Ok(None);
+    }
+
+    let connection_string = connection_string.or(config.connection_string.as_deref())
+        .ok_or("backup_before_destructive needs a connection string for pg_dump")?;
+    let backup_dir = config.backup_dir.clone().unwrap_or_else(|| PathBuf::from("backups"));
+    std::fs::create_dir_all(&backup_dir)?;
+    let path = create_backup_file(&backup_dir)?;
+
+    // Run without blocking the runtime, which under `pgmg serve` also answers
+    // health checks while the dump runs
+    let mut command = tokio::process::Command::new("pg_dump");
+    command.args(["--format=custom", "--no-owner", "--file"]).arg(&path);
+    for relation in &relations {
+        command.arg(format!("--table={}", relation));
+    }
+    let output = command.args(["--dbname", connection_string])
+        .output()
+        .await
+        .map_err(|_| "pg_dump not found. Please ensure PostgreSQL client tools are installed.")?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&path);
+        return Err(format!(
+            "Backup before destructive apply failed, nothing was changed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ).into());
+    }
+
+    info!(path = %path.display(), relations = relations.len(), "Backed up relations the plan drops");
+    Ok(Some(path))
+}
+
+/// Create the empty file a backup is dumped to, named after the current
+/// time. A backup taken earlier in the same second keeps its file, and this
+/// one is numbered instead.
+fn create_backup_file(backup_dir: &Path) -> std::io::Result<PathBuf> {
+    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S").to_string();
+    let mut attempt = 1;
+    loop {
+        let name = match attempt {
+            1 => format!("pgmg_{}.dump", timestamp),
+            n => format!("pgmg_{}_{}.dump", timestamp, n),
+        };
+        let path = backup_dir.join(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A failed migration as recorded in the apply's errors: the statement, where
+/// it is in the file and PostgreSQL's detail and hint, for MigrationFailed
+fn migration_error_report(e: &(dyn std::error::Error + 'static)) -> String {
+    match e.downcast_ref::<PgmgError>() {
+        Some(PgmgError::MigrationFailed { name, statement_index, file, line, sql, pg_error }) => format_postgres_error_with_details(
+            &format!("migration {} (statement {})", name, statement_index),
+            Some(file),
+            *line,
+            sql,
+            pg_error,
+        ),
+        _ => e.to_string(),
+    }
+}
+
+async fn apply_migration<C: GenericClient>(
+    client: &C,
+    migrations_dir: &PathBuf,
+    migration_name: &str,
+    skip_rules: &SkipRules,
+    vars: Option<&SqlVars>,
+    config_hooks: &Hooks,
+    pre_committed_enum_stmts: &HashSet<String>,
+    skipped: &mut Vec<SkippedStatement>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let migration_path = migrations_dir.join(format!("{}.sql", migration_name));
+    let migration_content = std::fs::read_to_string(&migration_path)?;
+    
+    // Split migration into statements and execute each one. The recorded
+    // hash is of the file itself, so a variable's value can change later.
+    let statements = split_sql_file(&substitute_vars(vars, &migration_path, migration_content.clone())?)?;
+    let hook_context = HookContext::for_migration(migration_name);
+    config_hooks.run(HookEvent::PreMigration, client, &hook_context).await?;
+    
+    for (idx, statement) in statements.iter().enumerate() {
+        if !statement.sql.trim().is_empty() {
+            if skip_statement(skip_rules, &statement.sql, Some(migration_path.as_path()), statement.start_line, skipped) {
+                continue;
             }
 
             // Skip ALTER TYPE ADD VALUE statements that were pre-committed
@@ -730,29 +2400,29 @@ async fn apply_migration<C: GenericClient>(
                     }
                 }
             }
-
-            match client.execute(&statement.sql, &[]).await {
-                Ok(_) => {},
-                Err(e) => {
-                    // Create a detailed error message with context
-                    let detailed_error = format_postgres_error_with_details(
-                        &format!("migration {} (statement {})", migration_name, idx + 1),
-                        Some(&migration_path),
-                        statement.start_line,
-                        &statement.sql,
-                        &e
-                    );
-                    return Err(detailed_error.into());
-                }
+
+            if let Err(e) = client.execute(&statement.sql, &[]).await {
+                return Err(PgmgError::MigrationFailed {
+                    name: migration_name.to_string(),
+                    statement_index: idx + 1,
+                    file: migration_path.clone(),
+                    line: statement.start_line,
+                    sql: statement.sql.clone(),
+                    pg_error: e,
+                }.into());
             }
         }
     }
     
-    // Record migration as applied in pgmg_migrations table
+    // Record migration as applied in pgmg_migrations table, with its content
+    // hash so later edits to the file are noticed
     client.execute(
-        "INSERT INTO pgmg.pgmg_migrations (name) VALUES ($1) ON CONFLICT (name) DO NOTHING",
-        &[&migration_name],
+        "INSERT INTO pgmg.pgmg_migrations (name, content_hash, git_commit, git_dirty)
+         VALUES ($1, $2, nullif(current_setting('pgmg.git_commit', true), ''), nullif(current_setting('pgmg.git_dirty', true), '')::boolean)
+         ON CONFLICT (name) DO NOTHING",
+        &[&migration_name, &migration_content_hash(&migration_content)],
     ).await?;
+    config_hooks.run(HookEvent::PostMigration, client, &hook_context).await?;
     
     Ok(())
 }
@@ -760,46 +2430,65 @@ async fn apply_migration<C: GenericClient>(
 async fn apply_create_object<C: GenericClient>(
     client: &C,
     object: &SqlObject,
-    config: &PgmgConfig,
-    test_mode: bool,
+    skip_rules: &SkipRules,
+    lock_retry: &LockRetry,
+    skipped: &mut Vec<SkippedStatement>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Skip pg_cron related objects in test mode
-    if test_mode && should_skip_in_test_mode(&object.ddl_statement) {
-        debug!("Skipping pg_cron object in test mode: {}", object.qualified_name.name);
+    if skip_statement(skip_rules, &object.ddl_statement, object.source_file.as_deref(), object.start_line, skipped) {
         return Ok(());
     }
     
-    // Execute the DDL statement
-    client.execute(&object.ddl_statement, &[]).await?;
+    if object.object_type == ObjectType::Extension {
+        apply_extension(client, object, lock_retry).await?;
+    } else {
+        // Execute the DDL statement
+        lock_retry.execute(client, &object.ddl_statement, &locked_relations(object)).await?;
+    }
+    apply_object_settings(client, object, lock_retry).await?;
     
     // Update state tracking with object hash
-    let ddl_hash = calculate_ddl_hash(&object.ddl_statement);
-    update_object_hash(client, &object.object_type, &object.qualified_name, &ddl_hash).await?;
+    update_object_hash(client, &object.object_type, &object.qualified_name, &object.ddl_hash, &object.ddl_source(), &object.directives.tags).await?;
     
     // Store object dependencies
     store_object_dependencies(client, &object.object_type, &object.qualified_name, &object.dependencies).await?;
     
-    // Emit NOTIFY event if in development mode
-    if config.development_mode.unwrap_or(false) && config.emit_notify_events.unwrap_or(false) {
-        let mut notification = ObjectLoadedNotification::from_sql_object(object);
-
-        // Try to get the OID of the created object
-        if let Ok(oid) = get_object_oid(client, &object.object_type, &object.qualified_name).await {
-            notification.oid = Some(oid);
-        }
+    Ok(())
+}
 
-        if let Err(e) = emit_object_loaded_notification(client, &notification).await {
-            // Log the error but don't fail the operation
-            warn!(error = %e, "Failed to emit NOTIFY event");
-        }
+/// Create an extension, or bring an installed one to the version its file
+/// pins with `ALTER EXTENSION ... UPDATE`. Extensions aren't dropped to apply
+/// a change, as everything using them would go too, so options other than
+/// the version only take effect when the extension is created.
+async fn apply_extension<C: GenericClient>(
+    client: &C,
+    object: &SqlObject,
+    lock_retry: &LockRetry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let name = &object.qualified_name.name;
+    let installed: Option<String> = client.query_opt(
+        "SELECT extversion FROM pg_extension WHERE extname = $1",
+        &[name],
+    ).await?.map(|row| row.get(0));
+
+    match (installed, extract_extension_version(&object.ddl_statement)) {
+        (None, _) => {
+            lock_retry.execute(client, &object.ddl_statement, &[]).await?;
+        }
+        (Some(installed), Some(pinned)) if installed != pinned => {
+            let statement = format!("ALTER EXTENSION {} UPDATE TO {}", quote_ident(name), quote_literal(&pinned));
+            lock_retry.execute(client, &statement, &[]).await?;
+            info!(extension = %name, from = %installed, to = %pinned, "Updated extension");
+        }
+        _ => {}
     }
-    
     Ok(())
 }
 
 async fn apply_drop_for_update<C: GenericClient>(
     client: &C,
     object: &SqlObject,
+    lock_retry: &LockRetry,
+    existing_signatures: &ExistingSignatures,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Handle special cases for object types that can't be dropped normally
     if object.object_type == ObjectType::Comment {
@@ -808,6 +2497,20 @@ async fn apply_drop_for_update<C: GenericClient>(
         client.execute(&comment_null_statement, &[]).await?;
         return Ok(());
     }
+
+    if object.object_type == ObjectType::SecurityLabel {
+        // Security labels can't be dropped either, only set to NULL
+        let label_null_statement = generate_security_label_null_statement(&object.qualified_name.name)?;
+        client.execute(&label_null_statement, &[]).await?;
+        return Ok(());
+    }
+
+    if object.directives.no_drop {
+        return Err(format!(
+            "{} is marked -- pgmg:no-drop, but this change recreates it. Make the change with a migration instead.",
+            format_object_name(object)
+        ).into());
+    }
     
     // Just drop the object - creation will happen in a separate phase
     let drop_statement = match object.object_type {
@@ -815,24 +2518,23 @@ async fn apply_drop_for_update<C: GenericClient>(
             // For triggers, we need to extract the table name from the DDL
             match extract_trigger_table(&object.ddl_statement) {
                 Ok(table_name) => {
-                    let trigger_name = quote_qualified_identifier(
-                        object.qualified_name.schema.as_deref(),
-                        &object.qualified_name.name
-                    );
-                    let table_full_name = quote_qualified_identifier(
-                        table_name.schema.as_deref(),
-                        &table_name.name
-                    );
-                    format!("DROP TRIGGER IF EXISTS {} ON {}", trigger_name, table_full_name)
+                    // Triggers live in their table's schema, so only the table is qualified
+                    format!(
+                        "DROP TRIGGER IF EXISTS {} ON {}",
+                        quote_ident(&object.qualified_name.name),
+                        quote_qualified_ident(&table_name)
+                    )
                 }
                 Err(e) => {
                     return Err(format!("Could not extract table name from trigger DDL: {}", e).into());
                 }
             }
         }
-        ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate => {
-            // For functions, procedures, and aggregates, we need to drop all existing overloads
-            let existing_signatures = get_existing_function_signatures(client, &object.object_type, &object.qualified_name).await?;
+        ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate
+        | ObjectType::OperatorClass | ObjectType::OperatorFamily => {
+            // For functions, procedures, and aggregates, we need to drop all existing overloads.
+            // Operator classes and families are dropped with the index method they're for.
+            let existing_signatures = existing_signatures.get(client, &object.object_type, &object.qualified_name).await?;
             
             if existing_signatures.is_empty() {
                 // No existing function found, nothing to drop
@@ -843,20 +2545,31 @@ async fn apply_drop_for_update<C: GenericClient>(
                 ObjectType::Function => "FUNCTION",
                 ObjectType::Procedure => "PROCEDURE",
                 ObjectType::Aggregate => "AGGREGATE",
+                ObjectType::OperatorClass => "OPERATOR CLASS",
+                ObjectType::OperatorFamily => "OPERATOR FAMILY",
                 _ => unreachable!(),
             };
             
             // Drop all existing overloads
             for signature in existing_signatures {
                 let drop_statement = format!("DROP {} IF EXISTS {}", object_type_str, signature);
-                client.execute(&drop_statement, &[]).await?;
+                lock_retry.execute(client, &drop_statement, &locked_relations(object)).await?;
             }
             
             return Ok(());
         }
+        ObjectType::Operator => {
+            // Only the operator the code defines, not others of the same name
+            let signatures = managed_operator_signatures(client, &object.qualified_name, Some(&object.ddl_statement)).await?;
+            for signature in signatures {
+                let drop_statement = format!("DROP OPERATOR IF EXISTS {}", signature);
+                lock_retry.execute(client, &drop_statement, &locked_relations(object)).await?;
+            }
+            return Ok(());
+        }
         _ => generate_drop_statement(&object.object_type, &object.qualified_name)
     };
-    client.execute(&drop_statement, &[]).await?;
+    lock_retry.execute(client, &drop_statement, &locked_relations(object)).await?;
     Ok(())
 }
 
@@ -864,13 +2577,20 @@ async fn apply_delete_object<C: GenericClient>(
     client: &C,
     object_type: &ObjectType,
     object_name: &str,
+    lock_retry: &LockRetry,
+    existing_signatures: &ExistingSignatures,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Parse the qualified name
     let qualified_name = crate::sql::QualifiedIdent::from_qualified_name(object_name);
+    let mut relations = vec![object_name.to_string()];
     
-    // Handle comment deletion specially - comments can't be dropped, only set to NULL
-    if object_type == &ObjectType::Comment {
-        let comment_null_statement = generate_comment_null_statement(object_name)?;
+    // Handle comment deletion specially - comments (and security labels) can't be dropped, only set to NULL
+    if matches!(object_type, ObjectType::Comment | ObjectType::SecurityLabel) {
+        let comment_null_statement = if object_type == &ObjectType::SecurityLabel {
+            generate_security_label_null_statement(object_name)?
+        } else {
+            generate_comment_null_statement(object_name)?
+        };
         
         // Create a savepoint before attempting the comment deletion
         client.execute("SAVEPOINT comment_deletion", &[]).await?;
@@ -892,9 +2612,22 @@ async fn apply_delete_object<C: GenericClient>(
         // This ensures we don't try to delete non-existent comments repeatedly
         remove_object_from_state(client, object_type, &qualified_name).await?;
         return Ok(());
-    } else if matches!(object_type, ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate | ObjectType::Operator) {
-        // For functions, procedures, aggregates, and operators, drop all existing overloads
-        let existing_signatures = get_existing_function_signatures(client, object_type, &qualified_name).await?;
+    } else if matches!(object_type, ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate | ObjectType::Operator
+        | ObjectType::OperatorClass | ObjectType::OperatorFamily)
+    {
+        // For functions, procedures, and aggregates, drop all existing overloads,
+        // and operator classes and families for each index method. Operators are
+        // dropped by their stored signature, falling back to all of that name if
+        // they were applied before pgmg stored them.
+        let managed_signatures = match object_type {
+            ObjectType::Operator => managed_operator_signatures(client, &qualified_name, None).await?,
+            _ => Vec::new(),
+        };
+        let existing_signatures = if managed_signatures.is_empty() {
+            existing_signatures.get(client, object_type, &qualified_name).await?
+        } else {
+            managed_signatures
+        };
         
         if !existing_signatures.is_empty() {
             let object_type_str = match object_type {
@@ -902,13 +2635,15 @@ async fn apply_delete_object<C: GenericClient>(
                 ObjectType::Procedure => "PROCEDURE",
                 ObjectType::Aggregate => "AGGREGATE",
                 ObjectType::Operator => "OPERATOR",
+                ObjectType::OperatorClass => "OPERATOR CLASS",
+                ObjectType::OperatorFamily => "OPERATOR FAMILY",
                 _ => unreachable!(),
             };
             
             // Drop all existing overloads
             for signature in existing_signatures {
                 let drop_statement = format!("DROP {} IF EXISTS {}", object_type_str, signature);
-                match client.execute(&drop_statement, &[]).await {
+                match lock_retry.execute(client, &drop_statement, &relations).await {
                     Ok(_) => {},
                     Err(e) => {
                         // Check if this is a dependency error
@@ -931,31 +2666,19 @@ async fn apply_delete_object<C: GenericClient>(
     } else if object_type == &ObjectType::Trigger {
         // Triggers need special handling - we need to find the table they're on
         let trigger_table = get_trigger_table_from_dependencies(client, &qualified_name).await?;
-        let trigger_name = quote_qualified_identifier(
-            qualified_name.schema.as_deref(),
-            &qualified_name.name
-        );
         
         // The trigger_table could be either "table_name" or "schema.table_name"
-        // We need to properly quote it
-        let quoted_table = if trigger_table.contains('.') {
-            // It's already qualified, split and quote each part
-            let parts: Vec<&str> = trigger_table.splitn(2, '.').collect();
-            if parts.len() == 2 {
-                quote_qualified_identifier(Some(parts[0]), parts[1])
-            } else {
-                quote_qualified_identifier(None, &trigger_table)
-            }
-        } else {
-            quote_qualified_identifier(None, &trigger_table)
-        };
-        
-        let drop_statement = format!("DROP TRIGGER IF EXISTS {} ON {}", trigger_name, quoted_table);
-        client.execute(&drop_statement, &[]).await?;
+        let drop_statement = format!(
+            "DROP TRIGGER IF EXISTS {} ON {}",
+            quote_ident(&qualified_name.name),
+            quote_dotted(&trigger_table)
+        );
+        relations.push(trigger_table);
+        lock_retry.execute(client, &drop_statement, &relations).await?;
     } else {
         // Drop the object
         let drop_statement = generate_drop_statement(object_type, &qualified_name);
-        client.execute(&drop_statement, &[]).await?;
+        lock_retry.execute(client, &drop_statement, &relations).await?;
     }
     
     // Remove from state tracking
@@ -966,50 +2689,7 @@ async fn apply_delete_object<C: GenericClient>(
 
 
 fn generate_comment_null_statement_from_object(object: &SqlObject) -> Result<String, Box<dyn std::error::Error>> {
-    let comment_identifier = &object.qualified_name.name;
-    
-    // Parse comment identifiers like:
-    // "table:users" -> "COMMENT ON TABLE users IS NULL"
-    // "column:users.email" -> "COMMENT ON COLUMN users.email IS NULL"  
-    // "function:api.get_user" -> "COMMENT ON FUNCTION api.get_user IS NULL"
-    // "trigger:my_trigger:my_table" -> "COMMENT ON TRIGGER my_trigger ON my_table IS NULL"
-    // "aggregate:my_aggregate" -> "COMMENT ON AGGREGATE my_aggregate IS NULL"
-    
-    let parts: Vec<&str> = comment_identifier.split(':').collect();
-    
-    match parts.as_slice() {
-        ["table", name] => Ok(format!("COMMENT ON TABLE {} IS NULL", name)),
-        ["view", name] => Ok(format!("COMMENT ON VIEW {} IS NULL", name)),
-        ["materialized_view", name] => Ok(format!("COMMENT ON MATERIALIZED VIEW {} IS NULL", name)),
-        ["function", name] => {
-            // Since pgmg prevents function overloading, we can use the name without parentheses
-            // Remove any trailing () if present in the identifier
-            let func_name = name.trim_end_matches("()");
-            Ok(format!("COMMENT ON FUNCTION {} IS NULL", func_name))
-        }
-        ["type", name] => Ok(format!("COMMENT ON TYPE {} IS NULL", name)),
-        ["domain", name] => Ok(format!("COMMENT ON DOMAIN {} IS NULL", name)),
-        ["column", name] => Ok(format!("COMMENT ON COLUMN {} IS NULL", name)),
-        ["trigger", trigger_name, table_name] => {
-            Ok(format!("COMMENT ON TRIGGER {} ON {} IS NULL", trigger_name, table_name))
-        }
-        ["aggregate", name] => {
-            // Since pgmg prevents aggregate overloading, we can use the name without parentheses
-            let agg_name = name.trim_end_matches("()");
-            Ok(format!("COMMENT ON AGGREGATE {} IS NULL", agg_name))
-        }
-        ["procedure", name] => {
-            // Since pgmg prevents procedure overloading, we can use the name without parentheses
-            let proc_name = name.trim_end_matches("()");
-            Ok(format!("COMMENT ON PROCEDURE {} IS NULL", proc_name))
-        }
-        ["operator", name] => {
-            // Operators need their full signature, which should be stored in the comment identifier
-            // Format: operator:name(lefttype,righttype)
-            Ok(format!("COMMENT ON OPERATOR {} IS NULL", name))
-        }
-        _ => Err(format!("Unknown comment identifier format: {}", comment_identifier).into()),
-    }
+    generate_comment_null_statement(&object.qualified_name.name)
 }
 
 fn generate_comment_null_statement(comment_identifier: &str) -> Result<String, Box<dyn std::error::Error>> {
@@ -1023,29 +2703,29 @@ fn generate_comment_null_statement(comment_identifier: &str) -> Result<String, B
     let parts: Vec<&str> = comment_identifier.split(':').collect();
     
     match parts.as_slice() {
-        ["table", name] => Ok(format!("COMMENT ON TABLE {} IS NULL", name)),
-        ["view", name] => Ok(format!("COMMENT ON VIEW {} IS NULL", name)),
-        ["materialized_view", name] => Ok(format!("COMMENT ON MATERIALIZED VIEW {} IS NULL", name)),
+        ["table", name] => Ok(format!("COMMENT ON TABLE {} IS NULL", quote_dotted(name))),
+        ["view", name] => Ok(format!("COMMENT ON VIEW {} IS NULL", quote_dotted(name))),
+        ["materialized_view", name] => Ok(format!("COMMENT ON MATERIALIZED VIEW {} IS NULL", quote_dotted(name))),
         ["function", name] => {
             // Since pgmg prevents function overloading, we can use the name without parentheses
             let func_name = name.trim_end_matches("()");
-            Ok(format!("COMMENT ON FUNCTION {} IS NULL", func_name))
+            Ok(format!("COMMENT ON FUNCTION {} IS NULL", quote_signature(func_name)))
         }
-        ["type", name] => Ok(format!("COMMENT ON TYPE {} IS NULL", name)),
-        ["domain", name] => Ok(format!("COMMENT ON DOMAIN {} IS NULL", name)),
-        ["column", name] => Ok(format!("COMMENT ON COLUMN {} IS NULL", name)),
+        ["type", name] => Ok(format!("COMMENT ON TYPE {} IS NULL", quote_dotted(name))),
+        ["domain", name] => Ok(format!("COMMENT ON DOMAIN {} IS NULL", quote_dotted(name))),
+        ["column", name] => Ok(format!("COMMENT ON COLUMN {} IS NULL", quote_dotted(name))),
         ["trigger", trigger_name, table_name] => {
-            Ok(format!("COMMENT ON TRIGGER {} ON {} IS NULL", trigger_name, table_name))
+            Ok(format!("COMMENT ON TRIGGER {} ON {} IS NULL", quote_ident(trigger_name), quote_dotted(table_name)))
         }
         ["aggregate", name] => {
             // Since pgmg prevents aggregate overloading, we can use the name without parentheses
             let agg_name = name.trim_end_matches("()");
-            Ok(format!("COMMENT ON AGGREGATE {} IS NULL", agg_name))
+            Ok(format!("COMMENT ON AGGREGATE {} IS NULL", quote_signature(agg_name)))
         }
         ["procedure", name] => {
             // Since pgmg prevents procedure overloading, we can use the name without parentheses
             let proc_name = name.trim_end_matches("()");
-            Ok(format!("COMMENT ON PROCEDURE {} IS NULL", proc_name))
+            Ok(format!("COMMENT ON PROCEDURE {} IS NULL", quote_signature(proc_name)))
         }
         ["operator", name] => {
             // Operators need their full signature, which should be stored in the comment identifier
@@ -1056,6 +2736,21 @@ fn generate_comment_null_statement(comment_identifier: &str) -> Result<String, B
     }
 }
 
+/// Generate the statement that removes a security label, e.g.
+/// "anon:column:users.email" -> "SECURITY LABEL FOR anon ON COLUMN users.email IS NULL"
+fn generate_security_label_null_statement(label_identifier: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let (provider, target) = split_security_label_name(label_identifier);
+
+    // Security labels identify their target the same way comments do
+    let comment_null_statement = generate_comment_null_statement(target)?;
+    let target_clause = comment_null_statement.trim_start_matches("COMMENT ON ");
+
+    Ok(match provider {
+        Some(provider) => format!("SECURITY LABEL FOR {} ON {}", quote_ident(provider), target_clause),
+        None => format!("SECURITY LABEL ON {}", target_clause),
+    })
+}
+
 fn generate_drop_statement(object_type: &ObjectType, qualified_name: &crate::sql::QualifiedIdent) -> String {
     let object_type_str = match object_type {
         ObjectType::Table => "TABLE",
@@ -1071,12 +2766,19 @@ fn generate_drop_statement(object_type: &ObjectType, qualified_name: &crate::sql
         ObjectType::CronJob => "CRON_JOB",  // Will be handled specially
         ObjectType::Aggregate => "AGGREGATE",
         ObjectType::Operator => "OPERATOR",
+        ObjectType::SecurityLabel => "SECURITY LABEL",  // Set to NULL instead, see apply_drop_for_update
+        ObjectType::Statistics => "STATISTICS",
+        ObjectType::Extension => "EXTENSION",
+        ObjectType::Publication => "PUBLICATION",
+        ObjectType::Collation => "COLLATION",
+        ObjectType::TextSearchConfiguration => "TEXT SEARCH CONFIGURATION",
+        ObjectType::TextSearchDictionary => "TEXT SEARCH DICTIONARY",
+        ObjectType::OperatorClass => "OPERATOR CLASS",
+        ObjectType::OperatorFamily => "OPERATOR FAMILY",
+        ObjectType::Cast => "CAST",
     };
     
-    let full_name = match &qualified_name.schema {
-        Some(schema) => format!("{}.{}", schema, qualified_name.name),
-        None => qualified_name.name.clone(),
-    };
+    let full_name = quote_qualified_ident(qualified_name);
     
     // Note: Triggers are not handled here because DROP TRIGGER requires the table name
     // (e.g., DROP TRIGGER trigger_name ON table_name). Since this function only has
@@ -1095,13 +2797,11 @@ fn generate_drop_statement(object_type: &ObjectType, qualified_name: &crate::sql
         }
         ObjectType::CronJob => {
             // For cron jobs, we use cron.unschedule
-            format!("SELECT cron.unschedule('{}')", qualified_name.name)
+            format!("SELECT cron.unschedule({})", quote_literal(&qualified_name.name))
         }
-        ObjectType::Operator => {
-            // Operators need special handling as they require their signature
-            // For now, we'll use a simplified approach
-            // TODO: Store and retrieve operator signatures properly
-            format!("DROP {} IF EXISTS {}", object_type_str, full_name)
+        ObjectType::Cast => {
+            // Casts are named by their types, e.g. `(api.money AS pg_catalog.numeric)`
+            format!("DROP {} IF EXISTS {}", object_type_str, qualified_name.name)
         }
         _ => {
             format!("DROP {} IF EXISTS {}", object_type_str, full_name)
@@ -1114,6 +2814,8 @@ async fn update_object_hash<C: GenericClient>(
     object_type: &ObjectType,
     object_name: &crate::sql::QualifiedIdent,
     ddl_hash: &str,
+    ddl_source: &str,
+    tags: &[String],
 ) -> Result<(), Box<dyn std::error::Error>> {
     let object_type_str = match object_type {
         ObjectType::Table => "table",
@@ -1129,6 +2831,16 @@ async fn update_object_hash<C: GenericClient>(
         ObjectType::CronJob => "cron_job",
         ObjectType::Aggregate => "aggregate",
         ObjectType::Operator => "operator",
+        ObjectType::SecurityLabel => "security_label",
+        ObjectType::Statistics => "statistics",
+        ObjectType::Extension => "extension",
+        ObjectType::Publication => "publication",
+        ObjectType::Collation => "collation",
+        ObjectType::TextSearchConfiguration => "text_search_configuration",
+        ObjectType::TextSearchDictionary => "text_search_dictionary",
+        ObjectType::OperatorClass => "operator_class",
+        ObjectType::OperatorFamily => "operator_family",
+        ObjectType::Cast => "cast",
     };
 
     let qualified_name = match &object_name.schema {
@@ -1136,14 +2848,22 @@ async fn update_object_hash<C: GenericClient>(
         None => object_name.name.clone(),
     };
 
+    // Cron jobs keep what they schedule apart, for changing it with cron.alter_job
+    let cron_job = crate::sql::parse_cron_job(ddl_source).filter(|_| object_type == &ObjectType::CronJob);
+    let cron_schedule = cron_job.as_ref().map(|job| job.schedule.as_str());
+    let cron_command = cron_job.as_ref().map(|job| job.command.as_str());
+    // Operators are dropped by their argument types, see managed_operator_signatures
+    let operator_signature = operator_signature(object_type, ddl_source);
+
     client.execute(
         r#"
-        INSERT INTO pgmg.pgmg_state (object_type, object_name, ddl_hash) 
-        VALUES ($1, $2, $3)
+        INSERT INTO pgmg.pgmg_state (object_type, object_name, ddl_hash, ddl_source, tags, cron_schedule, cron_command, operator_signature, git_commit, git_dirty) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, nullif(current_setting('pgmg.git_commit', true), ''), nullif(current_setting('pgmg.git_dirty', true), '')::boolean)
         ON CONFLICT (object_type, object_name) 
-        DO UPDATE SET ddl_hash = $3, last_applied = NOW()
+        DO UPDATE SET ddl_hash = $3, ddl_source = $4, tags = $5, cron_schedule = $6, cron_command = $7, operator_signature = $8,
+            git_commit = EXCLUDED.git_commit, git_dirty = EXCLUDED.git_dirty, last_applied = NOW()
         "#,
-        &[&object_type_str, &qualified_name, &ddl_hash],
+        &[&object_type_str, &qualified_name, &ddl_hash, &ddl_source, &tags, &cron_schedule, &cron_command, &operator_signature],
     ).await?;
 
     Ok(())
@@ -1169,6 +2889,16 @@ async fn store_object_dependencies<C: GenericClient>(
         ObjectType::CronJob => "cron_job",
         ObjectType::Aggregate => "aggregate",
         ObjectType::Operator => "operator",
+        ObjectType::SecurityLabel => "security_label",
+        ObjectType::Statistics => "statistics",
+        ObjectType::Extension => "extension",
+        ObjectType::Publication => "publication",
+        ObjectType::Collation => "collation",
+        ObjectType::TextSearchConfiguration => "text_search_configuration",
+        ObjectType::TextSearchDictionary => "text_search_dictionary",
+        ObjectType::OperatorClass => "operator_class",
+        ObjectType::OperatorFamily => "operator_family",
+        ObjectType::Cast => "cast",
     };
 
     let qualified_name = match &object_name.schema {
@@ -1258,6 +2988,16 @@ async fn remove_object_from_state<C: GenericClient>(
         ObjectType::CronJob => "cron_job",
         ObjectType::Aggregate => "aggregate",
         ObjectType::Operator => "operator",
+        ObjectType::SecurityLabel => "security_label",
+        ObjectType::Statistics => "statistics",
+        ObjectType::Extension => "extension",
+        ObjectType::Publication => "publication",
+        ObjectType::Collation => "collation",
+        ObjectType::TextSearchConfiguration => "text_search_configuration",
+        ObjectType::TextSearchDictionary => "text_search_dictionary",
+        ObjectType::OperatorClass => "operator_class",
+        ObjectType::OperatorFamily => "operator_family",
+        ObjectType::Cast => "cast",
     };
 
     let qualified_name = match &object_name.schema {
@@ -1297,6 +3037,13 @@ async fn remove_object_from_state<C: GenericClient>(
                 "DELETE FROM pgmg.pgmg_state WHERE object_type = 'comment' AND object_name = $1",
                 &[&object_comment],
             ).await?;
+
+            // Remove security labels on the object and its columns (with or without a provider prefix)
+            client.execute(
+                "DELETE FROM pgmg.pgmg_state WHERE object_type = 'security_label' \
+                 AND (object_name LIKE $1 OR object_name = $2 OR object_name LIKE '%:' || $2)",
+                &[&format!("%{}%", column_comment_pattern), &object_comment],
+            ).await?;
         }
         ObjectType::Function | ObjectType::Procedure | ObjectType::Operator => {
             // Remove function/procedure/operator comments
@@ -1335,88 +3082,317 @@ async fn remove_object_from_state<C: GenericClient>(
     Ok(())
 }
 
-async fn get_object_oid<C: GenericClient>(
+/// Look up the OIDs of applied objects, with one query per object type.
+/// Objects without an OID of their own (triggers, comments, ...) and
+/// overloaded routines are left out.
+async fn get_object_oids<C: GenericClient>(
     client: &C,
-    object_type: &ObjectType,
-    qualified_name: &crate::sql::QualifiedIdent,
-) -> Result<u32, Box<dyn std::error::Error>> {
-    let (schema_name, object_name) = match &qualified_name.schema {
-        Some(s) => (s.as_str(), qualified_name.name.as_str()),
-        None => ("public", qualified_name.name.as_str()),
-    };
-    
+    objects: &[&SqlObject],
+) -> Result<HashMap<(ObjectType, String), u32>, Box<dyn std::error::Error>> {
+    let mut keys_by_type: HashMap<ObjectType, Vec<String>> = HashMap::new();
+    for object in objects {
+        keys_by_type.entry(object.object_type.clone())
+            .or_default()
+            .push(catalog_key(&object.qualified_name));
+    }
+
+    let mut oids = HashMap::new();
+    for (object_type, keys) in keys_by_type {
+        let Some(query) = oid_query(&object_type) else {
+            continue;
+        };
+
+        let rows = client.query(query, &[&keys]).await?;
+        let mut found: HashMap<String, Vec<u32>> = HashMap::new();
+        for row in &rows {
+            found.entry(row.get(0)).or_default().push(row.get(1));
+        }
+
+        for (key, key_oids) in found {
+            if let [oid] = key_oids[..] {
+                oids.insert((object_type.clone(), key), oid);
+            }
+        }
+    }
+
+    Ok(oids)
+}
+
+/// Catalog query returning the `schema.name` and OID of objects of a type
+/// whose `schema.name` is in `$1`
+pub(crate) fn oid_query(object_type: &ObjectType) -> Option<&'static str> {
     let query = match object_type {
         ObjectType::Table => {
-            "SELECT c.oid FROM pg_class c 
+            "SELECT n.nspname || '.' || c.relname, c.oid FROM pg_class c 
              JOIN pg_namespace n ON n.oid = c.relnamespace 
-             WHERE n.nspname = $1 AND c.relname = $2 AND c.relkind = 'r'"
+             WHERE n.nspname || '.' || c.relname = ANY($1) AND c.relkind = 'r'"
         }
         ObjectType::View => {
-            "SELECT c.oid FROM pg_class c 
+            "SELECT n.nspname || '.' || c.relname, c.oid FROM pg_class c 
              JOIN pg_namespace n ON n.oid = c.relnamespace 
-             WHERE n.nspname = $1 AND c.relname = $2 AND c.relkind = 'v'"
+             WHERE n.nspname || '.' || c.relname = ANY($1) AND c.relkind = 'v'"
         }
         ObjectType::MaterializedView => {
-            "SELECT c.oid FROM pg_class c 
+            "SELECT n.nspname || '.' || c.relname, c.oid FROM pg_class c 
+             JOIN pg_namespace n ON n.oid = c.relnamespace 
+             WHERE n.nspname || '.' || c.relname = ANY($1) AND c.relkind = 'm'"
+        }
+        ObjectType::Index => {
+            "SELECT n.nspname || '.' || c.relname, c.oid FROM pg_class c 
              JOIN pg_namespace n ON n.oid = c.relnamespace 
-             WHERE n.nspname = $1 AND c.relname = $2 AND c.relkind = 'm'"
+             WHERE n.nspname || '.' || c.relname = ANY($1) AND c.relkind = 'i'"
+        }
+        ObjectType::Statistics => {
+            "SELECT n.nspname || '.' || s.stxname, s.oid FROM pg_statistic_ext s 
+             JOIN pg_namespace n ON n.oid = s.stxnamespace 
+             WHERE n.nspname || '.' || s.stxname = ANY($1)"
         }
         ObjectType::Function => {
-            "SELECT p.oid FROM pg_proc p 
+            "SELECT n.nspname || '.' || p.proname, p.oid FROM pg_proc p 
              JOIN pg_namespace n ON n.oid = p.pronamespace 
-             WHERE n.nspname = $1 AND p.proname = $2 AND p.prokind = 'f'"
+             WHERE n.nspname || '.' || p.proname = ANY($1) AND p.prokind = 'f'"
         }
         ObjectType::Procedure => {
-            "SELECT p.oid FROM pg_proc p 
+            "SELECT n.nspname || '.' || p.proname, p.oid FROM pg_proc p 
+             JOIN pg_namespace n ON n.oid = p.pronamespace 
+             WHERE n.nspname || '.' || p.proname = ANY($1) AND p.prokind = 'p'"
+        }
+        ObjectType::Aggregate => {
+            "SELECT n.nspname || '.' || p.proname, p.oid FROM pg_proc p 
              JOIN pg_namespace n ON n.oid = p.pronamespace 
-             WHERE n.nspname = $1 AND p.proname = $2 AND p.prokind = 'p'"
+             WHERE n.nspname || '.' || p.proname = ANY($1) AND p.prokind = 'a'"
         }
         ObjectType::Type => {
-            "SELECT t.oid FROM pg_type t 
+            "SELECT n.nspname || '.' || t.typname, t.oid FROM pg_type t 
              JOIN pg_namespace n ON n.oid = t.typnamespace 
-             WHERE n.nspname = $1 AND t.typname = $2 
+             WHERE n.nspname || '.' || t.typname = ANY($1) 
              AND t.typtype IN ('c', 'e')"
         }
         ObjectType::Domain => {
-            "SELECT t.oid FROM pg_type t 
+            "SELECT n.nspname || '.' || t.typname, t.oid FROM pg_type t 
              JOIN pg_namespace n ON n.oid = t.typnamespace 
-             WHERE n.nspname = $1 AND t.typname = $2 
+             WHERE n.nspname || '.' || t.typname = ANY($1) 
              AND t.typtype = 'd'"
         }
-        ObjectType::Index => {
-            "SELECT c.oid FROM pg_class c 
-             JOIN pg_namespace n ON n.oid = c.relnamespace 
-             WHERE n.nspname = $1 AND c.relname = $2 AND c.relkind = 'i'"
-        }
-        ObjectType::Trigger => {
-            // Triggers don't have their own OID in pg_class, they're in pg_trigger
-            // This requires both trigger name and table name, which is complex
-            // For now, we'll return an error for triggers
-            return Err("Trigger OID lookup not yet implemented".into());
-        }
-        ObjectType::Comment => {
-            // Comments don't have OIDs - they're metadata attached to objects
-            return Err("Comment OID lookup not applicable".into());
-        }
-        ObjectType::CronJob => {
-            // Cron jobs are stored in the cron.job table, not in pg_catalog
-            return Err("Cron job OID lookup not yet implemented".into());
-        }
-        ObjectType::Aggregate => {
-            "SELECT p.oid FROM pg_proc p 
-             JOIN pg_namespace n ON n.oid = p.pronamespace 
-             WHERE n.nspname = $1 AND p.proname = $2 AND p.prokind = 'a'"
-        }
         ObjectType::Operator => {
-            "SELECT o.oid FROM pg_operator o 
+            "SELECT n.nspname || '.' || o.oprname, o.oid FROM pg_operator o 
              JOIN pg_namespace n ON n.oid = o.oprnamespace 
-             WHERE n.nspname = $1 AND o.oprname = $2"
-        }
+             WHERE n.nspname || '.' || o.oprname = ANY($1)"
+        }
+        ObjectType::Collation => {
+            "SELECT n.nspname || '.' || c.collname, c.oid FROM pg_collation c 
+             JOIN pg_namespace n ON n.oid = c.collnamespace 
+             WHERE n.nspname || '.' || c.collname = ANY($1)"
+        }
+        ObjectType::TextSearchConfiguration => {
+            "SELECT n.nspname || '.' || c.cfgname, c.oid FROM pg_ts_config c 
+             JOIN pg_namespace n ON n.oid = c.cfgnamespace 
+             WHERE n.nspname || '.' || c.cfgname = ANY($1)"
+        }
+        ObjectType::TextSearchDictionary => {
+            "SELECT n.nspname || '.' || d.dictname, d.oid FROM pg_ts_dict d 
+             JOIN pg_namespace n ON n.oid = d.dictnamespace 
+             WHERE n.nspname || '.' || d.dictname = ANY($1)"
+        }
+        ObjectType::OperatorClass => {
+            "SELECT n.nspname || '.' || c.opcname, c.oid FROM pg_opclass c 
+             JOIN pg_namespace n ON n.oid = c.opcnamespace 
+             WHERE n.nspname || '.' || c.opcname = ANY($1)"
+        }
+        ObjectType::OperatorFamily => {
+            "SELECT n.nspname || '.' || f.opfname, f.oid FROM pg_opfamily f 
+             JOIN pg_namespace n ON n.oid = f.opfnamespace 
+             WHERE n.nspname || '.' || f.opfname = ANY($1)"
+        }
+        // Triggers live in pg_trigger and need their table to be identified;
+        // comments and security labels are attached to other objects, cron
+        // jobs are rows in cron.job, and extensions and publications aren't
+        // in a schema, and casts are named by their types
+        ObjectType::Trigger | ObjectType::Comment | ObjectType::SecurityLabel | ObjectType::CronJob | ObjectType::Extension | ObjectType::Publication
+        | ObjectType::Cast => return None,
     };
-    
-    let row = client.query_one(query, &[&schema_name, &object_name]).await?;
-    let oid: u32 = row.get(0);
-    Ok(oid)
+    Some(query)
+}
+
+/// `schema.name` as it appears in the catalogs, for matching against `= ANY($1)`
+fn catalog_key(qualified_name: &crate::sql::QualifiedIdent) -> String {
+    format!("{}.{}", qualified_name.schema.as_deref().unwrap_or("public"), qualified_name.name)
+}
+
+/// How a recreated materialized view is refreshed: its `-- pgmg:refresh`
+/// directive, or else `matview_refresh`
+fn refresh_strategy(object: &SqlObject, config: &PgmgConfig) -> Result<RefreshStrategy, String> {
+    match object.directives.refresh {
+        Some(strategy) => Ok(strategy),
+        None => config.matview_refresh_strategy(),
+    }
+}
+
+/// Refresh recreated materialized views that were created WITH NO DATA,
+/// unless they're to be left alone, so they aren't committed empty. Runs after
+/// every object is created, so their indexes exist.
+async fn refresh_recreated_matviews<C: GenericClient>(
+    client: &C,
+    apply_result: &mut ApplyResult,
+    matviews: &[&SqlObject],
+    config: &PgmgConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for object in matviews {
+        let name = quote_qualified_ident(&object.qualified_name);
+        let populated: bool = client.query_one(
+            "SELECT relispopulated FROM pg_class WHERE oid = to_regclass($1)",
+            &[&name],
+        ).await?.get(0);
+
+        let status = match refresh_strategy(object, config)? {
+            _ if populated => MatviewRefreshStatus::Populated,
+            RefreshStrategy::Skip => {
+                warn!(matview = %format_object_name(object), "Recreated materialized view is empty until it is refreshed");
+                MatviewRefreshStatus::LeftEmpty
+            }
+            // CONCURRENTLY can't fill an empty view
+            RefreshStrategy::Refresh | RefreshStrategy::Concurrently => {
+                client.batch_execute(&format!("REFRESH MATERIALIZED VIEW {}", name)).await
+                    .map_err(|e| format!("Failed to refresh materialized view {}: {}", format_object_name(object), e))?;
+                debug!(matview = %format_object_name(object), "Refreshed recreated materialized view");
+                MatviewRefreshStatus::Refreshed
+            }
+        };
+        apply_result.matview_refreshes.push(MatviewRefresh { name: format_object_name(object), status });
+    }
+
+    Ok(())
+}
+
+/// Refresh recreated views set to `concurrently` that were created WITH DATA
+/// once more after the commit, picking up rows other sessions wrote while the
+/// apply ran without blocking readers. Everything has committed by now, so a
+/// failure (e.g. no unique index) is reported rather than failing the apply.
+async fn refresh_matviews_concurrently<C: GenericClient>(
+    client: &C,
+    apply_result: &mut ApplyResult,
+    plan_result: &PlanResult,
+    config: &PgmgConfig,
+) {
+    for change in &plan_result.changes {
+        let ChangeOperation::UpdateObject { object, .. } = change else { continue };
+        if refresh_strategy(object, config) != Ok(RefreshStrategy::Concurrently) {
+            continue;
+        }
+        let name = format_object_name(object);
+        let Some(refresh) = apply_result.matview_refreshes.iter_mut()
+            .find(|refresh| refresh.name == name && refresh.status == MatviewRefreshStatus::Populated) else {
+            continue;
+        };
+
+        let sql = format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}", quote_qualified_ident(&object.qualified_name));
+        refresh.status = match client.batch_execute(&sql).await {
+            Ok(()) => MatviewRefreshStatus::RefreshedConcurrently,
+            Err(e) => {
+                warn!(matview = %name, error = %e, "Concurrent refresh after apply failed");
+                MatviewRefreshStatus::Failed(e.to_string())
+            }
+        };
+    }
+}
+
+/// Emit an object_loaded NOTIFY event for each applied object when running in
+/// development mode. Failures are logged but never fail the apply.
+async fn emit_loaded_notifications<C: GenericClient>(
+    client: &C,
+    objects: &[&SqlObject],
+    config: &PgmgConfig,
+) {
+    if objects.is_empty() || !config.development_mode.unwrap_or(false) || !config.emit_notify_events.unwrap_or(false) {
+        return;
+    }
+
+    let oids = get_object_oids(client, objects).await.unwrap_or_else(|e| {
+        warn!(error = %e, "Failed to look up OIDs of applied objects");
+        HashMap::new()
+    });
+
+    for object in objects {
+        let mut notification = ObjectLoadedNotification::from_sql_object(object);
+        notification.oid = oids.get(&(object.object_type.clone(), catalog_key(&object.qualified_name))).copied();
+
+        if let Err(e) = emit_object_loaded_notification(client, &notification).await {
+            warn!(error = %e, "Failed to emit NOTIFY event");
+        }
+    }
+}
+
+/// Run the integration actions configured in pgmg.toml, e.g. telling
+/// PostgREST to reload, once everything is committed. The changes are in by
+/// then, so a failed action is only a warning.
+async fn run_integration_actions<C: GenericClient>(
+    client: &C,
+    apply_result: &ApplyResult,
+    plan_result: &PlanResult,
+    actions: &[IntegrationAction],
+    schemas: &SchemaFilter,
+    test_mode: bool,
+) {
+    if test_mode || !changed_anything(apply_result) {
+        return;
+    }
+    if !schemas.is_empty() && !changes_schemas(plan_result, schemas) {
+        debug!("No changes in the integration schemas, skipping integration actions");
+        return;
+    }
+
+    for action in actions {
+        match action.run(client).await {
+            Ok(()) => info!(action = %action, "Ran integration action"),
+            Err(e) => warn!(action = %action, error = %e, "Integration action failed"),
+        }
+    }
+}
+
+/// The `[hooks]` to run; none in test mode, like integration actions
+fn configured_hooks(config: &PgmgConfig, test_mode: bool) -> Hooks {
+    if test_mode {
+        Hooks::default()
+    } else {
+        config.hooks()
+    }
+}
+
+/// Run `post_apply` hooks if the apply changed something. The changes are
+/// committed, so a failing hook is only warned about.
+async fn run_post_apply_hooks<C: GenericClient>(client: &C, apply_result: &ApplyResult, config_hooks: &Hooks) {
+    if !changed_anything(apply_result) {
+        return;
+    }
+
+    if let Err(e) = config_hooks.run(HookEvent::PostApply, client, &HookContext::applied(apply_result)).await {
+        warn!(error = %e, "post_apply hook failed");
+    }
+}
+
+fn changed_anything(apply_result: &ApplyResult) -> bool {
+    !apply_result.migrations_applied.is_empty()
+        || !apply_result.objects_created.is_empty()
+        || !apply_result.objects_updated.is_empty()
+        || !apply_result.objects_deleted.is_empty()
+}
+
+/// Whether the plan runs a migration, which may change any schema, or changes
+/// an object or enum type in one of `schemas`
+fn changes_schemas(plan_result: &PlanResult, schemas: &SchemaFilter) -> bool {
+    !plan_result.new_migrations.is_empty()
+        || plan_result.enum_additions.iter().any(|addition| {
+            schemas.includes(&addition.object.object_type, &addition.object.qualified_name, None)
+        })
+        || plan_result.changes.iter().any(|change| match change {
+            ChangeOperation::CreateObject { object, .. } | ChangeOperation::UpdateObject { object, .. } => {
+                schemas.includes(&object.object_type, &object.qualified_name, Some(&object.ddl_statement))
+            }
+            ChangeOperation::DeleteObject { object_type, object_name, .. } => {
+                schemas.includes(object_type, &crate::sql::QualifiedIdent::from_qualified_name(object_name), None)
+            }
+            ChangeOperation::ApplyMigration { .. } => false,
+        })
 }
 
 /// Print the appropriate success message based on SQL and plpgsql_check results
@@ -1441,6 +3417,22 @@ fn print_apply_success_message(result: &ApplyResult, test_mode: bool) {
     }
 }
 
+/// Key identifying an object across the apply phases, e.g. `View:api.users`
+fn object_key(object: &SqlObject) -> String {
+    format!("{:?}:{}", object.object_type, format_object_name(object))
+}
+
+/// Relations a statement for this object may have to lock: the object itself
+/// and the tables and views it references
+fn locked_relations(object: &SqlObject) -> Vec<String> {
+    std::iter::once(format_object_name(object))
+        .chain(object.dependencies.relations.iter().map(|relation| match &relation.schema {
+            Some(schema) => format!("{}.{}", schema, relation.name),
+            None => relation.name.clone(),
+        }))
+        .collect()
+}
+
 fn format_object_name(object: &SqlObject) -> String {
     match &object.qualified_name.schema {
         Some(schema) => format!("{}.{}", schema, object.qualified_name.name),
@@ -1500,36 +3492,92 @@ async fn get_existing_function_signatures<C: GenericClient>(
     object_type: &ObjectType,
     qualified_name: &crate::sql::QualifiedIdent,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let (schema_name, function_name) = match &qualified_name.schema {
-        Some(s) => (s.as_str(), qualified_name.name.as_str()),
-        None => ("public", qualified_name.name.as_str()),
+    let rows = query_existing_signatures(client, object_type, &[catalog_key(qualified_name)]).await?;
+    Ok(rows.into_iter().map(|(_, signature)| signature).collect())
+}
+
+/// The signatures to drop a managed operator by: the one stored when it was
+/// last applied, and the one `ddl` defines if its argument types changed.
+/// Other operators of the same name, for other types, are left alone.
+async fn managed_operator_signatures<C: GenericClient>(
+    client: &C,
+    qualified_name: &crate::sql::QualifiedIdent,
+    ddl: Option<&str>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let object_name = match &qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, qualified_name.name),
+        None => qualified_name.name.clone(),
     };
-    
+    let row = client.query_opt(
+        "SELECT operator_signature FROM pgmg.pgmg_state WHERE object_type = 'operator' AND object_name = $1",
+        &[&object_name],
+    ).await?;
+
+    let mut signatures: Vec<String> = row.and_then(|row| row.get(0)).into_iter().collect();
+    if let Some(signature) = ddl.and_then(|ddl| operator_signature(&ObjectType::Operator, ddl)) {
+        if !signatures.contains(&signature) {
+            signatures.push(signature);
+        }
+    }
+    Ok(signatures)
+}
+
+/// Existing overloads of routines or operators of one type, as
+/// `(schema.name, signature)` pairs for every `schema.name` in `keys`. The
+/// signature of an operator class or family is its name and index method,
+/// e.g. `api.point_ops USING gist`.
+async fn query_existing_signatures<C: GenericClient>(
+    client: &C,
+    object_type: &ObjectType,
+    keys: &[String],
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let opclass_query = match object_type {
+        ObjectType::OperatorClass => Some(
+            "SELECT n.nspname || '.' || c.opcname,
+                    format('%I.%I USING %I', n.nspname, c.opcname, am.amname)
+             FROM pg_opclass c
+             JOIN pg_namespace n ON n.oid = c.opcnamespace
+             JOIN pg_am am ON am.oid = c.opcmethod
+             WHERE n.nspname || '.' || c.opcname = ANY($1)"
+        ),
+        ObjectType::OperatorFamily => Some(
+            "SELECT n.nspname || '.' || f.opfname,
+                    format('%I.%I USING %I', n.nspname, f.opfname, am.amname)
+             FROM pg_opfamily f
+             JOIN pg_namespace n ON n.oid = f.opfnamespace
+             JOIN pg_am am ON am.oid = f.opfmethod
+             WHERE n.nspname || '.' || f.opfname = ANY($1)"
+        ),
+        _ => None,
+    };
+    if let Some(query) = opclass_query {
+        let rows = client.query(query, &[&keys]).await?;
+        return Ok(rows.iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+            .collect());
+    }
+
     // Handle operators separately as they use pg_operator, not pg_proc
     if object_type == &ObjectType::Operator {
         let query = r#"
             SELECT 
+                n.nspname || '.' || o.oprname AS object_key,
                 CASE 
                     WHEN n.nspname = 'public' THEN o.oprname
                     ELSE n.nspname || '.' || o.oprname
                 END || '(' || 
-                COALESCE(tl.typname, 'NONE') || ', ' || 
-                COALESCE(tr.typname, 'NONE') || ')' AS signature
+                CASE WHEN o.oprleft = 0 THEN 'NONE' ELSE format_type(o.oprleft, NULL) END || ', ' || 
+                CASE WHEN o.oprright = 0 THEN 'NONE' ELSE format_type(o.oprright, NULL) END || ')' AS signature
             FROM pg_operator o
             JOIN pg_namespace n ON n.oid = o.oprnamespace
-            LEFT JOIN pg_type tl ON tl.oid = o.oprleft
-            LEFT JOIN pg_type tr ON tr.oid = o.oprright
-            WHERE n.nspname = $1 
-              AND o.oprname = $2
+            WHERE n.nspname || '.' || o.oprname = ANY($1)
         "#;
         
-        let rows = client.query(query, &[&schema_name, &function_name]).await?;
-        
-        let signatures: Vec<String> = rows.iter()
-            .map(|row| row.get::<_, String>(0))
-            .collect();
+        let rows = client.query(query, &[&keys]).await?;
         
-        return Ok(signatures);
+        return Ok(rows.iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+            .collect());
     }
     
     let prokind: &str = match object_type {
@@ -1539,66 +3587,103 @@ async fn get_existing_function_signatures<C: GenericClient>(
         _ => return Ok(vec![]),
     };
     
-    // Query to get all overloads of a function with their full signatures
+    // Query to get all overloads of the functions with their full signatures
     let query = r#"
         SELECT 
+            n.nspname || '.' || p.proname AS object_key,
             CASE 
                 WHEN n.nspname = 'public' THEN p.proname
                 ELSE n.nspname || '.' || p.proname
             END || '(' || pg_get_function_identity_arguments(p.oid) || ')' AS signature
         FROM pg_proc p
         JOIN pg_namespace n ON n.oid = p.pronamespace
-        WHERE n.nspname = $1 
-          AND p.proname = $2
-          AND p.prokind = $3::char
+        WHERE n.nspname || '.' || p.proname = ANY($1)
+          AND p.prokind = $2::char
     "#;
     
-    let rows = client.query(query, &[&schema_name, &function_name, &prokind]).await?;
+    let rows = client.query(query, &[&keys, &prokind]).await?;
     
-    let signatures: Vec<String> = rows.iter()
-        .map(|row| row.get::<_, String>(0))
-        .collect();
-    
-    Ok(signatures)
+    Ok(rows.iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+        .collect())
 }
 
-
 #[cfg(feature = "cli")]
 pub fn print_apply_summary(result: &ApplyResult) {
-    println!("\n{}", "=== PGMG Apply Summary ===".bold().blue());
+    use console::style;
+
+    if !result.errors.is_empty() {
+        // Errors are reported even in quiet mode
+        for error in &result.errors {
+            output::error(error);
+        }
+    }
+
+    if output::is_quiet() {
+        return;
+    }
+
+    output::line(format!("\n{}", style("=== PGMG Apply Summary ===").bold().blue()));
     
     if !result.migrations_applied.is_empty() {
-        println!("\n{}:", "Migrations Applied".bold().green());
+        output::line(format!("\n{}:", style("Migrations Applied").bold().green()));
         for migration in &result.migrations_applied {
-            println!("  {} {}", "✓".green().bold(), migration.cyan());
+            output::line(format!("  {} {}", style(output::symbol("✓")).green().bold(), style(migration).cyan()));
         }
     }
     
     if !result.objects_created.is_empty() {
-        println!("\n{}:", "Objects Created".bold().green());
+        output::line(format!("\n{}:", style("Objects Created").bold().green()));
         for object in &result.objects_created {
-            println!("  {} {}", "+".green().bold(), object.cyan());
+            output::line(format!("  {} {}", style("+").green().bold(), style(object).cyan()));
         }
     }
     
     if !result.objects_updated.is_empty() {
-        println!("\n{}:", "Objects Updated".bold().yellow());
+        output::line(format!("\n{}:", style("Objects Updated").bold().yellow()));
         for object in &result.objects_updated {
-            println!("  {} {}", "~".yellow().bold(), object.cyan());
+            output::line(format!("  {} {}", style("~").yellow().bold(), style(object).cyan()));
         }
     }
     
     if !result.objects_deleted.is_empty() {
-        println!("\n{}:", "Objects Deleted".bold().red());
+        output::line(format!("\n{}:", style("Objects Deleted").bold().red()));
         for object in &result.objects_deleted {
-            println!("  {} {}", "-".red().bold(), object.cyan());
+            output::line(format!("  {} {}", style("-").red().bold(), style(object).cyan()));
         }
     }
     
-    if !result.errors.is_empty() {
-        println!("\n{}:", "Errors".bold().red());
-        for error in &result.errors {
-            println!("  {} {}", "✗".red().bold(), error.red());
+    if !result.matview_refreshes.is_empty() {
+        output::line(format!("\n{}:", style("Materialized View Data").bold()));
+        for refresh in &result.matview_refreshes {
+            let status = match &refresh.status {
+                MatviewRefreshStatus::Populated => style("populated on creation".to_string()).green(),
+                MatviewRefreshStatus::Refreshed => style("refreshed".to_string()).green(),
+                MatviewRefreshStatus::RefreshedConcurrently => style("refreshed concurrently after commit".to_string()).green(),
+                MatviewRefreshStatus::LeftEmpty => style("empty until refreshed (set matview_refresh)".to_string()).yellow(),
+                MatviewRefreshStatus::Failed(e) => style(format!("concurrent refresh failed: {}", e)).red(),
+            };
+            output::line(format!("  {} {}", style(&refresh.name).cyan(), status));
+        }
+    }
+    
+    if let Some(backup_path) = &result.backup_path {
+        output::line(format!("\n{}: {}", style("Backup").bold(), style(backup_path.display()).cyan()));
+    }
+    
+    if !result.skipped.is_empty() {
+        output::line(format!("\n{}:", style("Skipped Statements").bold().dim()));
+        for skipped in &result.skipped {
+            let location = match (&skipped.file, skipped.line) {
+                (Some(file), Some(line)) => format!("{}:{}", file.display(), line),
+                (Some(file), None) => file.display().to_string(),
+                _ => String::new(),
+            };
+            output::line(format!("  {} {} {}",
+                style("-").dim(),
+                style(&skipped.statement).cyan(),
+                style(format!("({}) {}", skipped.reason, location)).dim()
+            ));
         }
     }
     
@@ -1608,64 +3693,52 @@ pub fn print_apply_summary(result: &ApplyResult) {
                        result.objects_deleted.len();
     
     if total_changes == 0 && result.errors.is_empty() {
-        println!("\n{}", "No changes applied. Database was already up to date.".green());
+        output::line(format!("\n{}", style("No changes applied. Database was already up to date.").green()));
     } else if result.errors.is_empty() {
         if result.plpgsql_errors_found > 0 {
-            println!("\n{} {} {} {} {}", 
-                "✓".yellow().bold(), 
-                "Applied".yellow().bold(),
-                format!("{} changes", total_changes).yellow(),
-                "with".yellow().bold(),
-                "PL/pgSQL errors".red().bold()
-            );
+            output::line(format!("\n{} {} {} {} {}", 
+                style(output::symbol("✓")).yellow().bold(), 
+                style("Applied").yellow().bold(),
+                style(format!("{} changes", total_changes)).yellow(),
+                style("with").yellow().bold(),
+                style("PL/pgSQL errors").red().bold()
+            ));
         } else if result.plpgsql_warnings_found > 0 {
-            println!("\n{} {} {} {} {}", 
-                "✓".yellow().bold(), 
-                "Applied".yellow().bold(),
-                format!("{} changes", total_changes).yellow(),
-                "with".yellow().bold(),
-                "PL/pgSQL warnings".yellow().bold()
-            );
+            output::line(format!("\n{} {} {} {} {}", 
+                style(output::symbol("✓")).yellow().bold(), 
+                style("Applied").yellow().bold(),
+                style(format!("{} changes", total_changes)).yellow(),
+                style("with").yellow().bold(),
+                style("PL/pgSQL warnings").yellow().bold()
+            ));
         } else {
-            println!("\n{} {} {}", 
-                "✓".green().bold(), 
-                "Successfully applied".green().bold(), 
-                format!("{} changes", total_changes).yellow()
-            );
+            output::line(format!("\n{} {} {}", 
+                style(output::symbol("✓")).green().bold(), 
+                style("Successfully applied").green().bold(), 
+                style(format!("{} changes", total_changes)).yellow()
+            ));
         }
     } else {
-        println!("\n{} {} {}", 
-            "✗".red().bold(), 
-            "Apply failed with".red().bold(), 
-            format!("{} errors", result.errors.len()).yellow()
-        );
+        output::line(format!("\n{} {} {}", 
+            style(output::symbol("✗")).red().bold(), 
+            style("Apply failed with").red().bold(), 
+            style(format!("{} errors", result.errors.len())).yellow()
+        ));
     }
     
     // Show plpgsql_check summary if there were any issues
     if result.plpgsql_errors_found > 0 || result.plpgsql_warnings_found > 0 {
-        println!();
-        println!("{}:", "PL/pgSQL Check Results".bold().yellow());
+        output::line("");
+        output::line(format!("{}:", style("PL/pgSQL Check Results").bold().yellow()));
         if result.plpgsql_errors_found > 0 {
-            println!("  {} {} errors found", "✗".red(), result.plpgsql_errors_found.to_string().red().bold());
+            output::line(format!("  {} {} errors found", style(output::symbol("✗")).red(), style(result.plpgsql_errors_found).red().bold()));
         }
         if result.plpgsql_warnings_found > 0 {
-            println!("  {} {} warnings found", "⚠".yellow(), result.plpgsql_warnings_found.to_string().yellow().bold());
+            output::line(format!("  {} {} warnings found", style(output::symbol("⚠")).yellow(), style(result.plpgsql_warnings_found).yellow().bold()));
         }
     }
 }
 
-// Helper function to quote identifiers properly
-fn quote_qualified_identifier(schema: Option<&str>, name: &str) -> String {
-    match schema {
-        Some(s) => format!("{}.{}", quote_identifier(s), quote_identifier(name)),
-        None => quote_identifier(name),
-    }
-}
-
-fn quote_identifier(name: &str) -> String {
-    format!("\"{}\"", name.replace("\"", "\"\""))
-}
-
 /// Check if we're running on AWS RDS by looking for the rdsadmin database
 async fn is_aws_rds<C: GenericClient>(client: &C) -> bool {
     match client.query_one(
@@ -1677,43 +3750,22 @@ async fn is_aws_rds<C: GenericClient>(client: &C) -> bool {
     }
 }
 
-/// Check if a SQL statement should be skipped in test mode
-fn should_skip_in_test_mode(sql: &str) -> bool {
-    let sql_lower = sql.to_lowercase();
-    
-    // Skip pg_cron extension creation
-    if sql_lower.contains("create extension") && sql_lower.contains("pg_cron") {
-        return true;
-    }
-    
-    // Skip comments on pg_cron extension
-    if sql_lower.contains("comment on extension") && sql_lower.contains("pg_cron") {
-        return true;
-    }
-    
-    // Skip cron.schedule calls
-    if sql_lower.contains("cron.schedule") {
-        return true;
-    }
-    
-    // Skip cron.unschedule calls
-    if sql_lower.contains("cron.unschedule") {
-        return true;
-    }
-    
-    false
-}
-
-/// Check if a SQL statement is related to plpgsql_check and should be skipped on RDS
-fn should_skip_plpgsql_check_on_rds(sql: &str) -> bool {
-    let sql_lower = sql.to_lowercase();
-
-    // Skip plpgsql_check extension creation
-    if sql_lower.contains("plpgsql_check") {
-        return true;
-    }
+/// Record `sql` as skipped if one of the skip rules matches it
+fn skip_statement(
+    skip_rules: &SkipRules,
+    sql: &str,
+    file: Option<&Path>,
+    line: Option<usize>,
+    skipped: &mut Vec<SkippedStatement>,
+) -> bool {
+    let Some(rule) = skip_rules.matching(sql) else {
+        return false;
+    };
 
-    false
+    let statement = SkippedStatement::new(&rule.reason, file, line, sql);
+    debug!(statement = %statement.statement, reason = %rule.reason, "Skipping statement");
+    skipped.push(statement);
+    true
 }
 
 /// Helper to order changes by deletion order from dependency graph
@@ -0,0 +1,110 @@
+use std::time::SystemTime;
+use crate::analysis::SearchPath;
+use crate::analysis::search_path::object_key;
+use crate::commands::show::format_qualified_name;
+use crate::db::{connect_with_url, RunRecord, StateManager};
+use crate::sql::{ObjectType, QualifiedIdent};
+
+#[derive(Debug, Clone)]
+pub struct BlamedObject {
+    pub object_type: ObjectType,
+    pub object_name: String,
+    pub last_applied: SystemTime,
+    /// The git commit the object was last applied from
+    pub git_commit: Option<String>,
+    /// Whether the code files had uncommitted changes when it was applied
+    pub git_dirty: Option<bool>,
+    /// The recorded apply run that last changed the object
+    pub run: Option<RunRecord>,
+}
+
+#[derive(Debug)]
+pub struct BlameResult {
+    pub target: String,
+    pub objects: Vec<BlamedObject>,
+}
+
+/// Find which commit and apply run last changed a managed object, from the
+/// commit stamped on its pgmg_state row and the pgmg_runs entry that was
+/// running when it was applied.
+///
+/// Objects last applied by a pgmg that didn't record commits or runs, or
+/// from outside a git repository, have neither. Unqualified names are looked
+/// up through `search_path`.
+pub async fn execute_blame(
+    target: String,
+    connection_string: String,
+    search_path: &SearchPath,
+) -> Result<BlameResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let state_manager = StateManager::new(&client);
+    state_manager.initialize().await?;
+
+    let tracked = state_manager.get_tracked_objects().await?;
+    let known: Vec<QualifiedIdent> = tracked.iter().map(|record| record.object_name.clone()).collect();
+    let target_key = search_path.qualify(&target, &known);
+
+    let mut objects = Vec::new();
+    for record in tracked {
+        if object_key(&record.object_name) != target_key {
+            continue;
+        }
+        let object_name = format_qualified_name(&record.object_name);
+
+        objects.push(BlamedObject {
+            object_type: record.object_type,
+            object_name,
+            last_applied: record.last_applied,
+            git_commit: record.git_commit,
+            git_dirty: record.git_dirty,
+            run: state_manager.get_run_at(record.last_applied).await?,
+        });
+    }
+
+    if objects.is_empty() {
+        return Err(format!("No managed object named {}", target).into());
+    }
+
+    Ok(BlameResult { target, objects })
+}
+
+#[cfg(feature = "cli")]
+pub fn print_blame_summary(result: &BlameResult) {
+    use crate::logging::output;
+    use chrono::{DateTime, Local};
+    use console::style;
+
+    for obj in &result.objects {
+        output::line(format!("\n{} {}",
+            style(obj.object_type.to_string().to_lowercase()).dim(),
+            style(&obj.object_name).cyan().bold()
+        ));
+
+        let last_applied: DateTime<Local> = obj.last_applied.into();
+        output::line(format!("  {:<14}{}", "Last applied:", last_applied.format("%Y-%m-%d %H:%M:%S %Z")));
+
+        match (&obj.git_commit, obj.git_dirty) {
+            (Some(commit), Some(true)) => output::line(format!("  {:<14}{} {}",
+                "Commit:", commit, style("(with uncommitted changes)").yellow())),
+            (Some(commit), _) => output::line(format!("  {:<14}{}", "Commit:", commit)),
+            (None, _) => output::line(format!("  {:<14}{}", "Commit:", style("not recorded").dim())),
+        }
+
+        match &obj.run {
+            Some(run) => {
+                let by = match &run.os_user {
+                    Some(os_user) => format!("{} ({})", run.db_user, os_user),
+                    None => run.db_user.clone(),
+                };
+                let environment = run.environment.as_ref()
+                    .map(|environment| format!(" in {}", environment))
+                    .unwrap_or_default();
+                output::line(format!("  {:<14}#{} by {}{}, pgmg {}",
+                    "Apply run:", run.id, by, environment, run.pgmg_version));
+            }
+            None => output::line(format!("  {:<14}{}", "Apply run:", style("not recorded").dim())),
+        }
+    }
+}
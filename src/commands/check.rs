@@ -1,7 +1,11 @@
 use crate::db::{connect_with_url, scan_sql_files};
-use crate::plpgsql_check::{check_all_functions, is_plpgsql_check_available, resolve_source_location, PlpgsqlCheckError, display_check_errors};
+use crate::commands::apply::apply_uncommitted;
+use crate::config::PgmgConfig;
+use crate::plpgsql_check::{check_all_functions, is_plpgsql_check_available, resolve_source_location, find_source_object, PlpgsqlCheckError, PlpgsqlCheckResult, format_check_errors, CheckPolicy};
+use crate::sql::SqlObject;
 use crate::BuiltinCatalog;
 use owo_colors::OwoColorize;
+use crate::logging::output;
 use std::path::PathBuf;
 use std::time::Instant;
 
@@ -10,6 +14,12 @@ pub struct CheckResult {
     pub functions_checked: usize,
     pub errors_found: usize,
     pub warnings_found: usize,
+    /// Findings at or above the policy's `fail_on` level
+    pub failures: usize,
+    /// Findings left out by the ignore list or ignore-check directives
+    pub ignored: usize,
+    /// Warnings counted but not listed, with `--errors-only`
+    pub hidden_warnings: usize,
     pub check_errors: Vec<PlpgsqlCheckError>,
     pub duration: std::time::Duration,
 }
@@ -20,6 +30,7 @@ pub async fn execute_check(
     schemas: Option<Vec<String>>,
     errors_only: bool,
     code_dir: Option<PathBuf>,
+    policy: &CheckPolicy,
 ) -> Result<CheckResult, Box<dyn std::error::Error>> {
     let start_time = Instant::now();
 
@@ -43,8 +54,8 @@ pub async fn execute_check(
             match scan_sql_files(dir, &catalog).await {
                 Ok(objs) => objs,
                 Err(e) => {
-                    eprintln!("{} Failed to scan {}: {} — line numbers will be function-relative",
-                        "warning:".yellow().bold(), dir.display(), e);
+                    output::warning(format!("Failed to scan {}: {} {} line numbers will be function-relative",
+                        dir.display(), e, output::symbol("—")));
                     Vec::new()
                 }
             }
@@ -52,36 +63,102 @@ pub async fn execute_check(
         _ => Vec::new(),
     };
 
-    // Build schema filter
-    let schema_filter = if let Some(ref schema_list) = schemas {
-        if schema_list.is_empty() {
-            Some(vec!["public".to_string()])
-        } else {
-            Some(schema_list.clone())
-        }
-    } else {
-        // Default: check all user schemas (excluding pg_* and information_schema)
-        None
-    };
-
     // Use the new bulk query approach
+    let schema_filter = schema_filter(schemas);
     let (all_results, functions_checked) = check_all_functions(&client, schema_filter.as_deref(), function_name.as_deref()).await?;
 
+    Ok(summarize_results(all_results, functions_checked, &source_objects, errors_only, policy, start_time))
+}
+
+/// Like `execute_check`, but first applies the pending migrations and code
+/// files inside a transaction that is rolled back afterwards, so functions
+/// are checked as the files define them without deploying anything (e.g. to
+/// lint a pull request). Source locations come from the code directory.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_check_files(
+    connection_string: String,
+    function_name: Option<String>,
+    schemas: Option<Vec<String>>,
+    errors_only: bool,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    config: &PgmgConfig,
+    policy: &CheckPolicy,
+) -> Result<CheckResult, Box<dyn std::error::Error>> {
+    let start_time = Instant::now();
+
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    // Plain BEGIN rather than a Transaction, as the plan needs the Client.
+    // Nothing below commits, and the transaction is always rolled back.
+    client.batch_execute("BEGIN").await?;
+    let checked = async {
+        let (apply_result, plan_result) = apply_uncommitted(&client, migrations_dir, code_dir, config).await
+            .map_err(|e| format!("Failed to apply the SQL files: {}", e))?;
+        let changed = apply_result.objects_created.len() + apply_result.objects_updated.len() + apply_result.objects_deleted.len();
+        output::line(format!(
+            "{} Applied {} migration(s) and {} object change(s) in a transaction that will be rolled back",
+            output::symbol("→").cyan(),
+            apply_result.migrations_applied.len(),
+            changed
+        ));
+
+        // A pending migration may be what installs the extension
+        if !is_plpgsql_check_available(&client).await? {
+            return Err("plpgsql_check extension is not installed. Please install it with: CREATE EXTENSION plpgsql_check;".into());
+        }
+
+        let schema_filter = schema_filter(schemas);
+        let (all_results, functions_checked) = check_all_functions(&client, schema_filter.as_deref(), function_name.as_deref()).await?;
+        Ok::<_, Box<dyn std::error::Error>>((all_results, functions_checked, plan_result.file_objects))
+    }.await;
+    client.batch_execute("ROLLBACK").await?;
+
+    let (all_results, functions_checked, source_objects) = checked?;
+    Ok(summarize_results(all_results, functions_checked, &source_objects, errors_only, policy, start_time))
+}
+
+/// Which schemas to check: all user schemas (excluding pg_* and
+/// information_schema) by default, and public if the list is empty
+fn schema_filter(schemas: Option<Vec<String>>) -> Option<Vec<String>> {
+    match schemas {
+        Some(schema_list) if schema_list.is_empty() => Some(vec!["public".to_string()]),
+        schema_list => schema_list,
+    }
+}
+
+/// Count and locate plpgsql_check's findings, leaving out those the policy
+/// ignores
+fn summarize_results(
+    all_results: Vec<PlpgsqlCheckResult>,
+    functions_checked: usize,
+    source_objects: &[SqlObject],
+    errors_only: bool,
+    policy: &CheckPolicy,
+    start_time: Instant,
+) -> CheckResult {
     if functions_checked == 0 {
-        return Ok(CheckResult {
+        return CheckResult {
             functions_checked: 0,
             errors_found: 0,
             warnings_found: 0,
+            failures: 0,
+            ignored: 0,
+            hidden_warnings: 0,
             check_errors: vec![],
             duration: start_time.elapsed(),
-        });
+        };
     }
 
-    println!("{} Checking {} PL/pgSQL functions/procedures...", "→".cyan(), functions_checked.to_string().yellow());
+    output::line(format!("{} Checking {} PL/pgSQL functions/procedures...", output::symbol("→").cyan(), functions_checked.to_string().yellow()));
 
     let mut all_errors = Vec::new();
     let mut errors_found = 0;
     let mut warnings_found = 0;
+    let mut failures = 0;
+    let mut ignored = 0;
+    let mut hidden_warnings = 0;
 
     // Process results. plpgsql_check emits levels like "warning extra",
     // "warning performance", "warning security" — match on prefix, not equality.
@@ -90,16 +167,28 @@ pub async fn execute_check(
             let is_error = level.starts_with("error");
             let is_warning = level.starts_with("warning");
 
+            let source_object = result.functionid.as_deref()
+                .and_then(|fid| find_source_object(source_objects, fid));
+            if (is_error || is_warning) && policy.is_ignored(&result, source_object) {
+                ignored += 1;
+                continue;
+            }
+            if policy.is_failure(&result) {
+                failures += 1;
+            }
+
             if is_error {
                 errors_found += 1;
             } else if is_warning {
                 warnings_found += 1;
             }
 
-            if is_error || (is_warning && !errors_only) {
+            if is_warning && errors_only {
+                hidden_warnings += 1;
+            } else if is_error || is_warning {
                 let function_name = result.functionid.as_deref().unwrap_or("unknown");
                 let (source_file, source_line) = match &result.functionid {
-                    Some(fid) => resolve_source_location(&source_objects, fid, result.lineno),
+                    Some(fid) => resolve_source_location(source_objects, fid, result.lineno),
                     None => (None, None),
                 };
                 let error = PlpgsqlCheckError {
@@ -114,47 +203,58 @@ pub async fn execute_check(
     }
     
     // Display progress
-    if functions_checked > 0 && all_errors.is_empty() {
-        println!("  {} All checks passed!", "✓".green().bold());
+    if functions_checked > 0 && all_errors.is_empty() && hidden_warnings == 0 {
+        output::line(format!("  {} All checks passed!", output::symbol("✓").green().bold()));
+    } else if functions_checked > 0 && all_errors.is_empty() {
+        output::line(format!("  {} No errors", output::symbol("✓").green().bold()));
     }
     
-    Ok(CheckResult {
+    CheckResult {
         functions_checked,
         errors_found,
         warnings_found,
+        failures,
+        ignored,
+        hidden_warnings,
         check_errors: all_errors,
         duration: start_time.elapsed(),
-    })
+    }
 }
 
 pub fn print_check_summary(result: &CheckResult) {
-    // Display any errors found
-    display_check_errors(&result.check_errors);
+    // Findings grouped by function, paged when they don't fit on the screen
+    output::page(&format_check_errors(&result.check_errors));
     
-    println!();
-    println!("{}", "Check Summary".bold().bright_blue());
-    println!("{}", "=".repeat(50).bright_black());
+    output::line("");
+    output::line("Check Summary".bold().bright_blue());
+    output::line("=".repeat(50).bright_black());
     
     // Overall status
-    if result.errors_found == 0 && result.warnings_found == 0 {
-        println!("{} {} All checks passed!", "✅".green(), "SUCCESS".green().bold());
-    } else if result.errors_found > 0 {
-        println!("{} {} Issues found", "❌".red(), "FAILURE".red().bold());
+    if result.failures == 0 && result.errors_found == 0 && result.warnings_found == result.hidden_warnings {
+        output::line(format!("{} {} All checks passed!", output::symbol("✅").green(), "SUCCESS".green().bold()));
+    } else if result.failures > 0 {
+        output::line(format!("{} {} Issues found", output::symbol("❌").red(), "FAILURE".red().bold()));
     } else {
-        println!("{} {} Warnings found", "⚠️ ".yellow(), "WARNING".yellow().bold());
+        output::line(format!("{} {} Warnings found", output::symbol("⚠️ ").yellow(), "WARNING".yellow().bold()));
     }
     
-    println!();
-    println!("{} {} functions/procedures checked", "→".cyan(), result.functions_checked);
+    output::line("");
+    output::line(format!("{} {} functions/procedures checked", output::symbol("→").cyan(), result.functions_checked));
     
     if result.errors_found > 0 {
-        println!("{} {} errors", "✗".red(), result.errors_found.to_string().red().bold());
+        output::line(format!("{} {} errors", output::symbol("✗").red(), result.errors_found.to_string().red().bold()));
+    }
+    
+    if result.warnings_found > result.hidden_warnings {
+        output::line(format!("{} {} warnings", output::symbol("⚠").yellow(), result.warnings_found.to_string().yellow().bold()));
+    } else if result.hidden_warnings > 0 {
+        output::line(format!("{} {} warnings hidden by --errors-only", output::symbol("↷").bright_black(), result.hidden_warnings));
     }
     
-    if result.warnings_found > 0 {
-        println!("{} {} warnings", "⚠".yellow(), result.warnings_found.to_string().yellow().bold());
+    if result.ignored > 0 {
+        output::line(format!("{} {} ignored", output::symbol("↷").bright_black(), result.ignored));
     }
     
-    println!("{} Check duration: {:.2?}", "⏱".bright_black(), result.duration);
-    println!();
+    output::line(format!("{} Check duration: {:.2?}", output::symbol("⏱").bright_black(), result.duration));
+    output::line("");
 }
\ No newline at end of file
@@ -0,0 +1,347 @@
+use std::path::Path;
+use tokio_postgres::Client;
+use crate::config::PgmgConfig;
+use crate::db::{connect_with_url, AdvisoryLockManager, StateManager, STATE_SCHEMA_VERSION};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    /// Not applicable, or couldn't run because an earlier check failed
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// How to fix a failed or warning check
+    pub suggestion: Option<String>,
+}
+
+impl DoctorCheck {
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+            suggestion: None,
+        }
+    }
+
+    fn suggest(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DoctorResult {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorResult {
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|check| check.status == status).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.count(CheckStatus::Fail) > 0
+    }
+}
+
+/// Extensions pgmg integrates with, and what they're used for
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("pgtap", "needed by `pgmg test`"),
+    ("plpgsql_check", "needed by `pgmg check` and check_plpgsql"),
+    ("pg_cron", "needed to manage cron.schedule() jobs"),
+];
+
+/// State tables created by the first apply
+const STATE_TABLES: &[&str] = &["pgmg_migrations", "pgmg_state", "pgmg_dependencies"];
+
+/// Diagnose the environment: the config file, the database connection, server
+/// version, optional extensions, pgmg's state tables and the apply lock.
+///
+/// Problems are reported as failed checks rather than errors, so one problem
+/// doesn't hide the others.
+pub async fn execute_doctor(
+    config_path: &Path,
+    connection_string: Option<String>,
+) -> Result<DoctorResult, Box<dyn std::error::Error>> {
+    let mut result = DoctorResult::default();
+
+    let (config_check, config) = check_config_file(config_path);
+    result.checks.push(config_check);
+    let config = config.unwrap_or_default();
+    result.checks.extend(check_directories(&config));
+
+    let conn_str = connection_string
+        .or_else(|| config.connection_string.clone())
+        .or_else(|| std::env::var("DATABASE_URL").ok());
+
+    let Some(conn_str) = conn_str else {
+        result.checks.push(DoctorCheck::new("Database connection", CheckStatus::Fail, "No connection string configured")
+            .suggest("Pass --connection-string, set DATABASE_URL, or set connection_string in pgmg.toml"));
+        return Ok(result);
+    };
+
+    let client = match connect_with_url(&conn_str).await {
+        Ok((client, connection)) => {
+            connection.spawn();
+            result.checks.push(DoctorCheck::new("Database connection", CheckStatus::Pass, "Connected"));
+            client
+        }
+        Err(e) => {
+            debug!(error = %e, "Doctor could not connect");
+            result.checks.push(DoctorCheck::new("Database connection", CheckStatus::Fail, e.to_string())
+                .suggest("Check that the server is running and the host, port, credentials and database name are correct"));
+            return Ok(result);
+        }
+    };
+
+    // The remaining checks only read the catalog, so a failure in one is
+    // reported and the rest still run
+    let checks = [
+        ("Server version", check_server_version(&client).await),
+        ("pgmg state", check_state_tables(&client).await),
+        ("Apply lock", check_apply_lock(&client, config.apply_lock_manager(&conn_str)).await),
+    ];
+    for (name, check) in checks {
+        result.checks.push(check.unwrap_or_else(|e| DoctorCheck::new(name, CheckStatus::Fail, e.to_string())));
+    }
+
+    match check_extensions(&client, &config).await {
+        Ok(checks) => result.checks.extend(checks),
+        Err(e) => result.checks.push(DoctorCheck::new("Extensions", CheckStatus::Fail, e.to_string())),
+    }
+
+    Ok(result)
+}
+
+/// Parse the config file, returning the config if it's valid
+fn check_config_file(path: &Path) -> (DoctorCheck, Option<PgmgConfig>) {
+    const NAME: &str = "Config file";
+
+    if !path.exists() {
+        let check = DoctorCheck::new(NAME, CheckStatus::Skip, format!("{} not found; using command-line options only", path.display()))
+            .suggest("Run `pgmg init` to generate a sample configuration");
+        return (check, None);
+    }
+
+    let config = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| toml::from_str::<PgmgConfig>(&content).map_err(|e| e.to_string()));
+
+    match config {
+        Ok(config) => match config.build_tls_config() {
+            Ok(_) => (DoctorCheck::new(NAME, CheckStatus::Pass, format!("{} is valid", path.display())), Some(config)),
+            Err(e) => {
+                let check = DoctorCheck::new(NAME, CheckStatus::Fail, format!("Invalid [tls] section: {}", e))
+                    .suggest("sslmode must be one of disable, prefer, require, verify-ca, verify-full");
+                (check, Some(config))
+            }
+        },
+        Err(e) => {
+            let check = DoctorCheck::new(NAME, CheckStatus::Fail, format!("{} could not be parsed: {}", path.display(), e.trim()))
+                .suggest("Compare it with the output of `pgmg init`");
+            (check, None)
+        }
+    }
+}
+
+/// Configured directories that don't exist
+fn check_directories(config: &PgmgConfig) -> Vec<DoctorCheck> {
+    [
+        ("Migrations directory", &config.migrations_dir),
+        ("Code directory", &config.code_dir),
+        ("Seed directory", &config.seed_dir),
+    ]
+        .into_iter()
+        .filter_map(|(name, dir)| {
+            let dir = dir.as_ref()?;
+            Some(if dir.is_dir() {
+                DoctorCheck::new(name, CheckStatus::Pass, dir.display().to_string())
+            } else {
+                DoctorCheck::new(name, CheckStatus::Warn, format!("{} does not exist", dir.display()))
+                    .suggest("Create the directory or fix the path in pgmg.toml")
+            })
+        })
+        .collect()
+}
+
+async fn check_server_version(client: &Client) -> Result<DoctorCheck, Box<dyn std::error::Error>> {
+    let row = client.query_one(
+        "SELECT current_setting('server_version'), current_setting('server_version_num')::int",
+        &[],
+    ).await?;
+    let version: String = row.get(0);
+    let version_num: i32 = row.get(1);
+
+    // CREATE PROCEDURE arrived in PostgreSQL 11
+    Ok(if version_num >= 110000 {
+        DoctorCheck::new("Server version", CheckStatus::Pass, format!("PostgreSQL {}", version))
+    } else {
+        DoctorCheck::new("Server version", CheckStatus::Warn, format!("PostgreSQL {} is older than 11", version))
+            .suggest("Procedures are not supported before PostgreSQL 11; upgrade the server if you manage any")
+    })
+}
+
+async fn check_extensions(client: &Client, config: &PgmgConfig) -> Result<Vec<DoctorCheck>, Box<dyn std::error::Error>> {
+    let mut checks = Vec::new();
+
+    for (extension, used_for) in EXTENSIONS {
+        let row = client.query_one(
+            "SELECT (SELECT extversion FROM pg_extension WHERE extname = $1),
+                    (SELECT default_version FROM pg_available_extensions WHERE name = $1)",
+            &[extension],
+        ).await?;
+        let installed: Option<String> = row.get(0);
+        let available: Option<String> = row.get(1);
+
+        let required = *extension == "plpgsql_check" && config.check_plpgsql.unwrap_or(false);
+        let name = format!("Extension {}", extension);
+
+        let check = match (installed, available) {
+            (Some(installed), Some(available)) if installed != available => {
+                DoctorCheck::new(&name, CheckStatus::Warn, format!("{} installed, {} available", installed, available))
+                    .suggest(format!("ALTER EXTENSION {} UPDATE;", extension))
+            }
+            (Some(installed), _) => DoctorCheck::new(&name, CheckStatus::Pass, format!("{} installed", installed)),
+            (None, Some(available)) => {
+                let status = if required { CheckStatus::Fail } else { CheckStatus::Skip };
+                DoctorCheck::new(&name, status, format!("Not installed ({} available; {})", available, used_for))
+                    .suggest(format!("CREATE EXTENSION {};", extension))
+            }
+            (None, None) => {
+                let status = if required { CheckStatus::Fail } else { CheckStatus::Skip };
+                DoctorCheck::new(&name, status, format!("Not available on this server ({})", used_for))
+                    .suggest(format!("Install the {} package for this PostgreSQL version", extension))
+            }
+        };
+        checks.push(check);
+    }
+
+    Ok(checks)
+}
+
+async fn check_state_tables(client: &Client) -> Result<DoctorCheck, Box<dyn std::error::Error>> {
+    const NAME: &str = "pgmg state";
+
+    let rows = client.query(
+        "SELECT table_name::text FROM information_schema.tables WHERE table_schema = 'pgmg'",
+        &[],
+    ).await?;
+    let present: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    let missing: Vec<&str> = STATE_TABLES.iter()
+        .filter(|table| !present.iter().any(|name| name == *table))
+        .copied()
+        .collect();
+
+    if missing.len() == STATE_TABLES.len() {
+        return Ok(DoctorCheck::new(NAME, CheckStatus::Warn, "pgmg has not been applied to this database yet")
+            .suggest("Run `pgmg apply` to create the pgmg schema and state tables"));
+    }
+
+    if !missing.is_empty() {
+        return Ok(DoctorCheck::new(NAME, CheckStatus::Fail, format!("Missing tables: {}", missing.join(", ")))
+            .suggest("Run `pgmg apply` to recreate them; state tables are created if they don't exist"));
+    }
+
+    let row = client.query_one(
+        "SELECT (SELECT count(*) FROM pgmg.pgmg_migrations), (SELECT count(*) FROM pgmg.pgmg_state)",
+        &[],
+    ).await?;
+    let migrations: i64 = row.get(0);
+    let objects: i64 = row.get(1);
+    let version = StateManager::new(client).schema_version().await?;
+    let detail = format!(
+        "{} migrations applied, {} objects tracked, schema version {}",
+        migrations, objects, version
+    );
+
+    Ok(match version.cmp(&STATE_SCHEMA_VERSION) {
+        std::cmp::Ordering::Equal => DoctorCheck::new(NAME, CheckStatus::Pass, detail),
+        std::cmp::Ordering::Less => DoctorCheck::new(NAME, CheckStatus::Warn, detail)
+            .suggest(format!("The next `pgmg apply` upgrades the state tables to version {}", STATE_SCHEMA_VERSION)),
+        std::cmp::Ordering::Greater => DoctorCheck::new(NAME, CheckStatus::Fail, detail)
+            .suggest(format!("This pgmg supports state schema version {}; upgrade pgmg", STATE_SCHEMA_VERSION)),
+    })
+}
+
+/// Report whether another session holds the advisory lock apply takes
+async fn check_apply_lock(client: &Client, lock_manager: AdvisoryLockManager) -> Result<DoctorCheck, Box<dyn std::error::Error>> {
+    const NAME: &str = "Apply lock";
+
+    let holders = lock_manager.holders(client).await?;
+    let Some(holder) = holders.first() else {
+        return Ok(DoctorCheck::new(NAME, CheckStatus::Pass, "Not held"));
+    };
+
+    Ok(DoctorCheck::new(NAME, CheckStatus::Warn, format!("Held by {}", holder.describe()))
+        .suggest("Another apply is probably running. If that process is gone, end its session with `pgmg unlock --force`"))
+}
+
+#[cfg(feature = "cli")]
+pub fn print_doctor_summary(result: &DoctorResult) {
+    use crate::logging::output;
+    use console::style;
+
+    for check in &result.checks {
+        let marker = match check.status {
+            CheckStatus::Pass => style(output::symbol("✓")).green().bold(),
+            CheckStatus::Warn => style(output::symbol("⚠")).yellow().bold(),
+            CheckStatus::Fail => style(output::symbol("✗")).red().bold(),
+            CheckStatus::Skip => style(output::symbol("↷")).dim(),
+        };
+        output::line(format!("{} {}: {}", marker, style(&check.name).bold(), check.detail));
+
+        if check.status != CheckStatus::Pass {
+            if let Some(suggestion) = &check.suggestion {
+                output::line(format!("    {} {}", style(output::symbol("→")).cyan(), style(suggestion).dim()));
+            }
+        }
+    }
+
+    output::line(format!("\n{} {} passed, {} warnings, {} failed, {} skipped",
+        style("Summary:").bold(),
+        style(result.count(CheckStatus::Pass)).green(),
+        style(result.count(CheckStatus::Warn)).yellow(),
+        style(result.count(CheckStatus::Fail)).red(),
+        result.count(CheckStatus::Skip)
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_config_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("pgmg.toml");
+
+        let (check, config) = check_config_file(&path);
+        assert_eq!(check.status, CheckStatus::Skip);
+        assert!(config.is_none());
+
+        std::fs::write(&path, "migrations_dir = \"migrations\"\n").unwrap();
+        let (check, config) = check_config_file(&path);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(config.unwrap().migrations_dir.is_some());
+
+        std::fs::write(&path, "migrations_dir = [\n").unwrap();
+        let (check, config) = check_config_file(&path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(config.is_none());
+
+        std::fs::write(&path, "[tls]\nsslmode = \"sometimes\"\n").unwrap();
+        let (check, _) = check_config_file(&path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.suggestion.is_some());
+    }
+}
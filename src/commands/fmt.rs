@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::commands::lint::collect_code_files;
+use crate::sql::format::format_sql;
+
+#[derive(Debug)]
+pub struct FmtResult {
+    /// Files that were reformatted, or with `check`, would be
+    pub changed: Vec<PathBuf>,
+    pub files_checked: usize,
+    /// Files that could not be read, parsed or safely formatted
+    pub errors: Vec<String>,
+    pub check: bool,
+}
+
+/// Format the .sql files in the code directory in the canonical style. With
+/// `check`, nothing is written and the files that would change are reported.
+pub async fn execute_fmt(
+    code_dir: PathBuf,
+    check: bool,
+) -> Result<FmtResult, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    collect_code_files(&code_dir, &mut files)?;
+
+    let mut result = FmtResult {
+        changed: Vec::new(),
+        files_checked: 0,
+        errors: Vec::new(),
+        check,
+    };
+
+    for path in files {
+        result.files_checked += 1;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                result.errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let formatted = match format_sql(&content) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                result.errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        if formatted != content {
+            if !check {
+                fs::write(&path, formatted)?;
+            }
+            result.changed.push(path);
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(feature = "cli")]
+pub fn print_fmt_summary(result: &FmtResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    for path in &result.changed {
+        let path = match path.strip_prefix(std::env::current_dir().unwrap_or_default()) {
+            Ok(relative_path) => relative_path.display().to_string(),
+            Err(_) => path.display().to_string(),
+        };
+        if result.check {
+            output::line(format!("{} {} is not formatted", output::symbol("✗").red(), path.bright_blue()));
+        } else {
+            output::line(format!("{} Formatted {}", output::symbol("✓").green(), path.bright_blue()));
+        }
+    }
+
+    for error in &result.errors {
+        output::line(format!("{} {}", output::symbol("✗").red(), error.red()));
+    }
+
+    output::line("");
+    if result.changed.is_empty() && result.errors.is_empty() {
+        output::line(format!(
+            "{} {} files checked, all formatted",
+            output::symbol("✓").green().bold(),
+            result.files_checked
+        ));
+    } else if result.check {
+        output::line(format!(
+            "{} {} of {} files need formatting, run pgmg fmt",
+            output::symbol("✗").red().bold(),
+            result.changed.len().to_string().red().bold(),
+            result.files_checked
+        ));
+    } else {
+        output::line(format!(
+            "{} Formatted {} of {} files",
+            output::symbol("✓").green().bold(),
+            result.changed.len(),
+            result.files_checked
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_fmt_check_then_write() {
+        let temp_dir = tempdir().unwrap();
+        let code_dir = temp_dir.path().join("sql");
+        fs::create_dir_all(code_dir.join("views")).unwrap();
+
+        let unformatted = "create view api.users as select id, email from public.users\n";
+        fs::write(code_dir.join("views/users.sql"), unformatted).unwrap();
+        fs::write(code_dir.join("views/formatted.sql"), "CREATE VIEW api.one AS\nSELECT 1;\n").unwrap();
+        fs::write(code_dir.join("views/users.test.sql"), "select 1").unwrap();
+        fs::write(code_dir.join("views/broken.sql"), "CREATE VIEW AS;").unwrap();
+
+        let result = execute_fmt(code_dir.clone(), true).await.unwrap();
+        assert_eq!(result.files_checked, 3);
+        assert_eq!(result.changed, vec![code_dir.join("views/users.sql")]);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(fs::read_to_string(code_dir.join("views/users.sql")).unwrap(), unformatted);
+
+        let result = execute_fmt(code_dir.clone(), false).await.unwrap();
+        assert_eq!(result.changed.len(), 1);
+        assert_eq!(
+            fs::read_to_string(code_dir.join("views/users.sql")).unwrap(),
+            "CREATE VIEW api.users AS\nSELECT id, email\nFROM public.users;\n"
+        );
+
+        let result = execute_fmt(code_dir, true).await.unwrap();
+        assert!(result.changed.is_empty());
+    }
+}
@@ -0,0 +1,430 @@
+use std::path::PathBuf;
+use std::fs;
+use chrono::{DateTime, Utc};
+use pg_query::NodeEnum;
+use tokio_postgres::GenericClient;
+use crate::commands::new::new_migration_path;
+use crate::db::{connect_with_url, scan_sql_files};
+use crate::sql::{SqlObject, ObjectType};
+use crate::sql::quote::quote_ident;
+use crate::builtin_catalog::{BuiltinCatalog, DEFAULT_POSTGRES_VERSION};
+use tracing::debug;
+
+/// Scratch schema the desired table definitions are created in, inside a
+/// transaction that is always rolled back
+const SCRATCH_SCHEMA: &str = "pgmg_generate_migration";
+
+#[derive(Debug, Clone, PartialEq)]
+struct ColumnShape {
+    name: String,
+    data_type: String,
+    not_null: bool,
+    default: Option<String>,
+}
+
+/// Columns and constraints of a table, as reported by the catalog
+#[derive(Debug, Clone, PartialEq, Default)]
+struct TableShape {
+    columns: Vec<ColumnShape>,
+    /// (constraint name, definition from pg_get_constraintdef)
+    constraints: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct TableDiff {
+    pub table: String,
+    pub statements: Vec<String>,
+    /// Things to double check before applying, e.g. NOT NULL columns without defaults
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct GenerateMigrationResult {
+    pub diffs: Vec<TableDiff>,
+    /// Tables in the code directory that don't exist in the database yet
+    pub missing_tables: Vec<String>,
+    pub migration_file: Option<String>,
+    pub migration_path: Option<PathBuf>,
+    /// The generated migration SQL (empty if no table changed)
+    pub sql: String,
+}
+
+/// Diff every `CREATE TABLE` in the code directory against the live table and
+/// write a candidate `ALTER TABLE` migration for review.
+///
+/// The desired definitions are created in a scratch schema inside a transaction
+/// that is rolled back, so types, defaults and constraints are compared in the
+/// form PostgreSQL itself reports them.
+pub async fn execute_generate_migration(
+    name: String,
+    table_filter: Option<String>,
+    code_dir: PathBuf,
+    migrations_dir: PathBuf,
+    connection_string: String,
+    dry_run: bool,
+) -> Result<GenerateMigrationResult, Box<dyn std::error::Error>> {
+    if !code_dir.exists() {
+        return Err(format!("Code directory not found: {}", code_dir.display()).into());
+    }
+
+    let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+    let tables: Vec<SqlObject> = scan_sql_files(&code_dir, &builtin_catalog).await?
+        .into_iter()
+        .filter(|obj| obj.object_type == ObjectType::Table)
+        .filter(|obj| table_filter.as_ref().map_or(true, |filter| {
+            qualified_table_name(obj) == *filter || obj.qualified_name.name == *filter
+        }))
+        .collect();
+
+    if tables.is_empty() {
+        return Err(match table_filter {
+            Some(filter) => format!("Table '{}' not found in code directory", filter),
+            None => "No CREATE TABLE statements found in code directory".to_string(),
+        }.into());
+    }
+
+    let (mut client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let mut diffs = Vec::new();
+    let mut missing_tables = Vec::new();
+
+    for table in &tables {
+        let table_name = qualified_table_name(table);
+
+        let Some(current) = introspect_table(&client, &table_name, None).await? else {
+            missing_tables.push(table_name);
+            continue;
+        };
+
+        let transaction = client.transaction().await?;
+        transaction.batch_execute(&format!("CREATE SCHEMA {}", SCRATCH_SCHEMA)).await?;
+        transaction.batch_execute(&rewrite_into_scratch_schema(&table.ddl_statement)?).await?;
+        let scratch_name = format!("{}.{}", SCRATCH_SCHEMA, quote_ident(&table.qualified_name.name));
+        let desired = introspect_table(&transaction, &scratch_name, table.qualified_name.schema.as_deref()).await?
+            .ok_or("Scratch table was not created")?;
+        transaction.rollback().await?;
+
+        let diff = diff_tables(&table_name, &current, &desired);
+        debug!("{}: {} statements", table_name, diff.statements.len());
+        if !diff.statements.is_empty() {
+            diffs.push(diff);
+        }
+    }
+
+    let mut result = GenerateMigrationResult {
+        diffs,
+        missing_tables,
+        migration_file: None,
+        migration_path: None,
+        sql: String::new(),
+    };
+
+    if result.diffs.is_empty() {
+        return Ok(result);
+    }
+
+    let now: DateTime<Utc> = Utc::now();
+    result.sql = render_migration(&name, now, &result.diffs);
+
+    if !dry_run {
+        if !migrations_dir.exists() {
+            fs::create_dir_all(&migrations_dir)?;
+        }
+        let (migration_file, migration_path) = new_migration_path(&migrations_dir, &name, now)?;
+        fs::write(&migration_path, &result.sql)?;
+        result.migration_file = Some(migration_file);
+        result.migration_path = Some(migration_path);
+    }
+
+    Ok(result)
+}
+
+fn qualified_table_name(table: &SqlObject) -> String {
+    format!(
+        "{}.{}",
+        quote_ident(table.qualified_name.schema.as_deref().unwrap_or("public")),
+        quote_ident(&table.qualified_name.name)
+    )
+}
+
+/// Point the table of a `CREATE TABLE` statement at the scratch schema
+fn rewrite_into_scratch_schema(ddl: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let parsed = pg_query::parse(ddl)?;
+    let node = parsed.protobuf.stmts.first()
+        .and_then(|stmt| stmt.stmt.as_ref())
+        .and_then(|stmt| stmt.node.as_ref());
+
+    match node {
+        Some(NodeEnum::CreateStmt(create)) => {
+            let mut create = create.clone();
+            let relation = create.relation.as_mut().ok_or("CREATE TABLE without a table name")?;
+            relation.schemaname = SCRATCH_SCHEMA.to_string();
+            Ok(NodeEnum::CreateStmt(create).deparse()?)
+        }
+        _ => Err("Expected a CREATE TABLE statement".into()),
+    }
+}
+
+/// Read columns and constraints of a table. When reading the scratch copy,
+/// references to the scratch schema are rewritten to `schema` so that
+/// defaults like `nextval('orders_id_seq')` compare equal.
+async fn introspect_table<C: GenericClient>(
+    client: &C,
+    table: &str,
+    schema: Option<&str>,
+) -> Result<Option<TableShape>, Box<dyn std::error::Error>> {
+    let exists: bool = client.query_one("SELECT to_regclass($1) IS NOT NULL", &[&table]).await?.get(0);
+    if !exists {
+        return Ok(None);
+    }
+
+    let unscratch = |text: String| match schema {
+        Some(schema) if schema != "public" => text.replace(&format!("{}.", SCRATCH_SCHEMA), &format!("{}.", schema)),
+        _ => text.replace(&format!("{}.", SCRATCH_SCHEMA), ""),
+    };
+
+    let column_rows = client.query(
+        "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), a.attnotnull, pg_get_expr(d.adbin, d.adrelid)
+         FROM pg_attribute a
+         LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+         WHERE a.attrelid = to_regclass($1) AND a.attnum > 0 AND NOT a.attisdropped
+         ORDER BY a.attnum",
+        &[&table],
+    ).await?;
+
+    let constraint_rows = client.query(
+        "SELECT conname::text, pg_get_constraintdef(oid)
+         FROM pg_constraint
+         WHERE conrelid = to_regclass($1) AND contype IN ('p', 'u', 'f', 'c', 'x')
+         ORDER BY conname",
+        &[&table],
+    ).await?;
+
+    Ok(Some(TableShape {
+        columns: column_rows.iter()
+            .map(|row| ColumnShape {
+                name: row.get(0),
+                data_type: row.get(1),
+                not_null: row.get(2),
+                default: row.get::<_, Option<String>>(3).map(&unscratch),
+            })
+            .collect(),
+        constraints: constraint_rows.iter()
+            .map(|row| (row.get(0), unscratch(row.get(1))))
+            .collect(),
+    }))
+}
+
+/// Build the ALTER TABLE statements turning `current` into `desired`.
+///
+/// Constraints are dropped first and added last so that column changes
+/// aren't blocked by them. Renames can't be told apart from a drop and an
+/// add, so they show up as both.
+fn diff_tables(table: &str, current: &TableShape, desired: &TableShape) -> TableDiff {
+    let mut statements = Vec::new();
+    let mut notes = Vec::new();
+
+    let changed_constraints: Vec<&(String, String)> = desired.constraints.iter()
+        .filter(|(name, def)| current.constraints.iter().any(|(n, d)| n == name && d != def))
+        .collect();
+
+    for (name, _) in &current.constraints {
+        let removed = !desired.constraints.iter().any(|(n, _)| n == name);
+        let changed = changed_constraints.iter().any(|(n, _)| n == name);
+        if removed || changed {
+            statements.push(format!("ALTER TABLE {} DROP CONSTRAINT {};", table, quote_ident(name)));
+        }
+    }
+
+    for column in &current.columns {
+        if !desired.columns.iter().any(|c| c.name == column.name) {
+            statements.push(format!("ALTER TABLE {} DROP COLUMN {};", table, quote_ident(&column.name)));
+            notes.push(format!("{}.{} is dropped; if it was renamed, use RENAME COLUMN instead", table, column.name));
+        }
+    }
+
+    for column in &desired.columns {
+        let Some(existing) = current.columns.iter().find(|c| c.name == column.name) else {
+            let mut statement = format!("ALTER TABLE {} ADD COLUMN {} {}", table, quote_ident(&column.name), column.data_type);
+            if let Some(default) = &column.default {
+                statement.push_str(&format!(" DEFAULT {}", default));
+            }
+            if column.not_null {
+                statement.push_str(" NOT NULL");
+                if column.default.is_none() {
+                    notes.push(format!("{}.{} is NOT NULL without a default and will fail on a non-empty table", table, column.name));
+                }
+            }
+            statements.push(format!("{};", statement));
+            continue;
+        };
+
+        let column_name = quote_ident(&column.name);
+
+        if existing.data_type != column.data_type {
+            statements.push(format!(
+                "ALTER TABLE {} ALTER COLUMN {} TYPE {} USING {}::{};",
+                table, column_name, column.data_type, column_name, column.data_type
+            ));
+            notes.push(format!(
+                "{}.{} changes type from {} to {}, which may rewrite the table",
+                table, column.name, existing.data_type, column.data_type
+            ));
+        }
+
+        if existing.default != column.default {
+            match &column.default {
+                Some(default) => statements.push(format!(
+                    "ALTER TABLE {} ALTER COLUMN {} SET DEFAULT {};", table, column_name, default
+                )),
+                None => statements.push(format!(
+                    "ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT;", table, column_name
+                )),
+            }
+        }
+
+        if existing.not_null != column.not_null {
+            let action = if column.not_null { "SET NOT NULL" } else { "DROP NOT NULL" };
+            statements.push(format!("ALTER TABLE {} ALTER COLUMN {} {};", table, column_name, action));
+        }
+    }
+
+    for (name, def) in &desired.constraints {
+        let added = !current.constraints.iter().any(|(n, _)| n == name);
+        let changed = changed_constraints.iter().any(|(n, _)| n == name);
+        if added || changed {
+            statements.push(format!("ALTER TABLE {} ADD CONSTRAINT {} {};", table, quote_ident(name), def));
+        }
+    }
+
+    TableDiff {
+        table: table.to_string(),
+        statements,
+        notes,
+    }
+}
+
+fn render_migration(name: &str, now: DateTime<Utc>, diffs: &[TableDiff]) -> String {
+    let mut sql = format!(
+        "-- Migration: {}\n-- Created: {}\n-- Generated by pgmg generate-migration. Review before applying.\n",
+        name,
+        now.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+
+    for diff in diffs {
+        sql.push_str(&format!("\n-- {}\n", diff.table));
+        for note in &diff.notes {
+            sql.push_str(&format!("-- NOTE: {}\n", note));
+        }
+        for statement in &diff.statements {
+            sql.push_str(statement);
+            sql.push('\n');
+        }
+    }
+
+    sql
+}
+
+#[cfg(feature = "cli")]
+pub fn print_generate_migration_summary(result: &GenerateMigrationResult) {
+    use crate::logging::output;
+    use console::style;
+
+    for table in &result.missing_tables {
+        output::warning(format!("{} does not exist in the database yet; apply creates it", table));
+    }
+
+    if result.diffs.is_empty() {
+        output::success("All tables match their definitions. No migration generated.");
+        return;
+    }
+
+    for diff in &result.diffs {
+        output::line(format!("\n{}", style(&diff.table).cyan().bold()));
+        for statement in &diff.statements {
+            output::line(format!("  {}", statement));
+        }
+        for note in &diff.notes {
+            output::line(format!("  {} {}", style("note:").yellow(), note));
+        }
+    }
+
+    match &result.migration_path {
+        Some(path) => output::success(format!("Wrote migration to {}", path.display())),
+        None => {
+            // Dry run: the migration is the command's output
+            print!("\n{}", result.sql);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, not_null: bool, default: Option<&str>) -> ColumnShape {
+        ColumnShape {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            not_null,
+            default: default.map(|d| d.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_diff_tables() {
+        let current = TableShape {
+            columns: vec![
+                column("id", "bigint", true, None),
+                column("total", "integer", false, None),
+                column("legacy", "text", false, None),
+            ],
+            constraints: vec![
+                ("orders_pkey".to_string(), "PRIMARY KEY (id)".to_string()),
+                ("orders_total_check".to_string(), "CHECK ((total > 0))".to_string()),
+            ],
+        };
+        let desired = TableShape {
+            columns: vec![
+                column("id", "bigint", true, None),
+                column("total", "numeric(12,2)", true, Some("0")),
+                column("status", "text", true, None),
+            ],
+            constraints: vec![
+                ("orders_pkey".to_string(), "PRIMARY KEY (id)".to_string()),
+                ("orders_total_check".to_string(), "CHECK ((total >= (0)::numeric))".to_string()),
+            ],
+        };
+
+        let diff = diff_tables("public.orders", &current, &desired);
+
+        assert_eq!(diff.statements, vec![
+            "ALTER TABLE public.orders DROP CONSTRAINT orders_total_check;",
+            "ALTER TABLE public.orders DROP COLUMN legacy;",
+            "ALTER TABLE public.orders ALTER COLUMN total TYPE numeric(12,2) USING total::numeric(12,2);",
+            "ALTER TABLE public.orders ALTER COLUMN total SET DEFAULT 0;",
+            "ALTER TABLE public.orders ALTER COLUMN total SET NOT NULL;",
+            "ALTER TABLE public.orders ADD COLUMN status text NOT NULL;",
+            "ALTER TABLE public.orders ADD CONSTRAINT orders_total_check CHECK ((total >= (0)::numeric));",
+        ]);
+        assert_eq!(diff.notes.len(), 3);
+    }
+
+    #[test]
+    fn test_diff_tables_unchanged() {
+        let shape = TableShape {
+            columns: vec![column("id", "bigint", true, None)],
+            constraints: vec![("users_pkey".to_string(), "PRIMARY KEY (id)".to_string())],
+        };
+
+        assert!(diff_tables("public.users", &shape, &shape).statements.is_empty());
+    }
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("orders"), "orders");
+        assert_eq!(quote_ident("Order"), "\"Order\"");
+        assert_eq!(quote_ident("order items"), "\"order items\"");
+    }
+}
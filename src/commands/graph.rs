@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::collections::HashSet;
+use crate::analysis::{DependencyGraph, ObjectRef, SearchPath};
+use crate::db::scan_sql_files;
+use crate::sql::{matches_tags, ObjectType, QualifiedIdent};
+use crate::builtin_catalog::{BuiltinCatalog, DEFAULT_POSTGRES_VERSION};
+use tracing::debug;
+
+/// Output format for `pgmg graph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!("Unknown graph format '{}'. Expected one of: dot, mermaid, json", s)),
+        }
+    }
+}
+
+/// Filters applied to the graph before rendering
+#[derive(Debug, Clone, Default)]
+pub struct GraphFilter {
+    /// Only include objects connected to this object (e.g. `api.create_order`)
+    pub root: Option<String>,
+    /// Maximum number of hops from `root` (unlimited if not set)
+    pub depth: Option<usize>,
+    /// Only include objects of these types (all types if empty)
+    pub object_types: Vec<ObjectType>,
+    /// Only include objects with one of these `-- pgmg:tags` (all objects if empty)
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct GraphResult {
+    pub rendered: String,
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub output_path: Option<PathBuf>,
+}
+
+/// Build the dependency graph from the code directory and render it,
+/// resolving unqualified references through `search_path`.
+/// This only reads SQL files, no database connection is needed.
+pub async fn execute_graph(
+    code_dir: PathBuf,
+    format: GraphFormat,
+    filter: GraphFilter,
+    search_path: &SearchPath,
+    output_path: Option<PathBuf>,
+) -> Result<GraphResult, Box<dyn std::error::Error>> {
+    if !code_dir.exists() {
+        return Err(format!("Code directory not found: {}", code_dir.display()).into());
+    }
+
+    let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+    let file_objects = scan_sql_files(&code_dir, &builtin_catalog).await?;
+    let graph = DependencyGraph::build_with_search_path(&file_objects, &builtin_catalog, search_path)?;
+
+    debug!("Built dependency graph: {} objects, {} dependencies", graph.node_count(), graph.edge_count());
+
+    let tagged: HashSet<ObjectRef> = file_objects.iter()
+        .filter(|obj| matches_tags(&obj.directives.tags, &filter.tags))
+        .map(|obj| ObjectRef {
+            object_type: obj.object_type.clone(),
+            qualified_name: obj.qualified_name.clone(),
+        })
+        .collect();
+
+    let graph = apply_filter(&graph, &filter, &tagged)?;
+
+    let rendered = match format {
+        GraphFormat::Dot => graph.to_graphviz(),
+        GraphFormat::Mermaid => graph.to_mermaid(),
+        GraphFormat::Json => graph.to_json(),
+    };
+
+    if let Some(ref path) = output_path {
+        std::fs::write(path, &rendered)?;
+    }
+
+    Ok(GraphResult {
+        rendered,
+        node_count: graph.node_count(),
+        edge_count: graph.edge_count(),
+        output_path,
+    })
+}
+
+/// `tagged` holds the objects passing the tag filter
+fn apply_filter(
+    graph: &DependencyGraph,
+    filter: &GraphFilter,
+    tagged: &HashSet<ObjectRef>,
+) -> Result<DependencyGraph, Box<dyn std::error::Error>> {
+    let neighborhood = match &filter.root {
+        Some(root) => {
+            let roots = graph.find_by_name(&QualifiedIdent::from_qualified_name(root));
+            if roots.is_empty() {
+                return Err(format!("Object '{}' not found in code directory", root).into());
+            }
+            Some(graph.neighborhood(&roots, filter.depth))
+        }
+        None => None,
+    };
+
+    Ok(graph.subgraph(|obj_ref| {
+        let in_neighborhood = neighborhood.as_ref()
+            .map_or(true, |objects| objects.contains(obj_ref));
+        let type_matches = filter.object_types.is_empty()
+            || filter.object_types.contains(&obj_ref.object_type);
+        let tag_matches = filter.tags.is_empty() || tagged.contains(obj_ref);
+        in_neighborhood && type_matches && tag_matches
+    }))
+}
+
+#[cfg(feature = "cli")]
+pub fn print_graph_summary(result: &GraphResult) {
+    use crate::logging::output;
+
+    match &result.output_path {
+        Some(path) => output::success(format!(
+            "Dependency graph written to {} ({} objects, {} dependencies)",
+            path.display(),
+            result.node_count,
+            result.edge_count
+        )),
+        // The rendered graph is the command's output, so it is printed even in quiet mode
+        None => print!("{}", result.rendered),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use std::fs;
+
+    #[test]
+    fn test_graph_format_from_str() {
+        assert_eq!("dot".parse::<GraphFormat>().unwrap(), GraphFormat::Dot);
+        assert_eq!("Mermaid".parse::<GraphFormat>().unwrap(), GraphFormat::Mermaid);
+        assert_eq!("json".parse::<GraphFormat>().unwrap(), GraphFormat::Json);
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_graph_with_root_and_type_filter() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("schema.sql"), r#"
+            CREATE VIEW api.active_users AS SELECT 1 AS id;
+            CREATE VIEW api.active_user_ids AS SELECT id FROM api.active_users;
+            CREATE FUNCTION api.count_users() RETURNS bigint LANGUAGE sql AS $$ SELECT count(*) FROM api.active_users $$;
+            CREATE VIEW api.unrelated AS SELECT 2 AS id;
+        "#).unwrap();
+
+        let filter = GraphFilter {
+            root: Some("api.active_users".to_string()),
+            depth: Some(1),
+            object_types: vec![ObjectType::View],
+            tags: Vec::new(),
+        };
+        let result = execute_graph(temp_dir.path().to_path_buf(), GraphFormat::Json, filter, &SearchPath::default(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.node_count, 2);
+        assert!(result.rendered.contains("api.active_user_ids"));
+        assert!(!result.rendered.contains("api.unrelated"));
+        assert!(!result.rendered.contains("api.count_users"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_graph_with_tag_filter() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("schema.sql"), r#"
+            -- pgmg:tags billing
+            CREATE VIEW api.invoices AS SELECT 1 AS id;
+            -- pgmg:tags billing, reporting
+            CREATE VIEW api.invoice_totals AS SELECT count(*) FROM api.invoices;
+            CREATE VIEW api.users AS SELECT 2 AS id;
+        "#).unwrap();
+
+        let filter = GraphFilter {
+            tags: vec!["billing".to_string()],
+            ..Default::default()
+        };
+        let result = execute_graph(temp_dir.path().to_path_buf(), GraphFormat::Json, filter, &SearchPath::default(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.node_count, 2);
+        assert_eq!(result.edge_count, 1);
+        assert!(!result.rendered.contains("api.users"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_graph_unknown_root() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("schema.sql"), "CREATE VIEW api.v AS SELECT 1;").unwrap();
+
+        let filter = GraphFilter {
+            root: Some("api.missing".to_string()),
+            ..Default::default()
+        };
+        let result = execute_graph(temp_dir.path().to_path_buf(), GraphFormat::Dot, filter, &SearchPath::default(), None).await;
+
+        assert!(result.is_err());
+    }
+}
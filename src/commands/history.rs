@@ -0,0 +1,159 @@
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use crate::db::{connect_with_url, RunRecord, StateManager};
+
+#[derive(Debug)]
+pub struct HistoryResult {
+    /// Apply runs, most recent first
+    pub runs: Vec<RunRecord>,
+}
+
+/// The most recent `limit` apply runs recorded in pgmg.pgmg_runs: who ran
+/// them, with which pgmg and commit, how long they took, what they changed
+/// and the errors of those that failed. Nothing is created or changed, so
+/// this is safe to run against any environment.
+pub async fn execute_history(
+    connection_string: String,
+    limit: usize,
+) -> Result<HistoryResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let runs = StateManager::new(&client).get_runs(limit as i64).await?;
+    Ok(HistoryResult { runs })
+}
+
+/// A recorded run as JSON, with RFC 3339 timestamps
+pub fn run_to_json(run: &RunRecord) -> Value {
+    let timestamp = |time: SystemTime| DateTime::<Utc>::from(time).to_rfc3339();
+    json!({
+        "id": run.id,
+        "started_at": timestamp(run.started_at),
+        "finished_at": timestamp(run.finished_at),
+        "db_user": run.db_user,
+        "os_user": run.os_user,
+        "pgmg_version": run.pgmg_version,
+        "git_commit": run.git_commit,
+        "git_dirty": run.git_dirty,
+        "environment": run.environment,
+        "duration_ms": run.duration.as_millis() as u64,
+        "succeeded": run.succeeded,
+        "changes": run.changes,
+        "errors": run.errors,
+        "backup_path": run.backup_path,
+    })
+}
+
+#[cfg(feature = "cli")]
+pub fn print_history_summary(result: &HistoryResult, as_json: bool) {
+    use crate::logging::{format_duration, output};
+    use chrono::Local;
+    use owo_colors::OwoColorize;
+
+    if as_json {
+        // The JSON is the command's output, so it is printed even in quiet mode
+        let runs: Vec<Value> = result.runs.iter().map(run_to_json).collect();
+        println!("{}", serde_json::to_string_pretty(&runs).unwrap_or_default());
+        return;
+    }
+
+    if result.runs.is_empty() {
+        output::info("No apply runs recorded");
+        return;
+    }
+
+    for run in &result.runs {
+        let marker = if run.succeeded {
+            output::symbol("✓").green().to_string()
+        } else {
+            output::symbol("✗").red().to_string()
+        };
+        let started_at: DateTime<Local> = run.started_at.into();
+        let by = match &run.os_user {
+            Some(os_user) => format!("{} ({})", run.db_user, os_user),
+            None => run.db_user.clone(),
+        };
+        output::line(format!(
+            "{} #{} {} by {} {}",
+            marker,
+            run.id,
+            started_at.format("%Y-%m-%d %H:%M:%S %Z"),
+            by,
+            format!("({})", format_duration(run.duration)).dimmed()
+        ));
+
+        let mut details = vec![format!("pgmg {}", run.pgmg_version)];
+        if let Some(environment) = &run.environment {
+            details.push(format!("environment {}", environment));
+        }
+        if let Some(commit) = &run.git_commit {
+            let dirty = if run.git_dirty == Some(true) { " with uncommitted changes" } else { "" };
+            details.push(format!("commit {}{}", &commit[..commit.len().min(12)], dirty));
+        }
+        if let Some(backup_path) = &run.backup_path {
+            details.push(format!("backup {}", backup_path));
+        }
+        output::line(format!("    {}", details.join(", ").dimmed()));
+
+        let changes = &run.changes;
+        let objects_changed = changes.objects_created.len() + changes.objects_updated.len() + changes.objects_deleted.len();
+        if changes.migrations_applied.is_empty() && objects_changed == 0 {
+            output::line(format!("    {}", "No changes".dimmed()));
+        } else {
+            output::line(format!(
+                "    {} migration(s), {} created, {} updated, {} deleted",
+                changes.migrations_applied.len(),
+                changes.objects_created.len(),
+                changes.objects_updated.len(),
+                changes.objects_deleted.len()
+            ));
+        }
+        for migration in &changes.migrations_applied {
+            output::line(format!("      migrated {}", migration.cyan()));
+        }
+        for error in &run.errors {
+            output::line(format!("    {}", error.lines().next().unwrap_or_default().red()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RunChanges;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_to_json() {
+        let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let run = RunRecord {
+            id: 7,
+            started_at,
+            finished_at: started_at + Duration::from_millis(2500),
+            db_user: "deployer".to_string(),
+            os_user: Some("ci".to_string()),
+            pgmg_version: "0.1.0".to_string(),
+            git_commit: Some("4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string()),
+            git_dirty: Some(false),
+            environment: Some("production".to_string()),
+            duration: Duration::from_millis(2500),
+            succeeded: true,
+            changes: RunChanges {
+                migrations_applied: vec!["004_invoices".to_string()],
+                ..RunChanges::default()
+            },
+            errors: Vec::new(),
+            backup_path: None,
+        };
+
+        let json = run_to_json(&run);
+        assert_eq!(json["started_at"], "2023-11-14T22:13:20+00:00");
+        assert_eq!(json["finished_at"], "2023-11-14T22:13:22.500+00:00");
+        assert_eq!(json["duration_ms"], 2500);
+        assert_eq!(json["changes"]["migrations_applied"], json!(["004_invoices"]));
+        assert_eq!(json["changes"]["objects_created"], json!([]));
+        assert_eq!(json["errors"], json!([]));
+        assert_eq!(json["backup_path"], json!(null));
+    }
+}
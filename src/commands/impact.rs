@@ -0,0 +1,367 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use crate::db::{StateManager, connect_with_url, scan_sql_files};
+use crate::sql::{matches_tags, Dependencies, SqlObject, ObjectType, QualifiedIdent};
+use crate::analysis::{DependencyGraph, ObjectRef, SearchPath};
+use crate::analysis::search_path::object_key;
+use crate::builtin_catalog::{BuiltinCatalog, DEFAULT_POSTGRES_VERSION};
+use tracing::debug;
+
+/// What apply would have to do with a dependent object if the target changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ImpactKind {
+    /// Reached only through hard dependencies: dropped and recreated
+    Recreate,
+    /// Reached through at least one soft dependency: re-checked, not recreated
+    Check,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImpactedObject {
+    pub object_type: ObjectType,
+    pub object_name: String,
+    /// Number of dependency hops from the target
+    pub depth: usize,
+    pub kind: ImpactKind,
+    /// The object this one depends on directly
+    pub via: String,
+}
+
+#[derive(Debug)]
+pub struct ImpactResult {
+    pub target: String,
+    /// Types of the managed objects matching the target (empty if the target
+    /// isn't managed by pgmg, e.g. a table created by a migration)
+    pub target_types: Vec<ObjectType>,
+    pub impacted: Vec<ImpactedObject>,
+    /// Whether stored dependencies from pgmg_dependencies were included
+    pub used_stored_dependencies: bool,
+}
+
+impl ImpactResult {
+    pub fn recreated(&self) -> impl Iterator<Item = &ImpactedObject> {
+        self.impacted.iter().filter(|obj| obj.kind == ImpactKind::Recreate)
+    }
+
+    pub fn checked(&self) -> impl Iterator<Item = &ImpactedObject> {
+        self.impacted.iter().filter(|obj| obj.kind == ImpactKind::Check)
+    }
+}
+
+/// List every object that transitively depends on `target`.
+///
+/// Dependencies are read from the code directory and, when a connection string
+/// is given, from pgmg_dependencies as well (which covers objects that were
+/// applied but have since been removed from files), and walked in the same
+/// dependency graph plan orders changes with. With `tags`, only the
+/// dependents tagged with one of them are listed, though the walk still
+/// passes through untagged ones. Unqualified names, the target's and those
+/// objects reference, are looked up through `search_path`.
+pub async fn execute_impact(
+    target: String,
+    code_dir: Option<PathBuf>,
+    connection_string: Option<String>,
+    tags: &[String],
+    search_path: &SearchPath,
+) -> Result<ImpactResult, Box<dyn std::error::Error>> {
+    let client = match &connection_string {
+        Some(conn_str) => {
+            let (client, connection) = connect_with_url(conn_str).await?;
+            connection.spawn();
+            Some(client)
+        }
+        None => None,
+    };
+
+    let builtin_catalog = match &client {
+        Some(client) => BuiltinCatalog::for_database(client).await?,
+        None => BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION),
+    };
+
+    let file_objects = match &code_dir {
+        Some(dir) if dir.exists() => scan_sql_files(dir, &builtin_catalog).await?,
+        _ => Vec::new(),
+    };
+
+    let stored = match &client {
+        Some(client) => {
+            let state_manager = StateManager::new(client);
+            state_manager.initialize().await?;
+            let tracked = state_manager.get_tracked_objects().await?;
+            let tracked_names: Vec<(ObjectType, String)> = tracked.iter()
+                .map(|record| (record.object_type.clone(), format_qualified_name(&record.object_name)))
+                .collect();
+            Some((state_manager.get_deleted_object_dependencies(&tracked_names).await?, tracked))
+        }
+        None => None,
+    };
+
+    if file_objects.is_empty() && stored.is_none() {
+        return Err("No SQL objects found. Provide --code-dir or a connection string".into());
+    }
+
+    let used_stored_dependencies = stored.is_some();
+    let (stored_dependencies, tracked) = stored.unwrap_or_default();
+
+    let known: Vec<QualifiedIdent> = file_objects.iter().map(|obj| obj.qualified_name.clone())
+        .chain(tracked.iter().map(|record| record.object_name.clone()))
+        .collect();
+    let target_key = search_path.qualify(&target, &known);
+    let target_types = file_objects.iter()
+        .filter(|obj| object_key(&obj.qualified_name) == target_key)
+        .map(|obj| obj.object_type.clone())
+        .collect();
+
+    let mut impacted = impacted_objects(&target_key, &file_objects, stored_dependencies, &builtin_catalog, search_path)?;
+    if !tags.is_empty() {
+        // Tags in code take precedence over those an object was last applied with
+        let mut object_tags: HashMap<String, &[String]> = tracked.iter()
+            .map(|record| (object_key(&record.object_name), record.tags.as_slice()))
+            .collect();
+        object_tags.extend(file_objects.iter()
+            .map(|obj| (object_key(&obj.qualified_name), obj.directives.tags.as_slice())));
+
+        impacted.retain(|obj| object_tags.get(&object_key(&QualifiedIdent::from_qualified_name(&obj.object_name)))
+            .is_some_and(|object_tags| matches_tags(object_tags, tags)));
+    }
+
+    Ok(ImpactResult {
+        impacted,
+        target,
+        target_types,
+        used_stored_dependencies,
+    })
+}
+
+/// The dependents of the object named `target_key`, from a graph built the
+/// way plan builds its ordering graph: the objects in files, with the
+/// dependencies they were last applied with merged in, and the objects only
+/// left in pgmg_dependencies.
+fn impacted_objects(
+    target_key: &str,
+    file_objects: &[SqlObject],
+    stored: Vec<(ObjectType, QualifiedIdent, Dependencies)>,
+    builtin_catalog: &BuiltinCatalog,
+    search_path: &SearchPath,
+) -> Result<Vec<ImpactedObject>, Box<dyn std::error::Error>> {
+    let mut objects = file_objects.to_vec();
+    for (object_type, name, deps) in stored {
+        let key = object_key(&name);
+        match objects.iter_mut().find(|obj| obj.object_type == object_type && object_key(&obj.qualified_name) == key) {
+            Some(obj) => {
+                obj.dependencies.relations.extend(deps.relations);
+                obj.dependencies.functions.extend(deps.functions);
+                obj.dependencies.types.extend(deps.types);
+            }
+            None => objects.push(SqlObject::new(object_type, name, String::new(), deps, None)),
+        }
+    }
+
+    // A target pgmg doesn't manage was created by a migration, as a relation
+    // or a function
+    if !objects.iter().any(|obj| object_key(&obj.qualified_name) == target_key) {
+        for object_type in [ObjectType::Table, ObjectType::Function] {
+            objects.push(SqlObject::new(
+                object_type,
+                QualifiedIdent::from_qualified_name(target_key),
+                String::new(),
+                Dependencies::default(),
+                None,
+            ));
+        }
+    }
+
+    let graph = DependencyGraph::build_with_search_path(&objects, builtin_catalog, search_path)?;
+    debug!("Impact analysis over {} dependency edges", graph.edge_count());
+
+    let targets: Vec<ObjectRef> = objects.iter()
+        .filter(|obj| object_key(&obj.qualified_name) == target_key)
+        .map(ObjectRef::from)
+        .collect();
+    Ok(walk_dependents(&graph, &targets))
+}
+
+/// Breadth-first walk over dependents. An object reached through any soft edge
+/// is only re-checked by apply; hard-only chains mean it is dropped and recreated.
+fn walk_dependents(graph: &DependencyGraph, targets: &[ObjectRef]) -> Vec<ImpactedObject> {
+    let mut impacted: HashMap<ObjectRef, ImpactedObject> = HashMap::new();
+    let mut queue: VecDeque<(ObjectRef, usize, ImpactKind)> = targets.iter()
+        .map(|target| (target.clone(), 0, ImpactKind::Recreate))
+        .collect();
+
+    while let Some((object_ref, depth, kind)) = queue.pop_front() {
+        let hard_dependents = graph.hard_dependents_of(&object_ref);
+        for dependent in graph.dependents_of(&object_ref) {
+            if targets.contains(&dependent) {
+                continue;
+            }
+
+            let edge_kind = if kind == ImpactKind::Recreate && hard_dependents.contains(&dependent) {
+                ImpactKind::Recreate
+            } else {
+                ImpactKind::Check
+            };
+
+            // Revisit only if we found a path that upgrades the object to Recreate
+            if let Some(existing) = impacted.get(&dependent) {
+                if existing.kind <= edge_kind {
+                    continue;
+                }
+            }
+
+            impacted.insert(dependent.clone(), ImpactedObject {
+                object_type: dependent.object_type.clone(),
+                object_name: format_qualified_name(&dependent.qualified_name),
+                depth: depth + 1,
+                kind: edge_kind,
+                via: format_qualified_name(&object_ref.qualified_name),
+            });
+            queue.push_back((dependent, depth + 1, edge_kind));
+        }
+    }
+
+    let mut result: Vec<ImpactedObject> = impacted.into_values().collect();
+    result.sort_by(|a, b| (a.kind, a.depth, &a.object_name).cmp(&(b.kind, b.depth, &b.object_name)));
+    result
+}
+
+fn format_qualified_name(qualified_name: &QualifiedIdent) -> String {
+    match &qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, qualified_name.name),
+        None => qualified_name.name.clone(),
+    }
+}
+
+#[cfg(feature = "cli")]
+pub fn print_impact_summary(result: &ImpactResult) {
+    use crate::logging::output;
+    use console::style;
+
+    let target_desc = if result.target_types.is_empty() {
+        "unmanaged object".to_string()
+    } else {
+        result.target_types.iter()
+            .map(|t| t.to_string().to_lowercase())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    output::line(format!("\n{} {} ({})",
+        style("Impact of changing").bold(),
+        style(&result.target).cyan().bold(),
+        style(target_desc).dim()
+    ));
+
+    if result.impacted.is_empty() {
+        output::line(format!("\n{}", style("Nothing depends on this object.").green()));
+        return;
+    }
+
+    let recreated: Vec<_> = result.recreated().collect();
+    if !recreated.is_empty() {
+        output::line(format!("\n{}:", style("Dropped and recreated (hard dependencies)").bold().yellow()));
+        for obj in &recreated {
+            output::line(format!("  {}{} {} {} {}",
+                "  ".repeat(obj.depth - 1),
+                style("~").yellow().bold(),
+                style(obj.object_type.to_string().to_lowercase()).dim(),
+                style(&obj.object_name).cyan(),
+                style(format!("(via {})", obj.via)).dim()
+            ));
+        }
+    }
+
+    let checked: Vec<_> = result.checked().collect();
+    if !checked.is_empty() {
+        output::line(format!("\n{}:", style("Re-checked only (soft dependencies)").bold().blue()));
+        for obj in &checked {
+            output::line(format!("  {}{} {} {} {}",
+                "  ".repeat(obj.depth - 1),
+                style("?").blue().bold(),
+                style(obj.object_type.to_string().to_lowercase()).dim(),
+                style(&obj.object_name).cyan(),
+                style(format!("(via {})", obj.via)).dim()
+            ));
+        }
+    }
+
+    output::line(format!("\n{} {} recreated, {} checked",
+        style("Summary:").bold(),
+        style(recreated.len()).yellow(),
+        style(checked.len()).blue()
+    ));
+
+    if !result.used_stored_dependencies {
+        output::line(format!("{}", style("Note: only SQL files were analyzed; pass a connection string to include pgmg_dependencies").dim()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(object_type: ObjectType, name: &str, relations: &[&str], functions: &[&str]) -> SqlObject {
+        let mut deps = Dependencies::default();
+        deps.relations.extend(relations.iter().map(|r| QualifiedIdent::from_qualified_name(r)));
+        deps.functions.extend(functions.iter().map(|f| QualifiedIdent::from_qualified_name(f)));
+        SqlObject::new(
+            object_type,
+            QualifiedIdent::from_qualified_name(name),
+            String::new(),
+            deps,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_walk_dependents_hard_and_soft() {
+        let objects = vec![
+            object(ObjectType::View, "api.orders_view", &["public.orders"], &[]),
+            object(ObjectType::Function, "api.order_total", &["api.orders_view"], &[]),
+            object(ObjectType::Function, "api.checkout", &[], &["api.order_total"]),
+            object(ObjectType::View, "api.products_view", &["public.products"], &[]),
+        ];
+
+        let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+        let impacted = impacted_objects("public.orders", &objects, Vec::new(), &builtin_catalog, &SearchPath::default()).unwrap();
+
+        let names: Vec<(&str, ImpactKind, usize)> = impacted.iter()
+            .map(|obj| (obj.object_name.as_str(), obj.kind, obj.depth))
+            .collect();
+        assert_eq!(names, vec![
+            ("api.orders_view", ImpactKind::Recreate, 1),
+            ("api.order_total", ImpactKind::Recreate, 2),
+            ("api.checkout", ImpactKind::Check, 3),
+        ]);
+        assert_eq!(impacted[1].via, "api.orders_view");
+    }
+
+    #[test]
+    fn test_stored_dependencies_are_included() {
+        let mut deps = Dependencies::default();
+        deps.relations.insert(QualifiedIdent::from_qualified_name("public.orders"));
+        let stored = vec![(ObjectType::Trigger, QualifiedIdent::from_qualified_name("audit_orders"), deps)];
+
+        let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+        let impacted = impacted_objects("public.orders", &[], stored, &builtin_catalog, &SearchPath::default()).unwrap();
+
+        assert_eq!(impacted.len(), 1);
+        assert_eq!(impacted[0].object_type, ObjectType::Trigger);
+        assert_eq!(impacted[0].kind, ImpactKind::Recreate);
+    }
+
+    #[test]
+    fn test_applied_dependencies_are_merged_into_file_objects() {
+        // The file no longer reads orders, but the applied view still does
+        let objects = vec![object(ObjectType::View, "api.orders_view", &[], &[])];
+        let mut deps = Dependencies::default();
+        deps.relations.insert(QualifiedIdent::from_qualified_name("orders"));
+        let stored = vec![(ObjectType::View, QualifiedIdent::from_qualified_name("api.orders_view"), deps)];
+
+        let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+        let impacted = impacted_objects("public.orders", &objects, stored, &builtin_catalog, &SearchPath::default()).unwrap();
+
+        assert_eq!(impacted.len(), 1);
+        assert_eq!(impacted[0].object_name, "api.orders_view");
+        assert_eq!(impacted[0].via, "public.orders");
+    }
+}
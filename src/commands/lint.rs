@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::PgmgConfig;
+use crate::db::scan_migrations;
+use crate::sql::lint::{apply_fixes, lint_code_file, lint_migration_file, LintFinding};
+
+#[derive(Debug)]
+pub struct LintFileResult {
+    pub path: PathBuf,
+    /// Findings left in the file (after fixing, with `fix`)
+    pub findings: Vec<LintFinding>,
+    /// Findings fixed in the file
+    pub fixed: usize,
+}
+
+#[derive(Debug)]
+pub struct LintResult {
+    /// Files with findings or fixes
+    pub files: Vec<LintFileResult>,
+    pub files_checked: usize,
+    /// Files that could not be read or parsed
+    pub errors: Vec<String>,
+}
+
+impl LintResult {
+    pub fn findings(&self) -> usize {
+        self.files.iter().map(|file| file.findings.len()).sum()
+    }
+
+    pub fn fixed(&self) -> usize {
+        self.files.iter().map(|file| file.fixed).sum()
+    }
+}
+
+/// Check the migration and code files against the `[lint]` rules without a
+/// database. With `fix`, findings with a trivial fix (a missing IF EXISTS /
+/// IF NOT EXISTS, an implicit VOLATILE) are fixed in place.
+pub async fn execute_lint(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    config: &PgmgConfig,
+    fix: bool,
+) -> Result<LintResult, Box<dyn std::error::Error>> {
+    let rules = config.lint_rules()?;
+    let mut result = LintResult {
+        files: Vec::new(),
+        files_checked: 0,
+        errors: Vec::new(),
+    };
+
+    let mut files: Vec<(PathBuf, bool)> = Vec::new();
+    if let Some(dir) = &migrations_dir {
+        files.extend(scan_migrations(dir).await?.into_iter().map(|migration| (migration.path, true)));
+    }
+    if let Some(dir) = &code_dir {
+        let mut code_files = Vec::new();
+        collect_code_files(dir, &mut code_files)?;
+        files.extend(code_files.into_iter().map(|path| (path, false)));
+    }
+
+    for (path, migration) in files {
+        result.files_checked += 1;
+        let lint = |content: &str| if migration {
+            lint_migration_file(content, &rules)
+        } else {
+            lint_code_file(content, &rules)
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                result.errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let mut findings = match lint(&content) {
+            Ok(findings) => findings,
+            Err(e) => {
+                result.errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        let mut fixed = 0;
+        if fix && findings.iter().any(|finding| finding.fix.is_some()) {
+            let fixed_content = apply_fixes(&content, &findings);
+            // Lint again for what's left, and so a fix that broke the file isn't written
+            let remaining = lint(&fixed_content)
+                .map_err(|e| format!("Fixing {} would leave it unparseable: {}", path.display(), e))?;
+            fs::write(&path, fixed_content)?;
+            fixed = findings.len().saturating_sub(remaining.len());
+            findings = remaining;
+        }
+
+        if !findings.is_empty() || fixed > 0 {
+            result.files.push(LintFileResult { path, findings, fixed });
+        }
+    }
+
+    Ok(result)
+}
+
+/// The .sql files under the code directory, leaving out pgTAP test files as
+/// the scanner does
+pub(crate) fn collect_code_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            collect_code_files(&path, files)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("sql")
+            && !path.file_name().and_then(|s| s.to_str()).is_some_and(|name| name.contains(".test.")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+pub fn print_lint_summary(result: &LintResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    for file in &result.files {
+        let path = match file.path.strip_prefix(std::env::current_dir().unwrap_or_default()) {
+            Ok(relative_path) => relative_path.display().to_string(),
+            Err(_) => file.path.display().to_string(),
+        };
+        for finding in &file.findings {
+            let location = match finding.line {
+                Some(line) => format!("{}:{}", path, line),
+                None => path.clone(),
+            };
+            let fixable = if finding.fix.is_some() { " (fixable with --fix)" } else { "" };
+            output::line(format!(
+                "{} {} {} {}{}",
+                output::symbol("✗").red(),
+                location.bright_blue(),
+                finding.message,
+                format!("[{}]", finding.rule).dimmed(),
+                fixable.dimmed()
+            ));
+        }
+        if file.fixed > 0 {
+            output::line(format!("{} {} fixed {} finding(s)", output::symbol("✓").green(), path.bright_blue(), file.fixed));
+        }
+    }
+
+    for error in &result.errors {
+        output::line(format!("{} {}", output::symbol("✗").red(), error.red()));
+    }
+
+    output::line("");
+    let findings = result.findings();
+    if findings == 0 && result.errors.is_empty() {
+        output::line(format!(
+            "{} {} files checked, no findings",
+            output::symbol("✓").green().bold(),
+            result.files_checked
+        ));
+    } else {
+        output::line(format!(
+            "{} {} findings in {} of {} files",
+            output::symbol("✗").red().bold(),
+            findings.to_string().red().bold(),
+            result.files.iter().filter(|file| !file.findings.is_empty()).count(),
+            result.files_checked
+        ));
+    }
+    if result.fixed() > 0 {
+        output::line(format!("{} {} fixed", output::symbol("✓").green(), result.fixed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_lint_fixes_files_in_place() {
+        let temp_dir = tempdir().unwrap();
+        let migrations_dir = temp_dir.path().join("migrations");
+        let code_dir = temp_dir.path().join("sql");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::create_dir_all(code_dir.join("functions")).unwrap();
+
+        fs::write(migrations_dir.join("001_orders.sql"), "CREATE TABLE public.orders (id integer, placed_at timestamp);\n").unwrap();
+        fs::write(code_dir.join("functions/one.sql"), "CREATE FUNCTION api.one() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql;\n").unwrap();
+        fs::write(code_dir.join("functions/one.test.sql"), "SELECT * FROM api.one();\n").unwrap();
+
+        let result = execute_lint(Some(migrations_dir.clone()), Some(code_dir.clone()), &PgmgConfig::default(), true).await.unwrap();
+
+        assert_eq!(result.files_checked, 2);
+        assert_eq!(result.fixed(), 2);
+        // The forbidden timestamp column has no automatic fix
+        assert_eq!(result.findings(), 1);
+        assert_eq!(
+            fs::read_to_string(migrations_dir.join("001_orders.sql")).unwrap(),
+            "CREATE TABLE IF NOT EXISTS public.orders (id integer, placed_at timestamp);\n"
+        );
+        assert_eq!(
+            fs::read_to_string(code_dir.join("functions/one.sql")).unwrap(),
+            "CREATE FUNCTION api.one() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql VOLATILE;\n"
+        );
+    }
+}
@@ -3,23 +3,67 @@ pub mod apply;
 pub mod watch;
 pub mod reset;
 pub mod test;
+pub mod test_report;
+pub mod test_db;
 pub mod seed;
 pub mod new;
 pub mod check;
 pub mod run;
+pub mod graph;
+pub mod impact;
+pub mod generate_migration;
+pub mod plan_offline;
+pub mod doctor;
+pub mod show;
+pub mod serve;
+pub mod snapshot;
+pub mod lint;
+pub mod fmt;
+pub mod refresh;
+pub mod partitions;
+pub mod wait;
+pub mod history;
+pub mod blame;
+pub mod support_bundle;
+pub mod unlock;
+pub mod status;
+#[cfg(feature = "simulate")]
+pub mod simulate;
 
-pub use plan::{execute_plan, PlanResult, ChangeOperation};
-pub use apply::{execute_apply, ApplyResult};
+pub use plan::{execute_plan, execute_plan_in_environment, execute_plan_with_options, execute_plan_offline, PlanOptions, collect_change_usage, migration_dependencies, validate_no_tables_in_code_dir, find_edited_migrations, PlanResult, ChangeOperation, EnumAddition, PublicationChange, CronJobChange, RelationSize};
+pub use apply::{execute_apply, execute_apply_with_cancellation, execute_apply_with_hooks, verify_replica, ApplyResult, ApplyHooks, ApplyPause, ApplyPhase, MatviewRefresh, MatviewRefreshStatus, SkippedStatement};
 pub use watch::{execute_watch, WatchConfig};
 pub use reset::{execute_reset, ResetResult};
-pub use test::{execute_test, execute_test_with_options, TestResult};
-pub use seed::{execute_seed, SeedResult};
+pub use test::{execute_test, execute_test_with_options, execute_changed_tests, execute_tagged_tests, ChangedSince, TestDatabaseMode, TestResult, TestFileResult, TestCase, TestStatus};
+pub use test_report::{write_test_reports, junit_xml, test_report_json, TestReport, ReportFormat};
+pub use test_db::{execute_test_db_gc, parse_ttl, TestDbGcResult};
+pub use seed::{execute_seed, execute_seed_reset, SeedResult, SeedOptions, SeedTransactionMode, SeedFileResult, SeedResetOptions, SeedResetResult, SEED_MANIFEST};
 pub use new::{execute_new, NewResult};
-pub use check::{execute_check, CheckResult};
+pub use check::{execute_check, execute_check_files, CheckResult};
 pub use run::{execute_run, run_sql_file};
+pub use graph::{execute_graph, GraphFormat, GraphFilter, GraphResult};
+pub use impact::{execute_impact, ImpactResult, ImpactedObject, ImpactKind};
+pub use generate_migration::{execute_generate_migration, GenerateMigrationResult, TableDiff};
+pub use plan_offline::{plan_offline, OfflinePlan, OfflineChange, OfflineChangeKind, EdgeChange};
+pub use doctor::{execute_doctor, DoctorResult, DoctorCheck, CheckStatus};
+pub use show::{execute_show, ShowResult, ShownObject, ShownDependency, ObjectStatus};
+pub use serve::{execute_serve, ServeConfig};
+pub use snapshot::{execute_snapshot_schema, execute_snapshot_export, normalize_schema_dump, SnapshotResult, SnapshotExportResult, SnapshotSource};
+pub use lint::{execute_lint, LintResult, LintFileResult};
+pub use fmt::{execute_fmt, FmtResult};
+pub use refresh::{execute_refresh, RefreshResult, RefreshedView};
+pub use partitions::{execute_partitions, MaintenanceSchedule, PartitionChange, PartitionsResult};
+pub use wait::{execute_wait_for_apply, CompletedApply};
+pub use history::{execute_history, run_to_json, HistoryResult};
+pub use blame::{execute_blame, BlameResult, BlamedObject};
+pub use support_bundle::{execute_support_bundle, SupportBundleResult};
+pub use unlock::{execute_unlock, UnlockResult};
+pub use status::{execute_status, StatusResult};
+#[cfg(feature = "simulate")]
+pub use simulate::{execute_simulate_concurrency, print_simulation_summary, Interleaving, SimulationResult};
 
 #[cfg(feature = "cli")]
-pub use plan::print_plan_summary;
+pub use plan::{print_plan_summary, print_usage_report};
 #[cfg(feature = "cli")]
 pub use apply::print_apply_summary;
 #[cfg(feature = "cli")]
@@ -27,8 +71,42 @@ pub use reset::print_reset_summary;
 #[cfg(feature = "cli")]
 pub use test::print_test_summary;
 #[cfg(feature = "cli")]
-pub use seed::print_seed_summary;
+pub use test_db::print_test_db_gc_summary;
+#[cfg(feature = "cli")]
+pub use seed::{print_seed_summary, print_seed_reset_summary};
 #[cfg(feature = "cli")]
 pub use new::print_new_summary;
 #[cfg(feature = "cli")]
-pub use check::print_check_summary;
\ No newline at end of file
+pub use check::print_check_summary;
+#[cfg(feature = "cli")]
+pub use graph::print_graph_summary;
+#[cfg(feature = "cli")]
+pub use impact::print_impact_summary;
+#[cfg(feature = "cli")]
+pub use generate_migration::print_generate_migration_summary;
+#[cfg(feature = "cli")]
+pub use doctor::print_doctor_summary;
+#[cfg(feature = "cli")]
+pub use show::print_show_summary;
+#[cfg(feature = "cli")]
+pub use snapshot::{print_snapshot_summary, print_snapshot_export_summary};
+#[cfg(feature = "cli")]
+pub use lint::print_lint_summary;
+#[cfg(feature = "cli")]
+pub use fmt::print_fmt_summary;
+#[cfg(feature = "cli")]
+pub use refresh::print_refresh_summary;
+#[cfg(feature = "cli")]
+pub use partitions::print_partitions_summary;
+#[cfg(feature = "cli")]
+pub use wait::print_wait_summary;
+#[cfg(feature = "cli")]
+pub use history::print_history_summary;
+#[cfg(feature = "cli")]
+pub use blame::print_blame_summary;
+#[cfg(feature = "cli")]
+pub use support_bundle::print_support_bundle_summary;
+#[cfg(feature = "cli")]
+pub use unlock::print_unlock_summary;
+#[cfg(feature = "cli")]
+pub use status::print_status_summary;
@@ -1,8 +1,9 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::{self, Write};
 use chrono::{Utc, DateTime};
 use owo_colors::OwoColorize;
+use crate::logging::output;
 use crate::config::PgmgConfig;
 
 #[derive(Debug)]
@@ -24,10 +25,10 @@ pub async fn execute_new(
     // Ensure migrations directory exists
     if !migrations_dir.exists() {
         fs::create_dir_all(&migrations_dir)?;
-        println!("{} Created migrations directory: {}",
-            "✓".green().bold(),
+        output::line(format!("{} Created migrations directory: {}",
+            output::symbol("✓").green().bold(),
             migrations_dir.display().to_string().cyan()
-        );
+        ));
     }
 
     // Get migration name from argument or prompt interactively
@@ -44,6 +45,38 @@ pub async fn execute_new(
     };
     let migration_name = input_name.trim();
 
+    let now: DateTime<Utc> = Utc::now();
+    let (migration_filename, migration_path) = new_migration_path(&migrations_dir, migration_name, now)?;
+
+    // Create empty migration file with helpful comment
+    let migration_content = format!(
+        "-- Migration: {}\n-- Created: {}\n\n-- Add your migration SQL here\n\n",
+        migration_name,
+        now.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+
+    fs::write(&migration_path, migration_content)?;
+
+    let result = NewResult {
+        migration_file: migration_filename.clone(),
+        migration_path: migration_path.clone(),
+    };
+
+    output::line(format!("{} Created migration: {}", 
+        output::symbol("✓").green().bold(), 
+        migration_filename.cyan()
+    ));
+    output::line(format!("  Path: {}", migration_path.display().to_string().dimmed()));
+
+    Ok(result)
+}
+
+/// Validate a migration name and build its timestamped file name and path
+pub(crate) fn new_migration_path(
+    migrations_dir: &Path,
+    migration_name: &str,
+    now: DateTime<Utc>,
+) -> Result<(String, PathBuf), Box<dyn std::error::Error>> {
     if migration_name.is_empty() {
         return Err("Migration name cannot be empty".into());
     }
@@ -54,7 +87,6 @@ pub async fn execute_new(
     }
 
     // Generate timestamp
-    let now: DateTime<Utc> = Utc::now();
     let timestamp = now.format("%Y%m%d%H%M%S").to_string();
     
     // Create migration filename
@@ -66,36 +98,16 @@ pub async fn execute_new(
         return Err(format!("Migration file already exists: {}", migration_path.display()).into());
     }
 
-    // Create empty migration file with helpful comment
-    let migration_content = format!(
-        "-- Migration: {}\n-- Created: {}\n\n-- Add your migration SQL here\n\n",
-        migration_name,
-        now.format("%Y-%m-%d %H:%M:%S UTC")
-    );
-
-    fs::write(&migration_path, migration_content)?;
-
-    let result = NewResult {
-        migration_file: migration_filename.clone(),
-        migration_path: migration_path.clone(),
-    };
-
-    println!("{} Created migration: {}", 
-        "✓".green().bold(), 
-        migration_filename.cyan()
-    );
-    println!("  Path: {}", migration_path.display().to_string().dimmed());
-
-    Ok(result)
+    Ok((migration_filename, migration_path))
 }
 
 pub fn print_new_summary(result: &NewResult) {
-    println!("\n{}", "=== PGMG New Migration Summary ===".bold().blue());
-    println!("\n{}:", "Migration Created".bold().green());
-    println!("  {} {}", "File:".bold(), result.migration_file.cyan());
-    println!("  {} {}", "Path:".bold(), result.migration_path.display().to_string().dimmed());
-    println!("\n{} {}", 
-        "✓".green().bold(), 
+    output::line(format!("\n{}", "=== PGMG New Migration Summary ===".bold().blue()));
+    output::line(format!("\n{}:", "Migration Created".bold().green()));
+    output::line(format!("  {} {}", "File:".bold(), result.migration_file.cyan()));
+    output::line(format!("  {} {}", "Path:".bold(), result.migration_path.display().to_string().dimmed()));
+    output::line(format!("\n{} {}", 
+        output::symbol("✓").green().bold(), 
         "Migration file created successfully. You can now edit it and run 'pgmg apply' to apply the changes.".green()
-    );
+    ));
 }
\ No newline at end of file
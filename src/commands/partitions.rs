@@ -0,0 +1,186 @@
+use crate::config::PgmgConfig;
+use crate::db::{StateManager, connect_with_url};
+use crate::sql::{maintenance_command, PartitionMaintenance};
+use crate::sql::partitions::MAINTENANCE_JOB_NAME;
+use crate::sql::quote::quote_qualified_ident;
+use tracing::debug;
+
+/// One partition `pgmg partitions` created or removed
+#[derive(Debug, Clone)]
+pub struct PartitionChange {
+    /// The partitioned table
+    pub table: String,
+    pub partition: String,
+    /// create, detach or drop
+    pub action: String,
+    pub statement: String,
+}
+
+/// What happened to the pg_cron job running the maintenance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaintenanceSchedule {
+    /// No schedule is configured and there was no job to remove, or this was
+    /// a dry run
+    NotScheduled,
+    /// The job was created or updated with this schedule
+    Scheduled(String),
+    /// The schedule was removed from pgmg.toml, so the job was too
+    Unscheduled,
+    /// A schedule is configured but pg_cron isn't installed
+    CronUnavailable,
+}
+
+#[derive(Debug)]
+pub struct PartitionsResult {
+    /// Changes made, or only planned with `dry_run`
+    pub changes: Vec<PartitionChange>,
+    /// The change that failed and the error. Maintenance stops there.
+    pub failed: Option<(PartitionChange, String)>,
+    pub schedule: MaintenanceSchedule,
+    pub dry_run: bool,
+}
+
+/// Create the partitions each table in `[[partitions.tables]]` needs ahead of
+/// time and detach or drop those past their retention. Each change is made
+/// on its own, so the parent table is only locked briefly each time.
+///
+/// With `[partitions] schedule` set and pg_cron installed, the same work is
+/// also scheduled as a pg_cron job in this database, replacing the job's
+/// command each time so it follows the config.
+pub async fn execute_partitions(
+    connection_string: String,
+    config: &PgmgConfig,
+    dry_run: bool,
+) -> Result<PartitionsResult, Box<dyn std::error::Error>> {
+    let maintenance = config.partition_maintenance()?;
+    if maintenance.tables.is_empty() {
+        return Err("No partitioned tables to maintain. Add [[partitions.tables]] to pgmg.toml".into());
+    }
+
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    // The maintenance query leaves partitions declared in code to apply
+    let state_manager = StateManager::new(&client);
+    state_manager.initialize().await?;
+
+    let mut result = PartitionsResult {
+        changes: Vec::new(),
+        failed: None,
+        schedule: MaintenanceSchedule::NotScheduled,
+        dry_run,
+    };
+
+    for table in &maintenance.tables {
+        let table_name = quote_qualified_ident(&table.table);
+        let rows = client.query(&table.maintenance_query(), &[]).await
+            .map_err(|e| format!("Failed to plan partitions of {}: {}", table_name, e))?;
+
+        for row in rows {
+            let change = PartitionChange {
+                table: table_name.clone(),
+                action: row.get("action"),
+                partition: row.get("partition"),
+                statement: row.get("statement"),
+            };
+            if dry_run {
+                result.changes.push(change);
+                continue;
+            }
+
+            debug!(sql = %change.statement, "Maintaining partition");
+            match client.batch_execute(&change.statement).await {
+                Ok(()) => result.changes.push(change),
+                Err(e) => {
+                    result.failed = Some((change, e.to_string()));
+                    return Ok(result);
+                }
+            }
+        }
+    }
+
+    if !dry_run {
+        result.schedule = update_maintenance_job(&client, &maintenance).await?;
+    }
+
+    Ok(result)
+}
+
+/// Schedule the maintenance with pg_cron, or unschedule it when no schedule
+/// is configured any more
+async fn update_maintenance_job(
+    client: &tokio_postgres::Client,
+    maintenance: &PartitionMaintenance,
+) -> Result<MaintenanceSchedule, Box<dyn std::error::Error>> {
+    let cron_installed: bool = client.query_one(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_cron')",
+        &[],
+    ).await?.get(0);
+
+    match (&maintenance.schedule, cron_installed) {
+        (Some(_), false) => Ok(MaintenanceSchedule::CronUnavailable),
+        (Some(schedule), true) => {
+            // Jobs run in the database pg_cron is installed in unless told
+            // otherwise. Scheduling under an existing name updates that job.
+            client.query_one(
+                "SELECT cron.schedule_in_database($1, $2, $3, current_database())",
+                &[&MAINTENANCE_JOB_NAME, schedule, &maintenance_command(&maintenance.tables)],
+            ).await?;
+            Ok(MaintenanceSchedule::Scheduled(schedule.clone()))
+        }
+        (None, false) => Ok(MaintenanceSchedule::NotScheduled),
+        (None, true) => {
+            let removed = client.query(
+                "SELECT cron.unschedule(jobid) FROM cron.job WHERE jobname = $1",
+                &[&MAINTENANCE_JOB_NAME],
+            ).await?;
+            Ok(if removed.is_empty() { MaintenanceSchedule::NotScheduled } else { MaintenanceSchedule::Unscheduled })
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+pub fn print_partitions_summary(result: &PartitionsResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    for change in &result.changes {
+        let (symbol, action) = match change.action.as_str() {
+            "create" => ("+".green().bold().to_string(), "CREATE".green().bold().to_string()),
+            action => ("-".red().bold().to_string(), action.to_uppercase().red().bold().to_string()),
+        };
+        output::line(format!(
+            "  {} {} {} {}",
+            symbol,
+            action,
+            change.partition.bright_blue(),
+            format!("({})", change.table).dimmed()
+        ));
+    }
+
+    if let Some((change, error)) = &result.failed {
+        output::error(format!("Failed to {} {}: {}", change.action, change.partition, error));
+        return;
+    }
+
+    if result.changes.is_empty() {
+        output::success("Partitions are up to date");
+    } else if result.dry_run {
+        output::info(format!("{} partition change(s) to make (dry run)", result.changes.len()));
+    } else {
+        output::info(format!("Made {} partition change(s)", result.changes.len()));
+    }
+
+    match &result.schedule {
+        MaintenanceSchedule::NotScheduled => {}
+        MaintenanceSchedule::Scheduled(schedule) => {
+            output::info(format!("pg_cron job {} runs the maintenance on \"{}\"", MAINTENANCE_JOB_NAME, schedule));
+        }
+        MaintenanceSchedule::Unscheduled => {
+            output::info(format!("Removed pg_cron job {}, as no schedule is configured", MAINTENANCE_JOB_NAME));
+        }
+        MaintenanceSchedule::CronUnavailable => {
+            output::warning("A maintenance schedule is configured but pg_cron isn't installed: run pgmg partitions on a schedule instead");
+        }
+    }
+}
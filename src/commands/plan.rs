@@ -1,11 +1,16 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet};
-use crate::db::{StateManager, connect_with_url, scan_sql_files, scan_migrations};
-use crate::sql::{SqlObject, ObjectType, QualifiedIdent, objects::calculate_ddl_hash, extract_altered_tables};
-use crate::analysis::{DependencyGraph, ObjectRef};
+use crate::db::{StateManager, StateSnapshot, ObjectRecord, migration_content_hash, connect_with_url, scan_sql_files, scan_sql_files_with_options, ScanOptions, scan_migrations, ObjectUsage, collect_object_usage, is_pg_stat_statements_available};
+use crate::sql::{ColumnReference, SqlObject, ObjectType, PartitionRemoval, QualifiedIdent, SchemaFilter, SqlVars, TenantTemplate, substitute_vars, partition_parent, enum_value_additions, publication_changes, cron_job_changes, extract_enum_values, extract_altered_tables, extract_altered_columns, extract_created_objects, extract_destructive_operations, matches_tags, parse_object_directives, format::formatting_fingerprint};
+use crate::config::{environment_from_env, PgmgConfig};
+use crate::hooks::{HookContext, HookEvent, Hooks};
+use crate::analysis::{DependencyGraph, ObjectRef, SearchPath};
 use crate::BuiltinCatalog;
+use crate::diff::DiffLine;
 #[cfg(feature = "cli")]
 use owo_colors::OwoColorize;
+#[cfg(feature = "cli")]
+use crate::logging::output;
 use tracing::{debug, info};
 
 #[derive(Debug)]
@@ -14,6 +19,127 @@ pub struct PlanResult {
     pub new_migrations: Vec<String>,
     pub dependency_graph: Option<DependencyGraph>,
     pub file_objects: Vec<SqlObject>,
+    /// Order in which created/updated objects will be applied. Deterministic for
+    /// a given set of files, so the same plan always applies in the same order.
+    pub apply_order: Vec<ObjectRef>,
+    /// Objects created by pending migrations, with the name of the migration
+    /// creating them. Managed objects may depend on these.
+    pub migration_objects: Vec<(String, SqlObject)>,
+    /// Objects whose code only changed in whitespace or comments, with
+    /// `ignore_formatting_changes`. Apply records their new source without
+    /// recreating them.
+    pub formatting_only: Vec<SqlObject>,
+    /// Enum types whose code only adds values. Apply adds them with
+    /// `ALTER TYPE ... ADD VALUE` instead of recreating the type.
+    pub enum_additions: Vec<EnumAddition>,
+    /// Publications whose code only changes the tables or schemas they
+    /// publish. Apply alters them instead of recreating them.
+    pub publication_changes: Vec<PublicationChange>,
+    /// Cron jobs whose code changes their schedule or command, or only how
+    /// the job is written. Apply alters them instead of rescheduling them.
+    pub cron_job_changes: Vec<CronJobChange>,
+    /// Relations the plan drops or rewrites, with their size, largest first
+    pub relation_sizes: Vec<RelationSize>,
+}
+
+impl PlanResult {
+    /// Whether applying the plan would change anything: migrations to run,
+    /// objects to create, update or delete, or enum values, published tables
+    /// or cron jobs to alter
+    pub fn has_changes(&self) -> bool {
+        !self.new_migrations.is_empty()
+            || !self.changes.is_empty()
+            || !self.enum_additions.is_empty()
+            || !self.publication_changes.is_empty()
+            || !self.cron_job_changes.is_empty()
+    }
+}
+
+/// Values added to an enum type in code
+#[derive(Debug, Clone)]
+pub struct EnumAddition {
+    pub object: SqlObject,
+    /// `ALTER TYPE ... ADD VALUE` statements, in the order to run them. Empty
+    /// if the database already has the values, e.g. added by a migration.
+    pub statements: Vec<String>,
+}
+
+/// Tables and schemas added to or dropped from a publication in code
+#[derive(Debug, Clone)]
+pub struct PublicationChange {
+    pub object: SqlObject,
+    /// `ALTER PUBLICATION ... ADD/DROP` statements, in the order to run them.
+    /// Empty if the same tables are published, e.g. only listed differently.
+    pub statements: Vec<String>,
+}
+
+/// A change to the schedule or command of a cron job in code
+#[derive(Debug, Clone)]
+pub struct CronJobChange {
+    pub object: SqlObject,
+    /// The `cron.alter_job` statement making the change. Empty if the job
+    /// schedules the same command at the same times, e.g. only reformatted.
+    pub statements: Vec<String>,
+}
+
+/// A relation dropped or rewritten by the plan and its size on disk
+#[derive(Debug, Clone)]
+pub struct RelationSize {
+    /// What the plan does to it, e.g. "DROP MATERIALIZED VIEW"
+    pub operation: String,
+    pub relation: String,
+    /// Including indexes and TOAST data, as pg_total_relation_size reports it
+    pub bytes: u64,
+}
+
+impl RelationSize {
+    pub fn describe(&self) -> String {
+        format!("{} {} {} {}", self.operation, self.relation, crate::logging::output::symbol("—"), crate::logging::format_size(self.bytes))
+    }
+}
+
+/// Settings that change what a plan contains
+#[derive(Debug, Clone, Default)]
+pub struct PlanOptions {
+    /// Objects marked `-- pgmg:skip-env` for this environment are left out
+    pub environment: Option<String>,
+    /// Report edits to whitespace and comments alone as no-ops
+    pub ignore_formatting: bool,
+    /// Only create, update and drop objects with one of these
+    /// `-- pgmg:tags`; all objects if empty
+    pub tags: Vec<String>,
+    /// Objects outside these schemas are left alone
+    pub schemas: SchemaFilter,
+    /// Code files using the tenant placeholder are planned once per tenant
+    pub tenants: Option<TenantTemplate>,
+    /// Values for `${NAME}` variables in code files and pending migrations
+    pub vars: Option<SqlVars>,
+    /// `pre_plan` and `post_plan` hooks, run by [`execute_plan_with_options`]
+    pub hooks: Hooks,
+    /// Schemas unqualified references between objects resolve through
+    pub search_path: SearchPath,
+    /// Whether partitions removed from code are detached or dropped
+    pub partition_removal: PartitionRemoval,
+    /// Plan CREATE TABLE in the code directory instead of refusing it
+    pub allow_tables_in_code_dir: bool,
+}
+
+impl PlanOptions {
+    pub fn from_config(config: &PgmgConfig) -> Self {
+        Self {
+            environment: config.environment_name(),
+            ignore_formatting: config.ignore_formatting_changes.unwrap_or(false),
+            tags: config.tags.clone().unwrap_or_default(),
+            schemas: config.schema_filter(),
+            tenants: config.tenant_template(),
+            vars: config.sql_vars(),
+            hooks: config.hooks(),
+            search_path: config.search_path(),
+            // An invalid value is reported by apply, the only place it matters
+            partition_removal: config.partition_removal().unwrap_or_default(),
+            allow_tables_in_code_dir: config.allow_tables_in_code_dir.unwrap_or(false),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -27,11 +153,24 @@ pub enum ChangeOperation {
         old_hash: String,
         new_hash: String,
         reason: String,
+        /// The DDL the object was last applied with, if pgmg stored it. `None`
+        /// for objects recreated because something they depend on changed.
+        old_ddl: Option<String>,
+        /// Whether the object is only recreated because something it depends
+        /// on is, rather than for a change of its own
+        cascade: bool,
     },
     DeleteObject {
         object_type: ObjectType,
         object_name: String,
         reason: String,
+        /// The object was applied with `-- pgmg:no-drop`: it is left in the
+        /// database and only stops being tracked
+        no_drop: bool,
+        /// The object is a partition removed from code with
+        /// `partition_removal = "detach"`: it is detached from this parent
+        /// and left in the database instead of being dropped
+        detach_from: Option<String>,
     },
     ApplyMigration {
         name: String,
@@ -44,37 +183,189 @@ pub async fn execute_plan(
     code_dir: Option<PathBuf>, 
     connection_string: String,
     output_graph: Option<PathBuf>,
-) -> Result<PlanResult, Box<dyn std::error::Error>> {
+) -> crate::error::Result<PlanResult> {
+    execute_plan_in_environment(
+        migrations_dir,
+        code_dir,
+        connection_string,
+        output_graph,
+        environment_from_env().as_deref(),
+    ).await
+}
+
+/// Plan for the named environment, leaving out objects marked
+/// `-- pgmg:skip-env` for it
+pub async fn execute_plan_in_environment(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    connection_string: String,
+    output_graph: Option<PathBuf>,
+    environment: Option<&str>,
+) -> crate::error::Result<PlanResult> {
+    let options = PlanOptions {
+        environment: environment.map(str::to_string),
+        ..PlanOptions::default()
+    };
+    execute_plan_with_options(migrations_dir, code_dir, connection_string, output_graph, &options).await
+}
+
+pub async fn execute_plan_with_options(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    connection_string: String,
+    output_graph: Option<PathBuf>,
+    options: &PlanOptions,
+) -> crate::error::Result<PlanResult> {
     // Connect to database
     let (client, connection) = connect_with_url(&connection_string).await?;
     
     // Spawn connection handler
     connection.spawn();
 
+    options.hooks.run(HookEvent::PrePlan, &client, &HookContext::default()).await?;
+    let plan_result = execute_plan_with_client(&client, migrations_dir, code_dir, output_graph, options).await?;
+    options.hooks.run(HookEvent::PostPlan, &client, &HookContext::planned(&plan_result)).await?;
+
+    Ok(plan_result)
+}
+
+/// Plan against a snapshot exported with `pgmg snapshot export` instead of the
+/// database, e.g. where the database can't be reached. Migration ordering,
+/// dependencies and parse errors are checked as in [`execute_plan_with_options`],
+/// but the plan is only as current as the snapshot. Hooks aren't run, and
+/// the sizes of relations the plan would drop are unknown.
+pub async fn execute_plan_offline(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    snapshot: &StateSnapshot,
+    output_graph: Option<PathBuf>,
+    options: &PlanOptions,
+) -> crate::error::Result<PlanResult> {
+    Ok(plan_against(&PlanSource::Snapshot(snapshot), migrations_dir, code_dir, output_graph, options).await?)
+}
+
+/// Plan against a connection that is already open, e.g. the one apply holds
+/// its advisory lock on
+pub(crate) async fn execute_plan_with_client(
+    client: &tokio_postgres::Client,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    output_graph: Option<PathBuf>,
+    options: &PlanOptions,
+) -> Result<PlanResult, Box<dyn std::error::Error>> {
     // Initialize state tracking
-    let state_manager = StateManager::new(&client);
-    state_manager.initialize().await?;
+    StateManager::new(client).initialize().await?;
 
-    let builtin_catalog = BuiltinCatalog::from_database(&client).await?;
+    plan_against(&PlanSource::Database(client), migrations_dir, code_dir, output_graph, options).await
+}
+
+/// Where a plan reads the database's state from
+enum PlanSource<'a> {
+    Database(&'a tokio_postgres::Client),
+    Snapshot(&'a StateSnapshot),
+}
+
+impl PlanSource<'_> {
+    async fn builtin_catalog(&self) -> Result<BuiltinCatalog, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => BuiltinCatalog::for_database(client).await,
+            PlanSource::Snapshot(snapshot) => Ok(snapshot.builtin_catalog.clone()),
+        }
+    }
+
+    async fn applied_migration_names(&self) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => StateManager::new(client).get_applied_migration_names().await,
+            PlanSource::Snapshot(snapshot) => Ok(snapshot.applied_migration_names()),
+        }
+    }
+
+    async fn tracked_objects(&self) -> Result<Vec<ObjectRecord>, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => StateManager::new(client).get_tracked_objects().await,
+            PlanSource::Snapshot(snapshot) => Ok(snapshot.objects.clone()),
+        }
+    }
+
+    async fn owned_sequences(&self, tables: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => StateManager::new(client).find_owned_sequences(tables).await,
+            PlanSource::Snapshot(snapshot) => Ok(snapshot.owned_sequences(tables)),
+        }
+    }
+
+    async fn dependents_of_relations(&self, relations: &[String]) -> Result<Vec<(ObjectType, String)>, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => StateManager::new(client).find_dependents_of_relations(relations).await,
+            PlanSource::Snapshot(snapshot) => Ok(snapshot.dependents_of_relations(relations)),
+        }
+    }
+
+    /// Views and functions reading the columns. A snapshot doesn't have
+    /// PostgreSQL's record of them, so only the parsed code is searched.
+    async fn column_dependents(
+        &self,
+        columns: &[ColumnReference],
+    ) -> Result<Vec<(ObjectType, String, ColumnReference)>, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => StateManager::new(client).find_column_dependents(columns).await,
+            PlanSource::Snapshot(_) => Ok(Vec::new()),
+        }
+    }
+
+    async fn object_dependencies(
+        &self,
+        objects: &[(ObjectType, String)],
+    ) -> Result<Vec<(ObjectType, QualifiedIdent, crate::sql::Dependencies)>, Box<dyn std::error::Error>> {
+        match self {
+            PlanSource::Database(client) => StateManager::new(client).get_deleted_object_dependencies(objects).await,
+            PlanSource::Snapshot(snapshot) => Ok(snapshot.object_dependencies(objects)),
+        }
+    }
+}
+
+async fn plan_against(
+    source: &PlanSource<'_>,
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    output_graph: Option<PathBuf>,
+    options: &PlanOptions,
+) -> Result<PlanResult, Box<dyn std::error::Error>> {
+    let builtin_catalog = source.builtin_catalog().await?;
     
     let mut plan_result = PlanResult {
         changes: Vec::new(),
         new_migrations: Vec::new(),
         dependency_graph: None,
         file_objects: Vec::new(),
+        apply_order: Vec::new(),
+        migration_objects: Vec::new(),
+        formatting_only: Vec::new(),
+        enum_additions: Vec::new(),
+        publication_changes: Vec::new(),
+        cron_job_changes: Vec::new(),
+        relation_sizes: Vec::new(),
     };
 
     // Step 1: Check for new migrations
     if let Some(migrations_dir) = &migrations_dir {
         plan_result.new_migrations = check_new_migrations(
             migrations_dir, 
-            &state_manager
+            source
         ).await?;
         
         for migration_name in &plan_result.new_migrations {
             // Read migration content for the plan
             let migration_path = migrations_dir.join(format!("{}.sql", migration_name));
             if let Ok(content) = std::fs::read_to_string(&migration_path) {
+                let content = substitute_vars(options.vars.as_ref(), &migration_path, content)?;
+                match extract_created_objects(&content) {
+                    Ok(objects) => plan_result.migration_objects.extend(
+                        objects.into_iter().map(|obj| (migration_name.clone(), obj))
+                    ),
+                    Err(e) => debug!("Could not analyze migration {} for created objects: {}", migration_name, e),
+                }
+
                 plan_result.changes.push(ChangeOperation::ApplyMigration {
                     name: migration_name.clone(),
                     content,
@@ -85,14 +376,32 @@ pub async fn execute_plan(
 
     // Step 2: Analyze code directory for object changes
     if let Some(code_dir) = &code_dir {
-        let file_objects = scan_sql_files(code_dir, &builtin_catalog).await?;
+        let tenants = match &options.tenants {
+            Some(tenants) => Some(resolve_tenant_schemas(source, tenants).await?),
+            None => None,
+        };
+        let scan_options = ScanOptions { tenants: tenants.as_ref(), vars: options.vars.as_ref() };
+        let file_objects = scan_sql_files_with_options(code_dir, &builtin_catalog, &scan_options).await?;
         
         // Check for duplicate object names in files
         validate_no_duplicate_objects_in_files(&file_objects)?;
+        validate_no_tables_in_code_dir(&file_objects, options.allow_tables_in_code_dir)
+            .map_err(crate::error::PgmgError::Configuration)?;
         
-        let db_objects = state_manager.get_tracked_objects().await?;
+        let db_objects = source.tracked_objects().await?;
         
-        let mut object_changes = detect_object_changes(&file_objects, &db_objects).await?;
+        let mut object_changes = detect_object_changes(&file_objects, &db_objects, options.partition_removal).await?;
+        let file_objects = skip_environment_objects(file_objects, &mut object_changes, options.environment.as_deref());
+        let file_objects = skip_unmanaged_schemas(file_objects, &mut object_changes, &db_objects, &options.schemas);
+        if !options.tags.is_empty() {
+            retain_tagged_changes(&mut object_changes, &db_objects, &options.tags);
+        }
+        if options.ignore_formatting {
+            plan_result.formatting_only = take_formatting_changes(&mut object_changes, &db_objects);
+        }
+        plan_result.enum_additions = take_enum_additions(source, &mut object_changes).await?;
+        plan_result.publication_changes = take_publication_changes(&mut object_changes, &db_objects);
+        plan_result.cron_job_changes = take_cron_job_changes(&mut object_changes, &db_objects);
 
         // Store file objects in the result
         plan_result.file_objects = file_objects.clone();
@@ -102,13 +411,37 @@ pub async fn execute_plan(
         if !plan_result.new_migrations.is_empty() {
             if let Some(migrations_dir) = &migrations_dir {
                 let mut affected_tables: HashSet<QualifiedIdent> = HashSet::new();
+                let mut altered_columns: Vec<ColumnReference> = Vec::new();
 
                 for migration_name in &plan_result.new_migrations {
                     let path = migrations_dir.join(format!("{}.sql", migration_name));
                     if let Ok(content) = std::fs::read_to_string(&path) {
+                        let content = substitute_vars(options.vars.as_ref(), &path, content)?;
                         if let Ok(tables) = extract_altered_tables(&content) {
                             affected_tables.extend(tables);
                         }
+                        if let Ok(columns) = extract_altered_columns(&content) {
+                            altered_columns.extend(columns);
+                        }
+                    }
+                }
+
+                // Views and functions reading a column a migration drops or
+                // retypes would stop it running, however they name the table.
+                // Checked first, so the plan names the column.
+                if !altered_columns.is_empty() {
+                    let mut readers = source.column_dependents(&altered_columns).await?;
+                    readers.extend(column_readers(&file_objects, &altered_columns));
+                    debug!("Found {} managed objects reading altered columns", readers.len());
+
+                    for (obj_type, obj_name, column) in readers {
+                        recreate_for_migration(
+                            &mut object_changes,
+                            &file_objects,
+                            obj_type,
+                            &QualifiedIdent::from_qualified_name(&obj_name),
+                            format!("Migration drops or changes the type of {}", column),
+                        );
                     }
                 }
 
@@ -119,51 +452,34 @@ pub async fn execute_plan(
                     }
 
                     // Convert to "schema.table" format for database query
-                    let table_names: Vec<String> = affected_tables.iter()
+                    let mut table_names: Vec<String> = affected_tables.iter()
                         .map(|t| format_qualified_name(t))
                         .collect();
 
+                    // Sequences of serial and identity columns change with their
+                    // table, and objects calling nextval() on them depend on it
+                    let owned_sequences = source.owned_sequences(&table_names).await?;
+                    for sequence in &owned_sequences {
+                        debug!("  - {} (owned sequence)", sequence);
+                    }
+                    table_names.extend(owned_sequences);
+
                     // Find all managed objects depending on these tables
-                    let dependents = state_manager
-                        .find_dependents_of_relations(&table_names)
+                    let dependents = source
+                        .dependents_of_relations(&table_names)
                         .await?;
 
                     debug!("Found {} managed objects depending on altered tables", dependents.len());
 
                     // Add these as UpdateObject operations (to trigger pre-drop/recreate)
                     for (obj_type, obj_name) in dependents {
-                        let obj_qualified = QualifiedIdent::from_qualified_name(&obj_name);
-
-                        // Skip if already in changes
-                        let already_included = object_changes.iter().any(|change| match change {
-                            ChangeOperation::UpdateObject { object, .. } |
-                            ChangeOperation::CreateObject { object, .. } => {
-                                object.object_type == obj_type &&
-                                object.qualified_name == obj_qualified
-                            }
-                            ChangeOperation::DeleteObject { object_type, object_name, .. } => {
-                                let qname = QualifiedIdent::from_qualified_name(object_name);
-                                object_type == &obj_type && qname == obj_qualified
-                            }
-                            _ => false,
-                        });
-
-                        if !already_included {
-                            // Find the object in file_objects to get its DDL
-                            if let Some(file_obj) = file_objects.iter().find(|obj|
-                                obj.object_type == obj_type &&
-                                obj.qualified_name == obj_qualified
-                            ) {
-                                debug!("  Adding {:?} {} for recreation (migration alters dependent table)",
-                                    obj_type, format_qualified_name(&obj_qualified));
-                                object_changes.push(ChangeOperation::UpdateObject {
-                                    object: file_obj.clone(),
-                                    old_hash: String::new(),
-                                    new_hash: calculate_ddl_hash(&file_obj.ddl_statement),
-                                    reason: "Migration alters dependent table".to_string(),
-                                });
-                            }
-                        }
+                        recreate_for_migration(
+                            &mut object_changes,
+                            &file_objects,
+                            obj_type,
+                            &QualifiedIdent::from_qualified_name(&obj_name),
+                            "Migration alters dependent table".to_string(),
+                        );
                     }
                 }
             }
@@ -183,7 +499,7 @@ pub async fn execute_plan(
 
             // Get stored dependencies for deleted objects
             let deleted_object_deps = if !deleted_objects.is_empty() {
-                state_manager.get_deleted_object_dependencies(&deleted_objects).await?
+                source.object_dependencies(&deleted_objects).await?
             } else {
                 Vec::new()
             };
@@ -201,7 +517,7 @@ pub async fn execute_plan(
                 .collect();
 
             let updated_object_stored_deps = if !updated_objects_for_deps.is_empty() {
-                state_manager.get_deleted_object_dependencies(&updated_objects_for_deps).await?
+                source.object_dependencies(&updated_objects_for_deps).await?
             } else {
                 Vec::new()
             };
@@ -215,7 +531,7 @@ pub async fn execute_plan(
 
             // Build the "file-only" graph first - this is used for VALIDATION
             // (checking if the new state after apply will be consistent)
-            let file_graph = DependencyGraph::build_from_objects(&file_objects, &builtin_catalog)?;
+            let file_graph = DependencyGraph::build_with_search_path(&file_objects, &builtin_catalog, &options.search_path)?;
 
             // Now build the "merged" graph for PRE-DROP ORDERING
             // This includes stored dependencies so we know what the database currently has
@@ -233,6 +549,18 @@ pub async fn execute_plan(
                 }
             }
 
+            // Objects created by pending migrations satisfy dependencies of managed
+            // objects, which are then ordered after them
+            for (_, migration_obj) in &plan_result.migration_objects {
+                let already_known = all_objects_for_ordering.iter().any(|obj|
+                    obj.object_type == migration_obj.object_type &&
+                    obj.qualified_name == migration_obj.qualified_name
+                );
+                if !already_known {
+                    all_objects_for_ordering.push(migration_obj.clone());
+                }
+            }
+
             for (obj_type, obj_name, deps) in deleted_object_deps {
                 // Create a minimal SqlObject for deleted objects
                 let deleted_obj = SqlObject::new(
@@ -246,7 +574,7 @@ pub async fn execute_plan(
             }
 
             // Build ordering graph from merged dependencies
-            let graph = DependencyGraph::build_from_objects(&all_objects_for_ordering, &builtin_catalog)?;
+            let graph = DependencyGraph::build_with_search_path(&all_objects_for_ordering, &builtin_catalog, &options.search_path)?;
             
             // Step 3.25: Validate that deletions are safe
             // Check if any objects being deleted have dependents that aren't also being deleted
@@ -348,15 +676,50 @@ pub async fn execute_plan(
                             object_changes.push(ChangeOperation::UpdateObject {
                                 object: file_obj.clone(),
                                 old_hash: String::new(), // We don't have the old hash, but it's not critical
-                                new_hash: calculate_ddl_hash(&file_obj.ddl_statement),
+                                new_hash: file_obj.ddl_hash.clone(),
                                 reason: "Dependency requires recreation".to_string(),
+                                old_ddl: None,
+                                cascade: true,
                             });
                         }
                     }
                 }
             }
             
+            // Recreated anyway, e.g. after a dependency changed
+            plan_result.formatting_only.retain(|formatted| !object_changes.iter().any(|change| matches!(
+                change,
+                ChangeOperation::UpdateObject { object, .. }
+                    if object.object_type == formatted.object_type && object.qualified_name == formatted.qualified_name
+            )));
+            plan_result.publication_changes.retain(|altered| !object_changes.iter().any(|change| matches!(
+                change,
+                ChangeOperation::UpdateObject { object, .. }
+                    if object.object_type == altered.object.object_type && object.qualified_name == altered.object.qualified_name
+            )));
+            plan_result.cron_job_changes.retain(|altered| !object_changes.iter().any(|change| matches!(
+                change,
+                ChangeOperation::UpdateObject { object, .. }
+                    if object.object_type == altered.object.object_type && object.qualified_name == altered.object.qualified_name
+            )));
             plan_result.changes.extend(object_changes);
+
+            // Record the order creates/updates will be applied in
+            match graph.creation_order() {
+                Ok(order) => {
+                    plan_result.apply_order = order.into_iter()
+                        .filter(|obj_ref| plan_result.changes.iter().any(|change| match change {
+                            ChangeOperation::CreateObject { object, .. } |
+                            ChangeOperation::UpdateObject { object, .. } => {
+                                object.object_type == obj_ref.object_type &&
+                                object.qualified_name == obj_ref.qualified_name
+                            }
+                            _ => false,
+                        }))
+                        .collect();
+                }
+                Err(e) => debug!("Could not determine apply order: {}", e),
+            }
             
             // Write graph output if requested
             if let Some(output_path) = output_graph {
@@ -369,15 +732,71 @@ pub async fn execute_plan(
         }
     }
 
+    if let PlanSource::Database(client) = source {
+        plan_result.relation_sizes = destructive_relation_sizes(client, &plan_result.changes).await?;
+    }
+
     Ok(plan_result)
 }
 
+/// Size up the relations the plan drops or rewrites: tables, materialized
+/// views and indexes apply recreates or drops, and those dropped, truncated
+/// or rewritten by pending migrations. Relations that don't exist yet and
+/// views, which have no storage, are left out.
+async fn destructive_relation_sizes(
+    client: &tokio_postgres::Client,
+    changes: &[ChangeOperation],
+) -> Result<Vec<RelationSize>, Box<dyn std::error::Error>> {
+    let has_storage = |object_type: &ObjectType| matches!(
+        object_type,
+        ObjectType::Table | ObjectType::MaterializedView | ObjectType::Index
+    );
+
+    let mut touched: Vec<(String, QualifiedIdent)> = Vec::new();
+    for change in changes {
+        match change {
+            ChangeOperation::UpdateObject { object, .. } if has_storage(&object.object_type) => {
+                touched.push((format!("DROP and recreate {}", object.object_type), object.qualified_name.clone()));
+            }
+            ChangeOperation::DeleteObject { object_type, object_name, no_drop: false, detach_from: None, .. } if has_storage(object_type) => {
+                touched.push((format!("DROP {}", object_type), QualifiedIdent::from_qualified_name(object_name)));
+            }
+            ChangeOperation::ApplyMigration { name, content } => match extract_destructive_operations(content) {
+                Ok(operations) => touched.extend(operations.into_iter().map(|op| {
+                    (format!("{} (migration {})", op.operation, name), op.relation)
+                })),
+                Err(e) => debug!("Could not analyze migration {} for destructive operations: {}", name, e),
+            },
+            _ => {}
+        }
+    }
+
+    let mut sizes = Vec::new();
+    for (operation, relation) in touched {
+        let row = client.query_one(
+            "SELECT pg_total_relation_size(to_regclass($1))",
+            &[&crate::sql::quote::quote_qualified_ident(&relation)],
+        ).await?;
+        let bytes: Option<i64> = row.get(0);
+        if let Some(bytes) = bytes.filter(|bytes| *bytes > 0) {
+            sizes.push(RelationSize {
+                operation,
+                relation: format_qualified_name(&relation),
+                bytes: bytes as u64,
+            });
+        }
+    }
+
+    sizes.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+    Ok(sizes)
+}
+
 async fn check_new_migrations(
     migrations_dir: &PathBuf,
-    state_manager: &StateManager<'_>,
+    source: &PlanSource<'_>,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let migration_files = scan_migrations(migrations_dir).await?;
-    let applied_migrations = state_manager.get_applied_migration_names().await?;
+    let applied_migrations = source.applied_migration_names().await?;
     
     let mut new_migrations = Vec::new();
     
@@ -390,9 +809,109 @@ async fn check_new_migrations(
     Ok(new_migrations)
 }
 
+/// Applied migrations whose file has been edited or removed since it was
+/// applied, in name order. Migrations applied by a pgmg that didn't record
+/// their content can't be checked and are left out.
+pub async fn find_edited_migrations(
+    client: &tokio_postgres::Client,
+    migrations_dir: &Path,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let state_manager = StateManager::new(client);
+    state_manager.initialize().await?;
+
+    let files: HashMap<String, PathBuf> = scan_migrations(migrations_dir).await?
+        .into_iter()
+        .map(|file| (file.name, file.path))
+        .collect();
+
+    let mut edited = Vec::new();
+    for (name, hash) in state_manager.get_migration_hashes().await? {
+        let Some(hash) = hash else { continue };
+        let unchanged = files.get(&name)
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .is_some_and(|content| migration_content_hash(&content) == hash);
+        if !unchanged {
+            edited.push(name);
+        }
+    }
+
+    edited.sort();
+    Ok(edited)
+}
+
+/// Queue the managed object for dropping and recreating around the pending
+/// migrations, unless it's already being changed
+fn recreate_for_migration(
+    object_changes: &mut Vec<ChangeOperation>,
+    file_objects: &[SqlObject],
+    obj_type: ObjectType,
+    obj_name: &QualifiedIdent,
+    reason: String,
+) {
+    let same_object = |object_type: &ObjectType, name: &QualifiedIdent| {
+        object_type == &obj_type && same_name(name, obj_name)
+    };
+
+    let already_included = object_changes.iter().any(|change| match change {
+        ChangeOperation::UpdateObject { object, .. } |
+        ChangeOperation::CreateObject { object, .. } => same_object(&object.object_type, &object.qualified_name),
+        ChangeOperation::DeleteObject { object_type, object_name, .. } => {
+            same_object(object_type, &QualifiedIdent::from_qualified_name(object_name))
+        }
+        _ => false,
+    });
+    if already_included {
+        return;
+    }
+
+    // Find the object in file_objects to get its DDL
+    if let Some(file_obj) = file_objects.iter().find(|obj| same_object(&obj.object_type, &obj.qualified_name)) {
+        debug!("  Adding {:?} {} for recreation ({})", obj_type, format_qualified_name(obj_name), reason);
+        object_changes.push(ChangeOperation::UpdateObject {
+            object: file_obj.clone(),
+            old_hash: String::new(),
+            new_hash: file_obj.ddl_hash.clone(),
+            reason,
+            old_ddl: None,
+            cascade: false,
+        });
+    }
+}
+
+/// Managed views and functions whose code reads one of the columns, directly
+/// or through `*`
+fn column_readers(
+    file_objects: &[SqlObject],
+    columns: &[ColumnReference],
+) -> Vec<(ObjectType, String, ColumnReference)> {
+    let mut readers = Vec::new();
+    for obj in file_objects {
+        if !matches!(obj.object_type, ObjectType::View | ObjectType::MaterializedView | ObjectType::Function | ObjectType::Procedure) {
+            continue;
+        }
+        for column in columns {
+            let reads = obj.dependencies.columns.iter().any(|read| {
+                same_name(&read.relation, &column.relation) &&
+                (read.column == column.column || read.column == "*")
+            });
+            if reads {
+                readers.push((obj.object_type.clone(), format_qualified_name(&obj.qualified_name), column.clone()));
+            }
+        }
+    }
+    readers
+}
+
+/// Whether the names are the same, with unqualified ones taken to be in public
+fn same_name(a: &QualifiedIdent, b: &QualifiedIdent) -> bool {
+    a.name == b.name &&
+    a.schema.as_deref().unwrap_or("public") == b.schema.as_deref().unwrap_or("public")
+}
+
 async fn detect_object_changes(
     file_objects: &[SqlObject],
     db_objects: &[crate::db::ObjectRecord],
+    partition_removal: PartitionRemoval,
 ) -> Result<Vec<ChangeOperation>, Box<dyn std::error::Error>> {
     let mut changes = Vec::new();
     
@@ -421,13 +940,26 @@ async fn detect_object_changes(
         file_object_set.insert(key);
     }
     
-    // Check for deleted objects first (in database but not in files)
+    // Check for deleted objects first (in database but not in files). The
+    // directives are those the object was last applied with.
     for (key, db_obj) in &db_object_map {
         if !file_object_set.contains(key) {
+            let no_drop = db_obj.ddl_source.as_deref()
+                .is_some_and(|ddl| parse_object_directives(ddl).0.no_drop);
+            let detach_from = db_obj.ddl_source.as_deref()
+                .filter(|_| db_obj.object_type == ObjectType::Table && partition_removal == PartitionRemoval::Detach)
+                .and_then(partition_parent)
+                .map(|parent| format_qualified_name(&parent));
             changes.push(ChangeOperation::DeleteObject {
                 object_type: db_obj.object_type.clone(),
                 object_name: format_qualified_name(&db_obj.object_name),
-                reason: "Object no longer exists in code".to_string(),
+                reason: match &detach_from {
+                    _ if no_drop => "Object no longer exists in code, left in place (no-drop)".to_string(),
+                    Some(parent) => format!("Partition no longer exists in code, detached from {}", parent),
+                    None => "Object no longer exists in code".to_string(),
+                },
+                no_drop,
+                detach_from,
             });
         }
     }
@@ -437,7 +969,7 @@ async fn detect_object_changes(
         let key = format!("{:?}:{}", file_obj.object_type,
             format_qualified_name(&file_obj.qualified_name));
         
-        let new_hash = calculate_ddl_hash(&file_obj.ddl_statement);
+        let new_hash = file_obj.ddl_hash.clone();
         
         match db_object_map.get(&key) {
             Some(db_obj) => {
@@ -448,6 +980,8 @@ async fn detect_object_changes(
                         old_hash: db_obj.ddl_hash.clone(),
                         new_hash,
                         reason: "DDL content has changed".to_string(),
+                        old_ddl: db_obj.ddl_source.clone(),
+                        cascade: false,
                     });
                 }
             }
@@ -486,6 +1020,276 @@ async fn detect_object_changes(
     Ok(changes)
 }
 
+/// Take out the updates whose code only changed in whitespace or comments
+/// since it was applied, returning their objects
+fn take_formatting_changes(
+    changes: &mut Vec<ChangeOperation>,
+    db_objects: &[crate::db::ObjectRecord],
+) -> Vec<SqlObject> {
+    let applied_sources: HashMap<String, &str> = db_objects.iter()
+        .filter_map(|db_obj| Some((
+            format!("{:?}:{}", db_obj.object_type, format_qualified_name(&db_obj.object_name)),
+            db_obj.ddl_source.as_deref()?,
+        )))
+        .collect();
+
+    let mut formatting_only = Vec::new();
+    changes.retain(|change| {
+        let ChangeOperation::UpdateObject { object, .. } = change else {
+            return true;
+        };
+        let key = format!("{:?}:{}", object.object_type, format_qualified_name(&object.qualified_name));
+        let unchanged = applied_sources.get(&key).is_some_and(|applied| {
+            match (formatting_fingerprint(applied), formatting_fingerprint(&object.ddl_source())) {
+                (Ok(applied), Ok(current)) => applied == current,
+                _ => false,
+            }
+        });
+        if unchanged {
+            debug!("Only the formatting of {:?} {} changed", object.object_type, format_qualified_name(&object.qualified_name));
+            formatting_only.push(object.clone());
+        }
+        !unchanged
+    });
+
+    formatting_only
+}
+
+/// Take out the updates to publications that only change the tables or
+/// schemas they publish, returning the statements making the change.
+/// Recreating a publication would break the subscriptions reading from it.
+/// Publications applied before their source was stored, or whose options
+/// change, are recreated as usual.
+fn take_publication_changes(
+    changes: &mut Vec<ChangeOperation>,
+    db_objects: &[crate::db::ObjectRecord],
+) -> Vec<PublicationChange> {
+    let mut altered = Vec::new();
+    changes.retain(|change| {
+        let ChangeOperation::UpdateObject { object, .. } = change else {
+            return true;
+        };
+        if object.object_type != ObjectType::Publication {
+            return true;
+        }
+        let applied = db_objects.iter()
+            .find(|db_obj| db_obj.object_type == ObjectType::Publication && db_obj.object_name == object.qualified_name)
+            .and_then(|db_obj| db_obj.ddl_source.as_deref());
+        match applied.and_then(|applied| publication_changes(applied, &object.ddl_statement)) {
+            Some(statements) => {
+                altered.push(PublicationChange { object: object.clone(), statements });
+                false
+            }
+            None => true,
+        }
+    });
+    altered
+}
+
+/// Take out the updates to cron jobs, returning the `cron.alter_job`
+/// statement changing the schedule or command. Rescheduling a job would give
+/// it a new id and lose its run history. Jobs applied before their source was
+/// stored are rescheduled as usual.
+fn take_cron_job_changes(
+    changes: &mut Vec<ChangeOperation>,
+    db_objects: &[crate::db::ObjectRecord],
+) -> Vec<CronJobChange> {
+    let mut altered = Vec::new();
+    changes.retain(|change| {
+        let ChangeOperation::UpdateObject { object, .. } = change else {
+            return true;
+        };
+        if object.object_type != ObjectType::CronJob {
+            return true;
+        }
+        let applied = db_objects.iter()
+            .find(|db_obj| db_obj.object_type == ObjectType::CronJob && db_obj.object_name == object.qualified_name)
+            .and_then(|db_obj| db_obj.cron_job());
+        match (applied, crate::sql::parse_cron_job(&object.ddl_statement)) {
+            (Some(applied), Some(current)) => {
+                altered.push(CronJobChange { object: object.clone(), statements: cron_job_changes(&applied, &current) });
+                false
+            }
+            _ => true,
+        }
+    });
+    altered
+}
+
+/// Take out the updates to enum types that only add values, returning the
+/// statements adding them. An enum used by a column can't be dropped, so
+/// these are altered rather than recreated, and nothing depending on the type
+/// is recreated with it. Removing or reordering values is an error.
+async fn take_enum_additions(
+    source: &PlanSource<'_>,
+    changes: &mut Vec<ChangeOperation>,
+) -> Result<Vec<EnumAddition>, Box<dyn std::error::Error>> {
+    let mut additions = Vec::new();
+    let mut errors = Vec::new();
+
+    for change in std::mem::take(changes) {
+        let ChangeOperation::UpdateObject { object, .. } = &change else {
+            changes.push(change);
+            continue;
+        };
+        let current = match object.object_type {
+            ObjectType::Type => extract_enum_values(&object.ddl_statement),
+            _ => None,
+        };
+        let Some(current) = current else {
+            changes.push(change);
+            continue;
+        };
+
+        // Compared with the values in the database rather than the applied
+        // source, so values added by migrations count as already there
+        let applied = match source {
+            PlanSource::Database(client) => applied_enum_values(client, &object.qualified_name).await?,
+            PlanSource::Snapshot(snapshot) => snapshot.enum_values(&object.qualified_name),
+        };
+        if applied.is_empty() {
+            // Not an enum in the database (yet), so it's recreated as usual
+            changes.push(change);
+            continue;
+        }
+
+        match enum_value_additions(&object.qualified_name, &applied, &current) {
+            Ok(statements) => additions.push(EnumAddition { object: object.clone(), statements }),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+    Ok(additions)
+}
+
+/// The values of an enum type in the database, in order. Empty if there is no
+/// enum type by that name.
+async fn applied_enum_values(
+    client: &tokio_postgres::Client,
+    qualified_name: &QualifiedIdent,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let rows = client.query(
+        "SELECT enumlabel::text FROM pg_enum WHERE enumtypid = to_regtype($1)::oid ORDER BY enumsortorder",
+        &[&crate::sql::quote::quote_qualified_ident(qualified_name)],
+    ).await?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Leave out the objects marked `-- pgmg:skip-env` for the environment. They
+/// are neither created nor updated, and as they're still in code, objects
+/// applied before the directive was added aren't dropped either. Returns the
+/// remaining objects.
+fn skip_environment_objects(
+    file_objects: Vec<SqlObject>,
+    changes: &mut Vec<ChangeOperation>,
+    environment: Option<&str>,
+) -> Vec<SqlObject> {
+    let (skipped, file_objects): (Vec<SqlObject>, Vec<SqlObject>) = file_objects.into_iter()
+        .partition(|obj| obj.directives.skipped_in(environment));
+
+    for obj in &skipped {
+        debug!(environment = environment.unwrap_or_default(), "Skipping {:?} {} in this environment",
+            obj.object_type, format_qualified_name(&obj.qualified_name));
+    }
+    changes.retain(|change| match change {
+        ChangeOperation::CreateObject { object, .. } |
+        ChangeOperation::UpdateObject { object, .. } => !object.directives.skipped_in(environment),
+        _ => true,
+    });
+
+    file_objects
+}
+
+/// Add the schemas the tenant query returns to the listed ones, or those it
+/// returned when the snapshot was exported. With no tenants at all every
+/// tenant's objects would be dropped, so that fails.
+async fn resolve_tenant_schemas(
+    source: &PlanSource<'_>,
+    tenants: &TenantTemplate,
+) -> Result<TenantTemplate, Box<dyn std::error::Error>> {
+    let mut tenants = tenants.clone();
+
+    match (tenants.query.take(), source) {
+        (Some(query), PlanSource::Database(client)) => {
+            let rows = client.query(query.as_str(), &[]).await
+                .map_err(|e| format!("Tenant query failed: {}", e))?;
+            let schemas = rows.iter()
+                .map(|row| row.try_get::<_, String>(0))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Tenant query must return schema names as text in its first column: {}", e))?;
+            tenants.add_schemas(schemas);
+        }
+        (Some(_), PlanSource::Snapshot(snapshot)) => tenants.add_schemas(snapshot.tenant_schemas.clone()),
+        (None, _) => {}
+    }
+
+    if tenants.schemas.is_empty() {
+        return Err(format!(
+            "No tenant schemas to apply files using {} to. List them under [tenants] in pgmg.toml \
+            with schemas or a query returning them",
+            tenants.placeholder
+        ).into());
+    }
+    debug!(tenants = ?tenants.schemas, "Expanding files for tenant schemas");
+
+    Ok(tenants)
+}
+
+/// Leave out objects in schemas `filter` excludes: code files' objects are
+/// neither created nor recreated, and tracked objects there are never dropped.
+/// They are also left out of the returned file objects, so nothing in those
+/// schemas is recreated because a dependency changed.
+fn skip_unmanaged_schemas(
+    file_objects: Vec<SqlObject>,
+    changes: &mut Vec<ChangeOperation>,
+    db_objects: &[crate::db::ObjectRecord],
+    filter: &SchemaFilter,
+) -> Vec<SqlObject> {
+    if filter.is_empty() {
+        return file_objects;
+    }
+
+    let (file_objects, skipped): (Vec<SqlObject>, Vec<SqlObject>) = file_objects.into_iter()
+        .partition(|obj| filter.includes(&obj.object_type, &obj.qualified_name, Some(&obj.ddl_statement)));
+
+    for obj in &skipped {
+        debug!("Skipping {:?} {} outside the managed schemas",
+            obj.object_type, format_qualified_name(&obj.qualified_name));
+    }
+    changes.retain(|change| match change {
+        ChangeOperation::CreateObject { object, .. } |
+        ChangeOperation::UpdateObject { object, .. } => filter.includes(&object.object_type, &object.qualified_name, Some(&object.ddl_statement)),
+        ChangeOperation::DeleteObject { object_type, object_name, .. } => db_objects.iter()
+            .find(|db_obj| &db_obj.object_type == object_type && format_qualified_name(&db_obj.object_name) == *object_name)
+            .map_or(true, |db_obj| filter.includes(&db_obj.object_type, &db_obj.object_name, db_obj.ddl_source.as_deref())),
+        ChangeOperation::ApplyMigration { .. } => true,
+    });
+
+    file_objects
+}
+
+/// Keep only the changes to objects with one of `tags`: the tags in code for
+/// objects being created or updated, and those they were last applied with
+/// for objects being dropped. Objects that must be recreated because a kept
+/// change affects them are added back later, tagged or not.
+fn retain_tagged_changes(
+    changes: &mut Vec<ChangeOperation>,
+    db_objects: &[crate::db::ObjectRecord],
+    tags: &[String],
+) {
+    changes.retain(|change| match change {
+        ChangeOperation::CreateObject { object, .. } |
+        ChangeOperation::UpdateObject { object, .. } => matches_tags(&object.directives.tags, tags),
+        ChangeOperation::DeleteObject { object_type, object_name, .. } => db_objects.iter()
+            .find(|db_obj| &db_obj.object_type == object_type && format_qualified_name(&db_obj.object_name) == *object_name)
+            .is_some_and(|db_obj| matches_tags(&db_obj.tags, tags)),
+        ChangeOperation::ApplyMigration { .. } => true,
+    });
+}
+
 fn format_qualified_name(qualified_name: &crate::sql::QualifiedIdent) -> String {
     match &qualified_name.schema {
         Some(schema) => format!("{}.{}", schema, qualified_name.name),
@@ -526,6 +1330,55 @@ fn extract_comment_text(ddl: &str) -> Option<String> {
     None
 }
 
+/// Where an object is defined, as `path:line` relative to the working directory
+fn source_location(obj: &SqlObject) -> String {
+    let location = match &obj.source_file {
+        Some(path) => {
+            match path.strip_prefix(std::env::current_dir().unwrap_or_default()) {
+                Ok(relative_path) => relative_path.display().to_string(),
+                Err(_) => path.display().to_string(),
+            }
+        }
+        None => "unknown location".to_string(),
+    };
+    
+    // Add line number if available
+    if let Some(line) = obj.start_line {
+        format!("{}:{}", location, line)
+    } else {
+        location
+    }
+}
+
+/// Refuse CREATE TABLE in the code directory. Code objects are tracked and
+/// recreated from their files, so a table there is dropped, with its data,
+/// once its file is removed or renamed. `allowed` is pgmg.toml's
+/// `allow_tables_in_code_dir`, for projects that keep tables there as the
+/// desired state for `generate-migration`.
+pub fn validate_no_tables_in_code_dir(file_objects: &[SqlObject], allowed: bool) -> Result<(), String> {
+    if allowed {
+        return Ok(());
+    }
+    
+    let tables: Vec<String> = file_objects.iter()
+        .filter(|obj| obj.object_type == ObjectType::Table)
+        .map(|obj| format!("{} ({})", format_qualified_name(&obj.qualified_name), source_location(obj)))
+        .collect();
+    if tables.is_empty() {
+        return Ok(());
+    }
+    
+    Err(format!(
+        "CREATE TABLE found in the code directory:\n  - {}\n\
+        Tables hold data, so they belong in migrations, which run once and are never undone. \
+        Objects in the code directory are recreated whenever their file changes and dropped when it's removed, \
+        which would drop the table and everything in it.\n\
+        Move each table into a migration (`pgmg new <name>`), then delete it from the code directory. \
+        To keep tables there anyway, set allow_tables_in_code_dir = true in pgmg.toml.",
+        tables.join("\n  - ")
+    ))
+}
+
 /// Validate that no object names are duplicated in the SQL files
 fn validate_no_duplicate_objects_in_files(file_objects: &[SqlObject]) -> Result<(), Box<dyn std::error::Error>> {
     let mut object_locations: HashMap<String, Vec<(String, ObjectType)>> = HashMap::new();
@@ -545,26 +1398,20 @@ fn validate_no_duplicate_objects_in_files(file_objects: &[SqlObject]) -> Result<
             | ObjectType::Domain
             | ObjectType::Index
             | ObjectType::Aggregate
+            | ObjectType::Statistics
+            | ObjectType::Extension
+            | ObjectType::Publication
+            | ObjectType::Collation
+            | ObjectType::TextSearchConfiguration
+            | ObjectType::TextSearchDictionary
+            | ObjectType::OperatorClass
+            | ObjectType::OperatorFamily
+            | ObjectType::Cast
         );
         
         if should_check {
             let obj_name = format_qualified_name(&obj.qualified_name);
-            let location = match &obj.source_file {
-                Some(path) => {
-                    match path.strip_prefix(std::env::current_dir().unwrap_or_default()) {
-                        Ok(relative_path) => relative_path.display().to_string(),
-                        Err(_) => path.display().to_string(),
-                    }
-                }
-                None => "unknown location".to_string(),
-            };
-            
-            // Add line number if available
-            let location_with_line = if let Some(line) = obj.start_line {
-                format!("{}:{}", location, line)
-            } else {
-                location
-            };
+            let location_with_line = source_location(obj);
             
             object_locations.entry(obj_name).or_insert_with(Vec::new).push((location_with_line, obj.object_type.clone()));
         }
@@ -583,6 +1430,15 @@ fn validate_no_duplicate_objects_in_files(file_objects: &[SqlObject]) -> Result<
                 ObjectType::Domain => "domain",
                 ObjectType::Index => "index",
                 ObjectType::Aggregate => "aggregate",
+                ObjectType::Statistics => "statistics object",
+                ObjectType::Extension => "extension",
+                ObjectType::Publication => "publication",
+                ObjectType::Collation => "collation",
+                ObjectType::TextSearchConfiguration => "text search configuration",
+                ObjectType::TextSearchDictionary => "text search dictionary",
+                ObjectType::OperatorClass => "operator class",
+                ObjectType::OperatorFamily => "operator family",
+                ObjectType::Cast => "cast",
                 _ => "object",
             };
             
@@ -601,13 +1457,15 @@ fn validate_no_duplicate_objects_in_files(file_objects: &[SqlObject]) -> Result<
     Ok(())
 }
 
-pub fn print_plan_summary(plan: &PlanResult) {
-    println!("\n{}", "=== PGMG Plan Summary ===".bold().blue());
+/// Print the plan. With `show_diffs`, updated objects whose previous DDL is
+/// stored show a diff of it against their new definition.
+pub fn print_plan_summary(plan: &PlanResult, show_diffs: bool) {
+    output::line(format!("\n{}", "=== PGMG Plan Summary ===".bold().blue()));
     
     if !plan.new_migrations.is_empty() {
-        println!("\n{}:", "New Migrations to Apply".bold());
+        output::line(format!("\n{}:", "New Migrations to Apply".bold()));
         for migration in &plan.new_migrations {
-            println!("  {} {}", "+".green().bold(), migration.cyan());
+            output::line(format!("  {} {}", "+".green().bold(), migration.cyan()));
         }
 
         // Show objects that will be pre-dropped before migrations
@@ -619,22 +1477,22 @@ pub fn print_plan_summary(plan: &PlanResult) {
             .collect();
 
         if !objects_to_predrop.is_empty() {
-            println!("\n  {}:", "Objects to pre-drop before migrations".dimmed());
+            output::line(format!("\n  {}:", "Objects to pre-drop before migrations".dimmed()));
             for change in objects_to_predrop {
                 match change {
                     ChangeOperation::UpdateObject { object, .. } => {
-                        println!("    {} {} {} (will be recreated)",
-                            "↓".yellow(),
+                        output::line(format!("    {} {} {} (will be recreated)",
+                            output::symbol("↓").yellow(),
                             format!("{:?}", object.object_type).to_lowercase().dimmed(),
                             format_qualified_name(&object.qualified_name).cyan()
-                        );
+                        ));
                     }
                     ChangeOperation::DeleteObject { object_type, object_name, .. } => {
-                        println!("    {} {} {} (will be deleted)",
-                            "↓".red(),
+                        output::line(format!("    {} {} {} (will be deleted)",
+                            output::symbol("↓").red(),
                             format!("{:?}", object_type).to_lowercase().dimmed(),
                             object_name.cyan()
-                        );
+                        ));
                     }
                     _ => {}
                 }
@@ -643,7 +1501,7 @@ pub fn print_plan_summary(plan: &PlanResult) {
     }
 
     if !plan.changes.is_empty() {
-        println!("\n{}:", "Object Changes".bold());
+        output::line(format!("\n{}:", "Object Changes".bold()));
         
         // Group comments with their parent objects
         let mut printed_comments = HashSet::new();
@@ -661,85 +1519,250 @@ pub fn print_plan_summary(plan: &PlanResult) {
                     // Special handling for comments - display them inline with parent
                     if object.object_type == ObjectType::Comment {
                         // If this comment should be displayed standalone
-                        println!("  {} {} {} {} ({})", 
+                        output::line(format!("  {} {} {} {} ({})", 
                             "+".green().bold(),
                             "CREATE".green().bold(),
                             object.object_type.to_string().yellow(),
                             format_qualified_name(&object.qualified_name).cyan(),
                             reason.dimmed()
-                        );
+                        ));
                     } else {
                         // Regular object - check if it has an associated comment
-                        println!("  {} {} {} {} ({})", 
+                        output::line(format!("  {} {} {} {} ({})", 
                             "+".green().bold(),
                             "CREATE".green().bold(),
                             object.object_type.to_string().yellow(),
                             format_qualified_name(&object.qualified_name).cyan(),
                             reason.dimmed()
-                        );
+                        ));
                         
                         // Look for associated comment in subsequent changes
                         print_associated_comments(plan, i, &mut printed_comments, object);
                     }
                 }
-                ChangeOperation::UpdateObject { object, old_hash, new_hash, reason } => {
+                ChangeOperation::UpdateObject { object, old_hash, new_hash, reason, old_ddl, .. } => {
                     // Special handling for comments - display them inline with parent
                     if object.object_type == ObjectType::Comment {
                         // If this comment should be displayed standalone
-                        println!("  {} {} {} {} ({})", 
+                        output::line(format!("  {} {} {} {} ({})", 
                             "~".yellow().bold(),
                             "UPDATE".yellow().bold(),
                             object.object_type.to_string().yellow(),
                             format_qualified_name(&object.qualified_name).cyan(),
                             reason.dimmed()
-                        );
+                        ));
                     } else {
-                        println!("  {} {} {} {} ({})", 
+                        output::line(format!("  {} {} {} {} ({})", 
                             "~".yellow().bold(),
                             "UPDATE".yellow().bold(),
                             object.object_type.to_string().yellow(),
                             format_qualified_name(&object.qualified_name).cyan(),
                             reason.dimmed()
-                        );
+                        ));
                         if !old_hash.is_empty() && old_hash.len() >= 8 {
-                            println!("    {}: {}...", "Old hash".dimmed(), old_hash[..8].to_string().red());
+                            output::line(format!("    {}: {}...", "Old hash".dimmed(), old_hash[..8].to_string().red()));
                         }
                         if !new_hash.is_empty() && new_hash.len() >= 8 {
-                            println!("    {}: {}...", "New hash".dimmed(), new_hash[..8].to_string().green());
+                            output::line(format!("    {}: {}...", "New hash".dimmed(), new_hash[..8].to_string().green()));
+                        }
+                        if let (true, Some(old_ddl)) = (show_diffs, old_ddl) {
+                            print_ddl_diff(old_ddl, &object.ddl_source());
                         }
                         
                         // Look for associated comment in subsequent changes
                         print_associated_comments(plan, i, &mut printed_comments, object);
                     }
                 }
-                ChangeOperation::DeleteObject { object_type, object_name, reason } => {
-                    println!("  {} {} {} {} ({})", 
+                ChangeOperation::DeleteObject { object_type, object_name, reason, .. } => {
+                    output::line(format!("  {} {} {} {} ({})", 
                         "-".red().bold(),
                         "DELETE".red().bold(),
                         object_type.to_string().yellow(),
                         object_name.cyan(),
                         reason.dimmed()
-                    );
+                    ));
                 }
                 ChangeOperation::ApplyMigration { name, .. } => {
-                    println!("  {} {} {}", 
+                    output::line(format!("  {} {} {}", 
                         ">".magenta().bold(),
                         "MIGRATION".magenta().bold(),
                         name.cyan()
-                    );
+                    ));
                 }
             }
         }
-    } else if plan.new_migrations.is_empty() {
-        println!("\n{}", "No changes detected. Database is up to date.".green());
+    } else if !plan.has_changes() {
+        output::line(format!("\n{}", "No changes detected. Database is up to date.".green()));
+    }
+
+    if !plan.enum_additions.is_empty() {
+        output::line(format!("\n{}:", "Enum Values".bold()));
+        for addition in &plan.enum_additions {
+            if addition.statements.is_empty() {
+                output::line(format!("  {} {} {} ({})",
+                    "=".dimmed(),
+                    addition.object.object_type.to_string().yellow(),
+                    format_qualified_name(&addition.object.qualified_name).cyan(),
+                    "values already in the database".dimmed()
+                ));
+                continue;
+            }
+            output::line(format!("  {} {} {}",
+                "~".yellow().bold(),
+                addition.object.object_type.to_string().yellow(),
+                format_qualified_name(&addition.object.qualified_name).cyan()
+            ));
+            for statement in &addition.statements {
+                output::line(format!("      {}", statement.dimmed()));
+            }
+        }
+    }
+
+    if !plan.publication_changes.is_empty() {
+        output::line(format!("\n{}:", "Publications".bold()));
+        for change in &plan.publication_changes {
+            if change.statements.is_empty() {
+                output::line(format!("  {} {} {} ({})",
+                    "=".dimmed(),
+                    change.object.object_type.to_string().yellow(),
+                    format_qualified_name(&change.object.qualified_name).cyan(),
+                    "same tables published".dimmed()
+                ));
+                continue;
+            }
+            output::line(format!("  {} {} {}",
+                "~".yellow().bold(),
+                change.object.object_type.to_string().yellow(),
+                format_qualified_name(&change.object.qualified_name).cyan()
+            ));
+            for statement in &change.statements {
+                output::line(format!("      {}", statement.dimmed()));
+            }
+        }
+    }
+
+    if !plan.cron_job_changes.is_empty() {
+        output::line(format!("\n{}:", "Cron Jobs".bold()));
+        for change in &plan.cron_job_changes {
+            if change.statements.is_empty() {
+                output::line(format!("  {} {} {} ({})",
+                    "=".dimmed(),
+                    change.object.object_type.to_string().yellow(),
+                    format_qualified_name(&change.object.qualified_name).cyan(),
+                    "same schedule and command".dimmed()
+                ));
+                continue;
+            }
+            output::line(format!("  {} {} {}",
+                "~".yellow().bold(),
+                change.object.object_type.to_string().yellow(),
+                format_qualified_name(&change.object.qualified_name).cyan()
+            ));
+            for statement in &change.statements {
+                output::line(format!("      {}", statement.dimmed()));
+            }
+        }
+    }
+
+    if !plan.relation_sizes.is_empty() {
+        output::line(format!("\n{}:", "Dropped or Rewritten Relations".bold()));
+        for size in &plan.relation_sizes {
+            output::line(format!("  {} {} {} {} {}",
+                "!".yellow().bold(),
+                size.operation.yellow(),
+                size.relation.cyan(),
+                output::symbol("—"),
+                crate::logging::format_size(size.bytes).bold()
+            ));
+        }
+    }
+
+    if !plan.formatting_only.is_empty() {
+        output::line(format!("\n{}:", "Formatting Only".bold()));
+        for object in &plan.formatting_only {
+            output::line(format!("  {} {} {} ({})",
+                "=".dimmed(),
+                object.object_type.to_string().yellow(),
+                format_qualified_name(&object.qualified_name).cyan(),
+                "no-op (formatting)".dimmed()
+            ));
+        }
     }
     
+    if plan.apply_order.len() > 1 {
+        output::line(format!("\n{}:", "Apply Order".bold()));
+        for (i, obj_ref) in plan.apply_order.iter().enumerate() {
+            output::line(format!("  {:>3}. {} {}",
+                i + 1,
+                obj_ref.object_type.to_string().dimmed(),
+                format_qualified_name(&obj_ref.qualified_name).cyan()
+            ));
+        }
+    }
+
+    let waiting = migration_dependencies(plan);
+    if !waiting.is_empty() {
+        output::line(format!("\n{}:", "Created After Pending Migrations".bold()));
+        for (object, dependency, migration) in waiting {
+            output::line(format!("  {} {} {} {} {}",
+                object.object_type.to_string().dimmed(),
+                format_qualified_name(&object.qualified_name).cyan(),
+                "needs".dimmed(),
+                format_qualified_name(&dependency.qualified_name).cyan(),
+                format!("(from {})", migration).dimmed()
+            ));
+        }
+    }
+
     if let Some(graph) = &plan.dependency_graph {
-        println!("\n{}: {} objects, {} dependencies", 
+        output::line(format!("\n{}: {} objects, {} dependencies", 
             "Dependency Graph".bold(),
             graph.node_count().to_string().yellow(),
             graph.edge_count().to_string().yellow()
-        );
+        ));
+
+        if let Some(cycle) = graph.find_cycle() {
+            output::line(format!("\n{}", cycle.describe(&plan.file_objects).red()));
+            output::line("Apply will refuse to run until the cycle is resolved.".red().bold());
+        }
+    }
+}
+
+/// Created or updated objects that depend on objects created by pending
+/// migrations, as (object, dependency, migration name)
+pub fn migration_dependencies(plan: &PlanResult) -> Vec<(&SqlObject, &SqlObject, &str)> {
+    let mut result = Vec::new();
+
+    for change in &plan.changes {
+        let Some(object) = get_object_from_change(change) else { continue };
+        let deps = &object.dependencies;
+        let referenced: Vec<&QualifiedIdent> = deps.relations.iter()
+            .chain(deps.functions.iter())
+            .chain(deps.types.iter())
+            .collect();
+
+        for (migration, migration_obj) in &plan.migration_objects {
+            if referenced.contains(&&migration_obj.qualified_name) {
+                result.push((object, migration_obj, migration.as_str()));
+            }
+        }
+    }
+
+    result
+}
+
+/// Print a diff of an object's previous DDL against its new one, indented
+/// under its change
+fn print_ddl_diff(old_ddl: &str, new_ddl: &str) {
+    for hunk in crate::diff::hunks(old_ddl, new_ddl, crate::diff::CONTEXT_LINES) {
+        output::line(format!("    {}", hunk.header().cyan()));
+        for line in &hunk.lines {
+            match line {
+                DiffLine::Context(text) => output::line(format!("     {}", text.dimmed())),
+                DiffLine::Removed(text) => output::line(format!("    {}", format!("-{}", text).red())),
+                DiffLine::Added(text) => output::line(format!("    {}", format!("+{}", text).green())),
+            }
+        }
     }
 }
 
@@ -774,6 +1797,16 @@ fn print_associated_comments(
         ObjectType::CronJob => "cron_job",
         ObjectType::Aggregate => "aggregate",
         ObjectType::Operator => "operator",
+        ObjectType::SecurityLabel => "security_label",
+        ObjectType::Statistics => "statistics",
+        ObjectType::Extension => "extension",
+        ObjectType::Publication => "publication",
+        ObjectType::Collation => "collation",
+        ObjectType::TextSearchConfiguration => "text_search_configuration",
+        ObjectType::TextSearchDictionary => "text_search_dictionary",
+        ObjectType::OperatorClass => "operator_class",
+        ObjectType::OperatorFamily => "operator_family",
+        ObjectType::Cast => "cast",
     };
     
     let parent_name = format_qualified_name(&parent_object.qualified_name);
@@ -793,18 +1826,18 @@ fn print_associated_comments(
                     if let Some(comment_text) = extract_comment_text(&obj.ddl_statement) {
                         match change {
                             ChangeOperation::CreateObject { .. } => {
-                                println!("    {} {}: {}", 
-                                    "└─".dimmed(),
+                                output::line(format!("    {} {}: {}", 
+                                    output::symbol("└─").dimmed(),
                                     "COMMENT".green().dimmed(),
                                     comment_text.italic()
-                                );
+                                ));
                             }
                             ChangeOperation::UpdateObject { .. } => {
-                                println!("    {} {}: {}", 
-                                    "└─".dimmed(),
+                                output::line(format!("    {} {}: {}", 
+                                    output::symbol("└─").dimmed(),
                                     "COMMENT".yellow().dimmed(),
                                     comment_text.italic()
-                                );
+                                ));
                             }
                             _ => {}
                         }
@@ -818,6 +1851,74 @@ fn print_associated_comments(
     }
 }
 
+/// Look up pg_stat_statements usage for every object the plan drops or recreates.
+/// Returns `None` if the pg_stat_statements extension is not installed.
+pub async fn collect_change_usage(
+    connection_string: &str,
+    plan: &PlanResult,
+) -> Result<Option<Vec<ObjectUsage>>, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(connection_string).await?;
+    connection.spawn();
+
+    if !is_pg_stat_statements_available(&client).await? {
+        return Ok(None);
+    }
+
+    let targets: Vec<(ObjectType, String, bool)> = plan.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::UpdateObject { object, .. } => {
+                Some((object.object_type.clone(), format_qualified_name(&object.qualified_name), false))
+            }
+            ChangeOperation::DeleteObject { object_type, object_name, .. } => {
+                Some((object_type.clone(), object_name.clone(), true))
+            }
+            _ => None,
+        })
+        // Comments, labels, statistics, extensions, publications and cron jobs
+        // aren't referenced by application queries
+        .filter(|(object_type, _, _)| !matches!(object_type, ObjectType::Comment | ObjectType::SecurityLabel | ObjectType::Statistics | ObjectType::Extension | ObjectType::Publication | ObjectType::CronJob))
+        .collect();
+
+    if targets.is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+
+    Ok(Some(collect_object_usage(&client, &targets).await?))
+}
+
+#[cfg(feature = "cli")]
+pub fn print_usage_report(usage: &Option<Vec<ObjectUsage>>) {
+    output::line(format!("\n{}:", "Production Usage (pg_stat_statements)".bold()));
+
+    let usage = match usage {
+        Some(usage) => usage,
+        None => {
+            output::line(format!("  {}", "pg_stat_statements is not installed; no usage data available".dimmed()));
+            return;
+        }
+    };
+
+    if usage.is_empty() {
+        output::line(format!("  {}", "No recorded queries reference objects being dropped or recreated".green()));
+        return;
+    }
+
+    for object_usage in usage {
+        let rate = match object_usage.calls_per_minute {
+            Some(rate) => format!("{} calls/min", crate::db::usage::format_rate(rate)),
+            None => format!("{} calls", object_usage.calls),
+        };
+        let marker = if object_usage.is_delete { "!".red().bold().to_string() } else { "!".yellow().bold().to_string() };
+        output::line(format!("  {} {}: {} {} {}",
+            marker,
+            object_usage.object_name.cyan(),
+            rate.yellow(),
+            output::symbol("—"),
+            object_usage.risk().dimmed()
+        ));
+    }
+}
+
 /// Quick check for pending changes without building full plan
 /// Returns (has_changes, change_count)
 pub async fn check_for_pending_changes(
@@ -841,17 +1942,18 @@ pub async fn check_for_pending_changes(
     if let Some(migrations_dir) = &migrations_dir {
         let new_migrations = check_new_migrations(
             migrations_dir, 
-            &state_manager
+            &PlanSource::Database(&client)
         ).await?;
         change_count += new_migrations.len();
     }
 
     // Check for object changes
     if let Some(code_dir) = &code_dir {
-        let builtin_catalog = BuiltinCatalog::from_database(&client).await?;
+        let builtin_catalog = BuiltinCatalog::for_database(&client).await?;
         let file_objects = scan_sql_files(code_dir, &builtin_catalog).await?;
         let db_objects = state_manager.get_tracked_objects().await?;
-        let object_changes = detect_object_changes(&file_objects, &db_objects).await?;
+        let mut object_changes = detect_object_changes(&file_objects, &db_objects, PartitionRemoval::default()).await?;
+        skip_environment_objects(file_objects, &mut object_changes, environment_from_env().as_deref());
         change_count += object_changes.len();
     }
 
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::analysis::{DependencyGraph, DependencyCycle, DependencyType, ObjectRef, SearchPath};
+use crate::db::scan_sql_files;
+use crate::sql::{SqlObject, ObjectType};
+use crate::builtin_catalog::{BuiltinCatalog, DEFAULT_POSTGRES_VERSION};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OfflineChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// An object that differs between the two code directories
+#[derive(Debug, Clone)]
+pub struct OfflineChange {
+    pub kind: OfflineChangeKind,
+    pub object_type: ObjectType,
+    pub object_name: String,
+    /// File defining the object in the new tree (old tree for removals)
+    pub source_file: Option<PathBuf>,
+    pub start_line: Option<usize>,
+}
+
+/// A dependency edge present in only one of the two graphs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdgeChange {
+    pub dependency: ObjectRef,
+    pub dependent: ObjectRef,
+    pub kind: DependencyType,
+}
+
+#[derive(Debug)]
+pub struct OfflinePlan {
+    pub changes: Vec<OfflineChange>,
+    /// Unchanged objects that apply would drop and recreate, because an object
+    /// they hard-depend on was modified or removed
+    pub recreated: Vec<ObjectRef>,
+    pub added_edges: Vec<EdgeChange>,
+    pub removed_edges: Vec<EdgeChange>,
+    /// A dependency cycle in the new tree, which apply would refuse
+    pub new_cycle: Option<DependencyCycle>,
+}
+
+impl OfflinePlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.added_edges.is_empty() && self.removed_edges.is_empty()
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &OfflineChange> {
+        self.changes.iter().filter(|change| change.kind == OfflineChangeKind::Added)
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &OfflineChange> {
+        self.changes.iter().filter(|change| change.kind == OfflineChangeKind::Removed)
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &OfflineChange> {
+        self.changes.iter().filter(|change| change.kind == OfflineChangeKind::Modified)
+    }
+}
+
+/// Compare two code directories (e.g. the base and head of a pull request)
+/// without a database.
+///
+/// Objects are matched by type and name and compared by DDL hash, the same way
+/// `plan` compares files against pgmg_state. A directory that doesn't exist is
+/// treated as empty, so added or deleted code directories can be compared too.
+/// Unqualified references are resolved through `search_path`.
+pub async fn plan_offline(
+    old_dir: &Path,
+    new_dir: &Path,
+    search_path: &SearchPath,
+) -> Result<OfflinePlan, Box<dyn std::error::Error>> {
+    if !old_dir.exists() && !new_dir.exists() {
+        return Err(format!(
+            "Neither {} nor {} exists",
+            old_dir.display(),
+            new_dir.display()
+        ).into());
+    }
+
+    let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+    let old_objects = scan_dir(old_dir, &builtin_catalog).await?;
+    let new_objects = scan_dir(new_dir, &builtin_catalog).await?;
+    debug!("Offline plan: {} old objects, {} new objects", old_objects.len(), new_objects.len());
+
+    let old_graph = DependencyGraph::build_with_search_path(&old_objects, &builtin_catalog, search_path)?;
+    let new_graph = DependencyGraph::build_with_search_path(&new_objects, &builtin_catalog, search_path)?;
+
+    Ok(diff_trees(&old_objects, &new_objects, &old_graph, &new_graph))
+}
+
+async fn scan_dir(dir: &Path, builtin_catalog: &BuiltinCatalog) -> Result<Vec<SqlObject>, Box<dyn std::error::Error>> {
+    if dir.exists() {
+        scan_sql_files(dir, builtin_catalog).await
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+fn diff_trees(
+    old_objects: &[SqlObject],
+    new_objects: &[SqlObject],
+    old_graph: &DependencyGraph,
+    new_graph: &DependencyGraph,
+) -> OfflinePlan {
+    let old_by_ref: HashMap<ObjectRef, &SqlObject> = old_objects.iter().map(|obj| (ObjectRef::from(obj), obj)).collect();
+    let new_by_ref: HashMap<ObjectRef, &SqlObject> = new_objects.iter().map(|obj| (ObjectRef::from(obj), obj)).collect();
+
+    let mut changes = Vec::new();
+    let mut modified = Vec::new();
+    let mut removed = Vec::new();
+
+    for obj in new_objects {
+        let obj_ref = ObjectRef::from(obj);
+        let kind = match old_by_ref.get(&obj_ref) {
+            None => OfflineChangeKind::Added,
+            Some(old) if old.ddl_hash != obj.ddl_hash => {
+                modified.push(obj_ref);
+                OfflineChangeKind::Modified
+            }
+            Some(_) => continue,
+        };
+        changes.push(change(kind, obj));
+    }
+
+    for obj in old_objects {
+        let obj_ref = ObjectRef::from(obj);
+        if !new_by_ref.contains_key(&obj_ref) {
+            changes.push(change(OfflineChangeKind::Removed, obj));
+            removed.push(obj_ref);
+        }
+    }
+
+    changes.sort_by(|a, b| (a.kind, &a.object_name).cmp(&(b.kind, &b.object_name)));
+
+    // Dependents of modified objects are found in the new graph, dependents of
+    // removed objects in the old one. Only unchanged objects still in the new
+    // tree count; changed ones are listed already.
+    let changed: HashSet<&ObjectRef> = modified.iter().chain(&removed).collect();
+    let mut recreated: Vec<ObjectRef> = new_graph.affected_by_changes(&modified).into_iter()
+        .chain(old_graph.affected_by_changes(&removed))
+        .filter(|obj_ref| !changed.contains(obj_ref))
+        .filter(|obj_ref| old_by_ref.contains_key(obj_ref) && new_by_ref.contains_key(obj_ref))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    recreated.sort_by(|a, b| display_name(a).cmp(&display_name(b)));
+
+    let old_edges = edge_changes(old_graph);
+    let new_edges = edge_changes(new_graph);
+    let added_edges = new_edges.iter().filter(|edge| !old_edges.contains(edge)).cloned().collect();
+    let removed_edges = old_edges.iter().filter(|edge| !new_edges.contains(edge)).cloned().collect();
+
+    OfflinePlan {
+        changes,
+        recreated,
+        added_edges,
+        removed_edges,
+        new_cycle: new_graph.find_cycle(),
+    }
+}
+
+fn change(kind: OfflineChangeKind, obj: &SqlObject) -> OfflineChange {
+    OfflineChange {
+        kind,
+        object_type: obj.object_type.clone(),
+        object_name: display_name(&ObjectRef::from(obj)),
+        source_file: obj.source_file.clone(),
+        start_line: obj.start_line,
+    }
+}
+
+fn edge_changes(graph: &DependencyGraph) -> Vec<EdgeChange> {
+    graph.edges().into_iter()
+        .map(|(dependency, dependent, kind)| EdgeChange { dependency, dependent, kind })
+        .collect()
+}
+
+fn display_name(obj_ref: &ObjectRef) -> String {
+    match &obj_ref.qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, obj_ref.qualified_name.name),
+        None => obj_ref.qualified_name.name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_plan_offline_reports_changes_and_recreated_dependents() {
+        let temp_dir = tempdir().unwrap();
+        let old_dir = temp_dir.path().join("old");
+        let new_dir = temp_dir.path().join("new");
+        fs::create_dir_all(&old_dir).unwrap();
+        fs::create_dir_all(&new_dir).unwrap();
+
+        fs::write(old_dir.join("views.sql"), "
+            CREATE VIEW active_users AS SELECT 1 AS id;
+            CREATE VIEW active_user_count AS SELECT count(*) FROM active_users;
+            CREATE VIEW legacy_report AS SELECT 1 AS id;
+        ").unwrap();
+        fs::write(new_dir.join("views.sql"), "
+            CREATE VIEW active_users AS SELECT 2 AS id;
+            CREATE VIEW active_user_count AS SELECT count(*) FROM active_users;
+            CREATE VIEW signups AS SELECT 3 AS id;
+        ").unwrap();
+
+        let plan = plan_offline(&old_dir, &new_dir, &SearchPath::default()).await.unwrap();
+
+        let changes: Vec<(OfflineChangeKind, &str)> = plan.changes.iter()
+            .map(|change| (change.kind, change.object_name.as_str()))
+            .collect();
+        assert_eq!(changes, vec![
+            (OfflineChangeKind::Added, "signups"),
+            (OfflineChangeKind::Removed, "legacy_report"),
+            (OfflineChangeKind::Modified, "active_users"),
+        ]);
+
+        assert_eq!(plan.recreated.len(), 1);
+        assert_eq!(plan.recreated[0].qualified_name.name, "active_user_count");
+        assert!(plan.added_edges.is_empty());
+        assert!(plan.removed_edges.is_empty());
+        assert!(plan.new_cycle.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_plan_offline_missing_old_dir() {
+        let temp_dir = tempdir().unwrap();
+        let new_dir = temp_dir.path().join("new");
+        fs::create_dir_all(&new_dir).unwrap();
+        fs::write(new_dir.join("users.sql"), "
+            CREATE VIEW users_view AS SELECT 1 AS id;
+            CREATE VIEW users_summary AS SELECT count(*) FROM users_view;
+        ").unwrap();
+
+        let plan = plan_offline(&temp_dir.path().join("old"), &new_dir, &SearchPath::default()).await.unwrap();
+
+        assert_eq!(plan.added().count(), 2);
+        assert_eq!(plan.added_edges.len(), 1);
+        assert_eq!(plan.added_edges[0].dependency.qualified_name.name, "users_view");
+        assert_eq!(plan.added_edges[0].kind, DependencyType::Hard);
+    }
+}
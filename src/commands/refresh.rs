@@ -0,0 +1,257 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use crate::analysis::SearchPath;
+use crate::analysis::search_path::object_key;
+use crate::commands::seed::matches_pattern;
+use crate::db::{StateManager, DependencyRecord, connect_with_url};
+use crate::sql::{ObjectType, QualifiedIdent};
+use crate::sql::quote::quote_qualified_ident;
+use tracing::debug;
+
+/// One materialized view `pgmg refresh` ran REFRESH on
+#[derive(Debug, Clone)]
+pub struct RefreshedView {
+    pub name: String,
+    /// Refreshed with CONCURRENTLY; `false` for views without a unique index
+    /// or without data, which can't be refreshed that way
+    pub concurrently: bool,
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+pub struct RefreshResult {
+    pub refreshed: Vec<RefreshedView>,
+    /// The view whose refresh failed and the error. Refreshing stops there,
+    /// as views depending on it would be refreshed from stale data.
+    pub failed: Option<(String, String)>,
+    /// Views not refreshed because an earlier refresh failed
+    pub not_refreshed: Vec<String>,
+}
+
+/// Refresh the materialized views pgmg manages, each view after the
+/// materialized views it reads from, as recorded in pgmg_dependencies.
+///
+/// `views` limits the refresh to views whose name matches one of the
+/// patterns (`*` and `?` wildcards); views they depend on aren't added. Each
+/// view is refreshed in its own transaction. Unqualified names, in patterns
+/// and stored dependencies, are looked up through `search_path`.
+pub async fn execute_refresh(
+    connection_string: String,
+    views: Vec<String>,
+    concurrently: bool,
+    search_path: &SearchPath,
+) -> Result<RefreshResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let state_manager = StateManager::new(&client);
+    state_manager.initialize().await?;
+
+    let tracked = state_manager.get_tracked_objects().await?;
+    let known: Vec<QualifiedIdent> = tracked.iter().map(|record| record.object_name.clone()).collect();
+    let matviews: Vec<String> = tracked.iter()
+        .filter(|record| record.object_type == ObjectType::MaterializedView)
+        .map(|record| object_key(&record.object_name))
+        .collect();
+    let dependencies = state_manager.get_all_dependencies().await?;
+
+    let selected: Vec<String> = refresh_order(&matviews, &dependencies, search_path, &known).into_iter()
+        .filter(|name| views.is_empty() || views.iter().any(|pattern| matches_view(pattern, name, search_path)))
+        .collect();
+    if selected.is_empty() {
+        if views.is_empty() {
+            return Err("No managed materialized views to refresh".into());
+        }
+        return Err(format!("No managed materialized view matches {}", views.join(", ")).into());
+    }
+
+    let mut result = RefreshResult { refreshed: Vec::new(), failed: None, not_refreshed: Vec::new() };
+
+    for name in selected {
+        if result.failed.is_some() {
+            result.not_refreshed.push(display_name(&name));
+            continue;
+        }
+
+        let relation = quote_qualified_ident(&QualifiedIdent::from_qualified_name(&name));
+
+        // CONCURRENTLY needs the view to have data and a unique index on plain
+        // columns covering every row
+        let use_concurrently = concurrently && client.query_one(
+            "SELECT c.relispopulated AND EXISTS (
+                 SELECT 1 FROM pg_index i
+                 WHERE i.indrelid = c.oid AND i.indisunique AND i.indpred IS NULL AND i.indexprs IS NULL
+             )
+             FROM pg_class c WHERE c.oid = to_regclass($1)",
+            &[&relation],
+        ).await?.get::<_, bool>(0);
+
+        let sql = format!(
+            "REFRESH MATERIALIZED VIEW {}{}",
+            if use_concurrently { "CONCURRENTLY " } else { "" },
+            relation
+        );
+        debug!(sql = %sql, "Refreshing materialized view");
+
+        let started = Instant::now();
+        match client.batch_execute(&sql).await {
+            Ok(()) => result.refreshed.push(RefreshedView {
+                name: display_name(&name),
+                concurrently: use_concurrently,
+                duration: started.elapsed(),
+            }),
+            Err(e) => result.failed = Some((display_name(&name), e.to_string())),
+        }
+    }
+
+    Ok(result)
+}
+
+/// `matviews` ordered so that each comes after every other materialized view
+/// it depends on, directly or through views and functions. Views are
+/// otherwise sorted by name; a cycle is broken where it's first found.
+fn refresh_order(
+    matviews: &[String],
+    dependencies: &[DependencyRecord],
+    search_path: &SearchPath,
+    known: &[QualifiedIdent],
+) -> Vec<String> {
+    let mut edges: HashMap<String, Vec<String>> = HashMap::new();
+    for record in dependencies {
+        edges.entry(object_key(&QualifiedIdent::from_qualified_name(&record.dependent_name)))
+            .or_default()
+            .push(search_path.qualify(&record.dependency_name, known));
+    }
+
+    let matview_set: HashSet<&str> = matviews.iter().map(String::as_str).collect();
+    let mut sorted: Vec<&String> = matviews.iter().collect();
+    sorted.sort();
+
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    for name in sorted {
+        visit(name, &edges, &matview_set, &mut visited, &mut order);
+    }
+    order
+}
+
+/// Depth-first walk adding the materialized views `name` reads from before
+/// `name` itself
+fn visit(
+    name: &str,
+    edges: &HashMap<String, Vec<String>>,
+    matviews: &HashSet<&str>,
+    visited: &mut HashSet<String>,
+    order: &mut Vec<String>,
+) {
+    if !visited.insert(name.to_string()) {
+        return;
+    }
+
+    let mut dependencies: Vec<&String> = edges.get(name).map(|deps| deps.iter().collect()).unwrap_or_default();
+    dependencies.sort();
+    for dependency in dependencies {
+        visit(dependency, edges, matviews, visited, order);
+    }
+
+    if matviews.contains(name) {
+        order.push(name.to_string());
+    }
+}
+
+/// Unqualified patterns match views in the first schema on the search path,
+/// or in public by their printed name
+fn matches_view(pattern: &str, name: &str, search_path: &SearchPath) -> bool {
+    matches_pattern(&search_path.qualify(pattern, []), name) || matches_pattern(pattern, &display_name(name))
+}
+
+/// The name without the public schema, as the rest of pgmg prints it
+fn display_name(name: &str) -> String {
+    name.strip_prefix("public.").unwrap_or(name).to_string()
+}
+
+#[cfg(feature = "cli")]
+pub fn print_refresh_summary(result: &RefreshResult) {
+    use crate::logging::{format_duration, output};
+    use owo_colors::OwoColorize;
+
+    for view in &result.refreshed {
+        output::line(format!(
+            "{} {} {}",
+            output::symbol("✓").green(),
+            view.name.bright_blue(),
+            format!("({}{})", format_duration(view.duration), if view.concurrently { ", concurrently" } else { "" }).dimmed()
+        ));
+    }
+
+    if let Some((name, error)) = &result.failed {
+        output::error(format!("Failed to refresh {}: {}", name, error));
+        for name in &result.not_refreshed {
+            output::line(format!("{} {} {}", output::symbol("↷").bright_black(), name, "(not refreshed)".dimmed()));
+        }
+    } else {
+        let total: Duration = result.refreshed.iter().map(|view| view.duration).sum();
+        output::info(format!("Refreshed {} materialized view(s) in {}", result.refreshed.len(), format_duration(total)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(dependent: &str, dependency: &str) -> DependencyRecord {
+        DependencyRecord {
+            dependent_type: ObjectType::MaterializedView,
+            dependent_name: dependent.to_string(),
+            dependency_name: dependency.to_string(),
+            is_hard: true,
+        }
+    }
+
+    #[test]
+    fn test_refresh_order() {
+        let matviews: Vec<String> = ["public.a_summary", "reporting.daily", "reporting.monthly"]
+            .iter().map(|name| name.to_string()).collect();
+        let dependencies = vec![
+            // a_summary reads monthly through a plain view
+            dependency("a_summary", "reporting.monthly_view"),
+            dependency("reporting.monthly_view", "reporting.monthly"),
+            dependency("reporting.monthly", "reporting.daily"),
+            dependency("reporting.daily", "orders"),
+        ];
+
+        assert_eq!(refresh_order(&matviews, &dependencies, &SearchPath::default(), &[]), vec![
+            "reporting.daily", "reporting.monthly", "public.a_summary",
+        ]);
+    }
+
+    #[test]
+    fn test_matches_view() {
+        let search_path = SearchPath::default();
+        assert!(matches_view("reporting.*", "reporting.daily", &search_path));
+        assert!(matches_view("a_summary", "public.a_summary", &search_path));
+        assert!(matches_view("a_*", "public.a_summary", &search_path));
+        assert!(!matches_view("daily", "reporting.daily", &search_path));
+
+        let search_path = SearchPath::new(vec!["reporting".to_string(), "public".to_string()]);
+        assert!(matches_view("daily", "reporting.daily", &search_path));
+        assert!(matches_view("a_summary", "public.a_summary", &search_path));
+    }
+
+    #[test]
+    fn test_refresh_order_resolves_unqualified_dependencies() {
+        let matviews: Vec<String> = ["reporting.monthly", "reporting.weekly"]
+            .iter().map(|name| name.to_string()).collect();
+        // Stored as written in files with reporting first on the search path
+        let dependencies = vec![dependency("reporting.monthly", "weekly")];
+        let known = vec![
+            QualifiedIdent::from_qualified_name("reporting.monthly"),
+            QualifiedIdent::from_qualified_name("reporting.weekly"),
+        ];
+        let search_path = SearchPath::new(vec!["reporting".to_string(), "public".to_string()]);
+
+        assert_eq!(refresh_order(&matviews, &dependencies, &search_path, &known), vec![
+            "reporting.weekly", "reporting.monthly",
+        ]);
+    }
+}
@@ -1,6 +1,8 @@
 use std::io::{self, Write};
 use crate::db::{StateManager, connection::{DatabaseConfig, connect_to_database}};
 use owo_colors::OwoColorize;
+use crate::logging::output;
+use crate::sql::quote::quote_ident;
 
 #[derive(Debug)]
 pub struct ResetResult {
@@ -28,28 +30,28 @@ pub async fn execute_reset(
         ..target_config.clone()
     };
 
-    println!("{} Connecting to PostgreSQL server...", "→".cyan());
+    output::line(format!("{} Connecting to PostgreSQL server...", output::symbol("→").cyan()));
     let (admin_client, admin_connection) = connect_to_database(&admin_config).await?;
     
     // Spawn connection handler
     admin_connection.spawn();
 
     // Step 1: Terminate active connections to the target database
-    println!("{} Terminating active connections to database '{}'...", "→".cyan(), database_name);
+    output::line(format!("{} Terminating active connections to database '{}'...", output::symbol("→").cyan(), database_name));
     terminate_active_connections(&admin_client, &database_name).await?;
 
     // Step 2: Drop the database if it exists
-    println!("{} Dropping database '{}'...", "→".cyan(), database_name);
-    let drop_query = format!("DROP DATABASE IF EXISTS \"{}\"", database_name);
+    output::line(format!("{} Dropping database '{}'...", output::symbol("→").cyan(), database_name));
+    let drop_query = format!("DROP DATABASE IF EXISTS {}", quote_ident(&database_name));
     admin_client.execute(&drop_query, &[]).await?;
 
     // Step 3: Create a fresh database
-    println!("{} Creating fresh database '{}'...", "→".cyan(), database_name);
-    let create_query = format!("CREATE DATABASE \"{}\"", database_name);
+    output::line(format!("{} Creating fresh database '{}'...", output::symbol("→").cyan(), database_name));
+    let create_query = format!("CREATE DATABASE {}", quote_ident(&database_name));
     admin_client.execute(&create_query, &[]).await?;
 
     // Step 4: Connect to the new database and initialize state tables
-    println!("{} Initializing pgmg state tables...", "→".cyan());
+    output::line(format!("{} Initializing pgmg state tables...", output::symbol("→").cyan()));
     let (target_client, target_connection) = connect_to_database(&target_config).await?;
     
     // Spawn connection handler for target database
@@ -63,15 +65,11 @@ pub async fn execute_reset(
 }
 
 async fn confirm_reset(database_name: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    println!();
-    println!("{}", "⚠️  WARNING: DESTRUCTIVE OPERATION".red().bold());
-    println!("{}", "⚠️  This will completely destroy the database and all its data!".red());
-    println!("{} Database: {}", "⚠️  Target:".red(), database_name.yellow().bold());
-    println!("{}", "⚠️  All tables, views, functions, data, and objects will be permanently lost!".red());
-    println!("{}", "⚠️  Make sure you have a backup if you need to preserve any data.".red());
-    println!();
+    print_reset_warning(database_name);
     
-    print!("{} ", "Type the database name to confirm:".bold());
+    // The prompt stays on the line the name is typed on, and is shown even in
+    // quiet mode, so it's printed directly rather than through the output sink
+    print!("{} ", output::render("Type the database name to confirm:".bold()));
     io::stdout().flush()?;
     
     let mut input = String::new();
@@ -79,14 +77,26 @@ async fn confirm_reset(database_name: &str) -> Result<bool, Box<dyn std::error::
     let input = input.trim();
     
     if input == database_name {
-        println!("{} Proceeding with database reset...", "✓".green());
+        output::success("Proceeding with database reset...");
         Ok(true)
     } else {
-        println!("{} Database name mismatch. Reset cancelled.", "✗".red());
+        output::error("Database name mismatch. Reset cancelled.");
         Ok(false)
     }
 }
 
+/// Warn that the database and all its data are about to be destroyed, before
+/// asking for confirmation
+pub(crate) fn print_reset_warning(database_name: &str) {
+    output::line("");
+    output::warning("WARNING: DESTRUCTIVE OPERATION".bold());
+    output::warning("This will completely destroy the database and all its data!");
+    output::warning(format!("Target database: {}", database_name.bold()));
+    output::warning("All tables, views, functions, data, and objects will be permanently lost!");
+    output::warning("Make sure you have a backup if you need to preserve any data.");
+    output::line("");
+}
+
 async fn terminate_active_connections(
     admin_client: &tokio_postgres::Client,
     database_name: &str,
@@ -101,17 +111,17 @@ async fn terminate_active_connections(
     let rows = admin_client.query(terminate_query, &[&database_name]).await?;
     
     if !rows.is_empty() {
-        println!("{} Terminated {} active connection(s)", "→".cyan(), rows.len());
+        output::line(format!("{} Terminated {} active connection(s)", output::symbol("→").cyan(), rows.len()));
     }
     
     Ok(())
 }
 
 pub fn print_reset_summary(result: &ResetResult) {
-    println!();
-    println!("{} {}", "✅".green(), "Database reset completed successfully!".green().bold());
-    println!("{} Database '{}' has been dropped and recreated", "→".cyan(), result.database_name.yellow());
-    println!("{} pgmg state tables have been initialized", "→".cyan());
-    println!();
-    println!("{} The database is now ready for migrations and SQL objects", "💡".cyan());
+    output::line("");
+    output::line(format!("{} {}", output::symbol("✅").green(), "Database reset completed successfully!".green().bold()));
+    output::line(format!("{} Database '{}' has been dropped and recreated", output::symbol("→").cyan(), result.database_name.yellow()));
+    output::line(format!("{} pgmg state tables have been initialized", output::symbol("→").cyan()));
+    output::line("");
+    output::line(format!("{} The database is now ready for migrations and SQL objects", output::symbol("💡").cyan()));
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 use std::process::Command;
 use crate::config::PgmgConfig;
+use crate::logging::output;
 #[cfg(feature = "cli")]
 use owo_colors::OwoColorize;
 
@@ -22,10 +23,10 @@ pub async fn execute_run(
     
     // Display file being run
     #[cfg(feature = "cli")]
-    println!("{} Running: {} (via psql)", "→".cyan(), file.display().to_string().bright_blue());
+    output::line(format!("{} Running: {} (via psql)", output::symbol("→").cyan(), file.display().to_string().bright_blue()));
     #[cfg(not(feature = "cli"))]
-    println!("→ Running: {} (via psql)", file.display());
-    println!();
+    output::line(format!("{} Running: {} (via psql)", output::symbol("→"), file.display()));
+    output::line("");
     
     // Check if psql is available
     match Command::new("psql").arg("--version").output() {
@@ -48,9 +49,9 @@ pub async fn execute_run(
     
     if status.success() {
         #[cfg(feature = "cli")]
-        println!("\n{} SQL file executed successfully", "✓".green().bold());
+        output::line(format!("\n{} SQL file executed successfully", output::symbol("✓").green().bold()));
         #[cfg(not(feature = "cli"))]
-        println!("\n✓ SQL file executed successfully");
+        output::line(format!("\n{} SQL file executed successfully", output::symbol("✓")));
         Ok(())
     } else {
         Err(format!("psql exited with status: {}", status).into())
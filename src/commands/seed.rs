@@ -1,32 +1,121 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
-use crate::db::connect_with_url;
+use crate::db::{StateManager, connect_with_url};
+use crate::sql::quote::{quote_ident, quote_qualified};
+use crate::sql::splitter::split_sql_file;
 use owo_colors::OwoColorize;
-use tracing::{debug, info};
+use crate::logging::output;
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt};
+use sha2::{Digest, Sha256};
+use tokio_postgres::{GenericClient, Transaction};
+use tracing::{debug, info, warn};
+
+/// File in the seed directory listing the seed files to run, in order
+pub const SEED_MANIFEST: &str = "manifest.txt";
+
+/// CSV data is sent to COPY in chunks of this size
+const COPY_CHUNK_BYTES: usize = 64 * 1024;
+
+/// How seed files are wrapped in transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeedTransactionMode {
+    /// All files run in one transaction; any failure rolls everything back
+    #[default]
+    Single,
+    /// Each file runs in its own transaction; files before a failure stay committed
+    PerFile,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SeedOptions {
+    pub transaction_mode: SeedTransactionMode,
+    /// Only run these files (by file name, with or without the extension)
+    pub only: Vec<String>,
+    /// Skip files whose name matches one of these patterns (`*` and `?` wildcards)
+    pub skip: Vec<String>,
+    /// Record applied files in pgmg.pgmg_seeds and skip those whose content
+    /// hasn't changed since
+    pub track: bool,
+    /// With `track`, run files again even if they haven't changed
+    pub force: bool,
+    /// Empty tables before loading CSV files into them
+    pub truncate_csv: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SeedFileResult {
+    pub file_name: String,
+    /// Rows affected by the file's statements
+    pub rows_affected: u64,
+}
 
 #[derive(Debug)]
 pub struct SeedResult {
-    pub files_processed: Vec<String>,
+    pub files_processed: Vec<SeedFileResult>,
+    /// Files left out by --only or --skip
+    pub files_skipped: Vec<String>,
+    /// Tracked files already applied with the same content
+    pub files_unchanged: Vec<String>,
     pub errors: Vec<String>,
 }
 
+/// Options for `pgmg seed reset`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeedResetOptions {
+    /// Restart the sequences owned by the emptied tables (serial and identity columns)
+    pub restart_identity: bool,
+    /// Empty the tables with DELETE, one at a time, instead of a single
+    /// TRUNCATE. Slower, but fires DELETE triggers and only needs DELETE
+    /// privileges.
+    pub delete: bool,
+    /// List the tables and statements without running them
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub struct SeedResetResult {
+    /// Tables emptied (or that would be), referencing tables before the tables they reference
+    pub tables: Vec<String>,
+    /// Statements run, in order
+    pub statements: Vec<String>,
+    /// Tables named by seed files that don't exist
+    pub missing: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// A seed file about to run
+struct SeedFile {
+    file_name: String,
+    content: String,
+    content_hash: String,
+}
+
 pub async fn execute_seed(
     seed_dir: PathBuf,
     connection_string: String,
-) -> Result<SeedResult, Box<dyn std::error::Error>> {
-    // Connect to database
-    let (mut client, connection) = connect_with_url(&connection_string).await?;
-    
-    // Spawn connection handler
-    connection.spawn();
+    options: &SeedOptions,
+) -> crate::error::Result<SeedResult> {
+    Ok(seed(seed_dir, connection_string, options).await?)
+}
 
+async fn seed(
+    seed_dir: PathBuf,
+    connection_string: String,
+    options: &SeedOptions,
+) -> Result<SeedResult, Box<dyn std::error::Error>> {
     let mut result = SeedResult {
         files_processed: Vec::new(),
+        files_skipped: Vec::new(),
+        files_unchanged: Vec::new(),
         errors: Vec::new(),
     };
 
-    // Scan seed directory for .sql files
+    // Work out which seed files to run, and in what order
     let seed_files = scan_seed_files(&seed_dir)?;
+    let (seed_files, skipped) = filter_seed_files(seed_files, options)?;
+    result.files_skipped = skipped;
     
     if seed_files.is_empty() {
         info!("No seed files found in directory: {}", seed_dir.display());
@@ -34,61 +123,147 @@ pub async fn execute_seed(
     }
 
     info!("Found {} seed files to execute", seed_files.len());
+
+    // Connect to database
+    let (mut client, connection) = connect_with_url(&connection_string).await?;
     
-    // Start transaction for all seed files
-    let transaction = client.transaction().await?;
-    
-    let mut transaction_aborted = false;
-    
-    for seed_file in &seed_files {
-        if transaction_aborted {
-            break;
-        }
-        
-        let file_name = seed_file.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-            
-        debug!("Processing seed file: {}", file_name);
-        
-        match process_seed_file(&transaction, seed_file).await {
-            Ok(()) => {
-                result.files_processed.push(file_name.to_string());
-                println!("  {} Executed {}",
-                    "✓".green().bold(),
-                    file_name.cyan(),
-                );
+    // Spawn connection handler
+    connection.spawn();
+
+    // With tracking, files applied before with the same content are skipped
+    let applied_hashes = if options.track {
+        let state_manager = StateManager::new(&client);
+        state_manager.initialize().await?;
+        state_manager.get_seed_hashes().await?
+    } else {
+        HashMap::new()
+    };
+
+    let mut pending = Vec::new();
+    for path in &seed_files {
+        let content = fs::read_to_string(path)?;
+        let seed_file = SeedFile {
+            file_name: path.file_name().and_then(|n| n.to_str()).unwrap_or("unknown").to_string(),
+            content_hash: content_hash(&content),
+            content,
+        };
+
+        if !options.force && applied_hashes.get(&seed_file.file_name) == Some(&seed_file.content_hash) {
+            debug!("Seed file unchanged since it was applied: {}", seed_file.file_name);
+            result.files_unchanged.push(seed_file.file_name);
+        } else {
+            pending.push(seed_file);
+        }
+    }
+
+    if pending.is_empty() {
+        output::line("All seed files are already applied.".green().bold());
+        return Ok(result);
+    }
+
+    match options.transaction_mode {
+        SeedTransactionMode::Single => {
+            // Start transaction for all seed files
+            let transaction = client.transaction().await?;
+
+            for seed_file in &pending {
+                if !run_seed_file(&transaction, seed_file, options, &mut result).await {
+                    break;
+                }
             }
-            Err(e) => {
-                let error_msg = format!("Failed to process {}: {}", file_name, e);
-                result.errors.push(error_msg.clone());
-                println!("  {} {}", "✗".red().bold(), error_msg.red());
-                transaction_aborted = true;
+
+            // Commit or rollback transaction
+            if result.errors.is_empty() {
+                transaction.commit().await?;
+            } else {
+                transaction.rollback().await?;
+                output::error(format!(
+                    "Rolled back due to {} errors:\n  - {}",
+                    result.errors.len(),
+                    result.errors.join("\n  - ")
+                ));
+                return Err("Seed operation failed - all changes rolled back".into());
             }
         }
-    }
-    
-    // Commit or rollback transaction
-    if result.errors.is_empty() {
-        transaction.commit().await?;
-        println!("{}", "All seed files executed successfully!".green().bold());
-    } else {
-        transaction.rollback().await?;
-        eprintln!("{} {} {}", 
-            "Rolled back due to".red().bold(), 
-            result.errors.len().to_string().yellow(), 
-            "errors:".red().bold()
-        );
-        for error in &result.errors {
-            eprintln!("  {} {}", "-".red().bold(), error.red());
+        SeedTransactionMode::PerFile => {
+            for seed_file in &pending {
+                let transaction = client.transaction().await?;
+                if run_seed_file(&transaction, seed_file, options, &mut result).await {
+                    transaction.commit().await?;
+                } else {
+                    transaction.rollback().await?;
+                    break;
+                }
+            }
+
+            if !result.errors.is_empty() {
+                output::error(format!(
+                    "Rolled back the failing file, {} earlier files stay committed:\n  - {}",
+                    result.files_processed.len(),
+                    result.errors.join("\n  - ")
+                ));
+                return Err(format!(
+                    "Seed operation failed - {} of {} files were committed",
+                    result.files_processed.len(),
+                    pending.len()
+                ).into());
+            }
         }
-        return Err("Seed operation failed - all changes rolled back".into());
     }
 
+    output::line("All seed files executed successfully!".green().bold());
+
     Ok(result)
 }
 
-/// Scan the seed directory for .sql files and return them in alphanumeric order
+/// Run one seed file, recording the outcome. Returns false if it failed.
+async fn run_seed_file(
+    transaction: &Transaction<'_>,
+    seed_file: &SeedFile,
+    options: &SeedOptions,
+    result: &mut SeedResult,
+) -> bool {
+    let file_name = seed_file.file_name.as_str();
+        
+    debug!("Processing seed file: {}", file_name);
+
+    let outcome = if is_csv(file_name) {
+        load_csv_file(transaction, seed_file, options.truncate_csv).await
+    } else {
+        process_seed_file(transaction, &seed_file.content).await
+    };
+
+    let outcome = match outcome {
+        // Recorded in the same transaction, so it's only kept if the file's changes are
+        Ok(rows_affected) if options.track => record_seed(transaction, seed_file).await.map(|_| rows_affected),
+        outcome => outcome,
+    };
+    
+    match outcome {
+        Ok(rows_affected) => {
+            result.files_processed.push(SeedFileResult {
+                file_name: file_name.to_string(),
+                rows_affected,
+            });
+            output::line(format!("  {} Executed {} {}",
+                output::symbol("✓").green().bold(),
+                file_name.cyan(),
+                format!("({} rows)", rows_affected).dimmed(),
+            ));
+            true
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to process {}: {}", file_name, e);
+            result.errors.push(error_msg.clone());
+            output::line(format!("  {} {}", output::symbol("✗").red().bold(), error_msg.red()));
+            false
+        }
+    }
+}
+
+/// Scan the seed directory for .sql and .csv files and return them in the
+/// order they should run: the order listed in the manifest if there is one,
+/// otherwise by numeric prefix and then name
 fn scan_seed_files(seed_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let entries = fs::read_dir(seed_dir)?;
     let mut sql_files = Vec::new();
@@ -97,64 +272,536 @@ fn scan_seed_files(seed_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::
         let entry = entry?;
         let path = entry.path();
         
-        // Only include .sql files (not directories or other files)
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("sql") {
+        // Only include .sql and .csv files (not directories or other files)
+        if path.is_file() && matches!(path.extension().and_then(|s| s.to_str()), Some("sql") | Some("csv")) {
             sql_files.push(path);
         }
     }
+
+    let manifest_path = seed_dir.join(SEED_MANIFEST);
+    if manifest_path.is_file() {
+        return order_by_manifest(&fs::read_to_string(&manifest_path)?, seed_dir, sql_files);
+    }
     
-    // Sort files alphanumerically (lexicographic order)
-    sql_files.sort();
+    // Numeric prefixes are compared as numbers, so 10_orders.sql runs after 2_users.sql
+    sql_files.sort_by_key(|path| {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+        (numeric_prefix(&file_name).unwrap_or(u64::MAX), file_name)
+    });
     
     Ok(sql_files)
 }
 
-/// Process a single seed file by executing all its statements via batch_execute
-async fn process_seed_file(
-    client: &tokio_postgres::Transaction<'_>,
-    file_path: &Path,
+/// Leading digits of a file name, e.g. 10 for `010_orders.sql`
+fn numeric_prefix(file_name: &str) -> Option<u64> {
+    let digits: String = file_name.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Order seed files as listed in the manifest: one file name per line, with
+/// blank lines and `#` comments ignored. Files missing from the manifest are
+/// not run.
+fn order_by_manifest(
+    manifest: &str,
+    seed_dir: &Path,
+    sql_files: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut ordered = Vec::new();
+
+    for entry in manifest.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let path = seed_dir.join(entry);
+        if !sql_files.contains(&path) {
+            return Err(format!("{} lists {}, which is not a .sql or .csv file in {}", SEED_MANIFEST, entry, seed_dir.display()).into());
+        }
+        if ordered.contains(&path) {
+            return Err(format!("{} lists {} more than once", SEED_MANIFEST, entry).into());
+        }
+        ordered.push(path);
+    }
+
+    for path in sql_files.iter().filter(|path| !ordered.contains(path)) {
+        warn!(file = %path.display(), "Seed file is not listed in {} and will not run", SEED_MANIFEST);
+    }
+
+    Ok(ordered)
+}
+
+/// Apply --only and --skip, returning the files to run and the names of those left out
+fn filter_seed_files(
+    seed_files: Vec<PathBuf>,
+    options: &SeedOptions,
+) -> Result<(Vec<PathBuf>, Vec<String>), Box<dyn std::error::Error>> {
+    let file_name = |path: &Path| path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+
+    for only in &options.only {
+        if !seed_files.iter().any(|path| matches_only(only, &file_name(path))) {
+            return Err(format!("No seed file named {}", only).into());
+        }
+    }
+
+    let (run, skipped): (Vec<PathBuf>, Vec<PathBuf>) = seed_files.into_iter().partition(|path| {
+        let name = file_name(path);
+        (options.only.is_empty() || options.only.iter().any(|only| matches_only(only, &name)))
+            && !options.skip.iter().any(|pattern| matches_pattern(pattern, &name))
+    });
+
+    Ok((run, skipped.iter().map(|path| file_name(path)).collect()))
+}
+
+fn matches_only(only: &str, file_name: &str) -> bool {
+    only == file_name || file_name.strip_suffix(".sql").or_else(|| file_name.strip_suffix(".csv")) == Some(only)
+}
+
+/// Match a file name against a pattern where `*` matches any run of
+/// characters and `?` matches a single character
+pub(crate) fn matches_pattern(pattern: &str, file_name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = file_name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` swallow one more character
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, n));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Process a single seed file, executing its statements one at a time so the
+/// affected row counts can be reported
+async fn process_seed_file<C: GenericClient>(
+    client: &C,
+    content: &str,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut rows_affected = 0;
+    for statement in split_sql_file(content)? {
+        rows_affected += client.execute(&statement.sql, &[]).await
+            .map_err(|e| match statement.start_line {
+                Some(line) => format!("line {}: {}", line, e),
+                None => e.to_string(),
+            })?;
+    }
+    Ok(rows_affected)
+}
+
+fn is_csv(file_name: &str) -> bool {
+    file_name.ends_with(".csv")
+}
+
+/// Load a `schema.table.csv` file into its table with COPY. The first line
+/// must name the columns, so they don't have to be in table order.
+async fn load_csv_file(
+    transaction: &Transaction<'_>,
+    seed_file: &SeedFile,
+    truncate: bool,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let table = csv_target_table(&seed_file.file_name)?;
+    let header = seed_file.content.lines().next()
+        .ok_or("CSV file is empty; the first line must name the columns")?;
+    let columns = parse_csv_header(header)?
+        .iter()
+        .map(|column| quote_ident(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if truncate {
+        transaction.execute(&format!("TRUNCATE {}", table), &[]).await?;
+    }
+
+    let sink = transaction.copy_in(&format!("COPY {} ({}) FROM STDIN WITH (FORMAT csv, HEADER true)", table, columns)).await?;
+    pin_mut!(sink);
+    for chunk in seed_file.content.as_bytes().chunks(COPY_CHUNK_BYTES) {
+        sink.send(Bytes::copy_from_slice(chunk)).await?;
+    }
+    Ok(sink.finish().await?)
+}
+
+/// The quoted table a CSV seed file loads into: `orders.csv` loads
+/// public.orders and `sales.orders.csv` loads sales.orders. A numeric prefix
+/// for ordering (`010_sales.orders.csv`) is ignored.
+fn csv_target_table(file_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let name = file_name.strip_suffix(".csv").unwrap_or(file_name);
+    let name = match name.split_once('_') {
+        Some((prefix, rest)) if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => name,
+    };
+
+    match name.split('.').collect::<Vec<_>>()[..] {
+        [table] if !table.is_empty() => Ok(quote_ident(table)),
+        [schema, table] if !schema.is_empty() && !table.is_empty() => {
+            Ok(quote_qualified(Some(schema), table))
+        }
+        _ => Err(format!("CSV seed files must be named table.csv or schema.table.csv, not {}", file_name).into()),
+    }
+}
+
+/// Split a CSV header line into column names, honouring double quotes
+fn parse_csv_header(line: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut columns = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => columns.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quote in CSV header".into());
+    }
+    columns.push(current.trim().to_string());
+
+    if columns.iter().any(|column| column.is_empty()) {
+        return Err("CSV header has an empty column name".into());
+    }
+    Ok(columns)
+}
+
+/// Record a seed file as applied with its current content
+async fn record_seed<C: GenericClient>(
+    client: &C,
+    seed_file: &SeedFile,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(file_path)?;
-    client.batch_execute(&content).await?;
+    client.execute(
+        r#"
+        INSERT INTO pgmg.pgmg_seeds (file_name, content_hash)
+        VALUES ($1, $2)
+        ON CONFLICT (file_name) DO UPDATE
+        SET content_hash = EXCLUDED.content_hash, applied_at = NOW()
+        "#,
+        &[&seed_file.file_name, &seed_file.content_hash],
+    ).await?;
     Ok(())
 }
 
+/// Hash of a seed file's exact content. Unlike object DDL, seed data is not
+/// normalized first: a change in case or whitespace inside a literal is a
+/// change to the data.
+fn content_hash(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Empty every table the seed files load data into, so they can be run
+/// again. Tables are found from the targets of INSERT, COPY FROM and MERGE
+/// statements and from CSV file names, and emptied referencing tables first,
+/// by the foreign keys in the database. All in one transaction.
+///
+/// Tables seed files only UPDATE or DELETE from are left alone, since their
+/// rows usually come from migrations. Tables outside the seed files that
+/// reference a seeded table would stop TRUNCATE, so they are reported as an
+/// error rather than emptied with CASCADE.
+pub async fn execute_seed_reset(
+    seed_dir: PathBuf,
+    connection_string: String,
+    options: &SeedResetOptions,
+) -> crate::error::Result<SeedResetResult> {
+    Ok(reset(seed_dir, connection_string, options).await?)
+}
+
+async fn reset(
+    seed_dir: PathBuf,
+    connection_string: String,
+    options: &SeedResetOptions,
+) -> Result<SeedResetResult, Box<dyn std::error::Error>> {
+    let mut result = SeedResetResult {
+        tables: Vec::new(),
+        statements: Vec::new(),
+        missing: Vec::new(),
+        dry_run: options.dry_run,
+    };
+
+    // Tables in the order the seed files first load them
+    let mut table_names: Vec<String> = Vec::new();
+    for path in scan_seed_files(&seed_dir)? {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("unknown").to_string();
+        let names = if is_csv(&file_name) {
+            vec![csv_target_table(&file_name)?]
+        } else {
+            seeded_tables(&fs::read_to_string(&path)?)
+                .map_err(|e| format!("Failed to parse {}: {}", file_name, e))?
+        };
+        for name in names {
+            if !table_names.contains(&name) {
+                table_names.push(name);
+            }
+        }
+    }
+
+    if table_names.is_empty() {
+        info!("No seeded tables found in directory: {}", seed_dir.display());
+        return Ok(result);
+    }
+
+    let (mut client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    // Resolve the names as the seed files would, through the search_path
+    let mut tables: Vec<(u32, String)> = Vec::new();
+    for name in table_names {
+        let row = client.query_opt(
+            "SELECT c.oid, c.oid::regclass::text FROM pg_class c WHERE c.oid = to_regclass($1) AND c.relkind IN ('r', 'p')",
+            &[&name],
+        ).await?;
+        match row {
+            Some(row) => {
+                let oid: u32 = row.get(0);
+                // `users` and `public.users` are the same table
+                if !tables.iter().any(|(table, _)| *table == oid) {
+                    tables.push((oid, row.get(1)));
+                }
+            }
+            None => result.missing.push(name),
+        }
+    }
+    if tables.is_empty() {
+        return Ok(result);
+    }
+    let oids: Vec<u32> = tables.iter().map(|(oid, _)| *oid).collect();
+
+    // Foreign keys into the seeded tables. Partitions' copies of a
+    // partitioned table's keys are left out.
+    let foreign_keys = client.query(
+        "SELECT conrelid, confrelid, conrelid::regclass::text, confrelid::regclass::text \
+         FROM pg_constraint WHERE contype = 'f' AND conparentid = 0 AND confrelid = ANY($1)",
+        &[&oids],
+    ).await?;
+    let references: Vec<(u32, u32)> = foreign_keys.iter().map(|row| (row.get(0), row.get(1))).collect();
+
+    if !options.delete {
+        let mut outside: Vec<String> = foreign_keys.iter()
+            .filter(|row| !oids.contains(&row.get::<_, u32>(0)))
+            .map(|row| format!("{} references {}", row.get::<_, String>(2), row.get::<_, String>(3)))
+            .collect();
+        if !outside.is_empty() {
+            outside.sort();
+            return Err(format!(
+                "Tables that aren't seeded reference seeded tables, so TRUNCATE would fail:\n  - {}\n\
+                Seed them too, empty them first, or use --delete if none of their rows reference seed data.",
+                outside.join("\n  - ")
+            ).into());
+        }
+    }
+
+    let order = reset_order(&oids, &references);
+    result.tables = order.iter()
+        .filter_map(|oid| tables.iter().find(|(table, _)| table == oid).map(|(_, name)| name.clone()))
+        .collect();
+
+    if options.delete {
+        result.statements.extend(result.tables.iter().map(|table| format!("DELETE FROM {}", table)));
+        if options.restart_identity {
+            // The sequences TRUNCATE ... RESTART IDENTITY would restart
+            let sequences = client.query(
+                "SELECT s.oid::regclass::text FROM pg_depend d \
+                 JOIN pg_class s ON s.oid = d.objid AND s.relkind = 'S' \
+                 WHERE d.classid = 'pg_class'::regclass AND d.refclassid = 'pg_class'::regclass \
+                 AND d.deptype IN ('a', 'i') AND d.refobjid = ANY($1) \
+                 ORDER BY 1",
+                &[&oids],
+            ).await?;
+            result.statements.extend(sequences.iter().map(|row| format!("ALTER SEQUENCE {} RESTART", row.get::<_, String>(0))));
+        }
+    } else {
+        result.statements.push(format!(
+            "TRUNCATE {}{}",
+            result.tables.join(", "),
+            if options.restart_identity { " RESTART IDENTITY" } else { "" }
+        ));
+    }
+
+    if options.dry_run {
+        return Ok(result);
+    }
+
+    let transaction = client.transaction().await?;
+    for statement in &result.statements {
+        debug!("Seed reset: {}", statement);
+        transaction.batch_execute(statement).await
+            .map_err(|e| format!("{}: {}", statement, e))?;
+    }
+    transaction.commit().await?;
+
+    Ok(result)
+}
+
+/// Tables a seed SQL file loads rows into, quoted as written: the targets of
+/// INSERT (including in WITH clauses), COPY ... FROM and MERGE
+fn seeded_tables(sql: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    use pg_query::NodeEnum;
+
+    fn visit(node: &NodeEnum, tables: &mut Vec<String>) {
+        let (relation, with_clause) = match node {
+            NodeEnum::InsertStmt(insert) => (insert.relation.as_ref(), insert.with_clause.as_ref()),
+            NodeEnum::MergeStmt(merge) => (merge.relation.as_ref(), merge.with_clause.as_ref()),
+            NodeEnum::CopyStmt(copy) if copy.is_from => (copy.relation.as_ref(), None),
+            NodeEnum::SelectStmt(select) => (None, select.with_clause.as_ref()),
+            NodeEnum::UpdateStmt(update) => (None, update.with_clause.as_ref()),
+            NodeEnum::DeleteStmt(delete) => (None, delete.with_clause.as_ref()),
+            _ => (None, None),
+        };
+
+        if let Some(relation) = relation {
+            let schema = Some(relation.schemaname.as_str()).filter(|schema| !schema.is_empty());
+            let table = quote_qualified(schema, &relation.relname);
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+        for cte in with_clause.into_iter().flat_map(|with| &with.ctes) {
+            if let Some(NodeEnum::CommonTableExpr(cte)) = &cte.node {
+                if let Some(query) = cte.ctequery.as_ref().and_then(|query| query.node.as_ref()) {
+                    visit(query, tables);
+                }
+            }
+        }
+    }
+
+    let parsed = pg_query::parse(sql)?;
+    let mut tables = Vec::new();
+    for stmt in &parsed.protobuf.stmts {
+        if let Some(node) = stmt.stmt.as_ref().and_then(|stmt| stmt.node.as_ref()) {
+            visit(node, &mut tables);
+        }
+    }
+    Ok(tables)
+}
+
+/// Order tables so each comes before the tables it references, keeping the
+/// given order otherwise. Tables in a reference cycle keep the given order;
+/// a single TRUNCATE handles them, DELETE may not.
+fn reset_order<T: Copy + PartialEq>(tables: &[T], references: &[(T, T)]) -> Vec<T> {
+    let mut remaining: Vec<T> = tables.to_vec();
+    let mut order = Vec::with_capacity(tables.len());
+
+    while !remaining.is_empty() {
+        // The first table no other remaining table still references
+        let next = remaining.iter().position(|table| {
+            !references.iter().any(|(referencing, referenced)| {
+                referenced == table && referencing != table && remaining.contains(referencing)
+            })
+        }).unwrap_or(0);
+        order.push(remaining.remove(next));
+    }
+
+    order
+}
+
 pub fn print_seed_summary(result: &SeedResult) {
-    println!("\n{}", "=== PGMG Seed Summary ===".bold().blue());
+    output::line(format!("\n{}", "=== PGMG Seed Summary ===".bold().blue()));
     
     if !result.files_processed.is_empty() {
-        println!("\n{}:", "Files Processed".bold().green());
+        output::line(format!("\n{}:", "Files Processed".bold().green()));
         for file in &result.files_processed {
-            println!("  {} {}", "✓".green().bold(), file.cyan());
+            output::line(format!("  {} {} {}",
+                output::symbol("✓").green().bold(),
+                file.file_name.cyan(),
+                format!("({} rows)", file.rows_affected).dimmed(),
+            ));
         }
         
-        println!("\n{}: {} files",
+        output::line(format!("\n{}: {} files, {} rows",
             "Summary".bold(),
             result.files_processed.len().to_string().yellow(),
-        );
+            result.files_processed.iter().map(|file| file.rows_affected).sum::<u64>().to_string().yellow(),
+        ));
+    }
+
+    if !result.files_unchanged.is_empty() {
+        output::line(format!("\n{}:", "Already Applied".bold().dimmed()));
+        for file in &result.files_unchanged {
+            output::line(format!("  {} {}", "=".dimmed(), file.dimmed()));
+        }
+    }
+
+    if !result.files_skipped.is_empty() {
+        output::line(format!("\n{}:", "Files Skipped".bold().dimmed()));
+        for file in &result.files_skipped {
+            output::line(format!("  {} {}", "-".dimmed(), file.dimmed()));
+        }
     }
     
     if !result.errors.is_empty() {
-        println!("\n{}:", "Errors".bold().red());
+        output::line(format!("\n{}:", "Errors".bold().red()));
         for error in &result.errors {
-            println!("  {} {}", "✗".red().bold(), error.red());
+            output::line(format!("  {} {}", output::symbol("✗").red().bold(), error.red()));
         }
     }
     
-    if result.files_processed.is_empty() && result.errors.is_empty() {
-        println!("\n{}", "No seed files found or processed.".yellow());
+    if result.files_processed.is_empty() && result.errors.is_empty() && !result.files_unchanged.is_empty() {
+        output::line(format!("\n{}", "All seed files are already applied (use --force to run them again).".green()));
+    } else if result.files_processed.is_empty() && result.errors.is_empty() {
+        output::line(format!("\n{}", "No seed files found or processed.".yellow()));
     } else if result.errors.is_empty() {
-        println!("\n{} {} {}", 
-            "✓".green().bold(), 
+        output::line(format!("\n{} {} {}", 
+            output::symbol("✓").green().bold(), 
             "Successfully executed".green().bold(), 
             format!("{} seed files", result.files_processed.len()).yellow()
-        );
+        ));
     } else {
-        println!("\n{} {} {}", 
-            "✗".red().bold(), 
+        output::line(format!("\n{} {} {}", 
+            output::symbol("✗").red().bold(), 
             "Seed operation failed with".red().bold(), 
             format!("{} errors", result.errors.len()).yellow()
-        );
+        ));
+    }
+}
+
+pub fn print_seed_reset_summary(result: &SeedResetResult) {
+    if result.tables.is_empty() && result.missing.is_empty() {
+        output::line("No seeded tables found.".yellow());
+        return;
+    }
+
+    if !result.tables.is_empty() {
+        let heading = if result.dry_run { "Tables To Empty" } else { "Tables Emptied" };
+        output::line(format!("\n{}:", heading.bold().green()));
+        for table in &result.tables {
+            output::line(format!("  {} {}", output::symbol("✓").green().bold(), table.cyan()));
+        }
+    }
+
+    if !result.missing.is_empty() {
+        output::line(format!("\n{}:", "Not Found".bold().yellow()));
+        for table in &result.missing {
+            output::line(format!("  {} {}", output::symbol("⚠").yellow(), table.dimmed()));
+        }
+    }
+
+    if result.dry_run {
+        output::line(format!("\n{}:", "Statements".bold()));
+        for statement in &result.statements {
+            output::line(format!("  {}", statement.dimmed()));
+        }
+        output::line(format!("\n{}", "Dry run - nothing was emptied.".yellow()));
+    } else if !result.tables.is_empty() {
+        output::line(format!("\n{} {} {}",
+            output::symbol("✓").green().bold(),
+            "Emptied".green().bold(),
+            format!("{} seeded tables", result.tables.len()).yellow()
+        ));
     }
 }
 
@@ -207,4 +854,133 @@ mod tests {
         assert_eq!(files.len(), 1);
         assert_eq!(files[0].file_name().unwrap().to_str().unwrap(), "seed.sql");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_scan_seed_files_numeric_prefix_order() {
+        let temp_dir = tempdir().unwrap();
+        
+        fs::write(temp_dir.path().join("10_orders.sql"), "-- Orders").unwrap();
+        fs::write(temp_dir.path().join("2_users.sql"), "-- Users").unwrap();
+        fs::write(temp_dir.path().join("extras.sql"), "-- Extras").unwrap();
+        fs::write(temp_dir.path().join("1_roles.sql"), "-- Roles").unwrap();
+        
+        let files = scan_seed_files(temp_dir.path()).unwrap();
+        let file_names: Vec<&str> = files.iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        
+        assert_eq!(file_names, vec!["1_roles.sql", "2_users.sql", "10_orders.sql", "extras.sql"]);
+    }
+
+    #[test]
+    fn test_scan_seed_files_manifest_order() {
+        let temp_dir = tempdir().unwrap();
+        
+        fs::write(temp_dir.path().join("users.sql"), "-- Users").unwrap();
+        fs::write(temp_dir.path().join("orders.sql"), "-- Orders").unwrap();
+        fs::write(temp_dir.path().join("scratch.sql"), "-- Not listed").unwrap();
+        fs::write(temp_dir.path().join(SEED_MANIFEST), "# Users first\nusers.sql\n\norders.sql\n").unwrap();
+        
+        let files = scan_seed_files(temp_dir.path()).unwrap();
+        let file_names: Vec<&str> = files.iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        
+        assert_eq!(file_names, vec!["users.sql", "orders.sql"]);
+
+        fs::write(temp_dir.path().join(SEED_MANIFEST), "users.sql\nmissing.sql\n").unwrap();
+        let err = scan_seed_files(temp_dir.path()).unwrap_err();
+        assert!(err.to_string().contains("missing.sql"));
+    }
+
+    #[test]
+    fn test_filter_seed_files() {
+        let files = vec![
+            PathBuf::from("seeds/001_users.sql"),
+            PathBuf::from("seeds/002_orders.sql"),
+            PathBuf::from("seeds/003_demo_orders.sql"),
+        ];
+
+        let options = SeedOptions {
+            skip: vec!["*demo*".to_string()],
+            ..Default::default()
+        };
+        let (run, skipped) = filter_seed_files(files.clone(), &options).unwrap();
+        assert_eq!(run.len(), 2);
+        assert_eq!(skipped, vec!["003_demo_orders.sql".to_string()]);
+
+        let options = SeedOptions {
+            only: vec!["002_orders".to_string()],
+            ..Default::default()
+        };
+        let (run, _) = filter_seed_files(files.clone(), &options).unwrap();
+        assert_eq!(run, vec![PathBuf::from("seeds/002_orders.sql")]);
+
+        let options = SeedOptions {
+            only: vec!["004_missing.sql".to_string()],
+            ..Default::default()
+        };
+        assert!(filter_seed_files(files, &options).is_err());
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*demo*", "003_demo_orders.sql"));
+        assert!(matches_pattern("00?_users.sql", "001_users.sql"));
+        assert!(matches_pattern("*.sql", "users.sql"));
+        assert!(matches_pattern("users.sql", "users.sql"));
+        assert!(!matches_pattern("demo*", "003_demo_orders.sql"));
+        assert!(!matches_pattern("*.sql", "users.sql.bak"));
+    }
+
+    #[test]
+    fn test_csv_target_table() {
+        assert_eq!(csv_target_table("orders.csv").unwrap(), "orders");
+        assert_eq!(csv_target_table("sales.orders.csv").unwrap(), "sales.orders");
+        assert_eq!(csv_target_table("010_sales.orders.csv").unwrap(), "sales.orders");
+        assert_eq!(csv_target_table("order_items.csv").unwrap(), "order_items");
+        assert_eq!(csv_target_table("Sales.order.csv").unwrap(), "\"Sales\".\"order\"");
+        assert!(csv_target_table("a.b.c.csv").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_header() {
+        assert_eq!(parse_csv_header("id,name , email\r").unwrap(), vec!["id", "name", "email"]);
+        assert_eq!(parse_csv_header("id,\"Display, Name\"").unwrap(), vec!["id", "Display, Name"]);
+        assert!(parse_csv_header("id,,name").is_err());
+        assert!(parse_csv_header("id,\"name").is_err());
+    }
+
+    #[test]
+    fn test_seeded_tables() {
+        let sql = indoc::indoc! {r#"
+            INSERT INTO users (id, name) VALUES (1, 'alice');
+            INSERT INTO "Sales".orders VALUES (1, 1);
+            COPY tags FROM STDIN;
+            COPY users TO STDOUT;
+            WITH new_team AS (INSERT INTO teams (name) VALUES ('core') RETURNING id)
+            INSERT INTO memberships SELECT id, 1 FROM new_team;
+            UPDATE settings SET value = 'on';
+            INSERT INTO users VALUES (2, 'bob');
+        "#};
+
+        assert_eq!(
+            seeded_tables(sql).unwrap(),
+            vec!["users", "\"Sales\".orders", "tags", "memberships", "teams"]
+        );
+    }
+
+    #[test]
+    fn test_reset_order() {
+        // orders -> users, order_items -> orders, order_items -> products
+        let references = [("orders", "users"), ("order_items", "orders"), ("order_items", "products"), ("users", "users")];
+        assert_eq!(
+            reset_order(&["users", "products", "orders", "order_items"], &references),
+            vec!["order_items", "products", "orders", "users"]
+        );
+
+        // A cycle keeps the given order
+        let references = [("a", "b"), ("b", "a")];
+        assert_eq!(reset_order(&["a", "b"], &references), vec!["a", "b"]);
+    }
+}
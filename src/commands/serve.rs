@@ -0,0 +1,589 @@
+use crate::commands::{execute_plan_with_options, PlanOptions, execute_graph, ApplyResult, ChangeOperation, GraphFilter, GraphFormat, PlanResult};
+use crate::cancel::CancellationToken;
+use crate::commands::execute_apply_with_cancellation;
+use crate::config::PgmgConfig;
+use crate::error::{PgmgError, Result};
+use crate::logging::output;
+use crate::sql::SqlObject;
+use crate::diff::unified_diff;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+
+/// Largest request head (request line and headers) the server reads
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// How long a client has to send its request before the connection is
+/// answered with 408 and closed
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Configuration for the serve command
+#[derive(Debug)]
+pub struct ServeConfig {
+    /// Address to listen on, e.g. `127.0.0.1:7878`
+    pub listen: String,
+    /// Bearer token every request except `/health` must present
+    pub token: String,
+    pub migrations_dir: Option<PathBuf>,
+    pub code_dir: Option<PathBuf>,
+    pub connection_string: String,
+    pub pgmg_config: PgmgConfig,
+    /// Stops the server. An apply in progress is cancelled, and rolled back
+    /// before serving returns.
+    pub cancellation: CancellationToken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobState {
+    Idle,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// The most recent apply started through the API
+#[derive(Debug)]
+struct ApplyJob {
+    id: u64,
+    state: JobState,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    result: Option<Value>,
+    error: Option<String>,
+}
+
+impl ApplyJob {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "state": match self.state {
+                JobState::Idle => "idle",
+                JobState::Running => "running",
+                JobState::Succeeded => "succeeded",
+                JobState::Failed => "failed",
+            },
+            "started_at": self.started_at.map(|t| t.to_rfc3339()),
+            "finished_at": self.finished_at.map(|t| t.to_rfc3339()),
+            "result": self.result,
+            "error": self.error,
+        })
+    }
+}
+
+struct Server {
+    config: ServeConfig,
+    apply_job: RefCell<ApplyJob>,
+    /// The task running the latest apply, waited for on shutdown
+    apply_task: RefCell<Option<tokio::task::JoinHandle<()>>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    authorization: Option<String>,
+}
+
+impl Request {
+    fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string_pretty(&body).unwrap_or_else(|_| "{}".to_string()),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, json!({ "error": message.into() }))
+    }
+}
+
+/// Run pgmg as a long-running HTTP server exposing plan, apply, status and
+/// graph endpoints, so deploys can be triggered and monitored without
+/// shelling out to the binary.
+///
+/// Applies run in the background one at a time; `POST /apply` returns
+/// immediately and `GET /status` reports how the latest one went. Serving
+/// stops when `cancellation` is cancelled, once the apply it cancels has
+/// rolled back and released the apply lock.
+pub async fn execute_serve(config: ServeConfig) -> Result<()> {
+    if config.token.is_empty() {
+        return Err(PgmgError::Configuration(
+            "An auth token is required. Use --token or the PGMG_SERVE_TOKEN env var".to_string()
+        ));
+    }
+
+    let listener = TcpListener::bind(&config.listen).await
+        .map_err(|e| PgmgError::Configuration(format!("Could not listen on {}: {}", config.listen, e)))?;
+
+    output::header("Serve Mode");
+    info!("Listening on http://{}", config.listen);
+
+    let server = Rc::new(Server {
+        config,
+        apply_job: RefCell::new(ApplyJob {
+            id: 0,
+            state: JobState::Idle,
+            started_at: None,
+            finished_at: None,
+            result: None,
+            error: None,
+        }),
+        apply_task: RefCell::new(None),
+    });
+
+    // Requests and applies run on this thread, so nothing needs to be Send
+    let local = tokio::task::LocalSet::new();
+    local.run_until(async move {
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to accept connection");
+                            continue;
+                        }
+                    };
+                    let server = server.clone();
+                    tokio::task::spawn_local(async move {
+                        if let Err(e) = handle_connection(stream, &server).await {
+                            debug!(peer = %peer, error = %e, "Connection failed");
+                        }
+                    });
+                }
+                _ = server.config.cancellation.cancelled() => {
+                    info!("Shutting down");
+                    break;
+                }
+            }
+        }
+
+        // Dropping the LocalSet would abandon the apply mid-way, so it is
+        // left to finish cancelling
+        let apply_task = server.apply_task.borrow_mut().take();
+        if let Some(apply_task) = apply_task {
+            if !apply_task.is_finished() {
+                info!("Waiting for the running apply to roll back");
+            }
+            let _ = apply_task.await;
+        }
+    }).await;
+
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, server: &Rc<Server>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let head = match read_request(&mut reader, READ_TIMEOUT).await? {
+        ReadRequest::Head(head) => head,
+        ReadRequest::Closed => return Ok(()),
+        ReadRequest::TooLarge => {
+            return write_response(reader.get_mut(), Response::error(431, "Request headers too large")).await;
+        }
+        ReadRequest::TimedOut => {
+            return write_response(reader.get_mut(), Response::error(408, "Timed out reading the request")).await;
+        }
+    };
+
+    let response = match parse_request(&head) {
+        Some(request) => {
+            debug!(method = %request.method, path = %request.path, "Request");
+            route(server, &request).await
+        }
+        None => Response::error(400, "Malformed request"),
+    };
+
+    write_response(reader.get_mut(), response).await
+}
+
+/// How reading a request off a connection ended
+#[derive(Debug, PartialEq)]
+enum ReadRequest {
+    /// The request line and headers
+    Head(String),
+    /// The client closed the connection before sending a request
+    Closed,
+    TooLarge,
+    /// The client didn't send the whole request within the timeout
+    TimedOut,
+}
+
+/// Read the request head, and the body no endpoint uses, within `timeout`,
+/// so a client that stops sending doesn't hold the connection open
+async fn read_request(reader: &mut BufReader<TcpStream>, timeout: Duration) -> std::io::Result<ReadRequest> {
+    let read = async {
+        let mut head = String::new();
+        let mut content_length = 0usize;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(ReadRequest::Closed);
+            }
+            if line.to_ascii_lowercase().starts_with("content-length:") {
+                content_length = line["content-length:".len()..].trim().parse().unwrap_or(0);
+            }
+            let end_of_head = line == "\r\n" || line == "\n";
+            head.push_str(&line);
+            if end_of_head {
+                break;
+            }
+            if head.len() > MAX_HEAD_BYTES {
+                return Ok(ReadRequest::TooLarge);
+            }
+        }
+
+        // No endpoint takes a body, but it has to be read off the connection
+        let mut body = vec![0; content_length.min(MAX_HEAD_BYTES)];
+        reader.read_exact(&mut body).await?;
+        Ok(ReadRequest::Head(head))
+    };
+
+    tokio::time::timeout(timeout, read).await.unwrap_or(Ok(ReadRequest::TimedOut))
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    };
+
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Parse the request line and the headers we care about
+fn parse_request(head: &str) -> Option<Request> {
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?;
+    request_line.next().filter(|version| version.starts_with("HTTP/"))?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let decode = |s: &str| percent_encoding::percent_decode_str(&s.replace('+', " ")).decode_utf8_lossy().to_string();
+    let query = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect();
+
+    let authorization = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim().to_string());
+
+    Some(Request {
+        method,
+        path: path.to_string(),
+        query,
+        authorization,
+    })
+}
+
+/// Compare the presented bearer token without bailing out at the first
+/// differing byte
+fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    let Some(presented) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    let presented = presented.trim().as_bytes();
+    let expected = token.as_bytes();
+
+    presented.len() == expected.len() &&
+        presented.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn route(server: &Rc<Server>, request: &Request) -> Response {
+    // Health checks come from load balancers, which don't hold the token
+    if request.path == "/health" {
+        return Response::json(200, json!({ "status": "ok" }));
+    }
+
+    if !is_authorized(request.authorization.as_deref(), &server.config.token) {
+        return Response::error(401, "Missing or invalid bearer token");
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => Response::json(200, json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "apply": server.apply_job.borrow().to_json(),
+        })),
+        ("POST", "/plan") => handle_plan(server).await,
+        ("POST", "/apply") => start_apply(server),
+        ("GET", "/graph") => handle_graph(server, request).await,
+        (_, "/status") | (_, "/plan") | (_, "/apply") | (_, "/graph") => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+async fn handle_plan(server: &Server) -> Response {
+    let config = &server.config;
+    match execute_plan_with_options(
+        config.migrations_dir.clone(),
+        config.code_dir.clone(),
+        config.connection_string.clone(),
+        None,
+        &PlanOptions::from_config(&config.pgmg_config),
+    ).await {
+        Ok(plan) => Response::json(200, plan_to_json(&plan)),
+        Err(e) => Response::error(500, format!("Plan failed: {}", e)),
+    }
+}
+
+fn start_apply(server: &Rc<Server>) -> Response {
+    let id = {
+        let mut job = server.apply_job.borrow_mut();
+        if job.state == JobState::Running {
+            return Response::json(409, json!({
+                "error": "An apply is already running",
+                "apply": job.to_json(),
+            }));
+        }
+
+        job.id += 1;
+        job.state = JobState::Running;
+        job.started_at = Some(Utc::now());
+        job.finished_at = None;
+        job.result = None;
+        job.error = None;
+        job.id
+    };
+
+    info!(job = id, "Starting apply");
+    let running = server.clone();
+    let apply_task = tokio::task::spawn_local(async move {
+        let server = running;
+        let config = &server.config;
+        let outcome = execute_apply_with_cancellation(
+            config.migrations_dir.clone(),
+            config.code_dir.clone(),
+            config.connection_string.clone(),
+            &config.pgmg_config,
+            &config.cancellation,
+        ).await.map_err(|e| e.to_string());
+
+        let mut job = server.apply_job.borrow_mut();
+        job.finished_at = Some(Utc::now());
+        match outcome {
+            Ok(result) => {
+                info!(job = id, "Apply succeeded");
+                job.state = JobState::Succeeded;
+                job.result = Some(apply_result_to_json(&result));
+            }
+            Err(e) => {
+                error!(job = id, error = %e, "Apply failed");
+                job.state = JobState::Failed;
+                job.error = Some(e);
+            }
+        }
+    });
+    *server.apply_task.borrow_mut() = Some(apply_task);
+
+    Response::json(202, json!({ "apply": server.apply_job.borrow().to_json() }))
+}
+
+async fn handle_graph(server: &Server, request: &Request) -> Response {
+    let Some(code_dir) = server.config.code_dir.clone() else {
+        return Response::error(400, "No code directory configured");
+    };
+
+    let format = match request.query_param("format").unwrap_or("json").parse::<GraphFormat>() {
+        Ok(format) => format,
+        Err(e) => return Response::error(400, e),
+    };
+    let depth = match request.query_param("depth").map(str::parse::<usize>) {
+        None => None,
+        Some(Ok(depth)) => Some(depth),
+        Some(Err(_)) => return Response::error(400, "depth must be a number"),
+    };
+    let filter = GraphFilter {
+        root: request.query_param("root").map(str::to_string),
+        depth,
+        object_types: Vec::new(),
+        tags: request.query_param("tag")
+            .map(|tags| tags.split(',').map(|tag| tag.trim().to_string()).collect())
+            .unwrap_or_default(),
+    };
+
+    match execute_graph(code_dir, format, filter, &server.config.pgmg_config.search_path(), None).await {
+        Ok(result) => Response {
+            status: 200,
+            content_type: match format {
+                GraphFormat::Json => "application/json",
+                GraphFormat::Dot | GraphFormat::Mermaid => "text/plain; charset=utf-8",
+            },
+            body: result.rendered,
+        },
+        Err(e) => Response::error(400, e.to_string()),
+    }
+}
+
+pub(crate) fn plan_to_json(plan: &PlanResult) -> Value {
+    let object_name = |object: &SqlObject| match &object.qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, object.qualified_name.name),
+        None => object.qualified_name.name.clone(),
+    };
+
+    let mut changes: Vec<Value> = plan.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::CreateObject { object, reason } => Some(json!({
+                "action": "create",
+                "type": object.object_type.to_string().to_lowercase(),
+                "name": object_name(object),
+                "reason": reason,
+            })),
+            ChangeOperation::UpdateObject { object, reason, old_ddl, .. } => Some(json!({
+                "action": "update",
+                "type": object.object_type.to_string().to_lowercase(),
+                "name": object_name(object),
+                "reason": reason,
+                "diff": old_ddl.as_ref().map(|old_ddl| unified_diff(old_ddl, &object.ddl_source())),
+            })),
+            ChangeOperation::DeleteObject { object_type, object_name, reason, .. } => Some(json!({
+                "action": "delete",
+                "type": object_type.to_string().to_lowercase(),
+                "name": object_name,
+                "reason": reason,
+            })),
+            // Listed under "migrations"
+            ChangeOperation::ApplyMigration { .. } => None,
+        })
+        .collect();
+    changes.extend(plan.enum_additions.iter().map(|addition| json!({
+        "action": "update",
+        "type": addition.object.object_type.to_string().to_lowercase(),
+        "name": object_name(&addition.object),
+        "reason": "New enum values",
+    })));
+    changes.extend(plan.publication_changes.iter().map(|change| json!({
+        "action": "update",
+        "type": change.object.object_type.to_string().to_lowercase(),
+        "name": object_name(&change.object),
+        "reason": "Published tables changed",
+    })));
+    changes.extend(plan.cron_job_changes.iter().filter(|change| !change.statements.is_empty()).map(|change| json!({
+        "action": "update",
+        "type": change.object.object_type.to_string().to_lowercase(),
+        "name": object_name(&change.object),
+        "reason": "Schedule or command changed",
+    })));
+
+    json!({
+        "migrations": plan.new_migrations,
+        "changes": changes,
+        "relation_sizes": plan.relation_sizes.iter().map(|size| json!({
+            "operation": size.operation,
+            "relation": size.relation,
+            "bytes": size.bytes,
+        })).collect::<Vec<_>>(),
+        "up_to_date": plan.new_migrations.is_empty() && changes.is_empty(),
+    })
+}
+
+fn apply_result_to_json(result: &ApplyResult) -> Value {
+    json!({
+        "migrations_applied": result.migrations_applied,
+        "objects_created": result.objects_created,
+        "objects_updated": result.objects_updated,
+        "objects_deleted": result.objects_deleted,
+        "errors": result.errors,
+        "plpgsql_errors": result.plpgsql_errors_found,
+        "plpgsql_warnings": result.plpgsql_warnings_found,
+        "skipped": result.skipped.iter().map(|skipped| json!({
+            "reason": skipped.reason,
+            "file": skipped.file.as_ref().map(|file| file.display().to_string()),
+            "line": skipped.line,
+            "statement": skipped.statement,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let head = "GET /graph?format=mermaid&root=api.create%5Forder HTTP/1.1\r\nHost: localhost\r\nauthorization: Bearer secret\r\n\r\n";
+        let request = parse_request(head).unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/graph");
+        assert_eq!(request.query_param("format"), Some("mermaid"));
+        assert_eq!(request.query_param("root"), Some("api.create_order"));
+        assert_eq!(request.authorization.as_deref(), Some("Bearer secret"));
+
+        assert!(parse_request("garbage\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(Some("Bearer secret"), "secret"));
+        assert!(!is_authorized(Some("Bearer secreT"), "secret"));
+        assert!(!is_authorized(Some("Bearer secret2"), "secret"));
+        assert!(!is_authorized(Some("secret"), "secret"));
+        assert!(!is_authorized(None, "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_read_request_times_out() -> std::io::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let read = |request: &'static [u8], timeout: Duration| {
+            let listener = &listener;
+            async move {
+                let mut client = TcpStream::connect(listener.local_addr()?).await?;
+                let (stream, _) = listener.accept().await?;
+                client.write_all(request).await?;
+                read_request(&mut BufReader::new(stream), timeout).await
+            }
+        };
+
+        let head = b"GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(
+            read(head, Duration::from_secs(5)).await?,
+            ReadRequest::Head(String::from_utf8_lossy(head).to_string())
+        );
+        // The headers never end
+        assert_eq!(read(b"GET /status HTTP/1.1\r\n", Duration::from_millis(50)).await?, ReadRequest::TimedOut);
+        Ok(())
+    }
+}
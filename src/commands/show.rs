@@ -0,0 +1,367 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use crate::db::{StateManager, DependencyRecord, ObjectRecord, connect_with_url, scan_sql_files};
+use crate::sql::{SqlObject, ObjectType, QualifiedIdent};
+use crate::commands::apply::oid_query;
+use crate::analysis::SearchPath;
+use crate::analysis::search_path::object_key;
+use crate::BuiltinCatalog;
+
+/// How an object's definition in the code directory compares to what was applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectStatus {
+    UpToDate,
+    /// The file definition differs from the applied one; apply will update it
+    Modified,
+    /// In the code directory but not applied yet
+    NotApplied,
+    /// Applied but no longer in the code directory; apply will drop it
+    Removed,
+    /// No code directory was given, so there is nothing to compare with
+    Unknown,
+}
+
+/// An edge from pgmg_dependencies
+#[derive(Debug, Clone)]
+pub struct ShownDependency {
+    /// Type of the dependent object (only known for dependents)
+    pub object_type: Option<ObjectType>,
+    pub name: String,
+    pub is_hard: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShownObject {
+    pub object_type: ObjectType,
+    pub object_name: String,
+    pub status: ObjectStatus,
+    pub source_file: Option<PathBuf>,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+    /// Hash of the definition in the code directory
+    pub file_hash: Option<String>,
+    /// Hash recorded in pgmg_state when the object was last applied
+    pub applied_hash: Option<String>,
+    pub last_applied: Option<SystemTime>,
+    /// The DDL the object was last applied with
+    pub applied_ddl: Option<String>,
+    /// What this object depends on
+    pub dependencies: Vec<ShownDependency>,
+    /// What depends on this object
+    pub dependents: Vec<ShownDependency>,
+    /// Dependencies parsed from the file now, to compare with the stored ones
+    pub parsed_dependencies: Option<BTreeSet<String>>,
+    /// Whether the object is in the database catalogs, if it can be looked up
+    pub exists_in_database: Option<bool>,
+}
+
+impl ShownObject {
+    /// Parsed dependencies missing from pgmg_dependencies, and stored ones
+    /// the file no longer has. `None` without a file to parse.
+    pub fn dependency_drift(&self) -> Option<(Vec<&str>, Vec<&str>)> {
+        let parsed = self.parsed_dependencies.as_ref()?;
+        let stored: BTreeSet<&str> = self.dependencies.iter().map(|dep| dep.name.as_str()).collect();
+
+        let not_stored = parsed.iter().map(String::as_str).filter(|name| !stored.contains(name)).collect();
+        let not_parsed = stored.into_iter().filter(|name| !parsed.contains(*name)).collect();
+        Some((not_stored, not_parsed))
+    }
+}
+
+#[derive(Debug)]
+pub struct ShowResult {
+    pub target: String,
+    pub objects: Vec<ShownObject>,
+}
+
+/// Look up everything pgmg knows about an object: where it is defined, the
+/// state recorded when it was last applied, and its stored dependencies.
+///
+/// A name can match more than one object (e.g. a view and a function of the
+/// same name), in which case all of them are returned. Unqualified names are
+/// looked up through `search_path`, as plan resolves references.
+pub async fn execute_show(
+    target: String,
+    code_dir: Option<PathBuf>,
+    connection_string: String,
+    search_path: &SearchPath,
+) -> Result<ShowResult, Box<dyn std::error::Error>> {
+    let file_objects = match &code_dir {
+        Some(dir) if dir.exists() => {
+            let builtin_catalog = BuiltinCatalog::new();
+            scan_sql_files(dir, &builtin_catalog).await?
+        }
+        _ => Vec::new(),
+    };
+
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let state_manager = StateManager::new(&client);
+    state_manager.initialize().await?;
+    let tracked = state_manager.get_tracked_objects().await?;
+    let stored_dependencies = state_manager.get_all_dependencies().await?;
+
+    let known: Vec<QualifiedIdent> = file_objects.iter().map(|obj| obj.qualified_name.clone())
+        .chain(tracked.iter().map(|record| record.object_name.clone()))
+        .collect();
+    let target_key = search_path.qualify(&target, &known);
+
+    let mut objects: Vec<ShownObject> = Vec::new();
+
+    for file_obj in file_objects.iter().filter(|obj| object_key(&obj.qualified_name) == target_key) {
+        let record = tracked.iter().find(|record|
+            record.object_type == file_obj.object_type && record.object_name == file_obj.qualified_name
+        );
+        objects.push(shown_object(file_obj.object_type.clone(), &file_obj.qualified_name, Some(file_obj), record, code_dir.is_some()));
+    }
+
+    for record in tracked.iter().filter(|record| object_key(&record.object_name) == target_key) {
+        let already_shown = objects.iter().any(|obj|
+            obj.object_type == record.object_type && obj.object_name == format_qualified_name(&record.object_name)
+        );
+        if !already_shown {
+            objects.push(shown_object(record.object_type.clone(), &record.object_name, None, Some(record), code_dir.is_some()));
+        }
+    }
+
+    if objects.is_empty() {
+        return Err(format!("No managed object named {}", target).into());
+    }
+
+    for obj in &mut objects {
+        let key = object_key(&QualifiedIdent::from_qualified_name(&obj.object_name));
+        (obj.dependencies, obj.dependents) = split_dependencies(&obj.object_type, &key, &stored_dependencies, search_path, &known);
+
+        if let Some(query) = oid_query(&obj.object_type) {
+            let rows = client.query(query, &[&vec![key]]).await?;
+            obj.exists_in_database = Some(!rows.is_empty());
+        }
+    }
+
+    Ok(ShowResult { target, objects })
+}
+
+fn shown_object(
+    object_type: ObjectType,
+    name: &QualifiedIdent,
+    file_obj: Option<&SqlObject>,
+    record: Option<&ObjectRecord>,
+    have_code_dir: bool,
+) -> ShownObject {
+    let status = match (file_obj, record) {
+        (Some(file_obj), Some(record)) if file_obj.ddl_hash == record.ddl_hash => ObjectStatus::UpToDate,
+        (Some(_), Some(_)) => ObjectStatus::Modified,
+        (Some(_), None) => ObjectStatus::NotApplied,
+        (None, _) if have_code_dir => ObjectStatus::Removed,
+        (None, _) => ObjectStatus::Unknown,
+    };
+
+    ShownObject {
+        object_type,
+        object_name: format_qualified_name(name),
+        status,
+        source_file: file_obj.and_then(|obj| obj.source_file.clone()),
+        start_line: file_obj.and_then(|obj| obj.start_line),
+        end_line: file_obj.and_then(|obj| obj.end_line),
+        file_hash: file_obj.map(|obj| obj.ddl_hash.clone()),
+        applied_hash: record.map(|record| record.ddl_hash.clone()),
+        last_applied: record.map(|record| record.last_applied),
+        applied_ddl: record.and_then(|record| record.ddl_source.clone()),
+        dependencies: Vec::new(),
+        dependents: Vec::new(),
+        parsed_dependencies: file_obj.map(|obj| {
+            let deps = &obj.dependencies;
+            deps.relations.iter().chain(&deps.functions).chain(&deps.types).map(format_qualified_name).collect()
+        }),
+        exists_in_database: None,
+    }
+}
+
+/// Split stored edges into the object's own dependencies and its dependents.
+/// Dependencies are stored as they're written, so they're resolved through
+/// `search_path` among the `known` objects.
+fn split_dependencies(
+    object_type: &ObjectType,
+    key: &str,
+    stored: &[DependencyRecord],
+    search_path: &SearchPath,
+    known: &[QualifiedIdent],
+) -> (Vec<ShownDependency>, Vec<ShownDependency>) {
+    let dependencies = stored.iter()
+        .filter(|record| &record.dependent_type == object_type
+            && object_key(&QualifiedIdent::from_qualified_name(&record.dependent_name)) == key)
+        .map(|record| ShownDependency {
+            object_type: None,
+            name: record.dependency_name.clone(),
+            is_hard: record.is_hard,
+        })
+        .collect();
+
+    let dependents = stored.iter()
+        .filter(|record| search_path.qualify(&record.dependency_name, known) == key)
+        .map(|record| ShownDependency {
+            object_type: Some(record.dependent_type.clone()),
+            name: record.dependent_name.clone(),
+            is_hard: record.is_hard,
+        })
+        .collect();
+
+    (dependencies, dependents)
+}
+
+pub(crate) fn format_qualified_name(qualified_name: &QualifiedIdent) -> String {
+    match &qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, qualified_name.name),
+        None => qualified_name.name.clone(),
+    }
+}
+
+#[cfg(feature = "cli")]
+pub fn print_show_summary(result: &ShowResult, show_ddl: bool) {
+    use crate::logging::output;
+    use chrono::{DateTime, Local};
+    use console::style;
+
+    let kind = |is_hard: bool| if is_hard { "hard" } else { "soft" };
+
+    for obj in &result.objects {
+        output::line(format!("\n{} {}",
+            style(obj.object_type.to_string().to_lowercase()).dim(),
+            style(&obj.object_name).cyan().bold()
+        ));
+
+        let status = match obj.status {
+            ObjectStatus::UpToDate => style("up to date".to_string()).green(),
+            ObjectStatus::Modified => style("modified, apply will update it".to_string()).yellow(),
+            ObjectStatus::NotApplied => style("not applied yet".to_string()).yellow(),
+            ObjectStatus::Removed => style("no longer in the code directory, apply will drop it".to_string()).red(),
+            ObjectStatus::Unknown => style("applied (pass --code-dir to compare with files)".to_string()).dim(),
+        };
+        output::line(format!("  {:<14}{}", "Status:", status));
+
+        if let Some(file) = &obj.source_file {
+            let lines = match (obj.start_line, obj.end_line) {
+                (Some(start), Some(end)) if start != end => format!(":{}-{}", start, end),
+                (Some(start), _) => format!(":{}", start),
+                _ => String::new(),
+            };
+            output::line(format!("  {:<14}{}{}", "Source:", file.display(), lines));
+        }
+
+        if let Some(hash) = &obj.applied_hash {
+            output::line(format!("  {:<14}{}", "Applied hash:", hash));
+        }
+        if let Some(hash) = &obj.file_hash {
+            if obj.applied_hash.as_ref() != Some(hash) {
+                output::line(format!("  {:<14}{}", "File hash:", hash));
+            }
+        }
+        if let Some(last_applied) = obj.last_applied {
+            let last_applied: DateTime<Local> = last_applied.into();
+            output::line(format!("  {:<14}{}", "Last applied:", last_applied.format("%Y-%m-%d %H:%M:%S %Z")));
+        }
+        match obj.exists_in_database {
+            Some(true) => output::line(format!("  {:<14}{}", "In database:", style("yes").green())),
+            Some(false) if obj.applied_hash.is_some() => output::line(format!("  {:<14}{}",
+                "In database:", style("no, but pgmg_state has a row for it").red())),
+            Some(false) => output::line(format!("  {:<14}{}", "In database:", style("no").dim())),
+            None => {}
+        }
+
+        if obj.dependencies.is_empty() {
+            output::line(format!("  {:<14}{}", "Depends on:", style("nothing").dim()));
+        } else {
+            output::line(format!("  {}", "Depends on:"));
+            for dep in &obj.dependencies {
+                output::line(format!("    {} {}", style(&dep.name).cyan(), style(format!("({})", kind(dep.is_hard))).dim()));
+            }
+        }
+
+        if let Some((not_stored, not_parsed)) = obj.dependency_drift() {
+            for name in not_stored {
+                output::line(format!("    {} {}", style(name).yellow(), style("(parsed from the file, not stored)").dim()));
+            }
+            for name in not_parsed {
+                output::line(format!("    {} {}", style(name).yellow(), style("(stored, no longer in the file)").dim()));
+            }
+        }
+
+        if obj.dependents.is_empty() {
+            output::line(format!("  {:<14}{}", "Dependents:", style("none").dim()));
+        } else {
+            output::line(format!("  {}", "Dependents:"));
+            for dep in &obj.dependents {
+                let object_type = dep.object_type.as_ref()
+                    .map(|t| t.to_string().to_lowercase())
+                    .unwrap_or_default();
+                output::line(format!("    {} {} {}",
+                    style(object_type).dim(),
+                    style(&dep.name).cyan(),
+                    style(format!("({})", kind(dep.is_hard))).dim()
+                ));
+            }
+        }
+
+        if show_ddl {
+            match &obj.applied_ddl {
+                Some(ddl) => {
+                    output::line(format!("  {}", "Applied DDL:"));
+                    for line in ddl.lines() {
+                        output::line(format!("    {}", line));
+                    }
+                }
+                None => output::line(format!("  {:<14}{}", "Applied DDL:", style("not stored (applied by an older pgmg)").dim())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_dependencies() {
+        let stored = vec![
+            DependencyRecord {
+                dependent_type: ObjectType::View,
+                dependent_name: "api.order_summary".to_string(),
+                dependency_name: "orders".to_string(),
+                is_hard: true,
+            },
+            DependencyRecord {
+                dependent_type: ObjectType::Function,
+                dependent_name: "api.checkout".to_string(),
+                dependency_name: "api.order_summary".to_string(),
+                is_hard: true,
+            },
+            DependencyRecord {
+                dependent_type: ObjectType::Function,
+                dependent_name: "api.order_summary".to_string(),
+                dependency_name: "api.unrelated".to_string(),
+                is_hard: false,
+            },
+        ];
+
+        let (dependencies, dependents) = split_dependencies(&ObjectType::View, "api.order_summary", &stored);
+
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].name, "orders");
+        assert_eq!(dependents.len(), 1);
+        assert_eq!(dependents[0].name, "api.checkout");
+        assert_eq!(dependents[0].object_type, Some(ObjectType::Function));
+    }
+
+    #[test]
+    fn test_dependency_drift() {
+        let stored = |name: &str| ShownDependency { object_type: None, name: name.to_string(), is_hard: true };
+        let mut obj = shown_object(ObjectType::View, &QualifiedIdent::from_qualified_name("api.order_summary"), None, None, true);
+        obj.dependencies = vec![stored("orders"), stored("api.customers")];
+        assert_eq!(obj.dependency_drift(), None);
+
+        obj.parsed_dependencies = Some(["orders", "api.line_items"].iter().map(|name| name.to_string()).collect());
+        assert_eq!(obj.dependency_drift(), Some((vec!["api.line_items"], vec!["api.customers"])));
+    }
+}
@@ -0,0 +1,369 @@
+//! `pgmg simulate-concurrency`: run two applies against a scratch database
+//! in a chosen interleaving, then check that the advisory lock kept them
+//! apart and that nothing is left pending afterwards.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::{mpsc::UnboundedReceiver, oneshot};
+use tracing::warn;
+use crate::commands::apply::{execute_apply_with_hooks, ApplyHooks, ApplyPause, ApplyPhase, ApplyResult};
+use crate::commands::plan::{execute_plan_with_options, PlanOptions};
+use crate::config::PgmgConfig;
+use crate::db::TestDatabase;
+
+/// How long the second apply is given to start polling for the lock before
+/// the first one lets go of it
+const LOCK_WAIT: Duration = Duration::from_secs(2);
+
+/// The order the two applies are run in. In each, the first apply is held
+/// once its changes have run, with its transaction still open, while the
+/// second starts and waits for the lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interleaving {
+    /// The first apply finishes, then the second finds nothing left to do
+    Serialized,
+    /// The first apply holds the lock until the second gives up on it
+    LockTimeout,
+    /// The first apply fails before committing and the second applies everything
+    AbortFirst,
+}
+
+impl FromStr for Interleaving {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "serialized" => Ok(Interleaving::Serialized),
+            "lock-timeout" => Ok(Interleaving::LockTimeout),
+            "abort-first" => Ok(Interleaving::AbortFirst),
+            _ => Err(format!(
+                "Unknown interleaving '{}'. Expected one of: serialized, lock-timeout, abort-first", s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Interleaving {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Interleaving::Serialized => "serialized",
+            Interleaving::LockTimeout => "lock-timeout",
+            Interleaving::AbortFirst => "abort-first",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Process {
+    First,
+    Second,
+}
+
+impl fmt::Display for Process {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Process::First => "first",
+            Process::Second => "second",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SimulationResult {
+    pub interleaving: Interleaving,
+    /// Phases in the order the applies reached them
+    pub events: Vec<(Process, ApplyPhase)>,
+    pub first: Result<ApplyResult, String>,
+    pub second: Result<ApplyResult, String>,
+    /// Overlapping locks, unexpected outcomes and changes left pending
+    pub violations: Vec<String>,
+}
+
+impl SimulationResult {
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Run two applies of the same files against a new database created next to
+/// the one in `connection_string`, interleaved as given. The database is
+/// dropped afterwards.
+pub async fn execute_simulate_concurrency(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    connection_string: String,
+    config: &PgmgConfig,
+    interleaving: Interleaving,
+) -> Result<SimulationResult, Box<dyn std::error::Error>> {
+    let test_db = TestDatabase::new(&connection_string).await?;
+
+    let result = simulate(migrations_dir, code_dir, &test_db.connection_string, config, interleaving).await;
+
+    if let Err(e) = test_db.cleanup().await {
+        warn!("Failed to drop simulation database {}: {}", test_db.name, e);
+    }
+
+    result
+}
+
+async fn simulate(
+    migrations_dir: Option<PathBuf>,
+    code_dir: Option<PathBuf>,
+    connection_string: &str,
+    config: &PgmgConfig,
+    interleaving: Interleaving,
+) -> Result<SimulationResult, Box<dyn std::error::Error>> {
+    // Hooks act on the real deployment, e.g. paging Slack, so they don't run
+    // against the simulation database
+    let config = &PgmgConfig { hooks: None, ..config.clone() };
+    let second_lock_timeout = match interleaving {
+        Interleaving::LockTimeout => Duration::from_secs(1),
+        Interleaving::Serialized | Interleaving::AbortFirst => Duration::from_secs(30),
+    };
+
+    let (first_hooks, first_pauses) = ApplyHooks::channel();
+    let (second_hooks, second_pauses) = ApplyHooks::channel();
+    let (start_second, second_started) = oneshot::channel::<()>();
+
+    // The hooks move into each apply, so its channel closes when it finishes
+    let first = {
+        let (migrations_dir, code_dir) = (migrations_dir.clone(), code_dir.clone());
+        async move {
+            execute_apply_with_hooks(migrations_dir, code_dir, connection_string.to_string(), config,
+                                     Duration::from_secs(30), &first_hooks).await
+                .map_err(|e| e.to_string())
+        }
+    };
+    let second = {
+        let (migrations_dir, code_dir) = (migrations_dir.clone(), code_dir.clone());
+        async move {
+            if second_started.await.is_err() {
+                return Err("The second apply was never started".to_string());
+            }
+            execute_apply_with_hooks(migrations_dir, code_dir, connection_string.to_string(), config,
+                                     second_lock_timeout, &second_hooks).await
+                .map_err(|e| e.to_string())
+        }
+    };
+
+    let mut events = Vec::new();
+    let control = control(interleaving, first_pauses, second_pauses, start_second, &mut events);
+    let (first, second, control) = tokio::join!(first, second, control);
+    control?;
+
+    let mut violations = lock_overlaps(&events);
+    violations.extend(unexpected_outcomes(interleaving, &first, &second));
+
+    let plan = execute_plan_with_options(
+        migrations_dir,
+        code_dir,
+        connection_string.to_string(),
+        None,
+        &PlanOptions::from_config(config),
+    ).await?;
+    let pending = plan.changes.len() + plan.new_migrations.len();
+    if pending > 0 {
+        violations.push(format!("{} change(s) still pending after both applies", pending));
+    }
+
+    Ok(SimulationResult { interleaving, events, first, second, violations })
+}
+
+/// Drive both applies through `interleaving`, recording each phase reached
+async fn control(
+    interleaving: Interleaving,
+    mut first: UnboundedReceiver<ApplyPause>,
+    mut second: UnboundedReceiver<ApplyPause>,
+    start_second: oneshot::Sender<()>,
+    events: &mut Vec<(Process, ApplyPhase)>,
+) -> Result<(), String> {
+    let held = loop {
+        let pause = first.recv().await
+            .ok_or("The first apply finished without running any changes. The simulation needs pending migrations or code.")?;
+        events.push((Process::First, pause.phase));
+        if pause.phase == ApplyPhase::ChangesApplied {
+            break pause;
+        }
+        pause.resume();
+    };
+
+    let _ = start_second.send(());
+    let pause = second.recv().await.ok_or("The second apply finished before waiting for the lock")?;
+    events.push((Process::Second, pause.phase));
+    pause.resume();
+
+    match interleaving {
+        Interleaving::Serialized => {
+            tokio::time::sleep(LOCK_WAIT).await;
+            held.resume();
+        }
+        Interleaving::LockTimeout => {
+            // The second apply can only finish by giving up on the lock
+            while let Some(pause) = second.recv().await {
+                events.push((Process::Second, pause.phase));
+                pause.resume();
+            }
+            held.resume();
+        }
+        Interleaving::AbortFirst => {
+            tokio::time::sleep(LOCK_WAIT).await;
+            held.abort();
+        }
+    }
+
+    loop {
+        tokio::select! {
+            Some(pause) = first.recv() => {
+                events.push((Process::First, pause.phase));
+                pause.resume();
+            }
+            Some(pause) = second.recv() => {
+                events.push((Process::Second, pause.phase));
+                pause.resume();
+            }
+            else => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// A lock is held from `LockAcquired` until `ReleasingLock` is resumed, so
+/// the other apply reaching `LockAcquired` in between means both held it
+fn lock_overlaps(events: &[(Process, ApplyPhase)]) -> Vec<String> {
+    let mut violations = Vec::new();
+    let mut holder = None;
+
+    for (process, phase) in events {
+        match phase {
+            ApplyPhase::LockAcquired => {
+                if let Some(other) = holder.filter(|other| other != process) {
+                    violations.push(format!("The {} apply acquired the lock while the {} apply held it", process, other));
+                }
+                holder = Some(*process);
+            }
+            ApplyPhase::ReleasingLock if holder == Some(*process) => holder = None,
+            _ => {}
+        }
+    }
+
+    violations
+}
+
+fn unexpected_outcomes(
+    interleaving: Interleaving,
+    first: &Result<ApplyResult, String>,
+    second: &Result<ApplyResult, String>,
+) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    match (interleaving, first) {
+        (Interleaving::AbortFirst, Ok(_)) => violations.push("The first apply succeeded despite being aborted".to_string()),
+        (Interleaving::Serialized | Interleaving::LockTimeout, Err(e)) => {
+            violations.push(format!("The first apply failed: {}", e));
+        }
+        _ => {}
+    }
+
+    match (interleaving, second) {
+        (Interleaving::Serialized, Ok(result)) => {
+            let applied = result.migrations_applied.len() + result.objects_created.len()
+                + result.objects_updated.len() + result.objects_deleted.len();
+            if applied > 0 {
+                violations.push(format!("The second apply ran {} change(s) the first had already applied", applied));
+            }
+        }
+        (Interleaving::LockTimeout, Ok(_)) => {
+            violations.push("The second apply ran while the first held the lock".to_string());
+        }
+        (Interleaving::LockTimeout, Err(e)) if !e.contains("Could not acquire lock") => {
+            violations.push(format!("The second apply failed for a reason other than the lock: {}", e));
+        }
+        (Interleaving::Serialized | Interleaving::AbortFirst, Err(e)) => {
+            violations.push(format!("The second apply failed: {}", e));
+        }
+        _ => {}
+    }
+
+    violations
+}
+
+pub fn print_simulation_summary(result: &SimulationResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    for (process, phase) in &result.events {
+        output::line(format!("  {:<6} {:?}", process.to_string().bright_blue(), phase));
+    }
+    output::line("");
+
+    for (process, outcome) in [(Process::First, &result.first), (Process::Second, &result.second)] {
+        match outcome {
+            Ok(apply) => output::line(format!(
+                "{} The {} apply succeeded: {} migration(s), {} object change(s)",
+                output::symbol("✓").green(),
+                process,
+                apply.migrations_applied.len(),
+                apply.objects_created.len() + apply.objects_updated.len() + apply.objects_deleted.len()
+            )),
+            Err(e) => output::line(format!(
+                "{} The {} apply failed: {}",
+                output::symbol("→").dimmed(),
+                process,
+                e.lines().next().unwrap_or("")
+            )),
+        }
+    }
+    output::line("");
+
+    for violation in &result.violations {
+        output::line(format!("{} {}", output::symbol("✗").red(), violation.red()));
+    }
+    if result.passed() {
+        output::line(format!(
+            "{} The {} interleaving kept the lock and state consistent",
+            output::symbol("✓").green().bold(),
+            result.interleaving
+        ));
+    } else {
+        output::line(format!(
+            "{} The {} interleaving found {} problem(s)",
+            output::symbol("✗").red().bold(),
+            result.interleaving,
+            result.violations.len()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interleaving() {
+        assert_eq!("Lock-Timeout".parse::<Interleaving>(), Ok(Interleaving::LockTimeout));
+        assert_eq!(Interleaving::AbortFirst.to_string().parse::<Interleaving>(), Ok(Interleaving::AbortFirst));
+        assert!("parallel".parse::<Interleaving>().is_err());
+    }
+
+    #[test]
+    fn test_lock_overlaps() {
+        use ApplyPhase::*;
+        use Process::*;
+
+        let serialized = [
+            (First, WaitingForLock), (First, LockAcquired), (Second, WaitingForLock),
+            (First, ReleasingLock), (Second, LockAcquired), (Second, ReleasingLock),
+        ];
+        assert!(lock_overlaps(&serialized).is_empty());
+
+        let overlapping = [
+            (First, LockAcquired), (Second, LockAcquired), (First, ReleasingLock), (Second, ReleasingLock),
+        ];
+        assert_eq!(lock_overlaps(&overlapping), vec![
+            "The second apply acquired the lock while the first apply held it",
+        ]);
+    }
+}
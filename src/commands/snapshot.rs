@@ -0,0 +1,276 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use crate::config::PgmgConfig;
+use crate::db::{connect_with_url, StateSnapshot, TestDatabase};
+
+/// Lines pg_dump writes that change between runs, servers or pg_dump
+/// versions without the schema changing
+const VOLATILE_LINE_PREFIXES: &[&str] = &[
+    "-- Dumped from database version",
+    "-- Dumped by pg_dump version",
+    "-- Started on",
+    "-- Completed on",
+    "-- TOC entry",
+    "-- Dependencies:",
+    // pg_dump 17.6+ guards the script with a random key
+    "\\restrict",
+    "\\unrestrict",
+];
+
+/// Session settings at the top of the script, which vary with the pg_dump
+/// version. Only stripped before the first object, so function bodies are
+/// never touched.
+const PREAMBLE_LINE_PREFIXES: &[&str] = &[
+    "SET ",
+    "SELECT pg_catalog.set_config(",
+];
+
+/// Where a schema snapshot is taken from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SnapshotSource {
+    /// The database as it is
+    #[default]
+    Database,
+    /// A temporary database built from the migration and code files, dropped
+    /// afterwards
+    Fresh,
+}
+
+#[derive(Debug)]
+pub struct SnapshotResult {
+    /// The normalized dump
+    pub schema: String,
+    /// Where it was written, if not to stdout
+    pub output: Option<PathBuf>,
+    /// Whether the file at `output` already had exactly this content
+    pub unchanged: bool,
+}
+
+/// Take a schema-only dump with pg_dump and normalize it so it only changes
+/// when the schema does: owners, tablespaces, pgmg's own bookkeeping schema,
+/// version banners and session settings are left out, and whitespace is
+/// tidied. pg_dump already orders objects by type, schema and name within
+/// dependency order, so two databases with the same schema dump the same.
+/// Comments and privileges are kept.
+pub async fn execute_snapshot_schema(
+    connection_string: String,
+    output: Option<PathBuf>,
+    source: &SnapshotSource,
+    config: &PgmgConfig,
+) -> Result<SnapshotResult, Box<dyn std::error::Error>> {
+    let schema = match source {
+        SnapshotSource::Database => dump_schema(&connection_string)?,
+        SnapshotSource::Fresh => {
+            let test_db = TestDatabase::new_fresh(
+                &connection_string,
+                config.migrations_dir.clone(),
+                config.code_dir.clone(),
+                None,
+                config,
+            ).await?;
+            let dump = dump_schema(&test_db.connection_string);
+            test_db.cleanup().await?;
+            dump?
+        }
+    };
+    let schema = normalize_schema_dump(&schema);
+
+    let unchanged = output.as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .is_some_and(|existing| existing == schema);
+    if let Some(path) = &output {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &schema)?;
+    }
+
+    Ok(SnapshotResult { schema, output, unchanged })
+}
+
+#[derive(Debug)]
+pub struct SnapshotExportResult {
+    pub snapshot: StateSnapshot,
+    pub output: PathBuf,
+}
+
+/// Export the built-in catalog and pgmg's state to `output`, for
+/// `pgmg plan --offline` to plan against without a connection
+pub async fn execute_snapshot_export(
+    connection_string: String,
+    output: PathBuf,
+    config: &PgmgConfig,
+) -> Result<SnapshotExportResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let snapshot = StateSnapshot::from_database(&client, config.tenant_template().as_ref()).await?;
+    snapshot.write(&output)?;
+
+    Ok(SnapshotExportResult { snapshot, output })
+}
+
+fn dump_schema(connection_string: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("pg_dump")
+        .args([
+            "--schema-only",
+            "--no-owner",
+            "--no-tablespaces",
+            "--exclude-schema=pgmg",
+            "--dbname",
+            connection_string,
+        ])
+        .output()
+        .map_err(|_| "pg_dump not found. Please ensure PostgreSQL client tools are installed.")?;
+
+    if !output.status.success() {
+        return Err(format!("pg_dump failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Strip the volatile parts of a pg_dump script, trailing whitespace and runs
+/// of blank lines
+pub fn normalize_schema_dump(dump: &str) -> String {
+    let mut normalized = String::with_capacity(dump.len());
+    let mut blank_lines = 0;
+    let mut in_preamble = true;
+
+    for line in dump.lines() {
+        let line = line.trim_end();
+        in_preamble &= !line.starts_with("-- Name:");
+        if VOLATILE_LINE_PREFIXES.iter().any(|prefix| line.starts_with(prefix)) ||
+           (in_preamble && PREAMBLE_LINE_PREFIXES.iter().any(|prefix| line.starts_with(prefix))) {
+            continue;
+        }
+
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if blank_lines > 0 && !normalized.is_empty() {
+            normalized.push('\n');
+        }
+        blank_lines = 0;
+
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+
+    normalized
+}
+
+#[cfg(feature = "cli")]
+pub fn print_snapshot_summary(result: &SnapshotResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    match &result.output {
+        Some(path) if result.unchanged => output::line(format!(
+            "{} {} is up to date",
+            output::symbol("✓").green(),
+            path.display().to_string().bright_blue()
+        )),
+        Some(path) => output::line(format!(
+            "{} Wrote schema snapshot to {} ({} lines)",
+            output::symbol("✓").green(),
+            path.display().to_string().bright_blue(),
+            result.schema.lines().count()
+        )),
+        // Written as is, so it can be redirected to a file
+        None => print!("{}", result.schema),
+    }
+}
+
+#[cfg(feature = "cli")]
+pub fn print_snapshot_export_summary(result: &SnapshotExportResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    output::line(format!(
+        "{} Exported {} applied migration(s) and {} managed object(s) to {}",
+        output::symbol("✓").green(),
+        result.snapshot.applied_migrations.len(),
+        result.snapshot.objects.len(),
+        result.output.display().to_string().bright_blue()
+    ));
+    output::info(format!("Plan against it with: pgmg plan --offline --snapshot {}", result.output.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_schema_dump() {
+        let dump = indoc::indoc! {r#"
+            --
+            -- PostgreSQL database dump
+            --
+
+            \restrict Xq3kTQ8hVdJbYd2Ww1Z
+
+            -- Dumped from database version 16.4 (Debian 16.4-1.pgdg120+1)
+            -- Dumped by pg_dump version 17.6
+
+            SET statement_timeout = 0;
+            SET transaction_timeout = 0;
+            SELECT pg_catalog.set_config('search_path', '', false);
+            SET default_table_access_method = heap;
+
+
+
+            --
+            -- Name: users; Type: TABLE; Schema: public; Owner: -
+            --
+
+            CREATE TABLE public.users (
+                id integer NOT NULL,
+                name text
+            );
+
+
+            --
+            -- Name: TABLE users; Type: COMMENT; Schema: public; Owner: -
+            --
+
+            COMMENT ON TABLE public.users IS 'Registered users';
+
+            SET default_table_access_method = heap;
+
+            \unrestrict Xq3kTQ8hVdJbYd2Ww1Z
+        "#};
+
+        assert_eq!(normalize_schema_dump(dump), indoc::indoc! {r#"
+            --
+            -- PostgreSQL database dump
+            --
+
+            --
+            -- Name: users; Type: TABLE; Schema: public; Owner: -
+            --
+
+            CREATE TABLE public.users (
+                id integer NOT NULL,
+                name text
+            );
+
+            --
+            -- Name: TABLE users; Type: COMMENT; Schema: public; Owner: -
+            --
+
+            COMMENT ON TABLE public.users IS 'Registered users';
+
+            SET default_table_access_method = heap;
+        "#});
+    }
+
+    #[test]
+    fn test_normalize_schema_dump_is_stable() {
+        let dump = "-- Dumped by pg_dump version 16.1\nCREATE TABLE t (id integer);\n\n\n";
+        let normalized = normalize_schema_dump(dump);
+        assert_eq!(normalized, "CREATE TABLE t (id integer);\n");
+        assert_eq!(normalize_schema_dump(&normalized), normalized);
+    }
+}
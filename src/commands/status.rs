@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use crate::commands::apply::oid_query;
+use crate::analysis::search_path::object_key;
+use crate::config::PgmgConfig;
+use crate::db::{
+    connect_with_url, scan_migrations, AdvisoryLockHolder, MigrationRecord, RunRecord, StateManager,
+    STATE_SCHEMA_VERSION,
+};
+use crate::sql::ObjectType;
+
+#[derive(Debug)]
+pub struct StatusResult {
+    /// Whether pgmg's state tables exist, i.e. pgmg has been applied
+    pub initialized: bool,
+    /// Version of the state tables, 0 if they predate pgmg_meta
+    pub schema_version: i32,
+    /// Applied migrations, oldest first
+    pub applied_migrations: Vec<MigrationRecord>,
+    /// Migration files not applied yet, in the order apply runs them. Empty
+    /// without a migrations directory.
+    pub pending_migrations: Vec<String>,
+    /// Number of managed objects of each type
+    pub object_counts: Vec<(ObjectType, usize)>,
+    /// Managed objects pgmg_state records as applied that aren't in the
+    /// database, e.g. because they were dropped by hand
+    pub missing_objects: Vec<(ObjectType, String)>,
+    /// Sessions holding the apply lock
+    pub lock_holders: Vec<AdvisoryLockHolder>,
+    /// The most recent recorded apply run
+    pub last_run: Option<RunRecord>,
+}
+
+/// Summarize the database's pgmg state from the state tables and catalogs:
+/// applied and pending migrations, managed objects by type and those
+/// missing from the database, who holds the apply lock, the state table
+/// version and the last apply run.
+///
+/// Unlike plan, the code directory isn't parsed, and nothing is created or
+/// changed, not even the state tables. Objects and runs are only read once
+/// apply has upgraded the state tables to the version this pgmg uses.
+pub async fn execute_status(
+    migrations_dir: Option<PathBuf>,
+    connection_string: String,
+    config: &PgmgConfig,
+) -> Result<StatusResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let lock_holders = config.apply_lock_manager(&connection_string).holders(&client).await?;
+
+    let initialized: bool = client.query_one(
+        "SELECT to_regclass('pgmg.pgmg_state') IS NOT NULL AND to_regclass('pgmg.pgmg_migrations') IS NOT NULL",
+        &[],
+    ).await?.get(0);
+
+    let mut result = StatusResult {
+        initialized,
+        schema_version: 0,
+        applied_migrations: Vec::new(),
+        pending_migrations: Vec::new(),
+        object_counts: Vec::new(),
+        missing_objects: Vec::new(),
+        lock_holders,
+        last_run: None,
+    };
+
+    let state_manager = StateManager::new(&client);
+    if initialized {
+        result.schema_version = state_manager.schema_version().await?;
+        result.applied_migrations = state_manager.get_applied_migrations().await?;
+    }
+
+    if let Some(migrations_dir) = &migrations_dir {
+        let applied: HashSet<&str> = result.applied_migrations.iter().map(|migration| migration.name.as_str()).collect();
+        result.pending_migrations = scan_migrations(migrations_dir).await?
+            .into_iter()
+            .map(|file| file.name)
+            .filter(|name| !applied.contains(name.as_str()))
+            .collect();
+    }
+
+    if !initialized || result.schema_version < STATE_SCHEMA_VERSION {
+        return Ok(result);
+    }
+
+    let mut names_by_type: Vec<(ObjectType, Vec<String>)> = Vec::new();
+    for record in state_manager.get_tracked_objects().await? {
+        let name = object_key(&record.object_name);
+        match names_by_type.iter_mut().find(|(object_type, _)| *object_type == record.object_type) {
+            Some((_, names)) => names.push(name),
+            None => names_by_type.push((record.object_type, vec![name])),
+        }
+    }
+    names_by_type.sort_by_key(|(object_type, _)| object_type.to_string());
+
+    for (object_type, names) in &names_by_type {
+        result.object_counts.push((object_type.clone(), names.len()));
+
+        // Types without a catalog lookup, e.g. comments, are taken as present
+        let Some(query) = oid_query(object_type) else { continue };
+        let present: HashSet<String> = client.query(query, &[names]).await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        result.missing_objects.extend(names.iter()
+            .filter(|name| !present.contains(*name))
+            .map(|name| (object_type.clone(), name.clone())));
+    }
+
+    result.last_run = state_manager.get_runs(1).await?.into_iter().next();
+
+    Ok(result)
+}
+
+#[cfg(feature = "cli")]
+pub fn print_status_summary(result: &StatusResult) {
+    use crate::logging::{format_duration, output};
+    use chrono::{DateTime, Local};
+    use owo_colors::OwoColorize;
+
+    /// Applied migrations listed before the rest are only counted
+    const RECENT_MIGRATIONS: usize = 5;
+
+    let timestamp = |time: std::time::SystemTime| {
+        DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S %Z").to_string()
+    };
+
+    if !result.initialized {
+        output::info("pgmg has not been applied to this database yet");
+    } else if result.schema_version < STATE_SCHEMA_VERSION {
+        output::line(format!("{:<16}{}", "State tables:", format!(
+            "version {}, the next apply upgrades them to version {}",
+            result.schema_version, STATE_SCHEMA_VERSION
+        ).yellow()));
+    } else {
+        output::line(format!("{:<16}version {}", "State tables:", result.schema_version));
+    }
+
+    output::line(format!(
+        "{:<16}{} applied, {} pending",
+        "Migrations:",
+        result.applied_migrations.len(),
+        result.pending_migrations.len()
+    ));
+    let earlier = result.applied_migrations.len().saturating_sub(RECENT_MIGRATIONS);
+    if earlier > 0 {
+        output::line(format!("  {}", format!("... {} earlier", earlier).dimmed()));
+    }
+    for migration in &result.applied_migrations[earlier..] {
+        output::line(format!(
+            "  {} {} {}",
+            output::symbol("✓").green(),
+            migration.name,
+            timestamp(migration.applied_at).dimmed()
+        ));
+    }
+    for name in &result.pending_migrations {
+        output::line(format!("  {} {} {}", "+".green().bold(), name.cyan(), "pending".yellow()));
+    }
+
+    if result.initialized && result.schema_version >= STATE_SCHEMA_VERSION {
+        let total: usize = result.object_counts.iter().map(|(_, count)| count).sum();
+        output::line(format!("{:<16}{}", "Objects:", total));
+        for (object_type, count) in &result.object_counts {
+            output::line(format!("  {:<28}{}", object_type.to_string().to_lowercase(), count));
+        }
+
+        if result.missing_objects.is_empty() {
+            output::line(format!("{:<16}{}", "Drift:", "none, every managed object is in the database".green()));
+        } else {
+            output::line(format!("{:<16}{}", "Drift:", format!(
+                "{} managed object(s) missing from the database",
+                result.missing_objects.len()
+            ).red()));
+            for (object_type, name) in &result.missing_objects {
+                output::line(format!("  {} {} {}", output::symbol("✗").red(), object_type.to_string().to_lowercase().dimmed(), name));
+            }
+        }
+    }
+
+    match result.lock_holders.first() {
+        Some(holder) => output::line(format!("{:<16}{}", "Apply lock:", format!("held by {}", holder.describe()).yellow())),
+        None => output::line(format!("{:<16}not held", "Apply lock:")),
+    }
+
+    match &result.last_run {
+        Some(run) => {
+            let outcome = if run.succeeded {
+                "succeeded".green().to_string()
+            } else {
+                "failed".red().to_string()
+            };
+            output::line(format!(
+                "{:<16}#{} {} {} by {} in {}",
+                "Last apply:",
+                run.id,
+                outcome,
+                timestamp(run.finished_at),
+                run.db_user,
+                format_duration(run.duration)
+            ));
+        }
+        None if result.initialized => output::line(format!("{:<16}{}", "Last apply:", "not recorded".dimmed())),
+        None => {}
+    }
+}
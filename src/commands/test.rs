@@ -3,7 +3,19 @@ use std::fs;
 use std::time::{Duration, Instant};
 use crate::db::{connect_with_url, TestDatabase};
 use crate::sql::splitter::split_sql_file;
+use futures_util::stream::{self, StreamExt};
 use owo_colors::OwoColorize;
+use crate::logging::{format_duration, output};
+use crate::commands::plan::{execute_plan, ChangeOperation};
+use crate::db::scan_sql_files;
+use crate::sql::{analyze_test_file, build_test_dependency_map, identify_sql_object, matches_tags, QualifiedIdent};
+use crate::analysis::{DependencyGraph, ObjectRef, SearchPath};
+use crate::builtin_catalog::{BuiltinCatalog, DEFAULT_POSTGRES_VERSION};
+use crate::cancel::CancellationToken;
+use crate::error::PgmgError;
+use std::collections::HashSet;
+use std::process::Command;
+use tracing::warn;
 // Manual TAP parsing implementation
 
 #[derive(Debug)]
@@ -25,10 +37,27 @@ pub struct TestFileResult {
     pub failed_count: usize,
     pub skipped_count: usize,
     pub failures: Vec<TestFailure>,
+    /// Every assertion reported in the TAP output, in order
+    pub cases: Vec<TestCase>,
     pub tap_output: String,
     pub duration: Duration,
 }
 
+/// One pgTAP assertion
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub test_number: usize,
+    pub description: String,
+    pub status: TestStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
 #[derive(Debug)]
 pub struct TestFailure {
     pub test_number: usize,
@@ -38,24 +67,54 @@ pub struct TestFailure {
     pub sql_context: Option<String>,
 }
 
+/// How the database the tests run in is built
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TestDatabaseMode {
+    /// Cloned from a template database that is rebuilt when the migration or
+    /// code files change
+    #[default]
+    Template,
+    /// Built from the migrations, code and seed files, then dropped. Cloned
+    /// from a cached template of its own that is rebuilt when any of those
+    /// files change, or always when `rebuild_template` is set.
+    Fresh {
+        seed_dir: Option<PathBuf>,
+        rebuild_template: bool,
+    },
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_test(
     path: Option<PathBuf>,
     connection_string: String,
     tap_output: bool,
     quiet: bool,
+    jobs: usize,
+    database: &TestDatabaseMode,
     config: &crate::config::PgmgConfig,
-) -> Result<TestResult, Box<dyn std::error::Error>> {
-    execute_test_with_options(path, connection_string, tap_output, !quiet, quiet, config).await
+) -> crate::error::Result<TestResult> {
+    execute_test_with_options(path, connection_string, tap_output, !quiet, quiet, jobs, database, config,
+                              &CancellationToken::new()).await
 }
 
+/// Run the test files found under `path` against a fresh test database.
+///
+/// With `jobs` above 1, up to that many files run at once, each in its own
+/// connection and in a transaction that is rolled back afterwards. Once
+/// `cancel` is cancelled the running tests are stopped, the test database is
+/// dropped and the run fails with [`PgmgError::Cancelled`].
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_test_with_options(
     path: Option<PathBuf>,
     connection_string: String,
     tap_output: bool,
     show_immediate_results: bool,
     quiet: bool,
+    jobs: usize,
+    database: &TestDatabaseMode,
     config: &crate::config::PgmgConfig,
-) -> Result<TestResult, Box<dyn std::error::Error>> {
+    cancel: &CancellationToken,
+) -> crate::error::Result<TestResult> {
     let start_time = Instant::now();
     
     // Discover test files
@@ -65,17 +124,292 @@ pub async fn execute_test_with_options(
         return Err("No test files found. Looking for files matching *.test.sql".into());
     }
     
-    println!("{} Found {} test file(s)", "→".cyan(), test_files.len());
+    output::line(format!("{} Found {} test file(s)", output::symbol("→").cyan(), test_files.len()));
+    
+    run_tests(test_files, connection_string, tap_output, show_immediate_results, quiet, jobs, database, config, start_time, cancel).await
+}
+
+/// Run only the test files under `path` that depend on objects changed since
+/// the last apply or since a git ref. Changed objects include everything that
+/// depends on them, so a test of a view runs when a function the view calls
+/// changes. With nothing affected, no test database is created.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_changed_tests(
+    path: Option<PathBuf>,
+    connection_string: String,
+    tap_output: bool,
+    quiet: bool,
+    jobs: usize,
+    since: &ChangedSince,
+    database: &TestDatabaseMode,
+    config: &crate::config::PgmgConfig,
+    cancel: &CancellationToken,
+) -> crate::error::Result<TestResult> {
+    let start_time = Instant::now();
+    
+    let test_files = discover_test_files(path)?;
+    if test_files.is_empty() {
+        return Err("No test files found. Looking for files matching *.test.sql".into());
+    }
+    
+    let code_dir = config.code_dir.clone()
+        .ok_or("Running changed tests needs a code directory. Set code_dir in pgmg.toml")?;
+    
+    let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+    let (changed_objects, changed_files) = match since {
+        ChangedSince::LastApply => (objects_changed_since_apply(&code_dir, &connection_string).await?, Vec::new()),
+        ChangedSince::GitRef(git_ref) => objects_changed_since_ref(&code_dir, git_ref, &builtin_catalog, &config.search_path()).await?,
+    };
+    
+    // Test files that changed themselves run too
+    let selected = select_tests(&test_files, &changed_objects, &changed_files, &builtin_catalog).await;
+    
+    output::line(format!(
+        "{} {} changed object(s) affect {} of {} test file(s)",
+        output::symbol("→").cyan(),
+        changed_objects.len(),
+        selected.len(),
+        test_files.len()
+    ));
+    
+    if selected.is_empty() {
+        return Ok(no_tests_run(start_time));
+    }
+    
+    run_tests(selected, connection_string, tap_output, !quiet, quiet, jobs, database, config, start_time, cancel).await
+}
+
+/// Run only the test files under `path` that depend on objects with one of
+/// `tags`, or on anything that depends on them
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_tagged_tests(
+    path: Option<PathBuf>,
+    connection_string: String,
+    tap_output: bool,
+    quiet: bool,
+    jobs: usize,
+    tags: &[String],
+    database: &TestDatabaseMode,
+    config: &crate::config::PgmgConfig,
+    cancel: &CancellationToken,
+) -> crate::error::Result<TestResult> {
+    let start_time = Instant::now();
+    
+    let test_files = discover_test_files(path)?;
+    if test_files.is_empty() {
+        return Err("No test files found. Looking for files matching *.test.sql".into());
+    }
+    
+    let code_dir = config.code_dir.clone()
+        .ok_or("Running tests by tag needs a code directory. Set code_dir in pgmg.toml")?;
+    
+    let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
+    let code_objects = scan_sql_files(&code_dir, &builtin_catalog).await?;
+    let tagged: Vec<ObjectRef> = code_objects.iter()
+        .filter(|object| matches_tags(&object.directives.tags, tags))
+        .map(|object| ObjectRef {
+            object_type: object.object_type.clone(),
+            qualified_name: object.qualified_name.clone(),
+        })
+        .collect();
+    let graph = DependencyGraph::build_with_search_path(&code_objects, &builtin_catalog, &config.search_path())?;
+    // affected_by_changes leaves out the objects it starts from
+    let mut affected = graph.affected_by_changes(&tagged);
+    affected.extend(tagged.iter().cloned());
+    
+    let selected = select_tests(&test_files, &affected, &[], &builtin_catalog).await;
+    
+    output::line(format!(
+        "{} {} object(s) tagged {} affect {} of {} test file(s)",
+        output::symbol("→").cyan(),
+        tagged.len(),
+        tags.join(", "),
+        selected.len(),
+        test_files.len()
+    ));
+    
+    if selected.is_empty() {
+        return Ok(no_tests_run(start_time));
+    }
+    
+    run_tests(selected, connection_string, tap_output, !quiet, quiet, jobs, database, config, start_time, cancel).await
+}
+
+/// The test files depending on any of `objects`, plus `extra_files`
+async fn select_tests(
+    test_files: &[PathBuf],
+    objects: &[ObjectRef],
+    extra_files: &[PathBuf],
+    builtin_catalog: &BuiltinCatalog,
+) -> Vec<PathBuf> {
+    let mut analyzed = Vec::new();
+    for test_file in test_files {
+        match analyze_test_file(test_file, builtin_catalog).await {
+            Ok(test) => analyzed.push(test),
+            Err(e) => warn!(file = %test_file.display(), error = %e, "Failed to analyze test file"),
+        }
+    }
+    let dependency_map = build_test_dependency_map(analyzed);
+    
+    let mut affected: HashSet<PathBuf> = dependency_map.find_tests_for_objects(objects).into_iter().collect();
+    affected.extend(extra_files.iter().filter_map(|file| fs::canonicalize(file).ok()));
+    test_files.iter()
+        .filter(|test_file| affected.contains(*test_file) || fs::canonicalize(test_file).is_ok_and(|path| affected.contains(&path)))
+        .cloned()
+        .collect()
+}
+
+fn no_tests_run(start_time: Instant) -> TestResult {
+    TestResult {
+        tests_run: 0,
+        tests_passed: 0,
+        tests_failed: 0,
+        tests_skipped: 0,
+        test_files: Vec::new(),
+        duration: start_time.elapsed(),
+    }
+}
+
+/// Where `pgmg test --changed` looks for changed objects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangedSince {
+    /// Objects whose code differs from what pgmg_state recorded at the last apply
+    LastApply,
+    /// Objects defined in code files changed since a git ref, committed or not
+    GitRef(String),
+}
+
+/// Objects the plan would create, update or drop, dependents included
+async fn objects_changed_since_apply(
+    code_dir: &Path,
+    connection_string: &str,
+) -> Result<Vec<ObjectRef>, Box<dyn std::error::Error>> {
+    let plan = execute_plan(None, Some(code_dir.to_path_buf()), connection_string.to_string(), None).await?;
+    
+    Ok(plan.changes.iter().filter_map(|change| match change {
+        ChangeOperation::CreateObject { object, .. } | ChangeOperation::UpdateObject { object, .. } => Some(ObjectRef {
+            object_type: object.object_type.clone(),
+            qualified_name: object.qualified_name.clone(),
+        }),
+        // Tests of a dropped object should fail, so they run too
+        ChangeOperation::DeleteObject { object_type, object_name, .. } => Some(ObjectRef {
+            object_type: object_type.clone(),
+            qualified_name: QualifiedIdent::from_qualified_name(object_name),
+        }),
+        ChangeOperation::ApplyMigration { .. } => None,
+    }).collect())
+}
+
+/// Objects defined, before or after, in the code files that differ from
+/// `git_ref` (untracked files included), plus their dependents. Also returns
+/// the changed test files. Every object in a changed file counts as changed.
+async fn objects_changed_since_ref(
+    code_dir: &Path,
+    git_ref: &str,
+    builtin_catalog: &BuiltinCatalog,
+    search_path: &SearchPath,
+) -> Result<(Vec<ObjectRef>, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    let mut changed_files = git_lines(&["diff", "--name-only", "--relative", git_ref, "--"])?;
+    changed_files.extend(git_lines(&["ls-files", "--others", "--exclude-standard"])?);
+    
+    let code_dir_path = fs::canonicalize(code_dir)?;
+    let cwd = std::env::current_dir()?;
+    
+    let mut changed_objects = Vec::new();
+    let mut changed_tests = Vec::new();
+    for file in changed_files {
+        let path = PathBuf::from(&file);
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else { continue };
+        if !file_name.ends_with(".sql") {
+            continue;
+        }
+        if file_name.ends_with(".test.sql") {
+            changed_tests.push(path);
+            continue;
+        }
+        
+        let in_code_dir = fs::canonicalize(&path)
+            .unwrap_or_else(|_| cwd.join(&path))
+            .starts_with(&code_dir_path);
+        if !in_code_dir {
+            continue;
+        }
+        
+        if let Ok(current) = fs::read_to_string(&path) {
+            changed_objects.extend(objects_in_sql(&current));
+        }
+        // `./` makes the path relative to the current directory rather than the repository root
+        if let Ok(previous) = git_output(&["show", &format!("{}:./{}", git_ref, file)]) {
+            changed_objects.extend(objects_in_sql(&previous));
+        }
+    }
+    
+    let code_objects = scan_sql_files(code_dir, builtin_catalog).await?;
+    let graph = DependencyGraph::build_with_search_path(&code_objects, builtin_catalog, search_path)?;
     
-    // Create isolated test database using template for speed
-    println!("{} Creating isolated test database...", "→".cyan());
-    let test_db = TestDatabase::new_with_template(
-        &connection_string,
-        config.migrations_dir.clone(),
-        config.code_dir.clone(),
-        config,
-    ).await?;
-    println!("  {} Created test database: {}", "✓".green(), test_db.name);
+    Ok((graph.affected_by_changes(&changed_objects), changed_tests))
+}
+
+/// The objects a SQL file defines. Statements that don't parse are ignored.
+fn objects_in_sql(content: &str) -> Vec<ObjectRef> {
+    let Ok(statements) = split_sql_file(content) else { return Vec::new() };
+    
+    statements.iter()
+        .filter_map(|statement| identify_sql_object(&statement.sql).ok().flatten())
+        .map(|object| ObjectRef {
+            object_type: object.object_type,
+            qualified_name: object.qualified_name,
+        })
+        .collect()
+}
+
+fn git_output(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+fn git_lines(args: &[&str]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Ok(git_output(args)?.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+}
+
+/// Create a test database and run `test_files` in it
+#[allow(clippy::too_many_arguments)]
+async fn run_tests(
+    test_files: Vec<PathBuf>,
+    connection_string: String,
+    tap_output: bool,
+    show_immediate_results: bool,
+    quiet: bool,
+    jobs: usize,
+    database: &TestDatabaseMode,
+    config: &crate::config::PgmgConfig,
+    start_time: Instant,
+    cancel: &CancellationToken,
+) -> crate::error::Result<TestResult> {
+    output::line(format!("{} Creating isolated test database...", output::symbol("→").cyan()));
+    let test_db = match database {
+        // Cloned from a template for speed
+        TestDatabaseMode::Template => TestDatabase::new_with_template(
+            &connection_string,
+            config.migrations_dir.clone(),
+            config.code_dir.clone(),
+            config,
+        ).await?,
+        TestDatabaseMode::Fresh { seed_dir, rebuild_template } => TestDatabase::new_fresh_from_template(
+            &connection_string,
+            config.migrations_dir.clone(),
+            config.code_dir.clone(),
+            seed_dir.clone(),
+            config,
+            *rebuild_template,
+        ).await?,
+    };
+    output::line(format!("  {} Created test database: {}", output::symbol("✓").green(), test_db.name));
     
     // Connect to test database
     let (client, connection) = connect_with_url(&test_db.connection_string).await?;
@@ -84,72 +418,131 @@ pub async fn execute_test_with_options(
     connection.spawn();
     
     // Run tests in a block to ensure cleanup happens even on error
-    let test_result = async {
+    let run = async {
         // Check if pgTAP is available
         check_pgtap_availability(&client).await?;
         
-        let mut test_results = Vec::new();
-        let mut total_passed = 0;
-        let mut total_failed = 0;
-        let mut total_skipped = 0;
-        let mut total_run = 0;
-        
-        // Run each test file
-        for test_file in test_files {
-            // Display relative path from current directory
-            let display_path = std::env::current_dir()
-                .ok()
-                .and_then(|cwd| test_file.strip_prefix(cwd).ok())
-                .unwrap_or(&test_file);
-            if !quiet {
-                println!("\n{} Running {}", "→".cyan(), display_path.display().to_string().bright_blue());
-            }
-            
-            let file_result = run_test_file(&client, &test_file, tap_output, quiet).await?;
-            
-            total_run += file_result.test_count;
-            total_passed += file_result.passed_count;
-            total_failed += file_result.failed_count;
-            total_skipped += file_result.skipped_count;
+        let test_results = if jobs > 1 {
+            // Installed up front so the files don't race to create it
+            client.execute("CREATE EXTENSION IF NOT EXISTS pgtap", &[]).await?;
+            run_test_files_parallel(&test_db.connection_string, &test_files, tap_output, show_immediate_results && !quiet, jobs).await?
+        } else {
+            let mut test_results = Vec::new();
             
-            // Print immediate results if requested and not in quiet mode
-            if show_immediate_results && !quiet {
-                if file_result.passed {
-                    println!("  {} {} tests passed", "✓".green(), file_result.test_count);
-                } else {
-                    println!("  {} {} tests failed", "✗".red(), file_result.failed_count);
+            // Run each test file
+            for test_file in test_files {
+                if !quiet {
+                    output::line(format!("\n{} Running {}", output::symbol("→").cyan(), display_path(&test_file).display().to_string().bright_blue()));
                 }
+                
+                let file_result = run_test_file(&client, &test_file, tap_output, quiet).await?;
+                
+                // Print immediate results if requested and not in quiet mode
+                if show_immediate_results && !quiet {
+                    if file_result.passed {
+                        output::line(format!("  {} {} tests passed ({})", output::symbol("✓").green(), file_result.test_count, format_duration(file_result.duration)));
+                    } else {
+                        output::line(format!("  {} {} tests failed ({})", output::symbol("✗").red(), file_result.failed_count, format_duration(file_result.duration)));
+                    }
+                }
+                
+                test_results.push(file_result);
+                
+                // Clean up any aborted transaction before next test file
+                // This ensures each test file starts with a clean connection state
+                let _ = client.simple_query("ROLLBACK").await;
             }
-            
-            test_results.push(file_result);
-            
-            // Clean up any aborted transaction before next test file
-            // This ensures each test file starts with a clean connection state
-            let _ = client.simple_query("ROLLBACK").await;
-        }
+            test_results
+        };
         
         Ok::<_, Box<dyn std::error::Error>>(TestResult {
-            tests_run: total_run,
-            tests_passed: total_passed,
-            tests_failed: total_failed,
-            tests_skipped: total_skipped,
+            tests_run: test_results.iter().map(|file| file.test_count).sum(),
+            tests_passed: test_results.iter().map(|file| file.passed_count).sum(),
+            tests_failed: test_results.iter().map(|file| file.failed_count).sum(),
+            tests_skipped: test_results.iter().map(|file| file.skipped_count).sum(),
             test_files: test_results,
             duration: start_time.elapsed(),
         })
-    }.await;
+    };
+    let test_result = tokio::select! {
+        result = run => result.map_err(PgmgError::from),
+        // Dropping the test database below ends the running test's session
+        _ = cancel.cancelled() => Err(PgmgError::Cancelled),
+    };
     
     // Clean up test database regardless of test outcome
-    println!("\n{} Cleaning up test database...", "→".cyan());
+    output::line(format!("\n{} Cleaning up test database...", output::symbol("→").cyan()));
     if let Err(e) = test_db.cleanup().await {
-        eprintln!("{} Failed to drop test database: {}", "Warning:".yellow(), e);
+        output::warning(format!("Failed to drop test database: {}", e));
     } else {
-        println!("  {} Test database dropped", "✓".green());
+        output::line(format!("  {} Test database dropped", output::symbol("✓").green()));
     }
     
     // Return the test result (propagating any errors)
     test_result
 }
 
+/// Run test files `jobs` at a time, each in its own connection and
+/// transaction. Only failures are printed while the files run, followed by a
+/// line per file as it finishes. Results are returned in `test_files` order.
+async fn run_test_files_parallel(
+    connection_string: &str,
+    test_files: &[PathBuf],
+    tap_output: bool,
+    show_immediate_results: bool,
+    jobs: usize,
+) -> Result<Vec<TestFileResult>, Box<dyn std::error::Error>> {
+    let mut running = stream::iter(test_files.iter().enumerate())
+        .map(|(idx, test_file)| async move {
+            (idx, run_isolated_test_file(connection_string, test_file, tap_output).await)
+        })
+        .buffer_unordered(jobs);
+    
+    let mut results = Vec::with_capacity(test_files.len());
+    while let Some((idx, file_result)) = running.next().await {
+        let file_result = file_result?;
+        
+        if show_immediate_results {
+            let path = display_path(&file_result.file_path).display().to_string();
+            if file_result.passed {
+                output::line(format!("  {} {} {} tests passed ({})", output::symbol("✓").green(), path.bright_blue(), file_result.test_count, format_duration(file_result.duration)));
+            } else {
+                output::line(format!("  {} {} {} tests failed ({})", output::symbol("✗").red(), path.bright_blue(), file_result.failed_count, format_duration(file_result.duration)));
+            }
+        }
+        
+        results.push((idx, file_result));
+    }
+    
+    results.sort_by_key(|(idx, _)| *idx);
+    Ok(results.into_iter().map(|(_, file_result)| file_result).collect())
+}
+
+/// Run a test file on a connection of its own, inside a transaction that is
+/// rolled back afterwards so nothing it does is seen by other files
+async fn run_isolated_test_file(
+    connection_string: &str,
+    test_file: &Path,
+    tap_output: bool,
+) -> Result<TestFileResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(connection_string).await?;
+    connection.spawn();
+    
+    client.batch_execute("BEGIN").await?;
+    let file_result = run_test_file(&client, test_file, tap_output, true).await;
+    let _ = client.batch_execute("ROLLBACK").await;
+    
+    file_result
+}
+
+/// Path relative to the current directory, for display
+fn display_path(path: &Path) -> &Path {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+}
+
 fn discover_test_files(path: Option<PathBuf>) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let search_path = match path {
         Some(p) => p,
@@ -247,6 +640,7 @@ async fn run_test_file(
                     detailed_error: None,
                     sql_context: None,
                 }],
+                cases: vec![],
                 tap_output: format!("# pgTAP extension error: {}", e),
                 duration: start_time.elapsed(),
             });
@@ -270,6 +664,7 @@ async fn run_test_file(
                 detailed_error: None,
                 sql_context: None,
             }],
+            cases: vec![],
             tap_output: "# Error: Test contains psql meta-commands".to_string(),
             duration: start_time.elapsed(),
         });
@@ -346,7 +741,7 @@ SET client_min_messages TO 'INFO';
                                 ));
                                 
                                 if let Some(error_line) = test_content.lines().nth(actual_line - 1) {
-                                    output.push_str(&format!("\n  → {}", error_line.red()));
+                                    output.push_str(&format!("\n  {} {}", output::symbol("→"), error_line.red()));
                                     if col > 0 {
                                         output.push_str(&format!("\n    {}{}", " ".repeat(col - 1), "^".red().bold()));
                                     }
@@ -399,6 +794,7 @@ SET client_min_messages TO 'INFO';
                     detailed_error: Some(detailed_error),
                     sql_context: Some(test_content.clone()), // Store original test content
                 }],
+                cases: vec![],
                 tap_output: format!("# Test execution failed: {}", e),
                 duration: start_time.elapsed(),
             });
@@ -422,6 +818,7 @@ SET client_min_messages TO 'INFO';
         failed_count: parsed_results.failed_count,
         skipped_count: parsed_results.skipped_count,
         failures: parsed_results.failures,
+        cases: parsed_results.cases,
         tap_output,
         duration,
     })
@@ -433,6 +830,7 @@ struct ParsedTapResults {
     failed_count: usize,
     skipped_count: usize,
     failures: Vec<TestFailure>,
+    cases: Vec<TestCase>,
 }
 
 fn parse_tap_output(tap_output: &str, quiet: bool) -> Result<ParsedTapResults, Box<dyn std::error::Error>> {
@@ -441,6 +839,7 @@ fn parse_tap_output(tap_output: &str, quiet: bool) -> Result<ParsedTapResults, B
     let mut failed_count = 0;
     let mut skipped_count = 0;
     let mut failures = Vec::new();
+    let mut cases = Vec::new();
     
     let lines: Vec<&str> = tap_output.lines().collect();
     let mut i = 0;
@@ -460,21 +859,23 @@ fn parse_tap_output(tap_output: &str, quiet: bool) -> Result<ParsedTapResults, B
             skipped_count += 1;
             let description = extract_test_description(line);
             if !quiet {
-                println!("    {} {} {}", "↷".yellow(), "SKIP".yellow(), description.bright_black());
+                output::line(format!("    {} {} {}", output::symbol("↷").yellow(), "SKIP".yellow(), description.bright_black()));
             }
+            cases.push(TestCase { test_number: test_count, description, status: TestStatus::Skipped });
         } else if line.starts_with("ok ") {
             test_count += 1;
             passed_count += 1;
             let description = extract_test_description(line);
             if !quiet && !description.is_empty() {
-                println!("    {} {}", "✓".green(), description.bright_black());
+                output::line(format!("    {} {}", output::symbol("✓").green(), description.bright_black()));
             }
+            cases.push(TestCase { test_number: test_count, description, status: TestStatus::Passed });
         } else if line.starts_with("not ok ") {
             test_count += 1;
             failed_count += 1;
             let description = extract_test_description(line);
             // Always show failures, even in quiet mode
-            println!("    {} {}", "✗".red(), description.red());
+            output::line(format!("    {} {}", output::symbol("✗").red(), description.red()));
             
             // Look ahead for diagnostic information
             let mut diagnostic_lines = Vec::new();
@@ -509,6 +910,7 @@ fn parse_tap_output(tap_output: &str, quiet: bool) -> Result<ParsedTapResults, B
                 Some(diagnostic_lines.join("\n"))
             };
             
+            cases.push(TestCase { test_number: test_count, description: description.clone(), status: TestStatus::Failed });
             failures.push(TestFailure {
                 test_number: test_count,
                 description: description.clone(),
@@ -527,6 +929,7 @@ fn parse_tap_output(tap_output: &str, quiet: bool) -> Result<ParsedTapResults, B
         failed_count,
         skipped_count,
         failures,
+        cases,
     })
 }
 
@@ -583,15 +986,16 @@ fn analyze_sql_error(test_content: &str, error_line: usize, error_col: usize) ->
             let is_error_line = actual_line_num == error_line;
             
             if is_error_line {
-                output.push_str(&format!("\n  → {:3}: {}", 
+                output.push_str(&format!("\n  {} {:3}: {}", 
+                    output::symbol("→"),
                     actual_line_num.to_string().yellow().bold(), 
                     line.red()
                 ));
                 // Add pointer to specific column if we have it
                 if error_col > 0 {
-                    let padding = format!("  → {:3}: ", actual_line_num);
+                    let padding = format!("  {} {:3}: ", output::symbol("→"), actual_line_num);
                     output.push_str(&format!("\n  {}{}{}", 
-                        " ".repeat(padding.len() + error_col - 1),
+                        " ".repeat(padding.chars().count() + error_col - 1),
                         "^".red().bold(),
                         " error here".red().dimmed()
                     ));
@@ -640,14 +1044,15 @@ fn analyze_sql_error(test_content: &str, error_line: usize, error_col: usize) ->
             if let Some(line) = lines.get(line_num) {
                 let actual_line_num = line_num + 1;
                 if actual_line_num == error_line {
-                    output.push_str(&format!("\n  → {:3}: {}", 
+                    output.push_str(&format!("\n  {} {:3}: {}", 
+                        output::symbol("→"),
                         actual_line_num.to_string().yellow().bold(), 
                         line.red()
                     ));
                     if error_col > 0 {
-                        let padding = format!("  → {:3}: ", actual_line_num);
+                        let padding = format!("  {} {:3}: ", output::symbol("→"), actual_line_num);
                         output.push_str(&format!("\n  {}{}{}", 
-                            " ".repeat(padding.len() + error_col - 1),
+                            " ".repeat(padding.chars().count() + error_col - 1),
                             "^".red().bold(),
                             " error here".red().dimmed()
                         ));
@@ -666,32 +1071,32 @@ fn analyze_sql_error(test_content: &str, error_line: usize, error_col: usize) ->
 }
 
 pub fn print_test_summary(result: &TestResult) {
-    println!();
-    println!("{}", "Test Summary".bold().bright_blue());
-    println!("{}", "=".repeat(50).bright_black());
+    output::line("");
+    output::line("Test Summary".bold().bright_blue());
+    output::line("=".repeat(50).bright_black());
     
     // Overall results
     if result.tests_failed == 0 {
-        println!("{} {} All tests passed!", "✅".green(), "SUCCESS".green().bold());
+        output::line(format!("{} {} All tests passed!", output::symbol("✅").green(), "SUCCESS".green().bold()));
     } else {
-        println!("{} {} Some tests failed", "❌".red(), "FAILURE".red().bold());
+        output::line(format!("{} {} Some tests failed", output::symbol("❌").red(), "FAILURE".red().bold()));
     }
     
-    println!();
-    println!("{} {} tests run", "→".cyan(), result.tests_run);
-    println!("{} {} passed", "✓".green(), result.tests_passed);
+    output::line("");
+    output::line(format!("{} {} tests run", output::symbol("→").cyan(), result.tests_run));
+    output::line(format!("{} {} passed", output::symbol("✓").green(), result.tests_passed));
     if result.tests_failed > 0 {
-        println!("{} {} failed", "✗".red(), result.tests_failed);
+        output::line(format!("{} {} failed", output::symbol("✗").red(), result.tests_failed));
     }
     if result.tests_skipped > 0 {
-        println!("{} {} skipped", "↷".yellow(), result.tests_skipped);
+        output::line(format!("{} {} skipped", output::symbol("↷").yellow(), result.tests_skipped));
     }
-    println!("{} Test duration: {:.2?}", "⏱".bright_black(), result.duration);
+    output::line(format!("{} Test duration: {:.2?}", output::symbol("⏱").bright_black(), result.duration));
     
     // Failed test details
     if result.tests_failed > 0 {
-        println!();
-        println!("{}", "Failed Tests:".red().bold());
+        output::line("");
+        output::line("Failed Tests:".red().bold());
         for file_result in &result.test_files {
             if !file_result.passed {
                 // Display relative path from current directory
@@ -699,20 +1104,20 @@ pub fn print_test_summary(result: &TestResult) {
                     .ok()
                     .and_then(|cwd| file_result.file_path.strip_prefix(cwd).ok())
                     .unwrap_or(&file_result.file_path);
-                println!("  {} {}", "📁".red(), display_path.display().to_string().red());
+                output::line(format!("  {} {}", output::symbol("📁").red(), display_path.display().to_string().red()));
                 
                 for failure in &file_result.failures {
-                    println!("    {} Test #{}: {}", "✗".red(), failure.test_number, failure.description);
+                    output::line(format!("    {} Test #{}: {}", output::symbol("✗").red(), failure.test_number, failure.description));
                     
                     // Show detailed error if available (SQL execution errors)
                     if let Some(detailed_error) = &failure.detailed_error {
                         // The detailed error already includes formatting, so just print it with indentation
                         for line in detailed_error.lines() {
-                            println!("      {}", line);
+                            output::line(format!("      {}", line));
                         }
                     } else if let Some(diagnostic) = &failure.diagnostic {
                         // Show pgtap diagnostic information with proper formatting
-                        println!("      {}: {}", "Diagnostic".yellow().bold(), "");
+                        output::line(format!("      {}: {}", "Diagnostic".yellow().bold(), ""));
                         for diag_line in diagnostic.lines() {
                             if diag_line.trim().is_empty() {
                                 continue;
@@ -720,22 +1125,22 @@ pub fn print_test_summary(result: &TestResult) {
                             
                             // Format specific pgtap diagnostic patterns
                             if diag_line.contains("Failed test") {
-                                println!("        {}: {}", "Test".dimmed(), diag_line.replace("Failed test", "").trim().trim_matches('"').yellow());
+                                output::line(format!("        {}: {}", "Test".dimmed(), diag_line.replace("Failed test", "").trim().trim_matches('"').yellow()));
                             } else if diag_line.contains("got:") || diag_line.contains("Got:") {
                                 let got_value = diag_line.split(':').nth(1).unwrap_or("").trim();
-                                println!("        {}: {}", "Got".red().bold(), got_value.red());
+                                output::line(format!("        {}: {}", "Got".red().bold(), got_value.red()));
                             } else if diag_line.contains("expected:") || diag_line.contains("Expected:") {
                                 let expected_value = diag_line.split(':').nth(1).unwrap_or("").trim();
-                                println!("        {}: {}", "Expected".green().bold(), expected_value.green());
+                                output::line(format!("        {}: {}", "Expected".green().bold(), expected_value.green()));
                             } else if diag_line.contains("DETAIL:") {
                                 let detail = diag_line.replace("DETAIL:", "").trim().to_string();
-                                println!("        {}: {}", "Detail".yellow(), detail);
+                                output::line(format!("        {}: {}", "Detail".yellow(), detail));
                             } else if diag_line.contains("HINT:") {
                                 let hint = diag_line.replace("HINT:", "").trim().to_string();
-                                println!("        {}: {}", "Hint".green(), hint);
+                                output::line(format!("        {}: {}", "Hint".green(), hint));
                             } else {
                                 // Generic diagnostic line
-                                println!("        {}", diag_line.bright_black());
+                                output::line(format!("        {}", diag_line.bright_black()));
                             }
                         }
                     }
@@ -744,5 +1149,5 @@ pub fn print_test_summary(result: &TestResult) {
         }
     }
     
-    println!();
+    output::line("");
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::time::Duration;
+use crate::db::{gc_test_databases, StaleTestDatabase};
+use crate::db::test_utils::{build_connection_string, parse_connection_string};
+
+#[derive(Debug)]
+pub struct TestDbGcResult {
+    pub ttl: Duration,
+    pub dry_run: bool,
+    pub databases: Vec<StaleTestDatabase>,
+}
+
+/// Drop test databases left behind by killed or crashed test runs. pgmg
+/// comments every test database it creates with its creation time, and
+/// older ones are recognized by their `<db>_test_<millis>` name.
+pub async fn execute_test_db_gc(
+    connection_string: String,
+    ttl: Duration,
+    dry_run: bool,
+) -> Result<TestDbGcResult, Box<dyn std::error::Error>> {
+    let components = parse_connection_string(&connection_string)?;
+    let admin_conn_str = build_connection_string(&components, "postgres");
+
+    let databases = gc_test_databases(&admin_conn_str, ttl, dry_run).await?;
+
+    Ok(TestDbGcResult { ttl, dry_run, databases })
+}
+
+/// Parse a TTL such as `90s`, `30m`, `24h` or `7d`. A bare number is seconds.
+pub fn parse_ttl(ttl: &str) -> Result<Duration, String> {
+    let ttl = ttl.trim();
+    let (amount, unit) = ttl.split_at(ttl.find(|c: char| !c.is_ascii_digit()).unwrap_or(ttl.len()));
+    let amount: u64 = amount.parse()
+        .map_err(|_| format!("Invalid TTL '{}'. Expected a number with a unit, e.g. 24h", ttl))?;
+
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        unit => return Err(format!("Unknown TTL unit '{}'. Expected one of: s, m, h, d", unit)),
+    };
+
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+#[cfg(feature = "cli")]
+pub fn print_test_db_gc_summary(result: &TestDbGcResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    if result.databases.is_empty() {
+        output::line(format!(
+            "{} No test databases older than {}",
+            output::symbol("✓").green(),
+            format_age(result.ttl)
+        ));
+        return;
+    }
+
+    for database in &result.databases {
+        let (symbol, action) = if database.in_use {
+            (output::symbol("⚠").yellow().to_string(), "skipped, still in use")
+        } else if database.dropped {
+            (output::symbol("✓").green().to_string(), "dropped")
+        } else {
+            (output::symbol("→").cyan().to_string(), "would drop")
+        };
+        output::line(format!(
+            "{} {} ({} old, {})",
+            symbol,
+            database.name.bright_blue(),
+            format_age(database.age),
+            action
+        ));
+    }
+
+    let dropped = result.databases.iter().filter(|database| database.dropped).count();
+    if result.dry_run {
+        let droppable = result.databases.iter().filter(|database| !database.in_use).count();
+        output::info(format!("Dry run: {} test database(s) would be dropped", droppable));
+    } else {
+        output::info(format!("Dropped {} test database(s)", dropped));
+    }
+}
+
+#[cfg(feature = "cli")]
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        s if s >= 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_ttl("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_ttl("24h").unwrap(), Duration::from_secs(24 * 60 * 60));
+        assert_eq!(parse_ttl("7d").unwrap(), Duration::from_secs(7 * 24 * 60 * 60));
+        assert!(parse_ttl("").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("2w").is_err());
+    }
+}
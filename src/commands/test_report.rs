@@ -0,0 +1,320 @@
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use serde_json::{json, Value};
+use crate::commands::test::{TestFailure, TestFileResult, TestResult, TestStatus};
+
+/// Machine-readable format for a `pgmg test` report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+/// A report to write after the tests run, given on the command line as
+/// `FORMAT=PATH`, e.g. `junit=reports/pgtap.xml`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for TestReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s.split_once('=')
+            .ok_or_else(|| format!("Invalid report '{}'. Expected FORMAT=PATH, e.g. junit=report.xml", s))?;
+
+        let format = match format.trim().to_lowercase().as_str() {
+            "junit" | "xml" => ReportFormat::Junit,
+            "json" => ReportFormat::Json,
+            _ => return Err(format!("Unknown report format '{}'. Expected one of: junit, json", format)),
+        };
+        if path.trim().is_empty() {
+            return Err(format!("Report '{}' has no path", s));
+        }
+
+        Ok(TestReport { format, path: PathBuf::from(path.trim()) })
+    }
+}
+
+/// Write each report, creating parent directories as needed
+pub fn write_test_reports(result: &TestResult, reports: &[TestReport]) -> Result<(), Box<dyn std::error::Error>> {
+    for report in reports {
+        let content = match report.format {
+            ReportFormat::Junit => junit_xml(result),
+            ReportFormat::Json => serde_json::to_string_pretty(&test_report_json(result))?,
+        };
+
+        if let Some(parent) = report.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&report.path, content)
+            .map_err(|e| format!("Failed to write report {}: {}", report.path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Format a test run as JUnit XML: a `<testsuite>` per test file and a
+/// `<testcase>` per pgTAP assertion. pgTAP reports a file's assertions all at
+/// once, so durations are per file. A file that fails to run gets a single
+/// `<error>` test case with the SQL error.
+pub fn junit_xml(result: &TestResult) -> String {
+    let cases: usize = result.test_files.iter().map(|file| file.cases.len()).sum();
+    let failures: usize = result.test_files.iter().map(failed_cases).sum();
+    let errors: usize = result.test_files.iter().map(|file| file_errors(file).count()).sum();
+    let skipped: usize = result.test_files.iter().map(|file| file.skipped_count).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"pgmg\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        cases + errors,
+        failures,
+        errors,
+        skipped,
+        seconds(result.duration)
+    ));
+
+    for file in &result.test_files {
+        let suite = file.file_path.display().to_string();
+        let file_errors: Vec<&TestFailure> = file_errors(file).collect();
+
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+            escape_xml(&suite),
+            file.cases.len() + file_errors.len(),
+            failed_cases(file),
+            file_errors.len(),
+            file.skipped_count,
+            seconds(file.duration)
+        ));
+
+        for case in &file.cases {
+            let name = if case.description.is_empty() {
+                format!("{}", case.test_number)
+            } else {
+                format!("{} - {}", case.test_number, case.description)
+            };
+            let open = format!("    <testcase classname=\"{}\" name=\"{}\"", escape_xml(&suite), escape_xml(&name));
+
+            match case.status {
+                TestStatus::Passed => xml.push_str(&format!("{}/>\n", open)),
+                TestStatus::Skipped => xml.push_str(&format!("{}>\n      <skipped/>\n    </testcase>\n", open)),
+                TestStatus::Failed => {
+                    let diagnostic = file.failures.iter()
+                        .find(|failure| failure.test_number == case.test_number)
+                        .and_then(|failure| failure.diagnostic.as_deref())
+                        .unwrap_or("");
+                    xml.push_str(&format!(
+                        "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                        open,
+                        escape_xml(&failure_message(&case.description, diagnostic)),
+                        escape_xml(diagnostic)
+                    ));
+                }
+            }
+        }
+
+        for error in file_errors {
+            let details = error.diagnostic.as_deref().unwrap_or(&error.description);
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\">\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+                escape_xml(&suite),
+                escape_xml(&error.description),
+                escape_xml(details.lines().next().unwrap_or("")),
+                escape_xml(details)
+            ));
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Summarize a test run as JSON, with every assertion and failure diagnostic
+pub fn test_report_json(result: &TestResult) -> Value {
+    let files: Vec<Value> = result.test_files.iter().map(|file| {
+        let tests: Vec<Value> = file.cases.iter().map(|case| {
+            let diagnostic = file.failures.iter()
+                .find(|failure| failure.test_number == case.test_number && case.status == TestStatus::Failed)
+                .and_then(|failure| failure.diagnostic.clone());
+            json!({
+                "number": case.test_number,
+                "description": case.description,
+                "status": match case.status {
+                    TestStatus::Passed => "passed",
+                    TestStatus::Failed => "failed",
+                    TestStatus::Skipped => "skipped",
+                },
+                "diagnostic": diagnostic,
+            })
+        }).collect();
+
+        let errors: Vec<Value> = file_errors(file).map(|error| json!({
+            "description": error.description,
+            "error": error.diagnostic,
+        })).collect();
+
+        json!({
+            "path": file.file_path.display().to_string(),
+            "passed": file.passed,
+            "tests_run": file.test_count,
+            "tests_passed": file.passed_count,
+            "tests_failed": file.failed_count,
+            "tests_skipped": file.skipped_count,
+            "duration_ms": file.duration.as_millis() as u64,
+            "tests": tests,
+            "errors": errors,
+        })
+    }).collect();
+
+    json!({
+        "passed": result.tests_failed == 0,
+        "tests_run": result.tests_run,
+        "tests_passed": result.tests_passed,
+        "tests_failed": result.tests_failed,
+        "tests_skipped": result.tests_skipped,
+        "duration_ms": result.duration.as_millis() as u64,
+        "files": files,
+    })
+}
+
+/// Failures that stopped a file from running at all (SQL errors, missing
+/// pgTAP) rather than failed assertions
+fn file_errors(file: &TestFileResult) -> impl Iterator<Item = &TestFailure> {
+    file.failures.iter().filter(|failure| failure.test_number == 0)
+}
+
+fn failed_cases(file: &TestFileResult) -> usize {
+    file.cases.iter().filter(|case| case.status == TestStatus::Failed).count()
+}
+
+fn failure_message(description: &str, diagnostic: &str) -> String {
+    if description.is_empty() {
+        diagnostic.lines().next().unwrap_or("Test failed").to_string()
+    } else {
+        description.to_string()
+    }
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines aren't allowed in XML 1.0
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test::TestCase;
+
+    fn test_result() -> TestResult {
+        let users = TestFileResult {
+            file_path: PathBuf::from("tests/users.test.sql"),
+            passed: false,
+            test_count: 3,
+            passed_count: 1,
+            failed_count: 1,
+            skipped_count: 1,
+            failures: vec![TestFailure {
+                test_number: 2,
+                description: "email is <unique> & required".to_string(),
+                diagnostic: Some("Failed test 2\n    have: 2\n    want: 1".to_string()),
+                detailed_error: None,
+                sql_context: None,
+            }],
+            cases: vec![
+                TestCase { test_number: 1, description: "users table exists".to_string(), status: TestStatus::Passed },
+                TestCase { test_number: 2, description: "email is <unique> & required".to_string(), status: TestStatus::Failed },
+                TestCase { test_number: 3, description: "needs pg_cron".to_string(), status: TestStatus::Skipped },
+            ],
+            tap_output: String::new(),
+            duration: Duration::from_millis(120),
+        };
+        let broken = TestFileResult {
+            file_path: PathBuf::from("tests/broken.test.sql"),
+            passed: false,
+            test_count: 0,
+            passed_count: 0,
+            failed_count: 1,
+            skipped_count: 0,
+            failures: vec![TestFailure {
+                test_number: 0,
+                description: "SQL execution error - check the failing statement above".to_string(),
+                diagnostic: Some("relation \"orders\" does not exist".to_string()),
+                detailed_error: None,
+                sql_context: None,
+            }],
+            cases: vec![],
+            tap_output: String::new(),
+            duration: Duration::from_millis(5),
+        };
+
+        TestResult {
+            tests_run: 3,
+            tests_passed: 1,
+            tests_failed: 2,
+            tests_skipped: 1,
+            test_files: vec![users, broken],
+            duration: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn test_parse_report() {
+        assert_eq!(
+            "junit=reports/pgtap.xml".parse::<TestReport>().unwrap(),
+            TestReport { format: ReportFormat::Junit, path: PathBuf::from("reports/pgtap.xml") }
+        );
+        assert_eq!("JSON=out.json".parse::<TestReport>().unwrap().format, ReportFormat::Json);
+        assert!("junit".parse::<TestReport>().is_err());
+        assert!("html=report.html".parse::<TestReport>().is_err());
+        assert!("json=".parse::<TestReport>().is_err());
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let xml = junit_xml(&test_result());
+
+        assert!(xml.contains("<testsuites name=\"pgmg\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"1.500\">"));
+        assert!(xml.contains("<testsuite name=\"tests/users.test.sql\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"0.120\">"));
+        assert!(xml.contains("<testcase classname=\"tests/users.test.sql\" name=\"1 - users table exists\"/>"));
+        assert!(xml.contains("name=\"2 - email is &lt;unique&gt; &amp; required\">"));
+        assert!(xml.contains("<failure message=\"email is &lt;unique&gt; &amp; required\">Failed test 2\n    have: 2\n    want: 1</failure>"));
+        assert!(xml.contains("<skipped/>"));
+        assert!(xml.contains("<error message=\"relation &quot;orders&quot; does not exist\">"));
+    }
+
+    #[test]
+    fn test_report_json_lists_assertions() {
+        let report = test_report_json(&test_result());
+
+        assert_eq!(report["passed"], false);
+        assert_eq!(report["duration_ms"], 1500);
+        assert_eq!(report["files"][0]["tests"][1]["status"], "failed");
+        assert_eq!(report["files"][0]["tests"][1]["diagnostic"], "Failed test 2\n    have: 2\n    want: 1");
+        assert_eq!(report["files"][0]["tests"][0]["diagnostic"], Value::Null);
+        assert_eq!(report["files"][1]["errors"][0]["error"], "relation \"orders\" does not exist");
+    }
+}
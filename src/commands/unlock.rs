@@ -0,0 +1,78 @@
+use crate::config::PgmgConfig;
+use crate::db::{connect_with_url, AdvisoryLockHolder};
+use tracing::{debug, warn};
+
+#[derive(Debug)]
+pub struct UnlockResult {
+    /// Sessions that held the apply lock
+    pub holders: Vec<AdvisoryLockHolder>,
+    /// Holders whose sessions were ended, releasing the lock
+    pub terminated: Vec<i32>,
+    /// Whether `--force` was given
+    pub forced: bool,
+}
+
+/// Show which sessions hold the advisory lock apply takes on this database,
+/// under the namespace configured in `[apply_lock]`.
+///
+/// An advisory lock can only be released by the session holding it, so with
+/// `force` a stale lock, e.g. from a deploy whose runner was killed while its
+/// connection stayed open, is released by ending that session with
+/// `pg_terminate_backend`. This needs superuser, pg_signal_backend, or the
+/// role the session connected as.
+pub async fn execute_unlock(
+    connection_string: String,
+    config: &PgmgConfig,
+    force: bool,
+) -> Result<UnlockResult, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    connection.spawn();
+
+    let lock_manager = config.apply_lock_manager(&connection_string);
+    debug!(lock_key = lock_manager.lock_key(), namespace = config.apply_lock_namespace(), "Looking up apply lock holders");
+    let holders = lock_manager.holders(&client).await?;
+
+    let mut terminated = Vec::new();
+    if force {
+        for holder in &holders {
+            let row = client.query_one("SELECT pg_terminate_backend($1)", &[&holder.pid]).await
+                .map_err(|e| format!("Failed to end session {}: {}", holder.pid, e))?;
+            if row.get::<_, bool>(0) {
+                terminated.push(holder.pid);
+            } else {
+                warn!(pid = holder.pid, "Session holding the apply lock had already ended");
+            }
+        }
+    }
+
+    Ok(UnlockResult { holders, terminated, forced: force })
+}
+
+#[cfg(feature = "cli")]
+pub fn print_unlock_summary(result: &UnlockResult) {
+    use crate::logging::output;
+    use owo_colors::OwoColorize;
+
+    if result.holders.is_empty() {
+        output::info("The apply lock is not held");
+        return;
+    }
+
+    for holder in &result.holders {
+        let marker = if result.terminated.contains(&holder.pid) {
+            output::symbol("✓").green().to_string()
+        } else {
+            output::symbol("⚠").yellow().to_string()
+        };
+        output::line(format!("{} Held by {}", marker, holder.describe()));
+        if !holder.query.is_empty() {
+            output::line(format!("    {}", holder.query.dimmed()));
+        }
+    }
+
+    if result.forced {
+        output::info(format!("Ended {} session(s); the apply lock is released", result.terminated.len()));
+    } else {
+        output::info("If no apply is running, release the lock by ending the session with `pgmg unlock --force`");
+    }
+}
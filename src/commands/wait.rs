@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant, SystemTime};
+use futures_util::future;
+use tokio::sync::mpsc;
+use tokio_postgres::{AsyncMessage, Client};
+use crate::db::{connect_with_url, PgConnection};
+use crate::notify::APPLY_COMPLETED_CHANNEL;
+use tracing::debug;
+
+/// How often pgmg_history is read while waiting. Notifications wake the wait
+/// sooner, but can't be received over TLS connections, whose messages pgmg
+/// doesn't handle itself.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The apply `pgmg wait-for-apply` saw finish, as recorded in pgmg_history
+#[derive(Debug, Clone)]
+pub struct CompletedApply {
+    pub id: i64,
+    pub finished_at: SystemTime,
+    pub migrations_applied: i32,
+    pub objects_changed: i32,
+    /// How long the wait took
+    pub waited: Duration,
+}
+
+/// Wait for an apply against the database to finish, so a CI job can start
+/// as soon as another pipeline's deploy is done.
+///
+/// Applies that finished before the wait began count if they finished within
+/// `since` of it, so a job started just after the deploy doesn't wait for the
+/// next one. Only successful applies are recorded; a failed one leaves the
+/// wait running until `timeout`.
+pub async fn execute_wait_for_apply(
+    connection_string: String,
+    timeout: Duration,
+    since: Option<Duration>,
+) -> Result<CompletedApply, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+
+    let (client, connection) = connect_with_url(&connection_string).await?;
+    let mut notified = listen(connection);
+
+    // Listening before reading the baseline means an apply finishing in
+    // between is still seen
+    client.batch_execute(&format!("LISTEN \"{}\"", APPLY_COMPLETED_CHANNEL)).await?;
+    let baseline = baseline_id(&client, since.unwrap_or_default()).await?;
+    debug!(baseline, "Waiting for an apply recorded after the baseline");
+
+    loop {
+        if let Some(mut apply) = applied_after(&client, baseline).await? {
+            apply.waited = started.elapsed();
+            return Ok(apply);
+        }
+
+        let remaining = timeout.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return Err(format!(
+                "No apply finished within {}",
+                crate::logging::format_duration(timeout)
+            ).into());
+        }
+
+        tokio::select! {
+            // A closed channel disables this branch, leaving the poll
+            Some(()) = notified.recv() => {}
+            _ = tokio::time::sleep(remaining.min(POLL_INTERVAL)) => {}
+        }
+    }
+}
+
+/// Drive the connection, waking the returned receiver on each notification
+fn listen(connection: PgConnection) -> mpsc::UnboundedReceiver<()> {
+    let (wake, notified) = mpsc::unbounded_channel();
+
+    match connection {
+        PgConnection::NoTls(mut connection) => {
+            tokio::spawn(async move {
+                while let Some(message) = future::poll_fn(|cx| connection.poll_message(cx)).await {
+                    match message {
+                        Ok(AsyncMessage::Notification(_)) => {
+                            let _ = wake.send(());
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            tracing::error!(error = %e, "Database connection error");
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+        // Already driven by the TLS connector; the wait falls back to polling
+        #[cfg(feature = "tls")]
+        PgConnection::Rustls(_) => {}
+    }
+
+    notified
+}
+
+/// The last apply that finished more than `since` ago, or 0
+async fn baseline_id(client: &Client, since: Duration) -> Result<i64, Box<dyn std::error::Error>> {
+    if !history_exists(client).await? {
+        return Ok(0);
+    }
+
+    let row = client.query_one(
+        "SELECT coalesce(max(id), 0) FROM pgmg.pgmg_history WHERE finished_at < now() - make_interval(secs => $1)",
+        &[&since.as_secs_f64()],
+    ).await?;
+    Ok(row.get(0))
+}
+
+/// The first apply recorded after `baseline`. The history table is read
+/// without creating it, as it won't exist until the first apply by a pgmg
+/// that records them.
+async fn applied_after(client: &Client, baseline: i64) -> Result<Option<CompletedApply>, Box<dyn std::error::Error>> {
+    if !history_exists(client).await? {
+        return Ok(None);
+    }
+
+    let row = client.query_opt(
+        "SELECT id, finished_at, migrations_applied, objects_changed
+         FROM pgmg.pgmg_history WHERE id > $1 ORDER BY id LIMIT 1",
+        &[&baseline],
+    ).await?;
+
+    Ok(row.map(|row| CompletedApply {
+        id: row.get(0),
+        finished_at: row.get(1),
+        migrations_applied: row.get(2),
+        objects_changed: row.get(3),
+        waited: Duration::ZERO,
+    }))
+}
+
+async fn history_exists(client: &Client) -> Result<bool, Box<dyn std::error::Error>> {
+    let row = client.query_one("SELECT to_regclass('pgmg.pgmg_history') IS NOT NULL", &[]).await?;
+    Ok(row.get(0))
+}
+
+#[cfg(feature = "cli")]
+pub fn print_wait_summary(apply: &CompletedApply) {
+    use crate::logging::{format_duration, output};
+    use owo_colors::OwoColorize;
+
+    output::line(format!(
+        "{} Apply #{} finished: {} migration(s), {} object change(s) {}",
+        output::symbol("✓").green(),
+        apply.id,
+        apply.migrations_applied,
+        apply.objects_changed,
+        format!("(waited {})", format_duration(apply.waited)).dimmed()
+    ));
+}
@@ -1,10 +1,13 @@
-use crate::commands::{execute_plan, execute_apply, execute_test_with_options};
+use crate::commands::{execute_plan_with_options, execute_apply_with_cancellation, PlanOptions, execute_reset, execute_test_with_options, find_edited_migrations, TestDatabaseMode};
+use crate::commands::reset::print_reset_warning;
 use crate::config::PgmgConfig;
+use crate::db::{connect_with_url, DatabaseConfig};
 use crate::error::{PgmgError, Result};
 use crate::logging::output;
 use crate::sql::{scan_test_files, build_test_dependency_map, TestDependencyMap};
 use crate::analysis::graph::ObjectRef;
-use crate::builtin_catalog::BuiltinCatalog;
+use crate::builtin_catalog::{BuiltinCatalog, DEFAULT_POSTGRES_VERSION};
+use crate::cancel::CancellationToken;
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use owo_colors::OwoColorize;
 use std::collections::HashSet;
@@ -22,6 +25,9 @@ pub struct WatchConfig {
     pub debounce_duration: Duration,
     pub auto_apply: bool,
     pub pgmg_config: PgmgConfig,
+    /// Stops the watch, and the apply or test run in progress, which fails
+    /// with [`PgmgError::Cancelled`]
+    pub cancellation: CancellationToken,
 }
 
 impl Default for WatchConfig {
@@ -33,6 +39,7 @@ impl Default for WatchConfig {
             debounce_duration: Duration::from_millis(500),
             auto_apply: true,
             pgmg_config: PgmgConfig::default(),
+            cancellation: CancellationToken::new(),
         }
     }
 }
@@ -42,6 +49,9 @@ impl Default for WatchConfig {
 struct WatchState {
     last_event_time: Instant,
     pending_paths: HashSet<PathBuf>,
+    /// Applied migrations whose files were edited since. Auto-apply stops
+    /// until the dev database is rebuilt or the edits are undone.
+    edited_migrations: Vec<String>,
 }
 
 impl WatchState {
@@ -49,6 +59,7 @@ impl WatchState {
         Self {
             last_event_time: Instant::now(),
             pending_paths: HashSet::new(),
+            edited_migrations: Vec::new(),
         }
     }
 
@@ -169,9 +180,20 @@ pub async fn execute_watch(config: WatchConfig) -> Result<()> {
     
     // Create shared state for debouncing
     let mut state = WatchState::new();
+
+    // Migrations edited before the watch started
+    if config.migrations_dir.is_some() {
+        check_edited_migrations(&config, &mut state.edited_migrations).await;
+    }
+
+    let commands = read_commands();
     
     // Handle incoming file events and process them
     loop {
+        if config.cancellation.is_cancelled() {
+            return Err(PgmgError::Cancelled);
+        }
+
         // Check for new events with a timeout
         match rx.recv_timeout(Duration::from_millis(100)) {
             Ok(path) => {
@@ -184,12 +206,132 @@ pub async fn execute_watch(config: WatchConfig) -> Result<()> {
                 if state.should_process(config.debounce_duration) {
                     let paths = state.take_paths();
                     if !paths.is_empty() {
-                        process_changes(&config, paths, test_dep_map.clone()).await;
+                        process_changes(&config, paths, test_dep_map.clone(), &mut state.edited_migrations).await;
                     }
                 }
             }
         }
+
+        while let Ok(command) = commands.try_recv() {
+            match command.trim() {
+                "" => {}
+                "rebuild" => rebuild_database(&config, &commands, &mut state.edited_migrations).await,
+                other => output::info(&format!(
+                    "Unknown command '{}'. Type 'rebuild' to rebuild the dev database from scratch.",
+                    other
+                )),
+            }
+        }
+    }
+}
+
+/// Read commands typed while watching, one per line. Reading stops at the end
+/// of input, e.g. when stdin isn't a terminal.
+fn read_commands() -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Look for applied migrations whose files were edited, pausing auto-apply
+/// while there are any. Editing an applied migration leaves the database
+/// out of step with the migration history, which applying code on top of
+/// would hide.
+async fn check_edited_migrations(config: &WatchConfig, edited_migrations: &mut Vec<String>) {
+    let Some(ref migrations_dir) = config.migrations_dir else { return };
+
+    let found = async {
+        let (client, connection) = connect_with_url(&config.connection_string).await?;
+        connection.spawn();
+        find_edited_migrations(&client, migrations_dir).await
+    }.await;
+
+    let found = match found {
+        Ok(found) => found,
+        Err(e) => {
+            output::error(&format!("Failed to check applied migrations for edits: {}", e));
+            return;
+        }
+    };
+
+    let was_paused = !edited_migrations.is_empty();
+    *edited_migrations = found;
+
+    if !edited_migrations.is_empty() {
+        output::warning(&format!(
+            "{} applied migration(s) changed since they were applied:",
+            edited_migrations.len()
+        ));
+        for name in edited_migrations.iter() {
+            output::line(format!("  - {}", name.yellow()));
+        }
+        output::info("Auto-apply is paused. Undo the edits, or type 'rebuild' and press Enter to drop the dev database and apply everything from scratch.");
+    } else if was_paused {
+        output::success("Applied migrations match their files again. Auto-apply resumed.");
+    }
+}
+
+/// Drop and recreate the dev database, then apply all migrations and code to
+/// it. The user confirms by typing the database name, as with `pgmg reset`.
+async fn rebuild_database(
+    config: &WatchConfig,
+    commands: &mpsc::Receiver<String>,
+    edited_migrations: &mut Vec<String>,
+) {
+    let database_name = match DatabaseConfig::from_url(&config.connection_string) {
+        Ok(database) => database.database,
+        Err(e) => {
+            output::error(&format!("Failed to read the database name: {}", e));
+            return;
+        }
+    };
+
+    print_reset_warning(&database_name);
+    output::line("Type the database name to confirm:".bold());
+    let confirmed = commands.recv().is_ok_and(|input| input.trim() == database_name);
+    if !confirmed {
+        output::info("Database name mismatch. Rebuild cancelled.");
+        return;
+    }
+
+    output::step(&format!("Rebuilding database '{}'...", database_name));
+    if let Err(e) = execute_reset(config.connection_string.clone(), true).await {
+        output::error(&format!("Failed to reset database: {}", e));
+        return;
+    }
+
+    match execute_apply_with_cancellation(
+        config.migrations_dir.clone(),
+        config.code_dir.clone(),
+        config.connection_string.clone(),
+        &config.pgmg_config,
+        &config.cancellation,
+    ).await {
+        Ok(apply_result) if apply_result.errors.is_empty() => {
+            output::success(&format!(
+                "Rebuilt '{}': {} migration(s) applied, {} object(s) created",
+                database_name,
+                apply_result.migrations_applied.len(),
+                apply_result.objects_created.len()
+            ));
+        }
+        Ok(apply_result) => {
+            output::error(&format!("Rebuild completed with {} error(s)", apply_result.errors.len()));
+            for error in &apply_result.errors {
+                output::line(format!("\n{}", error));
+            }
+        }
+        Err(e) => output::error(&format!("Failed to apply to the rebuilt database: {}", e)),
     }
+
+    check_edited_migrations(config, edited_migrations).await;
 }
 
 /// Process a set of file changes
@@ -197,6 +339,7 @@ async fn process_changes(
     config: &WatchConfig,
     paths: HashSet<PathBuf>,
     test_dep_map: Arc<Mutex<Option<TestDependencyMap>>>,
+    edited_migrations: &mut Vec<String>,
 ) {
     output::step(&format!("Detected changes in {} file(s)", paths.len()));
 
@@ -227,13 +370,16 @@ async fn process_changes(
 
     // Notify about migration changes (don't auto-apply - migrations require explicit 'pgmg apply')
     if !migration_files.is_empty() {
+        check_edited_migrations(config, edited_migrations).await;
+    }
+    if !migration_files.is_empty() && edited_migrations.is_empty() {
         output::info(&format!(
             "Migration file(s) changed ({}). Run 'pgmg apply' to apply migrations.",
             migration_files.len()
         ));
         for path in &migration_files {
             if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                println!("  - {}", name);
+                output::line(format!("  - {}", name));
             }
         }
     }
@@ -242,7 +388,7 @@ async fn process_changes(
     let mut changed_objects = Vec::new();
     if !code_files.is_empty() {
         output::step("Processing managed object changes...");
-        changed_objects = process_db_changes(config, code_files).await;
+        changed_objects = process_db_changes(config, code_files, !edited_migrations.is_empty()).await;
     }
     
     // Rebuild test dependency map if any test files changed
@@ -287,20 +433,23 @@ async fn process_changes(
     }
 }
 
-/// Process database object file changes (plan and apply)
-async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>) -> Vec<ObjectRef> {
+/// Process database object file changes (plan and apply). With `paused`,
+/// changes are only planned, as when auto-apply is disabled.
+async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>, paused: bool) -> Vec<ObjectRef> {
     // Run plan
     output::step("Running plan...");
     
-    match execute_plan(
+    match execute_plan_with_options(
         None, // Don't process migrations in watch mode - they require explicit 'pgmg apply'
         config.code_dir.clone(),
         config.connection_string.clone(),
         None, // No graph output in watch mode
+        &PlanOptions::from_config(&config.pgmg_config),
     ).await {
         Ok(plan_result) => {
             // Check if there are any changes (migrations are not processed in watch mode)
-            if plan_result.changes.is_empty() {
+            if plan_result.changes.is_empty() && plan_result.enum_additions.is_empty()
+                && plan_result.publication_changes.is_empty() && plan_result.cron_job_changes.is_empty() {
                 output::info("No changes detected");
                 return Vec::new();
             }
@@ -312,25 +461,25 @@ async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>) -> Vec<O
             output::subheader("Changes detected:");
 
             if !plan_result.changes.is_empty() {
-                println!("Managed object changes:");
+                output::line("Managed object changes:");
                 for change in &plan_result.changes {
                     match change {
                         crate::commands::plan::ChangeOperation::CreateObject { object, .. } => {
-                            println!("  + {:?} {}", object.object_type, object.qualified_name.name);
+                            output::line(format!("  + {:?} {}", object.object_type, object.qualified_name.name));
                             changed_objects.push(ObjectRef {
                                 object_type: object.object_type.clone(),
                                 qualified_name: object.qualified_name.clone(),
                             });
                         }
                         crate::commands::plan::ChangeOperation::UpdateObject { object, .. } => {
-                            println!("  ~ {:?} {}", object.object_type, object.qualified_name.name);
+                            output::line(format!("  ~ {:?} {}", object.object_type, object.qualified_name.name));
                             changed_objects.push(ObjectRef {
                                 object_type: object.object_type.clone(),
                                 qualified_name: object.qualified_name.clone(),
                             });
                         }
                         crate::commands::plan::ChangeOperation::DeleteObject { object_type, object_name, .. } => {
-                            println!("  - {:?} {}", object_type, object_name);
+                            output::line(format!("  - {:?} {}", object_type, object_name));
                             // Deleted objects don't need test runs
                         }
                         // ApplyMigration won't appear since we pass None for migrations_dir
@@ -338,16 +487,32 @@ async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>) -> Vec<O
                     }
                 }
             }
+            for addition in &plan_result.enum_additions {
+                output::line(format!("  ~ {:?} {} (new enum values)", addition.object.object_type, addition.object.qualified_name.name));
+                changed_objects.push(ObjectRef {
+                    object_type: addition.object.object_type.clone(),
+                    qualified_name: addition.object.qualified_name.clone(),
+                });
+            }
+            for change in &plan_result.publication_changes {
+                output::line(format!("  ~ {:?} {} (published tables)", change.object.object_type, change.object.qualified_name.name));
+            }
+            for change in &plan_result.cron_job_changes {
+                output::line(format!("  ~ {:?} {} (schedule or command)", change.object.object_type, change.object.qualified_name.name));
+            }
             
             // Auto-apply if enabled
-            if config.auto_apply {
+            if paused {
+                output::info("Auto-apply is paused until edited migrations are undone or the dev database is rebuilt (type 'rebuild').");
+            } else if config.auto_apply {
                 output::step("Applying changes...");
                 
-                match execute_apply(
+                match execute_apply_with_cancellation(
                     None, // Don't process migrations in watch mode - they require explicit 'pgmg apply'
                     config.code_dir.clone(),
                     config.connection_string.clone(),
                     &config.pgmg_config,
+                    &config.cancellation,
                 ).await {
                     Ok(apply_result) => {
                         if apply_result.errors.is_empty() {
@@ -366,14 +531,13 @@ async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>) -> Vec<O
                             // Display each error with proper formatting preserved
                             for error in &apply_result.errors {
                                 // The error already includes detailed formatting from apply command
-                                println!("\n{}", error);
+                                output::line(format!("\n{}", error));
                             }
                         }
                     }
                     Err(e) => {
                         output::error(&format!("Failed to apply changes: {}", e));
-                        let pgmg_error = PgmgError::from(e);
-                        if let Some(suggestion) = crate::error::suggest_fix(&pgmg_error) {
+                        if let Some(suggestion) = crate::error::suggest_fix(&e) {
                             output::info(&suggestion);
                         }
                     }
@@ -387,8 +551,7 @@ async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>) -> Vec<O
         }
         Err(e) => {
             output::error(&format!("Failed to plan changes: {}", e));
-            let pgmg_error = PgmgError::from(e);
-            if let Some(suggestion) = crate::error::suggest_fix(&pgmg_error) {
+            if let Some(suggestion) = crate::error::suggest_fix(&e) {
                 output::info(&suggestion);
             }
             Vec::new()
@@ -398,7 +561,7 @@ async fn process_db_changes(config: &WatchConfig, _paths: Vec<PathBuf>) -> Vec<O
 
 /// Build test dependency map for the code directory
 async fn build_test_dependencies(code_dir: &Path) -> std::result::Result<TestDependencyMap, Box<dyn std::error::Error>> {
-    let builtin_catalog = BuiltinCatalog::new();
+    let builtin_catalog = BuiltinCatalog::embedded(DEFAULT_POSTGRES_VERSION);
     let test_files = scan_test_files(code_dir, &builtin_catalog).await?;
     Ok(build_test_dependency_map(test_files))
 }
@@ -406,6 +569,9 @@ async fn build_test_dependencies(code_dir: &Path) -> std::result::Result<TestDep
 /// Run specific test files
 async fn run_specific_tests(config: &WatchConfig, test_files: Vec<PathBuf>) {
     for test_file in test_files {
+        if config.cancellation.is_cancelled() {
+            return;
+        }
         info!("Running test: {}", test_file.display());
         
         match execute_test_with_options(
@@ -414,7 +580,10 @@ async fn run_specific_tests(config: &WatchConfig, test_files: Vec<PathBuf>) {
             false, // Don't show TAP output in watch mode
             false, // Don't show immediate results (we'll show our own)
             true,  // Run quietly in watch mode
+            1,     // Test files run one at a time
+            &TestDatabaseMode::Template,
             &config.pgmg_config,
+            &config.cancellation,
         ).await {
             Ok(test_result) => {
                 // Display relative path from current directory
@@ -425,13 +594,15 @@ async fn run_specific_tests(config: &WatchConfig, test_files: Vec<PathBuf>) {
                     
                 if test_result.tests_failed == 0 {
                     output::success(&format!(
-                        "✓ {} - {} tests passed",
+                        "{} {} - {} tests passed",
+                        output::symbol("✓"),
                         display_path.display(),
                         test_result.tests_passed
                     ));
                 } else {
                     output::error(&format!(
-                        "❌ {} - {} failed, {} passed",
+                        "{} {} - {} failed, {} passed",
+                        output::symbol("❌"),
                         display_path.display(),
                         test_result.tests_failed,
                         test_result.tests_passed
@@ -440,17 +611,17 @@ async fn run_specific_tests(config: &WatchConfig, test_files: Vec<PathBuf>) {
                     // Show failures with enhanced formatting
                     for file_result in &test_result.test_files {
                         for failure in &file_result.failures {
-                            println!("    {} {}: {}", "✗".red(), failure.test_number, failure.description);
+                            output::line(format!("    {} {}: {}", output::symbol("✗").red(), failure.test_number, failure.description));
                             
                             // Show detailed error if available (SQL execution errors)
                             if let Some(detailed_error) = &failure.detailed_error {
                                 // The detailed error already includes formatting, so just print it with indentation
                                 for line in detailed_error.lines() {
-                                    println!("      {}", line);
+                                    output::line(format!("      {}", line));
                                 }
                             } else if let Some(diagnostic) = &failure.diagnostic {
                                 // Show pgtap diagnostic information with proper formatting
-                                println!("      {}: {}", "Diagnostic".yellow().bold(), "");
+                                output::line(format!("      {}: {}", "Diagnostic".yellow().bold(), ""));
                                 for diag_line in diagnostic.lines() {
                                     if diag_line.trim().is_empty() {
                                         continue;
@@ -458,28 +629,28 @@ async fn run_specific_tests(config: &WatchConfig, test_files: Vec<PathBuf>) {
                                     
                                     // Format specific pgtap diagnostic patterns
                                     if diag_line.contains("Failed test") {
-                                        println!("        {}: {}", "Test".dimmed(), diag_line.replace("Failed test", "").trim().trim_matches('"').yellow());
+                                        output::line(format!("        {}: {}", "Test".dimmed(), diag_line.replace("Failed test", "").trim().trim_matches('"').yellow()));
                                     } else if diag_line.contains("got:") || diag_line.contains("Got:") {
                                         let got_value = diag_line.split(':').nth(1).unwrap_or("").trim();
-                                        println!("        {}: {}", "Got".red().bold(), got_value.red());
+                                        output::line(format!("        {}: {}", "Got".red().bold(), got_value.red()));
                                     } else if diag_line.contains("expected:") || diag_line.contains("Expected:") {
                                         let expected_value = diag_line.split(':').nth(1).unwrap_or("").trim();
-                                        println!("        {}: {}", "Expected".green().bold(), expected_value.green());
+                                        output::line(format!("        {}: {}", "Expected".green().bold(), expected_value.green()));
                                     } else if diag_line.contains("DETAIL:") {
                                         let detail = diag_line.replace("DETAIL:", "").trim().to_string();
-                                        println!("        {}: {}", "Detail".yellow(), detail);
+                                        output::line(format!("        {}: {}", "Detail".yellow(), detail));
                                     } else if diag_line.contains("HINT:") {
                                         let hint = diag_line.replace("HINT:", "").trim().to_string();
-                                        println!("        {}: {}", "Hint".green(), hint);
+                                        output::line(format!("        {}: {}", "Hint".green(), hint));
                                     } else if diag_line.contains("caught:") {
                                         let caught_value = diag_line.split(':').skip(1).collect::<Vec<_>>().join(":").trim().to_string();
-                                        println!("        {}: {}", "Caught".red().bold(), caught_value.red());
+                                        output::line(format!("        {}: {}", "Caught".red().bold(), caught_value.red()));
                                     } else if diag_line.contains("wanted:") {
                                         let wanted_value = diag_line.split(':').skip(1).collect::<Vec<_>>().join(":").trim().to_string();
-                                        println!("        {}: {}", "Expected".green().bold(), wanted_value.green());
+                                        output::line(format!("        {}: {}", "Expected".green().bold(), wanted_value.green()));
                                     } else {
                                         // Generic diagnostic line
-                                        println!("        {}", diag_line.bright_black());
+                                        output::line(format!("        {}", diag_line.bright_black()));
                                     }
                                 }
                             }
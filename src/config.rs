@@ -1,7 +1,19 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::fs;
+use std::time::Duration;
 use crate::db::tls::{TlsMode, TlsConfig};
+use crate::db::lock_retry::LockRetryPolicy;
+use crate::db::locks::{AdvisoryLockManager, DEFAULT_LOCK_NAMESPACE};
+use crate::hooks::{HookAction, HookEvent, Hooks};
+use crate::notify::webhook::Webhook;
+use crate::sql::{MaintainedTable, ObjectType, PartitionMaintenance, PartitionRemoval, QualifiedIdent, RefreshStrategy, SchemaFilter, SkipRule, SqlVars, TenantTemplate};
+use crate::sql::tenants::DEFAULT_TENANT_PLACEHOLDER;
+use crate::sql::lint::{normalize_type_name, LintRule, LintRules};
+use crate::integrations::IntegrationAction;
+use crate::plpgsql_check::CheckPolicy;
+use crate::analysis::SearchPath;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PgmgConfig {
@@ -17,6 +29,10 @@ pub struct PgmgConfig {
     /// Directory containing seed SQL files
     pub seed_dir: Option<PathBuf>,
     
+    /// Seed directories per environment, selected with `pgmg seed --env`,
+    /// e.g. `seed_dirs = { dev = "seeds/dev", test = "seeds/test" }`
+    pub seed_dirs: Option<BTreeMap<String, PathBuf>>,
+    
     /// Path to output dependency graph (for plan command)
     pub output_graph: Option<PathBuf>,
     
@@ -29,8 +45,115 @@ pub struct PgmgConfig {
     /// Run plpgsql_check on modified functions (requires development_mode)
     pub check_plpgsql: Option<bool>,
     
+    /// Refuse to apply migrations that would break logical replication
+    pub replication_safe: Option<bool>,
+    
+    /// Print ASCII markers instead of unicode symbols and disable colors, for
+    /// terminals that can't render them
+    pub plain_output: Option<bool>,
+    
+    /// Apply even when safety checks report problems (CLI only)
+    #[serde(skip)]
+    pub force: Option<bool>,
+    
     /// TLS/SSL configuration
     pub tls: Option<TlsConfigSection>,
+    
+    /// Retry DDL that times out waiting for locks held by other sessions
+    pub lock_retry: Option<LockRetryConfigSection>,
+    
+    /// The advisory lock that keeps applies to one database from running at once
+    pub apply_lock: Option<ApplyLockConfigSection>,
+    
+    /// Statements to leave out of migrations and object apply, in addition to
+    /// pg_cron in test mode and plpgsql_check on AWS RDS
+    pub skip_rules: Option<Vec<SkipRuleConfigSection>>,
+    
+    /// Name of the environment profile in use, overridden by `PGMG_ENV`
+    pub environment: Option<String>,
+    
+    /// Environment profiles, e.g. `[environments.ci]`
+    pub environments: Option<BTreeMap<String, EnvironmentConfigSection>>,
+    
+    /// Built-in actions run after an apply changes something, e.g. `[integrations.postgrest]`
+    pub integrations: Option<IntegrationsConfigSection>,
+    
+    /// Tell PostgREST to reload its schema cache after an apply that changed
+    /// something; the same as an empty `[integrations.postgrest]` section
+    pub postgrest_reload: Option<bool>,
+    
+    /// Endpoints sent a JSON summary after an apply that changed something
+    /// or failed, e.g. Slack incoming webhooks
+    pub webhooks: Option<Vec<WebhookConfigSection>>,
+    
+    /// Which plpgsql_check findings fail `check` and block an apply
+    pub plpgsql_check: Option<PlpgsqlCheckConfigSection>,
+    
+    /// Allow CREATE TABLE in the code directory. Tables there are dropped
+    /// when their file is removed, so plan and apply refuse them by default.
+    pub allow_tables_in_code_dir: Option<bool>,
+    
+    /// Rules for `pgmg lint`
+    pub lint: Option<LintConfigSection>,
+    
+    /// Treat edits to whitespace and comments alone as no-ops instead of
+    /// recreating the object
+    pub ignore_formatting_changes: Option<bool>,
+    
+    /// Grant the privileges an object had again after apply drops and
+    /// recreates it (default: true)
+    pub preserve_privileges: Option<bool>,
+    
+    /// How materialized views recreated by apply are refreshed: none
+    /// (default), refresh or concurrently. `-- pgmg:refresh` overrides it.
+    pub matview_refresh: Option<String>,
+    
+    /// What apply does with a partition removed from the code directory:
+    /// detach (default) it from its parent, keeping its rows, or drop it
+    pub partition_removal: Option<String>,
+    
+    /// Time-partitioned tables whose partitions `pgmg partitions` creates
+    /// ahead of time and removes once past their retention
+    pub partitions: Option<PartitionsConfigSection>,
+    
+    /// Refuse to apply a plan that drops or rewrites a relation larger than
+    /// this, e.g. "10GB", unless forced
+    pub destructive_size_limit: Option<String>,
+    
+    /// Dump the tables and materialized views a plan drops, with their data,
+    /// before apply changes anything
+    pub backup_before_destructive: Option<bool>,
+    
+    /// Directory the dumps taken by `backup_before_destructive` are written
+    /// to (default "backups")
+    pub backup_dir: Option<PathBuf>,
+    
+    /// Only create, change and drop objects in these schemas. Migrations
+    /// run regardless.
+    pub managed_schemas: Option<Vec<String>>,
+    
+    /// Never create, change or drop objects in these schemas, e.g. one
+    /// another tool manages
+    pub ignored_schemas: Option<Vec<String>>,
+    
+    /// Schemas unqualified names in code files are looked up in, in order,
+    /// like PostgreSQL's search_path (default: public)
+    pub search_path: Option<Vec<String>>,
+    
+    /// Apply code files using a placeholder schema once per tenant schema
+    pub tenants: Option<TenantsConfigSection>,
+    
+    /// Values for `${NAME}` variables in code files and migrations. Files
+    /// are only substituted when this section is present.
+    pub vars: Option<BTreeMap<String, String>>,
+    
+    /// SQL or shell commands run before and after plan, apply and migrations
+    pub hooks: Option<HooksConfigSection>,
+    
+    /// Only plan and apply changes to objects with one of these
+    /// `-- pgmg:tags` (CLI only)
+    #[serde(skip)]
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +171,199 @@ pub struct TlsConfigSection {
     pub sslkey: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockRetryConfigSection {
+    /// Total tries per statement, including the first (default 5)
+    pub attempts: Option<u32>,
+    
+    /// How long each try may wait for a lock, in milliseconds (default 5000)
+    pub lock_timeout_ms: Option<u64>,
+    
+    /// Delay before the first retry in milliseconds, doubled after each try (default 1000)
+    pub backoff_ms: Option<u64>,
+    
+    /// Longest delay between tries, in milliseconds (default 30000)
+    pub max_backoff_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApplyLockConfigSection {
+    /// Hashed with the database's host, port and name to give the lock key
+    /// (default "pgmg_apply"). Give deployments that share a database but
+    /// manage separate objects their own namespace so they don't wait on
+    /// each other.
+    pub namespace: Option<String>,
+    
+    /// How long to wait for another apply to release the lock, in seconds (default 30)
+    pub timeout_secs: Option<u64>,
+    
+    /// Wait for the lock at all, or fail at once if it's held (default true)
+    pub wait: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantsConfigSection {
+    /// Schema name in code files replaced by each tenant's schema (default "__tenant__")
+    pub placeholder: Option<String>,
+    
+    /// Tenant schemas
+    pub schemas: Option<Vec<String>>,
+    
+    /// Query returning more tenant schemas in its first column, run at plan time
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartitionsConfigSection {
+    /// pg_cron schedule to run the maintenance on inside the database, e.g.
+    /// "0 3 * * *". The job is removed again when this is unset.
+    pub schedule: Option<String>,
+    
+    pub tables: Option<Vec<PartitionedTableConfigSection>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartitionedTableConfigSection {
+    /// The partitioned table, range-partitioned on one time column
+    pub table: String,
+    
+    /// Range each partition covers: day, week, month or year
+    pub interval: String,
+    
+    /// Partitions to keep created ahead of the current one (default 4)
+    pub premake: Option<u32>,
+    
+    /// Partitions to keep before the current one; all if unset
+    pub retain: Option<u32>,
+    
+    /// What happens to partitions past `retain`: detach or drop (default
+    /// `partition_removal`)
+    pub expired: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookConfigSection {
+    pub url: String,
+    
+    /// json (default) or slack
+    pub format: Option<String>,
+    
+    /// Extra request headers, e.g. `{ Authorization = "Bearer ..." }`
+    pub headers: Option<BTreeMap<String, String>>,
+    
+    /// Only send in these environments; all if unset
+    pub environments: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HooksConfigSection {
+    pub pre_plan: Option<Vec<HookConfigSection>>,
+    pub post_plan: Option<Vec<HookConfigSection>>,
+    
+    /// Run once the plan is known, before anything is changed
+    pub pre_apply: Option<Vec<HookConfigSection>>,
+    
+    /// Run after an apply that changed something. Failures are only warned
+    /// about, as the changes are committed by then.
+    pub post_apply: Option<Vec<HookConfigSection>>,
+    
+    pub pre_migration: Option<Vec<HookConfigSection>>,
+    pub post_migration: Option<Vec<HookConfigSection>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HookConfigSection {
+    /// SQL to run on pgmg's connection
+    pub sql: Option<String>,
+    
+    /// Shell command to run, after `sql` if both are given
+    pub run: Option<String>,
+    
+    /// Only run in these environments; all if unset
+    pub environments: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkipRuleConfigSection {
+    /// Shown in apply results for the statements the rule skips
+    pub reason: Option<String>,
+    
+    /// When the rule applies: always (default), test or rds
+    pub when: Option<String>,
+    
+    /// Extensions whose statements and functions are skipped
+    pub extensions: Option<Vec<String>>,
+    
+    /// Schemas whose objects are skipped when created or referenced
+    pub schemas: Option<Vec<String>>,
+    
+    /// Object types to skip, e.g. materialized_view
+    pub object_types: Option<Vec<String>>,
+}
+
+/// What pgmg should assume about one environment instead of detecting it.
+/// Anything left unset is detected at apply time as before.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvironmentConfigSection {
+    /// Whether the database is on AWS RDS
+    pub rds: Option<bool>,
+    
+    /// Extensions that are or aren't installed, e.g. `{ pg_cron = false, plpgsql_check = true }`.
+    /// Statements using a missing extension are always skipped; the built-in
+    /// skip rules leave an installed one alone.
+    pub extensions: Option<BTreeMap<String, bool>>,
+    
+    /// Emit NOTIFY events when objects are loaded (requires development_mode)
+    pub emit_notify_events: Option<bool>,
+    
+    /// Run plpgsql_check on modified functions (requires development_mode)
+    pub check_plpgsql: Option<bool>,
+    
+    /// `${NAME}` variables for this environment, overriding `[vars]`
+    pub vars: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlpgsqlCheckConfigSection {
+    /// Lowest level that fails: error (default) or warning
+    pub fail_on: Option<String>,
+    
+    /// Codes (SQLSTATEs) never reported, e.g. known false positives
+    pub ignore: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LintConfigSection {
+    /// Rules to turn off, e.g. `["schema-qualified"]`
+    pub disable: Option<Vec<String>>,
+    
+    /// Pattern names must match, per object type, e.g. `{ view = "^v_" }`
+    pub naming: Option<BTreeMap<String, String>>,
+    
+    /// Types columns, arguments and return values may not use (default `["timestamp"]`)
+    pub forbidden_types: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrationsConfigSection {
+    pub postgrest: Option<PostgrestIntegrationSection>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostgrestIntegrationSection {
+    /// What to tell PostgREST to reload: reload_schema (the default) and/or
+    /// reload_config, which also picks up in-database settings such as the
+    /// JWT role claim
+    pub actions: Option<Vec<String>>,
+    
+    /// Channel PostgREST listens on, its `db-channel` setting (default pgrst)
+    pub channel: Option<String>,
+    
+    /// Only reload when a migration ran or an object in one of these schemas
+    /// changed, e.g. PostgREST's `db-schemas`; after any change if unset
+    pub schemas: Option<Vec<String>>,
+}
+
 impl PgmgConfig {
     /// Load configuration from pgmg.toml file in current directory
     pub fn load_from_file() -> Result<Option<Self>, Box<dyn std::error::Error>> {
@@ -60,7 +376,7 @@ impl PgmgConfig {
         let content = fs::read_to_string(&config_path)?;
         let config: PgmgConfig = toml::from_str(&content)?;
         
-        Ok(Some(config))
+        Ok(Some(config.with_environment_profile()?))
     }
     
     /// Merge CLI arguments with config file values
@@ -79,11 +395,41 @@ impl PgmgConfig {
             migrations_dir: cli_migrations_dir.or(base_config.migrations_dir),
             code_dir: cli_code_dir.or(base_config.code_dir),
             seed_dir: base_config.seed_dir,
+            seed_dirs: base_config.seed_dirs,
             output_graph: cli_output_graph.or(base_config.output_graph),
             development_mode: base_config.development_mode,
             emit_notify_events: base_config.emit_notify_events,
             check_plpgsql: base_config.check_plpgsql,
+            replication_safe: base_config.replication_safe,
+            plain_output: base_config.plain_output,
+            force: None,
             tls: base_config.tls,
+            lock_retry: base_config.lock_retry,
+            apply_lock: base_config.apply_lock,
+            skip_rules: base_config.skip_rules,
+            environment: base_config.environment,
+            environments: base_config.environments,
+            integrations: base_config.integrations,
+            postgrest_reload: base_config.postgrest_reload,
+            webhooks: base_config.webhooks,
+            plpgsql_check: base_config.plpgsql_check,
+            allow_tables_in_code_dir: base_config.allow_tables_in_code_dir,
+            lint: base_config.lint,
+            ignore_formatting_changes: base_config.ignore_formatting_changes,
+            preserve_privileges: base_config.preserve_privileges,
+            matview_refresh: base_config.matview_refresh,
+            partition_removal: base_config.partition_removal,
+            partitions: base_config.partitions,
+            destructive_size_limit: base_config.destructive_size_limit,
+            backup_before_destructive: base_config.backup_before_destructive,
+            backup_dir: base_config.backup_dir,
+            managed_schemas: base_config.managed_schemas,
+            ignored_schemas: base_config.ignored_schemas,
+            search_path: base_config.search_path,
+            tenants: base_config.tenants,
+            vars: base_config.vars,
+            hooks: base_config.hooks,
+            tags: None,
         }
     }
     
@@ -101,11 +447,41 @@ impl PgmgConfig {
             migrations_dir: base_config.migrations_dir,
             code_dir: base_config.code_dir,
             seed_dir: cli_seed_dir.or(base_config.seed_dir),
+            seed_dirs: base_config.seed_dirs,
             output_graph: base_config.output_graph,
             development_mode: base_config.development_mode,
             emit_notify_events: base_config.emit_notify_events,
             check_plpgsql: base_config.check_plpgsql,
+            replication_safe: base_config.replication_safe,
+            plain_output: base_config.plain_output,
+            force: None,
             tls: base_config.tls,
+            lock_retry: base_config.lock_retry,
+            apply_lock: base_config.apply_lock,
+            skip_rules: base_config.skip_rules,
+            environment: base_config.environment,
+            environments: base_config.environments,
+            integrations: base_config.integrations,
+            postgrest_reload: base_config.postgrest_reload,
+            webhooks: base_config.webhooks,
+            plpgsql_check: base_config.plpgsql_check,
+            allow_tables_in_code_dir: base_config.allow_tables_in_code_dir,
+            lint: base_config.lint,
+            ignore_formatting_changes: base_config.ignore_formatting_changes,
+            preserve_privileges: base_config.preserve_privileges,
+            matview_refresh: base_config.matview_refresh,
+            partition_removal: base_config.partition_removal,
+            partitions: base_config.partitions,
+            destructive_size_limit: base_config.destructive_size_limit,
+            backup_before_destructive: base_config.backup_before_destructive,
+            backup_dir: base_config.backup_dir,
+            managed_schemas: base_config.managed_schemas,
+            ignored_schemas: base_config.ignored_schemas,
+            search_path: base_config.search_path,
+            tenants: base_config.tenants,
+            vars: base_config.vars,
+            hooks: base_config.hooks,
+            tags: None,
         }
     }
     
@@ -122,11 +498,41 @@ impl PgmgConfig {
             migrations_dir: cli_migrations_dir.or(base_config.migrations_dir),
             code_dir: base_config.code_dir,
             seed_dir: base_config.seed_dir,
+            seed_dirs: base_config.seed_dirs,
             output_graph: base_config.output_graph,
             development_mode: base_config.development_mode,
             emit_notify_events: base_config.emit_notify_events,
             check_plpgsql: base_config.check_plpgsql,
+            replication_safe: base_config.replication_safe,
+            plain_output: base_config.plain_output,
+            force: None,
             tls: base_config.tls,
+            lock_retry: base_config.lock_retry,
+            apply_lock: base_config.apply_lock,
+            skip_rules: base_config.skip_rules,
+            environment: base_config.environment,
+            environments: base_config.environments,
+            integrations: base_config.integrations,
+            postgrest_reload: base_config.postgrest_reload,
+            webhooks: base_config.webhooks,
+            plpgsql_check: base_config.plpgsql_check,
+            allow_tables_in_code_dir: base_config.allow_tables_in_code_dir,
+            lint: base_config.lint,
+            ignore_formatting_changes: base_config.ignore_formatting_changes,
+            preserve_privileges: base_config.preserve_privileges,
+            matview_refresh: base_config.matview_refresh,
+            partition_removal: base_config.partition_removal,
+            partitions: base_config.partitions,
+            destructive_size_limit: base_config.destructive_size_limit,
+            backup_before_destructive: base_config.backup_before_destructive,
+            backup_dir: base_config.backup_dir,
+            managed_schemas: base_config.managed_schemas,
+            ignored_schemas: base_config.ignored_schemas,
+            search_path: base_config.search_path,
+            tenants: base_config.tenants,
+            vars: base_config.vars,
+            hooks: base_config.hooks,
+            tags: None,
         }
     }
     
@@ -146,6 +552,53 @@ impl PgmgConfig {
         self
     }
     
+    /// The seed directory configured for an environment in `seed_dirs`
+    pub fn seed_dir_for_env(&self, env: &str) -> Result<PathBuf, String> {
+        let seed_dirs = self.seed_dirs.as_ref().filter(|dirs| !dirs.is_empty())
+            .ok_or_else(|| format!("No seed directories configured for environments. Add seed_dirs = {{ {} = \"seeds/{}\" }} to pgmg.toml", env, env))?;
+
+        seed_dirs.get(env).cloned().ok_or_else(|| format!(
+            "No seed directory configured for environment '{}'. Configured environments: {}",
+            env,
+            seed_dirs.keys().cloned().collect::<Vec<_>>().join(", ")
+        ))
+    }
+    
+    /// Limit plan and apply to objects with one of `tags`, from CLI
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        if !tags.is_empty() {
+            self.tags = Some(tags);
+        }
+        self
+    }
+    
+    /// Apply replication safety settings from CLI
+    pub fn with_replication_safety(mut self, replication_safe: bool, force: bool) -> Self {
+        if replication_safe {
+            self.replication_safe = Some(true);
+        }
+        if force {
+            self.force = Some(true);
+        }
+        self
+    }
+    
+    /// Apply lock wait settings from CLI
+    pub fn with_apply_lock(mut self, lock_timeout: Option<Duration>, no_wait: bool) -> Self {
+        if lock_timeout.is_none() && !no_wait {
+            return self;
+        }
+        let section = self.apply_lock.get_or_insert_with(ApplyLockConfigSection::default);
+        if let Some(lock_timeout) = lock_timeout {
+            section.timeout_secs = Some(lock_timeout.as_secs());
+            section.wait = Some(true);
+        }
+        if no_wait {
+            section.wait = Some(false);
+        }
+        self
+    }
+    
     /// Create a sample configuration file
     pub fn write_sample_config() -> Result<(), Box<dyn std::error::Error>> {
         let sample_config = PgmgConfig {
@@ -153,11 +606,41 @@ impl PgmgConfig {
             migrations_dir: Some(PathBuf::from("migrations")),
             code_dir: Some(PathBuf::from("sql")),
             seed_dir: Some(PathBuf::from("seeds")),
+            seed_dirs: None,
             output_graph: None,
             development_mode: Some(false),
             emit_notify_events: Some(false),
             check_plpgsql: Some(false),
+            replication_safe: Some(false),
+            plain_output: Some(false),
+            force: None,
             tls: None,
+            lock_retry: None,
+            apply_lock: None,
+            skip_rules: None,
+            environment: None,
+            environments: None,
+            integrations: None,
+            postgrest_reload: None,
+            webhooks: None,
+            plpgsql_check: None,
+            allow_tables_in_code_dir: None,
+            lint: None,
+            ignore_formatting_changes: None,
+            preserve_privileges: None,
+            matview_refresh: None,
+            partition_removal: None,
+            partitions: None,
+            destructive_size_limit: None,
+            backup_before_destructive: None,
+            backup_dir: None,
+            managed_schemas: None,
+            ignored_schemas: None,
+            search_path: None,
+            tenants: None,
+            vars: None,
+            hooks: None,
+            tags: None,
         };
         
         let content = toml::to_string_pretty(&sample_config)?;
@@ -190,6 +673,312 @@ impl PgmgConfig {
         
         Ok(tls_config)
     }
+    
+    /// Build the lock retry policy, if a `[lock_retry]` section is present
+    pub fn lock_retry_policy(&self) -> Option<LockRetryPolicy> {
+        let section = self.lock_retry.as_ref()?;
+        let defaults = LockRetryPolicy::default();
+        
+        Some(LockRetryPolicy {
+            attempts: section.attempts.unwrap_or(defaults.attempts).max(1),
+            lock_timeout: section.lock_timeout_ms.map(Duration::from_millis).unwrap_or(defaults.lock_timeout),
+            initial_backoff: section.backoff_ms.map(Duration::from_millis).unwrap_or(defaults.initial_backoff),
+            max_backoff: section.max_backoff_ms.map(Duration::from_millis).unwrap_or(defaults.max_backoff),
+        })
+    }
+    
+    /// The namespace the apply lock key is derived from
+    pub fn apply_lock_namespace(&self) -> &str {
+        self.apply_lock.as_ref()
+            .and_then(|section| section.namespace.as_deref())
+            .unwrap_or(DEFAULT_LOCK_NAMESPACE)
+    }
+    
+    /// How long apply waits for the lock: zero if `wait = false`, so a held
+    /// lock fails the apply at once
+    pub fn apply_lock_timeout(&self) -> Duration {
+        let section = self.apply_lock.clone().unwrap_or_default();
+        if section.wait == Some(false) {
+            return Duration::ZERO;
+        }
+        Duration::from_secs(section.timeout_secs.unwrap_or(30))
+    }
+    
+    /// The apply lock for the database at `connection_string`
+    pub fn apply_lock_manager(&self, connection_string: &str) -> AdvisoryLockManager {
+        AdvisoryLockManager::with_namespace(connection_string, self.apply_lock_namespace())
+    }
+    
+    /// The schemas plan and apply manage, from `managed_schemas` and `ignored_schemas`
+    pub fn schema_filter(&self) -> SchemaFilter {
+        SchemaFilter::new(
+            self.managed_schemas.clone().unwrap_or_default(),
+            self.ignored_schemas.clone().unwrap_or_default(),
+        )
+    }
+    
+    /// The schemas unqualified references in code files resolve through
+    pub fn search_path(&self) -> SearchPath {
+        SearchPath::new(self.search_path.clone().unwrap_or_default())
+    }
+    
+    /// The tenant templating set up in `[tenants]`, if any
+    pub fn tenant_template(&self) -> Option<TenantTemplate> {
+        let section = self.tenants.as_ref()?;
+        Some(TenantTemplate {
+            placeholder: section.placeholder.clone().unwrap_or_else(|| DEFAULT_TENANT_PLACEHOLDER.to_string()),
+            schemas: section.schemas.clone().unwrap_or_default(),
+            query: section.query.clone(),
+        })
+    }
+    
+    /// Variables for code files and migrations: `[vars]` overridden by the
+    /// environment profile's. None unless either is set.
+    pub fn sql_vars(&self) -> Option<SqlVars> {
+        let profile_vars = self.environment_profile().ok().flatten()
+            .and_then(|(_, profile)| profile.vars.clone());
+        if self.vars.is_none() && profile_vars.is_none() {
+            return None;
+        }
+        
+        let mut values = self.vars.clone().unwrap_or_default();
+        values.extend(profile_vars.unwrap_or_default());
+        Some(SqlVars::new(values))
+    }
+    
+    /// The hooks in `[hooks]` that run in the environment in use
+    pub fn hooks(&self) -> Hooks {
+        let environment = self.environment_name();
+        let Some(section) = &self.hooks else {
+            return Hooks { hooks: Vec::new(), environment };
+        };
+        
+        let mut hooks = Vec::new();
+        for (event, entries) in [
+            (HookEvent::PrePlan, &section.pre_plan),
+            (HookEvent::PostPlan, &section.post_plan),
+            (HookEvent::PreApply, &section.pre_apply),
+            (HookEvent::PostApply, &section.post_apply),
+            (HookEvent::PreMigration, &section.pre_migration),
+            (HookEvent::PostMigration, &section.post_migration),
+        ] {
+            for entry in entries.iter().flatten() {
+                let runs_here = entry.environments.as_ref().map_or(true, |environments| {
+                    environment.as_ref().is_some_and(|name| environments.contains(name))
+                });
+                if !runs_here {
+                    continue;
+                }
+                hooks.extend(entry.sql.clone().map(|sql| (event, HookAction::Sql(sql))));
+                hooks.extend(entry.run.clone().map(|command| (event, HookAction::Run(command))));
+            }
+        }
+        
+        Hooks { hooks, environment }
+    }
+    
+    /// The environment in use: the one named by `PGMG_ENV`, or else by
+    /// `environment` in pgmg.toml
+    pub fn environment_name(&self) -> Option<String> {
+        environment_from_env().or_else(|| self.environment.clone())
+    }
+    
+    /// The environment profile in use, for [`Self::environment_name`]
+    pub fn environment_profile(&self) -> Result<Option<(String, &EnvironmentConfigSection)>, String> {
+        let Some(name) = self.environment_name() else {
+            return Ok(None);
+        };
+        
+        let profile = self.environments.as_ref()
+            .and_then(|environments| environments.get(&name))
+            .ok_or_else(|| format!(
+                "No environment profile '{}'. Add an [environments.{}] section to pgmg.toml",
+                name, name
+            ))?;
+        Ok(Some((name, profile)))
+    }
+    
+    /// Apply the NOTIFY and plpgsql_check settings of the environment profile
+    /// in use. plpgsql_check isn't run where the profile says the extension is
+    /// missing.
+    pub fn with_environment_profile(mut self) -> Result<Self, String> {
+        let Some((_, profile)) = self.environment_profile()? else {
+            return Ok(self);
+        };
+        let profile = profile.clone();
+        
+        if profile.emit_notify_events.is_some() {
+            self.emit_notify_events = profile.emit_notify_events;
+        }
+        if profile.check_plpgsql.is_some() {
+            self.check_plpgsql = profile.check_plpgsql;
+        } else if profile.extensions.as_ref().and_then(|extensions| extensions.get("plpgsql_check")) == Some(&false) {
+            self.check_plpgsql = Some(false);
+        }
+        Ok(self)
+    }
+    
+    /// The built-in integration actions to run after an apply, in the order
+    /// they're listed
+    pub fn integration_actions(&self) -> Result<Vec<IntegrationAction>, String> {
+        let default_postgrest = PostgrestIntegrationSection::default();
+        let Some(postgrest) = self.integrations.as_ref().and_then(|integrations| integrations.postgrest.as_ref())
+            .or(self.postgrest_reload.unwrap_or(false).then_some(&default_postgrest)) else {
+            return Ok(Vec::new());
+        };
+        
+        let channel = postgrest.channel.clone().unwrap_or_else(|| "pgrst".to_string());
+        match &postgrest.actions {
+            Some(actions) => actions.iter()
+                .map(|action| IntegrationAction::postgrest(action, &channel))
+                .collect(),
+            None => Ok(vec![IntegrationAction::PostgrestReloadSchema { channel }]),
+        }
+    }
+    
+    /// The `[[webhooks]]` to send apply summaries to in the environment in use
+    pub fn webhooks(&self) -> Result<Vec<Webhook>, String> {
+        let environment = self.environment_name();
+        self.webhooks.iter().flatten()
+            .filter(|section| section.environments.as_ref().map_or(true, |environments| {
+                environment.as_ref().is_some_and(|name| environments.contains(name))
+            }))
+            .map(|section| Ok(Webhook {
+                url: section.url.clone(),
+                format: section.format.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+                headers: section.headers.clone().unwrap_or_default(),
+            }))
+            .collect()
+    }
+    
+    /// The schemas whose changes make PostgREST reload; all if empty
+    pub fn postgrest_schemas(&self) -> SchemaFilter {
+        let schemas = self.integrations.as_ref()
+            .and_then(|integrations| integrations.postgrest.as_ref())
+            .and_then(|postgrest| postgrest.schemas.clone());
+        SchemaFilter::new(schemas.unwrap_or_default(), Vec::new())
+    }
+    
+    /// Build the plpgsql_check policy from the `[plpgsql_check]` section
+    pub fn check_policy(&self) -> Result<CheckPolicy, String> {
+        let Some(section) = &self.plpgsql_check else {
+            return Ok(CheckPolicy::default());
+        };
+        
+        Ok(CheckPolicy {
+            fail_on: section.fail_on.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+            ignore: section.ignore.clone().unwrap_or_default(),
+        })
+    }
+    
+    /// How recreated materialized views are refreshed unless their file says
+    /// otherwise
+    pub fn matview_refresh_strategy(&self) -> Result<RefreshStrategy, String> {
+        self.matview_refresh.as_deref().map(str::parse).transpose().map(Option::unwrap_or_default)
+    }
+    
+    /// What happens to partitions removed from the code directory
+    pub fn partition_removal(&self) -> Result<PartitionRemoval, String> {
+        self.partition_removal.as_deref().map(str::parse).transpose().map(Option::unwrap_or_default)
+    }
+    
+    /// The tables in `[[partitions.tables]]` and the schedule they're
+    /// maintained on
+    pub fn partition_maintenance(&self) -> Result<PartitionMaintenance, String> {
+        let Some(section) = &self.partitions else {
+            return Ok(PartitionMaintenance::default());
+        };
+        
+        let tables = section.tables.iter().flatten()
+            .map(|table| Ok(MaintainedTable {
+                table: QualifiedIdent::from_qualified_name(&table.table),
+                interval: table.interval.parse()?,
+                premake: table.premake.unwrap_or(4),
+                retain: table.retain,
+                expired: match &table.expired {
+                    Some(expired) => expired.parse()?,
+                    None => self.partition_removal()?,
+                },
+            }))
+            .collect::<Result<Vec<_>, String>>()?;
+        
+        Ok(PartitionMaintenance { schedule: section.schedule.clone(), tables })
+    }
+    
+    /// Largest relation, in bytes, apply may drop or rewrite without --force
+    pub fn destructive_size_limit_bytes(&self) -> Result<Option<u64>, String> {
+        self.destructive_size_limit.as_deref().map(parse_size).transpose()
+    }
+    
+    /// Build the lint rules from the `[lint]` section
+    pub fn lint_rules(&self) -> Result<LintRules, String> {
+        let Some(section) = &self.lint else {
+            return Ok(LintRules::default());
+        };
+        
+        let disabled = section.disable.iter().flatten()
+            .map(|rule| rule.parse::<LintRule>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let naming = section.naming.iter().flatten()
+            .map(|(object_type, pattern)| {
+                let regex = regex::Regex::new(pattern)
+                    .map_err(|e| format!("Invalid naming pattern for {}: {}", object_type, e))?;
+                Ok((object_type.parse::<ObjectType>()?, regex))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let forbidden_types = match &section.forbidden_types {
+            Some(types) => types.iter().map(|name| (normalize_type_name(name), name.clone())).collect(),
+            None => LintRules::default().forbidden_types,
+        };
+        
+        Ok(LintRules { disabled, naming, forbidden_types })
+    }
+    
+    /// Build the skip rules configured in `[[skip_rules]]` sections
+    pub fn skip_rules(&self) -> Result<Vec<SkipRule>, String> {
+        let Some(sections) = &self.skip_rules else {
+            return Ok(Vec::new());
+        };
+        
+        sections.iter().enumerate().map(|(idx, section)| {
+            let object_types = section.object_types.iter().flatten()
+                .map(|object_type| object_type.parse::<ObjectType>())
+                .collect::<Result<Vec<_>, _>>()?;
+            
+            Ok(SkipRule {
+                reason: section.reason.clone().unwrap_or_else(|| format!("matched skip rule {}", idx + 1)),
+                when: section.when.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+                extensions: section.extensions.clone().unwrap_or_default(),
+                schemas: section.schemas.clone().unwrap_or_default(),
+                object_types,
+            })
+        }).collect()
+    }
+}
+
+/// The environment named by `PGMG_ENV`, for commands run without a config
+pub fn environment_from_env() -> Option<String> {
+    std::env::var("PGMG_ENV").ok().filter(|name| !name.is_empty())
+}
+
+/// Parse a size such as `500MB`, `10GB` or `1 TB`, in units of 1024 as
+/// PostgreSQL reports them. A bare number is bytes.
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (amount, unit) = size.split_at(size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len()));
+    let amount: u64 = amount.parse()
+        .map_err(|_| format!("Invalid size '{}'. Expected a number with a unit, e.g. 10GB", size))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        "TB" => 1 << 40,
+        _ => return Err(format!("Unknown size unit '{}'. Expected one of: B, kB, MB, GB, TB", unit.trim())),
+    };
+
+    amount.checked_mul(multiplier).ok_or_else(|| format!("Size '{}' is too large", size))
 }
 
 impl Default for PgmgConfig {
@@ -199,11 +988,41 @@ impl Default for PgmgConfig {
             migrations_dir: None,
             code_dir: None,
             seed_dir: None,
+            seed_dirs: None,
             output_graph: None,
             development_mode: None,
             emit_notify_events: None,
             check_plpgsql: None,
+            replication_safe: None,
+            plain_output: None,
+            force: None,
             tls: None,
+            lock_retry: None,
+            apply_lock: None,
+            skip_rules: None,
+            environment: None,
+            environments: None,
+            integrations: None,
+            postgrest_reload: None,
+            webhooks: None,
+            plpgsql_check: None,
+            allow_tables_in_code_dir: None,
+            lint: None,
+            ignore_formatting_changes: None,
+            preserve_privileges: None,
+            matview_refresh: None,
+            partition_removal: None,
+            partitions: None,
+            destructive_size_limit: None,
+            backup_before_destructive: None,
+            backup_dir: None,
+            managed_schemas: None,
+            ignored_schemas: None,
+            search_path: None,
+            tenants: None,
+            vars: None,
+            hooks: None,
+            tags: None,
         }
     }
 }
@@ -211,6 +1030,7 @@ impl Default for PgmgConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sql::SkipWhen;
     use tempfile::tempdir;
     use std::env;
 
@@ -221,11 +1041,41 @@ mod tests {
             migrations_dir: Some(PathBuf::from("migrations")),
             code_dir: Some(PathBuf::from("sql")),
             seed_dir: Some(PathBuf::from("seeds")),
+            seed_dirs: None,
             output_graph: Some(PathBuf::from("graph.dot")),
             development_mode: Some(true),
             emit_notify_events: Some(false),
             check_plpgsql: Some(true),
+            replication_safe: Some(false),
+            plain_output: None,
+            force: None,
             tls: None,
+            lock_retry: None,
+            apply_lock: None,
+            skip_rules: None,
+            environment: None,
+            environments: None,
+            integrations: None,
+            postgrest_reload: None,
+            webhooks: None,
+            plpgsql_check: None,
+            allow_tables_in_code_dir: None,
+            lint: None,
+            ignore_formatting_changes: None,
+            preserve_privileges: None,
+            matview_refresh: None,
+            partition_removal: None,
+            partitions: None,
+            destructive_size_limit: None,
+            backup_before_destructive: None,
+            backup_dir: None,
+            managed_schemas: None,
+            ignored_schemas: None,
+            search_path: None,
+            tenants: None,
+            vars: None,
+            hooks: None,
+            tags: None,
         };
         
         let toml_str = toml::to_string(&config).unwrap();
@@ -248,11 +1098,41 @@ mod tests {
             migrations_dir: Some(PathBuf::from("config_migrations")),
             code_dir: Some(PathBuf::from("config_sql")),
             seed_dir: Some(PathBuf::from("config_seeds")),
+            seed_dirs: None,
             output_graph: Some(PathBuf::from("config_graph.dot")),
             development_mode: Some(false),
             emit_notify_events: Some(true),
             check_plpgsql: Some(false),
+            replication_safe: Some(false),
+            plain_output: None,
+            force: None,
             tls: None,
+            lock_retry: None,
+            apply_lock: None,
+            skip_rules: None,
+            environment: None,
+            environments: None,
+            integrations: None,
+            postgrest_reload: None,
+            webhooks: None,
+            plpgsql_check: None,
+            allow_tables_in_code_dir: None,
+            lint: None,
+            ignore_formatting_changes: None,
+            preserve_privileges: None,
+            matview_refresh: None,
+            partition_removal: None,
+            partitions: None,
+            destructive_size_limit: None,
+            backup_before_destructive: None,
+            backup_dir: None,
+            managed_schemas: None,
+            ignored_schemas: None,
+            search_path: None,
+            tenants: None,
+            vars: None,
+            hooks: None,
+            tags: None,
         };
         
         let merged = PgmgConfig::merge_with_cli(
@@ -348,4 +1228,364 @@ code_dir = "test_sql"
         assert_eq!(config_no_dev.development_mode, None);
         assert_eq!(config_no_dev.emit_notify_events, None);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_lock_retry_policy() {
+        assert!(PgmgConfig::default().lock_retry_policy().is_none());
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [lock_retry]
+            attempts = 3
+            lock_timeout_ms = 250
+        "#}).unwrap();
+        let policy = config.lock_retry_policy().unwrap();
+        assert_eq!(policy.attempts, 3);
+        assert_eq!(policy.lock_timeout, Duration::from_millis(250));
+        assert_eq!(policy.initial_backoff, LockRetryPolicy::default().initial_backoff);
+    }
+
+    #[test]
+    fn test_apply_lock_settings() {
+        let conn = "postgres://localhost/app";
+        let default = PgmgConfig::default();
+        assert_eq!(default.apply_lock_timeout(), Duration::from_secs(30));
+        assert_eq!(default.apply_lock_manager(conn).lock_key(), AdvisoryLockManager::new(conn).lock_key());
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [apply_lock]
+            namespace = "billing"
+            timeout_secs = 120
+        "#}).unwrap();
+        assert_eq!(config.apply_lock_namespace(), "billing");
+        assert_eq!(config.apply_lock_timeout(), Duration::from_secs(120));
+        assert_ne!(config.apply_lock_manager(conn).lock_key(), default.apply_lock_manager(conn).lock_key());
+        
+        // CLI flags override the file
+        let no_wait = config.clone().with_apply_lock(None, true);
+        assert_eq!(no_wait.apply_lock_timeout(), Duration::ZERO);
+        assert_eq!(no_wait.apply_lock_namespace(), "billing");
+        let shorter = config.with_apply_lock(Some(Duration::from_secs(5)), false);
+        assert_eq!(shorter.apply_lock_timeout(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_tenant_template() {
+        assert!(PgmgConfig::default().tenant_template().is_none());
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [tenants]
+            schemas = ["acme", "globex"]
+            query = "SELECT schema_name FROM app.tenants"
+        "#}).unwrap();
+        let template = config.tenant_template().unwrap();
+        assert_eq!(template.placeholder, "__tenant__");
+        assert_eq!(template.schemas, vec!["acme", "globex"]);
+        assert_eq!(template.query.as_deref(), Some("SELECT schema_name FROM app.tenants"));
+    }
+
+    #[test]
+    fn test_skip_rules() {
+        assert!(PgmgConfig::default().skip_rules().unwrap().is_empty());
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [[skip_rules]]
+            reason = "timescaledb is not installed in CI"
+            when = "test"
+            extensions = ["timescaledb"]
+            
+            [[skip_rules]]
+            schemas = ["reporting"]
+            object_types = ["materialized_view"]
+        "#}).unwrap();
+        let rules = config.skip_rules().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].when, SkipWhen::TestMode);
+        assert_eq!(rules[0].extensions, vec!["timescaledb".to_string()]);
+        assert_eq!(rules[1].when, SkipWhen::Always);
+        assert_eq!(rules[1].reason, "matched skip rule 2");
+        assert_eq!(rules[1].object_types, vec![ObjectType::MaterializedView]);
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [[skip_rules]]
+            when = "staging"
+        "#}).unwrap();
+        assert!(config.skip_rules().is_err());
+    }
+
+    #[test]
+    fn test_seed_dirs_per_environment() {
+        let config: PgmgConfig = toml::from_str(r#"
+            seed_dir = "seeds"
+            seed_dirs = { dev = "seeds/dev", test = "seeds/test" }
+        "#).unwrap();
+
+        assert_eq!(config.seed_dir_for_env("test").unwrap(), PathBuf::from("seeds/test"));
+
+        let err = config.seed_dir_for_env("prod").unwrap_err();
+        assert!(err.contains("dev, test"), "{}", err);
+
+        assert!(PgmgConfig::default().seed_dir_for_env("dev").is_err());
+    }
+
+    #[test]
+    fn test_environment_profile() {
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            environment = "ci"
+            emit_notify_events = true
+            check_plpgsql = true
+            
+            [environments.ci]
+            rds = false
+            emit_notify_events = false
+            extensions = { pg_cron = false, plpgsql_check = false }
+            
+            [environments.production]
+            rds = true
+        "#}).unwrap();
+        
+        let (name, profile) = config.environment_profile().unwrap().unwrap();
+        assert_eq!(name, "ci");
+        assert_eq!(profile.rds, Some(false));
+        assert_eq!(profile.extensions.as_ref().unwrap().get("pg_cron"), Some(&false));
+        
+        let config = config.with_environment_profile().unwrap();
+        assert_eq!(config.emit_notify_events, Some(false));
+        // plpgsql_check is missing in CI
+        assert_eq!(config.check_plpgsql, Some(false));
+        
+        let config = PgmgConfig { environment: Some("staging".to_string()), ..config };
+        assert!(config.environment_profile().is_err());
+        
+        assert!(PgmgConfig::default().environment_profile().unwrap().is_none());
+    }
+    
+    #[test]
+    fn test_sql_vars() {
+        assert!(PgmgConfig::default().sql_vars().is_none());
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            environment = "ci"
+            
+            [vars]
+            FDW_HOST = "localhost"
+            APP_ROLE = "web"
+            
+            [environments.ci]
+            vars = { FDW_HOST = "reporting.ci.internal" }
+        "#}).unwrap();
+        
+        let vars = config.sql_vars().unwrap();
+        assert_eq!(vars.values.get("FDW_HOST").map(String::as_str), Some("reporting.ci.internal"));
+        assert_eq!(vars.values.get("APP_ROLE").map(String::as_str), Some("web"));
+    }
+    
+    #[test]
+    fn test_hooks() {
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            environment = "production"
+            
+            [hooks]
+            post_apply = [
+                { sql = "NOTIFY pgrst, 'reload schema'" },
+                { run = "./scripts/page-slack.sh", environments = ["production"] },
+                { run = "./scripts/seed-demo.sh", environments = ["staging"] },
+            ]
+            pre_migration = [{ sql = "SET lock_timeout = '5s'" }]
+        "#}).unwrap();
+        
+        let hooks = config.hooks();
+        assert_eq!(hooks.environment.as_deref(), Some("production"));
+        assert_eq!(hooks.hooks, vec![
+            (HookEvent::PostApply, HookAction::Sql("NOTIFY pgrst, 'reload schema'".to_string())),
+            (HookEvent::PostApply, HookAction::Run("./scripts/page-slack.sh".to_string())),
+            (HookEvent::PreMigration, HookAction::Sql("SET lock_timeout = '5s'".to_string())),
+        ]);
+        
+        assert!(PgmgConfig::default().hooks().is_empty());
+    }
+    
+    #[test]
+    fn test_integration_actions() {
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [integrations.postgrest]
+            actions = ["reload_schema", "reload_config"]
+            channel = "api"
+        "#}).unwrap();
+        assert_eq!(config.integration_actions().unwrap(), vec![
+            IntegrationAction::PostgrestReloadSchema { channel: "api".to_string() },
+            IntegrationAction::PostgrestReloadConfig { channel: "api".to_string() },
+        ]);
+        
+        let config: PgmgConfig = toml::from_str("[integrations.postgrest]").unwrap();
+        assert_eq!(config.integration_actions().unwrap(), vec![
+            IntegrationAction::PostgrestReloadSchema { channel: "pgrst".to_string() },
+        ]);
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [integrations.postgrest]
+            actions = ["restart"]
+        "#}).unwrap();
+        assert!(config.integration_actions().is_err());
+        
+        assert!(PgmgConfig::default().integration_actions().unwrap().is_empty());
+        
+        let config: PgmgConfig = toml::from_str("postgrest_reload = true").unwrap();
+        assert_eq!(config.integration_actions().unwrap(), vec![
+            IntegrationAction::PostgrestReloadSchema { channel: "pgrst".to_string() },
+        ]);
+        assert!(config.postgrest_schemas().is_empty());
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [integrations.postgrest]
+            schemas = ["api"]
+        "#}).unwrap();
+        assert_eq!(config.postgrest_schemas().managed, vec!["api"]);
+    }
+    
+    #[test]
+    fn test_webhooks() {
+        use crate::notify::webhook::WebhookFormat;
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            environment = "staging"
+            
+            [[webhooks]]
+            url = "https://hooks.slack.com/services/T000/B000/XXXX"
+            format = "slack"
+            environments = ["production"]
+            
+            [[webhooks]]
+            url = "https://deploys.internal/pgmg"
+            headers = { Authorization = "Bearer abc" }
+        "#}).unwrap();
+        
+        let webhooks = config.webhooks().unwrap();
+        assert_eq!(webhooks.len(), 1);
+        assert_eq!(webhooks[0].url, "https://deploys.internal/pgmg");
+        assert_eq!(webhooks[0].format, WebhookFormat::Json);
+        
+        let config = PgmgConfig { environment: Some("production".to_string()), ..config };
+        assert_eq!(config.webhooks().unwrap()[0].format, WebhookFormat::Slack);
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [[webhooks]]
+            url = "https://deploys.internal/pgmg"
+            format = "teams"
+        "#}).unwrap();
+        assert!(config.webhooks().is_err());
+    }
+    
+    #[test]
+    fn test_check_policy() {
+        use crate::plpgsql_check::CheckSeverity;
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [plpgsql_check]
+            fail_on = "warning"
+            ignore = ["42P01"]
+        "#}).unwrap();
+        let policy = config.check_policy().unwrap();
+        assert_eq!(policy.fail_on, CheckSeverity::Warning);
+        assert_eq!(policy.ignore, vec!["42P01".to_string()]);
+        
+        assert_eq!(PgmgConfig::default().check_policy().unwrap().fail_on, CheckSeverity::Error);
+        
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [plpgsql_check]
+            fail_on = "notice"
+        "#}).unwrap();
+        assert!(config.check_policy().is_err());
+    }
+    
+    #[test]
+    fn test_lint_rules() {
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [lint]
+            disable = ["schema-qualified"]
+            naming = { view = "^v_", materialized_view = "^mv_" }
+            forbidden_types = ["timestamp without time zone", "json"]
+        "#}).unwrap();
+        let rules = config.lint_rules().unwrap();
+        assert_eq!(rules.disabled, vec![LintRule::SchemaQualified]);
+        assert_eq!(rules.naming.len(), 2);
+        assert!(rules.naming.iter().any(|(object_type, pattern)| *object_type == ObjectType::View && pattern.as_str() == "^v_"));
+        assert_eq!(rules.forbidden_types[0], ("timestamp".to_string(), "timestamp without time zone".to_string()));
+        
+        assert_eq!(PgmgConfig::default().lint_rules().unwrap().forbidden_types.len(), 1);
+        
+        let config: PgmgConfig = toml::from_str("[lint]\ndisable = [\"no-tabs\"]").unwrap();
+        assert!(config.lint_rules().is_err());
+    }
+
+    #[test]
+    fn test_matview_refresh_strategy() {
+        assert_eq!(PgmgConfig::default().matview_refresh_strategy(), Ok(RefreshStrategy::Skip));
+
+        let config: PgmgConfig = toml::from_str(r#"matview_refresh = "concurrently""#).unwrap();
+        assert_eq!(config.matview_refresh_strategy(), Ok(RefreshStrategy::Concurrently));
+
+        let config: PgmgConfig = toml::from_str(r#"matview_refresh = "always""#).unwrap();
+        assert!(config.matview_refresh_strategy().is_err());
+    }
+
+    #[test]
+    fn test_partition_removal() {
+        assert_eq!(PgmgConfig::default().partition_removal(), Ok(PartitionRemoval::Detach));
+
+        let config: PgmgConfig = toml::from_str(r#"partition_removal = "drop""#).unwrap();
+        assert_eq!(config.partition_removal(), Ok(PartitionRemoval::Drop));
+
+        let config: PgmgConfig = toml::from_str(r#"partition_removal = "truncate""#).unwrap();
+        assert!(config.partition_removal().is_err());
+    }
+
+    #[test]
+    fn test_partition_maintenance() {
+        assert!(PgmgConfig::default().partition_maintenance().unwrap().tables.is_empty());
+
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            partition_removal = "drop"
+
+            [partitions]
+            schedule = "0 3 * * *"
+
+            [[partitions.tables]]
+            table = "api.events"
+            interval = "month"
+            retain = 12
+
+            [[partitions.tables]]
+            table = "metrics"
+            interval = "day"
+            premake = 7
+            expired = "detach"
+        "#}).unwrap();
+        let maintenance = config.partition_maintenance().unwrap();
+        assert_eq!(maintenance.schedule.as_deref(), Some("0 3 * * *"));
+        assert_eq!(maintenance.tables[0].table, QualifiedIdent::from_qualified_name("api.events"));
+        assert_eq!(maintenance.tables[0].premake, 4);
+        assert_eq!(maintenance.tables[0].expired, PartitionRemoval::Drop);
+        assert_eq!(maintenance.tables[1].retain, None);
+        assert_eq!(maintenance.tables[1].expired, PartitionRemoval::Detach);
+
+        let config: PgmgConfig = toml::from_str(indoc::indoc! {r#"
+            [[partitions.tables]]
+            table = "api.events"
+            interval = "hour"
+        "#}).unwrap();
+        assert!(config.partition_maintenance().is_err());
+    }
+
+    #[test]
+    fn test_destructive_size_limit() {
+        assert_eq!(PgmgConfig::default().destructive_size_limit_bytes(), Ok(None));
+
+        let config: PgmgConfig = toml::from_str(r#"destructive_size_limit = "10GB""#).unwrap();
+        assert_eq!(config.destructive_size_limit_bytes(), Ok(Some(10 * 1024 * 1024 * 1024)));
+
+        assert_eq!(parse_size("512 kB"), Ok(512 * 1024));
+        assert_eq!(parse_size("2048"), Ok(2048));
+        assert!(parse_size("10 GiB").is_err());
+        assert!(parse_size("GB").is_err());
+    }
+}
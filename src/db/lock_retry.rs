@@ -0,0 +1,261 @@
+use std::time::Duration;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::GenericClient;
+use tracing::{debug, warn};
+use crate::logging::output;
+
+/// How often and how long to retry DDL that can't get its locks
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockRetryPolicy {
+    /// Total tries per statement, including the first
+    pub attempts: u32,
+    /// How long one try may wait for a lock (sets `lock_timeout`)
+    pub lock_timeout: Duration,
+    /// Delay before the first retry; doubled after each further try
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for LockRetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            lock_timeout: Duration::from_secs(5),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl LockRetryPolicy {
+    /// Delay after the given (1-based) failed attempt
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// A session holding a lock on a relation that a blocked statement needs
+#[derive(Debug, Clone)]
+pub struct LockHolder {
+    pub pid: i32,
+    pub user: String,
+    pub application_name: String,
+    pub state: String,
+    pub relation: String,
+    pub mode: String,
+    /// Seconds since the holder's transaction started
+    pub transaction_age: f64,
+    pub query: String,
+}
+
+impl LockHolder {
+    pub fn describe(&self) -> String {
+        let application = if self.application_name.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", self.application_name)
+        };
+        format!(
+            "pid {} user {}{} holds {} on {}, {} for {:.0}s: {}",
+            self.pid, self.user, application, self.mode, self.relation,
+            self.state, self.transaction_age, self.query
+        )
+    }
+}
+
+/// Runs object DDL, retrying statements that time out waiting for locks when a
+/// policy is set. Without a policy statements run once, as they always have.
+#[derive(Debug, Clone, Default)]
+pub struct LockRetry {
+    policy: Option<LockRetryPolicy>,
+    in_transaction: bool,
+}
+
+impl LockRetry {
+    /// `in_transaction` says whether statements run inside a transaction block,
+    /// in which case each try runs under a savepoint so a lock timeout doesn't
+    /// abort the transaction.
+    pub fn new(policy: Option<LockRetryPolicy>, in_transaction: bool) -> Self {
+        Self { policy, in_transaction }
+    }
+
+    /// Execute `sql`. `relations` are the tables and views the statement locks,
+    /// used to report who is holding them.
+    pub async fn execute<C: GenericClient>(
+        &self,
+        client: &C,
+        sql: &str,
+        relations: &[String],
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => return Ok(client.execute(sql, &[]).await?),
+        };
+
+        let previous_timeout: String = client.query_one("SELECT current_setting('lock_timeout')", &[]).await?.get(0);
+        let lock_timeout = format!("{}ms", policy.lock_timeout.as_millis());
+
+        let mut attempt = 1;
+        loop {
+            if self.in_transaction {
+                client.execute("SAVEPOINT lock_retry", &[]).await?;
+            }
+            set_lock_timeout(client, &lock_timeout, self.in_transaction).await?;
+
+            let result = client.execute(sql, &[]).await;
+
+            match result {
+                Err(e) if e.code() == Some(&SqlState::LOCK_NOT_AVAILABLE) && attempt < policy.attempts => {
+                    // Rolling back to the savepoint also restores lock_timeout
+                    if self.in_transaction {
+                        client.execute("ROLLBACK TO SAVEPOINT lock_retry", &[]).await?;
+                        client.execute("RELEASE SAVEPOINT lock_retry", &[]).await?;
+                    } else {
+                        set_lock_timeout(client, &previous_timeout, false).await?;
+                    }
+
+                    let delay = policy.backoff(attempt);
+                    report_lock_holders(client, sql, relations).await;
+                    output::warning(format!(
+                        "Waiting for locks (attempt {} of {}), retrying in {:.1}s",
+                        attempt, policy.attempts, delay.as_secs_f64()
+                    ));
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) if e.code() == Some(&SqlState::LOCK_NOT_AVAILABLE) => {
+                    // The holders can only be looked up once the aborted
+                    // statement is rolled back
+                    if self.in_transaction {
+                        client.execute("ROLLBACK TO SAVEPOINT lock_retry", &[]).await?;
+                        client.execute("RELEASE SAVEPOINT lock_retry", &[]).await?;
+                    } else {
+                        set_lock_timeout(client, &previous_timeout, false).await?;
+                    }
+
+                    let holders = report_lock_holders(client, sql, relations).await;
+                    if holders.is_empty() {
+                        return Err(e.into());
+                    }
+                    let blockers: Vec<String> = holders.iter().map(LockHolder::describe).collect();
+                    return Err(format!("{}; blocked by {}", e, blockers.join("; ")).into());
+                }
+                Err(e) => {
+                    // Inside a transaction the caller rolls back; the savepoint
+                    // and setting go with it
+                    if !self.in_transaction {
+                        set_lock_timeout(client, &previous_timeout, false).await?;
+                    }
+                    return Err(e.into());
+                }
+                Ok(rows) => {
+                    if self.in_transaction {
+                        set_lock_timeout(client, &previous_timeout, true).await?;
+                        client.execute("RELEASE SAVEPOINT lock_retry", &[]).await?;
+                    } else {
+                        set_lock_timeout(client, &previous_timeout, false).await?;
+                    }
+                    return Ok(rows);
+                }
+            }
+        }
+    }
+}
+
+async fn set_lock_timeout<C: GenericClient>(
+    client: &C,
+    value: &str,
+    local: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client.execute("SELECT set_config('lock_timeout', $1, $2)", &[&value, &local]).await?;
+    Ok(())
+}
+
+/// Log the sessions holding locks on `relations` and return them. Best
+/// effort: failures to read the activity views are only logged at debug level.
+async fn report_lock_holders<C: GenericClient>(client: &C, sql: &str, relations: &[String]) -> Vec<LockHolder> {
+    let statement = sql.lines().next().unwrap_or_default().trim();
+
+    match find_lock_holders(client, relations).await {
+        Ok(holders) if holders.is_empty() => {
+            warn!(statement = %statement, "Statement timed out waiting for a lock; the holder has already released it");
+            holders
+        }
+        Ok(holders) => {
+            warn!(statement = %statement, holders = holders.len(), "Statement timed out waiting for a lock");
+            for holder in &holders {
+                output::line(format!("  blocked by {}", holder.describe()));
+            }
+            holders
+        }
+        Err(e) => {
+            warn!(statement = %statement, "Statement timed out waiting for a lock");
+            debug!(error = %e, "Could not look up lock holders");
+            Vec::new()
+        }
+    }
+}
+
+/// Find other sessions holding locks on the given relations, longest-running
+/// transaction first. Names that don't resolve to a relation are ignored.
+pub async fn find_lock_holders<C: GenericClient>(
+    client: &C,
+    relations: &[String],
+) -> Result<Vec<LockHolder>, Box<dyn std::error::Error>> {
+    let rows = client.query(
+        "SELECT DISTINCT a.pid,
+                coalesce(a.usename::text, ''),
+                coalesce(a.application_name, ''),
+                coalesce(a.state, ''),
+                l.relation::regclass::text,
+                l.mode,
+                coalesce(extract(epoch FROM now() - a.xact_start)::float8, 0),
+                left(coalesce(a.query, ''), 200)
+         FROM pg_locks l
+         JOIN pg_stat_activity a ON a.pid = l.pid
+         WHERE l.granted
+           AND l.pid <> pg_backend_pid()
+           AND l.relation IN (
+               SELECT to_regclass(name)::oid FROM unnest($1::text[]) AS name
+               WHERE to_regclass(name) IS NOT NULL
+           )
+         ORDER BY 7 DESC",
+        &[&relations],
+    ).await?;
+
+    Ok(rows.iter()
+        .map(|row| LockHolder {
+            pid: row.get(0),
+            user: row.get(1),
+            application_name: row.get(2),
+            state: row.get(3),
+            relation: row.get(4),
+            mode: row.get(5),
+            transaction_age: row.get(6),
+            query: row.get(7),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = LockRetryPolicy {
+            attempts: 10,
+            lock_timeout: Duration::from_secs(1),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(3));
+        assert_eq!(policy.backoff(40), Duration::from_secs(3));
+    }
+}
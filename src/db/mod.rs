@@ -4,10 +4,18 @@ pub mod scanner;
 pub mod tls;
 pub mod locks;
 pub mod test_utils;
+pub mod usage;
+pub mod replication;
+pub mod lock_retry;
+pub mod snapshot;
 
-pub use state::{StateManager, MigrationRecord, ObjectRecord};
+pub use state::{StateManager, migration_content_hash, operator_signature, MigrationRecord, ObjectRecord, DependencyRecord, NewRun, RunChanges, RunRecord, StateUpgrade, STATE_UPGRADES, STATE_SCHEMA_VERSION};
 pub use connection::{DatabaseConfig, connect_to_database, connect_with_url, ManagedConnection};
-pub use scanner::{scan_sql_files, scan_migrations, MigrationFile};
+pub use scanner::{scan_sql_files, scan_sql_files_with_options, ScanOptions, scan_migrations, MigrationFile};
 pub use tls::{TlsMode, TlsConfig, PgConnection};
-pub use locks::{AdvisoryLockManager, AdvisoryLockError};
-pub use test_utils::{TestDatabase, parse_connection_string, ConnectionComponents};
\ No newline at end of file
+pub use locks::{AdvisoryLockManager, AdvisoryLockError, AdvisoryLockHolder, DEFAULT_LOCK_NAMESPACE};
+pub use test_utils::{TestDatabase, parse_connection_string, ConnectionComponents, gc_test_databases, StaleTestDatabase};
+pub use usage::{ObjectUsage, collect_object_usage, is_pg_stat_statements_available};
+pub use replication::{ReplicatedTable, ReplicationConflict, get_replicated_tables, find_replication_conflicts, ReplicaExpectation, ReplicaVerification, collect_replica_expectation, wait_for_replica};
+pub use lock_retry::{LockRetry, LockRetryPolicy, LockHolder, find_lock_holders};
+pub use snapshot::{StateSnapshot, StoredDependency, OwnedSequence, EnumType, SNAPSHOT_FORMAT_VERSION};
@@ -0,0 +1,345 @@
+use std::time::{Duration, Instant};
+use tokio_postgres::Client;
+use tracing::debug;
+use crate::db::connect_with_url;
+use crate::sql::{QualifiedIdent, ReplicationHazard};
+
+/// A table taking part in logical replication, either published to an
+/// existing logical slot or received from a subscription
+#[derive(Debug, Clone)]
+pub struct ReplicatedTable {
+    pub schema: String,
+    pub table: String,
+    /// e.g. "publication orders_pub" or "subscription orders_sub"
+    pub via: String,
+}
+
+/// A pending migration statement that would affect a replicated table
+#[derive(Debug, Clone)]
+pub struct ReplicationConflict {
+    pub migration: String,
+    pub table: String,
+    pub operation: String,
+    pub via: String,
+    pub requires_resync: bool,
+}
+
+impl ReplicationConflict {
+    pub fn describe(&self) -> String {
+        let consequence = if self.requires_resync {
+            "subscribers will need to re-snapshot the table"
+        } else {
+            "subscriber tables must be changed to match first"
+        };
+        format!(
+            "{}: {} on {} ({}); {}",
+            self.migration, self.operation, self.table, self.via, consequence
+        )
+    }
+}
+
+/// List tables that are part of logical replication.
+///
+/// Published tables only count when at least one logical replication slot
+/// exists, since otherwise nothing is consuming the publication.
+pub async fn get_replicated_tables(client: &Client) -> Result<Vec<ReplicatedTable>, Box<dyn std::error::Error>> {
+    let mut tables = Vec::new();
+
+    let slot_count: i64 = client.query_one(
+        "SELECT count(*) FROM pg_replication_slots WHERE slot_type = 'logical'",
+        &[],
+    ).await?.get(0);
+    debug!("Found {} logical replication slots", slot_count);
+
+    if slot_count > 0 {
+        let rows = client.query(
+            "SELECT schemaname::text, tablename::text, pubname::text FROM pg_publication_tables",
+            &[],
+        ).await?;
+        tables.extend(rows.iter().map(|row| ReplicatedTable {
+            schema: row.get(0),
+            table: row.get(1),
+            via: format!("publication {}", row.get::<_, String>(2)),
+        }));
+    }
+
+    let rows = client.query(
+        "SELECT n.nspname::text, c.relname::text, s.subname::text
+         FROM pg_subscription_rel sr
+         JOIN pg_subscription s ON s.oid = sr.srsubid
+         JOIN pg_class c ON c.oid = sr.srrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE s.subenabled",
+        &[],
+    ).await?;
+    tables.extend(rows.iter().map(|row| ReplicatedTable {
+        schema: row.get(0),
+        table: row.get(1),
+        via: format!("subscription {}", row.get::<_, String>(2)),
+    }));
+
+    Ok(tables)
+}
+
+/// Match migration hazards against replicated tables. Unqualified table names
+/// are assumed to be in the public schema.
+pub fn find_replication_conflicts(
+    hazards: &[(String, ReplicationHazard)],
+    replicated: &[ReplicatedTable],
+) -> Vec<ReplicationConflict> {
+    let mut conflicts = Vec::new();
+
+    for (migration, hazard) in hazards {
+        for table in replicated.iter().filter(|table| matches_table(&hazard.table, table)) {
+            conflicts.push(ReplicationConflict {
+                migration: migration.clone(),
+                table: format!("{}.{}", table.schema, table.table),
+                operation: hazard.operation.clone(),
+                via: table.via.clone(),
+                requires_resync: hazard.requires_resync,
+            });
+        }
+    }
+
+    conflicts
+}
+
+fn matches_table(ident: &QualifiedIdent, table: &ReplicatedTable) -> bool {
+    ident.name == table.table && ident.schema.as_deref().unwrap_or("public") == table.schema
+}
+
+/// A pgmg_state row the replica must have before it counts as caught up
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedObject {
+    pub object_type: String,
+    pub object_name: String,
+    pub ddl_hash: String,
+}
+
+/// What a replica must show after an apply: the applied migrations, and the
+/// state rows (and catalog entries) of every created or updated object
+#[derive(Debug, Clone, Default)]
+pub struct ReplicaExpectation {
+    pub migrations: Vec<String>,
+    pub objects: Vec<ExpectedObject>,
+}
+
+#[derive(Debug)]
+pub struct ReplicaVerification {
+    pub elapsed: Duration,
+    pub attempts: usize,
+}
+
+/// Read the state rows for the given objects from the primary
+pub async fn collect_replica_expectation(
+    primary: &Client,
+    migrations: &[String],
+    object_names: &[String],
+) -> Result<ReplicaExpectation, Box<dyn std::error::Error>> {
+    let rows = primary.query(
+        "SELECT object_type, object_name, ddl_hash FROM pgmg.pgmg_state WHERE object_name = ANY($1)",
+        &[&object_names],
+    ).await?;
+
+    Ok(ReplicaExpectation {
+        migrations: migrations.to_vec(),
+        objects: rows.iter()
+            .map(|row| ExpectedObject {
+                object_type: row.get(0),
+                object_name: row.get(1),
+                ddl_hash: row.get(2),
+            })
+            .collect(),
+    })
+}
+
+/// Poll the replica until everything in `expectation` is visible, or fail
+/// once `timeout` has passed
+pub async fn wait_for_replica(
+    replica_url: &str,
+    expectation: &ReplicaExpectation,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<ReplicaVerification, Box<dyn std::error::Error>> {
+    let (client, connection) = connect_with_url(replica_url).await?;
+    connection.spawn();
+
+    let start = Instant::now();
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let missing = missing_on_replica(&client, expectation).await?;
+        if missing.is_empty() {
+            return Ok(ReplicaVerification { elapsed: start.elapsed(), attempts });
+        }
+
+        debug!("Replica check {}: {} items not yet visible", attempts, missing.len());
+
+        if start.elapsed() >= timeout {
+            return Err(format!(
+                "Replica did not catch up within {} seconds. Still missing:\n  - {}",
+                timeout.as_secs(),
+                missing.join("\n  - ")
+            ).into());
+        }
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Describe everything in `expectation` that the replica doesn't show yet
+async fn missing_on_replica(
+    client: &Client,
+    expectation: &ReplicaExpectation,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut missing = Vec::new();
+
+    // The state tables may not exist on the replica yet after a first deploy
+    let has_state: bool = client.query_one(
+        "SELECT to_regclass('pgmg.pgmg_state') IS NOT NULL AND to_regclass('pgmg.pgmg_migrations') IS NOT NULL",
+        &[],
+    ).await?.get(0);
+    if !has_state {
+        missing.push("pgmg state tables".to_string());
+        return Ok(missing);
+    }
+
+    if !expectation.migrations.is_empty() {
+        let rows = client.query(
+            "SELECT name FROM pgmg.pgmg_migrations WHERE name = ANY($1)",
+            &[&expectation.migrations],
+        ).await?;
+        let present: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+        missing.extend(expectation.migrations.iter()
+            .filter(|name| !present.contains(name))
+            .map(|name| format!("migration {}", name)));
+    }
+
+    if !expectation.objects.is_empty() {
+        let names: Vec<&str> = expectation.objects.iter().map(|obj| obj.object_name.as_str()).collect();
+        let rows = client.query(
+            "SELECT object_type, object_name, ddl_hash FROM pgmg.pgmg_state WHERE object_name = ANY($1)",
+            &[&names],
+        ).await?;
+        let present: Vec<ExpectedObject> = rows.iter()
+            .map(|row| ExpectedObject {
+                object_type: row.get(0),
+                object_name: row.get(1),
+                ddl_hash: row.get(2),
+            })
+            .collect();
+
+        for expected in &expectation.objects {
+            if !present.contains(expected) {
+                missing.push(format!("state of {} {}", expected.object_type, expected.object_name));
+            } else if !object_exists(client, expected).await? {
+                missing.push(format!("{} {}", expected.object_type, expected.object_name));
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Check the catalog for the object itself. Object types without a simple
+/// catalog lookup are taken as present once their state row is.
+async fn object_exists(client: &Client, object: &ExpectedObject) -> Result<bool, Box<dyn std::error::Error>> {
+    let query = match object.object_type.as_str() {
+        "table" | "view" | "materialized_view" => "SELECT to_regclass($1) IS NOT NULL",
+        "type" | "domain" => "SELECT to_regtype($1) IS NOT NULL",
+        "statistics" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_statistic_ext s
+                JOIN pg_namespace n ON n.oid = s.stxnamespace
+                WHERE n.nspname || '.' || s.stxname = $1 OR s.stxname = $1
+            )"
+        }
+        "extension" => "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = $1)",
+        "publication" => "SELECT EXISTS (SELECT 1 FROM pg_publication WHERE pubname = $1)",
+        "collation" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_collation c
+                JOIN pg_namespace n ON n.oid = c.collnamespace
+                WHERE n.nspname || '.' || c.collname = $1 OR c.collname = $1
+            )"
+        }
+        "text_search_configuration" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_ts_config c
+                JOIN pg_namespace n ON n.oid = c.cfgnamespace
+                WHERE n.nspname || '.' || c.cfgname = $1 OR c.cfgname = $1
+            )"
+        }
+        "text_search_dictionary" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_ts_dict d
+                JOIN pg_namespace n ON n.oid = d.dictnamespace
+                WHERE n.nspname || '.' || d.dictname = $1 OR d.dictname = $1
+            )"
+        }
+        "operator_class" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_opclass c
+                JOIN pg_namespace n ON n.oid = c.opcnamespace
+                WHERE n.nspname || '.' || c.opcname = $1 OR c.opcname = $1
+            )"
+        }
+        "operator_family" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_opfamily f
+                JOIN pg_namespace n ON n.oid = f.opfnamespace
+                WHERE n.nspname || '.' || f.opfname = $1 OR f.opfname = $1
+            )"
+        }
+        "function" | "procedure" | "aggregate" => {
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_proc p
+                JOIN pg_namespace n ON n.oid = p.pronamespace
+                WHERE n.nspname || '.' || p.proname = $1 OR p.proname = $1
+            )"
+        }
+        _ => return Ok(true),
+    };
+
+    Ok(client.query_one(query, &[&object.object_name]).await?.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_replication_conflicts() {
+        let hazards = vec![
+            ("001_orders".to_string(), ReplicationHazard {
+                table: QualifiedIdent::from_name("orders".to_string()),
+                operation: "column type change".to_string(),
+                requires_resync: true,
+            }),
+            ("002_users".to_string(), ReplicationHazard {
+                table: QualifiedIdent::from_qualified_name("api.users"),
+                operation: "column drop".to_string(),
+                requires_resync: false,
+            }),
+        ];
+        let replicated = vec![
+            ReplicatedTable {
+                schema: "public".to_string(),
+                table: "orders".to_string(),
+                via: "publication analytics".to_string(),
+            },
+            ReplicatedTable {
+                schema: "public".to_string(),
+                table: "users".to_string(),
+                via: "publication analytics".to_string(),
+            },
+        ];
+
+        let conflicts = find_replication_conflicts(&hazards, &replicated);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].table, "public.orders");
+        assert!(conflicts[0].describe().contains("re-snapshot"));
+    }
+}
@@ -1,8 +1,11 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fs;
-use crate::sql::{SqlObject, splitter::split_sql_file, objects::identify_sql_object};
+use crate::sql::{SqlObject, ObjectType, analyze_statement, splitter::split_sql_file, objects::{identify_sql_object, identify_object_setting, extract_apply_after_hints}};
+use crate::sql::directives::{parse_object_directives, owner_statement};
+use crate::sql::{substitute_vars, SqlVars, TenantTemplate};
 use crate::BuiltinCatalog;
 use pg_query;
+use tracing::warn;
 
 /// Scan a directory for .sql files and parse them into SQL objects
 pub async fn scan_sql_files(
@@ -11,7 +14,29 @@ pub async fn scan_sql_files(
 ) -> Result<Vec<SqlObject>, Box<dyn std::error::Error>> {
     let mut sql_objects = Vec::new();
     
-    scan_directory_recursive(directory, &mut sql_objects, builtin_catalog, directory)?;
+    scan_directory_recursive(directory, &mut sql_objects, builtin_catalog, directory, &ScanOptions::default())?;
+    
+    Ok(sql_objects)
+}
+
+/// How file content is rewritten before it's parsed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanOptions<'a> {
+    /// Files using the tenant placeholder are parsed once per tenant schema
+    pub tenants: Option<&'a TenantTemplate>,
+    /// `${NAME}` variables are substituted, failing the scan if one is undefined
+    pub vars: Option<&'a SqlVars>,
+}
+
+/// Like [`scan_sql_files`], substituting variables and expanding tenant files
+pub async fn scan_sql_files_with_options(
+    directory: &Path,
+    builtin_catalog: &BuiltinCatalog,
+    options: &ScanOptions<'_>,
+) -> Result<Vec<SqlObject>, Box<dyn std::error::Error>> {
+    let mut sql_objects = Vec::new();
+    
+    scan_directory_recursive(directory, &mut sql_objects, builtin_catalog, directory, options)?;
     
     Ok(sql_objects)
 }
@@ -21,6 +46,7 @@ fn scan_directory_recursive(
     sql_objects: &mut Vec<SqlObject>,
     builtin_catalog: &BuiltinCatalog,
     _base_path: &Path,
+    options: &ScanOptions<'_>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let entries = fs::read_dir(dir)?;
     
@@ -36,7 +62,7 @@ fn scan_directory_recursive(
         
         if path.is_dir() {
             // Recursively scan subdirectories
-            scan_directory_recursive(&path, sql_objects, builtin_catalog, _base_path)?;
+            scan_directory_recursive(&path, sql_objects, builtin_catalog, _base_path, options)?;
         } else if path.extension().and_then(|s| s.to_str()) == Some("sql") {
             // Skip test files - they should not be treated as database objects
             if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
@@ -45,9 +71,20 @@ fn scan_directory_recursive(
                 }
             }
             
+            // Read .sql files. An undefined variable fails the scan, as
+            // leaving the file out would drop its objects.
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!(file = %path.display(), error = %e, "Failed to read SQL file");
+                    continue;
+                }
+            };
+            let content = substitute_vars(options.vars, &path, content)?;
+            
             // Process .sql files
-            if let Err(e) = process_sql_file(&path, sql_objects, builtin_catalog, _base_path) {
-                eprintln!("Warning: Failed to process {}: {}", path.display(), e);
+            if let Err(e) = process_sql_file(&path, &content, sql_objects, builtin_catalog, _base_path, options.tenants) {
+                warn!(file = %path.display(), error = %e, "Failed to process SQL file");
                 continue;
             }
         }
@@ -58,29 +95,107 @@ fn scan_directory_recursive(
 
 fn process_sql_file(
     file_path: &Path,
+    content: &str,
     sql_objects: &mut Vec<SqlObject>,
     _builtin_catalog: &BuiltinCatalog,
     _base_path: &Path,
+    tenants: Option<&TenantTemplate>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Read file content
-    let content = fs::read_to_string(file_path)?;
-    
     // Skip empty files
     if content.trim().is_empty() {
         return Ok(());
     }
     
+    match tenants {
+        Some(tenants) => {
+            for content in tenants.expand(content) {
+                process_sql_content(file_path, &content, sql_objects)?;
+            }
+            Ok(())
+        }
+        None => process_sql_content(file_path, content, sql_objects),
+    }
+}
+
+/// Identify the objects in one file's content, attaching the settings that
+/// follow them
+fn process_sql_content(
+    file_path: &Path,
+    content: &str,
+    sql_objects: &mut Vec<SqlObject>,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Split into statements
-    let statements = split_sql_file(&content)?;
+    let statements = split_sql_file(content)?;
+    let apply_after = resolve_apply_after_hints(file_path, content);
     
     // Identify objects in each statement
+    let first_in_file = sql_objects.len();
     for statement in statements {
+        let (directives, warnings) = parse_object_directives(&statement.sql);
+        for warning in warnings {
+            warn!(file = %file_path.display(), line = statement.start_line.unwrap_or_default(), "Ignoring directive: {}", warning);
+        }
+
         if let Some(mut object) = identify_sql_object(&statement.sql)? {
             // Set the file path and line numbers for the object
             object.source_file = Some(file_path.to_path_buf());
             object.start_line = statement.start_line;
             object.end_line = statement.end_line;
+            object.apply_after = apply_after.clone();
+            if let Some(owner) = &directives.owner {
+                match owner_statement(&object.object_type, &object.qualified_name, owner) {
+                    Some(statement) => object.add_setting(statement),
+                    None => warn!(
+                        file = %file_path.display(),
+                        line = object.start_line.unwrap_or_default(),
+                        "Ignoring pgmg:owner directive: the owner of a {} can't be set",
+                        object.object_type.to_string().to_lowercase()
+                    ),
+                }
+            }
+            // Hinted dependencies count the same as parsed ones
+            let hinted = directives.dependencies();
+            object.dependencies.relations.extend(hinted.relations);
+            object.dependencies.functions.extend(hinted.functions);
+            object.dependencies.types.extend(hinted.types);
+            object.directives = directives;
             sql_objects.push(object);
+            continue;
+        }
+
+        if !directives.is_empty() {
+            warn!(
+                file = %file_path.display(),
+                line = statement.start_line.unwrap_or_default(),
+                "Ignoring pgmg: directives on a statement that doesn't define an object"
+            );
+        }
+        if let Some((object_type, name)) = identify_object_setting(&statement.sql)? {
+            // Options belong to an object defined earlier in the same file
+            match sql_objects[first_in_file..].iter_mut()
+                .rev()
+                .find(|object| object.object_type == object_type && object.qualified_name == name)
+            {
+                Some(object) => {
+                    // The dictionaries a configuration maps tokens to, and the
+                    // members added to an operator family, are created before it
+                    if matches!(object_type, ObjectType::TextSearchConfiguration | ObjectType::OperatorFamily) {
+                        let used = analyze_statement(&statement.sql)?;
+                        object.dependencies.types.extend(used.types.into_iter().filter(|used| *used != name));
+                        object.dependencies.functions.extend(used.functions);
+                    }
+                    object.add_setting(statement.sql)
+                }
+                None => {
+                    warn!(
+                        file = %file_path.display(),
+                        line = statement.start_line.unwrap_or_default(),
+                        "Skipping ALTER statement: it must follow the {} {} it sets options on in the same file",
+                        object_type.to_string().to_lowercase(),
+                        name.name
+                    );
+                }
+            }
         } else {
             // Log warning for unprocessable statements
             warn_unprocessable_statement(file_path, &statement)?;
@@ -90,6 +205,40 @@ fn process_sql_file(
     Ok(())
 }
 
+/// Resolve a file's `-- pgmg:apply-after` hints relative to its directory.
+/// Hints naming files that don't exist are skipped with a warning.
+fn resolve_apply_after_hints(file_path: &Path, content: &str) -> Vec<PathBuf> {
+    let dir = file_path.parent().unwrap_or(Path::new(""));
+
+    extract_apply_after_hints(content).into_iter()
+        .filter_map(|hint| {
+            let path = normalize_path(&dir.join(&hint));
+            if path.is_file() {
+                Some(path)
+            } else {
+                warn!(file = %file_path.display(), hint = %hint, "Ignoring apply-after hint: file not found");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Resolve `.` and `..` components without touching the filesystem, so paths
+/// to the same file compare equal however they were written
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
 /// Analyze and warn about unprocessable SQL statements
 fn warn_unprocessable_statement(
     file_path: &Path,
@@ -221,6 +370,7 @@ impl MigrationFile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sql::QualifiedIdent;
     use std::fs;
     use tempfile::tempdir;
 
@@ -304,4 +454,127 @@ mod tests {
         
         assert_eq!(migration.read_content().unwrap(), content);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_scan_attaches_settings_to_objects() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("search.sql"), "
+            CREATE FUNCTION search(query text) RETURNS SETOF text AS $$ SELECT query $$ LANGUAGE sql;
+            ALTER FUNCTION search(text) SET work_mem = '64MB';
+            ALTER FUNCTION missing() SET work_mem = '64MB';
+        ").unwrap();
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let sql_objects = scan_sql_files(temp_dir.path(), &builtin_catalog).await.unwrap();
+
+        assert_eq!(sql_objects.len(), 1);
+        assert_eq!(sql_objects[0].settings, vec!["ALTER FUNCTION search(text) SET work_mem = '64MB'".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_resolves_apply_after_hints() {
+        let temp_dir = tempdir().unwrap();
+        let api_dir = temp_dir.path().join("api");
+        let handlers_dir = temp_dir.path().join("handlers");
+        fs::create_dir(&api_dir).unwrap();
+        fs::create_dir(&handlers_dir).unwrap();
+
+        fs::write(handlers_dir.join("handlers.sql"), "CREATE FUNCTION handle() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql;").unwrap();
+        fs::write(api_dir.join("dispatch.sql"), "
+            -- pgmg:apply-after ../handlers/handlers.sql missing.sql
+            CREATE FUNCTION dispatch() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql;
+        ").unwrap();
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let sql_objects = scan_sql_files(temp_dir.path(), &builtin_catalog).await.unwrap();
+
+        let dispatch = sql_objects.iter().find(|obj| obj.qualified_name.name == "dispatch").unwrap();
+        assert_eq!(dispatch.apply_after, vec![handlers_dir.join("handlers.sql")]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_attaches_directives() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("report.sql"), "
+            -- pgmg:skip-env prod
+            -- pgmg:owner report_owner
+            -- pgmg:depends-on api.totals, api.refresh()
+            CREATE VIEW api.report AS SELECT 1 AS one;
+
+            -- pgmg:no-drop
+            CREATE TABLE api.archive (id int);
+        ").unwrap();
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let sql_objects = scan_sql_files(temp_dir.path(), &builtin_catalog).await.unwrap();
+
+        let report = sql_objects.iter().find(|obj| obj.qualified_name.name == "report").unwrap();
+        assert_eq!(report.directives.skip_envs, vec!["prod"]);
+        assert_eq!(report.settings, vec!["ALTER VIEW api.report OWNER TO report_owner".to_string()]);
+        assert!(!report.directives.no_drop);
+        assert!(report.dependencies.relations.contains(&QualifiedIdent::from_qualified_name("api.totals")));
+        assert!(report.dependencies.functions.contains(&QualifiedIdent::from_qualified_name("api.refresh")));
+
+        let archive = sql_objects.iter().find(|obj| obj.qualified_name.name == "archive").unwrap();
+        assert!(archive.directives.no_drop);
+        assert!(archive.settings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scan_expands_tenant_files() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("orders.sql"), "
+            CREATE VIEW __tenant__.open_orders AS SELECT * FROM __tenant__.orders;
+            ALTER VIEW __tenant__.open_orders SET (security_barrier = true);
+        ").unwrap();
+        fs::write(temp_dir.path().join("shared.sql"), "CREATE VIEW api.tenants AS SELECT 1 AS id;").unwrap();
+
+        let tenants = TenantTemplate {
+            placeholder: "__tenant__".to_string(),
+            schemas: vec!["acme".to_string(), "globex".to_string()],
+            query: None,
+        };
+        let builtin_catalog = BuiltinCatalog::new();
+        let options = ScanOptions { tenants: Some(&tenants), ..Default::default() };
+        let sql_objects = scan_sql_files_with_options(temp_dir.path(), &builtin_catalog, &options).await.unwrap();
+
+        let names: Vec<&QualifiedIdent> = sql_objects.iter().map(|obj| &obj.qualified_name).collect();
+        assert_eq!(names, vec![
+            &QualifiedIdent::from_qualified_name("acme.open_orders"),
+            &QualifiedIdent::from_qualified_name("globex.open_orders"),
+            &QualifiedIdent::from_qualified_name("api.tenants"),
+        ]);
+
+        let globex = &sql_objects[1];
+        assert!(globex.dependencies.relations.contains(&QualifiedIdent::from_qualified_name("globex.orders")));
+        assert_eq!(globex.settings, vec!["ALTER VIEW globex.open_orders SET (security_barrier = true)".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_substitutes_vars() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("reporting.sql"), "
+            CREATE VIEW api.reporting_host AS SELECT '${PGMG_TEST_FDW_HOST}'::text AS host;
+        ").unwrap();
+
+        let builtin_catalog = BuiltinCatalog::new();
+        let vars = SqlVars::new([("PGMG_TEST_FDW_HOST".to_string(), "reporting.internal".to_string())].into());
+        let options = ScanOptions { vars: Some(&vars), ..Default::default() };
+        let sql_objects = scan_sql_files_with_options(temp_dir.path(), &builtin_catalog, &options).await.unwrap();
+        assert_eq!(sql_objects.len(), 1);
+        assert!(sql_objects[0].ddl_statement.contains("'reporting.internal'::text"));
+
+        // An undefined variable fails the scan rather than leaving the file out
+        let vars = SqlVars::default();
+        let options = ScanOptions { vars: Some(&vars), ..Default::default() };
+        let err = scan_sql_files_with_options(temp_dir.path(), &builtin_catalog, &options).await.unwrap_err();
+        assert!(err.to_string().contains("Undefined variable PGMG_TEST_FDW_HOST"), "{}", err);
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path(Path::new("./sql/api/../handlers/a.sql")), PathBuf::from("sql/handlers/a.sql"));
+        assert_eq!(normalize_path(Path::new("../sql/a.sql")), PathBuf::from("../sql/a.sql"));
+        assert_eq!(normalize_path(Path::new("/code/views/../a.sql")), PathBuf::from("/code/a.sql"));
+    }
+}
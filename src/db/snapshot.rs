@@ -0,0 +1,333 @@
+//! A copy of what plan reads from the database, the built-in catalog and
+//! pgmg's state, exported to a file so plans can be made without a
+//! connection, e.g. in CI jobs that can't reach the database.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+use crate::builtin_catalog::BuiltinCatalog;
+use crate::db::state::{ObjectRecord, StateManager};
+use crate::sql::{Dependencies, ObjectType, QualifiedIdent, TenantTemplate};
+
+/// Version of the snapshot file layout. Bump it when a change would make
+/// older snapshots read wrongly, not for fields with defaults.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// The database as plan sees it, written by `pgmg snapshot export` and read
+/// by `pgmg plan --offline`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub format_version: u32,
+    /// The pgmg that exported the snapshot
+    pub pgmg_version: String,
+    pub exported_at: SystemTime,
+    pub builtin_catalog: BuiltinCatalog,
+    /// Names of the applied migrations, sorted
+    pub applied_migrations: Vec<String>,
+    /// Managed objects as recorded in pgmg_state
+    pub objects: Vec<ObjectRecord>,
+    /// Dependencies of managed objects as recorded in pgmg_dependencies
+    pub dependencies: Vec<StoredDependency>,
+    /// Sequences owned by tables, which change with tables pending
+    /// migrations alter
+    pub owned_sequences: Vec<OwnedSequence>,
+    /// Every enum type in the database with its values in order
+    pub enum_types: Vec<EnumType>,
+    /// Schemas the `[tenants]` query returned when the snapshot was exported
+    #[serde(default)]
+    pub tenant_schemas: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    format_version: u32,
+    pgmg_version: String,
+}
+
+/// A row of pgmg_dependencies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredDependency {
+    pub dependent_type: ObjectType,
+    pub dependent_name: String,
+    /// `relation`, `function` or `type`
+    pub dependency_type: String,
+    pub dependency_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnedSequence {
+    pub table: QualifiedIdent,
+    pub sequence: QualifiedIdent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumType {
+    pub name: QualifiedIdent,
+    pub values: Vec<String>,
+}
+
+impl StateSnapshot {
+    /// Read the catalog and pgmg's state from the database, creating the
+    /// state tables first if they don't exist, as plan does. With a
+    /// `[tenants]` query, its schemas are recorded too.
+    pub async fn from_database(
+        client: &Client,
+        tenants: Option<&TenantTemplate>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let state_manager = StateManager::new(client);
+        state_manager.initialize().await?;
+
+        let mut applied_migrations: Vec<String> = state_manager.get_applied_migration_names().await?
+            .into_iter()
+            .collect();
+        applied_migrations.sort();
+
+        let dependencies = client.query(
+            "SELECT dependent_type, dependent_name, dependency_type, dependency_name
+             FROM pgmg.pgmg_dependencies
+             ORDER BY dependent_type, dependent_name, dependency_type, dependency_name",
+            &[],
+        ).await?
+            .iter()
+            .filter_map(|row| Some(StoredDependency {
+                dependent_type: row.get::<_, String>(0).parse().ok()?,
+                dependent_name: row.get(1),
+                dependency_type: row.get(2),
+                dependency_name: row.get(3),
+            }))
+            .collect();
+
+        let owned_sequences = client.query(
+            r#"
+            SELECT DISTINCT tn.nspname::text, t.relname::text, sn.nspname::text, s.relname::text
+            FROM pg_depend d
+            JOIN pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+            JOIN pg_namespace sn ON sn.oid = s.relnamespace
+            JOIN pg_class t ON t.oid = d.refobjid
+            JOIN pg_namespace tn ON tn.oid = t.relnamespace
+            WHERE d.classid = 'pg_class'::regclass
+            AND d.refclassid = 'pg_class'::regclass
+            AND d.deptype IN ('a', 'i')
+            ORDER BY 1, 2, 3, 4
+            "#,
+            &[],
+        ).await?
+            .iter()
+            .map(|row| OwnedSequence {
+                table: QualifiedIdent::new(Some(row.get(0)), row.get(1)),
+                sequence: QualifiedIdent::new(Some(row.get(2)), row.get(3)),
+            })
+            .collect();
+
+        let enum_types = client.query(
+            r#"
+            SELECT n.nspname::text, t.typname::text, array_agg(e.enumlabel::text ORDER BY e.enumsortorder)
+            FROM pg_enum e
+            JOIN pg_type t ON t.oid = e.enumtypid
+            JOIN pg_namespace n ON n.oid = t.typnamespace
+            GROUP BY 1, 2
+            ORDER BY 1, 2
+            "#,
+            &[],
+        ).await?
+            .iter()
+            .map(|row| EnumType {
+                name: QualifiedIdent::new(Some(row.get(0)), row.get(1)),
+                values: row.get(2),
+            })
+            .collect();
+
+        let mut tenant_schemas = Vec::new();
+        if let Some(query) = tenants.and_then(|tenants| tenants.query.as_deref()) {
+            let rows = client.query(query, &[]).await
+                .map_err(|e| format!("Tenant query failed: {}", e))?;
+            tenant_schemas = rows.iter()
+                .map(|row| row.try_get::<_, String>(0))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Tenant query must return schema names as text in its first column: {}", e))?;
+        }
+
+        Ok(Self {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            pgmg_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: SystemTime::now(),
+            builtin_catalog: BuiltinCatalog::for_database(client).await?,
+            applied_migrations,
+            objects: state_manager.get_tracked_objects().await?,
+            dependencies,
+            owned_sequences,
+            enum_types,
+            tenant_schemas,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read snapshot {}: {}", path.display(), e))?;
+        let not_a_snapshot = |e: serde_json::Error| format!("{} is not a pgmg snapshot: {}", path.display(), e);
+
+        // Checked first, as a newer layout may not read as this one
+        let header: SnapshotHeader = serde_json::from_str(&content).map_err(not_a_snapshot)?;
+        if header.format_version > SNAPSHOT_FORMAT_VERSION {
+            return Err(format!(
+                "{} was exported by pgmg {}, which writes a newer snapshot format. \
+                 Upgrade pgmg or export the snapshot again with this version.",
+                path.display(), header.pgmg_version
+            ).into());
+        }
+        Ok(serde_json::from_str(&content).map_err(not_a_snapshot)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// [`StateManager::get_applied_migration_names`] as of the snapshot
+    pub fn applied_migration_names(&self) -> HashSet<String> {
+        self.applied_migrations.iter().cloned().collect()
+    }
+
+    /// [`StateManager::find_dependents_of_relations`] as of the snapshot
+    pub fn dependents_of_relations(&self, relations: &[String]) -> Vec<(ObjectType, String)> {
+        let mut dependents: Vec<(ObjectType, String)> = Vec::new();
+        for dependency in &self.dependencies {
+            let dependent = (dependency.dependent_type.clone(), dependency.dependent_name.clone());
+            if dependency.dependency_type == "relation"
+                && relations.contains(&dependency.dependency_name)
+                && !dependents.contains(&dependent) {
+                dependents.push(dependent);
+            }
+        }
+        dependents
+    }
+
+    /// [`StateManager::get_deleted_object_dependencies`] as of the snapshot
+    pub fn object_dependencies(
+        &self,
+        objects: &[(ObjectType, String)],
+    ) -> Vec<(ObjectType, QualifiedIdent, Dependencies)> {
+        objects.iter().map(|(object_type, object_name)| {
+            let mut dependencies = Dependencies::default();
+            for dependency in self.dependencies.iter()
+                .filter(|dependency| &dependency.dependent_type == object_type && &dependency.dependent_name == object_name) {
+                let name = QualifiedIdent::from_qualified_name(&dependency.dependency_name);
+                match dependency.dependency_type.as_str() {
+                    "relation" => { dependencies.relations.insert(name); }
+                    "function" => { dependencies.functions.insert(name); }
+                    "type" => { dependencies.types.insert(name); }
+                    _ => {}
+                }
+            }
+            (object_type.clone(), QualifiedIdent::from_qualified_name(object_name), dependencies)
+        }).collect()
+    }
+
+    /// [`StateManager::find_owned_sequences`] as of the snapshot. Tables
+    /// without a schema are looked up in public.
+    pub fn owned_sequences(&self, tables: &[String]) -> Vec<String> {
+        let tables: Vec<QualifiedIdent> = tables.iter().map(|table| QualifiedIdent::from_qualified_name(table)).collect();
+
+        let mut sequences = Vec::new();
+        for owned in self.owned_sequences.iter().filter(|owned| tables.iter().any(|table| same_relation(table, &owned.table))) {
+            let schema = owned.sequence.schema.as_deref().unwrap_or("public");
+            if schema == "public" {
+                sequences.push(owned.sequence.name.clone());
+            }
+            sequences.push(format!("{}.{}", schema, owned.sequence.name));
+        }
+        sequences.dedup();
+        sequences
+    }
+
+    /// The values of an enum type, in order. Empty if there is no enum type
+    /// by that name.
+    pub fn enum_values(&self, name: &QualifiedIdent) -> Vec<String> {
+        self.enum_types.iter()
+            .find(|enum_type| same_relation(name, &enum_type.name))
+            .map(|enum_type| enum_type.values.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether `name`, as written in code, names `qualified`, taking names
+/// without a schema to be in public
+fn same_relation(name: &QualifiedIdent, qualified: &QualifiedIdent) -> bool {
+    name.name == qualified.name
+        && name.schema.as_deref().unwrap_or("public") == qualified.schema.as_deref().unwrap_or("public")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> StateSnapshot {
+        let public = |name: &str| QualifiedIdent::new(Some("public".to_string()), name.to_string());
+        StateSnapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            pgmg_version: "0.1.0".to_string(),
+            exported_at: SystemTime::UNIX_EPOCH,
+            builtin_catalog: BuiltinCatalog::new(),
+            applied_migrations: vec!["001_orders".to_string()],
+            objects: Vec::new(),
+            dependencies: vec![
+                StoredDependency {
+                    dependent_type: ObjectType::View,
+                    dependent_name: "order_totals".to_string(),
+                    dependency_type: "relation".to_string(),
+                    dependency_name: "orders".to_string(),
+                },
+                StoredDependency {
+                    dependent_type: ObjectType::View,
+                    dependent_name: "order_totals".to_string(),
+                    dependency_type: "function".to_string(),
+                    dependency_name: "api.total".to_string(),
+                },
+            ],
+            owned_sequences: vec![OwnedSequence { table: public("orders"), sequence: public("orders_id_seq") }],
+            enum_types: vec![EnumType { name: public("order_status"), values: vec!["new".to_string(), "paid".to_string()] }],
+            tenant_schemas: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_answers_like_the_state_tables() {
+        let snapshot = snapshot();
+
+        assert_eq!(snapshot.dependents_of_relations(&["orders".to_string()]), vec![(ObjectType::View, "order_totals".to_string())]);
+        assert!(snapshot.dependents_of_relations(&["api.total".to_string()]).is_empty());
+
+        let dependencies = snapshot.object_dependencies(&[(ObjectType::View, "order_totals".to_string())]);
+        assert_eq!(dependencies.len(), 1);
+        assert!(dependencies[0].2.relations.contains(&QualifiedIdent::from_name("orders".to_string())));
+        assert!(dependencies[0].2.functions.contains(&QualifiedIdent::from_qualified_name("api.total")));
+
+        assert_eq!(snapshot.owned_sequences(&["orders".to_string()]), vec!["orders_id_seq", "public.orders_id_seq"]);
+        assert!(snapshot.owned_sequences(&["billing.orders".to_string()]).is_empty());
+
+        assert_eq!(snapshot.enum_values(&QualifiedIdent::from_name("order_status".to_string())), vec!["new", "paid"]);
+        assert!(snapshot.enum_values(&QualifiedIdent::from_name("missing".to_string())).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshots/prod.json");
+
+        snapshot().write(&path).unwrap();
+        let loaded = StateSnapshot::load(&path).unwrap();
+        assert_eq!(loaded.applied_migrations, vec!["001_orders"]);
+        assert_eq!(loaded.dependencies, snapshot().dependencies);
+
+        let newer = fs::read_to_string(&path).unwrap()
+            .replace(&format!("\"format_version\": {}", SNAPSHOT_FORMAT_VERSION), "\"format_version\": 999");
+        fs::write(&path, newer).unwrap();
+        assert!(StateSnapshot::load(&path).unwrap_err().to_string().contains("newer snapshot format"));
+    }
+}
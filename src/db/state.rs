@@ -1,7 +1,216 @@
 use tokio_postgres::Client;
-use std::collections::HashSet;
-use crate::sql::{ObjectType, QualifiedIdent};
-use std::time::SystemTime;
+use std::collections::{HashMap, HashSet};
+use crate::sql::{ColumnReference, ObjectType, QualifiedIdent};
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+/// A change to pgmg's own tables, applied once per database by
+/// [`StateManager::initialize`]
+pub struct StateUpgrade {
+    pub version: i32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Upgrades to the state tables, oldest first. Append new ones with the next
+/// version number; never edit one that has shipped. Each must be safe to run
+/// against tables that already have the change, since installations that
+/// predate pgmg_meta replay them all.
+pub const STATE_UPGRADES: &[StateUpgrade] = &[
+    StateUpgrade {
+        version: 1,
+        description: "Create migration, object state and dependency tables",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_migrations (
+                name TEXT PRIMARY KEY,
+                applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+            );
+
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_state (
+                object_type TEXT NOT NULL,
+                object_name TEXT NOT NULL,
+                ddl_hash TEXT NOT NULL,
+                last_applied TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                PRIMARY KEY (object_type, object_name)
+            );
+
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_dependencies (
+                dependent_type TEXT NOT NULL,
+                dependent_name TEXT NOT NULL,
+                dependency_type TEXT NOT NULL,
+                dependency_name TEXT NOT NULL,
+                dependency_kind TEXT NOT NULL,
+                PRIMARY KEY (dependent_type, dependent_name, dependency_type, dependency_name)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_state_object_type
+            ON pgmg.pgmg_state (object_type);
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_state_last_applied
+            ON pgmg.pgmg_state (last_applied);
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_migrations_applied_at
+            ON pgmg.pgmg_migrations (applied_at);
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_dependencies_dependent
+            ON pgmg.pgmg_dependencies (dependent_type, dependent_name);
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_dependencies_dependency
+            ON pgmg.pgmg_dependencies (dependency_type, dependency_name);
+        "#,
+    },
+    StateUpgrade {
+        version: 2,
+        description: "Store the applied DDL of each object",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS ddl_source TEXT;
+        "#,
+    },
+    StateUpgrade {
+        version: 3,
+        description: "Track applied seed files",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_seeds (
+                file_name TEXT PRIMARY KEY,
+                content_hash TEXT NOT NULL,
+                applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+            );
+        "#,
+    },
+    StateUpgrade {
+        version: 4,
+        description: "Record finished applies",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_history (
+                id BIGSERIAL PRIMARY KEY,
+                finished_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                migrations_applied INTEGER NOT NULL,
+                objects_changed INTEGER NOT NULL
+            );
+        "#,
+    },
+    StateUpgrade {
+        version: 5,
+        description: "Store object tags",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
+        "#,
+    },
+    StateUpgrade {
+        version: 6,
+        description: "Store the content hash of applied migrations",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_migrations ADD COLUMN IF NOT EXISTS content_hash TEXT;
+        "#,
+    },
+    StateUpgrade {
+        version: 7,
+        description: "Store the schedule and command of cron jobs",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS cron_schedule TEXT;
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS cron_command TEXT;
+        "#,
+    },
+    StateUpgrade {
+        version: 8,
+        description: "Record every apply run for auditing",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_runs (
+                id BIGSERIAL PRIMARY KEY,
+                started_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                finished_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                db_user TEXT NOT NULL DEFAULT session_user,
+                os_user TEXT,
+                pgmg_version TEXT NOT NULL,
+                git_commit TEXT,
+                environment TEXT,
+                duration_ms BIGINT NOT NULL,
+                succeeded BOOLEAN NOT NULL,
+                changes JSONB NOT NULL,
+                errors JSONB NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_runs_started_at
+            ON pgmg.pgmg_runs (started_at);
+        "#,
+    },
+    StateUpgrade {
+        version: 9,
+        description: "Store the git commit migrations and objects were applied from",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_migrations ADD COLUMN IF NOT EXISTS git_commit TEXT;
+            ALTER TABLE pgmg.pgmg_migrations ADD COLUMN IF NOT EXISTS git_dirty BOOLEAN;
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS git_commit TEXT;
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS git_dirty BOOLEAN;
+            ALTER TABLE pgmg.pgmg_runs ADD COLUMN IF NOT EXISTS git_dirty BOOLEAN;
+
+            CREATE INDEX IF NOT EXISTS idx_pgmg_runs_finished_at
+            ON pgmg.pgmg_runs (finished_at);
+        "#,
+    },
+    StateUpgrade {
+        version: 10,
+        description: "Store the signature of operators",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_state ADD COLUMN IF NOT EXISTS operator_signature TEXT;
+        "#,
+    },
+    StateUpgrade {
+        version: 11,
+        description: "Record where the backup taken before an apply was written",
+        sql: r#"
+            ALTER TABLE pgmg.pgmg_runs ADD COLUMN IF NOT EXISTS backup_path TEXT;
+        "#,
+    },
+];
+
+/// The state table layout this version of pgmg expects
+pub const STATE_SCHEMA_VERSION: i32 = STATE_UPGRADES[STATE_UPGRADES.len() - 1].version;
+
+/// The pgmg_runs columns [`run_from_row`] reads, in order
+const RUN_COLUMNS: &str = "id, started_at, finished_at, db_user, os_user, pgmg_version, git_commit, git_dirty, \
+    environment, duration_ms, succeeded, changes::text, errors::text, backup_path";
+
+fn run_from_row(row: &tokio_postgres::Row) -> Result<RunRecord, Box<dyn std::error::Error>> {
+    let duration_ms: i64 = row.get(9);
+    let changes: String = row.get(11);
+    let errors: String = row.get(12);
+    Ok(RunRecord {
+        id: row.get(0),
+        started_at: row.get(1),
+        finished_at: row.get(2),
+        db_user: row.get(3),
+        os_user: row.get(4),
+        pgmg_version: row.get(5),
+        git_commit: row.get(6),
+        git_dirty: row.get(7),
+        environment: row.get(8),
+        duration: Duration::from_millis(duration_ms.max(0) as u64),
+        succeeded: row.get(10),
+        changes: serde_json::from_str(&changes)?,
+        errors: serde_json::from_str(&errors)?,
+        backup_path: row.get(13),
+    })
+}
+
+/// Hash of a migration file's exact content, recorded when it's applied so
+/// later edits to the file can be noticed
+pub fn migration_content_hash(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// The signature an operator is stored with, which `DROP OPERATOR` needs.
+/// `None` for other objects.
+pub fn operator_signature(object_type: &ObjectType, ddl_source: &str) -> Option<String> {
+    if object_type != &ObjectType::Operator {
+        return None;
+    }
+    crate::sql::objects::extract_operator_signature(ddl_source).ok()
+}
 
 #[derive(Debug, Clone)]
 pub struct MigrationRecord {
@@ -9,12 +218,102 @@ pub struct MigrationRecord {
     pub applied_at: SystemTime,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectRecord {
     pub object_type: ObjectType,
     pub object_name: QualifiedIdent,
     pub ddl_hash: String,
     pub last_applied: SystemTime,
+    /// The DDL that was last applied, if it was applied by a pgmg that
+    /// stores it
+    pub ddl_source: Option<String>,
+    /// The `-- pgmg:tags` the object was last applied with
+    pub tags: Vec<String>,
+    /// The schedule and command of a cron job, if it was applied by a pgmg
+    /// that stores them
+    pub cron_schedule: Option<String>,
+    pub cron_command: Option<String>,
+    /// The git commit the object was last applied from, and whether the
+    /// working tree had uncommitted changes
+    pub git_commit: Option<String>,
+    pub git_dirty: Option<bool>,
+}
+
+impl ObjectRecord {
+    /// The cron job as it was last applied, from its stored schedule and
+    /// command or else its stored DDL
+    pub fn cron_job(&self) -> Option<crate::sql::CronJob> {
+        if self.object_type != ObjectType::CronJob {
+            return None;
+        }
+        match (&self.cron_schedule, &self.cron_command) {
+            (Some(schedule), Some(command)) => Some(crate::sql::CronJob {
+                name: self.object_name.name.clone(),
+                schedule: schedule.clone(),
+                command: command.clone(),
+            }),
+            _ => crate::sql::parse_cron_job(self.ddl_source.as_deref()?),
+        }
+    }
+}
+
+/// What an apply run changed, stored as JSON in pgmg_runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunChanges {
+    pub migrations_applied: Vec<String>,
+    pub objects_created: Vec<String>,
+    pub objects_updated: Vec<String>,
+    pub objects_deleted: Vec<String>,
+}
+
+/// An apply run to record in pgmg_runs. The database role and when it
+/// started and finished are filled in by the database, so they can be
+/// compared with the times it stamps on migrations and objects.
+#[derive(Debug, Clone)]
+pub struct NewRun {
+    pub duration: Duration,
+    /// The operating system user running pgmg
+    pub os_user: Option<String>,
+    /// HEAD of the git repository the migrations are in
+    pub git_commit: Option<String>,
+    /// Whether the migrations or code files had uncommitted changes
+    pub git_dirty: Option<bool>,
+    pub environment: Option<String>,
+    pub succeeded: bool,
+    pub changes: RunChanges,
+    pub errors: Vec<String>,
+    /// The dump `backup_before_destructive` took before the apply
+    pub backup_path: Option<String>,
+}
+
+/// An apply run recorded in pgmg_runs
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub id: i64,
+    pub started_at: SystemTime,
+    pub finished_at: SystemTime,
+    /// The role pgmg connected as
+    pub db_user: String,
+    pub os_user: Option<String>,
+    pub pgmg_version: String,
+    pub git_commit: Option<String>,
+    pub git_dirty: Option<bool>,
+    pub environment: Option<String>,
+    pub duration: Duration,
+    pub succeeded: bool,
+    pub changes: RunChanges,
+    pub errors: Vec<String>,
+    pub backup_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DependencyRecord {
+    pub dependent_type: ObjectType,
+    pub dependent_name: String,
+    pub dependency_name: String,
+    /// `true` for hard (structural) dependencies, `false` for soft (runtime lookup) ones
+    pub is_hard: bool,
 }
 
 pub struct StateManager<'a> {
@@ -26,109 +325,76 @@ impl<'a> StateManager<'a> {
         Self { client }
     }
 
-    /// Initialize the state tracking tables if they don't exist
+    /// Create the state tracking tables, or upgrade them to the layout this
+    /// version of pgmg expects.
+    ///
+    /// The layout version is recorded in pgmg.pgmg_meta. Installations from
+    /// before pgmg_meta existed are treated as version 0 and replay every
+    /// upgrade, which is why upgrades must be idempotent.
     pub async fn initialize(&self) -> Result<(), Box<dyn std::error::Error>> {
         // Suppress NOTICE messages during initialization
         // (PostgreSQL emits "already exists, skipping" for IF NOT EXISTS)
         self.client.execute("SET client_min_messages = 'WARNING'", &[]).await?;
 
-        // Create pgmg schema if it doesn't exist
-        self.client.execute(
-            r#"
-            CREATE SCHEMA IF NOT EXISTS pgmg
-            "#,
-            &[],
-        ).await?;
+        let result = self.upgrade_schema().await;
 
-        // Create pgmg_migrations table
-        self.client.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS pgmg.pgmg_migrations (
-                name TEXT PRIMARY KEY,
-                applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
-            )
-            "#,
-            &[],
-        ).await?;
+        // Restore default message level
+        self.client.execute("SET client_min_messages = 'NOTICE'", &[]).await?;
 
-        // Create pgmg_state table for object tracking
-        self.client.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS pgmg.pgmg_state (
-                object_type TEXT NOT NULL,
-                object_name TEXT NOT NULL,
-                ddl_hash TEXT NOT NULL,
-                last_applied TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
-                PRIMARY KEY (object_type, object_name)
-            )
-            "#,
-            &[],
-        ).await?;
+        result
+    }
 
-        // Create pgmg_dependencies table for tracking object dependencies
-        self.client.execute(
+    async fn upgrade_schema(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.client.batch_execute(
             r#"
-            CREATE TABLE IF NOT EXISTS pgmg.pgmg_dependencies (
-                dependent_type TEXT NOT NULL,
-                dependent_name TEXT NOT NULL,
-                dependency_type TEXT NOT NULL,
-                dependency_name TEXT NOT NULL,
-                dependency_kind TEXT NOT NULL,
-                PRIMARY KEY (dependent_type, dependent_name, dependency_type, dependency_name)
-            )
-            "#,
-            &[],
-        ).await?;
+            CREATE SCHEMA IF NOT EXISTS pgmg;
 
-        // Create indexes for performance optimization
-        // Index on object_type for filtering queries by type
-        self.client.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_pgmg_state_object_type
-            ON pgmg.pgmg_state (object_type)
+            CREATE TABLE IF NOT EXISTS pgmg.pgmg_meta (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+            );
             "#,
-            &[],
         ).await?;
 
-        // Index on last_applied for time-based queries
-        self.client.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_pgmg_state_last_applied
-            ON pgmg.pgmg_state (last_applied)
-            "#,
-            &[],
-        ).await?;
+        let current = self.schema_version().await?;
+        if current > STATE_SCHEMA_VERSION {
+            return Err(format!(
+                "pgmg state schema is at version {}, but this pgmg only supports up to version {}. \
+                 Upgrade pgmg before running it against this database.",
+                current, STATE_SCHEMA_VERSION
+            ).into());
+        }
 
-        // Index on migrations applied_at for chronological queries
-        self.client.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_pgmg_migrations_applied_at
-            ON pgmg.pgmg_migrations (applied_at)
-            "#,
-            &[],
-        ).await?;
+        for upgrade in STATE_UPGRADES.iter().filter(|upgrade| upgrade.version > current) {
+            debug!(version = upgrade.version, description = upgrade.description, "Upgrading pgmg state schema");
 
-        // Indexes for dependency lookups
-        self.client.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_pgmg_dependencies_dependent
-            ON pgmg.pgmg_dependencies (dependent_type, dependent_name)
-            "#,
-            &[],
-        ).await?;
+            // A multi-statement batch runs as one implicit transaction, so the
+            // upgrade and its version row are committed together
+            self.client.batch_execute(&format!(
+                "{}\nINSERT INTO pgmg.pgmg_meta (version, description) VALUES ({}, '{}') ON CONFLICT (version) DO NOTHING;",
+                upgrade.sql,
+                upgrade.version,
+                upgrade.description.replace('\'', "''"),
+            )).await?;
+        }
 
-        self.client.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_pgmg_dependencies_dependency
-            ON pgmg.pgmg_dependencies (dependency_type, dependency_name)
-            "#,
-            &[],
-        ).await?;
+        Ok(())
+    }
 
-        // Restore default message level
-        self.client.execute("SET client_min_messages = 'NOTICE'", &[]).await?;
+    /// The version of the state table layout, 0 if pgmg_meta doesn't exist or
+    /// is empty
+    pub async fn schema_version(&self) -> Result<i32, Box<dyn std::error::Error>> {
+        let exists: bool = self.client.query_one(
+            "SELECT to_regclass('pgmg.pgmg_meta') IS NOT NULL",
+            &[],
+        ).await?.get(0);
+        if !exists {
+            return Ok(0);
+        }
 
-        Ok(())
+        let row = self.client.query_one("SELECT coalesce(max(version), 0) FROM pgmg.pgmg_meta", &[]).await?;
+        Ok(row.get(0))
     }
 
     /// Get all applied migrations
@@ -159,10 +425,94 @@ impl<'a> StateManager<'a> {
         Ok(())
     }
 
+    /// Record a finished apply run in pgmg_runs, returning its id
+    pub async fn record_run(&self, run: &NewRun) -> Result<i64, Box<dyn std::error::Error>> {
+        let duration_ms = run.duration.as_millis() as i64;
+        let changes = serde_json::to_string(&run.changes)?;
+        let errors = serde_json::to_string(&run.errors)?;
+
+        let row = self.client.query_one(
+            "INSERT INTO pgmg.pgmg_runs
+                (started_at, os_user, pgmg_version, git_commit, git_dirty, environment, duration_ms, succeeded, changes, errors, backup_path)
+             VALUES (now() - make_interval(secs => $6::bigint / 1000.0), $1, $2, $3, $4, $5, $6, $7, $8::text::jsonb, $9::text::jsonb, $10)
+             RETURNING id",
+            &[
+                &run.os_user,
+                &env!("CARGO_PKG_VERSION"),
+                &run.git_commit,
+                &run.git_dirty,
+                &run.environment,
+                &duration_ms,
+                &run.succeeded,
+                &changes,
+                &errors,
+                &run.backup_path,
+            ],
+        ).await?;
+
+        Ok(row.get(0))
+    }
+
+    /// The most recent apply runs, newest first. Read without creating
+    /// pgmg_runs, so a database no apply has recorded a run in has none.
+    pub async fn get_runs(&self, limit: i64) -> Result<Vec<RunRecord>, Box<dyn std::error::Error>> {
+        let exists: bool = self.client.query_one(
+            "SELECT to_regclass('pgmg.pgmg_runs') IS NOT NULL",
+            &[],
+        ).await?.get(0);
+        if !exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = self.client.query(
+            &format!("SELECT {} FROM pgmg.pgmg_runs ORDER BY id DESC LIMIT $1", RUN_COLUMNS),
+            &[&limit],
+        ).await?;
+
+        rows.iter().map(run_from_row).collect()
+    }
+
+    /// The recorded apply run that was running at `time`, e.g. the one that
+    /// last applied an object
+    pub async fn get_run_at(&self, time: SystemTime) -> Result<Option<RunRecord>, Box<dyn std::error::Error>> {
+        let row = self.client.query_opt(
+            &format!(
+                "SELECT {} FROM pgmg.pgmg_runs WHERE started_at <= $1 AND finished_at >= $1 ORDER BY id DESC LIMIT 1",
+                RUN_COLUMNS
+            ),
+            &[&time],
+        ).await?;
+
+        row.as_ref().map(run_from_row).transpose()
+    }
+
+    /// Content hashes of applied migrations, by name. Migrations applied by a
+    /// pgmg that didn't record them have none.
+    pub async fn get_migration_hashes(&self) -> Result<HashMap<String, Option<String>>, Box<dyn std::error::Error>> {
+        let rows = self.client.query(
+            "SELECT name, content_hash FROM pgmg.pgmg_migrations",
+            &[],
+        ).await?;
+
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Content hashes of the seed files applied with tracking, by file name
+    pub async fn get_seed_hashes(&self) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+        let rows = self.client.query(
+            "SELECT file_name, content_hash FROM pgmg.pgmg_seeds",
+            &[],
+        ).await?;
+
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
     /// Get all tracked objects with their current hashes
     pub async fn get_tracked_objects(&self) -> Result<Vec<ObjectRecord>, Box<dyn std::error::Error>> {
         let rows = self.client.query(
-            "SELECT object_type, object_name, ddl_hash, last_applied FROM pgmg.pgmg_state ORDER BY object_name",
+            "SELECT object_type, object_name, ddl_hash, last_applied, ddl_source, tags, cron_schedule, cron_command,
+                    git_commit, git_dirty
+             FROM pgmg.pgmg_state ORDER BY object_name",
             &[],
         ).await?;
 
@@ -181,6 +531,16 @@ impl<'a> StateManager<'a> {
                 "trigger" => ObjectType::Trigger,
                 "comment" => ObjectType::Comment,
                 "cron_job" => ObjectType::CronJob,
+                "security_label" => ObjectType::SecurityLabel,
+                "statistics" => ObjectType::Statistics,
+                "extension" => ObjectType::Extension,
+                "publication" => ObjectType::Publication,
+                "collation" => ObjectType::Collation,
+                "text_search_configuration" => ObjectType::TextSearchConfiguration,
+                "text_search_dictionary" => ObjectType::TextSearchDictionary,
+                "operator_class" => ObjectType::OperatorClass,
+                "operator_family" => ObjectType::OperatorFamily,
+                "cast" => ObjectType::Cast,
                 _ => continue, // Skip unknown types
             };
 
@@ -192,6 +552,12 @@ impl<'a> StateManager<'a> {
                 object_name,
                 ddl_hash: row.get(2),
                 last_applied: row.get(3),
+                ddl_source: row.get(4),
+                tags: row.get(5),
+                cron_schedule: row.get(6),
+                cron_command: row.get(7),
+                git_commit: row.get(8),
+                git_dirty: row.get(9),
             });
         }
 
@@ -207,12 +573,14 @@ impl<'a> StateManager<'a> {
         Ok(count == 0)
     }
 
-    /// Update or insert an object's hash
+    /// Update or insert an object's hash and the DDL it was created from.
+    /// Large definitions are compressed by PostgreSQL's TOAST storage.
     pub async fn update_object_hash(
         &self,
         object_type: &ObjectType,
         object_name: &QualifiedIdent,
         ddl_hash: &str,
+        ddl_source: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let object_type_str = match object_type {
             ObjectType::Table => "table",
@@ -228,6 +596,16 @@ impl<'a> StateManager<'a> {
             ObjectType::CronJob => "cron_job",
             ObjectType::Aggregate => "aggregate",
             ObjectType::Operator => "operator",
+            ObjectType::SecurityLabel => "security_label",
+            ObjectType::Statistics => "statistics",
+            ObjectType::Extension => "extension",
+            ObjectType::Publication => "publication",
+            ObjectType::Collation => "collation",
+            ObjectType::TextSearchConfiguration => "text_search_configuration",
+            ObjectType::TextSearchDictionary => "text_search_dictionary",
+            ObjectType::OperatorClass => "operator_class",
+            ObjectType::OperatorFamily => "operator_family",
+            ObjectType::Cast => "cast",
         };
 
         let qualified_name = match &object_name.schema {
@@ -235,14 +613,19 @@ impl<'a> StateManager<'a> {
             None => object_name.name.clone(),
         };
 
+        let cron_job = crate::sql::parse_cron_job(ddl_source).filter(|_| object_type == &ObjectType::CronJob);
+        let cron_schedule = cron_job.as_ref().map(|job| job.schedule.as_str());
+        let cron_command = cron_job.as_ref().map(|job| job.command.as_str());
+        let operator_signature = operator_signature(object_type, ddl_source);
+
         self.client.execute(
             r#"
-            INSERT INTO pgmg.pgmg_state (object_type, object_name, ddl_hash) 
-            VALUES ($1, $2, $3)
+            INSERT INTO pgmg.pgmg_state (object_type, object_name, ddl_hash, ddl_source, cron_schedule, cron_command, operator_signature) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             ON CONFLICT (object_type, object_name) 
-            DO UPDATE SET ddl_hash = $3, last_applied = NOW()
+            DO UPDATE SET ddl_hash = $3, ddl_source = $4, cron_schedule = $5, cron_command = $6, operator_signature = $7, last_applied = NOW()
             "#,
-            &[&object_type_str, &qualified_name, &ddl_hash],
+            &[&object_type_str, &qualified_name, &ddl_hash, &ddl_source, &cron_schedule, &cron_command, &operator_signature],
         ).await?;
 
         Ok(())
@@ -288,6 +671,16 @@ impl<'a> StateManager<'a> {
             ObjectType::CronJob => "cron_job",
             ObjectType::Aggregate => "aggregate",
             ObjectType::Operator => "operator",
+            ObjectType::SecurityLabel => "security_label",
+            ObjectType::Statistics => "statistics",
+            ObjectType::Extension => "extension",
+            ObjectType::Publication => "publication",
+            ObjectType::Collation => "collation",
+            ObjectType::TextSearchConfiguration => "text_search_configuration",
+            ObjectType::TextSearchDictionary => "text_search_dictionary",
+            ObjectType::OperatorClass => "operator_class",
+            ObjectType::OperatorFamily => "operator_family",
+            ObjectType::Cast => "cast",
         };
 
         let qualified_name = match &object_name.schema {
@@ -307,6 +700,21 @@ impl<'a> StateManager<'a> {
         }
     }
 
+    /// Get the DDL an object was last applied with. `None` if the object isn't
+    /// tracked or was applied before pgmg stored definitions.
+    pub async fn get_object_ddl(
+        &self,
+        object_type: &ObjectType,
+        object_name: &QualifiedIdent,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let row = self.client.query_opt(
+            "SELECT ddl_source FROM pgmg.pgmg_state WHERE object_type = $1 AND object_name = $2",
+            &[&self.object_type_to_string(object_type), &self.format_qualified_name(object_name)],
+        ).await?;
+
+        Ok(row.and_then(|row| row.get(0)))
+    }
+
     /// Get names of all applied migrations
     pub async fn get_applied_migration_names(&self) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
         let rows = self.client.query(
@@ -478,6 +886,16 @@ impl<'a> StateManager<'a> {
             ObjectType::CronJob => "cron_job",
             ObjectType::Aggregate => "aggregate",
             ObjectType::Operator => "operator",
+            ObjectType::SecurityLabel => "security_label",
+            ObjectType::Statistics => "statistics",
+            ObjectType::Extension => "extension",
+            ObjectType::Publication => "publication",
+            ObjectType::Collation => "collation",
+            ObjectType::TextSearchConfiguration => "text_search_configuration",
+            ObjectType::TextSearchDictionary => "text_search_dictionary",
+            ObjectType::OperatorClass => "operator_class",
+            ObjectType::OperatorFamily => "operator_family",
+            ObjectType::Cast => "cast",
         }
     }
     
@@ -505,10 +923,49 @@ impl<'a> StateManager<'a> {
             "cron_job" => Some(ObjectType::CronJob),
             "aggregate" => Some(ObjectType::Aggregate),
             "operator" => Some(ObjectType::Operator),
+            "security_label" => Some(ObjectType::SecurityLabel),
+            "statistics" => Some(ObjectType::Statistics),
+            "extension" => Some(ObjectType::Extension),
+            "publication" => Some(ObjectType::Publication),
+            "collation" => Some(ObjectType::Collation),
+            "text_search_configuration" => Some(ObjectType::TextSearchConfiguration),
+            "text_search_dictionary" => Some(ObjectType::TextSearchDictionary),
+            "operator_class" => Some(ObjectType::OperatorClass),
+            "operator_family" => Some(ObjectType::OperatorFamily),
+            "cast" => Some(ObjectType::Cast),
             _ => None,
         }
     }
 
+    /// Get every stored dependency edge
+    pub async fn get_all_dependencies(&self) -> Result<Vec<DependencyRecord>, Box<dyn std::error::Error>> {
+        let rows = self.client.query(
+            r#"
+            SELECT dependent_type, dependent_name, dependency_name, dependency_kind
+            FROM pgmg.pgmg_dependencies
+            ORDER BY dependent_type, dependent_name, dependency_name
+            "#,
+            &[],
+        ).await?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let dep_type_str: String = row.get(0);
+            let dependency_kind: String = row.get(3);
+
+            if let Some(dependent_type) = self.string_to_object_type(&dep_type_str) {
+                result.push(DependencyRecord {
+                    dependent_type,
+                    dependent_name: row.get(1),
+                    dependency_name: row.get(2),
+                    is_hard: dependency_kind == "hard",
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
     /// Find all managed objects that depend on the given relations (tables).
     ///
     /// This is used to identify objects that need to be pre-dropped before
@@ -543,6 +1000,106 @@ impl<'a> StateManager<'a> {
 
         Ok(result)
     }
+
+    /// Find the views, materialized views and SQL-standard function bodies
+    /// that read the given columns, from what PostgreSQL recorded in
+    /// pg_depend when it created them. Each is returned by its qualified
+    /// name, with the column it reads.
+    ///
+    /// Unlike the stored dependencies, this finds them however they named
+    /// the table, and only if they use the column.
+    pub async fn find_column_dependents(
+        &self,
+        columns: &[ColumnReference],
+    ) -> Result<Vec<(ObjectType, String, ColumnReference)>, Box<dyn std::error::Error>> {
+        if columns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let schemas: Vec<Option<&str>> = columns.iter().map(|column| column.relation.schema.as_deref()).collect();
+        let relations: Vec<&str> = columns.iter().map(|column| column.relation.name.as_str()).collect();
+        let names: Vec<&str> = columns.iter().map(|column| column.column.as_str()).collect();
+
+        let rows = self.client.query(
+            r#"
+            SELECT DISTINCT
+                CASE
+                    WHEN d.classid = 'pg_proc'::regclass THEN CASE p.prokind WHEN 'p' THEN 'procedure' ELSE 'function' END
+                    WHEN v.relkind = 'm' THEN 'materialized_view'
+                    ELSE 'view'
+                END,
+                coalesce(pn.nspname, vn.nspname)::text || '.' || coalesce(p.proname, v.relname)::text,
+                altered.position
+            FROM unnest($1::text[], $2::text[], $3::text[]) WITH ORDINALITY AS altered(schema_name, relation_name, column_name, position)
+            JOIN pg_attribute a
+              ON a.attrelid = to_regclass(coalesce(quote_ident(altered.schema_name) || '.', '') || quote_ident(altered.relation_name))
+             AND a.attname = altered.column_name
+            JOIN pg_depend d
+              ON d.refclassid = 'pg_class'::regclass AND d.refobjid = a.attrelid AND d.refobjsubid = a.attnum
+            LEFT JOIN pg_rewrite r ON d.classid = 'pg_rewrite'::regclass AND r.oid = d.objid
+            LEFT JOIN pg_class v ON v.oid = r.ev_class
+            LEFT JOIN pg_namespace vn ON vn.oid = v.relnamespace
+            LEFT JOIN pg_proc p ON d.classid = 'pg_proc'::regclass AND p.oid = d.objid
+            LEFT JOIN pg_namespace pn ON pn.oid = p.pronamespace
+            -- A table's own rules depend on its columns too
+            WHERE (v.oid IS NOT NULL AND v.oid <> a.attrelid) OR p.oid IS NOT NULL
+            "#,
+            &[&schemas, &relations, &names],
+        ).await?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let dep_type_str: String = row.get(0);
+            let dep_name: String = row.get(1);
+            let position: i64 = row.get(2);
+
+            if let Some(obj_type) = self.string_to_object_type(&dep_type_str) {
+                result.push((obj_type, dep_name, columns[position as usize - 1].clone()));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Find the sequences owned by the given tables: those of serial and
+    /// identity columns, and any set with `OWNED BY`. Each is returned by its
+    /// qualified name and, in the public schema, its bare name too, as
+    /// dependencies are stored under the name they are written with.
+    pub async fn find_owned_sequences(
+        &self,
+        tables: &[String],
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if tables.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rows = self.client.query(
+            r#"
+            SELECT DISTINCT n.nspname::text, s.relname::text
+            FROM pg_depend d
+            JOIN pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+            JOIN pg_namespace n ON n.oid = s.relnamespace
+            WHERE d.classid = 'pg_class'::regclass
+            AND d.refclassid = 'pg_class'::regclass
+            AND d.deptype IN ('a', 'i')
+            AND d.refobjid IN (SELECT to_regclass(t)::oid FROM unnest($1::text[]) AS t)
+            "#,
+            &[&tables],
+        ).await?;
+
+        let mut sequences = Vec::new();
+        for row in rows {
+            let schema: String = row.get(0);
+            let name: String = row.get(1);
+
+            if schema == "public" {
+                sequences.push(name.clone());
+            }
+            sequences.push(format!("{}.{}", schema, name));
+        }
+
+        Ok(sequences)
+    }
 }
 
 #[cfg(test)]
@@ -569,11 +1126,29 @@ mod tests {
             ObjectType::CronJob => "cron_job",
             ObjectType::Aggregate => "aggregate",
             ObjectType::Operator => "operator",
+            ObjectType::SecurityLabel => "security_label",
+            ObjectType::Statistics => "statistics",
+            ObjectType::Extension => "extension",
+            ObjectType::Publication => "publication",
+            ObjectType::Collation => "collation",
+            ObjectType::TextSearchConfiguration => "text_search_configuration",
+            ObjectType::TextSearchDictionary => "text_search_dictionary",
+            ObjectType::OperatorClass => "operator_class",
+            ObjectType::OperatorFamily => "operator_family",
+            ObjectType::Cast => "cast",
         };
         
         assert_eq!(type_str, "view");
     }
 
+    #[test]
+    fn test_state_upgrades_are_sequential() {
+        for (index, upgrade) in STATE_UPGRADES.iter().enumerate() {
+            assert_eq!(upgrade.version, index as i32 + 1, "{}", upgrade.description);
+        }
+        assert_eq!(STATE_SCHEMA_VERSION, STATE_UPGRADES.len() as i32);
+    }
+
     #[test]
     fn test_qualified_name_formatting() {
         let qualified = QualifiedIdent::new(Some("api".to_string()), "user_stats".to_string());
@@ -1,4 +1,4 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::path::PathBuf;
 use tokio_postgres::NoTls;
 use url::Url;
@@ -6,6 +6,9 @@ use sha2::{Sha256, Digest};
 use std::fs;
 use crate::config::PgmgConfig;
 use owo_colors::OwoColorize;
+use crate::logging::output;
+use crate::sql::quote::{quote_ident, quote_literal};
+use tracing::{error, warn};
 
 /// Parse a PostgreSQL connection string and extract its components
 pub fn parse_connection_string(conn_str: &str) -> Result<ConnectionComponents, Box<dyn std::error::Error>> {
@@ -35,13 +38,122 @@ pub struct ConnectionComponents {
     pub password: Option<String>,
 }
 
-/// Generate a unique test database name
+/// Start of the comment put on databases created for a test run, followed by
+/// their creation time in milliseconds since the epoch
+const TEST_DATABASE_MARKER: &str = "pgmg test database created_at=";
+
+/// Longest part of the original database name kept in a test database name,
+/// leaving room for the suffix within PostgreSQL's 63 byte limit
+const MAX_BASE_NAME_LEN: usize = 30;
+
+/// Generate a unique test database name, `<base>_test_<millis>_<pid>`. The
+/// timestamp lets `gc_test_databases` tell the age of databases created
+/// before they were marked with a comment.
 pub fn generate_test_database_name(base_name: &str) -> String {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_millis();
-    format!("{}_test_{}", base_name, timestamp)
+    let mut base_len = base_name.len().min(MAX_BASE_NAME_LEN);
+    while !base_name.is_char_boundary(base_len) {
+        base_len -= 1;
+    }
+    format!("{}_test_{}_{}", &base_name[..base_len], timestamp, std::process::id())
+}
+
+/// Comment a database as created by pgmg for a test run, so
+/// `gc_test_databases` can drop it if it's left behind
+pub async fn mark_test_database(
+    admin_client: &tokio_postgres::Client,
+    test_db_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    admin_client
+        .batch_execute(&format!(
+            "COMMENT ON DATABASE {} IS {}",
+            quote_ident(test_db_name),
+            quote_literal(&format!("{}{}", TEST_DATABASE_MARKER, created_at))
+        ))
+        .await?;
+    Ok(())
+}
+
+/// When a database was created for a test run, in milliseconds since the
+/// epoch: from its pgmg comment, or else from a `<base>_test_<millis>` name.
+/// `None` for databases pgmg didn't create.
+fn test_database_created_at(name: &str, comment: Option<&str>) -> Option<u128> {
+    if let Some(millis) = comment.and_then(|comment| comment.strip_prefix(TEST_DATABASE_MARKER)) {
+        return millis.trim().parse().ok();
+    }
+    
+    let pattern = regex::Regex::new(r"_test_(\d{13})(?:_\d+)?$").unwrap();
+    pattern.captures(name)?.get(1)?.as_str().parse().ok()
+}
+
+/// A test database left behind by a run that didn't clean up after itself
+#[derive(Debug, Clone)]
+pub struct StaleTestDatabase {
+    pub name: String,
+    pub age: Duration,
+    /// Sessions are still connected, so it was left alone
+    pub in_use: bool,
+    pub dropped: bool,
+}
+
+/// Find the test databases pgmg created more than `ttl` ago and, unless
+/// `dry_run`, drop them. Databases with connected sessions are skipped, since
+/// a long test run may still be using them. Template databases are caches
+/// rather than leftovers and are never touched.
+pub async fn gc_test_databases(
+    admin_conn_str: &str,
+    ttl: Duration,
+    dry_run: bool,
+) -> Result<Vec<StaleTestDatabase>, Box<dyn std::error::Error>> {
+    let (client, connection) = tokio_postgres::connect(admin_conn_str, NoTls).await?;
+    
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            error!(error = %e, "Admin connection error");
+        }
+    });
+    
+    let rows = client
+        .query(
+            "SELECT d.datname::text,
+                    shobj_description(d.oid, 'pg_database'),
+                    (SELECT count(*) FROM pg_stat_activity a WHERE a.datid = d.oid)
+             FROM pg_database d
+             WHERE NOT d.datistemplate
+             ORDER BY d.datname",
+            &[],
+        )
+        .await?;
+    
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let mut stale = Vec::new();
+    
+    for row in rows {
+        let name: String = row.get(0);
+        let comment: Option<String> = row.get(1);
+        let sessions: i64 = row.get(2);
+        
+        let Some(created_at) = test_database_created_at(&name, comment.as_deref()) else {
+            continue;
+        };
+        let age = Duration::from_millis(now.saturating_sub(created_at) as u64);
+        if age < ttl {
+            continue;
+        }
+        
+        let in_use = sessions > 0;
+        let dropped = !in_use && !dry_run;
+        if dropped {
+            drop_test_database(admin_conn_str, &name).await?;
+        }
+        stale.push(StaleTestDatabase { name, age, in_use, dropped });
+    }
+    
+    Ok(stale)
 }
 
 /// Build a connection string from components
@@ -68,13 +180,13 @@ pub async fn create_test_database(
     
     tokio::spawn(async move {
         if let Err(e) = connection.await {
-            eprintln!("Admin connection error: {}", e);
+            error!(error = %e, "Admin connection error");
         }
     });
     
     // Create the test database
     client
-        .execute(&format!("CREATE DATABASE \"{}\"", test_db_name), &[])
+        .execute(&format!("CREATE DATABASE {}", quote_ident(test_db_name)), &[])
         .await?;
     
     Ok(())
@@ -89,7 +201,7 @@ pub async fn drop_test_database(
     
     tokio::spawn(async move {
         if let Err(e) = connection.await {
-            eprintln!("Admin connection error during cleanup: {}", e);
+            error!(error = %e, "Admin connection error during cleanup");
         }
     });
     
@@ -103,7 +215,7 @@ pub async fn drop_test_database(
     
     // Drop the test database
     client
-        .execute(&format!("DROP DATABASE IF EXISTS \"{}\"", test_db_name), &[])
+        .execute(&format!("DROP DATABASE IF EXISTS {}", quote_ident(test_db_name)), &[])
         .await?;
     
     Ok(())
@@ -127,6 +239,7 @@ impl TestDatabase {
         
         // Create the test database
         create_test_database(&admin_conn_str, &test_db_name).await?;
+        mark_new_test_database(&admin_conn_str, &test_db_name).await;
         
         // Build connection string for the new test database
         let test_conn_str = build_connection_string(&components, &test_db_name);
@@ -143,39 +256,119 @@ impl TestDatabase {
         drop_test_database(&self.admin_connection_string, &self.name).await
     }
     
+    /// Create an empty database and build it from scratch: migrations and code
+    /// applied in test mode, then the seed files in `seed_dir` if given. Nothing
+    /// is cached, so every run starts from the files as they are. The database
+    /// is dropped again if any step fails.
+    pub async fn new_fresh(
+        original_conn_str: &str,
+        migrations_dir: Option<PathBuf>,
+        code_dir: Option<PathBuf>,
+        seed_dir: Option<PathBuf>,
+        config: &PgmgConfig,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let test_db = Self::new(original_conn_str).await?;
+        
+        let built = async {
+            let apply_result = crate::commands::apply::execute_apply_with_test_mode(
+                migrations_dir,
+                code_dir,
+                test_db.connection_string.clone(),
+                config,
+                true, // test_mode
+            ).await?;
+            if !apply_result.errors.is_empty() {
+                return Err(format!("Failed to apply migrations to test database: {:?}", apply_result.errors).into());
+            }
+            
+            if let Some(seed_dir) = seed_dir {
+                let seed_result = crate::commands::seed::execute_seed(
+                    seed_dir,
+                    test_db.connection_string.clone(),
+                    &crate::commands::seed::SeedOptions::default(),
+                ).await?;
+                output::line(format!("  {} Seeded {} file(s)", output::symbol("✓").green(), seed_result.files_processed.len()));
+            }
+            
+            Ok::<_, Box<dyn std::error::Error>>(())
+        }.await;
+        
+        if let Err(e) = built {
+            let _ = test_db.cleanup().await;
+            return Err(e);
+        }
+        Ok(test_db)
+    }
+    
     /// Create a test database using a template for faster setup
     pub async fn new_with_template(
         original_conn_str: &str,
         migrations_dir: Option<PathBuf>,
         code_dir: Option<PathBuf>,
         config: &PgmgConfig,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_template(original_conn_str, migrations_dir, code_dir, None, config, false).await
+    }
+    
+    /// Clone a database built from the migration, code and seed files, like
+    /// `new_fresh` but in milliseconds. The template it's cloned from is only
+    /// rebuilt when one of those files changes, or when `rebuild_template` is
+    /// set (e.g. after changing an extension or setting the checksum can't
+    /// see). Seeded templates are kept apart from the unseeded one, so
+    /// switching between the two doesn't rebuild either.
+    pub async fn new_fresh_from_template(
+        original_conn_str: &str,
+        migrations_dir: Option<PathBuf>,
+        code_dir: Option<PathBuf>,
+        seed_dir: Option<PathBuf>,
+        config: &PgmgConfig,
+        rebuild_template: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_template(original_conn_str, migrations_dir, code_dir, seed_dir, config, rebuild_template).await
+    }
+    
+    async fn from_template(
+        original_conn_str: &str,
+        migrations_dir: Option<PathBuf>,
+        code_dir: Option<PathBuf>,
+        seed_dir: Option<PathBuf>,
+        config: &PgmgConfig,
+        rebuild_template: bool,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let components = parse_connection_string(original_conn_str)?;
-        let template_name = format!("{}_pgmg_template", components.database);
+        let template_name = if seed_dir.is_some() {
+            format!("{}_pgmg_seeded_template", components.database)
+        } else {
+            format!("{}_pgmg_template", components.database)
+        };
         let test_db_name = generate_test_database_name(&components.database);
         
         // Admin connection uses 'postgres' database
         let admin_conn_str = build_connection_string(&components, "postgres");
         
         // Check if template exists and is current
-        let template_checksum = calculate_template_checksum(&migrations_dir, &code_dir)?;
+        let template_checksum = calculate_template_checksum(&migrations_dir, &code_dir, &seed_dir)?;
         
-        if !template_exists_and_current(&admin_conn_str, &template_name, &template_checksum).await? {
-            println!("  {} Creating or updating template database...", "→".cyan());
+        if rebuild_template || !template_exists_and_current(&admin_conn_str, &template_name, &template_checksum).await? {
+            output::line(format!("  {} Creating or updating template database...", output::symbol("→").cyan()));
             create_template_database(
                 &admin_conn_str,
                 &template_name,
                 &components,
                 migrations_dir,
                 code_dir,
+                seed_dir,
                 config,
                 &template_checksum,
             ).await?;
-            println!("  {} Template database ready", "✓".green());
+            output::line(format!("  {} Template database ready", output::symbol("✓").green()));
+        } else {
+            output::line(format!("  {} Template database is up to date", output::symbol("✓").green()));
         }
         
         // Clone from template
         clone_from_template(&admin_conn_str, &template_name, &test_db_name).await?;
+        mark_new_test_database(&admin_conn_str, &test_db_name).await;
         
         // Build connection string for the new test database
         let test_conn_str = build_connection_string(&components, &test_db_name);
@@ -197,16 +390,35 @@ impl Drop for TestDatabase {
         
         tokio::spawn(async move {
             if let Err(e) = drop_test_database(&admin_conn, &db_name).await {
-                eprintln!("Failed to drop test database '{}': {}", db_name, e);
+                warn!(database = %db_name, error = %e, "Failed to drop test database");
+            }
+        });
+    }
+}
+
+/// Mark a database just created for a test run. A failure only means a
+/// leaked database is found by its name instead, so it's logged and ignored.
+async fn mark_new_test_database(admin_conn_str: &str, test_db_name: &str) {
+    let marked = async {
+        let (client, connection) = tokio_postgres::connect(admin_conn_str, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                error!(error = %e, "Admin connection error");
             }
         });
+        mark_test_database(&client, test_db_name).await
+    }.await;
+    
+    if let Err(e) = marked {
+        warn!(database = %test_db_name, error = %e, "Failed to mark test database");
     }
 }
 
-/// Calculate a checksum of all migration AND code files
+/// Calculate a checksum of all migration, code and seed files
 fn calculate_template_checksum(
     migrations_dir: &Option<PathBuf>,
     code_dir: &Option<PathBuf>,
+    seed_dir: &Option<PathBuf>,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let mut hasher = Sha256::new();
 
@@ -220,9 +432,37 @@ fn calculate_template_checksum(
         hash_sql_directory_recursive(&mut hasher, dir)?;
     }
 
+    // Hash seed directory
+    if let Some(dir) = seed_dir {
+        hash_seed_directory(&mut hasher, dir)?;
+    }
+
     Ok(format!("{:x}", hasher.finalize()))
 }
 
+/// Hash every file in a seed directory (SQL, CSV and the manifest) along with
+/// its name, since seed files run in name order and CSV names pick the table
+fn hash_seed_directory(hasher: &mut Sha256, dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .collect();
+    entries.sort_by_key(|e| e.path());
+
+    for entry in entries {
+        hasher.update(entry.file_name().to_string_lossy().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(fs::read(entry.path())?);
+        hasher.update(b"\n");
+    }
+
+    Ok(())
+}
+
 /// Hash all .sql files in a directory (non-recursive)
 fn hash_sql_directory(hasher: &mut Sha256, dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     if !dir.exists() {
@@ -295,7 +535,7 @@ async fn template_exists_and_current(
     
     tokio::spawn(async move {
         if let Err(e) = connection.await {
-            eprintln!("Admin connection error: {}", e);
+            error!(error = %e, "Admin connection error");
         }
     });
     
@@ -320,7 +560,7 @@ async fn template_exists_and_current(
     
     tokio::spawn(async move {
         if let Err(e) = template_connection.await {
-            eprintln!("Template connection error: {}", e);
+            error!(error = %e, "Template connection error");
         }
     });
     
@@ -364,6 +604,7 @@ async fn create_template_database(
     components: &ConnectionComponents,
     migrations_dir: Option<PathBuf>,
     code_dir: Option<PathBuf>,
+    seed_dir: Option<PathBuf>,
     config: &PgmgConfig,
     migrations_checksum: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -391,12 +632,27 @@ async fn create_template_database(
         return Err(format!("Failed to apply migrations to template: {:?}", apply_result.errors).into());
     }
     
+    if let Some(seed_dir) = seed_dir {
+        let seeded = crate::commands::seed::execute_seed(
+            seed_dir,
+            template_conn_str.clone(),
+            &crate::commands::seed::SeedOptions::default(),
+        ).await;
+        match seeded {
+            Ok(seed_result) => output::line(format!("  {} Seeded {} file(s)", output::symbol("✓").green(), seed_result.files_processed.len())),
+            Err(e) => {
+                let _ = drop_test_database(admin_conn_str, template_name).await;
+                return Err(format!("Failed to seed template: {}", e).into());
+            }
+        }
+    }
+    
     // Store migrations checksum
     let (client, connection) = tokio_postgres::connect(&template_conn_str, NoTls).await?;
     
     tokio::spawn(async move {
         if let Err(e) = connection.await {
-            eprintln!("Template connection error: {}", e);
+            error!(error = %e, "Template connection error");
         }
     });
     
@@ -432,17 +688,64 @@ async fn clone_from_template(
     
     tokio::spawn(async move {
         if let Err(e) = connection.await {
-            eprintln!("Admin connection error: {}", e);
+            error!(error = %e, "Admin connection error");
         }
     });
     
     // Clone from template - this is MUCH faster than running migrations
     client
         .execute(
-            &format!("CREATE DATABASE \"{}\" WITH TEMPLATE \"{}\"", new_db_name, template_name),
+            &format!("CREATE DATABASE {} WITH TEMPLATE {}", quote_ident(new_db_name), quote_ident(template_name)),
             &[],
         )
         .await?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_test_database_name() {
+        let name = generate_test_database_name("orders");
+        assert!(name.starts_with("orders_test_"));
+        assert!(test_database_created_at(&name, None).is_some());
+        
+        let name = generate_test_database_name(&"a_very_long_application_database_name".repeat(3));
+        assert!(name.len() <= 63, "{} is too long", name);
+    }
+
+    #[test]
+    fn test_database_created_at_from_comment_or_name() {
+        assert_eq!(test_database_created_at("scratch", Some("pgmg test database created_at=1718000000000")), Some(1718000000000));
+        assert_eq!(test_database_created_at("app_test_1718000000000", None), Some(1718000000000));
+        assert_eq!(test_database_created_at("app_test_1718000000000_4242", None), Some(1718000000000));
+        
+        // Not created by pgmg
+        assert_eq!(test_database_created_at("app", None), None);
+        assert_eq!(test_database_created_at("app_test_results", Some("Nightly test results")), None);
+        assert_eq!(test_database_created_at("app_pgmg_template", None), None);
+    }
+
+    #[test]
+    fn test_template_checksum_covers_seed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let seed_dir = Some(dir.path().to_path_buf());
+        std::fs::write(dir.path().join("1_users.sql"), "INSERT INTO users VALUES (1);").unwrap();
+        
+        let unseeded = calculate_template_checksum(&None, &None, &None).unwrap();
+        let seeded = calculate_template_checksum(&None, &None, &seed_dir).unwrap();
+        assert_ne!(unseeded, seeded);
+        assert_eq!(seeded, calculate_template_checksum(&None, &None, &seed_dir).unwrap());
+        
+        std::fs::write(dir.path().join("public.orders.csv"), "id\n1\n").unwrap();
+        let with_csv = calculate_template_checksum(&None, &None, &seed_dir).unwrap();
+        assert_ne!(seeded, with_csv);
+        
+        // Renaming changes the order seed files run in
+        std::fs::rename(dir.path().join("1_users.sql"), dir.path().join("2_users.sql")).unwrap();
+        assert_ne!(with_csv, calculate_template_checksum(&None, &None, &seed_dir).unwrap());
+    }
+}
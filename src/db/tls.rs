@@ -198,7 +198,7 @@ impl PgConnection {
             PgConnection::NoTls(conn) => {
                 tokio::spawn(async move {
                     if let Err(e) = conn.await {
-                        eprintln!("Database connection error: {}", e);
+                        tracing::error!(error = %e, "Database connection error");
                     }
                 });
             }
@@ -238,7 +238,7 @@ pub async fn connect_with_tls(
                         // Spawn the TLS connection handler immediately
                         tokio::spawn(async move {
                             if let Err(e) = connection.await {
-                                eprintln!("TLS connection error: {}", e);
+                                tracing::error!(error = %e, "TLS connection error");
                             }
                         });
                         Ok((client, PgConnection::Rustls(Box::new(()))))
@@ -254,7 +254,7 @@ pub async fn connect_with_tls(
                 // Spawn the TLS connection handler immediately
                 tokio::spawn(async move {
                     if let Err(e) = connection.await {
-                        eprintln!("TLS connection error: {}", e);
+                        tracing::error!(error = %e, "TLS connection error");
                     }
                 });
                 Ok((client, PgConnection::Rustls(Box::new(()))))
@@ -0,0 +1,139 @@
+use tokio_postgres::Client;
+use tracing::debug;
+use crate::sql::ObjectType;
+
+/// How often a planned drop/recreate target is referenced by production queries,
+/// according to pg_stat_statements
+#[derive(Debug, Clone)]
+pub struct ObjectUsage {
+    pub object_type: ObjectType,
+    pub object_name: String,
+    /// Whether the object is being dropped for good (vs. recreated)
+    pub is_delete: bool,
+    /// Total calls of statements referencing the object since stats were last reset
+    pub calls: i64,
+    /// Calls per minute, averaged over the time since stats were last reset
+    pub calls_per_minute: Option<f64>,
+}
+
+impl ObjectUsage {
+    /// Human-readable risk note, e.g. "recreation will invalidate plans"
+    pub fn risk(&self) -> &'static str {
+        if self.is_delete {
+            "drop will break callers"
+        } else {
+            match self.object_type {
+                ObjectType::Function | ObjectType::Procedure | ObjectType::Aggregate | ObjectType::Operator => {
+                    "recreation will invalidate plans"
+                }
+                ObjectType::MaterializedView => "recreation will block readers until repopulated",
+                _ => "recreation will invalidate plans and briefly block queries",
+            }
+        }
+    }
+}
+
+/// Check whether pg_stat_statements is installed in the current database
+pub async fn is_pg_stat_statements_available(client: &Client) -> Result<bool, Box<dyn std::error::Error>> {
+    let row = client.query_one(
+        "SELECT EXISTS(SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements')",
+        &[],
+    ).await?;
+    Ok(row.get(0))
+}
+
+/// Look up how often each object is referenced by statements in pg_stat_statements.
+///
+/// Matching is done on the query text, so this is an estimate: every statement
+/// mentioning the object's name counts towards its calls. Objects with no
+/// matching statements are omitted.
+pub async fn collect_object_usage(
+    client: &Client,
+    objects: &[(ObjectType, String, bool)],
+) -> Result<Vec<ObjectUsage>, Box<dyn std::error::Error>> {
+    let minutes_since_reset = stats_age_minutes(client).await?;
+    debug!("pg_stat_statements window: {:?} minutes", minutes_since_reset);
+
+    let mut usage = Vec::new();
+    for (object_type, object_name, is_delete) in objects {
+        let bare_name = object_name.rsplit('.').next().unwrap_or(object_name);
+        let pattern = format!(r"\m{}\M", regex::escape(bare_name));
+
+        let row = client.query_one(
+            "SELECT COALESCE(SUM(calls), 0)::bigint FROM pg_stat_statements WHERE query ~* $1",
+            &[&pattern],
+        ).await?;
+        let calls: i64 = row.get(0);
+
+        if calls > 0 {
+            usage.push(ObjectUsage {
+                object_type: object_type.clone(),
+                object_name: object_name.clone(),
+                is_delete: *is_delete,
+                calls,
+                calls_per_minute: minutes_since_reset
+                    .filter(|minutes| *minutes > 0.0)
+                    .map(|minutes| calls as f64 / minutes),
+            });
+        }
+    }
+
+    // Busiest objects first
+    usage.sort_by(|a, b| b.calls.cmp(&a.calls));
+    Ok(usage)
+}
+
+/// Minutes since pg_stat_statements was last reset (PostgreSQL 14+), falling
+/// back to the server start time on older versions
+async fn stats_age_minutes(client: &Client) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+    let query = "SELECT EXTRACT(EPOCH FROM (now() - stats_reset))::float8 / 60 FROM pg_stat_statements_info";
+    let row = match client.query_opt(query, &[]).await {
+        Ok(row) => row,
+        Err(_) => client.query_opt(
+            "SELECT EXTRACT(EPOCH FROM (now() - pg_postmaster_start_time()))::float8 / 60",
+            &[],
+        ).await?,
+    };
+    Ok(row.and_then(|r| r.get::<_, Option<f64>>(0)))
+}
+
+/// Format a call rate compactly, e.g. `1.2k`
+pub fn format_rate(rate: f64) -> String {
+    if rate >= 1_000_000.0 {
+        format!("{:.1}M", rate / 1_000_000.0)
+    } else if rate >= 1_000.0 {
+        format!("{:.1}k", rate / 1_000.0)
+    } else if rate >= 10.0 {
+        format!("{:.0}", rate)
+    } else {
+        format!("{:.1}", rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(format_rate(1234.0), "1.2k");
+        assert_eq!(format_rate(2_500_000.0), "2.5M");
+        assert_eq!(format_rate(42.4), "42");
+        assert_eq!(format_rate(0.3), "0.3");
+    }
+
+    #[test]
+    fn test_risk_messages() {
+        let usage = ObjectUsage {
+            object_type: ObjectType::Function,
+            object_name: "api.get_cart".to_string(),
+            is_delete: false,
+            calls: 100,
+            calls_per_minute: None,
+        };
+        assert_eq!(usage.risk(), "recreation will invalidate plans");
+
+        let dropped = ObjectUsage { is_delete: true, ..usage };
+        assert_eq!(dropped.risk(), "drop will break callers");
+    }
+}
@@ -0,0 +1,196 @@
+//! Line diffs between the DDL an object was last applied with and its
+//! definition in the code directory, for showing what an update changes.
+
+/// Unchanged lines kept around each change
+pub const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Context(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A run of changes and the context around them. Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine<'a>>,
+}
+
+impl Hunk<'_> {
+    /// The `@@ -1,4 +1,5 @@` line that starts the hunk
+    pub fn header(&self) -> String {
+        format!("@@ -{} +{} @@", range(self.old_start, self.old_len), range(self.new_start, self.new_len))
+    }
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        1 => start.to_string(),
+        // An empty range is numbered by the line before it
+        0 => format!("{},0", start.saturating_sub(1)),
+        _ => format!("{},{}", start, len),
+    }
+}
+
+/// Every line of `old` and `new`, marked as kept, removed or added, using the
+/// longest common subsequence of their lines
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Lines shared at the start and end don't need the table
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]: length of the longest common subsequence of old_mid[i..] and new_mid[j..]
+    let mut lcs = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(|line| DiffLine::Context(line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            lines.push(DiffLine::Context(old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if j < new_mid.len() && (i == old_mid.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(DiffLine::Added(new_mid[j]));
+            j += 1;
+        } else {
+            lines.push(DiffLine::Removed(old_mid[i]));
+            i += 1;
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| DiffLine::Context(line)));
+
+    // Removals read better before the additions that replace them
+    for run in lines.split_mut(|line| matches!(line, DiffLine::Context(_))) {
+        run.sort_by_key(|line| matches!(line, DiffLine::Added(_)));
+    }
+    lines
+}
+
+/// The changes between `old` and `new`, grouped into hunks with up to
+/// `context` unchanged lines around them. Empty if they're the same.
+pub fn hunks<'a>(old: &'a str, new: &'a str, context: usize) -> Vec<Hunk<'a>> {
+    let lines = diff_lines(old, new);
+    let changed: Vec<usize> = lines.iter().enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    // Changes whose context would touch or overlap share a hunk
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(lines.len());
+        match spans.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => spans.push((start, end)),
+        }
+    }
+
+    spans.into_iter().map(|(start, end)| {
+        let (before, span) = (&lines[..start], &lines[start..end]);
+        Hunk {
+            old_start: before.iter().filter(|line| in_old(line)).count() + 1,
+            old_len: span.iter().filter(|line| in_old(line)).count(),
+            new_start: before.iter().filter(|line| in_new(line)).count() + 1,
+            new_len: span.iter().filter(|line| in_new(line)).count(),
+            lines: span.to_vec(),
+        }
+    }).collect()
+}
+
+fn in_old(line: &DiffLine) -> bool {
+    !matches!(line, DiffLine::Added(_))
+}
+
+fn in_new(line: &DiffLine) -> bool {
+    !matches!(line, DiffLine::Removed(_))
+}
+
+/// A unified diff of `old` and `new`, without file headers. Empty if they're
+/// the same.
+pub fn unified_diff(old: &str, new: &str) -> String {
+    let mut diff = String::new();
+    for hunk in hunks(old, new, CONTEXT_LINES) {
+        diff.push_str(&hunk.header());
+        diff.push('\n');
+        for line in &hunk.lines {
+            let (marker, text) = match line {
+                DiffLine::Context(text) => (' ', text),
+                DiffLine::Removed(text) => ('-', text),
+                DiffLine::Added(text) => ('+', text),
+            };
+            diff.push(marker);
+            diff.push_str(text);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "SELECT id,\n       total\nFROM orders;";
+        let new = "SELECT id,\n       total,\n       status\nFROM orders;";
+
+        assert_eq!(diff_lines(old, new), vec![
+            DiffLine::Context("SELECT id,"),
+            DiffLine::Removed("       total"),
+            DiffLine::Added("       total,"),
+            DiffLine::Added("       status"),
+            DiffLine::Context("FROM orders;"),
+        ]);
+        assert!(diff_lines(old, old).iter().all(|line| matches!(line, DiffLine::Context(_))));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = (1..=12).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        let new = old.replace("line 2\n", "line two\n").replace("line 11", "line eleven");
+
+        assert_eq!(unified_diff(&old, &new), "\
+@@ -1,5 +1,5 @@
+ line 1
+-line 2
++line two
+ line 3
+ line 4
+ line 5
+@@ -8,5 +8,5 @@
+ line 8
+ line 9
+ line 10
+-line 11
++line eleven
+ line 12
+");
+        assert_eq!(unified_diff(&old, &old), "");
+    }
+
+    #[test]
+    fn test_unified_diff_added_to_empty() {
+        assert_eq!(unified_diff("", "CREATE VIEW v AS\nSELECT 1;"), "@@ -0,0 +1,2 @@\n+CREATE VIEW v AS\n+SELECT 1;\n");
+    }
+}
@@ -0,0 +1,291 @@
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio_postgres::Client;
+use tracing::{info, info_span, Instrument};
+use crate::cancel::CancellationToken;
+use crate::commands::apply::{execute_apply_inner, ApplyHooks, ApplyResult};
+use crate::config::PgmgConfig;
+use crate::db::AdvisoryLockManager;
+
+/// Migration and code files held in memory, for services that ship as a single
+/// binary without a sql/ directory next to it.
+///
+/// Paths are relative to the migrations or code directory, and subdirectories
+/// are kept (code files ending in `_test.sql` are skipped as usual).
+///
+/// # Example
+/// With the `include_dir` crate:
+/// ```ignore
+/// static MIGRATIONS: include_dir::Dir = include_dir::include_dir!("$CARGO_MANIFEST_DIR/migrations");
+/// static SQL: include_dir::Dir = include_dir::include_dir!("$CARGO_MANIFEST_DIR/sql");
+///
+/// fn files(dir: &'static include_dir::Dir) -> impl Iterator<Item = (&'static Path, &'static str)> {
+///     dir.find("**/*.sql").unwrap()
+///         .filter_map(|entry| entry.as_file())
+///         .filter_map(|file| Some((file.path(), file.contents_utf8()?)))
+/// }
+///
+/// let sql = EmbeddedSql::new()
+///     .with_migrations(files(&MIGRATIONS))
+///     .with_code_files(files(&SQL));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddedSql {
+    migrations: Vec<(PathBuf, String)>,
+    code_files: Vec<(PathBuf, String)>,
+}
+
+impl EmbeddedSql {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_migration(mut self, path: impl Into<PathBuf>, contents: impl Into<String>) -> Self {
+        self.migrations.push((path.into(), contents.into()));
+        self
+    }
+
+    pub fn with_migrations<P: Into<PathBuf>, S: Into<String>>(mut self, files: impl IntoIterator<Item = (P, S)>) -> Self {
+        self.migrations.extend(files.into_iter().map(|(path, contents)| (path.into(), contents.into())));
+        self
+    }
+
+    pub fn with_code_file(mut self, path: impl Into<PathBuf>, contents: impl Into<String>) -> Self {
+        self.code_files.push((path.into(), contents.into()));
+        self
+    }
+
+    pub fn with_code_files<P: Into<PathBuf>, S: Into<String>>(mut self, files: impl IntoIterator<Item = (P, S)>) -> Self {
+        self.code_files.extend(files.into_iter().map(|(path, contents)| (path.into(), contents.into())));
+        self
+    }
+
+    /// Write the files to a temporary directory so the regular scanners can
+    /// read them. Returns the directory, which is removed on drop, and the
+    /// migrations and code directories inside it (if there are any such files).
+    pub fn materialize(&self) -> Result<(TempDir, Option<PathBuf>, Option<PathBuf>), Box<dyn std::error::Error>> {
+        let root = tempfile::Builder::new().prefix("pgmg-embedded-").tempdir()?;
+
+        let migrations_dir = write_files(root.path(), "migrations", &self.migrations)?;
+        let code_dir = write_files(root.path(), "sql", &self.code_files)?;
+
+        Ok((root, migrations_dir, code_dir))
+    }
+}
+
+fn write_files(root: &Path, name: &str, files: &[(PathBuf, String)]) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = root.join(name);
+    std::fs::create_dir_all(&dir)?;
+
+    for (path, contents) in files {
+        if !path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return Err(format!("Embedded SQL path must be relative and stay inside its directory: {}", path.display()).into());
+        }
+
+        let file_path = dir.join(path);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file_path, contents)?;
+    }
+
+    Ok(Some(dir))
+}
+
+/// Options for [`migrate`]
+#[derive(Debug, Clone)]
+pub struct MigrateOptions {
+    pub migrations_dir: Option<PathBuf>,
+    pub code_dir: Option<PathBuf>,
+    /// Files to use instead of `migrations_dir` and `code_dir`
+    pub embedded_sql: Option<EmbeddedSql>,
+    /// How long to wait for another instance's apply to finish
+    pub lock_timeout: Duration,
+    /// Connection string used only to derive the advisory lock key. Set this to
+    /// the URL the CLI uses if both may apply to the same database; otherwise
+    /// the key is derived from `config`'s connection string, or failing that
+    /// from the address, port and database the server reports.
+    pub lock_connection_string: Option<String>,
+    /// Settings such as plpgsql checking, replication safety and the apply
+    /// lock namespace. The connection string is only handed to pg_dump by
+    /// `backup_before_destructive`; TLS settings and the apply lock timeout
+    /// are ignored, see `lock_timeout`.
+    pub config: PgmgConfig,
+    /// Stops the migration when cancelled, e.g. when the application is
+    /// asked to shut down while it waits for the lock
+    pub cancellation: CancellationToken,
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        Self {
+            migrations_dir: None,
+            code_dir: None,
+            embedded_sql: None,
+            lock_timeout: Duration::from_secs(30),
+            lock_connection_string: None,
+            config: PgmgConfig::default(),
+            cancellation: CancellationToken::new(),
+        }
+    }
+}
+
+impl MigrateOptions {
+    pub fn new(migrations_dir: impl Into<PathBuf>, code_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            migrations_dir: Some(migrations_dir.into()),
+            code_dir: Some(code_dir.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Migrate from files held in memory rather than on disk
+    pub fn embedded(sql: EmbeddedSql) -> Self {
+        Self {
+            embedded_sql: Some(sql),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    pub fn with_lock_connection_string(mut self, connection_string: impl Into<String>) -> Self {
+        self.lock_connection_string = Some(connection_string.into());
+        self
+    }
+
+    pub fn with_config(mut self, config: PgmgConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+/// Apply pending migrations and code changes over a connection owned by the
+/// host application.
+///
+/// pgmg opens no connections of its own. Concurrent callers (e.g. several
+/// replicas of a service booting at once) are serialized with an advisory lock,
+/// so only one applies and the others find nothing left to do.
+///
+/// A `deadpool_postgres::Object` can be passed as `&mut **conn`.
+///
+/// # Example
+/// ```no_run
+/// use pgmg::embedded::{migrate, MigrateOptions};
+///
+/// async fn on_startup(database_url: &str) -> Result<(), Box<dyn std::error::Error>> {
+///     let (mut client, connection) = tokio_postgres::connect(database_url, tokio_postgres::NoTls).await?;
+///     tokio::spawn(connection);
+///
+///     migrate(&mut client, &MigrateOptions::new("migrations", "sql")).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn migrate(
+    client: &mut Client,
+    options: &MigrateOptions,
+) -> crate::error::Result<ApplyResult> {
+    let lock_manager = match options.lock_connection_string.as_ref().or(options.config.connection_string.as_ref()) {
+        Some(connection_string) => options.config.apply_lock_manager(connection_string),
+        None => AdvisoryLockManager::for_connection_with_namespace(client, options.config.apply_lock_namespace()).await?,
+    };
+
+    // Embedded files are written out for the duration of the apply
+    let (_embedded_root, migrations_dir, code_dir) = match &options.embedded_sql {
+        Some(sql) => {
+            let (root, migrations_dir, code_dir) = sql.materialize()?;
+            (Some(root), migrations_dir, code_dir)
+        }
+        None => (
+            None,
+            options.migrations_dir.clone().or_else(|| options.config.migrations_dir.clone()),
+            options.code_dir.clone().or_else(|| options.config.code_dir.clone()),
+        ),
+    };
+
+    info!("Starting embedded database migrations");
+
+    let span = info_span!("embedded_migrate");
+    let result = execute_apply_inner(
+        client,
+        lock_manager,
+        options.lock_timeout,
+        None,
+        migrations_dir,
+        code_dir,
+        &options.config,
+        false,
+        &ApplyHooks::default(),
+        &options.cancellation,
+    ).instrument(span).await?;
+
+    info!(
+        migrations_applied = result.migrations_applied.len(),
+        objects_created = result.objects_created.len(),
+        objects_updated = result.objects_updated.len(),
+        objects_deleted = result.objects_deleted.len(),
+        "Embedded migration completed successfully"
+    );
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_materialize_embedded_sql() {
+        let sql = EmbeddedSql::new()
+            .with_migration("001_users.sql", "CREATE TABLE users (id integer);")
+            .with_code_files([
+                ("views/active_users.sql", "CREATE VIEW active_users AS SELECT id FROM users;"),
+            ]);
+
+        let (root, migrations_dir, code_dir) = sql.materialize().unwrap();
+        let migrations_dir = migrations_dir.unwrap();
+        let code_dir = code_dir.unwrap();
+
+        assert!(migrations_dir.starts_with(root.path()));
+        assert_eq!(
+            std::fs::read_to_string(migrations_dir.join("001_users.sql")).unwrap(),
+            "CREATE TABLE users (id integer);"
+        );
+        assert!(code_dir.join("views/active_users.sql").is_file());
+
+        let root_path = root.path().to_path_buf();
+        drop(root);
+        assert!(!root_path.exists());
+    }
+
+    #[test]
+    fn test_materialize_rejects_escaping_paths() {
+        let sql = EmbeddedSql::new().with_code_file("../outside.sql", "SELECT 1;");
+        assert!(sql.materialize().is_err());
+
+        let sql = EmbeddedSql::new().with_code_file("/etc/outside.sql", "SELECT 1;");
+        assert!(sql.materialize().is_err());
+    }
+
+    #[test]
+    fn test_materialize_without_code_files() {
+        let sql = EmbeddedSql::new().with_migration("001_users.sql", "CREATE TABLE users (id integer);");
+        let (_root, migrations_dir, code_dir) = sql.materialize().unwrap();
+
+        assert!(migrations_dir.is_some());
+        assert!(code_dir.is_none());
+    }
+}
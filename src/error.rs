@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 use std::env;
 
@@ -66,8 +67,15 @@ pub enum PgmgError {
     DirectoryNotFound(PathBuf),
 
     // Dependency Resolution Errors
-    #[error("Circular dependency detected: {0}")]
-    CircularDependency(String),
+    /// Objects in the code directory that depend on each other in a cycle.
+    /// `description` lists each dependency in it and how to break it.
+    #[error("{description}")]
+    DependencyCycle {
+        /// The objects in the cycle, each a dependency of the next and the
+        /// last of the first
+        path: Vec<String>,
+        description: String,
+    },
 
     #[error("Missing dependency: {object} depends on {dependency} which doesn't exist")]
     MissingDependency {
@@ -89,10 +97,23 @@ pub enum PgmgError {
     },
 
     // Migration Errors
-    #[error("Migration {name} failed at statement {statement}: {message}")]
+    #[error("Migration {name} failed at statement {statement_index}: {}", postgres_message(.pg_error))]
     MigrationFailed {
         name: String,
-        statement: usize,
+        /// Position of the failing statement in the file, starting at 1
+        statement_index: usize,
+        file: PathBuf,
+        /// Line of the file the statement starts on
+        line: Option<usize>,
+        sql: String,
+        #[source]
+        pg_error: tokio_postgres::Error,
+    },
+
+    /// Pending migrations that don't parse, found before any of them runs
+    #[error("{message}")]
+    MigrationParse {
+        names: Vec<String>,
         message: String,
     },
 
@@ -128,6 +149,17 @@ pub enum PgmgError {
     
     #[error("Lock acquisition failed: {0}")]
     LockFailed(String),
+
+    /// Another session held the apply lock for all of `timeout`
+    #[error("Could not acquire lock for apply operation {}.\n\
+        Another pgmg apply process may be running against this database.\n\
+        {}If you're sure no other process is running, the lock is stale: run `pgmg unlock` to see who holds it \
+        and `pgmg unlock --force` to end that session.", lock_wait(.timeout), lock_holders(.holders))]
+    LockTimeout {
+        timeout: Duration,
+        /// The sessions holding the lock when the wait ran out
+        holders: Vec<String>,
+    },
     
     #[error("Resource cleanup failed: {0}")]
     CleanupFailed(String),
@@ -204,7 +236,52 @@ impl From<pg_query::Error> for PgmgError {
 
 impl From<Box<dyn std::error::Error>> for PgmgError {
     fn from(err: Box<dyn std::error::Error>) -> Self {
-        PgmgError::Other(err.to_string())
+        // Errors raised as a PgmgError and boxed on the way up keep their kind
+        let err = match err.downcast::<PgmgError>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+        match err.downcast::<tokio_postgres::Error>() {
+            Ok(err) => PgmgError::from(*err),
+            Err(err) => PgmgError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<String> for PgmgError {
+    fn from(message: String) -> Self {
+        PgmgError::Other(message)
+    }
+}
+
+impl From<&str> for PgmgError {
+    fn from(message: &str) -> Self {
+        PgmgError::Other(message.to_string())
+    }
+}
+
+/// The server's message for a PostgreSQL error, without the `db error:`
+/// prefix of its Display
+fn postgres_message(err: &tokio_postgres::Error) -> String {
+    match err.as_db_error() {
+        Some(db_err) => db_err.message().to_string(),
+        None => err.to_string(),
+    }
+}
+
+fn lock_wait(timeout: &Duration) -> String {
+    if timeout.is_zero() {
+        "without waiting (lock wait is disabled)".to_string()
+    } else {
+        format!("after {} seconds", timeout.as_secs())
+    }
+}
+
+fn lock_holders(holders: &[String]) -> String {
+    if holders.is_empty() {
+        String::new()
+    } else {
+        format!("The lock is held by {}.\n", holders.join(", "))
     }
 }
 
@@ -306,10 +383,20 @@ pub fn suggest_fix(err: &PgmgError) -> Option<String> {
                     - Check file permissions\n\
                     - You may need to run with appropriate privileges", path.display())
         ),
-        PgmgError::CircularDependency(details) => Some(
+        PgmgError::DependencyCycle { path, .. } => Some(
             format!("Circular dependency detected: {}\n\
                     - Review your SQL object dependencies\n\
-                    - Consider breaking the circular reference", details)
+                    - Consider breaking the circular reference",
+                    path.iter().chain(path.first()).map(String::as_str).collect::<Vec<_>>().join(" -> "))
+        ),
+        PgmgError::MigrationFailed { name, statement_index, file, line, .. } => Some(
+            format!("Statement {} of {}{} failed.\n\
+                    - Fix the statement and run apply again\n\
+                    - {} is not recorded as applied, so it runs again from the start",
+                    statement_index, file.display(), line.map(|line| format!(" (line {})", line)).unwrap_or_default(), name)
+        ),
+        PgmgError::LockTimeout { .. } => Some(
+            "If applies are expected to take longer, raise timeout_secs under [apply_lock] in pgmg.toml".to_string()
         ),
         PgmgError::MissingDependency { object, dependency } => Some(
             format!("Object '{}' depends on '{}' which doesn't exist.\n\
@@ -498,6 +585,21 @@ mod tests {
         // Position at 'c' in carrier_code
         assert_eq!(calculate_line_column(sql, 15), (1, 16));
     }
+
+    #[test]
+    fn test_boxed_pgmg_error_keeps_its_kind() {
+        let boxed: Box<dyn std::error::Error> = PgmgError::LockTimeout {
+            timeout: Duration::from_secs(5),
+            holders: Vec::new(),
+        }.into();
+        assert!(matches!(
+            PgmgError::from(boxed),
+            PgmgError::LockTimeout { timeout, .. } if timeout == Duration::from_secs(5)
+        ));
+
+        let boxed: Box<dyn std::error::Error> = "Pre-drop failed".into();
+        assert!(matches!(PgmgError::from(boxed), PgmgError::Other(message) if message == "Pre-drop failed"));
+    }
 }
 
 /// A RAII guard for safely changing the current working directory
@@ -557,8 +659,7 @@ impl Drop for DirectoryGuard {
     fn drop(&mut self) {
         if self.changed {
             if let Err(e) = env::set_current_dir(&self.original_dir) {
-                eprintln!("Warning: Failed to restore directory to {}: {}", 
-                         self.original_dir.display(), e);
+                tracing::warn!(directory = %self.original_dir.display(), error = %e, "Failed to restore directory");
                 // We can't return an error from Drop, so we just log the issue
             }
         }
@@ -0,0 +1,87 @@
+//! The git checkout migrations and code files are applied from, recorded
+//! with each apply so a change can be traced back to the commit that made it.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The commit checked out in a git working tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitHead {
+    pub commit: String,
+    /// Whether the directories looked at have changes that aren't committed,
+    /// untracked files included
+    pub dirty: bool,
+}
+
+/// HEAD of the git repository `dirs` are in, and whether any of them have
+/// uncommitted changes. `None` when the first isn't in a git repository or
+/// git isn't installed.
+pub fn git_head(dirs: &[PathBuf]) -> Option<GitHead> {
+    let repo = dirs.first().map(PathBuf::as_path).unwrap_or(Path::new("."));
+    let commit = git(repo, &["rev-parse", "HEAD"])?;
+
+    // Paths are given absolute, as git resolves them against `repo`
+    let paths: Vec<String> = dirs.iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .map(|dir| dir.display().to_string())
+        .collect();
+    let mut status = vec!["status", "--porcelain", "--"];
+    status.extend(paths.iter().map(String::as_str));
+    let changes = git(repo, &status)?;
+
+    Some(GitHead { commit, dirty: !changes.is_empty() })
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C").arg(dir)
+            .args(["-c", "user.name=pgmg", "-c", "user.email=pgmg@example.com"])
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_git_head() {
+        let repo = tempfile::tempdir().unwrap();
+        let migrations = repo.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(migrations.join("001_orders.sql"), "CREATE TABLE orders (id int);").unwrap();
+
+        // Not a repository yet
+        assert_eq!(git_head(&[migrations.clone()]), None);
+
+        run_git(repo.path(), &["init", "-q"]);
+        run_git(repo.path(), &["add", "."]);
+        run_git(repo.path(), &["commit", "-q", "-m", "Add orders"]);
+
+        let head = git_head(&[migrations.clone()]).unwrap();
+        assert_eq!(head.commit.len(), 40);
+        assert!(!head.dirty);
+
+        // Changes outside the directories looked at don't count
+        std::fs::write(repo.path().join("README.md"), "notes").unwrap();
+        assert!(!git_head(&[migrations.clone()]).unwrap().dirty);
+
+        std::fs::write(migrations.join("002_customers.sql"), "CREATE TABLE customers (id int);").unwrap();
+        let dirty = git_head(&[migrations]).unwrap();
+        assert_eq!(dirty.commit, head.commit);
+        assert!(dirty.dirty);
+    }
+}
@@ -0,0 +1,206 @@
+//! Hooks configured in `[hooks]`: SQL run on pgmg's connection, or shell
+//! commands, before and after plan, apply and each migration. Commands get
+//! the event and environment in `PGMG_HOOK` and `PGMG_ENV`, and the changes
+//! as JSON on stdin.
+
+use std::process::Stdio;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio_postgres::GenericClient;
+use crate::commands::{ApplyResult, ChangeOperation, PlanResult};
+use crate::sql::SqlObject;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    PrePlan,
+    PostPlan,
+    PreApply,
+    PostApply,
+    PreMigration,
+    PostMigration,
+}
+
+impl HookEvent {
+    /// The event's key in `[hooks]`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PrePlan => "pre_plan",
+            HookEvent::PostPlan => "post_plan",
+            HookEvent::PreApply => "pre_apply",
+            HookEvent::PostApply => "post_apply",
+            HookEvent::PreMigration => "pre_migration",
+            HookEvent::PostMigration => "post_migration",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookAction {
+    /// SQL run on the connection plan or apply uses, inside the apply
+    /// transaction for migration hooks
+    Sql(String),
+    /// Command run with `sh -c`
+    Run(String),
+}
+
+/// The hooks for the environment in use, in the order they're configured
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    pub hooks: Vec<(HookEvent, HookAction)>,
+    pub environment: Option<String>,
+}
+
+/// What a hook is run for: the changes planned or applied, and for migration
+/// hooks the migration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookContext {
+    pub migration: Option<String>,
+    pub migrations: Vec<String>,
+    pub objects_created: Vec<String>,
+    pub objects_updated: Vec<String>,
+    pub objects_deleted: Vec<String>,
+}
+
+impl HookContext {
+    pub fn for_migration(name: &str) -> Self {
+        Self { migration: Some(name.to_string()), ..Self::default() }
+    }
+
+    /// The changes a plan would make
+    pub fn planned(plan: &PlanResult) -> Self {
+        let mut context = Self { migrations: plan.new_migrations.clone(), ..Self::default() };
+        for change in &plan.changes {
+            match change {
+                ChangeOperation::CreateObject { object, .. } => context.objects_created.push(object_name(object)),
+                ChangeOperation::UpdateObject { object, .. } => context.objects_updated.push(object_name(object)),
+                ChangeOperation::DeleteObject { object_name, .. } => context.objects_deleted.push(object_name.clone()),
+                ChangeOperation::ApplyMigration { .. } => {}
+            }
+        }
+        context
+    }
+
+    /// The changes an apply made
+    pub fn applied(result: &ApplyResult) -> Self {
+        Self {
+            migration: None,
+            migrations: result.migrations_applied.clone(),
+            objects_created: result.objects_created.clone(),
+            objects_updated: result.objects_updated.clone(),
+            objects_deleted: result.objects_deleted.clone(),
+        }
+    }
+
+    fn to_json(&self, event: HookEvent, environment: Option<&str>) -> Value {
+        json!({
+            "event": event.name(),
+            "environment": environment,
+            "migration": self.migration,
+            "migrations": self.migrations,
+            "objects": {
+                "created": self.objects_created,
+                "updated": self.objects_updated,
+                "deleted": self.objects_deleted,
+            },
+        })
+    }
+}
+
+fn object_name(object: &SqlObject) -> String {
+    match &object.qualified_name.schema {
+        Some(schema) => format!("{}.{}", schema, object.qualified_name.name),
+        None => object.qualified_name.name.clone(),
+    }
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Run the hooks for `event` in order, stopping at the first that fails
+    pub async fn run<C: GenericClient>(
+        &self,
+        event: HookEvent,
+        client: &C,
+        context: &HookContext,
+    ) -> Result<(), String> {
+        for (_, action) in self.hooks.iter().filter(|(hook_event, _)| *hook_event == event) {
+            tracing::debug!(hook = event.name(), ?action, "Running hook");
+            match action {
+                HookAction::Sql(sql) => client.batch_execute(sql).await
+                    .map_err(|e| format!("{} hook failed: {}\nSQL: {}", event.name(), e, sql))?,
+                HookAction::Run(command) => self.run_command(event, command, context).await
+                    .map_err(|e| format!("{} hook failed: {}\nCommand: {}", event.name(), e, command))?,
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_command(&self, event: HookEvent, command: &str, context: &HookContext) -> Result<(), String> {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command)
+            .env("PGMG_HOOK", event.name())
+            .stdin(Stdio::piped());
+        if let Some(environment) = &self.environment {
+            cmd.env("PGMG_ENV", environment);
+        }
+        if let Some(migration) = &context.migration {
+            cmd.env("PGMG_MIGRATION", migration);
+        }
+
+        let mut child = cmd.spawn().map_err(|e| e.to_string())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let payload = context.to_json(event, self.environment.as_deref()).to_string();
+            // Commands that don't read stdin may exit before it's written
+            let _ = stdin.write_all(payload.as_bytes()).await;
+        }
+
+        let status = child.wait().await.map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("exited with {}", status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_context_json() {
+        let context = HookContext {
+            migrations: vec!["003_add_orders".to_string()],
+            objects_updated: vec!["api.orders".to_string()],
+            ..HookContext::default()
+        };
+
+        assert_eq!(context.to_json(HookEvent::PostApply, Some("production")), json!({
+            "event": "post_apply",
+            "environment": "production",
+            "migration": null,
+            "migrations": ["003_add_orders"],
+            "objects": { "created": [], "updated": ["api.orders"], "deleted": [] },
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_gets_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("hook.out");
+        let command = format!("echo \"$PGMG_HOOK $PGMG_ENV $PGMG_MIGRATION\" > {0} && cat >> {0}", out.display());
+        let hooks = Hooks { hooks: Vec::new(), environment: Some("staging".to_string()) };
+
+        hooks.run_command(HookEvent::PreMigration, &command, &HookContext::for_migration("004_backfill")).await.unwrap();
+
+        let written = std::fs::read_to_string(&out).unwrap();
+        let (env_line, json_line) = written.split_once('\n').unwrap();
+        assert_eq!(env_line, "pre_migration staging 004_backfill");
+        let payload: Value = serde_json::from_str(json_line).unwrap();
+        assert_eq!(payload["migration"], "004_backfill");
+
+        let err = hooks.run_command(HookEvent::PostApply, "exit 3", &HookContext::default()).await.unwrap_err();
+        assert!(err.contains("exited with"), "{}", err);
+    }
+}
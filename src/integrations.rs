@@ -0,0 +1,72 @@
+use std::fmt;
+use tokio_postgres::GenericClient;
+
+/// A built-in action run after an apply that changed something, configured
+/// per integration target in pgmg.toml instead of as a hand-written hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrationAction {
+    /// `NOTIFY pgrst, 'reload schema'`: PostgREST reloads its schema cache,
+    /// picking up new functions, views and grants
+    PostgrestReloadSchema { channel: String },
+    /// `NOTIFY pgrst, 'reload config'`: PostgREST re-reads its configuration,
+    /// including settings stored in the database such as the JWT role claim
+    PostgrestReloadConfig { channel: String },
+}
+
+impl IntegrationAction {
+    /// Parse a `[integrations.postgrest]` action name
+    pub fn postgrest(action: &str, channel: &str) -> Result<Self, String> {
+        let channel = channel.to_string();
+        match action.trim().to_lowercase().replace(' ', "_").as_str() {
+            "reload_schema" => Ok(IntegrationAction::PostgrestReloadSchema { channel }),
+            "reload_config" => Ok(IntegrationAction::PostgrestReloadConfig { channel }),
+            _ => Err(format!(
+                "Unknown PostgREST integration action '{}'. Expected one of: reload_schema, reload_config",
+                action
+            )),
+        }
+    }
+
+    /// The channel and payload to notify
+    fn notification(&self) -> (&str, &str) {
+        match self {
+            IntegrationAction::PostgrestReloadSchema { channel } => (channel, "reload schema"),
+            IntegrationAction::PostgrestReloadConfig { channel } => (channel, "reload config"),
+        }
+    }
+
+    pub async fn run<C: GenericClient>(&self, client: &C) -> Result<(), tokio_postgres::Error> {
+        let (channel, payload) = self.notification();
+        client.execute("SELECT pg_notify($1, $2)", &[&channel, &payload]).await?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for IntegrationAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, payload) = self.notification();
+        write!(f, "PostgREST {}", payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_postgrest_actions() {
+        assert_eq!(
+            IntegrationAction::postgrest("reload_schema", "pgrst").unwrap(),
+            IntegrationAction::PostgrestReloadSchema { channel: "pgrst".to_string() }
+        );
+        assert_eq!(
+            IntegrationAction::postgrest("reload config", "api").unwrap(),
+            IntegrationAction::PostgrestReloadConfig { channel: "api".to_string() }
+        );
+        assert!(IntegrationAction::postgrest("restart", "pgrst").is_err());
+
+        let action = IntegrationAction::postgrest("reload_config", "pgrst").unwrap();
+        assert_eq!(action.notification(), ("pgrst", "reload config"));
+        assert_eq!(action.to_string(), "PostgREST reload config");
+    }
+}
@@ -12,16 +12,29 @@ pub mod config;
 pub mod error;
 pub mod logging;
 pub mod notify;
+pub mod integrations;
+pub mod hooks;
+pub mod git;
+pub mod diff;
+pub mod cancel;
+pub mod slack;
 pub mod plpgsql_check;
 pub mod output;
+pub mod embedded;
+pub mod client;
 
 // Re-export key public APIs for convenience
 pub use builtin_catalog::BuiltinCatalog;
 pub use sql::{analyze_statement, analyze_plpgsql, filter_builtins, Dependencies, QualifiedIdent, SqlObject, ObjectType};
-pub use analysis::{DependencyGraph, ObjectRef, DependencyType};
+pub use analysis::{DependencyGraph, ObjectRef, DependencyType, SearchPath};
 pub use db::{StateManager, DatabaseConfig, connect_to_database, connect_with_url, scan_sql_files, scan_migrations};
 pub use config::PgmgConfig;
 pub use error::{PgmgError, Result, ErrorContext};
+pub use cancel::CancellationToken;
 
 // Re-export library-friendly command functions
-pub use commands::apply::{apply_migrations, apply_migrations_with_options, ApplyResult};
\ No newline at end of file
+pub use commands::apply::{apply_migrations, apply_migrations_with_options, ApplyResult};
+pub use commands::plan_offline::{plan_offline, OfflinePlan};
+
+// Typed facade for applications embedding pgmg
+pub use client::{Pgmg, PgmgBuilder};
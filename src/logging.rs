@@ -25,7 +25,7 @@ pub fn init(verbosity: u8) -> Result<(), Box<dyn std::error::Error + Send + Sync
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(format!("pgmg={},tokio_postgres=warn,hyper=warn", log_level)));
     
-    // Check if we're running in a terminal for color output
+    // Check if we're running in a terminal for color output (and colors weren't disabled)
     let is_terminal = std::io::stdout().is_terminal();
     
     // Set up the formatting layer
@@ -33,7 +33,7 @@ pub fn init(verbosity: u8) -> Result<(), Box<dyn std::error::Error + Send + Sync
         .with_target(false)
         .with_thread_ids(false)
         .with_thread_names(false)
-        .with_ansi(is_terminal)
+        .with_ansi(is_terminal && output::colors_enabled())
         .with_timer(UtcTime::rfc_3339())
         .with_span_events(FmtSpan::CLOSE);
     
@@ -162,10 +162,155 @@ macro_rules! log_object_change {
 }
 
 /// Format output for CLI with colors
+///
+/// This is the output sink shared by all commands. Everything written here
+/// honors `--quiet` (only errors are printed), `--no-color` / `NO_COLOR` and
+/// `plain_output` (ASCII markers instead of unicode symbols).
 pub mod output {
     #[cfg(feature = "cli")]
     use console::{style, Emoji};
     use std::fmt::Display;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static QUIET: AtomicBool = AtomicBool::new(false);
+    static COLOR: AtomicBool = AtomicBool::new(true);
+    static PLAIN: AtomicBool = AtomicBool::new(false);
+
+    /// Configure the output sink. Must be called before any output is written.
+    ///
+    /// Colors are disabled if `no_color` is set or the `NO_COLOR` environment
+    /// variable is present and non-empty (see https://no-color.org). That
+    /// covers text styled with owo-colors too, as the sink strips its styling.
+    pub fn configure(quiet: bool, no_color: bool) {
+        let no_color = no_color || std::env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty());
+
+        QUIET.store(quiet, Ordering::Relaxed);
+        COLOR.store(!no_color, Ordering::Relaxed);
+
+        #[cfg(feature = "cli")]
+        if no_color {
+            console::set_colors_enabled(false);
+            console::set_colors_enabled_stderr(false);
+        }
+    }
+
+    /// Switch to plain output: no colors, including styling the sink strips
+    /// from owo-colors text, and ASCII in place of unicode symbols
+    pub fn set_plain(plain: bool) {
+        PLAIN.store(plain, Ordering::Relaxed);
+
+        if plain {
+            COLOR.store(false, Ordering::Relaxed);
+
+            #[cfg(feature = "cli")]
+            {
+                console::set_colors_enabled(false);
+                console::set_colors_enabled_stderr(false);
+            }
+        }
+    }
+
+    /// Whether plain output is enabled
+    pub fn is_plain() -> bool {
+        PLAIN.load(Ordering::Relaxed)
+    }
+
+    /// The ASCII replacement for a unicode symbol when plain output is enabled,
+    /// otherwise the symbol itself
+    pub fn symbol(unicode: &'static str) -> &'static str {
+        if !is_plain() {
+            return unicode;
+        }
+        match unicode.trim_end().trim_end_matches('\u{fe0f}') {
+            "✓" | "✅" => "[OK]",
+            "✗" | "❌" => "[FAIL]",
+            "⚠" => "[WARN]",
+            "ℹ" | "💡" => "[INFO]",
+            "→" => "->",
+            "↓" => "v",
+            "↷" => "[SKIP]",
+            "└─" => "`-",
+            "—" => "-",
+            "⏱" => "time:",
+            "📁" => "file:",
+            _ => unicode,
+        }
+    }
+
+    /// Whether non-error output is suppressed
+    pub fn is_quiet() -> bool {
+        QUIET.load(Ordering::Relaxed)
+    }
+
+    /// Whether colored output is allowed
+    pub fn colors_enabled() -> bool {
+        COLOR.load(Ordering::Relaxed)
+    }
+
+    /// `message` as the sink writes it. Summaries style their text with
+    /// owo-colors, which always emits ANSI codes, so these are stripped here
+    /// when colors are disabled.
+    pub fn render(message: impl Display) -> String {
+        let message = message.to_string();
+        if colors_enabled() {
+            message
+        } else {
+            console::strip_ansi_codes(&message).into_owned()
+        }
+    }
+
+    /// Kind of change made to a single object or migration
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Change {
+        Created,
+        Updated,
+        Deleted,
+        Dropped,
+        Migrated,
+    }
+
+    impl Change {
+        fn marker(self) -> &'static str {
+            match self {
+                Change::Created => "+",
+                Change::Updated => "~",
+                Change::Deleted => "-",
+                Change::Dropped => symbol("↓"),
+                Change::Migrated => ">",
+            }
+        }
+
+        fn verb(self) -> &'static str {
+            match self {
+                Change::Created => "created",
+                Change::Updated => "updated",
+                Change::Deleted => "deleted",
+                Change::Dropped => "dropped",
+                Change::Migrated => "applied",
+            }
+        }
+    }
+
+    /// Per-object progress line, e.g. `  + created view api.users`
+    pub fn change(kind: Change, object_type: impl Display, name: impl Display) {
+        if is_quiet() {
+            return;
+        }
+        let marker = match kind {
+            Change::Created => style(kind.marker()).green().bold(),
+            Change::Updated => style(kind.marker()).yellow().bold(),
+            Change::Deleted | Change::Dropped => style(kind.marker()).red().bold(),
+            Change::Migrated => style(kind.marker()).magenta().bold(),
+        };
+        println!("  {} {} {} {}", marker, kind.verb(), style(object_type).dim(), style(name).cyan());
+    }
+
+    /// Plain line of output
+    pub fn line(message: impl Display) {
+        if !is_quiet() {
+            println!("{}", render(message));
+        }
+    }
     
     #[cfg(feature = "cli")]
     static CHECKMARK: Emoji<'_, '_> = Emoji("✓ ", "[OK] ");
@@ -178,32 +323,91 @@ pub mod output {
     #[cfg(feature = "cli")]
     static INFO: Emoji<'_, '_> = Emoji("ℹ ", "[INFO] ");
     
+    /// An emoji marker, or its ASCII fallback when plain output is enabled
+    #[cfg(feature = "cli")]
+    fn emoji(emoji: &Emoji<'static, 'static>) -> String {
+        if is_plain() {
+            emoji.1.to_string()
+        } else {
+            emoji.to_string()
+        }
+    }
+    
     pub fn success(message: impl Display) {
-        println!("{} {}", style(CHECKMARK).green(), message);
+        if is_quiet() {
+            return;
+        }
+        println!("{} {}", style(emoji(&CHECKMARK)).green(), render(message));
     }
     
     pub fn error(message: impl Display) {
-        eprintln!("{} {}", style(CROSS).red(), style(message).red());
+        eprintln!("{} {}", style(emoji(&CROSS)).red(), style(render(message)).red());
     }
     
     pub fn warning(message: impl Display) {
-        println!("{} {}", style(WARNING).yellow(), style(message).yellow());
+        if is_quiet() {
+            return;
+        }
+        println!("{} {}", style(emoji(&WARNING)).yellow(), style(render(message)).yellow());
     }
     
     pub fn info(message: impl Display) {
-        println!("{} {}", style(INFO).blue(), message);
+        if is_quiet() {
+            return;
+        }
+        println!("{} {}", style(emoji(&INFO)).blue(), render(message));
     }
     
     pub fn step(message: impl Display) {
-        println!("{} {}", style(ARROW).cyan(), message);
+        if is_quiet() {
+            return;
+        }
+        println!("{} {}", style(emoji(&ARROW)).cyan(), render(message));
     }
     
     pub fn header(message: impl Display) {
-        println!("\n{}", style(message).bold().underlined());
+        if is_quiet() {
+            return;
+        }
+        println!("\n{}", style(render(message)).bold().underlined());
+    }
+    
+    /// Print `text` through `$PAGER` (`less -R` if unset) when stdout is a
+    /// terminal the text doesn't fit on, otherwise print it as is. Like errors,
+    /// this is printed even in quiet mode.
+    pub fn page(text: &str) {
+        use std::io::{IsTerminal, Write};
+        use std::process::{Command, Stdio};
+
+        let text = &render(text);
+
+        let fits = console::Term::stdout().size_checked()
+            .map_or(true, |(rows, _)| text.lines().count() < rows as usize);
+        if fits || !std::io::stdout().is_terminal() {
+            print!("{}", text);
+            return;
+        }
+
+        let pager = std::env::var("PAGER").ok()
+            .filter(|pager| !pager.trim().is_empty())
+            .unwrap_or_else(|| "less -R".to_string());
+        match Command::new("sh").arg("-c").arg(&pager).stdin(Stdio::piped()).spawn() {
+            Ok(mut child) => {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // Quitting the pager early closes the pipe
+                    let _ = stdin.write_all(text.as_bytes());
+                }
+                let _ = child.wait();
+            }
+            Err(_) => print!("{}", text),
+        }
     }
     
     pub fn subheader(message: impl Display) {
-        println!("\n{}", style(message).bold());
+        if is_quiet() {
+            return;
+        }
+        println!("\n{}", style(render(message)).bold());
     }
 }
 
@@ -221,4 +425,24 @@ pub fn format_duration(duration: std::time::Duration) -> String {
         let secs = secs % 60;
         format!("{}m {}s", mins, secs)
     }
+}
+
+/// Format a size in bytes the way pg_size_pretty does, e.g. "48 GB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{:.1} {}", size, UNITS[unit])
+    } else {
+        format!("{:.0} {}", size, UNITS[unit])
+    }
 }
\ No newline at end of file
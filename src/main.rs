@@ -1,10 +1,12 @@
 use tokio_postgres::NoTls;
 use pgmg::{analyze_statement, filter_builtins, BuiltinCatalog, DependencyGraph};
-use pgmg::cli::{Cli, Commands};
-use pgmg::commands::{execute_plan, print_plan_summary, execute_apply, print_apply_summary, execute_watch, WatchConfig, execute_reset, print_reset_summary, execute_test, print_test_summary, execute_seed, print_seed_summary, execute_new, print_new_summary, execute_check, print_check_summary, execute_run};
+use pgmg::cli::{Cli, Commands, SeedAction, SnapshotTarget, StateAction, TestDbAction};
+use pgmg::commands::{execute_plan_with_options, execute_plan_offline, PlanOptions, print_plan_summary, collect_change_usage, print_usage_report, execute_apply_with_cancellation, print_apply_summary, execute_watch, WatchConfig, execute_reset, print_reset_summary, execute_test_with_options, execute_changed_tests, execute_tagged_tests, ChangedSince, TestDatabaseMode, TestReport, write_test_reports, print_test_summary, execute_seed, SeedOptions, SeedTransactionMode, print_seed_summary, execute_seed_reset, SeedResetOptions, print_seed_reset_summary, execute_new, print_new_summary, execute_check, execute_check_files, print_check_summary, execute_lint, print_lint_summary, execute_fmt, print_fmt_summary, execute_run, execute_graph, print_graph_summary, GraphFormat, GraphFilter, execute_impact, print_impact_summary, execute_generate_migration, print_generate_migration_summary, execute_doctor, print_doctor_summary, execute_show, print_show_summary, execute_refresh, print_refresh_summary, execute_partitions, print_partitions_summary, execute_wait_for_apply, print_wait_summary, execute_history, print_history_summary, execute_blame, print_blame_summary, execute_unlock, print_unlock_summary, execute_status, print_status_summary, execute_support_bundle, print_support_bundle_summary, execute_serve, ServeConfig, execute_snapshot_schema, print_snapshot_summary, execute_snapshot_export, print_snapshot_export_summary, SnapshotSource, execute_test_db_gc, parse_ttl, print_test_db_gc_summary};
 use pgmg::config::PgmgConfig;
+use pgmg::db::StateSnapshot;
 use pgmg::error::{PgmgError, Result};
 use pgmg::logging;
+use pgmg::CancellationToken;
 use std::path::PathBuf;
 use tracing::{debug, info, warn};
 use color_eyre;
@@ -23,6 +25,7 @@ async fn main() -> color_eyre::Result<()> {
     // Initialize logging and error handling
     // Verbosity: 0 = warn, 1 = info, 2 = debug, 3+ = trace
     let verbosity = cli.verbose.unwrap_or(0);
+    logging::output::configure(cli.quiet, cli.no_color);
     if let Err(e) = logging::init(verbosity) {
         eprintln!("Failed to initialize logging: {}", e);
         std::process::exit(1);
@@ -34,6 +37,11 @@ async fn main() -> color_eyre::Result<()> {
     
     // Run the actual command
     if let Err(e) = run(cli).await {
+        if matches!(e, PgmgError::Cancelled) {
+            logging::output::warning("Cancelled");
+            std::process::exit(130);
+        }
+
         // Use the new error formatting
         logging::output::error(&pgmg::error::format_error_chain(&e));
         
@@ -62,6 +70,10 @@ async fn run(cli: Cli) -> Result<()> {
         }
     };
 
+    if config_file.as_ref().and_then(|config| config.plain_output).unwrap_or(false) {
+        logging::output::set_plain(true);
+    }
+
     match cli.command {
         Commands::Init => {
             logging::output::step("Generating sample configuration file...");
@@ -72,7 +84,45 @@ async fn run(cli: Cli) -> Result<()> {
             logging::output::success("Created pgmg.toml.example - rename to pgmg.toml to use");
             Ok(())
         }
-        Commands::Plan { migrations_dir, code_dir, connection_string, output_graph } => {
+        // --offline requires --snapshot, and rules out --usage-report
+        Commands::Plan { migrations_dir, code_dir, connection_string, output_graph, tags, no_diff, detailed_exitcode, snapshot: Some(snapshot), .. } => {
+            logging::output::header("Planning Changes (offline)");
+
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                migrations_dir,
+                code_dir,
+                connection_string,
+                output_graph,
+            ).with_tags(tags);
+
+            let snapshot_state = StateSnapshot::load(&snapshot)
+                .map_err(|e| PgmgError::Configuration(e.to_string()))?;
+            let exported_at: chrono::DateTime<chrono::Local> = snapshot_state.exported_at.into();
+            logging::output::info(format!(
+                "Planning against {} exported {}",
+                snapshot.display(),
+                exported_at.format("%Y-%m-%d %H:%M:%S %Z")
+            ));
+
+            let options = PlanOptions::from_config(&merged_config);
+            let plan_result = execute_plan_offline(
+                merged_config.migrations_dir,
+                merged_config.code_dir,
+                &snapshot_state,
+                merged_config.output_graph,
+                &options,
+            ).await?;
+
+            print_plan_summary(&plan_result, !no_diff);
+
+            if detailed_exitcode && plan_result.has_changes() {
+                std::process::exit(2);
+            }
+            Ok(())
+        }
+
+        Commands::Plan { migrations_dir, code_dir, connection_string, output_graph, usage_report, tags, no_diff, detailed_exitcode, .. } => {
             logging::output::header("Planning Changes");
             
             // Merge CLI args with config file
@@ -82,7 +132,7 @@ async fn run(cli: Cli) -> Result<()> {
                 code_dir,
                 connection_string,
                 output_graph,
-            );
+            ).with_tags(tags);
             
             // Require connection string
             let conn_str = merged_config.connection_string.clone()
@@ -107,21 +157,34 @@ async fn run(cli: Cli) -> Result<()> {
             
             // Execute plan with progress tracking
             let start = std::time::Instant::now();
-            let plan_result = execute_plan(
+            let options = PlanOptions::from_config(&merged_config);
+            let plan_result = execute_plan_with_options(
                 merged_config.migrations_dir,
                 merged_config.code_dir,
-                conn_str,
+                conn_str.clone(),
                 merged_config.output_graph,
+                &options,
             ).await?;
             
             let elapsed = start.elapsed();
             info!("Planning completed in {}", logging::format_duration(elapsed));
             
-            print_plan_summary(&plan_result);
+            print_plan_summary(&plan_result, !no_diff);
+
+            if usage_report {
+                let usage = collect_change_usage(&conn_str, &plan_result).await
+                    .map_err(|e| PgmgError::Other(format!("Usage report failed: {}", e)))?;
+                print_usage_report(&usage);
+            }
+
+            // Errors already exit with 1, so 2 tells pending changes apart from them
+            if detailed_exitcode && plan_result.has_changes() {
+                std::process::exit(2);
+            }
             Ok(())
         }
         
-        Commands::Status { migrations_dir, code_dir, connection_string, output_graph } => {
+        Commands::Status { migrations_dir, code_dir, connection_string, output_graph, tags, full } => {
             logging::output::header("Checking Status");
             
             // Merge CLI args with config file
@@ -131,7 +194,7 @@ async fn run(cli: Cli) -> Result<()> {
                 code_dir,
                 connection_string,
                 output_graph,
-            );
+            ).with_tags(tags);
             
             // Require connection string
             let conn_str = merged_config.connection_string.clone()
@@ -154,23 +217,29 @@ async fn run(cli: Cli) -> Result<()> {
                 debug!("Code directory: {}", dir.display());
             }
             
-            // Execute plan with progress tracking
             let start = std::time::Instant::now();
-            let plan_result = execute_plan(
-                merged_config.migrations_dir,
-                merged_config.code_dir,
-                conn_str,
-                merged_config.output_graph,
-            ).await?;
+            let status = execute_status(merged_config.migrations_dir.clone(), conn_str.clone(), &merged_config).await
+                .map_err(|e| PgmgError::Other(format!("Status failed: {}", e)))?;
+            print_status_summary(&status);
+
+            if full {
+                let options = PlanOptions::from_config(&merged_config);
+                let plan_result = execute_plan_with_options(
+                    merged_config.migrations_dir,
+                    merged_config.code_dir,
+                    conn_str,
+                    merged_config.output_graph,
+                    &options,
+                ).await?;
+                print_plan_summary(&plan_result, true);
+            }
             
             let elapsed = start.elapsed();
             info!("Status check completed in {}", logging::format_duration(elapsed));
-            
-            print_plan_summary(&plan_result);
             Ok(())
         }
         
-        Commands::Apply { migrations_dir, code_dir, connection_string, dev } => {
+        Commands::Apply { migrations_dir, code_dir, connection_string, dev, replication_safe, force, verify_replica, verify_replica_timeout, lock_timeout, no_wait, tags } => {
             logging::output::header("Applying Changes");
             
             // Merge CLI args with config file (no output_graph for apply)
@@ -180,7 +249,10 @@ async fn run(cli: Cli) -> Result<()> {
                 code_dir,
                 connection_string,
                 None, // apply command doesn't use output_graph
-            ).with_dev_mode(dev);
+            ).with_dev_mode(dev)
+            .with_replication_safety(replication_safe, force)
+            .with_apply_lock(lock_timeout, no_wait)
+            .with_tags(tags);
             
             // Log configuration
             if let Some(ref dir) = merged_config.migrations_dir {
@@ -208,21 +280,41 @@ async fn run(cli: Cli) -> Result<()> {
             
             // Execute apply with progress tracking
             let start = std::time::Instant::now();
-            let apply_result = execute_apply(
+            let apply_result = execute_apply_with_cancellation(
                 merged_config.migrations_dir.clone(),
                 merged_config.code_dir.clone(),
-                conn_str,
+                conn_str.clone(),
                 &merged_config,
+                &cancel_on_ctrl_c(),
             ).await?;
             
             let elapsed = start.elapsed();
             info!("Apply completed in {}", logging::format_duration(elapsed));
             
             print_apply_summary(&apply_result);
+
+            if let Some(replica_url) = verify_replica {
+                if !apply_result.errors.is_empty() {
+                    return Err(PgmgError::Other("Skipping replica verification because apply reported errors".to_string()));
+                }
+                logging::output::step("Waiting for replica to catch up...");
+                let verification = pgmg::commands::verify_replica(
+                    &conn_str,
+                    &replica_url,
+                    &apply_result,
+                    std::time::Duration::from_secs(verify_replica_timeout),
+                ).await
+                    .map_err(|e| PgmgError::Other(format!("Replica verification failed: {}", e)))?;
+                logging::output::success(format!(
+                    "Replica caught up in {} ({} checks)",
+                    logging::format_duration(verification.elapsed),
+                    verification.attempts
+                ));
+            }
             Ok(())
         }
         
-        Commands::Migrate { migrations_dir, code_dir, connection_string, dev } => {
+        Commands::Migrate { migrations_dir, code_dir, connection_string, dev, replication_safe, force, verify_replica, verify_replica_timeout, lock_timeout, no_wait, tags } => {
             logging::output::header("Migrating Database");
             
             // Merge CLI args with config file (no output_graph for migrate)
@@ -232,7 +324,10 @@ async fn run(cli: Cli) -> Result<()> {
                 code_dir,
                 connection_string,
                 None, // migrate command doesn't use output_graph
-            ).with_dev_mode(dev);
+            ).with_dev_mode(dev)
+            .with_replication_safety(replication_safe, force)
+            .with_apply_lock(lock_timeout, no_wait)
+            .with_tags(tags);
             
             // Log configuration
             if let Some(ref dir) = merged_config.migrations_dir {
@@ -260,17 +355,37 @@ async fn run(cli: Cli) -> Result<()> {
             
             // Execute apply with progress tracking
             let start = std::time::Instant::now();
-            let apply_result = execute_apply(
+            let apply_result = execute_apply_with_cancellation(
                 merged_config.migrations_dir.clone(),
                 merged_config.code_dir.clone(),
-                conn_str,
+                conn_str.clone(),
                 &merged_config,
+                &cancel_on_ctrl_c(),
             ).await?;
             
             let elapsed = start.elapsed();
             info!("Migration completed in {}", logging::format_duration(elapsed));
             
             print_apply_summary(&apply_result);
+
+            if let Some(replica_url) = verify_replica {
+                if !apply_result.errors.is_empty() {
+                    return Err(PgmgError::Other("Skipping replica verification because apply reported errors".to_string()));
+                }
+                logging::output::step("Waiting for replica to catch up...");
+                let verification = pgmg::commands::verify_replica(
+                    &conn_str,
+                    &replica_url,
+                    &apply_result,
+                    std::time::Duration::from_secs(verify_replica_timeout),
+                ).await
+                    .map_err(|e| PgmgError::Other(format!("Replica verification failed: {}", e)))?;
+                logging::output::success(format!(
+                    "Replica caught up in {} ({} checks)",
+                    logging::format_duration(verification.elapsed),
+                    verification.attempts
+                ));
+            }
             Ok(())
         }
         
@@ -299,6 +414,7 @@ async fn run(cli: Cli) -> Result<()> {
                 debounce_duration: std::time::Duration::from_millis(debounce_ms),
                 auto_apply: !no_auto_apply,
                 pgmg_config: merged_config,
+                cancellation: cancel_on_ctrl_c(),
             };
             
             // Log configuration
@@ -344,7 +460,7 @@ async fn run(cli: Cli) -> Result<()> {
             print_reset_summary(&result);
             Ok(())
         }
-        Commands::Test { path, connection_string, tap_output, quiet, all } => {
+        Commands::Test { path, connection_string, tap_output, all, jobs, changed, since, report, fresh, seed_env, rebuild_template, tags } => {
             logging::output::header("Running pgTAP Tests");
             
             // Get connection string from CLI arg, config file, or environment
@@ -377,6 +493,14 @@ async fn run(cli: Cli) -> Result<()> {
             debug!("Test path: {:?}", test_path);
             debug!("TAP output: {}", tap_output);
             debug!("Run all tests: {}", all);
+            debug!("Jobs: {}", jobs);
+            debug!("Changed only: {} (since: {:?})", changed, since);
+            debug!("Fresh database: {} (seed env: {:?}, rebuild template: {})", fresh, seed_env, rebuild_template);
+            
+            let reports = report.iter()
+                .map(|r| r.parse())
+                .collect::<std::result::Result<Vec<TestReport>, String>>()
+                .map_err(PgmgError::Configuration)?;
             
             // Merge config for test command
             let merged_config = PgmgConfig::merge_with_cli(
@@ -387,12 +511,45 @@ async fn run(cli: Cli) -> Result<()> {
                 None, // no output_graph for test
             );
             
+            // A fresh database also gets the seed files
+            let database = if fresh {
+                let seed_dir = match &seed_env {
+                    Some(env) => Some(merged_config.seed_dir_for_env(env).map_err(PgmgError::Configuration)?),
+                    None => merged_config.seed_dir.clone(),
+                };
+                TestDatabaseMode::Fresh { seed_dir, rebuild_template }
+            } else {
+                TestDatabaseMode::Template
+            };
+            
+            // --since implies --changed
+            let changed_since = match since {
+                Some(git_ref) => Some(ChangedSince::GitRef(git_ref)),
+                None if changed => Some(ChangedSince::LastApply),
+                None => None,
+            };
+            
             // Execute tests
-            let result = execute_test(test_path, conn_str, tap_output, quiet, &merged_config).await
-                .map_err(|e| PgmgError::Other(format!("Test execution failed: {}", e)))?;
+            let quiet = logging::output::is_quiet();
+            let cancel = cancel_on_ctrl_c();
+            let result = match changed_since {
+                None if !tags.is_empty() => execute_tagged_tests(test_path, conn_str, tap_output, quiet, usize::from(jobs), &tags, &database, &merged_config, &cancel).await,
+                Some(since) => execute_changed_tests(test_path, conn_str, tap_output, quiet, usize::from(jobs), &since, &database, &merged_config, &cancel).await,
+                None => execute_test_with_options(test_path, conn_str, tap_output, !quiet, quiet, usize::from(jobs), &database, &merged_config, &cancel).await,
+            }
+                .map_err(|e| match e {
+                    PgmgError::Cancelled => e,
+                    e => PgmgError::Other(format!("Test execution failed: {}", e)),
+                })?;
             
             print_test_summary(&result);
             
+            write_test_reports(&result, &reports)
+                .map_err(|e| PgmgError::Other(format!("Failed to write test report: {}", e)))?;
+            for report in &reports {
+                logging::output::info(format!("Wrote test report to {}", report.path.display()));
+            }
+            
             // Exit with non-zero code if tests failed
             if result.tests_failed > 0 {
                 std::process::exit(1);
@@ -401,8 +558,8 @@ async fn run(cli: Cli) -> Result<()> {
             Ok(())
         }
         
-        Commands::Seed { seed_dir, connection_string } => {
-            logging::output::header("Executing Seed Files");
+        Commands::Seed { action, seed_dir, env, connection_string, transaction_per_file, only, skip, track, force, truncate_csv } => {
+            logging::output::header(if action.is_some() { "Resetting Seeded Tables" } else { "Executing Seed Files" });
             
             // Merge CLI args with config file
             let merged_config = PgmgConfig::merge_with_cli_seed(
@@ -423,11 +580,17 @@ async fn run(cli: Cli) -> Result<()> {
                 return Err(PgmgError::InvalidConnectionString(conn_str));
             }
             
-            // Require seed directory
-            let seed_directory = merged_config.seed_dir
-                .ok_or_else(|| PgmgError::Configuration(
-                    "No seed directory provided. Use --seed-dir or specify seed_dir in pgmg.toml".to_string()
-                ))?;
+            // Require seed directory, from seed_dirs when an environment is given
+            let seed_directory = match env {
+                Some(env) => {
+                    info!("Seeding environment: {}", env);
+                    merged_config.seed_dir_for_env(&env).map_err(PgmgError::Configuration)?
+                }
+                None => merged_config.seed_dir
+                    .ok_or_else(|| PgmgError::Configuration(
+                        "No seed directory provided. Use --seed-dir, --env or specify seed_dir in pgmg.toml".to_string()
+                    ))?,
+            };
             
             // Validate seed directory exists
             if !seed_directory.exists() {
@@ -446,9 +609,29 @@ async fn run(cli: Cli) -> Result<()> {
             debug!("Connection: {}", conn_str.replace(|c: char| c == ':' || c == '@', "*"));
             debug!("Seed directory: {}", seed_directory.display());
             
+            if let Some(SeedAction::Reset { restart_identity, delete, dry_run }) = action {
+                let options = SeedResetOptions { restart_identity, delete, dry_run };
+                let result = execute_seed_reset(seed_directory, conn_str, &options).await
+                    .map_err(|e| PgmgError::Other(format!("Seed reset failed: {}", e)))?;
+                print_seed_reset_summary(&result);
+                return Ok(());
+            }
+            
             // Execute seed with progress tracking
             let start = std::time::Instant::now();
-            let result = execute_seed(seed_directory, conn_str).await
+            let options = SeedOptions {
+                transaction_mode: if transaction_per_file {
+                    SeedTransactionMode::PerFile
+                } else {
+                    SeedTransactionMode::Single
+                },
+                only,
+                skip,
+                track,
+                force,
+                truncate_csv,
+            };
+            let result = execute_seed(seed_directory, conn_str, &options).await
                 .map_err(|e| PgmgError::Other(format!("Seed execution failed: {}", e)))?;
             
             let elapsed = start.elapsed();
@@ -484,15 +667,22 @@ async fn run(cli: Cli) -> Result<()> {
             Ok(())
         }
         
-        Commands::Check { function_name, connection_string, schema, errors_only } => {
+        Commands::Check { function_name, connection_string, schema, errors_only, from_files, migrations_dir, code_dir } => {
             logging::output::header("Checking Functions with plpgsql_check");
 
-            // Pull code_dir out of config (used to map plpgsql_check linenos to file:line).
-            let code_dir = config_file.as_ref().and_then(|c| c.code_dir.clone());
+            // code_dir maps plpgsql_check linenos to file:line and holds ignore-check
+            // directives; with --from-files its files are applied first
+            let code_dir = code_dir.or_else(|| config_file.as_ref().and_then(|c| c.code_dir.clone()));
+            let migrations_dir = migrations_dir.or_else(|| config_file.as_ref().and_then(|c| c.migrations_dir.clone()));
+            let policy = config_file.as_ref()
+                .map(|c| c.check_policy())
+                .transpose()
+                .map_err(PgmgError::Configuration)?
+                .unwrap_or_default();
 
             // Get connection string from CLI, env, or config
             let conn_str = connection_string
-                .or(config_file.and_then(|c| c.connection_string))
+                .or(config_file.as_ref().and_then(|c| c.connection_string.clone()))
                 .or_else(|| std::env::var("DATABASE_URL").ok())
                 .ok_or_else(|| PgmgError::Configuration(
                     "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
@@ -509,21 +699,112 @@ async fn run(cli: Cli) -> Result<()> {
                 debug!("Schemas: {:?}", schemas);
             }
             debug!("Errors only: {}", errors_only);
+            debug!("Check policy: {:?}", policy);
+            debug!("From files: {}", from_files);
 
             // Execute check
-            let result = execute_check(conn_str, function_name, schema, errors_only, code_dir).await
-                .map_err(|e| PgmgError::Other(format!("Check failed: {}", e)))?;
+            let result = if from_files {
+                let config = config_file.unwrap_or_default();
+                execute_check_files(conn_str, function_name, schema, errors_only, migrations_dir, code_dir, &config, &policy).await
+            } else {
+                execute_check(conn_str, function_name, schema, errors_only, code_dir, &policy).await
+            }.map_err(|e| PgmgError::Other(format!("Check failed: {}", e)))?;
             
             print_check_summary(&result);
             
-            // Exit with non-zero code if errors found
-            if result.errors_found > 0 {
+            // Exit with non-zero code if errors (or warnings, with fail_on = "warning") found
+            if result.failures > 0 {
                 std::process::exit(1);
             }
             
             Ok(())
         }
         
+        Commands::Lint { migrations_dir, code_dir, fix } => {
+            logging::output::header("Linting SQL Files");
+
+            let migrations_dir = migrations_dir.or_else(|| config_file.as_ref().and_then(|c| c.migrations_dir.clone()));
+            let code_dir = code_dir.or_else(|| config_file.as_ref().and_then(|c| c.code_dir.clone()));
+            if migrations_dir.is_none() && code_dir.is_none() {
+                return Err(PgmgError::Configuration(
+                    "No directories to lint. Use --migrations-dir, --code-dir or set them in pgmg.toml".to_string()
+                ));
+            }
+
+            let config = config_file.unwrap_or_default();
+            let result = execute_lint(migrations_dir, code_dir, &config, fix).await
+                .map_err(|e| PgmgError::Other(format!("Lint failed: {}", e)))?;
+
+            print_lint_summary(&result);
+
+            if result.findings() > 0 || !result.errors.is_empty() {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Fmt { code_dir, check } => {
+            logging::output::header(if check { "Checking SQL Formatting" } else { "Formatting SQL Files" });
+
+            let code_dir = code_dir
+                .or_else(|| config_file.as_ref().and_then(|c| c.code_dir.clone()))
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No code directory to format. Use --code-dir or set code_dir in pgmg.toml".to_string()
+                ))?;
+
+            let result = execute_fmt(code_dir, check).await
+                .map_err(|e| PgmgError::Other(format!("Formatting failed: {}", e)))?;
+
+            print_fmt_summary(&result);
+
+            if !result.errors.is_empty() || (check && !result.changed.is_empty()) {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+
+        #[cfg(feature = "simulate")]
+        Commands::SimulateConcurrency { migrations_dir, code_dir, connection_string, interleaving } => {
+            use pgmg::commands::{execute_simulate_concurrency, print_simulation_summary, Interleaving};
+
+            let interleaving: Interleaving = interleaving.parse()
+                .map_err(PgmgError::Configuration)?;
+            logging::output::header(format!("Simulating Concurrent Applies ({})", interleaving));
+
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                migrations_dir,
+                code_dir,
+                connection_string,
+                None,
+            );
+
+            let conn_str = merged_config.connection_string.clone()
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            let result = execute_simulate_concurrency(
+                merged_config.migrations_dir.clone(),
+                merged_config.code_dir.clone(),
+                conn_str,
+                &merged_config,
+                interleaving,
+            ).await
+                .map_err(|e| PgmgError::Other(format!("Simulation failed: {}", e)))?;
+
+            print_simulation_summary(&result);
+
+            if !result.passed() {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+        
         Commands::Run { file, connection_string } => {
             logging::output::header("Running SQL File");
             
@@ -549,9 +830,447 @@ async fn run(cli: Cli) -> Result<()> {
             
             Ok(())
         }
+
+        Commands::Graph { code_dir, format, root, depth, object_types, tags, output } => {
+            // No header here: the graph itself is written to stdout by default
+
+            let code_dir = code_dir
+                .or_else(|| config_file.as_ref().and_then(|c| c.code_dir.clone()))
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No code directory provided. Use --code-dir or set code_dir in pgmg.toml".to_string()
+                ))?;
+
+            let format: GraphFormat = format.parse()
+                .map_err(PgmgError::Configuration)?;
+
+            let object_types = object_types.iter()
+                .map(|t| t.parse())
+                .collect::<std::result::Result<Vec<_>, String>>()
+                .map_err(PgmgError::Configuration)?;
+
+            let filter = GraphFilter { root, depth, object_types, tags };
+            debug!("Graph filter: {:?}", filter);
+
+            let search_path = config_file.as_ref().map(PgmgConfig::search_path).unwrap_or_default();
+            let result = execute_graph(code_dir, format, filter, &search_path, output).await
+                .map_err(|e| PgmgError::Other(format!("Graph failed: {}", e)))?;
+
+            print_graph_summary(&result);
+            Ok(())
+        }
+
+        Commands::Impact { object, code_dir, connection_string, tags } => {
+            logging::output::header("Analyzing Impact");
+
+            let code_dir = code_dir
+                .or_else(|| config_file.as_ref().and_then(|c| c.code_dir.clone()));
+
+            // Connection is optional: without it only SQL files are analyzed
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok());
+
+            if let Some(ref conn_str) = conn_str {
+                if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                    return Err(PgmgError::InvalidConnectionString(conn_str.clone()));
+                }
+            } else {
+                debug!("No connection string, analyzing SQL files only");
+            }
+
+            let search_path = config_file.as_ref().map(PgmgConfig::search_path).unwrap_or_default();
+            let result = execute_impact(object, code_dir, conn_str, &tags, &search_path).await
+                .map_err(|e| PgmgError::Other(format!("Impact analysis failed: {}", e)))?;
+
+            print_impact_summary(&result);
+            Ok(())
+        }
+
+        Commands::GenerateMigration { name, table, migrations_dir, code_dir, connection_string, dry_run } => {
+            logging::output::header("Generating Migration");
+
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                migrations_dir,
+                code_dir,
+                connection_string,
+                None,
+            );
+
+            let code_dir = merged_config.code_dir.clone()
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No code directory provided. Use --code-dir or set code_dir in pgmg.toml".to_string()
+                ))?;
+            let migrations_dir = merged_config.migrations_dir.clone()
+                .unwrap_or_else(|| PathBuf::from("migrations"));
+
+            let conn_str = merged_config.connection_string.clone()
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let result = execute_generate_migration(name, table, code_dir, migrations_dir, conn_str, dry_run).await
+                .map_err(|e| PgmgError::Other(format!("Migration generation failed: {}", e)))?;
+
+            print_generate_migration_summary(&result);
+            Ok(())
+        }
+
+        Commands::Doctor { connection_string } => {
+            logging::output::header("Diagnosing pgmg Setup");
+
+            let result = execute_doctor(std::path::Path::new("pgmg.toml"), connection_string).await
+                .map_err(|e| PgmgError::Other(format!("Doctor failed: {}", e)))?;
+
+            print_doctor_summary(&result);
+
+            if result.has_failures() {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::SupportBundle { output, logs, migrations_dir, code_dir, connection_string } => {
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                migrations_dir,
+                code_dir,
+                connection_string,
+                None, // support-bundle command doesn't use output_graph
+            );
+
+            logging::output::header("Collecting Support Bundle");
+
+            let result = execute_support_bundle(std::path::Path::new("pgmg.toml"), &merged_config, output, &logs).await
+                .map_err(|e| PgmgError::Other(format!("Support bundle failed: {}", e)))?;
+
+            print_support_bundle_summary(&result);
+
+            Ok(())
+        }
+
+        Commands::Unlock { force, connection_string } => {
+            logging::output::header("Apply Lock");
+
+            let config = config_file.unwrap_or_default();
+            let conn_str = connection_string
+                .or_else(|| config.connection_string.clone())
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let result = execute_unlock(conn_str, &config, force).await
+                .map_err(|e| PgmgError::Other(format!("Unlock failed: {}", e)))?;
+
+            print_unlock_summary(&result);
+
+            Ok(())
+        }
+
+        Commands::History { limit, json, connection_string } => {
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            if !json {
+                logging::output::header("Apply History");
+            }
+
+            let result = execute_history(conn_str, limit).await
+                .map_err(|e| PgmgError::Other(format!("History failed: {}", e)))?;
+
+            print_history_summary(&result, json);
+            Ok(())
+        }
+
+        Commands::WaitForApply { timeout, since, connection_string } => {
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            logging::output::info(format!("Waiting up to {} for an apply to finish", logging::format_duration(timeout)));
+
+            let apply = execute_wait_for_apply(conn_str, timeout, since).await
+                .map_err(|e| PgmgError::Other(format!("Wait for apply failed: {}", e)))?;
+
+            print_wait_summary(&apply);
+
+            Ok(())
+        }
+
+        Commands::Refresh { views, concurrently, connection_string } => {
+            logging::output::header("Refreshing Materialized Views");
+
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let search_path = config_file.as_ref().map(PgmgConfig::search_path).unwrap_or_default();
+            let result = execute_refresh(conn_str, views, concurrently, &search_path).await
+                .map_err(|e| PgmgError::Other(format!("Refresh failed: {}", e)))?;
+
+            print_refresh_summary(&result);
+
+            if result.failed.is_some() {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Partitions { dry_run, connection_string } => {
+            logging::output::header("Maintaining Partitions");
+
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let config = config_file.unwrap_or_default();
+            let result = execute_partitions(conn_str, &config, dry_run).await
+                .map_err(|e| PgmgError::Other(format!("Partition maintenance failed: {}", e)))?;
+
+            print_partitions_summary(&result);
+
+            if result.failed.is_some() {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Show { object, code_dir, connection_string, ddl } |
+        Commands::State { action: StateAction::Show { object, code_dir, connection_string, ddl } } => {
+            logging::output::header("Object Details");
+
+            let code_dir = code_dir
+                .or_else(|| config_file.as_ref().and_then(|c| c.code_dir.clone()));
+
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let search_path = config_file.as_ref().map(PgmgConfig::search_path).unwrap_or_default();
+            let result = execute_show(object, code_dir, conn_str, &search_path).await
+                .map_err(|e| PgmgError::Other(format!("Show failed: {}", e)))?;
+
+            print_show_summary(&result, ddl);
+            Ok(())
+        }
+
+        Commands::Blame { object, connection_string } => {
+            logging::output::header("Object History");
+
+            let conn_str = connection_string
+                .or_else(|| config_file.as_ref().and_then(|c| c.connection_string.clone()))
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let search_path = config_file.as_ref().map(PgmgConfig::search_path).unwrap_or_default();
+            let result = execute_blame(object, conn_str, &search_path).await
+                .map_err(|e| PgmgError::Other(format!("Blame failed: {}", e)))?;
+
+            print_blame_summary(&result);
+            Ok(())
+        }
+
+        Commands::Serve { migrations_dir, code_dir, connection_string, listen, token } => {
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                migrations_dir,
+                code_dir,
+                connection_string,
+                None, // serve command doesn't use output_graph
+            );
+
+            let conn_str = merged_config.connection_string.clone()
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let token = token
+                .or_else(|| std::env::var("PGMG_SERVE_TOKEN").ok())
+                .unwrap_or_default();
+
+            execute_serve(ServeConfig {
+                listen,
+                token,
+                migrations_dir: merged_config.migrations_dir.clone(),
+                code_dir: merged_config.code_dir.clone(),
+                connection_string: conn_str,
+                pgmg_config: merged_config,
+                cancellation: cancel_on_ctrl_c(),
+            }).await
+        }
+
+        Commands::Snapshot { target: SnapshotTarget::Schema { output, connection_string, fresh, migrations_dir, code_dir } } => {
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                migrations_dir,
+                code_dir,
+                connection_string,
+                None, // snapshot command doesn't use output_graph
+            );
+
+            let conn_str = merged_config.connection_string.clone()
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            debug!("Snapshot output: {:?}", output);
+            debug!("Fresh database: {}", fresh);
+
+            let source = if fresh { SnapshotSource::Fresh } else { SnapshotSource::Database };
+            let result = execute_snapshot_schema(conn_str, output, &source, &merged_config).await
+                .map_err(|e| PgmgError::Other(format!("Snapshot failed: {}", e)))?;
+
+            print_snapshot_summary(&result);
+
+            Ok(())
+        }
+
+        Commands::Snapshot { target: SnapshotTarget::Export { output, connection_string } } => {
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                None,
+                None,
+                connection_string,
+                None, // snapshot command doesn't use output_graph
+            );
+
+            let conn_str = merged_config.connection_string.clone()
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            debug!("Snapshot output: {}", output.display());
+
+            let result = execute_snapshot_export(conn_str, output, &merged_config).await
+                .map_err(|e| PgmgError::Other(format!("Snapshot export failed: {}", e)))?;
+
+            print_snapshot_export_summary(&result);
+
+            Ok(())
+        }
+
+        Commands::TestDb { action: TestDbAction::Gc { connection_string, older_than, dry_run } } => {
+            let merged_config = PgmgConfig::merge_with_cli(
+                config_file,
+                None,
+                None,
+                connection_string,
+                None, // test-db command doesn't use output_graph
+            );
+
+            let conn_str = merged_config.connection_string.clone()
+                .or_else(|| std::env::var("DATABASE_URL").ok())
+                .ok_or_else(|| PgmgError::Configuration(
+                    "No connection string provided. Use --connection-string, DATABASE_URL env var, or pgmg.toml".to_string()
+                ))?;
+
+            if !conn_str.starts_with("postgres://") && !conn_str.starts_with("postgresql://") {
+                return Err(PgmgError::InvalidConnectionString(conn_str));
+            }
+
+            let ttl = parse_ttl(&older_than).map_err(PgmgError::Configuration)?;
+            debug!("Test database TTL: {:?}", ttl);
+            debug!("Dry run: {}", dry_run);
+
+            let result = execute_test_db_gc(conn_str, ttl, dry_run).await
+                .map_err(|e| PgmgError::Other(format!("Test database cleanup failed: {}", e)))?;
+
+            print_test_db_gc_summary(&result);
+
+            Ok(())
+        }
     }
 }
 
+/// A token cancelled on the first Ctrl-C, so the command can roll back and
+/// release the apply lock. A second Ctrl-C exits at once.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let on_ctrl_c = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        logging::output::warning("Cancelling... press Ctrl-C again to exit immediately");
+        on_ctrl_c.cancel();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
 // Keep the demo for testing, but adapt to new error handling
 #[allow(dead_code)]
 async fn demo_sql_analysis() -> Result<()> {
@@ -622,6 +1341,7 @@ $$;";
 async fn generate_dependency_graph(
     code_dir: &PathBuf,
     output_path: &PathBuf,
+    search_path: &pgmg::SearchPath,
 ) -> Result<()> {
     use std::fs;
     
@@ -650,6 +1370,7 @@ async fn generate_dependency_graph(
             relations: HashSet::new(),
             functions: HashSet::new(),
             types: HashSet::new(),
+            columns: HashSet::new(),
         },
         Some(code_dir.join("tables/users.sql")),
     );
@@ -659,6 +1380,7 @@ async fn generate_dependency_graph(
         relations: HashSet::new(),
         functions: HashSet::new(),
         types: HashSet::new(),
+        columns: HashSet::new(),
     };
     user_stats_deps.relations.insert(QualifiedIdent::from_name("users".to_string()));
     
@@ -675,6 +1397,7 @@ async fn generate_dependency_graph(
         relations: HashSet::new(),
         functions: HashSet::new(),
         types: HashSet::new(),
+        columns: HashSet::new(),
     };
     calc_total_deps.relations.insert(QualifiedIdent::from_name("user_stats".to_string()));
     
@@ -694,7 +1417,7 @@ async fn generate_dependency_graph(
     let builtin_catalog = BuiltinCatalog::new();
     
     // Build the dependency graph
-    let graph = DependencyGraph::build_from_objects(&demo_objects, &builtin_catalog)?;
+    let graph = DependencyGraph::build_with_search_path(&demo_objects, &builtin_catalog, search_path)?;
     
     // Generate Graphviz output
     let graphviz_output = graph.to_graphviz();
@@ -1,6 +1,12 @@
+pub mod webhook;
+
 use serde::{Serialize, Deserialize};
 use crate::sql::{SqlObject, ObjectType};
 
+/// Channel notified each time an apply finishes, whether or not it changed
+/// anything
+pub const APPLY_COMPLETED_CHANNEL: &str = "pgmg.apply_completed";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectLoadedNotification {
     #[serde(rename = "type")]
@@ -34,6 +40,16 @@ impl ObjectLoadedNotification {
             ObjectType::CronJob => "cron_job",
             ObjectType::Aggregate => "aggregate",
             ObjectType::Operator => "operator",
+            ObjectType::SecurityLabel => "security_label",
+            ObjectType::Statistics => "statistics",
+            ObjectType::Extension => "extension",
+            ObjectType::Publication => "publication",
+            ObjectType::Collation => "collation",
+            ObjectType::TextSearchConfiguration => "text_search_configuration",
+            ObjectType::TextSearchDictionary => "text_search_dictionary",
+            ObjectType::OperatorClass => "operator_class",
+            ObjectType::OperatorFamily => "operator_family",
+            ObjectType::Cast => "cast",
         }.to_string();
         
         let span = match (obj.start_line, obj.end_line) {
@@ -81,6 +97,40 @@ pub async fn emit_object_loaded_notification<C: tokio_postgres::GenericClient>(
     Ok(())
 }
 
+/// Payload of the apply-completed notification: the pgmg_history row the
+/// apply was recorded as
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApplyCompletedNotification {
+    pub id: i64,
+    pub migrations_applied: i32,
+    pub objects_changed: i32,
+}
+
+/// Record a finished apply in pgmg_history and notify
+/// [`APPLY_COMPLETED_CHANNEL`]. Outside a transaction the notification is
+/// delivered at once; inside one, when it commits.
+pub async fn record_apply_completed<C: tokio_postgres::GenericClient>(
+    client: &C,
+    migrations_applied: usize,
+    objects_changed: usize,
+) -> Result<ApplyCompletedNotification, Box<dyn std::error::Error>> {
+    let migrations_applied = migrations_applied as i32;
+    let objects_changed = objects_changed as i32;
+
+    let row = client.query_one(
+        "INSERT INTO pgmg.pgmg_history (migrations_applied, objects_changed) VALUES ($1, $2) RETURNING id",
+        &[&migrations_applied, &objects_changed],
+    ).await?;
+    let notification = ApplyCompletedNotification { id: row.get(0), migrations_applied, objects_changed };
+
+    client.execute(
+        "SELECT pg_notify($1, $2)",
+        &[&APPLY_COMPLETED_CHANNEL, &serde_json::to_string(&notification)?],
+    ).await?;
+
+    Ok(notification)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
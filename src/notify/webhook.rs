@@ -0,0 +1,242 @@
+//! POSTing a summary of each apply to the webhooks in `[[webhooks]]`, as
+//! plain JSON or as a Slack message. A webhook that can't be reached is
+//! logged and doesn't fail the deploy.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+use url::Url;
+use crate::commands::ApplyResult;
+use crate::slack::{apply_summary_message, ApplySummaryContext};
+
+/// How long a webhook gets to respond
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// The summary from [`apply_summary_json`]
+    #[default]
+    Json,
+    /// A Slack Block Kit message, for incoming webhooks
+    Slack,
+}
+
+impl std::str::FromStr for WebhookFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(WebhookFormat::Json),
+            "slack" => Ok(WebhookFormat::Slack),
+            _ => Err(format!("Unknown webhook format '{}'. Expected one of: json, slack", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    pub format: WebhookFormat,
+    /// Extra request headers, e.g. Authorization
+    pub headers: BTreeMap<String, String>,
+}
+
+/// An apply result as JSON: the outcome, environment, duration, migrations
+/// applied, objects changed and errors
+pub fn apply_summary_json(result: &ApplyResult, context: &ApplySummaryContext) -> Value {
+    let failed = !result.errors.is_empty() || result.plpgsql_errors_found > 0;
+    json!({
+        "status": if failed { "failed" } else { "succeeded" },
+        "environment": context.environment,
+        "duration_ms": context.duration.map(|duration| duration.as_millis() as u64),
+        "migrations_applied": result.migrations_applied,
+        "objects_created": result.objects_created,
+        "objects_updated": result.objects_updated,
+        "objects_deleted": result.objects_deleted,
+        "errors": result.errors,
+        "plpgsql_errors": result.plpgsql_errors_found,
+        "plpgsql_warnings": result.plpgsql_warnings_found,
+    })
+}
+
+/// Send the apply summary to every webhook, warning about those that fail
+pub async fn send_apply_summary(webhooks: &[Webhook], result: &ApplyResult, context: &ApplySummaryContext) {
+    for webhook in webhooks {
+        let body = match webhook.format {
+            WebhookFormat::Json => apply_summary_json(result, context),
+            WebhookFormat::Slack => apply_summary_message(result, context),
+        };
+        let host = Url::parse(&webhook.url).ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+
+        match tokio::time::timeout(WEBHOOK_TIMEOUT, post_json(webhook, &body)).await {
+            Ok(Ok(())) => info!(host = %host, "Sent apply summary to webhook"),
+            Ok(Err(e)) => warn!(host = %host, error = %e, "Webhook failed"),
+            Err(_) => warn!(host = %host, "Webhook timed out after {}s", WEBHOOK_TIMEOUT.as_secs()),
+        }
+    }
+}
+
+async fn post_json(webhook: &Webhook, body: &Value) -> Result<(), String> {
+    let url = Url::parse(&webhook.url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    let host = url.host_str().ok_or("Webhook URL has no host")?.to_string();
+    let port = url.port_or_known_default().ok_or("Webhook URL has no port")?;
+
+    let body = body.to_string();
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: pgmg\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        &url[url::Position::BeforePath..],
+        host,
+        body.len()
+    );
+    for (name, value) in &webhook.headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(&body);
+
+    let response = match url.scheme() {
+        "http" => send_plain(&host, port, request).await,
+        "https" => send_tls(host, port, request).await,
+        scheme => Err(format!("Unsupported webhook URL scheme '{}'", scheme)),
+    }.map_err(|e| format!("Request failed: {}", e))?;
+
+    check_response(&response)
+}
+
+async fn send_plain(host: &str, port: u16, request: String) -> Result<Vec<u8>, String> {
+    let mut stream = TcpStream::connect((host, port)).await.map_err(|e| e.to_string())?;
+    stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.map_err(|e| e.to_string())?;
+    Ok(response)
+}
+
+#[cfg(feature = "tls")]
+async fn send_tls(host: String, port: u16, request: String) -> Result<Vec<u8>, String> {
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+
+    tokio::task::spawn_blocking(move || {
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+        let server_name = rustls::pki_types::ServerName::try_from(host.clone()).map_err(|e| e.to_string())?;
+        let connection = rustls::ClientConnection::new(Arc::new(config), server_name).map_err(|e| e.to_string())?;
+        let socket = std::net::TcpStream::connect((host.as_str(), port)).map_err(|e| e.to_string())?;
+        socket.set_read_timeout(Some(WEBHOOK_TIMEOUT)).map_err(|e| e.to_string())?;
+        let mut stream = rustls::StreamOwned::new(connection, socket);
+
+        stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+        let mut response = Vec::new();
+        match stream.read_to_end(&mut response) {
+            Ok(_) => Ok(response),
+            // Servers often close without a TLS close_notify once they've responded
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => Ok(response),
+            Err(e) => Err(e.to_string()),
+        }
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[cfg(not(feature = "tls"))]
+async fn send_tls(_host: String, _port: u16, _request: String) -> Result<Vec<u8>, String> {
+    Err("https webhooks need pgmg built with the tls feature".to_string())
+}
+
+/// Fail unless the response has a 2xx status
+fn check_response(response: &[u8]) -> Result<(), String> {
+    let response = String::from_utf8_lossy(response);
+    let status = response.lines().next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or("Malformed response")?;
+
+    if !(200..300).contains(&status) {
+        let body = response.split_once("\r\n\r\n").map(|(_, body)| body.trim()).unwrap_or_default();
+        return Err(format!("Responded with status {}: {}", status, body.chars().take(200).collect::<String>()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn result() -> ApplyResult {
+        ApplyResult {
+            migrations_applied: vec!["004_invoices".to_string()],
+            objects_created: vec!["api.invoices".to_string()],
+            ..ApplyResult::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_summary_json() {
+        let context = ApplySummaryContext {
+            environment: Some("production".to_string()),
+            duration: Some(Duration::from_millis(1500)),
+        };
+
+        assert_eq!(apply_summary_json(&result(), &context), json!({
+            "status": "succeeded",
+            "environment": "production",
+            "duration_ms": 1500,
+            "migrations_applied": ["004_invoices"],
+            "objects_created": ["api.invoices"],
+            "objects_updated": [],
+            "objects_deleted": [],
+            "errors": [],
+            "plpgsql_errors": 0,
+            "plpgsql_warnings": 0,
+        }));
+    }
+
+    #[test]
+    fn test_check_response() {
+        assert!(check_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").is_ok());
+        assert!(check_response(b"HTTP/1.1 204 No Content\r\n\r\n").is_ok());
+
+        let err = check_response(b"HTTP/1.1 404 Not Found\r\n\r\nno_service").unwrap_err();
+        assert_eq!(err, "Responded with status 404: no_service");
+        assert!(check_response(b"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_post_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the whole JSON body has arrived
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let webhook = Webhook {
+            url: format!("http://127.0.0.1:{}/deploys?team=db", port),
+            format: WebhookFormat::Json,
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer secret".to_string())]),
+        };
+        post_json(&webhook, &json!({ "status": "succeeded" })).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /deploys?team=db HTTP/1.1\r\n"), "{}", request);
+        assert!(request.contains("\r\nAuthorization: Bearer secret\r\n"), "{}", request);
+        assert!(request.ends_with("\r\n\r\n{\"status\":\"succeeded\"}"), "{}", request);
+    }
+}
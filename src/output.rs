@@ -84,39 +84,36 @@ impl OutputHandler for LibraryOutputHandler {
     }
 }
 
-/// CLI output handler that prints to stdout with colors
+/// CLI output handler that prints through the `logging::output` sink, so it
+/// follows `--quiet` and `--no-color`
 #[cfg(feature = "cli")]
 pub struct CliOutputHandler;
 
 #[cfg(feature = "cli")]
 impl OutputHandler for CliOutputHandler {
     fn success(&self, message: &str) {
-        use owo_colors::OwoColorize;
-        println!("{} {}", "✓".green(), message);
+        crate::logging::output::success(message);
     }
     
     fn error(&self, message: &str) {
-        use owo_colors::OwoColorize;
-        eprintln!("{} {}", "✗".red(), message);
+        crate::logging::output::error(message);
     }
     
     fn info(&self, message: &str) {
-        println!("{}", message);
+        crate::logging::output::line(message);
     }
     
     fn warning(&self, message: &str) {
-        use owo_colors::OwoColorize;
-        println!("{} {}", "⚠".yellow(), message);
+        crate::logging::output::warning(message);
     }
     
     fn heading(&self, message: &str) {
-        use owo_colors::OwoColorize;
-        println!("\n{}", message.bold());
+        crate::logging::output::subheader(message);
     }
     
     fn status(&self, action: &str, message: &str) {
         use owo_colors::OwoColorize;
-        println!("{:>12} {}", action.green().bold(), message);
+        crate::logging::output::line(format!("{:>12} {}", action.green().bold(), message));
     }
     
     fn debug(&self, message: &str) {
@@ -1,5 +1,6 @@
 use crate::sql::{SqlObject, ObjectType};
 use owo_colors::OwoColorize;
+use crate::logging::output;
 
 #[derive(Debug, Clone)]
 pub struct PlpgsqlCheckResult {
@@ -24,6 +25,87 @@ pub struct PlpgsqlCheckError {
     pub check_result: PlpgsqlCheckResult,
 }
 
+/// Directive in a function's definition, before it or in its body, that
+/// leaves out plpgsql_check findings with the given codes:
+/// `-- pgmg:ignore-check(42P01, 00000)`. Without codes, every finding for the
+/// function is left out.
+pub const IGNORE_CHECK_DIRECTIVE: &str = "-- pgmg:ignore-check";
+
+/// The lowest level that makes `check` fail and blocks an apply
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CheckSeverity {
+    #[default]
+    Error,
+    Warning,
+}
+
+impl std::str::FromStr for CheckSeverity {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "error" => Ok(CheckSeverity::Error),
+            "warning" => Ok(CheckSeverity::Warning),
+            _ => Err(format!("Invalid fail_on '{}'. Expected one of: error, warning", s)),
+        }
+    }
+}
+
+/// Which plpgsql_check findings are reported and which of them are failures,
+/// from the `[plpgsql_check]` section of pgmg.toml
+#[derive(Debug, Clone, Default)]
+pub struct CheckPolicy {
+    pub fail_on: CheckSeverity,
+    /// Codes (SQLSTATEs) left out for every function, for known false positives
+    pub ignore: Vec<String>,
+}
+
+impl CheckPolicy {
+    /// Whether a finding is left out, by the global ignore list or by an
+    /// ignore-check directive in `function`'s definition
+    pub fn is_ignored(&self, result: &PlpgsqlCheckResult, function: Option<&SqlObject>) -> bool {
+        let code = result.sqlstate.as_deref().unwrap_or("");
+        if self.ignore.iter().any(|ignored| ignored.eq_ignore_ascii_case(code)) {
+            return true;
+        }
+        
+        function
+            .and_then(|function| ignored_check_codes(&function.ddl_statement))
+            .is_some_and(|codes| codes.is_empty() || codes.iter().any(|ignored| ignored.eq_ignore_ascii_case(code)))
+    }
+    
+    /// Whether a finding fails a check or blocks an apply. plpgsql_check
+    /// emits levels like "warning extra", so match on prefix.
+    pub fn is_failure(&self, result: &PlpgsqlCheckResult) -> bool {
+        let level = result.level.as_deref().unwrap_or("");
+        level.starts_with("error") || (self.fail_on == CheckSeverity::Warning && level.starts_with("warning"))
+    }
+}
+
+/// The codes named by ignore-check directives in a definition: `None` without
+/// a directive, and an empty list if a directive names no codes
+fn ignored_check_codes(ddl: &str) -> Option<Vec<String>> {
+    let mut found = false;
+    let mut codes = Vec::new();
+    
+    for line in ddl.lines() {
+        let Some(rest) = line.trim().strip_prefix(IGNORE_CHECK_DIRECTIVE) else {
+            continue;
+        };
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            // A bare directive ignores everything
+            return Some(Vec::new());
+        }
+        let Some(list) = rest.strip_prefix('(').and_then(|rest| rest.split_once(')')).map(|(list, _)| list) else {
+            continue;
+        };
+        found = true;
+        codes.extend(list.split(',').map(str::trim).filter(|code| !code.is_empty()).map(str::to_string));
+    }
+    
+    found.then_some(codes)
+}
+
 /// Check if the plpgsql_check extension is installed
 pub async fn is_plpgsql_check_available<C>(client: &C) -> Result<bool, Box<dyn std::error::Error>>
 where
@@ -204,8 +286,7 @@ where
     
     // Check if extension is available
     if !is_plpgsql_check_available(client).await? {
-        eprintln!("{}: plpgsql_check extension is not installed. Skipping function/procedure checks.", 
-            "Warning".yellow().bold());
+        output::warning("plpgsql_check extension is not installed. Skipping function/procedure checks.");
         return Ok(errors);
     }
     
@@ -361,7 +442,7 @@ where
     }
     
     if errors.is_empty() && num_functions_to_check > 0 {
-        println!("  {} All dependent functions remain compatible", "✓".green().bold());
+        output::line(format!("  {} All dependent functions remain compatible", output::symbol("✓").green().bold()));
     }
     
     Ok(errors)
@@ -457,97 +538,124 @@ fn calculate_source_line(function: &SqlObject, function_line: Option<i32>) -> Op
     Some(start + body_offset + line.saturating_sub(1))
 }
 
-/// Format and display plpgsql_check errors, sorted by severity (warnings first, then errors)
-pub fn display_check_errors(errors: &[PlpgsqlCheckError]) {
-    if errors.is_empty() {
-        return;
+/// How severe a finding is, most severe first. plpgsql_check emits variants
+/// like "warning extra", so match on prefix.
+fn severity_rank(result: &PlpgsqlCheckResult) -> u8 {
+    let level = result.level.as_deref().unwrap_or("error");
+    if level.starts_with("error") {
+        0
+    } else if level.starts_with("warning") {
+        1
+    } else {
+        2
     }
-    
-    println!("\n{}", "=== PL/pgSQL Check Results ===".bold().yellow());
-    
-    // Sort errors by level - warnings first, then errors. plpgsql_check emits
-    // variants like "warning extra", so match on prefix.
-    fn level_order(level: &str) -> u8 {
-        if level.starts_with("warning") {
-            0
-        } else if level.starts_with("error") {
-            1
-        } else {
-            2
+}
+
+/// Findings grouped by function. Functions with errors come first, then by
+/// file and name; each function's findings are sorted by severity, then line.
+pub fn group_check_errors(errors: &[PlpgsqlCheckError]) -> Vec<(&str, Vec<&PlpgsqlCheckError>)> {
+    let mut groups: Vec<(&str, Vec<&PlpgsqlCheckError>)> = Vec::new();
+    for error in errors {
+        match groups.iter_mut().find(|(name, _)| *name == error.function_name) {
+            Some((_, group)) => group.push(error),
+            None => groups.push((&error.function_name, vec![error])),
         }
     }
 
-    let mut sorted_errors = errors.to_vec();
-    sorted_errors.sort_by(|a, b| {
-        let level_a = a.check_result.level.as_deref().unwrap_or("error");
-        let level_b = b.check_result.level.as_deref().unwrap_or("error");
-        level_order(level_a).cmp(&level_order(level_b))
+    for (_, group) in &mut groups {
+        group.sort_by_key(|error| (severity_rank(&error.check_result), error.source_line, error.check_result.lineno));
+    }
+    groups.sort_by(|(a_name, a), (b_name, b)| {
+        severity_rank(&a[0].check_result).cmp(&severity_rank(&b[0].check_result))
+            .then_with(|| a[0].source_file.cmp(&b[0].source_file))
+            .then_with(|| a_name.cmp(b_name))
     });
-    
-    for error in &sorted_errors {
-        let level_str = error.check_result.level.as_deref().unwrap_or("error");
-        let level_colored = if level_str.starts_with("error") {
-            format!("{}", level_str.red().bold())
-        } else if level_str.starts_with("warning") {
-            format!("{}", level_str.yellow().bold())
-        } else {
-            format!("{}", level_str.blue().bold())
-        };
-        
-        // Format location
-        let location = match (&error.source_file, error.source_line) {
-            (Some(file), Some(line)) => format!("{}:{}", file, line),
-            (Some(file), None) => file.clone(),
-            _ => error.function_name.clone(),
-        };
-        
-        println!("\n{} {} in {}", 
-            level_colored,
-            format!("[{}]", error.check_result.sqlstate.as_deref().unwrap_or("00000")).dimmed(),
-            location.cyan()
+    groups
+}
+
+/// The report `display_check_errors` prints, grouped by function
+pub fn format_check_errors(errors: &[PlpgsqlCheckError]) -> String {
+    use std::fmt::Write;
+
+    let mut report = String::new();
+    if errors.is_empty() {
+        return report;
+    }
+
+    let _ = writeln!(report, "\n{}", "=== PL/pgSQL Check Results ===".bold().yellow());
+
+    for (function_name, group) in group_check_errors(errors) {
+        let file = group.iter().find_map(|error| error.source_file.as_deref());
+        let _ = writeln!(report, "\n{} {}",
+            function_name.cyan().bold(),
+            file.map(|file| file.dimmed().to_string()).unwrap_or_default()
         );
-        
-        // Display the main message
-        if let Some(message) = &error.check_result.message {
-            println!("  {}", message);
-        }
-        
-        // Display detail if available
-        if let Some(detail) = &error.check_result.detail {
-            println!("  {}: {}", "Detail".dimmed(), detail);
-        }
-        
-        // Display hint if available
-        if let Some(hint) = &error.check_result.hint {
-            println!("  {}: {}", "Hint".green().dimmed(), hint);
-        }
-        
-        // Display context if available
-        if let Some(context) = &error.check_result.context {
-            println!("  {}: {}", "Context".dimmed(), context);
+
+        for error in group {
+            let level_str = error.check_result.level.as_deref().unwrap_or("error");
+            let level_colored = if level_str.starts_with("error") {
+                format!("{}", level_str.red().bold())
+            } else if level_str.starts_with("warning") {
+                format!("{}", level_str.yellow().bold())
+            } else {
+                format!("{}", level_str.blue().bold())
+            };
+
+            // Lines in the file where known, otherwise relative to the function body
+            let location = match (error.source_line, error.check_result.lineno) {
+                (Some(line), _) => format!("line {}", line),
+                (None, Some(lineno)) => format!("function line {}", lineno),
+                (None, None) => String::new(),
+            };
+
+            let _ = writeln!(report, "  {} {} {}",
+                level_colored,
+                format!("[{}]", error.check_result.sqlstate.as_deref().unwrap_or("00000")).dimmed(),
+                location.cyan()
+            );
+
+            if let Some(message) = &error.check_result.message {
+                let _ = writeln!(report, "    {}", message);
+            }
+            if let Some(detail) = &error.check_result.detail {
+                let _ = writeln!(report, "    {}: {}", "Detail".dimmed(), detail);
+            }
+            if let Some(hint) = &error.check_result.hint {
+                let _ = writeln!(report, "    {}: {}", "Hint".green().dimmed(), hint);
+            }
+            if let Some(context) = &error.check_result.context {
+                let _ = writeln!(report, "    {}: {}", "Context".dimmed(), context);
+            }
         }
     }
-    
+
     // Count warnings and errors (match prefix to include "warning extra", etc.)
-    let warnings = sorted_errors.iter()
+    let warnings = errors.iter()
         .filter(|e| e.check_result.level.as_deref().is_some_and(|l| l.starts_with("warning")))
         .count();
-    let errors_count = sorted_errors.iter()
+    let errors_count = errors.iter()
         .filter(|e| e.check_result.level.as_deref().is_some_and(|l| l.starts_with("error")))
         .count();
-    
-    // Display summary
-    print!("\n{} ", sorted_errors.len().to_string().yellow().bold());
+
+    let _ = write!(report, "\n{} ", errors.len().to_string().yellow().bold());
     if warnings > 0 && errors_count > 0 {
-        print!("issues ({} warnings, {} errors) ", warnings, errors_count);
+        let _ = write!(report, "issues ({} warnings, {} errors) ", warnings, errors_count);
     } else if warnings > 0 {
-        print!("warning{} ", if warnings == 1 { "" } else { "s" });
+        let _ = write!(report, "warning{} ", if warnings == 1 { "" } else { "s" });
     } else if errors_count > 0 {
-        print!("error{} ", if errors_count == 1 { "" } else { "s" });
+        let _ = write!(report, "error{} ", if errors_count == 1 { "" } else { "s" });
     } else {
-        print!("issue{} ", if sorted_errors.len() == 1 { "" } else { "s" });
+        let _ = write!(report, "issue{} ", if errors.len() == 1 { "" } else { "s" });
     }
-    println!("found by plpgsql_check");
+    let _ = writeln!(report, "found by plpgsql_check");
+
+    report
+}
+
+/// Display plpgsql_check errors grouped by function, most severe first.
+/// These are printed even in quiet mode, like other errors.
+pub fn display_check_errors(errors: &[PlpgsqlCheckError]) {
+    print!("{}", crate::logging::output::render(format_check_errors(errors)));
 }
 
 #[cfg(test)]
@@ -619,4 +727,99 @@ mod tests {
         function.start_line = None;
         assert_eq!(calculate_source_line(&function, Some(1)), None);
     }
-}
\ No newline at end of file
+
+    fn check_result(level: &str, sqlstate: &str) -> PlpgsqlCheckResult {
+        PlpgsqlCheckResult {
+            functionid: Some("test.my_func()".to_string()),
+            lineno: Some(3),
+            statement: None,
+            sqlstate: Some(sqlstate.to_string()),
+            message: Some("relation \"missing\" does not exist".to_string()),
+            detail: None,
+            hint: None,
+            level: Some(level.to_string()),
+            position: None,
+            query: None,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_ignored_check_codes() {
+        assert_eq!(ignored_check_codes("CREATE FUNCTION f() ..."), None);
+        assert_eq!(
+            ignored_check_codes("-- pgmg:ignore-check(42P01, 00000)\nCREATE FUNCTION f() ..."),
+            Some(vec!["42P01".to_string(), "00000".to_string()])
+        );
+        assert_eq!(ignored_check_codes("CREATE FUNCTION f() AS $$\n  -- pgmg:ignore-check\nBEGIN END; $$"), Some(vec![]));
+        assert_eq!(ignored_check_codes("-- pgmg:ignore-checks(42P01)\nCREATE FUNCTION f() ..."), None);
+    }
+
+    #[test]
+    fn test_check_policy_ignores_codes() {
+        let policy = CheckPolicy { ignore: vec!["42p01".to_string()], ..Default::default() };
+        assert!(policy.is_ignored(&check_result("error", "42P01"), None));
+        assert!(!policy.is_ignored(&check_result("error", "42703"), None));
+
+        let function = make_function("-- pgmg:ignore-check(42703)\nCREATE FUNCTION f() RETURNS void LANGUAGE plpgsql AS $$ BEGIN END; $$", 1);
+        let policy = CheckPolicy::default();
+        assert!(policy.is_ignored(&check_result("error", "42703"), Some(&function)));
+        assert!(!policy.is_ignored(&check_result("error", "42P01"), Some(&function)));
+    }
+
+    #[test]
+    fn test_check_policy_fail_on() {
+        let policy = CheckPolicy::default();
+        assert!(policy.is_failure(&check_result("error", "42P01")));
+        assert!(!policy.is_failure(&check_result("warning extra", "00000")));
+
+        let policy = CheckPolicy { fail_on: CheckSeverity::Warning, ..Default::default() };
+        assert!(policy.is_failure(&check_result("warning extra", "00000")));
+        assert!(!policy.is_failure(&check_result("notice", "00000")));
+        assert!("warnings".parse::<CheckSeverity>().is_err());
+    }
+
+    fn finding(function_name: &str, level: &str, source_line: Option<usize>) -> PlpgsqlCheckError {
+        PlpgsqlCheckError {
+            function_name: function_name.to_string(),
+            source_file: Some(format!("sql/{}.sql", function_name)),
+            source_line,
+            check_result: check_result(level, "42P01"),
+        }
+    }
+
+    #[test]
+    fn test_group_check_errors() {
+        let errors = vec![
+            finding("api.b", "warning extra", Some(4)),
+            finding("api.a", "warning", Some(9)),
+            finding("api.b", "error", Some(12)),
+            finding("api.a", "warning performance", Some(3)),
+            finding("api.b", "error", Some(7)),
+        ];
+
+        let groups = group_check_errors(&errors);
+        let summary: Vec<(&str, Vec<(&str, Option<usize>)>)> = groups.iter()
+            .map(|(name, group)| (*name, group.iter()
+                .map(|error| (error.check_result.level.as_deref().unwrap(), error.source_line))
+                .collect()))
+            .collect();
+
+        assert_eq!(summary, vec![
+            ("api.b", vec![("error", Some(7)), ("error", Some(12)), ("warning extra", Some(4))]),
+            ("api.a", vec![("warning performance", Some(3)), ("warning", Some(9))]),
+        ]);
+    }
+
+    #[test]
+    fn test_check_errors_render_without_colors() {
+        let errors = vec![finding("api.a", "error", Some(3)), finding("api.b", "warning", None)];
+        let report = format_check_errors(&errors);
+        assert!(report.contains("\x1b["), "the report is styled");
+
+        output::configure(false, true);
+        let rendered = output::render(&report);
+        assert!(!rendered.contains("\x1b["), "{:?}", rendered);
+        assert!(rendered.contains("api.a"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::commands::ApplyResult;
+use crate::logging::format_duration;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Slack rejects section text longer than this
+const MAX_SECTION_TEXT: usize = 3000;
+
+/// Names listed per section before the rest are summarized as "and N more"
+const MAX_LISTED: usize = 15;
+
+/// Details about the deploy shown alongside an apply result
+#[derive(Debug, Clone, Default)]
+pub struct ApplySummaryContext {
+    /// e.g. `production`
+    pub environment: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+/// Format an apply result as a Slack Block Kit message: a header with the
+/// outcome, the environment and duration, then a section each for migrations,
+/// changed objects and errors. The returned JSON can be posted as is to an
+/// incoming webhook or as the body of `chat.postMessage`.
+pub fn apply_summary_message(result: &ApplyResult, context: &ApplySummaryContext) -> Value {
+    let failed = !result.errors.is_empty() || result.plpgsql_errors_found > 0;
+    let title = match (&context.environment, failed) {
+        (Some(env), false) => format!("Schema deploy to {} succeeded", env),
+        (Some(env), true) => format!("Schema deploy to {} failed", env),
+        (None, false) => "Schema deploy succeeded".to_string(),
+        (None, true) => "Schema deploy failed".to_string(),
+    };
+    let changed = result.objects_created.len() + result.objects_updated.len() + result.objects_deleted.len();
+
+    let mut fields = Vec::new();
+    if let Some(env) = &context.environment {
+        fields.push(field("Environment", env));
+    }
+    if let Some(duration) = context.duration {
+        fields.push(field("Duration", &format_duration(duration)));
+    }
+    fields.push(field("Migrations", &result.migrations_applied.len().to_string()));
+    fields.push(field("Objects changed", &changed.to_string()));
+
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": format!("{} {}", if failed { ":x:" } else { ":white_check_mark:" }, title), "emoji": true },
+        }),
+        json!({ "type": "section", "fields": fields }),
+    ];
+
+    if !result.migrations_applied.is_empty() {
+        blocks.push(section(&format!("*Migrations*\n{}", bullet_list(&result.migrations_applied))));
+    }
+
+    if changed > 0 {
+        let mut lines = Vec::new();
+        for (label, objects) in [
+            ("Created", &result.objects_created),
+            ("Updated", &result.objects_updated),
+            ("Deleted", &result.objects_deleted),
+        ] {
+            if !objects.is_empty() {
+                lines.push(format!("_{}_\n{}", label, bullet_list(objects)));
+            }
+        }
+        blocks.push(section(&format!("*Objects*\n{}", lines.join("\n"))));
+    }
+
+    if !result.errors.is_empty() {
+        let errors = result.errors.iter().map(|e| format!("```{}```", e)).collect::<Vec<_>>().join("\n");
+        blocks.push(section(&format!("*Errors*\n{}", errors)));
+    }
+
+    if result.plpgsql_errors_found > 0 || result.plpgsql_warnings_found > 0 {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{
+                "type": "mrkdwn",
+                "text": format!(
+                    "plpgsql_check: {} errors, {} warnings",
+                    result.plpgsql_errors_found,
+                    result.plpgsql_warnings_found
+                ),
+            }],
+        }));
+    }
+
+    if !result.skipped.is_empty() {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{
+                "type": "mrkdwn",
+                "text": format!("{} statements skipped", result.skipped.len()),
+            }],
+        }));
+    }
+
+    json!({
+        // Shown in notifications and by clients that can't render blocks
+        "text": format!("{}: {} migrations, {} objects changed", title, result.migrations_applied.len(), changed),
+        "blocks": blocks,
+    })
+}
+
+fn field(label: &str, value: &str) -> Value {
+    json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", label, value) })
+}
+
+fn section(text: &str) -> Value {
+    json!({ "type": "section", "text": { "type": "mrkdwn", "text": truncate(text, MAX_SECTION_TEXT) } })
+}
+
+fn bullet_list(names: &[String]) -> String {
+    let mut lines: Vec<String> = names.iter()
+        .take(MAX_LISTED)
+        .map(|name| format!("• `{}`", name))
+        .collect();
+    if names.len() > MAX_LISTED {
+        lines.push(format!("…and {} more", names.len() - MAX_LISTED));
+    }
+    lines.join("\n")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    // Don't leave a code block open
+    if truncated.matches("```").count() % 2 == 1 {
+        truncated.truncate(truncated.len() - '…'.len_utf8());
+        truncated.truncate(truncated.char_indices().nth(max_chars - 4).map(|(i, _)| i).unwrap_or(truncated.len()));
+        truncated.push_str("…```");
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_result() -> ApplyResult {
+        ApplyResult {
+            migrations_applied: vec!["001_orders".to_string()],
+            objects_created: vec!["api.order_totals".to_string()],
+            objects_updated: (0..20).map(|i| format!("api.view_{}", i)).collect(),
+            objects_deleted: vec![],
+            errors: vec![],
+            plpgsql_errors_found: 0,
+            plpgsql_warnings_found: 0,
+            skipped: vec![],
+            matview_refreshes: vec![],
+            backup_path: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_summary_message() {
+        let context = ApplySummaryContext {
+            environment: Some("production".to_string()),
+            duration: Some(Duration::from_millis(2500)),
+        };
+        let message = apply_summary_message(&apply_result(), &context);
+
+        assert_eq!(message["text"], "Schema deploy to production succeeded: 1 migrations, 21 objects changed");
+
+        let blocks = message["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "header");
+        assert!(blocks[0]["text"]["text"].as_str().unwrap().contains("production succeeded"));
+        assert_eq!(blocks[1]["fields"][1]["text"], "*Duration*\n2.500s");
+
+        let objects = blocks[3]["text"]["text"].as_str().unwrap();
+        assert!(objects.contains("`api.order_totals`"));
+        assert!(objects.contains("…and 5 more"));
+    }
+
+    #[test]
+    fn test_apply_summary_message_with_errors() {
+        let mut result = apply_result();
+        result.errors.push("relation \"orders\" does not exist".to_string());
+
+        let message = apply_summary_message(&result, &ApplySummaryContext::default());
+
+        assert!(message["text"].as_str().unwrap().starts_with("Schema deploy failed"));
+        let errors = message["blocks"].as_array().unwrap().last().unwrap()["text"]["text"].as_str().unwrap();
+        assert!(errors.starts_with("*Errors*"));
+    }
+
+    #[test]
+    fn test_truncate_closes_code_block() {
+        let text = format!("```{}```", "x".repeat(100));
+        let truncated = truncate(&text, 50);
+
+        assert_eq!(truncated.chars().count(), 50);
+        assert!(truncated.ends_with("…```"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Changes to pg_cron jobs defined in code. A job is identified by its name,
+//! so a change to its schedule or command is made with `cron.alter_job` on the
+//! existing job instead of unscheduling it and scheduling it again, which
+//! would give it a new id and lose its run history.
+
+use sha2::{Digest, Sha256};
+use crate::sql::quote::quote_literal;
+
+/// A job scheduled with `cron.schedule(job_name, schedule, command)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronJob {
+    pub name: String,
+    /// The schedule with its fields separated by single spaces
+    pub schedule: String,
+    /// The command exactly as given, without surrounding whitespace
+    pub command: String,
+}
+
+impl CronJob {
+    /// Hash of the job's name, schedule and command. Unlike the hash of other
+    /// objects' DDL it keeps the case of the command, and ignores how the
+    /// strings are quoted.
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}\n{}\n{}", self.name, self.schedule, self.command).as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// The job a `SELECT cron.schedule(job_name, schedule, command)` statement
+/// schedules, or None if the statement doesn't schedule a named job with
+/// string constants
+pub fn parse_cron_job(statement: &str) -> Option<CronJob> {
+    let parsed = pg_query::parse(statement).ok()?;
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .find_map(|node| {
+            let pg_query::NodeEnum::SelectStmt(select) = node else {
+                return None;
+            };
+            select.target_list.iter().find_map(|target| {
+                let Some(pg_query::NodeEnum::ResTarget(res_target)) = &target.node else {
+                    return None;
+                };
+                let Some(pg_query::NodeEnum::FuncCall(func_call)) = res_target.val.as_ref()?.node.as_ref() else {
+                    return None;
+                };
+                let names: Vec<&str> = func_call.funcname.iter()
+                    .filter_map(|node| match &node.node {
+                        Some(pg_query::NodeEnum::String(s)) => Some(s.sval.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                if !matches!(names.as_slice(), ["cron", "schedule"] | ["schedule"]) {
+                    return None;
+                }
+
+                let args: Vec<&str> = func_call.args.iter().map(string_constant).collect::<Option<_>>()?;
+                let [name, schedule, command] = args.as_slice() else {
+                    return None;
+                };
+                Some(CronJob {
+                    name: name.to_string(),
+                    schedule: schedule.split_whitespace().collect::<Vec<_>>().join(" "),
+                    command: command.trim().to_string(),
+                })
+            })
+        })
+}
+
+fn string_constant(node: &pg_query::protobuf::Node) -> Option<&str> {
+    match &node.node {
+        Some(pg_query::NodeEnum::AConst(a_const)) => match &a_const.val {
+            Some(pg_query::protobuf::a_const::Val::Sval(s)) => Some(s.sval.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Build the statement that turns the `applied` job into the `current` one,
+/// passing `cron.alter_job` only what changed. Empty if neither the schedule
+/// nor the command changed, e.g. the statement was only reformatted.
+pub fn cron_job_changes(applied: &CronJob, current: &CronJob) -> Vec<String> {
+    let mut changed = Vec::new();
+    if applied.schedule != current.schedule {
+        changed.push(format!("schedule => {}", quote_literal(&current.schedule)));
+    }
+    if applied.command != current.command {
+        changed.push(format!("command => {}", quote_literal(&current.command)));
+    }
+    if changed.is_empty() {
+        return Vec::new();
+    }
+
+    vec![format!(
+        "SELECT cron.alter_job(jobid, {}) FROM cron.job WHERE jobname = {}",
+        changed.join(", "),
+        quote_literal(&current.name)
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(schedule: &str, command: &str) -> CronJob {
+        CronJob {
+            name: "cleanup".to_string(),
+            schedule: schedule.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_cron_job() {
+        let parsed = parse_cron_job(
+            "SELECT cron.schedule('cleanup', '0  3 * * *', $$DELETE FROM logs WHERE level = 'Debug'$$)"
+        ).unwrap();
+        assert_eq!(parsed, job("0 3 * * *", "DELETE FROM logs WHERE level = 'Debug'"));
+
+        // Quoting doesn't change the job
+        let requoted = parse_cron_job(
+            "select cron.schedule('cleanup', '0 3 * * *', 'DELETE FROM logs WHERE level = ''Debug''');"
+        ).unwrap();
+        assert_eq!(requoted, parsed);
+        assert_eq!(requoted.hash(), parsed.hash());
+
+        // Unnamed jobs can't be tracked
+        assert_eq!(parse_cron_job("SELECT cron.schedule('0 3 * * *', 'VACUUM')"), None);
+        assert_eq!(parse_cron_job("SELECT cron.unschedule('cleanup')"), None);
+    }
+
+    #[test]
+    fn test_cron_job_hash_keeps_command_case() {
+        let lower = job("0 3 * * *", "DELETE FROM logs WHERE level = 'debug'");
+        let upper = job("0 3 * * *", "DELETE FROM logs WHERE level = 'Debug'");
+        assert_ne!(lower.hash(), upper.hash());
+    }
+
+    #[test]
+    fn test_cron_job_changes() {
+        let applied = job("0 3 * * *", "VACUUM logs");
+
+        assert!(cron_job_changes(&applied, &applied).is_empty());
+        assert_eq!(
+            cron_job_changes(&applied, &job("30 4 * * *", "VACUUM logs")),
+            vec!["SELECT cron.alter_job(jobid, schedule => '30 4 * * *') FROM cron.job WHERE jobname = 'cleanup'"]
+        );
+        assert_eq!(
+            cron_job_changes(&applied, &job("30 4 * * *", "VACUUM ANALYZE logs")),
+            vec!["SELECT cron.alter_job(jobid, schedule => '30 4 * * *', command => 'VACUUM ANALYZE logs') \
+                  FROM cron.job WHERE jobname = 'cleanup'"]
+        );
+    }
+}
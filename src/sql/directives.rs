@@ -0,0 +1,347 @@
+//! Per-object directives written as comments above an object's definition:
+//!
+//! ```sql
+//! -- pgmg:depends-on api.lookup_table, api.lookup_func()
+//! -- pgmg:skip-env prod
+//! -- pgmg:no-drop
+//! -- pgmg:owner api_owner
+//! -- pgmg:refresh concurrently
+//! -- pgmg:tags billing, reporting
+//! CREATE VIEW api.report AS ...
+//! ```
+//!
+//! Only the comment lines directly before a statement are read. Comments
+//! don't change an object's hash, so adding or removing a directive never
+//! recreates the object by itself (`owner` is the exception, as it adds an
+//! ALTER statement).
+
+use std::fmt;
+use std::str::FromStr;
+use crate::sql::{Dependencies, ObjectType, QualifiedIdent};
+use crate::sql::quote::{quote_ident, quote_qualified_ident};
+
+const DIRECTIVE_PREFIX: &str = "-- pgmg:";
+
+/// Directives handled elsewhere, which may appear in the same comment block
+const OTHER_DIRECTIVES: &[&str] = &["no-transaction", "apply-after", "ignore-check"];
+
+/// An object named by `-- pgmg:depends-on`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependsOn {
+    /// Written with parentheses: `api.lookup_func()`
+    Function(QualifiedIdent),
+    /// A table, view or type
+    Relation(QualifiedIdent),
+}
+
+impl DependsOn {
+    fn parse(arg: &str) -> Option<Self> {
+        let (name, is_function) = match arg.split_once('(') {
+            Some((name, rest)) if rest.ends_with(')') => (name, true),
+            Some(_) => return None,
+            None => (arg, false),
+        };
+        if name.is_empty() || name.split('.').count() > 2 || name.split('.').any(str::is_empty) {
+            return None;
+        }
+
+        let name = QualifiedIdent::from_qualified_name(name);
+        Some(if is_function { DependsOn::Function(name) } else { DependsOn::Relation(name) })
+    }
+}
+
+/// What apply does with the data of a materialized view it recreated:
+/// `matview_refresh` in pgmg.toml, or `-- pgmg:refresh` for one view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RefreshStrategy {
+    /// Leave it as created; a view created WITH NO DATA stays empty
+    #[default]
+    Skip,
+    /// Refresh a view created WITH NO DATA before the apply commits
+    Refresh,
+    /// As `Refresh`, and refresh a view created WITH DATA again after the
+    /// commit with REFRESH ... CONCURRENTLY, which needs a unique index
+    Concurrently,
+}
+
+impl FromStr for RefreshStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "none" => Ok(RefreshStrategy::Skip),
+            "refresh" => Ok(RefreshStrategy::Refresh),
+            "concurrently" => Ok(RefreshStrategy::Concurrently),
+            _ => Err(format!("Invalid refresh strategy '{}'. Expected one of: none, refresh, concurrently", s)),
+        }
+    }
+}
+
+impl fmt::Display for RefreshStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RefreshStrategy::Skip => "none",
+            RefreshStrategy::Refresh => "refresh",
+            RefreshStrategy::Concurrently => "concurrently",
+        })
+    }
+}
+
+/// The directives given for one object
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectDirectives {
+    /// Objects this one depends on that the parser can't see, e.g. through
+    /// dynamic SQL: `-- pgmg:depends-on api.lookup_table, api.lookup_func()`
+    pub depends_on: Vec<DependsOn>,
+    /// Environments the object isn't created in: `-- pgmg:skip-env prod`
+    pub skip_envs: Vec<String>,
+    /// The object is never dropped. Removing it from code only stops pgmg
+    /// from tracking it, and changes that need a drop are refused.
+    pub no_drop: bool,
+    /// Role the object is handed to after creation: `-- pgmg:owner api_owner`
+    pub owner: Option<String>,
+    /// How a recreated materialized view is refreshed, overriding
+    /// `matview_refresh`: `-- pgmg:refresh concurrently`
+    pub refresh: Option<RefreshStrategy>,
+    /// Functional areas the object belongs to, for `--tag` filters:
+    /// `-- pgmg:tags billing, critical`. Stored lowercase.
+    pub tags: Vec<String>,
+}
+
+impl ObjectDirectives {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The `depends-on` objects as parsed dependencies. A name without
+    /// parentheses may be a relation or a type, so it's added as both.
+    pub fn dependencies(&self) -> Dependencies {
+        let mut dependencies = Dependencies::default();
+        for depends_on in &self.depends_on {
+            match depends_on {
+                DependsOn::Function(name) => {
+                    dependencies.functions.insert(name.clone());
+                }
+                DependsOn::Relation(name) => {
+                    dependencies.relations.insert(name.clone());
+                    dependencies.types.insert(name.clone());
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// Whether the object is left out in the given environment
+    pub fn skipped_in(&self, environment: Option<&str>) -> bool {
+        match environment {
+            Some(environment) => self.skip_envs.iter().any(|env| env == environment),
+            None => false,
+        }
+    }
+}
+
+/// Read the directives from the comment lines leading a statement. Unknown
+/// or malformed directives are returned as warnings rather than errors, so a
+/// typo can't make pgmg skip the file and drop its objects.
+pub fn parse_object_directives(statement: &str) -> (ObjectDirectives, Vec<String>) {
+    let mut directives = ObjectDirectives::default();
+    let mut warnings = Vec::new();
+
+    for line in statement.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with("--") {
+            break;
+        }
+        let Some(directive) = line.strip_prefix(DIRECTIVE_PREFIX) else {
+            continue;
+        };
+
+        let (name, rest) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+        let args = split_arguments(rest);
+
+        match name {
+            "depends-on" if !args.is_empty() => {
+                for arg in args {
+                    match DependsOn::parse(arg) {
+                        Some(depends_on) => directives.depends_on.push(depends_on),
+                        None => warnings.push(format!("`pgmg:depends-on` can't read `{}` as an object name", arg)),
+                    }
+                }
+            }
+            "skip-env" if !args.is_empty() => {
+                directives.skip_envs.extend(args.iter().map(|arg| arg.to_string()));
+            }
+            "tags" if !args.is_empty() => {
+                for arg in args {
+                    let tag = arg.to_lowercase();
+                    if !directives.tags.contains(&tag) {
+                        directives.tags.push(tag);
+                    }
+                }
+            }
+            "no-drop" if args.is_empty() => directives.no_drop = true,
+            "owner" if args.len() == 1 => directives.owner = Some(args[0].to_string()),
+            "refresh" if args.len() == 1 => match args[0].parse() {
+                Ok(strategy) => directives.refresh = Some(strategy),
+                Err(e) => warnings.push(format!("`pgmg:refresh`: {}", e)),
+            },
+            "depends-on" | "skip-env" | "tags" => {
+                warnings.push(format!("`pgmg:{}` needs at least one name", name));
+            }
+            "no-drop" => warnings.push("`pgmg:no-drop` takes no arguments".to_string()),
+            "owner" => warnings.push("`pgmg:owner` takes exactly one role name".to_string()),
+            "refresh" => warnings.push("`pgmg:refresh` takes one of none, refresh, concurrently".to_string()),
+            _ if OTHER_DIRECTIVES.iter().any(|other| name.starts_with(other)) => {}
+            _ => warnings.push(format!("Unknown directive `pgmg:{}`", name)),
+        }
+    }
+
+    (directives, warnings)
+}
+
+/// Whether an object with `tags` passes a `--tag` filter: it has any of the
+/// tags in `filter`, or the filter is empty
+pub fn matches_tags(tags: &[String], filter: &[String]) -> bool {
+    filter.is_empty() || filter.iter().any(|wanted| tags.iter().any(|tag| tag.eq_ignore_ascii_case(wanted)))
+}
+
+/// Split directive arguments on whitespace and commas, except inside
+/// parentheses, so `api.f(integer, text)` stays one argument
+fn split_arguments(rest: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if depth == 0 && (c.is_whitespace() || c == ',') => {
+                if start < i {
+                    args.push(&rest[start..i]);
+                }
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    if start < rest.len() {
+        args.push(&rest[start..]);
+    }
+
+    args
+}
+
+/// The statement handing an object to `role`, or `None` for object types
+/// whose ownership isn't changed this way
+pub fn owner_statement(object_type: &ObjectType, name: &QualifiedIdent, role: &str) -> Option<String> {
+    match object_type {
+        ObjectType::Table | ObjectType::View | ObjectType::MaterializedView |
+        ObjectType::Function | ObjectType::Procedure | ObjectType::Type |
+        ObjectType::Domain | ObjectType::Statistics | ObjectType::Collation |
+        ObjectType::TextSearchConfiguration | ObjectType::TextSearchDictionary => Some(format!(
+            "ALTER {} {} OWNER TO {}",
+            object_type,
+            quote_qualified_ident(name),
+            quote_ident(role)
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_object_directives() {
+        let sql = indoc! {"
+            -- Monthly report
+            -- pgmg:depends-on api.lookup_table, reporting.totals
+            -- pgmg:depends-on api.refresh_totals(integer, text) helpers()
+            -- pgmg:skip-env prod staging
+            -- pgmg:no-transaction
+
+            -- pgmg:no-drop
+            -- pgmg:owner report_owner
+            -- pgmg:refresh Concurrently
+            -- pgmg:tags Billing,critical
+            -- pgmg:tags reporting billing
+            CREATE VIEW api.report AS SELECT 1;
+            -- pgmg:skip-env dev
+        "};
+
+        let (directives, warnings) = parse_object_directives(sql);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(directives.depends_on, vec![
+            DependsOn::Relation(QualifiedIdent::from_qualified_name("api.lookup_table")),
+            DependsOn::Relation(QualifiedIdent::from_qualified_name("reporting.totals")),
+            DependsOn::Function(QualifiedIdent::from_qualified_name("api.refresh_totals")),
+            DependsOn::Function(QualifiedIdent::from_qualified_name("helpers")),
+        ]);
+
+        let dependencies = directives.dependencies();
+        assert!(dependencies.relations.contains(&QualifiedIdent::from_qualified_name("api.lookup_table")));
+        assert!(dependencies.types.contains(&QualifiedIdent::from_qualified_name("reporting.totals")));
+        assert!(dependencies.functions.contains(&QualifiedIdent::from_qualified_name("api.refresh_totals")));
+        assert_eq!(dependencies.functions.len(), 2);
+        assert_eq!(directives.skip_envs, vec!["prod", "staging"]);
+        assert!(directives.no_drop);
+        assert_eq!(directives.owner.as_deref(), Some("report_owner"));
+        assert_eq!(directives.refresh, Some(RefreshStrategy::Concurrently));
+        assert_eq!(directives.tags, vec!["billing", "critical", "reporting"]);
+
+        assert!(directives.skipped_in(Some("prod")));
+        assert!(!directives.skipped_in(Some("dev")));
+        assert!(!directives.skipped_in(None));
+    }
+
+    #[test]
+    fn test_parse_object_directives_warnings() {
+        let sql = indoc! {"
+            -- pgmg:no-drp
+            -- pgmg:owner
+            -- pgmg:no-drop now
+            -- pgmg:depends-on api.a.b api.f(
+            -- pgmg:ignore-check(42P01)
+            -- pgmg:refresh sometimes
+            -- pgmg:tags
+            CREATE VIEW api.report AS SELECT 1;
+        "};
+
+        let (directives, warnings) = parse_object_directives(sql);
+        assert!(directives.is_empty());
+        assert_eq!(warnings, vec![
+            "Unknown directive `pgmg:no-drp`",
+            "`pgmg:owner` takes exactly one role name",
+            "`pgmg:no-drop` takes no arguments",
+            "`pgmg:depends-on` can't read `api.a.b` as an object name",
+            "`pgmg:depends-on` can't read `api.f(` as an object name",
+            "`pgmg:refresh`: Invalid refresh strategy 'sometimes'. Expected one of: none, refresh, concurrently",
+            "`pgmg:tags` needs at least one name",
+        ]);
+    }
+
+    #[test]
+    fn test_matches_tags() {
+        let tags = vec!["billing".to_string(), "critical".to_string()];
+        assert!(matches_tags(&tags, &[]));
+        assert!(matches_tags(&tags, &["Billing".to_string()]));
+        assert!(matches_tags(&tags, &["search".to_string(), "critical".to_string()]));
+        assert!(!matches_tags(&tags, &["search".to_string()]));
+        assert!(!matches_tags(&[], &["billing".to_string()]));
+    }
+
+    #[test]
+    fn test_owner_statement() {
+        let name = QualifiedIdent::from_qualified_name("api.Report");
+        assert_eq!(
+            owner_statement(&ObjectType::MaterializedView, &name, "report_owner").as_deref(),
+            Some("ALTER MATERIALIZED VIEW api.\"Report\" OWNER TO report_owner")
+        );
+        assert_eq!(owner_statement(&ObjectType::Trigger, &name, "report_owner"), None);
+    }
+}
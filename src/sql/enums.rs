@@ -0,0 +1,174 @@
+//! Changes to enum types defined in code. An enum can't be dropped and
+//! recreated while columns use it, so a change that only adds values is made
+//! with `ALTER TYPE ... ADD VALUE` instead. PostgreSQL can't remove or reorder
+//! the values of an enum, so those changes are rejected.
+
+use crate::sql::QualifiedIdent;
+use crate::sql::quote::{quote_literal, quote_qualified_ident};
+
+/// The values of the enum created by a `CREATE TYPE ... AS ENUM` statement,
+/// in order, or None if the statement doesn't create an enum
+pub fn extract_enum_values(statement: &str) -> Option<Vec<String>> {
+    let parsed = pg_query::parse(statement).ok()?;
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .find_map(|node| match node {
+            pg_query::NodeEnum::CreateEnumStmt(enum_stmt) => Some(
+                enum_stmt.vals.iter()
+                    .filter_map(|val| match &val.node {
+                        Some(pg_query::NodeEnum::String(s)) => Some(s.sval.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            ),
+            _ => None,
+        })
+}
+
+/// Build the `ALTER TYPE ... ADD VALUE` statements that turn an enum with the
+/// `applied` values into one with the `current` values. Run in order, each
+/// statement puts its value after the one before it in `current`, so values
+/// can be added anywhere in the list.
+///
+/// Fails if a value was removed or existing values were reordered, explaining
+/// how to make the change instead.
+pub fn enum_value_additions(
+    name: &QualifiedIdent,
+    applied: &[String],
+    current: &[String],
+) -> Result<Vec<String>, String> {
+    let type_name = quote_qualified_ident(name);
+
+    let removed: Vec<String> = applied.iter()
+        .filter(|value| !current.contains(*value))
+        .map(|value| quote_literal(value))
+        .collect();
+    if !removed.is_empty() {
+        return Err(format!(
+            "Enum type {} no longer has {}. PostgreSQL can't remove enum values: \
+            rename a value with ALTER TYPE ... RENAME VALUE in a migration, or move the \
+            columns using the type to a new type there, and apply that migration before \
+            changing the type in code",
+            type_name,
+            removed.join(", ")
+        ));
+    }
+
+    let kept: Vec<&String> = current.iter().filter(|value| applied.contains(*value)).collect();
+    if !kept.iter().copied().eq(applied.iter()) {
+        return Err(format!(
+            "Enum type {} reorders its existing values. PostgreSQL can't reorder enum values: \
+            keep them in the order {} and add new values between them, or replace the type \
+            in a migration",
+            type_name,
+            applied.iter().map(|value| quote_literal(value)).collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    let mut statements = Vec::new();
+    for (i, value) in current.iter().enumerate() {
+        if applied.contains(value) {
+            continue;
+        }
+
+        let position = if current[i..].iter().all(|later| !applied.contains(later)) {
+            // Added at the end
+            String::new()
+        } else if i > 0 {
+            format!(" AFTER {}", quote_literal(&current[i - 1]))
+        } else {
+            format!(" BEFORE {}", quote_literal(&applied[0]))
+        };
+
+        statements.push(format!(
+            "ALTER TYPE {} ADD VALUE IF NOT EXISTS {}{}",
+            type_name,
+            quote_literal(value),
+            position
+        ));
+    }
+
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(list: &[&str]) -> Vec<String> {
+        list.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn status() -> QualifiedIdent {
+        QualifiedIdent::new(Some("api".to_string()), "status".to_string())
+    }
+
+    #[test]
+    fn test_extract_enum_values() {
+        assert_eq!(
+            extract_enum_values("CREATE TYPE api.status AS ENUM ('draft', 'it''s live')"),
+            Some(values(&["draft", "it's live"]))
+        );
+        assert_eq!(extract_enum_values("CREATE TYPE api.status AS ENUM ()"), Some(Vec::new()));
+        assert_eq!(extract_enum_values("CREATE TYPE api.pair AS (a int, b int)"), None);
+    }
+
+    #[test]
+    fn test_enum_value_additions_appended() {
+        let statements = enum_value_additions(
+            &status(),
+            &values(&["draft", "live"]),
+            &values(&["draft", "live", "archived", "deleted"]),
+        ).unwrap();
+
+        assert_eq!(statements, vec![
+            "ALTER TYPE api.status ADD VALUE IF NOT EXISTS 'archived'",
+            "ALTER TYPE api.status ADD VALUE IF NOT EXISTS 'deleted'",
+        ]);
+    }
+
+    #[test]
+    fn test_enum_value_additions_in_between() {
+        let statements = enum_value_additions(
+            &status(),
+            &values(&["draft", "live"]),
+            &values(&["new", "draft", "review", "live"]),
+        ).unwrap();
+
+        assert_eq!(statements, vec![
+            "ALTER TYPE api.status ADD VALUE IF NOT EXISTS 'new' BEFORE 'draft'",
+            "ALTER TYPE api.status ADD VALUE IF NOT EXISTS 'review' AFTER 'draft'",
+        ]);
+    }
+
+    #[test]
+    fn test_enum_value_additions_unchanged() {
+        let applied = values(&["draft", "live"]);
+        assert!(enum_value_additions(&status(), &applied, &applied).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_enum_value_removal_is_rejected() {
+        let error = enum_value_additions(
+            &status(),
+            &values(&["draft", "live", "archived"]),
+            &values(&["draft", "live"]),
+        ).unwrap_err();
+
+        assert!(error.contains("api.status no longer has 'archived'"), "{}", error);
+        assert!(error.contains("RENAME VALUE"), "{}", error);
+    }
+
+    #[test]
+    fn test_enum_value_reorder_is_rejected() {
+        let error = enum_value_additions(
+            &status(),
+            &values(&["draft", "live"]),
+            &values(&["live", "draft", "archived"]),
+        ).unwrap_err();
+
+        assert!(error.contains("reorders its existing values"), "{}", error);
+        assert!(error.contains("'draft', 'live'"), "{}", error);
+    }
+}
@@ -0,0 +1,635 @@
+//! Canonical formatting for `pgmg fmt`.
+//!
+//! Built on the pg_query scanner rather than the deparser, so comments,
+//! string literals and function bodies are kept exactly as written. Only
+//! whitespace and the case of unquoted words change: keywords are uppercased,
+//! identifiers and type names lowercased, query clauses start on their own
+//! line, table columns get a line each and every statement ends with a
+//! semicolon. The result must scan to the same tokens as the input, or the
+//! file is left alone.
+
+use std::collections::HashSet;
+use pg_query::protobuf::KeywordKind;
+use pg_query::{NodeEnum, NodeRef};
+use sha2::{Digest, Sha256};
+
+const INDENT: &str = "    ";
+
+/// Non-reserved keywords uppercased along with the reserved ones. Others
+/// (`name`, `value`, `data`...) are more often column names than syntax.
+const UPPERCASE_KEYWORDS: &[&str] = &[
+    "action", "add", "after", "aggregate", "alter", "always", "at", "attach", "before", "begin",
+    "between", "by", "called", "cascade", "coalesce", "comment", "commit", "concurrently",
+    "conflict", "constraints", "cost", "cross", "data", "definer", "delete", "deferred", "detach",
+    "disable", "domain", "drop", "each", "enable", "enum", "execute", "exists", "extension",
+    "filter", "following", "full", "function", "functions", "generated", "greatest", "identity",
+    "if", "ilike", "immutable", "include", "increment", "index", "inherits", "inner", "inout",
+    "insert", "instead", "invoker", "is", "isnull", "join", "key", "language", "leakproof",
+    "least", "left", "level", "like", "materialized", "maxvalue", "minvalue", "natural", "no",
+    "nothing", "notnull", "nullif", "nulls", "ordinality", "out", "outer", "over", "overlaps",
+    "owned", "owner", "parallel", "partition", "policy", "preceding", "procedure", "recursive",
+    "referencing", "refresh", "rename", "replace", "restart", "restrict", "return", "returns",
+    "revoke", "right", "row", "rows", "schema", "security", "sequence", "sequences", "set",
+    "setof", "similar", "stable", "start", "statement", "stored", "strict", "support", "tables",
+    "tablesample", "temp", "temporary", "time", "trigger", "truncate", "type", "unbounded",
+    "unlogged", "update", "usage", "valid", "validate", "values", "view", "volatile", "within",
+    "zone",
+];
+
+/// Keywords that start a new line in queries (SELECT, INSERT, UPDATE,
+/// DELETE and view definitions)
+const QUERY_CLAUSES: &[&str] = &[
+    "select", "from", "where", "group", "having", "window", "order", "limit", "offset",
+    "union", "intersect", "except", "returning", "values",
+];
+
+/// Keywords that can begin a join
+const JOIN_KEYWORDS: &[&str] = &["left", "right", "full", "inner", "cross", "natural", "join"];
+
+/// Keywords that continue a multi-word type name, as in `timestamp with time zone`
+const TYPE_NAME_WORDS: &[&str] = &["with", "without", "time", "zone", "varying", "precision"];
+
+/// How a statement is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    Query { update: bool },
+    Function,
+    Other,
+}
+
+struct Token<'a> {
+    text: &'a str,
+    start: usize,
+    end: usize,
+    keyword: bool,
+    reserved: bool,
+}
+
+impl Token<'_> {
+    fn is_comment(&self) -> bool {
+        self.text.starts_with("--") || self.text.starts_with("/*")
+    }
+}
+
+/// What the parse tree says about positions in the file
+#[derive(Default)]
+struct Positions {
+    /// Statement start offsets and layouts, in order
+    statements: Vec<(usize, Layout)>,
+    /// Where identifiers start: column, table, function and type names
+    identifiers: HashSet<usize>,
+    /// Where type names start
+    types: HashSet<usize>,
+    /// Where table names start, so `users (id)` keeps its space
+    relations: HashSet<usize>,
+    /// Names followed by a parenthesized list laid out one item per line
+    /// (table columns and composite type attributes)
+    multiline_lists: HashSet<usize>,
+}
+
+impl Positions {
+    fn from_sql(sql: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let parsed = pg_query::parse(sql)?;
+        let mut positions = Positions::default();
+        let offset = |location: i32| usize::try_from(location).ok();
+
+        for raw in &parsed.protobuf.stmts {
+            let layout = match raw.stmt.as_ref().and_then(|stmt| stmt.node.as_ref()) {
+                Some(NodeEnum::UpdateStmt(_)) => Layout::Query { update: true },
+                Some(NodeEnum::SelectStmt(_) | NodeEnum::InsertStmt(_) | NodeEnum::DeleteStmt(_) |
+                     NodeEnum::ViewStmt(_) | NodeEnum::CreateTableAsStmt(_)) => Layout::Query { update: false },
+                Some(NodeEnum::CreateFunctionStmt(_)) => Layout::Function,
+                _ => Layout::Other,
+            };
+            positions.statements.push((offset(raw.stmt_location).unwrap_or(0), layout));
+        }
+
+        // Target columns, which unlike SELECT targets start with a name
+        fn targets(nodes: &[pg_query::protobuf::Node]) -> impl Iterator<Item = usize> + '_ {
+            nodes.iter().filter_map(|node| match &node.node {
+                Some(NodeEnum::ResTarget(target)) => usize::try_from(target.location).ok(),
+                _ => None,
+            })
+        }
+
+        for (node, _, _, _) in parsed.protobuf.nodes() {
+            match node {
+                NodeRef::ColumnRef(column) => positions.identifiers.extend(offset(column.location)),
+                NodeRef::ColumnDef(column) => positions.identifiers.extend(offset(column.location)),
+                NodeRef::FuncCall(call) => positions.identifiers.extend(offset(call.location)),
+                NodeRef::RangeVar(relation) => {
+                    positions.identifiers.extend(offset(relation.location));
+                    positions.relations.extend(offset(relation.location));
+                }
+                NodeRef::TypeName(type_name) => {
+                    positions.identifiers.extend(offset(type_name.location));
+                    positions.types.extend(offset(type_name.location));
+                }
+                NodeRef::InsertStmt(insert) => positions.identifiers.extend(targets(&insert.cols)),
+                NodeRef::UpdateStmt(update) => positions.identifiers.extend(targets(&update.target_list)),
+                NodeRef::OnConflictClause(on_conflict) => positions.identifiers.extend(targets(&on_conflict.target_list)),
+                NodeRef::CreateStmt(create) => {
+                    positions.multiline_lists.extend(create.relation.as_ref().and_then(|relation| offset(relation.location)));
+                }
+                NodeRef::CompositeTypeStmt(composite) => {
+                    positions.multiline_lists.extend(composite.typevar.as_ref().and_then(|typevar| offset(typevar.location)));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(positions)
+    }
+
+    fn layout_at(&self, offset: usize) -> Layout {
+        self.statements.iter()
+            .take_while(|(start, _)| *start <= offset)
+            .last()
+            .map(|(_, layout)| *layout)
+            .unwrap_or(Layout::Other)
+    }
+}
+
+/// Format a SQL file in the canonical style
+pub fn format_sql(sql: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let tokens = scan(sql)?;
+    if tokens.is_empty() {
+        return Ok(String::new());
+    }
+    let positions = Positions::from_sql(sql)?;
+
+    let formatted = Formatter::new(sql, &positions).format(&tokens);
+
+    if token_texts(sql, true)? != token_texts(&formatted, false)? {
+        return Err("formatting would change the statements; the file was left as is".into());
+    }
+    Ok(formatted)
+}
+
+fn scan(sql: &str) -> Result<Vec<Token<'_>>, Box<dyn std::error::Error>> {
+    let scanned = pg_query::scan(sql)?;
+    Ok(scanned.tokens.iter()
+        .filter_map(|token| {
+            let start = usize::try_from(token.start).ok()?;
+            let end = usize::try_from(token.end).ok()?;
+            let keyword_kind = token.keyword_kind();
+            Some(Token {
+                text: sql.get(start..end)?,
+                start,
+                end,
+                keyword: keyword_kind != KeywordKind::NoKeyword,
+                reserved: keyword_kind == KeywordKind::ReservedKeyword,
+            })
+        })
+        .collect())
+}
+
+/// The tokens of a file, with unquoted words lowercased. With `terminate`, a
+/// semicolon is added after the last statement if it has none, as formatting does.
+fn token_texts(sql: &str, terminate: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let tokens = scan(sql)?;
+    let mut texts: Vec<String> = tokens.iter()
+        .map(|token| if is_word(token.text) { token.text.to_lowercase() } else { token.text.to_string() })
+        .collect();
+
+    if terminate {
+        if let Some(last) = tokens.iter().rposition(|token| !token.is_comment()) {
+            if tokens[last].text != ";" {
+                texts.insert(last + 1, ";".to_string());
+            }
+        }
+    }
+    Ok(texts)
+}
+
+/// A hash of a statement's tokens without comments and whitespace, so two
+/// versions differing only in formatting fingerprint the same. Function
+/// bodies and other dollar-quoted strings are fingerprinted the same way if
+/// they scan, and taken as written if not.
+///
+/// pg_query's own fingerprint isn't used as it ignores constants, which
+/// would make `SELECT 1` and `SELECT 2` look the same.
+pub fn formatting_fingerprint(sql: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut hasher = Sha256::new();
+    for text in significant_tokens(sql)? {
+        hasher.update(text.as_bytes());
+        hasher.update(b" ");
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The tokens other than comments, with unquoted words lowercased and
+/// dollar-quoted strings split into their own tokens where possible
+fn significant_tokens(sql: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut texts = Vec::new();
+    for token in scan(sql)? {
+        if token.is_comment() {
+            continue;
+        }
+        match split_dollar_quoted(token.text) {
+            Some((tag, body)) => match significant_tokens(body) {
+                Ok(body_tokens) => {
+                    texts.push(tag.to_string());
+                    texts.extend(body_tokens);
+                    texts.push(tag.to_string());
+                }
+                Err(_) => texts.push(token.text.to_string()),
+            },
+            None if is_word(token.text) => texts.push(token.text.to_lowercase()),
+            None => texts.push(token.text.to_string()),
+        }
+    }
+    Ok(texts)
+}
+
+/// The tag and contents of a dollar-quoted string such as `$body$ ... $body$`
+fn split_dollar_quoted(text: &str) -> Option<(&str, &str)> {
+    let tag_len = text.strip_prefix('$')?.find('$')? + 2;
+    let tag = &text[..tag_len];
+    if text.len() < 2 * tag_len || !text.ends_with(tag) {
+        return None;
+    }
+    Some((tag, &text[tag_len..text.len() - tag_len]))
+}
+
+/// An unquoted identifier or keyword
+fn is_word(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+fn is_operator(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| "+-*/<>=~!@#%^&|`?".contains(c))
+}
+
+/// The last token written, as far as spacing and layout need to know
+struct Previous {
+    text: String,
+    /// An uppercased keyword
+    keyword: bool,
+    /// No space should follow, e.g. after `(` or a unary minus
+    glue: bool,
+}
+
+struct Formatter<'a> {
+    sql: &'a str,
+    positions: &'a Positions,
+    out: String,
+    previous: Option<Previous>,
+    /// Paren depth within the current statement
+    depth: usize,
+    /// Depths of the open one-item-per-line lists
+    lists: Vec<usize>,
+    /// The name of a table or composite type being created was just written,
+    /// so the next `(` opens its one-item-per-line list
+    list_pending: bool,
+    layout: Layout,
+    /// Where the dotted name being written started
+    name_start: Option<usize>,
+    in_type: bool,
+    /// Length of `out` after the last non-comment token
+    last_token_end: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(sql: &'a str, positions: &'a Positions) -> Self {
+        Self {
+            sql,
+            positions,
+            out: String::with_capacity(sql.len()),
+            previous: None,
+            depth: 0,
+            lists: Vec::new(),
+            list_pending: false,
+            layout: Layout::Other,
+            name_start: None,
+            in_type: false,
+            last_token_end: 0,
+        }
+    }
+
+    fn format(mut self, tokens: &[Token]) -> String {
+        let mut previous_end = 0;
+
+        for (idx, token) in tokens.iter().enumerate() {
+            let gap = &self.sql[previous_end.min(token.start)..token.start];
+            let newlines = gap.matches('\n').count();
+            previous_end = token.end;
+
+            if token.is_comment() {
+                self.write_comment(token, newlines);
+                continue;
+            }
+
+            let statement_start = match &self.previous {
+                Some(previous) => previous.text == ";",
+                None => true,
+            };
+            if statement_start {
+                self.start_statement(token, newlines);
+            }
+
+            let next = tokens[idx + 1..].iter().find(|next| !next.is_comment());
+            self.write_token(token, next, statement_start);
+        }
+
+        // Every statement ends with a semicolon
+        if self.previous.as_ref().is_some_and(|previous| previous.text != ";") {
+            self.out.insert(self.last_token_end, ';');
+        }
+
+        // Lines are trimmed as they end, not here, so string literals are untouched
+        let mut formatted = self.out.trim_end().to_string();
+        formatted.push('\n');
+        formatted
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn newline(&mut self) {
+        if self.at_line_start() {
+            return;
+        }
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+    }
+
+    fn indent(&mut self) {
+        self.out.push_str(&INDENT.repeat(self.lists.len()));
+    }
+
+    fn write_comment(&mut self, token: &Token, newlines: usize) {
+        if newlines > 0 || self.out.is_empty() {
+            self.newline();
+            if newlines > 1 && !self.out.is_empty() && !self.out.ends_with("\n\n") {
+                self.out.push('\n');
+            }
+            self.indent();
+        } else if !self.at_line_start() {
+            self.out.push(' ');
+        }
+        self.out.push_str(token.text);
+
+        if token.text.starts_with("--") {
+            self.out.push('\n');
+        } else if let Some(previous) = &mut self.previous {
+            previous.glue = false;
+        }
+    }
+
+    fn start_statement(&mut self, token: &Token, newlines: usize) {
+        self.depth = 0;
+        self.lists.clear();
+        self.layout = self.positions.layout_at(token.start);
+
+        self.newline();
+        // Keep one blank line where there was at least one
+        if newlines > 1 && !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn write_token(&mut self, token: &Token, next: Option<&Token>, statement_start: bool) {
+        let lower = token.text.to_lowercase();
+        let word = is_word(token.text);
+        let previous_text = self.previous.as_ref().map(|previous| previous.text.as_str()).unwrap_or("");
+
+        // Words are uppercased as keywords or lowercased as names
+        let (text, keyword) = if word {
+            let continues_type = self.in_type && TYPE_NAME_WORDS.contains(&lower.as_str());
+            let name = self.positions.identifiers.contains(&token.start)
+                || previous_text == "."
+                || next.is_some_and(|next| next.text == ".")
+                || continues_type;
+            self.in_type = self.positions.types.contains(&token.start) || continues_type;
+            if previous_text != "." {
+                self.name_start = Some(token.start);
+            }
+            // CREATE TABLE name (...), CREATE TYPE name AS (...)
+            self.list_pending = self.name_start.is_some_and(|start| self.positions.multiline_lists.contains(&start))
+                || (self.list_pending && lower == "as");
+
+            if token.keyword && !name && (token.reserved || UPPERCASE_KEYWORDS.contains(&lower.as_str())) {
+                (token.text.to_uppercase(), true)
+            } else {
+                (lower.clone(), false)
+            }
+        } else {
+            self.in_type = false;
+            (token.text.to_string(), false)
+        };
+
+        // Clauses start new lines
+        if !statement_start && self.depth == 0 && keyword && self.starts_clause(&lower, next) {
+            self.newline();
+        }
+
+        // Closing a one-item-per-line list
+        if token.text == ")" && self.lists.last() == Some(&self.depth) {
+            self.lists.pop();
+            self.newline();
+        }
+
+        if self.at_line_start() {
+            self.indent();
+        } else if self.needs_space(&text) {
+            self.out.push(' ');
+        }
+        self.out.push_str(&text);
+        self.last_token_end = self.out.len();
+
+        let unary = matches!(token.text, "-" | "+") && match &self.previous {
+            Some(previous) => previous.keyword || is_operator(&previous.text) || matches!(previous.text.as_str(), "(" | "[" | ","),
+            None => true,
+        };
+        self.previous = Some(Previous {
+            text,
+            keyword,
+            glue: matches!(token.text, "(" | "[" | "." | "::") || unary,
+        });
+
+        let list_pending = !word && std::mem::take(&mut self.list_pending);
+        match token.text {
+            "(" | "[" => {
+                self.depth += 1;
+                if token.text == "(" && list_pending {
+                    self.lists.push(self.depth);
+                    self.newline();
+                }
+            }
+            "." => self.list_pending = list_pending,
+            ")" | "]" => self.depth = self.depth.saturating_sub(1),
+            "," if self.lists.last() == Some(&self.depth) => self.newline(),
+            _ => {}
+        }
+    }
+
+    fn starts_clause(&self, lower: &str, next: Option<&Token>) -> bool {
+        let previous = self.previous.as_ref().map(|previous| previous.text.to_lowercase()).unwrap_or_default();
+        match self.layout {
+            Layout::Query { update } => {
+                (QUERY_CLAUSES.contains(&lower)
+                    // DELETE FROM, IS DISTINCT FROM
+                    && !(lower == "from" && matches!(previous.as_str(), "delete" | "distinct")))
+                    || (JOIN_KEYWORDS.contains(&lower) && !JOIN_KEYWORDS.contains(&previous.as_str()) && previous != "outer")
+                    || (update && lower == "set")
+                    || (lower == "on" && next.is_some_and(|next| next.text.eq_ignore_ascii_case("conflict")))
+            }
+            Layout::Function => {
+                matches!(lower, "returns" | "language")
+                    || (lower == "as" && next.is_some_and(|next| next.text.starts_with(['\'', '$'])))
+            }
+            Layout::Other => false,
+        }
+    }
+
+    fn needs_space(&self, text: &str) -> bool {
+        let Some(previous) = &self.previous else { return false };
+        // Operators are never glued together, so `- -1` can't become a comment
+        if is_operator(&previous.text) && is_operator(text) {
+            return true;
+        }
+        if previous.glue || matches!(text, ")" | "]" | "," | ";" | "." | "::" | "[") {
+            return false;
+        }
+        if text == "(" {
+            // Function calls and type modifiers are written f(x); tables keep a space
+            let after_name = is_word(&previous.text) || previous.text.starts_with('"');
+            let after_table = self.name_start.is_some_and(|start| self.positions.relations.contains(&start));
+            return !after_name || previous.keyword || after_table;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        let sql = "create table public.users (id integer primary key, email TEXT not null, created_at timestamp with time zone default now(), constraint email_lower check (email = lower(email)))";
+        assert_eq!(format_sql(sql).unwrap(), indoc::indoc! {r#"
+            CREATE TABLE public.users (
+                id integer PRIMARY KEY,
+                email text NOT NULL,
+                created_at timestamp with time zone DEFAULT now(),
+                CONSTRAINT email_lower CHECK (email = lower(email))
+            );
+        "#});
+    }
+
+    #[test]
+    fn test_format_view_keeps_comments() {
+        let sql = indoc::indoc! {r#"
+            -- Active users with their order count
+            create or replace view api.active_users as select u.id, u.name, count(o.id) as orders
+            from users u left join orders o on o.user_id = u.id -- includes users without orders
+            where u.active and o.status is distinct from 'cancelled' group by u.id, u.name
+
+
+            ;
+            comment on view api.active_users is 'Users  who  logged in recently'
+        "#};
+        assert_eq!(format_sql(sql).unwrap(), indoc::indoc! {r#"
+            -- Active users with their order count
+            CREATE OR REPLACE VIEW api.active_users AS
+            SELECT u.id, u.name, count(o.id) AS orders
+            FROM users u
+            LEFT JOIN orders o ON o.user_id = u.id -- includes users without orders
+            WHERE u.active AND o.status IS DISTINCT FROM 'cancelled'
+            GROUP BY u.id, u.name;
+            COMMENT ON VIEW api.active_users IS 'Users  who  logged in recently';
+        "#});
+    }
+
+    #[test]
+    fn test_format_function_keeps_body() {
+        let sql = indoc::indoc! {r#"
+            CREATE FUNCTION api.total(order_id bigint, discount numeric DEFAULT -1) RETURNS numeric
+            AS $$
+            BEGIN
+              -- body is kept as written
+              RETURN (SELECT sum(price)  FROM order_items WHERE order_id = $1);
+            END;
+            $$ language plpgsql stable;
+
+            GRANT EXECUTE ON FUNCTION api.total(bigint, numeric) TO web_anon;
+        "#};
+        assert_eq!(format_sql(sql).unwrap(), indoc::indoc! {r#"
+            CREATE FUNCTION api.total(order_id bigint, discount numeric DEFAULT -1)
+            RETURNS numeric
+            AS $$
+            BEGIN
+              -- body is kept as written
+              RETURN (SELECT sum(price)  FROM order_items WHERE order_id = $1);
+            END;
+            $$
+            LANGUAGE plpgsql STABLE;
+
+            GRANT EXECUTE ON FUNCTION api.total(bigint, numeric) TO web_anon;
+        "#});
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let sql = indoc::indoc! {r#"
+            insert into settings (name, value) values ('theme', 'dark') on conflict (name) do update set value = excluded.value returning *;
+            update settings set value = 'light' where name = 'theme';
+            create type api.address as (street text, city text);
+        "#};
+        let formatted = format_sql(sql).unwrap();
+        assert_eq!(format_sql(&formatted).unwrap(), formatted);
+        assert!(formatted.contains("INSERT INTO settings (name, value)\nVALUES ('theme', 'dark')\nON CONFLICT (name) DO UPDATE SET value = excluded.value\nRETURNING *;"));
+        assert!(formatted.contains("UPDATE settings\nSET value = 'light'\nWHERE name = 'theme';"));
+        assert!(formatted.contains("CREATE TYPE api.address AS (\n    street text,\n    city text\n);"));
+    }
+
+    #[test]
+    fn test_format_rejects_invalid_sql() {
+        assert!(format_sql("CREATE VIEW broken AS SELEC 1;").is_err());
+        assert_eq!(format_sql("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_formatting_fingerprint() {
+        let original = indoc::indoc! {"
+            CREATE FUNCTION api.total(p_id int) RETURNS numeric LANGUAGE plpgsql AS $$
+            BEGIN
+                RETURN (SELECT sum(amount) FROM orders WHERE id = p_id);
+            END;
+            $$;
+        "};
+        let reformatted = indoc::indoc! {"
+            -- Order total
+            create function api.total(p_id int)
+            returns numeric language plpgsql as $$
+            begin
+                -- one query is enough
+                return (select sum(amount)
+                        from orders
+                        where id = p_id);
+            end;
+            $$;
+        "};
+        let changed = original.replace("sum(amount)", "sum(amount * 2)");
+        let changed_literal = "CREATE VIEW v AS SELECT 'a  b' AS x";
+
+        let fingerprint = |sql: &str| formatting_fingerprint(sql).unwrap();
+        assert_eq!(fingerprint(original), fingerprint(reformatted));
+        assert_ne!(fingerprint(original), fingerprint(&changed));
+        assert_ne!(fingerprint("CREATE VIEW v AS SELECT 'a b' AS x"), fingerprint(changed_literal));
+        assert_ne!(fingerprint("CREATE VIEW v AS SELECT 1"), fingerprint("CREATE VIEW v AS SELECT 2"));
+    }
+
+    #[test]
+    fn test_split_dollar_quoted() {
+        assert_eq!(split_dollar_quoted("$$ SELECT 1 $$"), Some(("$$", " SELECT 1 ")));
+        assert_eq!(split_dollar_quoted("$body$x$body$"), Some(("$body$", "x")));
+        assert_eq!(split_dollar_quoted("$1"), None);
+        assert_eq!(split_dollar_quoted("'text'"), None);
+    }
+}
@@ -0,0 +1,550 @@
+//! Static checks on SQL files for `pgmg lint`, run on the parsed AST without
+//! a database: schema qualification, `SELECT *` in views, function
+//! volatility, naming conventions, forbidden types and idempotent migrations.
+//!
+//! Rules are on by default and configured under `[lint]` in pgmg.toml.
+
+use std::fmt;
+use regex::Regex;
+use crate::sql::ObjectType;
+use crate::sql::objects::identify_sql_object;
+use crate::sql::splitter::{split_sql_file, SqlStatement};
+use pg_query::protobuf::{AlterTableType, ObjectType as PgObjectType, SelectStmt, TypeName};
+use pg_query::NodeEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// Tables, views, functions and types are created with a schema
+    SchemaQualified,
+    /// Views and materialized views list their columns instead of `SELECT *`,
+    /// which is expanded once at creation and silently goes stale
+    NoSelectStar,
+    /// Functions say whether they are IMMUTABLE, STABLE or VOLATILE
+    FunctionVolatility,
+    /// Object names match the `[lint.naming]` pattern for their type
+    Naming,
+    /// Columns, arguments and return values don't use a `forbidden_types` type
+    ForbiddenType,
+    /// Migrations use IF EXISTS / IF NOT EXISTS, so re-running one after a
+    /// partial failure doesn't stop at the first object it already created
+    IdempotentMigration,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 6] = [
+        LintRule::SchemaQualified,
+        LintRule::NoSelectStar,
+        LintRule::FunctionVolatility,
+        LintRule::Naming,
+        LintRule::ForbiddenType,
+        LintRule::IdempotentMigration,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::SchemaQualified => "schema-qualified",
+            LintRule::NoSelectStar => "no-select-star",
+            LintRule::FunctionVolatility => "function-volatility",
+            LintRule::Naming => "naming",
+            LintRule::ForbiddenType => "forbidden-type",
+            LintRule::IdempotentMigration => "idempotent-migration",
+        }
+    }
+}
+
+impl std::str::FromStr for LintRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_lowercase().replace('_', "-");
+        LintRule::ALL.into_iter()
+            .find(|rule| rule.name() == normalized)
+            .ok_or_else(|| format!(
+                "Unknown lint rule '{}'. Expected one of: {}",
+                s,
+                LintRule::ALL.map(|rule| rule.name()).join(", ")
+            ))
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The configured rules, from `[lint]` in pgmg.toml
+#[derive(Debug, Clone)]
+pub struct LintRules {
+    pub disabled: Vec<LintRule>,
+    /// Pattern names must match, per object type
+    pub naming: Vec<(ObjectType, Regex)>,
+    /// Types that may not be used, as PostgreSQL names them internally
+    /// (`timestamp`, `int4`) paired with how they were configured
+    pub forbidden_types: Vec<(String, String)>,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        Self {
+            disabled: Vec::new(),
+            naming: Vec::new(),
+            forbidden_types: vec![(normalize_type_name("timestamp"), "timestamp".to_string())],
+        }
+    }
+}
+
+impl LintRules {
+    pub fn enabled(&self, rule: LintRule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+/// Text to insert into a file to fix a finding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFix {
+    /// Byte offset in the file
+    pub offset: usize,
+    pub insert: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    /// Line in the file
+    pub line: Option<usize>,
+    pub message: String,
+    /// Set when the finding can be fixed by `pgmg lint --fix`
+    pub fix: Option<LintFix>,
+}
+
+/// Object types that can and should be created with a schema. Index and
+/// trigger names take the schema of their table.
+const SCHEMA_QUALIFIED_TYPES: &[ObjectType] = &[
+    ObjectType::Table,
+    ObjectType::View,
+    ObjectType::MaterializedView,
+    ObjectType::Function,
+    ObjectType::Procedure,
+    ObjectType::Type,
+    ObjectType::Domain,
+    ObjectType::Aggregate,
+];
+
+/// Lint a file from the code directory
+pub fn lint_code_file(content: &str, rules: &LintRules) -> Result<Vec<LintFinding>, Box<dyn std::error::Error>> {
+    lint_file(content, rules, false)
+}
+
+/// Lint a migration file. Migrations are also checked for idempotency.
+pub fn lint_migration_file(content: &str, rules: &LintRules) -> Result<Vec<LintFinding>, Box<dyn std::error::Error>> {
+    lint_file(content, rules, true)
+}
+
+fn lint_file(content: &str, rules: &LintRules, migration: bool) -> Result<Vec<LintFinding>, Box<dyn std::error::Error>> {
+    let mut findings = Vec::new();
+
+    for statement in split_sql_file(content)? {
+        let parsed = pg_query::parse(&statement.sql)?;
+        let Some(node) = parsed.protobuf.stmts.first()
+            .and_then(|stmt| stmt.stmt.as_ref())
+            .and_then(|stmt| stmt.node.as_ref()) else {
+            continue;
+        };
+
+        let mut lint = StatementLint { statement: &statement, rules, findings: &mut findings };
+        lint.check_object();
+        lint.check_select_star(node);
+        lint.check_volatility(node);
+        lint.check_types(node);
+        if migration {
+            lint.check_idempotency(node);
+        }
+    }
+
+    findings.sort_by_key(|finding| finding.line);
+    Ok(findings)
+}
+
+/// Apply the fixes of the given findings to a file's content
+pub fn apply_fixes(content: &str, findings: &[LintFinding]) -> String {
+    let mut fixes: Vec<&LintFix> = findings.iter().filter_map(|finding| finding.fix.as_ref()).collect();
+    // From the end, so earlier offsets stay valid
+    fixes.sort_by(|a, b| b.offset.cmp(&a.offset));
+    fixes.dedup();
+
+    let mut fixed = content.to_string();
+    for fix in fixes {
+        fixed.insert_str(fix.offset, &fix.insert);
+    }
+    fixed
+}
+
+struct StatementLint<'a> {
+    statement: &'a SqlStatement,
+    rules: &'a LintRules,
+    findings: &'a mut Vec<LintFinding>,
+}
+
+impl StatementLint<'_> {
+    fn report(&mut self, rule: LintRule, location: Option<i32>, message: String, fix: Option<LintFix>) {
+        if !self.rules.enabled(rule) {
+            return;
+        }
+        let line = self.statement.start_line.map(|start| {
+            let offset = location.and_then(|location| usize::try_from(location).ok()).unwrap_or(0);
+            start + self.statement.sql.get(..offset).unwrap_or("").matches('\n').count()
+        });
+        self.findings.push(LintFinding { rule, line, message, fix });
+    }
+
+    /// Byte offset in the file of an offset in the statement
+    fn file_offset(&self, offset: usize) -> Option<usize> {
+        self.statement.start_location.map(|start| start + offset)
+    }
+
+    fn check_object(&mut self) {
+        let Ok(Some(object)) = identify_sql_object(&self.statement.sql) else {
+            return;
+        };
+        let name = &object.qualified_name;
+
+        if SCHEMA_QUALIFIED_TYPES.contains(&object.object_type) && name.schema.is_none() {
+            self.report(
+                LintRule::SchemaQualified,
+                None,
+                format!("{} {} is not schema-qualified", object.object_type, name.name),
+                None,
+            );
+        }
+
+        let rules = self.rules;
+        for (object_type, pattern) in &rules.naming {
+            if *object_type == object.object_type && !pattern.is_match(&name.name) {
+                let message = format!(
+                    "{} name {} doesn't match the naming pattern {}",
+                    object.object_type, name.name, pattern.as_str()
+                );
+                self.report(LintRule::Naming, None, message, None);
+            }
+        }
+    }
+
+    fn check_select_star(&mut self, node: &NodeEnum) {
+        let (kind, query) = match node {
+            NodeEnum::ViewStmt(view) => ("View", view.query.as_deref()),
+            NodeEnum::CreateTableAsStmt(create) if create.objtype() == PgObjectType::ObjectMatview => {
+                ("Materialized view", create.query.as_deref())
+            }
+            _ => return,
+        };
+        let select = match query.and_then(|query| query.node.as_ref()) {
+            Some(NodeEnum::SelectStmt(select)) => select,
+            _ => return,
+        };
+        if let Some(location) = select_star_location(select) {
+            self.report(
+                LintRule::NoSelectStar,
+                Some(location),
+                format!("{} selects * - list the columns instead", kind),
+                None,
+            );
+        }
+    }
+
+    fn check_volatility(&mut self, node: &NodeEnum) {
+        let NodeEnum::CreateFunctionStmt(function) = node else { return };
+        if function.is_procedure {
+            return;
+        }
+        let declared = function.options.iter().any(|option| {
+            matches!(&option.node, Some(NodeEnum::DefElem(elem)) if elem.defname == "volatility")
+        });
+        if declared {
+            return;
+        }
+
+        let name = function.funcname.iter()
+            .filter_map(|part| match &part.node {
+                Some(NodeEnum::String(s)) => Some(s.sval.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        // VOLATILE is the default, so saying so doesn't change the function
+        let fix = self.file_offset(self.statement.sql.len())
+            .map(|offset| LintFix { offset, insert: " VOLATILE".to_string() });
+        self.report(
+            LintRule::FunctionVolatility,
+            None,
+            format!("Function {} doesn't declare IMMUTABLE, STABLE or VOLATILE", name),
+            fix,
+        );
+    }
+
+    fn check_types(&mut self, node: &NodeEnum) {
+        fn column_types(nodes: &[pg_query::protobuf::Node]) -> impl Iterator<Item = &TypeName> {
+            nodes.iter().filter_map(|node| match &node.node {
+                Some(NodeEnum::ColumnDef(column)) => column.type_name.as_ref(),
+                _ => None,
+            })
+        }
+
+        let mut type_names: Vec<&TypeName> = Vec::new();
+
+        match node {
+            NodeEnum::CreateStmt(create) => type_names.extend(column_types(&create.table_elts)),
+            NodeEnum::CompositeTypeStmt(composite) => type_names.extend(column_types(&composite.coldeflist)),
+            NodeEnum::CreateDomainStmt(domain) => type_names.extend(domain.type_name.as_ref()),
+            NodeEnum::AlterTableStmt(alter) => {
+                for cmd in &alter.cmds {
+                    let Some(NodeEnum::AlterTableCmd(cmd)) = &cmd.node else { continue };
+                    // ADD COLUMN and ALTER COLUMN ... TYPE
+                    if let Some(NodeEnum::ColumnDef(column)) = cmd.def.as_ref().and_then(|def| def.node.as_ref()) {
+                        type_names.extend(column.type_name.as_ref());
+                    }
+                }
+            }
+            NodeEnum::CreateFunctionStmt(function) => {
+                for parameter in &function.parameters {
+                    if let Some(NodeEnum::FunctionParameter(parameter)) = &parameter.node {
+                        type_names.extend(parameter.arg_type.as_ref());
+                    }
+                }
+                type_names.extend(function.return_type.as_ref());
+            }
+            _ => {}
+        }
+
+        for type_name in type_names {
+            let Some(name) = type_name.names.last().and_then(|name| match &name.node {
+                Some(NodeEnum::String(s)) => Some(s.sval.to_lowercase()),
+                _ => None,
+            }) else {
+                continue;
+            };
+            if let Some((_, configured)) = self.rules.forbidden_types.iter().find(|(forbidden, _)| *forbidden == name) {
+                let message = format!("Uses the forbidden type {}", configured);
+                self.report(LintRule::ForbiddenType, Some(type_name.location), message, None);
+            }
+        }
+    }
+
+    fn check_idempotency(&mut self, node: &NodeEnum) {
+        match node {
+            NodeEnum::CreateStmt(create) if !create.if_not_exists => {
+                self.report_missing_guard("CREATE TABLE", "IF NOT EXISTS");
+            }
+            // Only named indexes can be created IF NOT EXISTS
+            NodeEnum::IndexStmt(index) if !index.if_not_exists && !index.idxname.is_empty() => {
+                self.report_missing_guard("CREATE INDEX", "IF NOT EXISTS");
+            }
+            NodeEnum::CreateSchemaStmt(schema) if !schema.if_not_exists => {
+                self.report_missing_guard("CREATE SCHEMA", "IF NOT EXISTS");
+            }
+            NodeEnum::CreateSeqStmt(sequence) if !sequence.if_not_exists => {
+                self.report_missing_guard("CREATE SEQUENCE", "IF NOT EXISTS");
+            }
+            NodeEnum::CreateExtensionStmt(extension) if !extension.if_not_exists => {
+                self.report_missing_guard("CREATE EXTENSION", "IF NOT EXISTS");
+            }
+            NodeEnum::DropStmt(drop) if !drop.missing_ok => {
+                self.report_missing_guard("DROP", "IF EXISTS");
+            }
+            NodeEnum::AlterTableStmt(alter) => {
+                for cmd in &alter.cmds {
+                    let Some(NodeEnum::AlterTableCmd(cmd)) = &cmd.node else { continue };
+                    let missing = match cmd.subtype() {
+                        AlterTableType::AtAddColumn if !cmd.missing_ok => Some(("ADD COLUMN", "IF NOT EXISTS")),
+                        AlterTableType::AtDropColumn if !cmd.missing_ok => Some(("DROP COLUMN", "IF EXISTS")),
+                        AlterTableType::AtDropConstraint if !cmd.missing_ok => Some(("DROP CONSTRAINT", "IF EXISTS")),
+                        _ => None,
+                    };
+                    if let Some((operation, guard)) = missing {
+                        self.report(
+                            LintRule::IdempotentMigration,
+                            None,
+                            format!("ALTER TABLE ... {} without {}", operation, guard),
+                            None,
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Report a CREATE or DROP without its guard, fixable when the statement
+    /// starts with the keywords the guard follows
+    fn report_missing_guard(&mut self, statement: &str, guard: &str) {
+        let prefix = Regex::new(if guard == "IF EXISTS" { DROP_PREFIX } else { CREATE_PREFIX }).unwrap();
+        let fix = prefix.find(&self.statement.sql)
+            .and_then(|keywords| self.file_offset(keywords.end()))
+            .map(|offset| LintFix { offset, insert: format!("{} ", guard) });
+        self.report(
+            LintRule::IdempotentMigration,
+            None,
+            format!("{} without {}", statement, guard),
+            fix,
+        );
+    }
+}
+
+/// The keywords a CREATE statement's IF NOT EXISTS follows
+const CREATE_PREFIX: &str = r"(?i)^CREATE\s+(?:(?:(?:GLOBAL|LOCAL)\s+)?(?:TEMP|TEMPORARY|UNLOGGED)\s+)?(?:TABLE|(?:UNIQUE\s+)?INDEX(?:\s+CONCURRENTLY)?|SCHEMA|SEQUENCE|EXTENSION)\s+";
+
+/// The keywords a DROP statement's IF EXISTS follows
+const DROP_PREFIX: &str = r"(?i)^DROP\s+(?:MATERIALIZED\s+VIEW|FOREIGN\s+TABLE|EVENT\s+TRIGGER|TEXT\s+SEARCH\s+\w+|INDEX(?:\s+CONCURRENTLY)?|\w+)\s+";
+
+/// Where a query selects `*` (or `t.*`), looking through UNION and friends
+fn select_star_location(select: &SelectStmt) -> Option<i32> {
+    for target in &select.target_list {
+        let Some(NodeEnum::ResTarget(target)) = &target.node else { continue };
+        if let Some(NodeEnum::ColumnRef(column)) = target.val.as_ref().and_then(|val| val.node.as_ref()) {
+            if column.fields.iter().any(|field| matches!(field.node, Some(NodeEnum::AStar(_)))) {
+                return Some(column.location);
+            }
+        }
+    }
+
+    [select.larg.as_deref(), select.rarg.as_deref()].into_iter()
+        .flatten()
+        .find_map(select_star_location)
+}
+
+/// The name PostgreSQL uses internally for a type, so `timestamp without
+/// time zone` in the config matches `timestamp` in the SQL
+pub fn normalize_type_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = name.strip_prefix("pg_catalog.").unwrap_or(&name);
+    match name {
+        "timestamp without time zone" => "timestamp",
+        "timestamp with time zone" => "timestamptz",
+        "time without time zone" => "time",
+        "time with time zone" => "timetz",
+        "integer" | "int" => "int4",
+        "smallint" => "int2",
+        "bigint" => "int8",
+        "real" => "float4",
+        "double precision" | "float" => "float8",
+        "boolean" => "bool",
+        "character varying" => "varchar",
+        "character" | "char" => "bpchar",
+        "decimal" => "numeric",
+        name => name,
+    }.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rule: LintRule) -> LintRules {
+        LintRules {
+            disabled: LintRule::ALL.into_iter().filter(|other| *other != rule).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn messages(findings: &[LintFinding]) -> Vec<(Option<usize>, String)> {
+        findings.iter().map(|finding| (finding.line, finding.message.clone())).collect()
+    }
+
+    #[test]
+    fn test_schema_qualified() {
+        let sql = "CREATE VIEW active_users AS SELECT 1 AS id;\nCREATE VIEW api.orders AS SELECT 1 AS id;\n";
+        let findings = lint_code_file(sql, &rules(LintRule::SchemaQualified)).unwrap();
+        assert_eq!(messages(&findings), vec![(Some(1), "VIEW active_users is not schema-qualified".to_string())]);
+    }
+
+    #[test]
+    fn test_no_select_star() {
+        let sql = indoc::indoc! {r#"
+            CREATE VIEW api.users AS SELECT id FROM users;
+            CREATE VIEW api.everything AS
+              SELECT id FROM a
+              UNION ALL
+              SELECT b.* FROM b;
+            CREATE MATERIALIZED VIEW api.totals AS SELECT count(*) FROM orders;
+        "#};
+        let findings = lint_code_file(sql, &rules(LintRule::NoSelectStar)).unwrap();
+        assert_eq!(messages(&findings), vec![(Some(5), "View selects * - list the columns instead".to_string())]);
+    }
+
+    #[test]
+    fn test_function_volatility_fix() {
+        let sql = indoc::indoc! {r#"
+            CREATE FUNCTION api.one() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql;
+            CREATE FUNCTION api.two() RETURNS int STABLE AS $$ SELECT 2 $$ LANGUAGE sql;
+        "#};
+        let findings = lint_code_file(sql, &rules(LintRule::FunctionVolatility)).unwrap();
+        assert_eq!(messages(&findings), vec![(Some(1), "Function api.one doesn't declare IMMUTABLE, STABLE or VOLATILE".to_string())]);
+
+        let fixed = apply_fixes(sql, &findings);
+        assert!(fixed.starts_with("CREATE FUNCTION api.one() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql VOLATILE;\n"));
+        assert!(lint_code_file(&fixed, &rules(LintRule::FunctionVolatility)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_naming_and_forbidden_types() {
+        let lint_rules = LintRules {
+            disabled: vec![LintRule::SchemaQualified, LintRule::IdempotentMigration],
+            naming: vec![(ObjectType::Table, Regex::new("^[a-z_]+s$").unwrap())],
+            forbidden_types: vec![
+                (normalize_type_name("timestamp without time zone"), "timestamp without time zone".to_string()),
+                (normalize_type_name("json"), "json".to_string()),
+            ],
+        };
+        let sql = indoc::indoc! {r#"
+            CREATE TABLE public.user_account (
+                id integer PRIMARY KEY,
+                created_at timestamp,
+                updated_at timestamptz,
+                data json
+            );
+            ALTER TABLE public.user_account ADD COLUMN seen_at timestamp without time zone;
+        "#};
+        let findings = lint_migration_file(sql, &lint_rules).unwrap();
+        assert_eq!(messages(&findings), vec![
+            (Some(1), "TABLE name user_account doesn't match the naming pattern ^[a-z_]+s$".to_string()),
+            (Some(3), "Uses the forbidden type timestamp without time zone".to_string()),
+            (Some(5), "Uses the forbidden type json".to_string()),
+            (Some(7), "Uses the forbidden type timestamp without time zone".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_idempotent_migration_fix() {
+        let sql = indoc::indoc! {r#"
+            CREATE TABLE public.orders (id integer);
+            create unique index orders_id_idx on public.orders (id);
+            CREATE TABLE IF NOT EXISTS public.users (id integer);
+            DROP VIEW public.old_orders;
+            ALTER TABLE public.orders ADD COLUMN total numeric;
+        "#};
+        let findings = lint_migration_file(sql, &rules(LintRule::IdempotentMigration)).unwrap();
+        assert_eq!(messages(&findings), vec![
+            (Some(1), "CREATE TABLE without IF NOT EXISTS".to_string()),
+            (Some(2), "CREATE INDEX without IF NOT EXISTS".to_string()),
+            (Some(4), "DROP without IF EXISTS".to_string()),
+            (Some(5), "ALTER TABLE ... ADD COLUMN without IF NOT EXISTS".to_string()),
+        ]);
+        assert!(findings[3].fix.is_none());
+
+        assert_eq!(apply_fixes(sql, &findings), indoc::indoc! {r#"
+            CREATE TABLE IF NOT EXISTS public.orders (id integer);
+            create unique index IF NOT EXISTS orders_id_idx on public.orders (id);
+            CREATE TABLE IF NOT EXISTS public.users (id integer);
+            DROP VIEW IF EXISTS public.old_orders;
+            ALTER TABLE public.orders ADD COLUMN total numeric;
+        "#});
+    }
+
+    #[test]
+    fn test_parse_lint_rule() {
+        assert_eq!("no_select_star".parse::<LintRule>().unwrap(), LintRule::NoSelectStar);
+        assert_eq!("Forbidden-Type".parse::<LintRule>().unwrap(), LintRule::ForbiddenType);
+        assert!("no-tabs".parse::<LintRule>().is_err());
+    }
+}
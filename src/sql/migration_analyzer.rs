@@ -4,15 +4,19 @@
 //! This module helps identify which managed objects need to be pre-dropped
 //! before migrations that alter tables they depend on, and which enum ADD VALUE
 //! statements need to be pre-committed outside a transaction to avoid PostgreSQL's
-//! "unsafe use of new value" error.
+//! "unsafe use of new value" error. It also flags table changes that can break
+//! logical replication, and drops and rewrites whose cost depends on table size.
 
 use std::collections::HashSet;
-use crate::sql::QualifiedIdent;
+use crate::sql::{ColumnReference, QualifiedIdent, SqlObject, ObjectType, Dependencies};
+use crate::sql::objects::{identify_sql_object, NO_TRANSACTION_DIRECTIVE};
+use crate::sql::splitter::split_sql_file;
 use pg_query::NodeEnum;
 
 /// Extract tables affected by ALTER TABLE statements in migration SQL.
 ///
-/// Returns a set of qualified table names that are being altered.
+/// Returns a set of qualified table names that are being altered, along
+/// with sequences altered by ALTER SEQUENCE.
 /// This is used to find managed objects (views, functions, etc.) that
 /// depend on these tables and need to be pre-dropped before the migration.
 pub fn extract_altered_tables(sql: &str) -> Result<HashSet<QualifiedIdent>, Box<dyn std::error::Error>> {
@@ -21,15 +25,19 @@ pub fn extract_altered_tables(sql: &str) -> Result<HashSet<QualifiedIdent>, Box<
 
     for stmt in &parsed.protobuf.stmts {
         if let Some(node) = &stmt.stmt {
-            if let Some(NodeEnum::AlterTableStmt(alter)) = &node.node {
-                if let Some(relation) = &alter.relation {
-                    let schema = if relation.schemaname.is_empty() {
-                        None
-                    } else {
-                        Some(relation.schemaname.clone())
-                    };
-                    tables.insert(QualifiedIdent::new(schema, relation.relname.clone()));
+            match &node.node {
+                Some(NodeEnum::AlterTableStmt(alter)) => {
+                    if let Some(relation) = &alter.relation {
+                        tables.insert(range_var_ident(relation));
+                    }
                 }
+                // Sequences are relations too, used through nextval()
+                Some(NodeEnum::AlterSeqStmt(alter)) => {
+                    if let Some(sequence) = &alter.sequence {
+                        tables.insert(range_var_ident(sequence));
+                    }
+                }
+                _ => {}
             }
         }
     }
@@ -37,6 +45,100 @@ pub fn extract_altered_tables(sql: &str) -> Result<HashSet<QualifiedIdent>, Box<
     Ok(tables)
 }
 
+/// Extract the columns migration SQL drops or changes the type of. Views and
+/// functions reading them would stop PostgreSQL from running the migration.
+pub fn extract_altered_columns(sql: &str) -> Result<Vec<ColumnReference>, Box<dyn std::error::Error>> {
+    use pg_query::protobuf::AlterTableType;
+
+    let parsed = pg_query::parse(sql)?;
+    let mut columns = Vec::new();
+
+    for stmt in &parsed.protobuf.stmts {
+        let Some(NodeEnum::AlterTableStmt(alter)) = stmt.stmt.as_ref().and_then(|n| n.node.as_ref()) else {
+            continue;
+        };
+        let Some(relation) = &alter.relation else { continue };
+
+        for cmd in &alter.cmds {
+            if let Some(NodeEnum::AlterTableCmd(table_cmd)) = &cmd.node {
+                if matches!(table_cmd.subtype(), AlterTableType::AtDropColumn | AlterTableType::AtAlterColumnType) {
+                    columns.push(ColumnReference::new(range_var_ident(relation), table_cmd.name.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Extract objects created by migration SQL that managed objects can depend on
+/// (tables, views, types, domains and routines).
+///
+/// Dependencies of the returned objects are cleared: migrations are always
+/// applied before managed objects, so only edges *into* these objects matter.
+pub fn extract_created_objects(sql: &str) -> Result<Vec<SqlObject>, Box<dyn std::error::Error>> {
+    let mut objects = Vec::new();
+
+    for statement in split_sql_file(sql)? {
+        if let Ok(Some(mut object)) = identify_sql_object(&statement.sql) {
+            if matches!(
+                object.object_type,
+                ObjectType::Table | ObjectType::View | ObjectType::MaterializedView |
+                ObjectType::Type | ObjectType::Domain | ObjectType::Function | ObjectType::Procedure
+            ) {
+                object.dependencies = Dependencies::default();
+                object.start_line = statement.start_line;
+                objects.push(object);
+            }
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Check that migration SQL parses, without running any of it. The error
+/// names the line the first statement that doesn't parse starts on.
+pub fn check_migration_syntax(sql: &str) -> Result<(), String> {
+    let Err(error) = pg_query::parse(sql) else {
+        return Ok(());
+    };
+
+    // The scanner splits files the parser can't, to find the broken statement
+    let Ok(statements) = pg_query::split_with_scanner(sql) else {
+        return Err(error.to_string());
+    };
+    let mut position = 0;
+    for statement in statements {
+        let statement = statement.trim();
+        let Some(offset) = sql[position..].find(statement) else {
+            continue;
+        };
+        let start = position + offset;
+        position = start + statement.len();
+
+        if let Err(e) = pg_query::parse(statement) {
+            let line = sql[..start].matches('\n').count() + 1;
+            return Err(format!("line {}: {}", line, e));
+        }
+    }
+
+    Err(error.to_string())
+}
+
+/// Check if a migration opts out of the apply transaction with a
+/// `-- pgmg:no-transaction` line in its header comment (before any SQL).
+pub fn is_no_transaction_migration(sql: &str) -> bool {
+    for line in sql.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if !line.starts_with("--") {
+            return false;
+        }
+        if line == NO_TRANSACTION_DIRECTIVE {
+            return true;
+        }
+    }
+    false
+}
+
 /// Extract `ALTER TYPE ... ADD VALUE` statements from migration SQL and return
 /// them rewritten with `IF NOT EXISTS`.
 ///
@@ -73,9 +175,194 @@ pub fn extract_enum_add_value_statements(sql: &str) -> Result<Vec<(String, Strin
     Ok(results)
 }
 
+/// A migration statement that can break logical replication of a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationHazard {
+    pub table: QualifiedIdent,
+    /// Short description of the operation, e.g. "column type change"
+    pub operation: String,
+    /// Whether subscribers will need to re-snapshot the table afterwards
+    pub requires_resync: bool,
+}
+
+/// Extract table changes from migration SQL that can break logical replication:
+/// column type changes, column adds/drops/renames, table rewrites, replica
+/// identity changes and table drops.
+///
+/// Column changes break subscribers whose tables no longer match the
+/// publisher's, and rewrites or drops leave subscribers to re-snapshot the table.
+pub fn extract_replication_hazards(sql: &str) -> Result<Vec<ReplicationHazard>, Box<dyn std::error::Error>> {
+    use pg_query::protobuf::{AlterTableType, ObjectType as PgObjectType};
+
+    let parsed = pg_query::parse(sql)?;
+    let mut hazards = Vec::new();
+
+    for stmt in &parsed.protobuf.stmts {
+        let Some(node) = stmt.stmt.as_ref().and_then(|n| n.node.as_ref()) else {
+            continue;
+        };
+
+        match node {
+            NodeEnum::AlterTableStmt(alter) => {
+                let Some(relation) = &alter.relation else { continue };
+
+                for cmd in &alter.cmds {
+                    if let Some(NodeEnum::AlterTableCmd(table_cmd)) = &cmd.node {
+                        let hazard = match table_cmd.subtype() {
+                            AlterTableType::AtAlterColumnType => Some(("column type change", true)),
+                            AlterTableType::AtAddColumn => Some(("column add", false)),
+                            AlterTableType::AtDropColumn => Some(("column drop", false)),
+                            AlterTableType::AtSetLogged | AlterTableType::AtSetUnLogged => {
+                                Some(("SET LOGGED/UNLOGGED (table rewrite)", true))
+                            }
+                            AlterTableType::AtSetAccessMethod => Some(("access method change (table rewrite)", true)),
+                            AlterTableType::AtReplicaIdentity => Some(("replica identity change", false)),
+                            _ => None,
+                        };
+
+                        if let Some((operation, requires_resync)) = hazard {
+                            hazards.push(ReplicationHazard {
+                                table: range_var_ident(relation),
+                                operation: operation.to_string(),
+                                requires_resync,
+                            });
+                        }
+                    }
+                }
+            }
+            NodeEnum::RenameStmt(rename) => {
+                let operation = match rename.rename_type() {
+                    PgObjectType::ObjectTable => "table rename",
+                    PgObjectType::ObjectColumn => "column rename",
+                    _ => continue,
+                };
+                if let Some(relation) = &rename.relation {
+                    hazards.push(ReplicationHazard {
+                        table: range_var_ident(relation),
+                        operation: operation.to_string(),
+                        requires_resync: false,
+                    });
+                }
+            }
+            NodeEnum::DropStmt(drop) if drop.remove_type() == PgObjectType::ObjectTable => {
+                for object in &drop.objects {
+                    if let Some(NodeEnum::List(list)) = &object.node {
+                        let parts: Vec<String> = list.items.iter()
+                            .filter_map(|item| match &item.node {
+                                Some(NodeEnum::String(s)) => Some(s.sval.clone()),
+                                _ => None,
+                            })
+                            .collect();
+                        hazards.push(ReplicationHazard {
+                            table: QualifiedIdent::from_qualified_name(&parts.join(".")),
+                            operation: "table drop".to_string(),
+                            requires_resync: true,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(hazards)
+}
+
+/// A migration statement that drops, empties or rewrites a relation
+#[derive(Debug, Clone, PartialEq)]
+pub struct DestructiveOperation {
+    pub relation: QualifiedIdent,
+    /// Short description of the operation, e.g. "DROP TABLE"
+    pub operation: String,
+}
+
+/// Extract statements from migration SQL whose cost grows with the size of
+/// the relation they touch: drops of tables, materialized views and indexes,
+/// TRUNCATE, and ALTER TABLE forms that rewrite the table.
+pub fn extract_destructive_operations(sql: &str) -> Result<Vec<DestructiveOperation>, Box<dyn std::error::Error>> {
+    use pg_query::protobuf::{AlterTableType, ObjectType as PgObjectType};
+
+    let parsed = pg_query::parse(sql)?;
+    let mut operations = Vec::new();
+
+    for stmt in &parsed.protobuf.stmts {
+        let Some(node) = stmt.stmt.as_ref().and_then(|n| n.node.as_ref()) else {
+            continue;
+        };
+
+        match node {
+            NodeEnum::DropStmt(drop) => {
+                let operation = match drop.remove_type() {
+                    PgObjectType::ObjectTable => "DROP TABLE",
+                    PgObjectType::ObjectMatview => "DROP MATERIALIZED VIEW",
+                    PgObjectType::ObjectIndex => "DROP INDEX",
+                    _ => continue,
+                };
+                for object in &drop.objects {
+                    if let Some(NodeEnum::List(list)) = &object.node {
+                        let parts: Vec<String> = list.items.iter()
+                            .filter_map(|item| match &item.node {
+                                Some(NodeEnum::String(s)) => Some(s.sval.clone()),
+                                _ => None,
+                            })
+                            .collect();
+                        operations.push(DestructiveOperation {
+                            relation: QualifiedIdent::from_qualified_name(&parts.join(".")),
+                            operation: operation.to_string(),
+                        });
+                    }
+                }
+            }
+            NodeEnum::TruncateStmt(truncate) => {
+                for relation in &truncate.relations {
+                    if let Some(NodeEnum::RangeVar(range_var)) = &relation.node {
+                        operations.push(DestructiveOperation {
+                            relation: range_var_ident(range_var),
+                            operation: "TRUNCATE".to_string(),
+                        });
+                    }
+                }
+            }
+            NodeEnum::AlterTableStmt(alter) => {
+                let Some(relation) = &alter.relation else { continue };
+
+                for cmd in &alter.cmds {
+                    if let Some(NodeEnum::AlterTableCmd(table_cmd)) = &cmd.node {
+                        let operation = match table_cmd.subtype() {
+                            AlterTableType::AtAlterColumnType => "column type change (table rewrite)",
+                            AlterTableType::AtSetLogged | AlterTableType::AtSetUnLogged => {
+                                "SET LOGGED/UNLOGGED (table rewrite)"
+                            }
+                            AlterTableType::AtSetAccessMethod => "access method change (table rewrite)",
+                            _ => continue,
+                        };
+                        operations.push(DestructiveOperation {
+                            relation: range_var_ident(relation),
+                            operation: operation.to_string(),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(operations)
+}
+
+fn range_var_ident(relation: &pg_query::protobuf::RangeVar) -> QualifiedIdent {
+    let schema = if relation.schemaname.is_empty() {
+        None
+    } else {
+        Some(relation.schemaname.clone())
+    };
+    QualifiedIdent::new(schema, relation.relname.clone())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use indoc::indoc;
 
     #[test]
     fn test_alter_column_type() {
@@ -104,6 +391,17 @@ mod tests {
         assert!(tables.contains(&QualifiedIdent::new(None, "users".to_string())));
     }
 
+    #[test]
+    fn test_extract_altered_columns() {
+        let sql = indoc! {r#"
+            ALTER TABLE api.users DROP COLUMN old_field, ADD COLUMN new_field text;
+            ALTER TABLE orders ALTER COLUMN total TYPE numeric(10,2), ALTER COLUMN note SET DEFAULT '';
+        "#};
+        let columns: Vec<String> = extract_altered_columns(sql).unwrap().iter().map(|column| column.to_string()).collect();
+
+        assert_eq!(columns, vec!["api.users.old_field", "orders.total"]);
+    }
+
     #[test]
     fn test_alter_table_add_column() {
         let sql = r#"ALTER TABLE api.users ADD COLUMN new_field text;"#;
@@ -148,6 +446,30 @@ mod tests {
         assert!(tables.contains(&QualifiedIdent::new(None, "orders".to_string())));
     }
 
+    #[test]
+    fn test_alter_sequence() {
+        let sql = r#"ALTER SEQUENCE api.order_ids RESTART WITH 1000 OWNED BY api.orders.id;"#;
+        let tables = extract_altered_tables(sql).unwrap();
+
+        assert_eq!(tables.len(), 1);
+        assert!(tables.contains(&QualifiedIdent::new(Some("api".to_string()), "order_ids".to_string())));
+    }
+
+    #[test]
+    fn test_check_migration_syntax() {
+        let sql = indoc! {r#"
+            CREATE TABLE orders (id integer PRIMARY KEY);
+
+            INSERT INTO orders (id)
+            SELECT id FORM legacy_orders;
+        "#};
+        let err = check_migration_syntax(sql).unwrap_err();
+        assert!(err.starts_with("line 3: "), "{}", err);
+        assert!(err.contains("legacy_orders"), "{}", err);
+
+        assert!(check_migration_syntax("CREATE TABLE orders (id integer); DO $$ BEGIN NULL; END $$;").is_ok());
+    }
+
     #[test]
     fn test_extract_enum_add_value() {
         let sql = r#"ALTER TYPE "public"."enum_status" ADD VALUE 'active' BEFORE 'inactive';"#;
@@ -198,4 +520,99 @@ mod tests {
 
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_replication_hazards() {
+        let sql = r#"
+            ALTER TABLE orders ALTER COLUMN total TYPE numeric(12,2);
+            ALTER TABLE api.users ADD COLUMN status text, DROP COLUMN legacy;
+            ALTER TABLE products RENAME COLUMN title TO name;
+            ALTER TABLE products ADD CONSTRAINT products_name_key UNIQUE (name);
+            DROP TABLE audit.old_events;
+            CREATE INDEX orders_total_idx ON orders (total);
+        "#;
+        let hazards = extract_replication_hazards(sql).unwrap();
+
+        let summary: Vec<(String, &str, bool)> = hazards.iter()
+            .map(|h| {
+                let table = match &h.table.schema {
+                    Some(schema) => format!("{}.{}", schema, h.table.name),
+                    None => h.table.name.clone(),
+                };
+                (table, h.operation.as_str(), h.requires_resync)
+            })
+            .collect();
+        assert_eq!(summary, vec![
+            ("orders".to_string(), "column type change", true),
+            ("api.users".to_string(), "column add", false),
+            ("api.users".to_string(), "column drop", false),
+            ("products".to_string(), "column rename", false),
+            ("audit.old_events".to_string(), "table drop", true),
+        ]);
+    }
+
+    #[test]
+    fn test_destructive_operations() {
+        let sql = r#"
+            DROP MATERIALIZED VIEW analytics.daily_rollup;
+            DROP TABLE audit.old_events, staging;
+            DROP VIEW api.active_users;
+            TRUNCATE events;
+            ALTER TABLE orders ALTER COLUMN total TYPE numeric(12,2), ADD COLUMN note text;
+            ALTER TABLE logs SET UNLOGGED;
+            DROP INDEX orders_total_idx;
+        "#;
+        let operations = extract_destructive_operations(sql).unwrap();
+
+        let summary: Vec<(String, &str)> = operations.iter()
+            .map(|op| {
+                let relation = match &op.relation.schema {
+                    Some(schema) => format!("{}.{}", schema, op.relation.name),
+                    None => op.relation.name.clone(),
+                };
+                (relation, op.operation.as_str())
+            })
+            .collect();
+        assert_eq!(summary, vec![
+            ("analytics.daily_rollup".to_string(), "DROP MATERIALIZED VIEW"),
+            ("audit.old_events".to_string(), "DROP TABLE"),
+            ("staging".to_string(), "DROP TABLE"),
+            ("events".to_string(), "TRUNCATE"),
+            ("orders".to_string(), "column type change (table rewrite)"),
+            ("logs".to_string(), "SET LOGGED/UNLOGGED (table rewrite)"),
+            ("orders_total_idx".to_string(), "DROP INDEX"),
+        ]);
+    }
+
+    #[test]
+    fn test_extract_created_objects() {
+        let sql = r#"
+            CREATE TABLE orders (id bigint PRIMARY KEY, user_id bigint REFERENCES users (id));
+            CREATE INDEX orders_user_idx ON orders (user_id);
+            CREATE TYPE api.order_status AS ENUM ('open', 'closed');
+            INSERT INTO orders VALUES (1, 1);
+        "#;
+        let objects = extract_created_objects(sql).unwrap();
+
+        let created: Vec<(ObjectType, &str)> = objects.iter()
+            .map(|obj| (obj.object_type.clone(), obj.qualified_name.name.as_str()))
+            .collect();
+        assert_eq!(created, vec![
+            (ObjectType::Table, "orders"),
+            (ObjectType::Type, "order_status"),
+        ]);
+        assert!(objects[0].dependencies.relations.is_empty());
+    }
+
+    #[test]
+    fn test_no_transaction_migration_header() {
+        assert!(is_no_transaction_migration(
+            "-- Add index without blocking writes\n-- pgmg:no-transaction\n\nCREATE INDEX CONCURRENTLY orders_user_idx ON orders (user_id);"
+        ));
+        assert!(!is_no_transaction_migration("CREATE INDEX orders_user_idx ON orders (user_id);"));
+        // Only the header counts
+        assert!(!is_no_transaction_migration(
+            "CREATE TABLE orders (id int);\n-- pgmg:no-transaction\nVACUUM orders;"
+        ));
+    }
 }
@@ -3,12 +3,33 @@ pub mod splitter;
 pub mod objects;
 pub mod test_analyzer;
 pub mod migration_analyzer;
+pub mod skip_rules;
+pub mod quote;
+pub mod lint;
+pub mod format;
+pub mod directives;
+pub mod enums;
+pub mod publications;
+pub mod cron;
+pub mod partitions;
+pub mod schema_filter;
+pub mod tenants;
+pub mod vars;
 
 pub use parser::{
     analyze_statement, analyze_plpgsql, filter_builtins,
-    Dependencies, QualifiedIdent
+    ColumnReference, Dependencies, QualifiedIdent
 };
 pub use splitter::{split_sql_file, SqlStatement};
 pub use objects::{identify_sql_object, calculate_ddl_hash, SqlObject, ObjectType};
+pub use directives::{matches_tags, parse_object_directives, ObjectDirectives, RefreshStrategy};
+pub use enums::{enum_value_additions, extract_enum_values};
+pub use publications::publication_changes;
+pub use cron::{cron_job_changes, parse_cron_job, CronJob};
+pub use partitions::{maintenance_command, partition_parent, MaintainedTable, PartitionInterval, PartitionMaintenance, PartitionRemoval};
+pub use schema_filter::SchemaFilter;
+pub use tenants::TenantTemplate;
+pub use vars::{substitute_vars, SqlVars};
+pub use skip_rules::{SkipRule, SkipRules, SkipWhen};
 pub use test_analyzer::{analyze_test_file, scan_test_files, build_test_dependency_map, TestFile, TestDependencyMap};
-pub use migration_analyzer::{extract_altered_tables, extract_enum_add_value_statements};
\ No newline at end of file
+pub use migration_analyzer::{extract_altered_tables, extract_altered_columns, extract_enum_add_value_statements, extract_replication_hazards, extract_destructive_operations, extract_created_objects, check_migration_syntax, is_no_transaction_migration, ReplicationHazard, DestructiveOperation};
\ No newline at end of file
@@ -1,8 +1,11 @@
 use std::path::PathBuf;
 use std::fmt;
 use crate::sql::parser::{Dependencies, QualifiedIdent};
+use crate::sql::directives::ObjectDirectives;
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use pg_query;
+use tracing::warn;
 
 /// Operations that can be performed on cron jobs
 #[derive(Debug)]
@@ -12,7 +15,9 @@ enum CronOperation {
     Unschedule { job_name: String },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Serialized as in the state tables, e.g. `materialized_view`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ObjectType {
     Table,
     View,
@@ -27,6 +32,16 @@ pub enum ObjectType {
     CronJob,
     Aggregate,
     Operator,
+    SecurityLabel,
+    Statistics,
+    Extension,
+    Publication,
+    Collation,
+    TextSearchConfiguration,
+    TextSearchDictionary,
+    OperatorClass,
+    OperatorFamily,
+    Cast,
 }
 
 impl fmt::Display for ObjectType {
@@ -45,6 +60,52 @@ impl fmt::Display for ObjectType {
             ObjectType::CronJob => write!(f, "CRON JOB"),
             ObjectType::Aggregate => write!(f, "AGGREGATE"),
             ObjectType::Operator => write!(f, "OPERATOR"),
+            ObjectType::SecurityLabel => write!(f, "SECURITY LABEL"),
+            ObjectType::Statistics => write!(f, "STATISTICS"),
+            ObjectType::Extension => write!(f, "EXTENSION"),
+            ObjectType::Publication => write!(f, "PUBLICATION"),
+            ObjectType::Collation => write!(f, "COLLATION"),
+            ObjectType::TextSearchConfiguration => write!(f, "TEXT SEARCH CONFIGURATION"),
+            ObjectType::TextSearchDictionary => write!(f, "TEXT SEARCH DICTIONARY"),
+            ObjectType::OperatorClass => write!(f, "OPERATOR CLASS"),
+            ObjectType::OperatorFamily => write!(f, "OPERATOR FAMILY"),
+            ObjectType::Cast => write!(f, "CAST"),
+        }
+    }
+}
+
+impl std::str::FromStr for ObjectType {
+    type Err = String;
+
+    /// Parse an object type name as used in state tables and on the command line
+    /// (e.g. `table`, `materialized_view`, `materialized-view`, `cron job`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_lowercase().replace(['-', ' '], "_");
+        match normalized.as_str() {
+            "table" => Ok(ObjectType::Table),
+            "view" => Ok(ObjectType::View),
+            "materialized_view" | "matview" => Ok(ObjectType::MaterializedView),
+            "function" => Ok(ObjectType::Function),
+            "procedure" => Ok(ObjectType::Procedure),
+            "type" => Ok(ObjectType::Type),
+            "domain" => Ok(ObjectType::Domain),
+            "index" => Ok(ObjectType::Index),
+            "trigger" => Ok(ObjectType::Trigger),
+            "comment" => Ok(ObjectType::Comment),
+            "cron_job" => Ok(ObjectType::CronJob),
+            "aggregate" => Ok(ObjectType::Aggregate),
+            "operator" => Ok(ObjectType::Operator),
+            "security_label" => Ok(ObjectType::SecurityLabel),
+            "statistics" => Ok(ObjectType::Statistics),
+            "extension" => Ok(ObjectType::Extension),
+            "publication" => Ok(ObjectType::Publication),
+            "collation" => Ok(ObjectType::Collation),
+            "text_search_configuration" => Ok(ObjectType::TextSearchConfiguration),
+            "text_search_dictionary" => Ok(ObjectType::TextSearchDictionary),
+            "operator_class" => Ok(ObjectType::OperatorClass),
+            "operator_family" => Ok(ObjectType::OperatorFamily),
+            "cast" => Ok(ObjectType::Cast),
+            _ => Err(format!("Unknown object type: {}", s)),
         }
     }
 }
@@ -59,6 +120,15 @@ pub struct SqlObject {
     pub ddl_hash: String,
     pub start_line: Option<usize>,
     pub end_line: Option<usize>,
+    /// ALTER statements from the same file that set options or the owner of
+    /// the object (see [`identify_object_setting`]), and the `owner` directive's. They are run after every creation,
+    /// since recreating the object would lose them.
+    pub settings: Vec<String>,
+    /// Files whose objects must be created before this one, from
+    /// `-- pgmg:apply-after` hints in the object's file
+    pub apply_after: Vec<PathBuf>,
+    /// `-- pgmg:` directives from the comments leading the definition
+    pub directives: ObjectDirectives,
 }
 
 /// Intermediate structure that holds both parsed AST and extracted metadata
@@ -81,7 +151,11 @@ impl SqlObject {
         dependencies: Dependencies,
         source_file: Option<PathBuf>,
     ) -> Self {
-        let ddl_hash = calculate_ddl_hash(&ddl_statement);
+        // Cron jobs are compared by what they schedule, see CronJob::hash
+        let ddl_hash = match object_type {
+            ObjectType::CronJob => crate::sql::cron::parse_cron_job(&ddl_statement).map(|job| job.hash()),
+            _ => None,
+        }.unwrap_or_else(|| calculate_ddl_hash(&ddl_statement));
         Self {
             object_type,
             qualified_name,
@@ -91,6 +165,9 @@ impl SqlObject {
             ddl_hash,
             start_line: None,
             end_line: None,
+            settings: Vec::new(),
+            apply_after: Vec::new(),
+            directives: ObjectDirectives::default(),
         }
     }
     
@@ -99,6 +176,22 @@ impl SqlObject {
         self.end_line = end_line;
         self
     }
+
+    /// The object's DDL followed by its settings, as stored in pgmg_state
+    pub fn ddl_source(&self) -> String {
+        std::iter::once(&self.ddl_statement)
+            .chain(&self.settings)
+            .map(|statement| format!("{};", statement.trim_end().trim_end_matches(';')))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Attach an option-setting statement. It becomes part of the object's
+    /// hash, so changing the option updates the object.
+    pub fn add_setting(&mut self, statement: String) {
+        self.settings.push(normalize_ddl(&statement));
+        self.ddl_hash = calculate_ddl_hash(&format!("{}\n{}", self.ddl_statement, self.settings.join("\n")));
+    }
 }
 
 /// Parse a SQL statement once and extract all necessary information
@@ -113,7 +206,15 @@ pub fn parse_sql_object(statement: &str) -> Result<Option<ParsedSqlObject>, Box<
                 match node {
                     pg_query::NodeEnum::CreateStmt(create_table) => {
                         let qualified_name = extract_range_var_name(&create_table.relation)?;
-                        let dependencies = extract_dependencies_from_parsed_with_sql(&parsed, statement)?;
+                        let mut dependencies = extract_dependencies_from_parsed_with_sql(&parsed, statement)?;
+
+                        // A partition, or a table inheriting from others, is
+                        // created after them and dropped along with them
+                        for parent in &create_table.inh_relations {
+                            if let Some(pg_query::NodeEnum::RangeVar(range_var)) = &parent.node {
+                                dependencies.relations.insert(extract_range_var_name(&Some(range_var.clone()))?);
+                            }
+                        }
                         
                         return Ok(Some(ParsedSqlObject {
                             statement: statement.to_string(),
@@ -237,6 +338,112 @@ pub fn parse_sql_object(statement: &str) -> Result<Option<ParsedSqlObject>, Box<
                             trigger_table: None,
                         }));
                     }
+                    pg_query::NodeEnum::CreateStatsStmt(stats_stmt) => {
+                        // Extended statistics (CREATE STATISTICS name ON a, b FROM table)
+                        if stats_stmt.defnames.is_empty() {
+                            return Err("Statistics object has no name".into());
+                        }
+                        let qualified_name = extract_name_from_node_list(&stats_stmt.defnames)?;
+                        let mut dependencies = extract_dependencies_from_parsed_with_sql(&parsed, statement)?;
+
+                        // The statistics object is dropped with its table, so make sure it depends on it
+                        for relation in &stats_stmt.relations {
+                            if let Some(pg_query::NodeEnum::RangeVar(range_var)) = &relation.node {
+                                dependencies.relations.insert(extract_range_var_name(&Some(range_var.clone()))?);
+                            }
+                        }
+
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::Statistics,
+                            qualified_name,
+                            dependencies,
+                            trigger_table: None,
+                        }));
+                    }
+                    pg_query::NodeEnum::CreateExtensionStmt(extension_stmt) => {
+                        // Extensions aren't in a schema: WITH SCHEMA only says
+                        // where their objects go
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::Extension,
+                            qualified_name: QualifiedIdent::from_name(extension_stmt.extname.clone()),
+                            dependencies: Dependencies::default(),
+                            trigger_table: None,
+                        }));
+                    }
+                    pg_query::NodeEnum::CreateOpClassStmt(opclass_stmt) => {
+                        // Includes the operators, support functions and types
+                        // it's made of, and the family it's added to
+                        let dependencies = extract_dependencies_from_parsed_with_sql(&parsed, statement)?;
+
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::OperatorClass,
+                            qualified_name: extract_name_from_node_list(&opclass_stmt.opclassname)?,
+                            dependencies,
+                            trigger_table: None,
+                        }));
+                    }
+                    pg_query::NodeEnum::CreateOpFamilyStmt(opfamily_stmt) => {
+                        // Its members are added with ALTER OPERATOR FAMILY,
+                        // applied as settings of the family
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::OperatorFamily,
+                            qualified_name: extract_name_from_node_list(&opfamily_stmt.opfamilyname)?,
+                            dependencies: Dependencies::default(),
+                            trigger_table: None,
+                        }));
+                    }
+                    pg_query::NodeEnum::CreateCastStmt(cast_stmt) => {
+                        // Casts aren't in a schema and are named by the types
+                        // they convert between, as DROP CAST takes them
+                        let mut dependencies = Dependencies::default();
+                        let mut type_names = Vec::new();
+                        for type_name in [&cast_stmt.sourcetype, &cast_stmt.targettype] {
+                            let type_name = type_name.as_ref().ok_or("Cast is missing a type")?;
+                            type_names.push(extract_type_name(type_name).ok_or("Cast type has no name")?);
+                            dependencies.types.insert(extract_name_from_node_list(&type_name.names)?);
+                        }
+                        if let Some(func) = &cast_stmt.func {
+                            dependencies.functions.insert(extract_name_from_node_list(&func.objname)?);
+                        }
+
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::Cast,
+                            qualified_name: QualifiedIdent::from_name(format!("({} AS {})", type_names[0], type_names[1])),
+                            dependencies,
+                            trigger_table: None,
+                        }));
+                    }
+                    pg_query::NodeEnum::CreatePublicationStmt(publication_stmt) => {
+                        // Publications aren't in a schema. They depend on the
+                        // tables they list, but not on the schemas they publish.
+                        let mut dependencies = Dependencies::default();
+                        for object in &publication_stmt.pubobjects {
+                            if let Some(pg_query::NodeEnum::PublicationObjSpec(spec)) = &object.node {
+                                if let Some(table) = &spec.pubtable {
+                                    dependencies.relations.insert(extract_range_var_name(&table.relation)?);
+                                }
+                            }
+                        }
+
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::Publication,
+                            qualified_name: QualifiedIdent::from_name(publication_stmt.pubname.clone()),
+                            dependencies,
+                            trigger_table: None,
+                        }));
+                    }
                     pg_query::NodeEnum::CreateTrigStmt(trigger_stmt) => {
                         let qualified_name = QualifiedIdent::from_name(trigger_stmt.trigname.clone());
                         let dependencies = extract_dependencies_from_parsed_with_sql(&parsed, statement)?;
@@ -280,6 +487,20 @@ pub fn parse_sql_object(statement: &str) -> Result<Option<ParsedSqlObject>, Box<
                             trigger_table: None,
                         }));
                     }
+                    pg_query::NodeEnum::SecLabelStmt(label_stmt) => {
+                        // Parse SECURITY LABEL statements (e.g. PostgreSQL Anonymizer masking rules).
+                        // Like comments, labels only depend on the object they are attached to.
+                        let (qualified_name, dependencies) = parse_security_label_target(label_stmt)?;
+
+                        return Ok(Some(ParsedSqlObject {
+                            statement: statement.to_string(),
+                            parsed,
+                            object_type: ObjectType::SecurityLabel,
+                            qualified_name,
+                            dependencies,
+                            trigger_table: None,
+                        }));
+                    }
                     pg_query::NodeEnum::SelectStmt(_) => {
                         // Check if this is a cron.schedule() or cron.unschedule() call
                         if let Some(cron_info) = parse_cron_call_from_statement(statement)? {
@@ -352,6 +573,26 @@ pub fn parse_sql_object(statement: &str) -> Result<Option<ParsedSqlObject>, Box<
                                 }));
                             }
                         }
+                        // Handle CREATE COLLATION and CREATE TEXT SEARCH CONFIGURATION/DICTIONARY
+                        else if let Some(object_type) = match define_stmt.kind() {
+                            pg_query::protobuf::ObjectType::ObjectCollation => Some(ObjectType::Collation),
+                            pg_query::protobuf::ObjectType::ObjectTsconfiguration => Some(ObjectType::TextSearchConfiguration),
+                            pg_query::protobuf::ObjectType::ObjectTsdictionary => Some(ObjectType::TextSearchDictionary),
+                            _ => None,
+                        } {
+                            let qualified_name = extract_defname(&define_stmt.defnames)?;
+                            // Includes the collation or configuration copied with FROM or COPY
+                            let dependencies = extract_dependencies_from_parsed_with_sql(&parsed, statement)?;
+
+                            return Ok(Some(ParsedSqlObject {
+                                statement: statement.to_string(),
+                                parsed,
+                                object_type,
+                                qualified_name,
+                                dependencies,
+                                trigger_table: None,
+                            }));
+                        }
                     }
                     _ => {}
                 }
@@ -463,6 +704,194 @@ pub fn extract_trigger_table(statement: &str) -> Result<QualifiedIdent, Box<dyn
     }
 }
 
+/// The signature `DROP OPERATOR` needs for the operator a definition creates,
+/// e.g. `api.<->(point, point)`, or `!(NONE, pg_catalog.int8)` for a prefix
+/// operator. Statements after the `CREATE OPERATOR`, such as its owner, are
+/// skipped.
+pub fn extract_operator_signature(statement: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let parsed = pg_query::parse(statement)?;
+
+    for stmt in &parsed.protobuf.stmts {
+        let Some(pg_query::NodeEnum::DefineStmt(define_stmt)) = stmt.stmt.as_ref().and_then(|stmt| stmt.node.as_ref()) else {
+            continue;
+        };
+        if define_stmt.kind() != pg_query::protobuf::ObjectType::ObjectOperator {
+            continue;
+        }
+
+        let qualified_name = extract_defname(&define_stmt.defnames)?;
+        let arg_type = |defname: &str| define_stmt.definition.iter().find_map(|node| match &node.node {
+            Some(pg_query::NodeEnum::DefElem(elem)) if elem.defname == defname => match elem.arg.as_ref()?.node.as_ref()? {
+                pg_query::NodeEnum::TypeName(type_name) => extract_type_name(type_name),
+                _ => None,
+            },
+            _ => None,
+        });
+        let right = arg_type("rightarg").ok_or("Operator has no RIGHTARG")?;
+        let left = arg_type("leftarg").unwrap_or_else(|| "NONE".to_string());
+
+        return Ok(format!("{}({}, {})", format_qualified_name(&qualified_name), left, right));
+    }
+
+    Err("Statement is not a CREATE OPERATOR".into())
+}
+
+/// Check whether a view or routine is defined with CREATE OR REPLACE, so it can
+/// be replaced without dropping it first
+pub fn is_create_or_replace(statement: &str) -> bool {
+    let Ok(parsed) = pg_query::parse(statement) else {
+        return false;
+    };
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .any(|node| match node {
+            pg_query::NodeEnum::ViewStmt(view_stmt) => view_stmt.replace,
+            pg_query::NodeEnum::CreateFunctionStmt(func_stmt) => func_stmt.replace,
+            _ => false,
+        })
+}
+
+/// The version a `CREATE EXTENSION ... VERSION '1.2'` statement pins the
+/// extension to, if any
+pub fn extract_extension_version(statement: &str) -> Option<String> {
+    let parsed = pg_query::parse(statement).ok()?;
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .find_map(|node| match node {
+            pg_query::NodeEnum::CreateExtensionStmt(extension_stmt) => extension_stmt.options.iter()
+                .find_map(|option| match &option.node {
+                    Some(pg_query::NodeEnum::DefElem(def)) if def.defname == "new_version" => {
+                        match def.arg.as_ref().and_then(|arg| arg.node.as_ref()) {
+                            Some(pg_query::NodeEnum::String(version)) => Some(version.sval.clone()),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }),
+            _ => None,
+        })
+}
+
+/// Directive that makes apply run a statement outside the apply transaction
+pub const NO_TRANSACTION_DIRECTIVE: &str = "-- pgmg:no-transaction";
+
+/// File-level directive ordering a file's objects after those of another file,
+/// for dependencies the parser can't see (e.g. functions called through
+/// dynamic SQL): `-- pgmg:apply-after ../functions/lookup.sql`
+pub const APPLY_AFTER_DIRECTIVE: &str = "-- pgmg:apply-after";
+
+/// Extract the paths named by `-- pgmg:apply-after` lines, as written
+pub fn extract_apply_after_hints(content: &str) -> Vec<String> {
+    content.lines()
+        .filter_map(|line| line.trim().strip_prefix(APPLY_AFTER_DIRECTIVE))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .flat_map(|rest| rest.split_whitespace())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Check if a statement can't run inside a transaction block: CREATE INDEX
+/// CONCURRENTLY, or anything preceded by a `-- pgmg:no-transaction` line
+pub fn runs_outside_transaction(statement: &str) -> bool {
+    if statement.lines().any(|line| line.trim() == NO_TRANSACTION_DIRECTIVE) {
+        return true;
+    }
+
+    let Ok(parsed) = pg_query::parse(statement) else {
+        return false;
+    };
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .any(|node| matches!(node, pg_query::NodeEnum::IndexStmt(index_stmt) if index_stmt.concurrent))
+}
+
+/// Identify statements that set options on an object rather than create one:
+/// `ALTER FUNCTION/PROCEDURE ...` attributes such as `SET work_mem = '64MB'`,
+/// `ALTER [MATERIALIZED] VIEW ... SET/RESET (...)` storage options,
+/// `ALTER TEXT SEARCH CONFIGURATION ... MAPPING` and `ALTER TEXT SEARCH
+/// DICTIONARY ... (...)`, `ALTER OPERATOR FAMILY ... ADD`, and `ALTER ...
+/// OWNER TO` for functions, procedures,
+/// aggregates, views, types, domains, statistics, collations and text search
+/// configurations and dictionaries.
+///
+/// Returns the type and name of the object the options belong to.
+pub fn identify_object_setting(statement: &str) -> Result<Option<(ObjectType, QualifiedIdent)>, Box<dyn std::error::Error>> {
+    use pg_query::protobuf::{AlterTableType, ObjectType as PgObjectType};
+
+    let parsed = pg_query::parse(statement)?;
+    let [stmt] = parsed.protobuf.stmts.as_slice() else {
+        return Ok(None);
+    };
+
+    match stmt.stmt.as_ref().and_then(|s| s.node.as_ref()) {
+        Some(pg_query::NodeEnum::AlterFunctionStmt(alter)) => {
+            let Some(func) = &alter.func else { return Ok(None) };
+            let object_type = match alter.objtype() {
+                PgObjectType::ObjectProcedure => ObjectType::Procedure,
+                _ => ObjectType::Function,
+            };
+            Ok(Some((object_type, extract_function_name_from_list(&func.objname)?)))
+        }
+        Some(pg_query::NodeEnum::AlterTableStmt(alter)) => {
+            let object_type = match alter.objtype() {
+                PgObjectType::ObjectView => ObjectType::View,
+                PgObjectType::ObjectMatview => ObjectType::MaterializedView,
+                _ => return Ok(None),
+            };
+
+            let only_settings = !alter.cmds.is_empty() && alter.cmds.iter().all(|cmd| matches!(
+                &cmd.node,
+                Some(pg_query::NodeEnum::AlterTableCmd(table_cmd))
+                    if matches!(
+                        table_cmd.subtype(),
+                        AlterTableType::AtSetRelOptions | AlterTableType::AtResetRelOptions | AlterTableType::AtChangeOwner
+                    )
+            ));
+            if !only_settings {
+                return Ok(None);
+            }
+
+            Ok(Some((object_type, extract_range_var_name(&alter.relation)?)))
+        }
+        Some(pg_query::NodeEnum::AlterOwnerStmt(alter)) => {
+            let object_type = match alter.object_type() {
+                PgObjectType::ObjectFunction => ObjectType::Function,
+                PgObjectType::ObjectProcedure => ObjectType::Procedure,
+                PgObjectType::ObjectAggregate => ObjectType::Aggregate,
+                PgObjectType::ObjectType => ObjectType::Type,
+                PgObjectType::ObjectDomain => ObjectType::Domain,
+                PgObjectType::ObjectStatisticExt => ObjectType::Statistics,
+                PgObjectType::ObjectCollation => ObjectType::Collation,
+                PgObjectType::ObjectTsconfiguration => ObjectType::TextSearchConfiguration,
+                PgObjectType::ObjectTsdictionary => ObjectType::TextSearchDictionary,
+                _ => return Ok(None),
+            };
+
+            let name = match alter.object.as_ref().and_then(|object| object.node.as_ref()) {
+                Some(pg_query::NodeEnum::ObjectWithArgs(func)) => extract_function_name_from_list(&func.objname)?,
+                Some(pg_query::NodeEnum::List(list)) => extract_name_from_node_list(&list.items)?,
+                Some(pg_query::NodeEnum::TypeName(type_name)) => extract_name_from_node_list(&type_name.names)?,
+                _ => return Ok(None),
+            };
+            Ok(Some((object_type, name)))
+        }
+        Some(pg_query::NodeEnum::AlterTsConfigurationStmt(alter)) => {
+            Ok(Some((ObjectType::TextSearchConfiguration, extract_name_from_node_list(&alter.cfgname)?)))
+        }
+        Some(pg_query::NodeEnum::AlterTsDictionaryStmt(alter)) => {
+            Ok(Some((ObjectType::TextSearchDictionary, extract_name_from_node_list(&alter.dictname)?)))
+        }
+        // Removing members is a change to make by editing the ADD instead
+        Some(pg_query::NodeEnum::AlterOpFamilyStmt(alter)) if !alter.is_drop => {
+            Ok(Some((ObjectType::OperatorFamily, extract_name_from_node_list(&alter.opfamilyname)?)))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// Extract function signature from CREATE FUNCTION statement
 pub fn extract_function_signature(statement: &str) -> Result<String, Box<dyn std::error::Error>> {
     // Parse the DDL to extract the full function signature
@@ -546,14 +975,53 @@ fn normalize_ddl(ddl: &str) -> String {
 
 /// Parse a COMMENT statement to extract target object and dependencies
 fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Result<(QualifiedIdent, Dependencies), Box<dyn std::error::Error>> {
+    parse_object_target(comment_stmt.objtype(), &comment_stmt.object)
+}
+
+/// Parse a SECURITY LABEL statement to extract target object and dependencies.
+/// The identifier is the comment identifier of the target, prefixed with the
+/// label provider when one is given (e.g. `anon:column:users.email`).
+fn parse_security_label_target(label_stmt: &pg_query::protobuf::SecLabelStmt) -> Result<(QualifiedIdent, Dependencies), Box<dyn std::error::Error>> {
+    let (target_id, dependencies) = parse_object_target(label_stmt.objtype(), &label_stmt.object)?;
+
+    let label_id = if label_stmt.provider.is_empty() {
+        target_id
+    } else {
+        QualifiedIdent::new(None, format!("{}:{}", label_stmt.provider, target_id.name))
+    };
+
+    Ok((label_id, dependencies))
+}
+
+/// Target kinds used as the first segment of comment identifiers
+const COMMENT_TARGET_KINDS: &[&str] = &[
+    "table", "column", "function", "procedure", "operator", "type",
+    "trigger", "domain", "view", "materialized_view", "aggregate",
+];
+
+/// Split a security label identifier into its provider (if any) and the
+/// comment identifier of its target
+pub fn split_security_label_name(label_name: &str) -> (Option<&str>, &str) {
+    match label_name.split_once(':') {
+        Some((first, rest)) if !COMMENT_TARGET_KINDS.contains(&first) => (Some(first), rest),
+        _ => (None, label_name),
+    }
+}
+
+/// Resolve the target of a COMMENT / SECURITY LABEL statement to an identifier
+/// (e.g. `column:users.email`) and the dependencies on the target object
+fn parse_object_target(
+    objtype: pg_query::protobuf::ObjectType,
+    object: &Option<Box<pg_query::protobuf::Node>>,
+) -> Result<(QualifiedIdent, Dependencies), Box<dyn std::error::Error>> {
     use pg_query::protobuf::ObjectType as PgObjectType;
     
     let mut dependencies = Dependencies::default();
     
-    match comment_stmt.objtype() {
+    match objtype {
         PgObjectType::ObjectTable => {
             // COMMENT ON TABLE schema.table_name
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::List(list) = node {
                         let qualified_name = extract_name_from_node_list(&list.items)?;
@@ -570,7 +1038,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectColumn => {
             // COMMENT ON COLUMN schema.table.column OR schema.type.field
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::List(list) = node {
                         let parts = extract_column_parts_from_list(&list.items)?;
@@ -592,7 +1060,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectFunction => {
             // COMMENT ON FUNCTION schema.func_name(args)
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::ObjectWithArgs(func_with_args) = node {
                         let qualified_name = extract_name_from_node_list(&func_with_args.objname)?;
@@ -611,7 +1079,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectProcedure => {
             // COMMENT ON PROCEDURE schema.proc_name(args)
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::ObjectWithArgs(proc_with_args) = node {
                         let qualified_name = extract_name_from_node_list(&proc_with_args.objname)?;
@@ -630,7 +1098,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectOperator => {
             // COMMENT ON OPERATOR schema.operator_name(lefttype, righttype)
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::ObjectWithArgs(op_with_args) = node {
                         let qualified_name = extract_name_from_node_list(&op_with_args.objname)?;
@@ -650,7 +1118,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectType => {
             // COMMENT ON TYPE schema.type_name
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::TypeName(type_name) = node {
                         let qualified_name = extract_name_from_node_list(&type_name.names)?;
@@ -666,7 +1134,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectTrigger => {
             // COMMENT ON TRIGGER trigger_name ON table_name
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::List(list) = node {
                         // For triggers, parse as [table_parts..., trigger_name]
@@ -702,7 +1170,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectDomain => {
             // COMMENT ON DOMAIN schema.domain_name
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::TypeName(type_name) = node {
                         let qualified_name = extract_name_from_node_list(&type_name.names)?;
@@ -718,7 +1186,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectView => {
             // COMMENT ON VIEW schema.view_name
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::List(list) = node {
                         let qualified_name = extract_name_from_node_list(&list.items)?;
@@ -735,7 +1203,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
         }
         PgObjectType::ObjectMatview => {
             // COMMENT ON MATERIALIZED VIEW schema.matview_name
-            if let Some(object) = &comment_stmt.object {
+            if let Some(object) = object {
                 if let Some(node) = &object.node {
                     if let pg_query::NodeEnum::List(list) = node {
                         let qualified_name = extract_name_from_node_list(&list.items)?;
@@ -751,7 +1219,7 @@ fn parse_comment_target(comment_stmt: &pg_query::protobuf::CommentStmt) -> Resul
             }
         }
         _ => {
-            return Err(format!("Unsupported comment target type: {:?}", comment_stmt.objtype()).into());
+            return Err(format!("Unsupported comment target type: {:?}", objtype).into());
         }
     }
     
@@ -1004,11 +1472,12 @@ fn parse_cron_command_dependencies(command: &str) -> Dependencies {
         Ok(deps) => deps,
         Err(e) => {
             // If parsing fails, log a warning and return empty dependencies
-            eprintln!("Warning: Failed to parse cron command '{}': {}", command, e);
+            warn!(command = %command, error = %e, "Failed to parse cron command");
             Dependencies {
                 relations: std::collections::HashSet::new(),
                 functions: std::collections::HashSet::new(),
                 types: std::collections::HashSet::new(),
+                columns: std::collections::HashSet::new(),
             }
         }
     }
@@ -1019,48 +1488,25 @@ fn extract_operator_dependencies(define_stmt: &pg_query::protobuf::DefineStmt, d
     // Parse the definition list to extract operator properties
     for def_elem in &define_stmt.definition {
         if let Some(pg_query::NodeEnum::DefElem(elem)) = &def_elem.node {
+            // Function names are parsed as type names, e.g. `FUNCTION = api.distance`
+            let Some(pg_query::NodeEnum::TypeName(type_name)) = elem.arg.as_ref().and_then(|arg| arg.node.as_ref()) else {
+                continue;
+            };
+            let Ok(name) = extract_name_from_node_list(&type_name.names) else {
+                continue;
+            };
             match elem.defname.as_str() {
-                "procedure" | "function" => {
-                    // Extract the function/procedure dependency
-                    if let Some(arg) = &elem.arg {
-                        if let Some(pg_query::NodeEnum::ObjectWithArgs(func_with_args)) = &arg.node {
-                            let func_name = extract_name_from_node_list(&func_with_args.objname)?;
-                            dependencies.functions.insert(func_name);
-                        }
-                    }
+                "procedure" | "function" | "restrict" | "join" => {
+                    // The implementing function and the selectivity estimators
+                    dependencies.functions.insert(name);
                 }
                 "leftarg" | "rightarg" => {
-                    // Extract type dependencies from operator arguments
-                    if let Some(arg) = &elem.arg {
-                        if let Some(pg_query::NodeEnum::TypeName(type_name)) = &arg.node {
-                            if let Some(type_str) = extract_type_name(type_name) {
-                                // Convert type string to QualifiedIdent
-                                let qualified_type = QualifiedIdent::from_qualified_name(&type_str);
-                                dependencies.types.insert(qualified_type);
-                            }
-                        }
-                    }
-                }
-                "commutator" | "negator" => {
-                    // Extract operator dependencies
-                    if let Some(arg) = &elem.arg {
-                        if let Some(pg_query::NodeEnum::String(s)) = &arg.node {
-                            // Operator references might be schema-qualified
-                            let _op_ref = QualifiedIdent::from_qualified_name(&s.sval);
-                            // Note: We don't add operator dependencies to the dependencies struct
-                            // as it doesn't have an operators field. This could be enhanced later.
-                        }
-                    }
-                }
-                "restrict" | "join" => {
-                    // Extract selectivity function dependencies
-                    if let Some(arg) = &elem.arg {
-                        if let Some(pg_query::NodeEnum::List(list)) = &arg.node {
-                            let func_name = extract_name_from_node_list(&list.items)?;
-                            dependencies.functions.insert(func_name);
-                        }
-                    }
+                    // The argument types, without any array bounds
+                    dependencies.types.insert(name);
                 }
+                // COMMUTATOR and NEGATOR aren't dependencies: PostgreSQL
+                // creates a shell for an operator that doesn't exist yet, and
+                // they often name the operator itself
                 _ => {}
             }
         }
@@ -1139,6 +1585,22 @@ mod tests {
         assert_eq!(signature, "myschema.calculate(pg_catalog.int4, pg_catalog.numeric)");
     }
 
+    #[test]
+    fn test_is_create_or_replace() {
+        assert!(is_create_or_replace("CREATE OR REPLACE VIEW api.v AS SELECT 1"));
+        assert!(is_create_or_replace("CREATE OR REPLACE FUNCTION f() RETURNS int LANGUAGE sql AS $$ SELECT 1 $$"));
+        assert!(!is_create_or_replace("CREATE VIEW api.v AS SELECT 1"));
+        assert!(!is_create_or_replace("CREATE MATERIALIZED VIEW api.mv AS SELECT 1"));
+    }
+
+    #[test]
+    fn test_runs_outside_transaction() {
+        assert!(runs_outside_transaction("CREATE INDEX CONCURRENTLY idx_users_email ON users (email)"));
+        assert!(runs_outside_transaction("-- pgmg:no-transaction\nCREATE INDEX idx_users_email ON users (email)"));
+        assert!(!runs_outside_transaction("CREATE INDEX idx_users_email ON users (email)"));
+        assert!(!runs_outside_transaction("-- pgmg:no-transactions please\nCREATE VIEW v AS SELECT 1"));
+    }
+
     #[test]
     fn test_identify_create_view() {
         let sql = "CREATE VIEW user_stats AS SELECT COUNT(*) FROM users";
@@ -1827,4 +2289,255 @@ $$"#;
         // Should have dependency on the qualified procedure
         assert!(obj.dependencies.functions.contains(&QualifiedIdent::new(Some("jobs".to_string()), "cancel_expired_shipments".to_string())));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_object_type_from_str() {
+        assert_eq!("table".parse::<ObjectType>().unwrap(), ObjectType::Table);
+        assert_eq!("FUNCTION".parse::<ObjectType>().unwrap(), ObjectType::Function);
+        assert_eq!("materialized_view".parse::<ObjectType>().unwrap(), ObjectType::MaterializedView);
+        assert_eq!("materialized-view".parse::<ObjectType>().unwrap(), ObjectType::MaterializedView);
+        assert_eq!("cron job".parse::<ObjectType>().unwrap(), ObjectType::CronJob);
+        assert!("sequence".parse::<ObjectType>().is_err());
+    }
+
+    #[test]
+    fn test_security_label_on_column() {
+        let sql = "SECURITY LABEL FOR anon ON COLUMN public.users.email IS 'MASKED WITH FUNCTION anon.fake_email()'";
+        let result = identify_sql_object(sql).unwrap();
+
+        assert!(result.is_some());
+        let obj = result.unwrap();
+        assert_eq!(obj.object_type, ObjectType::SecurityLabel);
+        assert_eq!(obj.qualified_name.name, "anon:column:public.users.email");
+        assert!(obj.qualified_name.schema.is_none());
+
+        // Should depend on the labeled table
+        assert!(obj.dependencies.relations.contains(&QualifiedIdent::new(Some("public".to_string()), "users".to_string())));
+    }
+
+    #[test]
+    fn test_security_label_without_provider() {
+        let sql = "SECURITY LABEL ON TABLE users IS 'classified'";
+        let result = identify_sql_object(sql).unwrap();
+
+        let obj = result.unwrap();
+        assert_eq!(obj.object_type, ObjectType::SecurityLabel);
+        assert_eq!(obj.qualified_name.name, "table:users");
+    }
+
+    #[test]
+    fn test_split_security_label_name() {
+        assert_eq!(split_security_label_name("anon:column:users.email"), (Some("anon"), "column:users.email"));
+        assert_eq!(split_security_label_name("table:users"), (None, "table:users"));
+    }
+
+    #[test]
+    fn test_create_statistics() {
+        let sql = "CREATE STATISTICS api.orders_customer_region (dependencies, ndistinct) ON customer_id, region FROM public.orders";
+        let obj = identify_sql_object(sql).unwrap().unwrap();
+
+        assert_eq!(obj.object_type, ObjectType::Statistics);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "orders_customer_region".to_string()));
+        assert!(obj.dependencies.relations.contains(&QualifiedIdent::new(Some("public".to_string()), "orders".to_string())));
+
+        // Unnamed statistics objects get a generated name, which can't be tracked
+        assert!(identify_sql_object("CREATE STATISTICS ON customer_id, region FROM orders").is_err());
+        assert_eq!("statistics".parse::<ObjectType>().unwrap(), ObjectType::Statistics);
+    }
+
+    #[test]
+    fn test_create_extension() {
+        let obj = identify_sql_object("CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA extensions VERSION '1.6'").unwrap().unwrap();
+
+        assert_eq!(obj.object_type, ObjectType::Extension);
+        assert_eq!(obj.qualified_name, QualifiedIdent::from_name("pg_trgm".to_string()));
+        assert!(obj.dependencies.functions.is_empty() && obj.dependencies.relations.is_empty());
+        assert_eq!(extract_extension_version(&obj.ddl_statement), Some("1.6".to_string()));
+        assert_eq!(extract_extension_version("CREATE EXTENSION citext"), None);
+        assert_eq!("extension".parse::<ObjectType>().unwrap(), ObjectType::Extension);
+    }
+
+    #[test]
+    fn test_create_publication() {
+        let obj = identify_sql_object("CREATE PUBLICATION analytics FOR TABLE orders, api.users (id, email), TABLES IN SCHEMA billing").unwrap().unwrap();
+
+        assert_eq!(obj.object_type, ObjectType::Publication);
+        assert_eq!(obj.qualified_name, QualifiedIdent::from_name("analytics".to_string()));
+        assert_eq!(obj.dependencies.relations, std::collections::HashSet::from([
+            QualifiedIdent::from_name("orders".to_string()),
+            QualifiedIdent::new(Some("api".to_string()), "users".to_string()),
+        ]));
+        assert_eq!("publication".parse::<ObjectType>().unwrap(), ObjectType::Publication);
+    }
+
+    #[test]
+    fn test_create_collation_and_text_search() {
+        let obj = identify_sql_object("CREATE COLLATION api.natural_sort (provider = icu, locale = 'en-u-kn-true')").unwrap().unwrap();
+        assert_eq!(obj.object_type, ObjectType::Collation);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "natural_sort".to_string()));
+
+        let obj = identify_sql_object("CREATE COLLATION german FROM \"de_DE\"").unwrap().unwrap();
+        assert_eq!(obj.object_type, ObjectType::Collation);
+        assert!(obj.dependencies.types.contains(&QualifiedIdent::from_name("de_DE".to_string())));
+
+        let obj = identify_sql_object("CREATE TEXT SEARCH DICTIONARY api.unaccent_dict (TEMPLATE = unaccent, RULES = 'unaccent')").unwrap().unwrap();
+        assert_eq!(obj.object_type, ObjectType::TextSearchDictionary);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "unaccent_dict".to_string()));
+
+        let obj = identify_sql_object("CREATE TEXT SEARCH CONFIGURATION api.english_unaccent (COPY = pg_catalog.english)").unwrap().unwrap();
+        assert_eq!(obj.object_type, ObjectType::TextSearchConfiguration);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "english_unaccent".to_string()));
+
+        // Mappings are settings of the configuration
+        let (object_type, name) = identify_object_setting(
+            "ALTER TEXT SEARCH CONFIGURATION api.english_unaccent ALTER MAPPING FOR hword, word WITH api.unaccent_dict, english_stem"
+        ).unwrap().unwrap();
+        assert_eq!(object_type, ObjectType::TextSearchConfiguration);
+        assert_eq!(name, QualifiedIdent::new(Some("api".to_string()), "english_unaccent".to_string()));
+
+        assert_eq!("collation".parse::<ObjectType>().unwrap(), ObjectType::Collation);
+        assert_eq!("text_search_configuration".parse::<ObjectType>().unwrap(), ObjectType::TextSearchConfiguration);
+        assert_eq!("text_search_dictionary".parse::<ObjectType>().unwrap(), ObjectType::TextSearchDictionary);
+    }
+
+    #[test]
+    fn test_create_operator_class_and_family() {
+        let obj = identify_sql_object(
+            "CREATE OPERATOR CLASS api.complex_abs_ops DEFAULT FOR TYPE api.complex USING btree FAMILY api.abs_ops AS
+                 OPERATOR 1 api.<# (api.complex, api.complex),
+                 OPERATOR 3 = (api.complex, api.complex),
+                 FUNCTION 1 api.complex_abs_cmp(api.complex, api.complex)"
+        ).unwrap().unwrap();
+
+        assert_eq!(obj.object_type, ObjectType::OperatorClass);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "complex_abs_ops".to_string()));
+        assert!(obj.dependencies.types.contains(&QualifiedIdent::new(Some("api".to_string()), "complex".to_string())));
+        assert!(obj.dependencies.types.contains(&QualifiedIdent::new(Some("api".to_string()), "abs_ops".to_string())));
+        assert!(obj.dependencies.functions.contains(&QualifiedIdent::new(Some("api".to_string()), "complex_abs_cmp".to_string())));
+        assert!(obj.dependencies.functions.contains(&QualifiedIdent::new(Some("api".to_string()), "<#".to_string())));
+
+        let obj = identify_sql_object("CREATE OPERATOR FAMILY api.abs_ops USING btree").unwrap().unwrap();
+        assert_eq!(obj.object_type, ObjectType::OperatorFamily);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "abs_ops".to_string()));
+
+        // Members added to a family are settings of it
+        let (object_type, name) = identify_object_setting(
+            "ALTER OPERATOR FAMILY api.abs_ops USING btree ADD OPERATOR 1 api.<# (api.complex, api.complex)"
+        ).unwrap().unwrap();
+        assert_eq!(object_type, ObjectType::OperatorFamily);
+        assert_eq!(name, QualifiedIdent::new(Some("api".to_string()), "abs_ops".to_string()));
+        assert!(identify_object_setting(
+            "ALTER OPERATOR FAMILY api.abs_ops USING btree DROP OPERATOR 1 (api.complex, api.complex)"
+        ).unwrap().is_none());
+
+        assert_eq!("operator_class".parse::<ObjectType>().unwrap(), ObjectType::OperatorClass);
+        assert_eq!("operator_family".parse::<ObjectType>().unwrap(), ObjectType::OperatorFamily);
+    }
+
+    #[test]
+    fn test_create_operator() {
+        let create = "CREATE OPERATOR api.<-> (
+                LEFTARG = api.complex[], RIGHTARG = api.complex[],
+                FUNCTION = api.complex_distance, RESTRICT = api.distance_sel, COMMUTATOR = <->
+            )";
+        let obj = identify_sql_object(create).unwrap().unwrap();
+
+        assert_eq!(obj.object_type, ObjectType::Operator);
+        assert_eq!(obj.qualified_name, QualifiedIdent::new(Some("api".to_string()), "<->".to_string()));
+        assert!(obj.dependencies.functions.contains(&QualifiedIdent::new(Some("api".to_string()), "complex_distance".to_string())));
+        assert!(obj.dependencies.functions.contains(&QualifiedIdent::new(Some("api".to_string()), "distance_sel".to_string())));
+        assert!(obj.dependencies.types.contains(&QualifiedIdent::new(Some("api".to_string()), "complex".to_string())));
+
+        // Settings after the definition are skipped
+        let ddl = format!("{};\nALTER OPERATOR api.<-> (api.complex[], api.complex[]) OWNER TO api_owner;", create);
+        assert_eq!(extract_operator_signature(&ddl).unwrap(), "api.<->(api.complex[], api.complex[])");
+        assert_eq!(
+            extract_operator_signature("CREATE OPERATOR ! (RIGHTARG = bigint, FUNCTION = factorial)").unwrap(),
+            "!(NONE, pg_catalog.int8)"
+        );
+        assert!(extract_operator_signature("CREATE FUNCTION f() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql").is_err());
+    }
+
+    #[test]
+    fn test_create_cast() {
+        let obj = identify_sql_object(
+            "CREATE CAST (api.money AS numeric) WITH FUNCTION api.money_to_numeric(api.money) AS ASSIGNMENT"
+        ).unwrap().unwrap();
+
+        assert_eq!(obj.object_type, ObjectType::Cast);
+        assert_eq!(obj.qualified_name, QualifiedIdent::from_name("(api.money AS pg_catalog.numeric)".to_string()));
+        assert!(obj.dependencies.types.contains(&QualifiedIdent::new(Some("api".to_string()), "money".to_string())));
+        assert!(obj.dependencies.functions.contains(&QualifiedIdent::new(Some("api".to_string()), "money_to_numeric".to_string())));
+
+        let obj = identify_sql_object("CREATE CAST (api.cents AS api.money) WITHOUT FUNCTION").unwrap().unwrap();
+        assert_eq!(obj.qualified_name.name, "(api.cents AS api.money)");
+        assert!(obj.dependencies.functions.is_empty());
+
+        assert_eq!("cast".parse::<ObjectType>().unwrap(), ObjectType::Cast);
+    }
+
+    #[test]
+    fn test_extract_apply_after_hints() {
+        let content = "-- pgmg:apply-after lookup.sql ../types/status.sql\n\
+                       -- pgmg:apply-afterwards.sql\n\
+                       CREATE FUNCTION f() RETURNS int AS $$ SELECT 1 $$ LANGUAGE sql;\n\
+                       -- pgmg:apply-after helpers.sql";
+        assert_eq!(
+            extract_apply_after_hints(content),
+            vec!["lookup.sql", "../types/status.sql", "helpers.sql"]
+        );
+    }
+
+    #[test]
+    fn test_identify_object_setting() {
+        let (object_type, name) = identify_object_setting("ALTER FUNCTION api.search(text) SET work_mem = '64MB'")
+            .unwrap().unwrap();
+        assert_eq!(object_type, ObjectType::Function);
+        assert_eq!(name, QualifiedIdent::new(Some("api".to_string()), "search".to_string()));
+
+        let (object_type, name) = identify_object_setting("ALTER VIEW user_emails SET (security_barrier = true)")
+            .unwrap().unwrap();
+        assert_eq!(object_type, ObjectType::View);
+        assert_eq!(name.name, "user_emails");
+
+        // Anything other than options is not a setting
+        assert!(identify_object_setting("ALTER VIEW user_emails RENAME TO emails").unwrap().is_none());
+        assert!(identify_object_setting("ALTER TABLE users SET (fillfactor = 70)").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_identify_owner_setting() {
+        let cases = [
+            ("ALTER VIEW api.report OWNER TO report_owner", ObjectType::View, "api.report"),
+            ("ALTER MATERIALIZED VIEW api.totals OWNER TO report_owner", ObjectType::MaterializedView, "api.totals"),
+            ("ALTER FUNCTION api.search(text) OWNER TO api_owner", ObjectType::Function, "api.search"),
+            ("ALTER PROCEDURE api.refresh() OWNER TO api_owner", ObjectType::Procedure, "api.refresh"),
+            ("ALTER TYPE api.status OWNER TO api_owner", ObjectType::Type, "api.status"),
+            ("ALTER DOMAIN email OWNER TO api_owner", ObjectType::Domain, "email"),
+            ("ALTER STATISTICS api.order_stats OWNER TO api_owner", ObjectType::Statistics, "api.order_stats"),
+        ];
+        for (sql, expected_type, expected_name) in cases {
+            let (object_type, name) = identify_object_setting(sql).unwrap().unwrap();
+            assert_eq!(object_type, expected_type, "{}", sql);
+            assert_eq!(name, QualifiedIdent::from_qualified_name(expected_name), "{}", sql);
+        }
+
+        // Owners of objects pgmg doesn't manage in code files are left alone
+        assert!(identify_object_setting("ALTER TABLE users OWNER TO api_owner").unwrap().is_none());
+        assert!(identify_object_setting("ALTER SCHEMA api OWNER TO api_owner").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_settings_change_object_hash() {
+        let mut object = identify_sql_object("CREATE VIEW user_emails AS SELECT email FROM users").unwrap().unwrap();
+        let hash = object.ddl_hash.clone();
+
+        object.add_setting("ALTER VIEW user_emails SET (security_barrier = true)".to_string());
+        assert_ne!(object.ddl_hash, hash);
+        assert_eq!(object.settings.len(), 1);
+        assert_eq!(
+            object.ddl_source(),
+            "CREATE VIEW user_emails AS SELECT email FROM users;\nALTER VIEW user_emails SET (security_barrier = true);"
+        );
+    }
+}
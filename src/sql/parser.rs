@@ -1,20 +1,49 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use pg_query::{NodeEnum, NodeRef};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tracing::warn;
 
 use crate::builtin_catalog::BuiltinCatalog;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct QualifiedIdent {
     pub schema: Option<String>,
     pub name: String,
 }
 
+/// A column of a relation a statement reads, e.g. `orders.status`. The
+/// column is `*` for `SELECT *`, which reads every column.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ColumnReference {
+    pub relation: QualifiedIdent,
+    pub column: String,
+}
+
+impl ColumnReference {
+    pub fn new(relation: QualifiedIdent, column: String) -> Self {
+        Self { relation, column }
+    }
+}
+
+impl fmt::Display for ColumnReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.relation.schema {
+            Some(schema) => write!(f, "{}.{}.{}", schema, self.relation.name, self.column),
+            None => write!(f, "{}.{}", self.relation.name, self.column),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Dependencies {
     pub relations: HashSet<QualifiedIdent>,
     pub functions: HashSet<QualifiedIdent>,
     pub types: HashSet<QualifiedIdent>,
+    /// Columns read by views and SQL functions, where the relation they're
+    /// read from can be told
+    pub columns: HashSet<ColumnReference>,
 }
 
 impl QualifiedIdent {
@@ -27,6 +56,12 @@ impl QualifiedIdent {
     }
     
     pub fn from_qualified_name(qualified_name: &str) -> Self {
+        // A cast is named by its types, e.g. `(api.money AS pg_catalog.int4)`,
+        // and isn't in a schema
+        if qualified_name.starts_with('(') {
+            return Self::from_name(qualified_name.to_string());
+        }
+
         let parts: Vec<&str> = qualified_name.split('.').collect();
         if parts.len() == 2 {
             Self {
@@ -71,6 +106,7 @@ pub fn extract_dependencies_from_parse_result_with_sql(parse_result: &pg_query::
     }
     
     let mut types = HashSet::new();
+    let mut columns = extract_column_references(parse_result);
     
     // Also traverse the entire AST to extract REFERENCES and DEFAULT functions
     for stmt in &parse_result.stmts {
@@ -116,10 +152,11 @@ pub fn extract_dependencies_from_parse_result_with_sql(parse_result: &pg_query::
                                                     relations.extend(body_deps.relations);
                                                     functions.extend(body_deps.functions);
                                                     types.extend(body_deps.types);
+                                                    columns.extend(body_deps.columns);
                                                 }
                                                 Err(e) => {
                                                     // Log the error but don't fail the entire analysis
-                                                    eprintln!("Warning: Failed to parse SQL function statement '{}': {}", trimmed, e);
+                                                    warn!(statement = %trimmed, error = %e, "Failed to parse SQL function statement");
                                                 }
                                             }
                                         }
@@ -127,7 +164,7 @@ pub fn extract_dependencies_from_parse_result_with_sql(parse_result: &pg_query::
                                 }
                                 Err(e) => {
                                     // Log the error but don't fail the entire analysis
-                                    eprintln!("Warning: Failed to split SQL function body: {}", e);
+                                    warn!(error = %e, "Failed to split SQL function body");
                                 }
                             }
                         }
@@ -141,11 +178,11 @@ pub fn extract_dependencies_from_parse_result_with_sql(parse_result: &pg_query::
                                     types.extend(plpgsql_deps.types);
                                 }
                                 Err(e) => {
-                                    eprintln!("Warning: Failed to analyze PL/pgSQL function: {}", e);
+                                    warn!(error = %e, "Failed to analyze PL/pgSQL function");
                                 }
                             }
                         } else {
-                            eprintln!("Warning: PL/pgSQL function body analysis requires original SQL text");
+                            warn!("PL/pgSQL function body analysis requires original SQL text");
                         }
                     }
                 }
@@ -187,6 +224,17 @@ pub fn extract_dependencies_from_parse_result_with_sql(parse_result: &pg_query::
         }
     }
     
+    // Sequences are named by string literals, which the above doesn't see
+    relations.extend(extract_sequence_references(parse_result));
+
+    // Collations and text search configurations are named like types, in
+    // COLLATE clauses and string literals the above doesn't see either
+    types.extend(extract_collation_and_text_search_references(parse_result));
+
+    // The members of operator classes and families, with operators counted as
+    // functions and the family a class is added to as a type
+    extract_operator_class_members(parse_result, &mut functions, &mut types);
+    
     // Extract types from cast expressions at the top level
     let top_level_types = extract_types_from_ast(parse_result)?;
     for typ in top_level_types {
@@ -197,6 +245,7 @@ pub fn extract_dependencies_from_parse_result_with_sql(parse_result: &pg_query::
         relations,
         functions,
         types,
+        columns,
     })
 }
 
@@ -212,9 +261,94 @@ pub fn filter_builtins(deps: Dependencies, catalog: &BuiltinCatalog) -> Dependen
         types: deps.types.into_iter()
             .filter(|typ| !catalog.types.contains(typ))
             .collect(),
+        columns: deps.columns.into_iter()
+            .filter(|column| !catalog.relations.contains(&column.relation))
+            .collect(),
     }
 }
 
+/// The columns a statement reads, with the relation each is read from.
+/// Qualified columns are matched to the relation or alias they name, and
+/// unqualified ones to the only relation read, if there's just one.
+///
+/// Aliases aren't scoped: one used for different relations in different
+/// subqueries counts for each of them.
+fn extract_column_references(parse_result: &pg_query::protobuf::ParseResult) -> HashSet<ColumnReference> {
+    // The object a statement creates is named by a RangeVar too, but isn't read
+    let created: Option<QualifiedIdent> = parse_result.stmts.iter()
+        .find_map(|stmt| match stmt.stmt.as_ref().and_then(|stmt| stmt.node.as_ref()) {
+            Some(NodeEnum::ViewStmt(view)) => view.view.as_ref().map(range_var_name),
+            Some(NodeEnum::CreateTableAsStmt(create)) => create.into.as_ref()
+                .and_then(|into| into.rel.as_ref())
+                .map(range_var_name),
+            _ => None,
+        });
+
+    let mut ctes: HashSet<&str> = HashSet::new();
+    let mut read: Vec<(QualifiedIdent, &str)> = Vec::new();
+    for (node, _, _, _) in parse_result.nodes() {
+        match node {
+            NodeRef::CommonTableExpr(cte) => {
+                ctes.insert(cte.ctename.as_str());
+            }
+            NodeRef::RangeVar(range_var) => {
+                let relation = range_var_name(range_var);
+                if Some(&relation) != created.as_ref() {
+                    let alias = range_var.alias.as_ref().map_or(range_var.relname.as_str(), |alias| alias.aliasname.as_str());
+                    read.push((relation, alias));
+                }
+            }
+            _ => {}
+        }
+    }
+    // CTEs are read by name like relations, but aren't any
+    read.retain(|(relation, _)| relation.schema.is_some() || !ctes.contains(relation.name.as_str()));
+
+    let mut by_alias: HashMap<&str, HashSet<&QualifiedIdent>> = HashMap::new();
+    for (relation, alias) in &read {
+        by_alias.entry(*alias).or_default().insert(relation);
+    }
+    let relations: HashSet<&QualifiedIdent> = read.iter().map(|(relation, _)| relation).collect();
+
+    let mut columns = HashSet::new();
+    for (node, _, _, _) in parse_result.nodes() {
+        let NodeRef::ColumnRef(column_ref) = node else {
+            continue;
+        };
+        // `*` is an A_Star node rather than a string
+        let fields: Vec<&str> = column_ref.fields.iter()
+            .map(|field| match &field.node {
+                Some(NodeEnum::String(s)) => s.sval.as_str(),
+                _ => "*",
+            })
+            .collect();
+
+        match fields.as_slice() {
+            [column] => {
+                if relations.len() == 1 {
+                    columns.extend(relations.iter().map(|relation| ColumnReference::new((*relation).clone(), column.to_string())));
+                }
+            }
+            [qualifier, column] => {
+                for relation in by_alias.get(qualifier).into_iter().flatten() {
+                    columns.insert(ColumnReference::new((*relation).clone(), column.to_string()));
+                }
+            }
+            [schema, table, column] => {
+                let relation = QualifiedIdent::new(Some(schema.to_string()), table.to_string());
+                columns.insert(ColumnReference::new(relation, column.to_string()));
+            }
+            _ => {}
+        }
+    }
+    columns
+}
+
+fn range_var_name(range_var: &pg_query::protobuf::RangeVar) -> QualifiedIdent {
+    let schema = (!range_var.schemaname.is_empty()).then(|| range_var.schemaname.clone());
+    QualifiedIdent::new(schema, range_var.relname.clone())
+}
+
 /// Check if a CREATE FUNCTION statement is using LANGUAGE SQL
 fn is_language_sql_function(create_func: &pg_query::protobuf::CreateFunctionStmt) -> bool {
     for option in &create_func.options {
@@ -459,6 +593,25 @@ fn extract_from_node_with_types(
                 }
             }
         }
+        NodeEnum::IndexStmt(index_stmt) => {
+            if let Some(relation) = &index_stmt.relation {
+                extract_from_node_recursive(&NodeEnum::RangeVar(relation.clone()), relations, functions, types);
+            }
+
+            // Expression columns, e.g. CREATE INDEX ON orders (api.order_key(id))
+            for param in index_stmt.index_params.iter().chain(&index_stmt.index_including_params) {
+                if let Some(NodeEnum::IndexElem(index_elem)) = &param.node {
+                    if let Some(expr) = &index_elem.expr {
+                        extract_from_node_with_types(expr.node.as_ref().unwrap(), relations, functions, types);
+                    }
+                }
+            }
+
+            // Partial index predicate, e.g. WHERE status = 'active'::order_status
+            if let Some(where_clause) = &index_stmt.where_clause {
+                extract_from_node_with_types(where_clause.node.as_ref().unwrap(), relations, functions, types);
+            }
+        }
         _ => {
             // For all other node types, use the original extraction but recurse with type tracking
             extract_from_node_recursive(node, relations, functions, types);
@@ -1046,6 +1199,234 @@ fn extract_type_from_type_name(type_name: &pg_query::protobuf::TypeName) -> Opti
     }
 }
 
+/// Functions taking a sequence as their first argument
+const SEQUENCE_FUNCTIONS: &[&str] = &["nextval", "currval", "setval"];
+
+/// Extract the sequences passed to `nextval('seq'::regclass)` and the like,
+/// in column defaults and queries alike
+fn extract_sequence_references(parse_result: &pg_query::protobuf::ParseResult) -> Vec<QualifiedIdent> {
+    let mut sequences = Vec::new();
+
+    for (node, _, _, _) in parse_result.nodes() {
+        let NodeRef::FuncCall(func_call) = node else {
+            continue;
+        };
+        let names: Vec<&str> = func_call.funcname.iter()
+            .filter_map(|node| match &node.node {
+                Some(NodeEnum::String(s)) => Some(s.sval.as_str()),
+                _ => None,
+            })
+            .collect();
+        let is_sequence_function = match names.as_slice() {
+            [name] | ["pg_catalog", name] => SEQUENCE_FUNCTIONS.contains(name),
+            _ => false,
+        };
+        if !is_sequence_function {
+            continue;
+        }
+
+        // The name may be cast to regclass explicitly or implicitly
+        let mut arg = func_call.args.first().and_then(|arg| arg.node.as_ref());
+        if let Some(NodeEnum::TypeCast(type_cast)) = arg {
+            arg = type_cast.arg.as_ref().and_then(|arg| arg.node.as_ref());
+        }
+        if let Some(NodeEnum::AConst(a_const)) = arg {
+            if let Some(pg_query::protobuf::a_const::Val::Sval(name)) = &a_const.val {
+                if let Some(sequence) = parse_regclass_literal(&name.sval) {
+                    sequences.push(sequence);
+                }
+            }
+        }
+    }
+
+    sequences
+}
+
+/// Functions taking a text search configuration as their first argument, and
+/// how many arguments they take when given one
+const TEXT_SEARCH_FUNCTIONS: &[(&str, usize)] = &[
+    ("to_tsvector", 2),
+    ("to_tsquery", 2),
+    ("plainto_tsquery", 2),
+    ("phraseto_tsquery", 2),
+    ("websearch_to_tsquery", 2),
+    ("json_to_tsvector", 3),
+    ("jsonb_to_tsvector", 3),
+    ("ts_headline", 4),
+];
+
+/// Extract the collations and text search configurations and dictionaries a
+/// statement uses: `COLLATE` clauses and index column collations,
+/// `'english_unaccent'::regconfig` literals, configurations passed to
+/// `to_tsvector('english_unaccent', ...)` and the like, the collation or
+/// configuration one is created `FROM` or `COPY`-ing, and the dictionaries
+/// `ALTER TEXT SEARCH CONFIGURATION ... WITH` maps tokens to
+fn extract_collation_and_text_search_references(parse_result: &pg_query::protobuf::ParseResult) -> Vec<QualifiedIdent> {
+    use pg_query::protobuf::ObjectType as PgObjectType;
+
+    let mut references = Vec::new();
+
+    for node in parse_result.stmts.iter().filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref())) {
+        match node {
+            NodeEnum::DefineStmt(define_stmt) => {
+                let copied_from = match define_stmt.kind() {
+                    PgObjectType::ObjectCollation => "from",
+                    PgObjectType::ObjectTsconfiguration => "copy",
+                    _ => continue,
+                };
+                for def in &define_stmt.definition {
+                    if let Some(NodeEnum::DefElem(def_elem)) = &def.node {
+                        if def_elem.defname == copied_from {
+                            references.extend(def_elem.arg.as_deref().and_then(extract_type_name_from_node));
+                        }
+                    }
+                }
+            }
+            NodeEnum::AlterTsConfigurationStmt(alter) => {
+                references.extend(alter.dicts.iter().filter_map(extract_type_name_from_node));
+            }
+            _ => {}
+        }
+    }
+
+    for (node, _, _, _) in parse_result.nodes() {
+        match node {
+            NodeRef::CollateClause(collate) => references.extend(name_from_strings(&collate.collname)),
+            NodeRef::ColumnDef(column_def) => {
+                if let Some(collate) = &column_def.coll_clause {
+                    references.extend(name_from_strings(&collate.collname));
+                }
+            }
+            NodeRef::CreateDomainStmt(domain_stmt) => {
+                if let Some(collate) = &domain_stmt.coll_clause {
+                    references.extend(name_from_strings(&collate.collname));
+                }
+            }
+            NodeRef::IndexElem(index_elem) => references.extend(name_from_strings(&index_elem.collation)),
+            NodeRef::TypeCast(type_cast) => {
+                let is_text_search_cast = type_cast.type_name.as_ref()
+                    .and_then(extract_type_from_type_name)
+                    .is_some_and(|type_name| matches!(type_name.name.as_str(), "regconfig" | "regdictionary"));
+                if is_text_search_cast {
+                    let arg = type_cast.arg.as_ref().and_then(|arg| arg.node.as_ref());
+                    references.extend(string_literal(arg).and_then(parse_regclass_literal));
+                }
+            }
+            NodeRef::FuncCall(func_call) => {
+                let names: Vec<&str> = func_call.funcname.iter()
+                    .filter_map(|node| match &node.node {
+                        Some(NodeEnum::String(s)) => Some(s.sval.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                let takes_configuration = match names.as_slice() {
+                    [name] | ["pg_catalog", name] => TEXT_SEARCH_FUNCTIONS.iter()
+                        .any(|(function, args)| function == name && *args == func_call.args.len()),
+                    _ => false,
+                };
+                // A configuration cast to regconfig is picked up with the cast
+                if takes_configuration {
+                    let arg = func_call.args.first().and_then(|arg| arg.node.as_ref());
+                    references.extend(string_literal(arg).and_then(parse_regclass_literal));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    references
+}
+
+/// Extract what `CREATE OPERATOR CLASS` and `ALTER OPERATOR FAMILY ... ADD`
+/// statements are made of: the operators and support functions they list, the
+/// types these take, the indexed and storage types, and the families a class
+/// is added to or orders by
+fn extract_operator_class_members(
+    parse_result: &pg_query::protobuf::ParseResult,
+    functions: &mut HashSet<QualifiedIdent>,
+    types: &mut HashSet<QualifiedIdent>,
+) {
+    for node in parse_result.stmts.iter().filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref())) {
+        let items = match node {
+            NodeEnum::CreateOpClassStmt(opclass_stmt) => {
+                types.extend(opclass_stmt.datatype.as_ref().and_then(extract_type_from_type_name));
+                types.extend(name_from_strings(&opclass_stmt.opfamilyname));
+                &opclass_stmt.items
+            }
+            NodeEnum::AlterOpFamilyStmt(alter) if !alter.is_drop => &alter.items,
+            _ => continue,
+        };
+
+        for item in items {
+            let Some(NodeEnum::CreateOpClassItem(item)) = &item.node else {
+                continue;
+            };
+            if let Some(member) = &item.name {
+                functions.extend(name_from_strings(&member.objname));
+                types.extend(member.objargs.iter().filter_map(extract_type_name_from_node));
+            }
+            types.extend(item.class_args.iter().filter_map(extract_type_name_from_node));
+            types.extend(name_from_strings(&item.order_family));
+            types.extend(item.storedtype.as_ref().and_then(extract_type_from_type_name));
+        }
+    }
+}
+
+/// A name given as a list of string nodes, e.g. a collation's
+fn name_from_strings(names: &[pg_query::protobuf::Node]) -> Option<QualifiedIdent> {
+    let parts: Vec<&str> = names.iter()
+        .filter_map(|node| match &node.node {
+            Some(NodeEnum::String(s)) => Some(s.sval.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    match parts.as_slice() {
+        [name] => Some(QualifiedIdent::from_name(name.to_string())),
+        [schema, name] => Some(QualifiedIdent::new(Some(schema.to_string()), name.to_string())),
+        _ => None,
+    }
+}
+
+fn string_literal(node: Option<&NodeEnum>) -> Option<&str> {
+    match node {
+        Some(NodeEnum::AConst(a_const)) => match &a_const.val {
+            Some(pg_query::protobuf::a_const::Val::Sval(s)) => Some(s.sval.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Parse a relation name the way a regclass literal is read: unquoted parts
+/// are folded to lowercase, quoted parts are kept as written
+fn parse_regclass_literal(literal: &str) -> Option<QualifiedIdent> {
+    let mut parts = vec![String::new()];
+    let mut chars = literal.trim().chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                parts.last_mut()?.push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(String::new()),
+            c if quoted => parts.last_mut()?.push(c),
+            c => parts.last_mut()?.extend(c.to_lowercase()),
+        }
+    }
+
+    if quoted || parts.iter().any(String::is_empty) {
+        return None;
+    }
+    match parts.len() {
+        1 => Some(QualifiedIdent::from_name(parts.remove(0))),
+        2 => Some(QualifiedIdent::new(Some(parts.remove(0)), parts.remove(0))),
+        _ => None,
+    }
+}
 
 pub fn analyze_plpgsql(sql: &str) -> Result<Dependencies, Box<dyn std::error::Error>> {
     let json_result = pg_query::parse_plpgsql(sql)?;
@@ -1091,6 +1472,7 @@ pub fn analyze_plpgsql(sql: &str) -> Result<Dependencies, Box<dyn std::error::Er
         relations: all_relations,
         functions: all_functions,
         types: all_types,
+        columns: HashSet::new(),
     })
 }
 
@@ -1146,8 +1528,8 @@ fn extract_dependencies_from_plpgsql_function(
             }
         } else {
             // It's a PL/pgSQL expression (assignment, condition, etc.)
-            // Extract function calls and type casts from the expression
-            extract_dependencies_from_plpgsql_expression(&expr, functions, types);
+            // Extract function calls, sequences and type casts from the expression
+            extract_dependencies_from_plpgsql_expression(&expr, relations, functions, types);
         }
     }
     
@@ -1185,6 +1567,7 @@ fn extract_plpgsql_expressions_from_json(value: &Value) -> Vec<String> {
 
 fn extract_dependencies_from_plpgsql_expression(
     expr: &str,
+    relations: &mut HashSet<QualifiedIdent>,
     functions: &mut HashSet<QualifiedIdent>,
     types: &mut HashSet<QualifiedIdent>
 ) {
@@ -1209,6 +1592,7 @@ fn extract_dependencies_from_plpgsql_expression(
                 extract_function_from_func_call(func_call, functions);
             }
         }
+        relations.extend(extract_sequence_references(&parse_result.protobuf));
         
         // Extract types using the existing function
         if let Ok(found_types) = extract_types_from_ast(&parse_result.protobuf) {
@@ -2015,4 +2399,147 @@ create type api.order_shipment as (
             result.functions
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_partial_index_predicate_types() {
+        let sql = "create index orders_active_idx on orders (created_at) where status = 'active'::order_status";
+        let result = analyze_statement(sql).unwrap();
+
+        assert!(result.relations.contains(&QualifiedIdent::from_name("orders".to_string())));
+        assert!(
+            result.types.contains(&QualifiedIdent::from_name("order_status".to_string())),
+            "Expected order_status, types were: {:?}",
+            result.types
+        );
+    }
+
+    #[test]
+    fn test_index_expression_and_predicate_functions() {
+        let sql = "create index orders_key_idx on api.orders (api.order_key(id, region::api.region_code)) \
+                   where api.is_open(status)";
+        let result = analyze_statement(sql).unwrap();
+
+        let order_key = QualifiedIdent::new(Some("api".to_string()), "order_key".to_string());
+        let is_open = QualifiedIdent::new(Some("api".to_string()), "is_open".to_string());
+        let region_code = QualifiedIdent::new(Some("api".to_string()), "region_code".to_string());
+        assert!(result.functions.contains(&order_key), "functions were: {:?}", result.functions);
+        assert!(result.functions.contains(&is_open), "functions were: {:?}", result.functions);
+        assert!(result.types.contains(&region_code), "types were: {:?}", result.types);
+    }
+
+    #[test]
+    fn test_sequence_references() {
+        let sql = "create table api.orders (id bigint default nextval('api.orders_id_seq'::regclass), \
+                   ref text default 'R' || currval('\"OrderRefs\"'))";
+        let result = analyze_statement(sql).unwrap();
+
+        let orders_id_seq = QualifiedIdent::new(Some("api".to_string()), "orders_id_seq".to_string());
+        let order_refs = QualifiedIdent::from_name("OrderRefs".to_string());
+        assert!(result.relations.contains(&orders_id_seq), "relations were: {:?}", result.relations);
+        assert!(result.relations.contains(&order_refs), "relations were: {:?}", result.relations);
+
+        let sql = r#"
+            CREATE FUNCTION api.next_order_id() RETURNS bigint AS $$
+            DECLARE
+                v_id bigint;
+            BEGIN
+                v_id := nextval('Api.Order_Ids');
+                PERFORM setval('api.invoice_ids', v_id);
+                RETURN v_id;
+            END;
+            $$ LANGUAGE plpgsql;
+        "#;
+        let result = analyze_statement(sql).unwrap();
+        let order_ids = QualifiedIdent::new(Some("api".to_string()), "order_ids".to_string());
+        let invoice_ids = QualifiedIdent::new(Some("api".to_string()), "invoice_ids".to_string());
+        assert!(result.relations.contains(&order_ids), "relations were: {:?}", result.relations);
+        assert!(result.relations.contains(&invoice_ids), "relations were: {:?}", result.relations);
+    }
+
+    #[test]
+    fn test_collation_and_text_search_references() {
+        let sql = "CREATE TABLE api.articles (
+                       title text COLLATE api.natural_sort,
+                       body text,
+                       search tsvector GENERATED ALWAYS AS (to_tsvector('api.english_unaccent', body)) STORED
+                   )";
+        let result = analyze_statement(sql).unwrap();
+        let natural_sort = QualifiedIdent::new(Some("api".to_string()), "natural_sort".to_string());
+        let english_unaccent = QualifiedIdent::new(Some("api".to_string()), "english_unaccent".to_string());
+        assert!(result.types.contains(&natural_sort), "types were: {:?}", result.types);
+        assert!(result.types.contains(&english_unaccent), "types were: {:?}", result.types);
+
+        let sql = "CREATE INDEX articles_search ON api.articles \
+                   USING gin (to_tsvector('Api.English_Unaccent'::regconfig, body))";
+        let result = analyze_statement(sql).unwrap();
+        assert!(result.types.contains(&english_unaccent), "types were: {:?}", result.types);
+
+        let sql = "CREATE INDEX articles_title ON api.articles (title COLLATE \"de-x-icu\")";
+        let result = analyze_statement(sql).unwrap();
+        assert!(result.types.contains(&QualifiedIdent::from_name("de-x-icu".to_string())), "types were: {:?}", result.types);
+
+        // The configuration copied, and the dictionaries mapped to
+        let result = analyze_statement("CREATE TEXT SEARCH CONFIGURATION api.english_unaccent (COPY = api.english_base)").unwrap();
+        assert!(result.types.contains(&QualifiedIdent::new(Some("api".to_string()), "english_base".to_string())));
+
+        let result = analyze_statement(
+            "ALTER TEXT SEARCH CONFIGURATION api.english_unaccent ALTER MAPPING FOR hword, word WITH api.unaccent_dict, english_stem"
+        ).unwrap();
+        assert!(result.types.contains(&QualifiedIdent::new(Some("api".to_string()), "unaccent_dict".to_string())));
+        assert!(result.types.contains(&QualifiedIdent::from_name("english_stem".to_string())));
+
+        // ts_headline(document, query, options) doesn't take a configuration
+        let result = analyze_statement("SELECT ts_headline('some text', q, 'MaxWords=10') FROM queries").unwrap();
+        assert!(!result.types.contains(&QualifiedIdent::from_name("some text".to_string())));
+    }
+
+    #[test]
+    fn test_parse_regclass_literal() {
+        assert_eq!(
+            parse_regclass_literal("Api.\"Order \"\"Ids\"\"\""),
+            Some(QualifiedIdent::new(Some("api".to_string()), "Order \"Ids\"".to_string()))
+        );
+        assert_eq!(parse_regclass_literal("a.b.c"), None);
+        assert_eq!(parse_regclass_literal("\"unterminated"), None);
+    }
+
+    #[test]
+    fn test_column_references() {
+        let columns = |sql: &str| {
+            let mut columns: Vec<String> = analyze_statement(sql).unwrap().columns.iter().map(|column| column.to_string()).collect();
+            columns.sort();
+            columns
+        };
+
+        assert_eq!(
+            columns("CREATE VIEW api.open_orders AS SELECT id, total FROM sales.orders WHERE status = 'open'"),
+            vec!["sales.orders.id", "sales.orders.status", "sales.orders.total"]
+        );
+        // Qualified by alias; which relation `note` is read from can't be told
+        assert_eq!(
+            columns("CREATE VIEW order_users AS SELECT o.id, u.*, note FROM orders o JOIN api.users u ON u.id = o.user_id"),
+            vec!["api.users.*", "api.users.id", "orders.id", "orders.user_id"]
+        );
+        // CTEs aren't relations
+        assert_eq!(
+            columns("CREATE VIEW recent AS WITH r AS (SELECT id FROM events) SELECT id FROM r"),
+            vec!["events.id"]
+        );
+        assert_eq!(
+            columns("CREATE FUNCTION total(o_id int) RETURNS numeric LANGUAGE sql AS $$ SELECT sum(amount) FROM api.lines WHERE order_id = o_id $$"),
+            vec!["api.lines.amount", "api.lines.o_id", "api.lines.order_id"]
+        );
+    }
+
+    #[test]
+    fn test_from_qualified_name_keeps_cast_names_whole() {
+        assert_eq!(
+            QualifiedIdent::from_qualified_name("api.orders"),
+            QualifiedIdent::new(Some("api".to_string()), "orders".to_string())
+        );
+        assert_eq!(
+            QualifiedIdent::from_qualified_name("(mytype AS pg_catalog.int4)"),
+            QualifiedIdent::from_name("(mytype AS pg_catalog.int4)".to_string())
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Partitions declared in code with `CREATE TABLE ... PARTITION OF parent`.
+//! A partition is created after its parent, and dropping the parent drops it,
+//! so it is recreated whenever the parent is. Removing one from code detaches
+//! it from the parent by default, keeping its rows.
+//!
+//! Tables partitioned by time can instead have their partitions maintained by
+//! `pgmg partitions`: one per interval is created ahead of time and those
+//! older than the retention are detached or dropped. The work is a query
+//! computing the statements to run, so the same query can be run by pg_cron
+//! inside the database.
+
+use std::fmt;
+use std::str::FromStr;
+use crate::sql::QualifiedIdent;
+use crate::sql::quote::{quote_literal, quote_qualified_ident};
+
+/// Name of the pg_cron job running partition maintenance
+pub const MAINTENANCE_JOB_NAME: &str = "pgmg_partitions";
+
+/// What apply does with a partition whose file was removed:
+/// `partition_removal` in pgmg.toml
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PartitionRemoval {
+    /// `ALTER TABLE parent DETACH PARTITION`, leaving it as a table of its
+    /// own that pgmg no longer tracks
+    #[default]
+    Detach,
+    /// `DROP TABLE`, along with its rows
+    Drop,
+}
+
+impl FromStr for PartitionRemoval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "detach" => Ok(PartitionRemoval::Detach),
+            "drop" => Ok(PartitionRemoval::Drop),
+            _ => Err(format!("Invalid partition removal '{}'. Expected one of: detach, drop", s)),
+        }
+    }
+}
+
+impl fmt::Display for PartitionRemoval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PartitionRemoval::Detach => "detach",
+            PartitionRemoval::Drop => "drop",
+        })
+    }
+}
+
+/// The time range each maintained partition covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionInterval {
+    Day,
+    /// ISO weeks, starting on Monday
+    Week,
+    Month,
+    Year,
+}
+
+impl PartitionInterval {
+    /// The `date_trunc` field a period starts on
+    fn field(&self) -> &'static str {
+        match self {
+            PartitionInterval::Day => "day",
+            PartitionInterval::Week => "week",
+            PartitionInterval::Month => "month",
+            PartitionInterval::Year => "year",
+        }
+    }
+
+    /// `to_char` pattern for the suffix of partition names, e.g.
+    /// `events_p2026_10` for October 2026
+    fn name_pattern(&self) -> &'static str {
+        match self {
+            PartitionInterval::Day => "YYYY_MM_DD",
+            PartitionInterval::Week => "IYYY\"w\"IW",
+            PartitionInterval::Month => "YYYY_MM",
+            PartitionInterval::Year => "YYYY",
+        }
+    }
+}
+
+impl FromStr for PartitionInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "day" | "daily" => Ok(PartitionInterval::Day),
+            "week" | "weekly" => Ok(PartitionInterval::Week),
+            "month" | "monthly" => Ok(PartitionInterval::Month),
+            "year" | "yearly" => Ok(PartitionInterval::Year),
+            _ => Err(format!("Invalid partition interval '{}'. Expected one of: day, week, month, year", s)),
+        }
+    }
+}
+
+/// What `pgmg partitions` maintains: `[partitions]` in pgmg.toml
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartitionMaintenance {
+    /// pg_cron schedule the maintenance also runs on inside the database
+    pub schedule: Option<String>,
+    pub tables: Vec<MaintainedTable>,
+}
+
+/// A table range-partitioned on one time column, whose partitions
+/// `pgmg partitions` maintains: a `[[partitions.tables]]` section in pgmg.toml
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintainedTable {
+    pub table: QualifiedIdent,
+    pub interval: PartitionInterval,
+    /// Partitions kept created ahead of the current one
+    pub premake: u32,
+    /// Partitions kept before the current one; older ones are removed. All
+    /// are kept if `None`.
+    pub retain: Option<u32>,
+    /// What happens to partitions older than `retain`
+    pub expired: PartitionRemoval,
+}
+
+impl MaintainedTable {
+    /// Query returning the `action` (create, detach or drop), `partition` and
+    /// `statement` of each change the table's partitions need, in the order
+    /// they're to be made.
+    ///
+    /// Periods start where `date_trunc` puts them in the session's time zone.
+    /// A period is created unless an existing partition overlaps it, so
+    /// partitions made by hand are left alone. Partitions are only removed
+    /// once the whole of their range is older than the retention, and never
+    /// when they're declared in the code directory.
+    pub fn maintenance_query(&self) -> String {
+        let field = quote_literal(self.interval.field());
+        let step = quote_literal(&format!("1 {}", self.interval.field()));
+        let name_pattern = quote_literal(self.interval.name_pattern());
+
+        let mut query = format!(
+            r#"WITH parent AS (
+    SELECT c.oid, n.nspname AS schema_name, c.relname AS table_name,
+           date_trunc({field}, now()) AS current_period, interval {step} AS step
+    FROM pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.oid = {table}::regclass
+),
+existing AS (
+    SELECT n.nspname AS schema_name, c.relname AS table_name,
+           bounds[1]::timestamptz AS lower_bound, bounds[2]::timestamptz AS upper_bound
+    FROM parent
+    JOIN pg_inherits i ON i.inhparent = parent.oid
+    JOIN pg_class c ON c.oid = i.inhrelid
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    CROSS JOIN LATERAL regexp_match(pg_get_expr(c.relpartbound, c.oid), $re$^FOR VALUES FROM \('([^']*)'\) TO \('([^']*)'\)$$re$) AS bounds
+    WHERE bounds IS NOT NULL
+)
+SELECT 'create' AS action,
+       format('%I.%I', parent.schema_name, parent.table_name || '_p' || to_char(period, {name_pattern})) AS partition,
+       format('CREATE TABLE %I.%I PARTITION OF %I.%I FOR VALUES FROM (%L) TO (%L)',
+              parent.schema_name, parent.table_name || '_p' || to_char(period, {name_pattern}),
+              parent.schema_name, parent.table_name,
+              to_char(period, 'YYYY-MM-DD'), to_char(period + parent.step, 'YYYY-MM-DD')) AS statement
+FROM parent
+CROSS JOIN generate_series(parent.current_period, parent.current_period + parent.step * {premake}, parent.step) AS period
+WHERE NOT EXISTS (
+    SELECT 1 FROM existing
+    WHERE existing.lower_bound < period + parent.step AND existing.upper_bound > period
+)"#,
+            table = quote_literal(&quote_qualified_ident(&self.table)),
+            premake = self.premake,
+        );
+
+        if let Some(retain) = self.retain {
+            let statement = match self.expired {
+                PartitionRemoval::Detach => "format('ALTER TABLE %I.%I DETACH PARTITION %I.%I', parent.schema_name, parent.table_name, existing.schema_name, existing.table_name)",
+                PartitionRemoval::Drop => "format('DROP TABLE %I.%I', existing.schema_name, existing.table_name)",
+            };
+            query.push_str(&format!(
+                r#"
+UNION ALL
+SELECT {action},
+       format('%I.%I', existing.schema_name, existing.table_name),
+       {statement}
+FROM parent
+JOIN existing ON existing.upper_bound <= parent.current_period - parent.step * {retain}
+WHERE NOT EXISTS (
+    SELECT 1 FROM pgmg.pgmg_state state
+    WHERE state.object_type = 'table'
+      AND state.object_name IN (existing.schema_name || '.' || existing.table_name, CASE existing.schema_name WHEN 'public' THEN existing.table_name END)
+)"#,
+                action = quote_literal(&self.expired.to_string()),
+            ));
+        }
+
+        query.push_str("\nORDER BY 1, 2");
+        query
+    }
+}
+
+/// The command the pg_cron job runs: a `DO` block making the changes each
+/// table's maintenance query returns
+pub fn maintenance_command(tables: &[MaintainedTable]) -> String {
+    let loops: Vec<String> = tables.iter()
+        .map(|table| format!(
+            "    FOR maintenance IN\n{}\n    LOOP\n        EXECUTE maintenance.statement;\n    END LOOP;",
+            table.maintenance_query()
+        ))
+        .collect();
+
+    format!(
+        "DO $pgmg$\nDECLARE\n    maintenance record;\nBEGIN\n{}\nEND\n$pgmg$",
+        loops.join("\n")
+    )
+}
+
+/// The parent of the partition a `CREATE TABLE ... PARTITION OF` statement
+/// creates, or `None` for other statements, including tables inheriting
+/// from another with `INHERITS`
+pub fn partition_parent(statement: &str) -> Option<QualifiedIdent> {
+    let parsed = pg_query::parse(statement).ok()?;
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|stmt| stmt.node.as_ref()))
+        .find_map(|node| match node {
+            pg_query::NodeEnum::CreateStmt(create_stmt) if create_stmt.partbound.is_some() => {
+                match create_stmt.inh_relations.first()?.node.as_ref()? {
+                    pg_query::NodeEnum::RangeVar(parent) => {
+                        let schema = Some(parent.schemaname.clone()).filter(|schema| !schema.is_empty());
+                        Some(QualifiedIdent::new(schema, parent.relname.clone()))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_parent() {
+        assert_eq!(
+            partition_parent("CREATE TABLE events_2026_10 PARTITION OF api.events FOR VALUES FROM ('2026-10-01') TO ('2026-11-01')"),
+            Some(QualifiedIdent::new(Some("api".to_string()), "events".to_string()))
+        );
+        assert_eq!(
+            partition_parent("CREATE TABLE events_default PARTITION OF events DEFAULT"),
+            Some(QualifiedIdent::from_name("events".to_string()))
+        );
+        assert_eq!(partition_parent("CREATE TABLE events (id int, at timestamptz) PARTITION BY RANGE (at)"), None);
+        assert_eq!(partition_parent("CREATE TABLE archived_events () INHERITS (events)"), None);
+    }
+
+    #[test]
+    fn test_maintenance_query() {
+        let mut table = MaintainedTable {
+            table: QualifiedIdent::new(Some("api".to_string()), "Events".to_string()),
+            interval: "monthly".parse().unwrap(),
+            premake: 3,
+            retain: None,
+            expired: PartitionRemoval::Detach,
+        };
+
+        let query = table.maintenance_query();
+        assert!(query.contains(r#"WHERE c.oid = 'api."Events"'::regclass"#));
+        assert!(query.contains("date_trunc('month', now())"));
+        assert!(query.contains("parent.step * 3"));
+        assert!(!query.contains("UNION ALL"));
+
+        table.retain = Some(12);
+        table.expired = PartitionRemoval::Drop;
+        let query = table.maintenance_query();
+        assert!(query.contains("SELECT 'drop'"));
+        assert!(query.contains("parent.step * 12"));
+
+        let command = maintenance_command(&[table]);
+        assert!(command.starts_with("DO $pgmg$"));
+        assert!(command.contains("EXECUTE maintenance.statement"));
+
+        assert!("hourly".parse::<PartitionInterval>().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Changes to publications defined in code. Dropping a publication breaks the
+//! subscriptions reading from it, so a change to the tables or schemas it
+//! publishes is made with `ALTER PUBLICATION ... ADD/DROP` instead of
+//! recreating it.
+
+use pg_query::NodeEnum;
+use pg_query::protobuf::{AlterPublicationAction, AlterPublicationStmt, CreatePublicationStmt, Node, PublicationObjSpecType};
+
+/// A table or schema a publication publishes
+struct PublishedObject {
+    /// Identifies the table or schema regardless of column list or row filter
+    key: String,
+    spec: Node,
+    /// The statement adding it to the publication
+    add: String,
+}
+
+/// Build the `ALTER PUBLICATION` statements that turn the publication created
+/// by `applied` into the one created by `current`. Tables and schemas no
+/// longer published are dropped before new ones are added, and a table whose
+/// column list or row filter changed is dropped and added again.
+///
+/// Returns None if the change can't be made this way and the publication has
+/// to be recreated: either statement publishes `FOR ALL TABLES`, or the
+/// `WITH` options differ.
+pub fn publication_changes(applied: &str, current: &str) -> Option<Vec<String>> {
+    let applied = parse_publication(applied)?;
+    let current = parse_publication(current)?;
+
+    if applied.pubname != current.pubname || applied.for_all_tables || current.for_all_tables {
+        return None;
+    }
+    if publication_options(&applied)? != publication_options(&current)? {
+        return None;
+    }
+
+    let applied_objects = published_objects(&applied)?;
+    let current_objects = published_objects(&current)?;
+
+    let mut drops = Vec::new();
+    let mut adds = Vec::new();
+    for object in &applied_objects {
+        match current_objects.iter().find(|published| published.key == object.key) {
+            Some(published) if published.add == object.add => {}
+            Some(published) => {
+                drops.push(drop_statement(&applied.pubname, &object.spec)?);
+                adds.push(published.add.clone());
+            }
+            None => drops.push(drop_statement(&applied.pubname, &object.spec)?),
+        }
+    }
+    for object in &current_objects {
+        if !applied_objects.iter().any(|published| published.key == object.key) {
+            adds.push(object.add.clone());
+        }
+    }
+
+    drops.extend(adds);
+    Some(drops)
+}
+
+fn parse_publication(statement: &str) -> Option<CreatePublicationStmt> {
+    let parsed = pg_query::parse(statement).ok()?;
+
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .find_map(|node| match node {
+            NodeEnum::CreatePublicationStmt(publication) => Some(CreatePublicationStmt::clone(publication)),
+            _ => None,
+        })
+}
+
+/// The statement with nothing published, leaving its name and `WITH` options
+fn publication_options(publication: &CreatePublicationStmt) -> Option<String> {
+    let options = CreatePublicationStmt {
+        pubobjects: Vec::new(),
+        ..publication.clone()
+    };
+    NodeEnum::CreatePublicationStmt(options.into()).deparse().ok()
+}
+
+fn published_objects(publication: &CreatePublicationStmt) -> Option<Vec<PublishedObject>> {
+    publication.pubobjects.iter()
+        .map(|node| {
+            let Some(NodeEnum::PublicationObjSpec(spec)) = &node.node else {
+                return None;
+            };
+            let key = match spec.pubobjtype() {
+                PublicationObjSpecType::PublicationobjTable => {
+                    let relation = spec.pubtable.as_ref()?.relation.as_ref()?;
+                    if relation.schemaname.is_empty() {
+                        format!("table {}", relation.relname)
+                    } else {
+                        format!("table {}.{}", relation.schemaname, relation.relname)
+                    }
+                }
+                PublicationObjSpecType::PublicationobjTablesInSchema => format!("schema {}", spec.name),
+                PublicationObjSpecType::PublicationobjTablesInCurSchema => "current schema".to_string(),
+                _ => return None,
+            };
+            let add = alter(&publication.pubname, AlterPublicationAction::ApAddObjects, node)?;
+            Some(PublishedObject { key, spec: node.clone(), add })
+        })
+        .collect()
+}
+
+fn alter(pubname: &str, action: AlterPublicationAction, spec: &Node) -> Option<String> {
+    let statement = AlterPublicationStmt {
+        pubname: pubname.to_string(),
+        pubobjects: vec![spec.clone()],
+        action: action as i32,
+        ..AlterPublicationStmt::default()
+    };
+    NodeEnum::AlterPublicationStmt(statement.into()).deparse().ok()
+}
+
+/// `DROP TABLE` doesn't take the column list or row filter the table was
+/// added with
+fn drop_statement(pubname: &str, spec: &Node) -> Option<String> {
+    let mut spec = spec.clone();
+    if let Some(NodeEnum::PublicationObjSpec(object)) = &mut spec.node {
+        if let Some(table) = object.pubtable.as_mut() {
+            table.columns.clear();
+            table.where_clause = None;
+        }
+    }
+    alter(pubname, AlterPublicationAction::ApDropObjects, &spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publication_table_changes() {
+        let statements = publication_changes(
+            "CREATE PUBLICATION analytics FOR TABLE orders, api.users, events",
+            "CREATE PUBLICATION analytics FOR TABLE orders, events, api.invoices",
+        ).unwrap();
+
+        assert_eq!(statements, vec![
+            "ALTER PUBLICATION analytics DROP TABLE api.users",
+            "ALTER PUBLICATION analytics ADD TABLE api.invoices",
+        ]);
+    }
+
+    #[test]
+    fn test_publication_schema_and_filter_changes() {
+        let statements = publication_changes(
+            "CREATE PUBLICATION analytics FOR TABLE orders WHERE (total > 0), TABLES IN SCHEMA reporting",
+            "CREATE PUBLICATION analytics FOR TABLE orders (id, total), TABLES IN SCHEMA billing",
+        ).unwrap();
+
+        assert_eq!(statements, vec![
+            "ALTER PUBLICATION analytics DROP TABLE orders",
+            "ALTER PUBLICATION analytics DROP TABLES IN SCHEMA reporting",
+            "ALTER PUBLICATION analytics ADD TABLE orders (id, total)",
+            "ALTER PUBLICATION analytics ADD TABLES IN SCHEMA billing",
+        ]);
+    }
+
+    #[test]
+    fn test_publication_unchanged_tables() {
+        let statements = publication_changes(
+            "CREATE PUBLICATION analytics FOR TABLE orders, events",
+            "CREATE PUBLICATION analytics\n    FOR TABLE orders,\n              events",
+        ).unwrap();
+
+        assert!(statements.is_empty());
+    }
+
+    #[test]
+    fn test_publication_recreated_for_other_changes() {
+        assert_eq!(publication_changes(
+            "CREATE PUBLICATION analytics FOR TABLE orders",
+            "CREATE PUBLICATION analytics FOR TABLE orders WITH (publish = 'insert')",
+        ), None);
+        assert_eq!(publication_changes(
+            "CREATE PUBLICATION analytics FOR TABLE orders",
+            "CREATE PUBLICATION analytics FOR ALL TABLES",
+        ), None);
+    }
+}
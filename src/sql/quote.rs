@@ -0,0 +1,169 @@
+//! Quoting of identifiers and literals in generated SQL.
+//!
+//! Every statement pgmg builds itself (drops, comment and label removal,
+//! generated migrations, seed COPY commands) goes through these helpers, so
+//! names with uppercase letters, spaces, quotes or reserved words survive.
+
+use crate::sql::QualifiedIdent;
+
+/// Keywords that can't be used as a bare identifier everywhere: PostgreSQL's
+/// reserved, type/function name and column name keywords. `quote_ident()` in
+/// PostgreSQL quotes the same set.
+const NON_UNRESERVED_KEYWORDS: &[&str] = &[
+    // Reserved
+    "all", "analyse", "analyze", "and", "any", "array", "as", "asc", "asymmetric",
+    "both", "case", "cast", "check", "collate", "column", "constraint", "create",
+    "current_catalog", "current_date", "current_role", "current_time",
+    "current_timestamp", "current_user", "default", "deferrable", "desc",
+    "distinct", "do", "else", "end", "except", "false", "fetch", "for", "foreign",
+    "from", "grant", "group", "having", "in", "initially", "intersect", "into",
+    "lateral", "leading", "limit", "localtime", "localtimestamp", "not", "null",
+    "offset", "on", "only", "or", "order", "placing", "primary", "references",
+    "returning", "select", "session_user", "some", "symmetric", "system_user",
+    "table", "then", "to", "trailing", "true", "union", "unique", "user", "using",
+    "variadic", "when", "where", "window", "with",
+    // Type or function names
+    "authorization", "binary", "collation", "concurrently", "cross",
+    "current_schema", "freeze", "full", "ilike", "inner", "is", "isnull", "join",
+    "left", "like", "natural", "notnull", "outer", "overlaps", "right", "similar",
+    "tablesample", "verbose",
+    // Column names
+    "between", "bigint", "bit", "boolean", "char", "character", "coalesce", "dec",
+    "decimal", "exists", "extract", "float", "greatest", "grouping", "inout",
+    "int", "integer", "interval", "json", "json_array", "json_arrayagg",
+    "json_exists", "json_object", "json_objectagg", "json_query", "json_scalar",
+    "json_serialize", "json_table", "json_value", "least", "merge_action",
+    "national", "nchar", "none", "normalize", "nullif", "numeric", "out",
+    "overlay", "position", "precision", "real", "row", "setof", "smallint",
+    "substring", "time", "timestamp", "treat", "trim", "values", "varchar",
+    "xmlattributes", "xmlconcat", "xmlelement", "xmlexists", "xmlforest",
+    "xmlnamespaces", "xmlparse", "xmlpi", "xmlroot", "xmlserialize", "xmltable",
+];
+
+/// Quote an identifier unless it can be written bare: a lowercase name that
+/// isn't a keyword
+pub fn quote_ident(ident: &str) -> String {
+    let is_plain = ident.chars().next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && ident.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !NON_UNRESERVED_KEYWORDS.contains(&ident);
+
+    if is_plain {
+        ident.to_string()
+    } else {
+        format!("\"{}\"", ident.replace('"', "\"\""))
+    }
+}
+
+/// Quote a name and its optional schema, e.g. `api."Order"`
+pub fn quote_qualified(schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", quote_ident(schema), quote_ident(name)),
+        None => quote_ident(name),
+    }
+}
+
+/// Quote a parsed name, e.g. `api."Order"`
+pub fn quote_qualified_ident(ident: &QualifiedIdent) -> String {
+    quote_qualified(ident.schema.as_deref(), &ident.name)
+}
+
+/// Quote each part of a dotted name as stored in the state tables and in
+/// comment identifiers, e.g. `api.Order.id` -> `api."Order".id`. Names that
+/// themselves contain a dot can't be told apart from qualified ones here.
+pub fn quote_dotted(name: &str) -> String {
+    name.split('.').map(quote_ident).collect::<Vec<_>>().join(".")
+}
+
+/// Quote the name in a routine signature and keep its argument list as is,
+/// e.g. `api.Refresh(integer)` -> `api."Refresh"(integer)`
+pub fn quote_signature(signature: &str) -> String {
+    match signature.split_once('(') {
+        Some((name, args)) => format!("{}({}", quote_dotted(name), args),
+        None => quote_dotted(signature),
+    }
+}
+
+/// Quote a string literal, e.g. `it's` -> `'it''s'`
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identifiers that need care: case, keywords, spaces, quotes, dots,
+    /// unicode and leading digits
+    fn odd_identifiers() -> Vec<String> {
+        let mut identifiers: Vec<String> = [
+            "orders", "Orders", "ORDERS", "order items", "order\"items", "\"quoted\"",
+            "order.items", "1st_order", "_private", "café", "naïve Table", "tab\tname",
+            "$price", "a-b", "it's", " leading", "trailing ", "Δ", "_1",
+        ].iter().map(|s| s.to_string()).collect();
+
+        for keyword in NON_UNRESERVED_KEYWORDS {
+            identifiers.push(keyword.to_string());
+            identifiers.push(keyword.to_uppercase());
+        }
+        identifiers
+    }
+
+    /// The relation name PostgreSQL's parser sees in `SELECT * FROM <sql>`
+    fn parsed_relation(sql: &str) -> (Option<String>, String) {
+        let parsed = pg_query::parse(&format!("SELECT * FROM {}", sql))
+            .unwrap_or_else(|e| panic!("{} does not parse: {}", sql, e));
+        let stmt = parsed.protobuf.stmts[0].stmt.as_ref().unwrap();
+        let Some(pg_query::NodeEnum::SelectStmt(select)) = &stmt.node else { panic!("not a select") };
+        let Some(pg_query::NodeEnum::RangeVar(range_var)) = &select.from_clause[0].node else { panic!("not a relation") };
+
+        let schema = Some(range_var.schemaname.clone()).filter(|schema| !schema.is_empty());
+        (schema, range_var.relname.clone())
+    }
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("orders"), "orders");
+        assert_eq!(quote_ident("order_items2"), "order_items2");
+        assert_eq!(quote_ident("Order"), "\"Order\"");
+        assert_eq!(quote_ident("order"), "\"order\"");
+        assert_eq!(quote_ident("say \"hi\""), "\"say \"\"hi\"\"\"");
+        // Unreserved keywords can be used bare
+        assert_eq!(quote_ident("name"), "name");
+    }
+
+    #[test]
+    fn test_quoted_identifiers_round_trip() {
+        for ident in odd_identifiers() {
+            assert_eq!(parsed_relation(&quote_ident(&ident)), (None, ident.clone()), "{:?}", ident);
+            assert_eq!(
+                parsed_relation(&quote_qualified(Some(&ident), &ident)),
+                (Some(ident.clone()), ident.clone()),
+                "{:?}", ident
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_dotted() {
+        assert_eq!(quote_dotted("api.Order.id"), "api.\"Order\".id");
+        assert_eq!(quote_dotted("users"), "users");
+        assert_eq!(quote_signature("api.Refresh(integer, text)"), "api.\"Refresh\"(integer, text)");
+        assert_eq!(quote_signature("jobs.cancel_expired"), "jobs.cancel_expired");
+        assert_eq!(quote_qualified_ident(&QualifiedIdent::new(Some("select".to_string()), "User".to_string())), "\"select\".\"User\"");
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        for value in ["nightly", "it's", "''", "back\\slash", "semi'; DROP TABLE users; --"] {
+            let parsed = pg_query::parse(&format!("SELECT {}", quote_literal(value))).unwrap();
+            assert_eq!(parsed.protobuf.stmts.len(), 1);
+
+            let stmt = parsed.protobuf.stmts[0].stmt.as_ref().unwrap();
+            let Some(pg_query::NodeEnum::SelectStmt(select)) = &stmt.node else { panic!("not a select") };
+            let Some(pg_query::NodeEnum::ResTarget(target)) = &select.target_list[0].node else { panic!("no target") };
+            let Some(pg_query::NodeEnum::AConst(constant)) = target.val.as_ref().and_then(|val| val.node.as_ref()) else { panic!("not a constant") };
+            let Some(pg_query::protobuf::a_const::Val::Sval(string)) = &constant.val else { panic!("not a string") };
+            assert_eq!(string.sval, value);
+        }
+    }
+}
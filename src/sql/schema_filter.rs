@@ -0,0 +1,145 @@
+//! Limiting pgmg to some schemas with `managed_schemas` and `ignored_schemas`
+//! in pgmg.toml, so objects in a schema another tool manages are never
+//! created, recreated or dropped, even when pgmg has tracked them before.
+
+use crate::sql::{ObjectType, QualifiedIdent};
+use crate::sql::objects::parse_sql_object;
+
+/// The schemas whose objects plan and apply may change
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaFilter {
+    /// Only objects in these schemas are managed; all schemas if empty
+    pub managed: Vec<String>,
+    /// Objects in these schemas are never managed, even if listed in `managed`
+    pub ignored: Vec<String>,
+}
+
+impl SchemaFilter {
+    pub fn new(managed: Vec<String>, ignored: Vec<String>) -> Self {
+        Self { managed, ignored }
+    }
+
+    /// Whether the filter leaves every schema managed
+    pub fn is_empty(&self) -> bool {
+        self.managed.is_empty() && self.ignored.is_empty()
+    }
+
+    pub fn includes_schema(&self, schema: &str) -> bool {
+        (self.managed.is_empty() || self.managed.iter().any(|managed| managed == schema))
+            && !self.ignored.iter().any(|ignored| ignored == schema)
+    }
+
+    /// Whether an object is managed. `ddl` is needed to tell the schema of
+    /// triggers, indexes, comments and security labels; without it they are
+    /// managed, as are objects that aren't in a schema.
+    pub fn includes(&self, object_type: &ObjectType, name: &QualifiedIdent, ddl: Option<&str>) -> bool {
+        object_schema(object_type, name, ddl).map_or(true, |schema| self.includes_schema(&schema))
+    }
+}
+
+/// The schema an object belongs to: the one it's created in, the table's for
+/// triggers and indexes, and the target's for comments and security labels.
+/// Unqualified names are taken to be in public.
+///
+/// None for extensions, publications, casts and cron jobs, which aren't in a schema,
+/// and for objects whose schema can't be told without their DDL.
+pub fn object_schema(object_type: &ObjectType, name: &QualifiedIdent, ddl: Option<&str>) -> Option<String> {
+    let schema_of = |ident: &QualifiedIdent| ident.schema.clone().unwrap_or_else(|| "public".to_string());
+
+    match object_type {
+        ObjectType::Extension | ObjectType::Publication | ObjectType::Cast | ObjectType::CronJob => None,
+        ObjectType::Trigger | ObjectType::Index | ObjectType::Comment | ObjectType::SecurityLabel => {
+            let parsed = parse_sql_object(ddl?).ok()??;
+            let target = match object_type {
+                ObjectType::Trigger => parsed.trigger_table.clone(),
+                ObjectType::Index => index_table(&parsed.parsed),
+                _ => parsed.dependencies.relations.iter()
+                    .chain(&parsed.dependencies.functions)
+                    .chain(&parsed.dependencies.types)
+                    .next()
+                    .cloned(),
+            };
+            target.map(|table| schema_of(&table))
+        }
+        _ => Some(schema_of(name)),
+    }
+}
+
+fn index_table(parsed: &pg_query::ParseResult) -> Option<QualifiedIdent> {
+    parsed.protobuf.stmts.iter()
+        .filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref()))
+        .find_map(|node| match node {
+            pg_query::NodeEnum::IndexStmt(index_stmt) => {
+                let relation = index_stmt.relation.as_ref()?;
+                let schema = Some(relation.schemaname.clone()).filter(|schema| !schema.is_empty());
+                Some(QualifiedIdent::new(schema, relation.relname.clone()))
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(managed: &[&str], ignored: &[&str]) -> SchemaFilter {
+        SchemaFilter::new(
+            managed.iter().map(|schema| schema.to_string()).collect(),
+            ignored.iter().map(|schema| schema.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_includes_schema() {
+        assert!(filter(&[], &[]).includes_schema("analytics"));
+
+        let managed = filter(&["api", "internal"], &[]);
+        assert!(managed.includes_schema("api"));
+        assert!(!managed.includes_schema("analytics"));
+
+        let ignored = filter(&[], &["analytics"]);
+        assert!(ignored.includes_schema("api"));
+        assert!(!ignored.includes_schema("analytics"));
+
+        // Ignoring wins
+        assert!(!filter(&["api"], &["api"]).includes_schema("api"));
+    }
+
+    #[test]
+    fn test_object_schema() {
+        let name = |name: &str| QualifiedIdent::from_qualified_name(name);
+
+        assert_eq!(object_schema(&ObjectType::View, &name("analytics.daily"), None), Some("analytics".to_string()));
+        assert_eq!(object_schema(&ObjectType::Function, &name("now_utc"), None), Some("public".to_string()));
+        assert_eq!(object_schema(&ObjectType::Extension, &name("pgcrypto"), None), None);
+
+        assert_eq!(object_schema(
+            &ObjectType::Trigger,
+            &name("audit_events"),
+            Some("CREATE TRIGGER audit_events AFTER INSERT ON analytics.events FOR EACH ROW EXECUTE FUNCTION api.audit()"),
+        ), Some("analytics".to_string()));
+        assert_eq!(object_schema(
+            &ObjectType::Index,
+            &name("events_created_at_idx"),
+            Some("CREATE INDEX events_created_at_idx ON analytics.events (created_at)"),
+        ), Some("analytics".to_string()));
+        assert_eq!(object_schema(
+            &ObjectType::Comment,
+            &name("table:analytics.events"),
+            Some("COMMENT ON TABLE analytics.events IS 'Raw events'"),
+        ), Some("analytics".to_string()));
+
+        // Without the DDL the table isn't known
+        assert_eq!(object_schema(&ObjectType::Index, &name("events_created_at_idx"), None), None);
+    }
+
+    #[test]
+    fn test_includes_object() {
+        let filter = filter(&[], &["analytics"]);
+        let name = QualifiedIdent::from_qualified_name("events_created_at_idx");
+
+        assert!(!filter.includes(&ObjectType::Index, &name, Some("CREATE INDEX events_created_at_idx ON analytics.events (created_at)")));
+        assert!(filter.includes(&ObjectType::Index, &name, Some("CREATE INDEX events_created_at_idx ON api.events (created_at)")));
+        assert!(filter.includes(&ObjectType::Publication, &QualifiedIdent::from_name("analytics".to_string()), None));
+    }
+}
@@ -0,0 +1,313 @@
+//! Rules for statements that apply leaves out instead of running: pg_cron in
+//! test mode, plpgsql_check on AWS RDS, and anything configured under
+//! `[[skip_rules]]` in pgmg.toml.
+//!
+//! Statements are matched on their parsed AST rather than their text, so a
+//! comment or string literal that merely mentions an extension is not skipped.
+
+use std::collections::{BTreeMap, HashSet};
+use crate::sql::ObjectType;
+use crate::sql::objects::identify_sql_object;
+use crate::sql::parser::extract_dependencies_from_parse_result_with_sql;
+use pg_query::NodeEnum;
+
+/// When a skip rule is in effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkipWhen {
+    #[default]
+    Always,
+    /// Only for `pgmg test` and other test-mode applies
+    TestMode,
+    /// Only when the database is on AWS RDS
+    Rds,
+}
+
+impl std::str::FromStr for SkipWhen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "always" => Ok(SkipWhen::Always),
+            "test" => Ok(SkipWhen::TestMode),
+            "rds" => Ok(SkipWhen::Rds),
+            _ => Err(format!("Unknown skip rule condition '{}' (expected always, test or rds)", s)),
+        }
+    }
+}
+
+/// Statements matching any of the listed extensions, schemas or object types
+/// are skipped
+#[derive(Debug, Clone, Default)]
+pub struct SkipRule {
+    /// Why the statements are skipped, shown in apply results
+    pub reason: String,
+    pub when: SkipWhen,
+    /// Extensions whose CREATE/ALTER/DROP/COMMENT statements are skipped, along
+    /// with calls to functions named after them (e.g. `plpgsql_check_function`)
+    pub extensions: Vec<String>,
+    /// Schemas whose objects are skipped when created or referenced
+    pub schemas: Vec<String>,
+    pub object_types: Vec<ObjectType>,
+}
+
+impl SkipRule {
+    fn matches(&self, targets: &StatementTargets) -> bool {
+        let touches_extension = |extension: &String| {
+            targets.extensions.contains(extension) ||
+            targets.functions.iter().any(|function| {
+                function == extension ||
+                function.strip_prefix(extension.as_str()).is_some_and(|rest| rest.starts_with('_'))
+            })
+        };
+
+        self.extensions.iter().any(touches_extension) ||
+        self.schemas.iter().any(|schema| targets.schemas.contains(schema)) ||
+        targets.object_type.as_ref().is_some_and(|object_type| self.object_types.contains(object_type))
+    }
+}
+
+/// The skip rules in effect for one apply
+#[derive(Debug, Clone, Default)]
+pub struct SkipRules {
+    rules: Vec<SkipRule>,
+}
+
+impl SkipRules {
+    /// The built-in rules plus `configured`, keeping only those that apply in
+    /// test mode and/or on RDS as given
+    pub fn for_environment(configured: Vec<SkipRule>, test_mode: bool, is_rds: bool) -> Self {
+        Self::for_profile(configured, test_mode, is_rds, &BTreeMap::new())
+    }
+
+    /// Like `for_environment`, with the extensions an environment profile says
+    /// are installed or missing. A missing extension's statements are always
+    /// skipped; the built-in rule for an installed one is dropped.
+    pub fn for_profile(configured: Vec<SkipRule>, test_mode: bool, is_rds: bool, extensions: &BTreeMap<String, bool>) -> Self {
+        let builtin = builtin_rules();
+        let mut profile_rules = Vec::new();
+
+        for (extension, &installed) in extensions {
+            if installed {
+                continue;
+            }
+            // The built-in rule also covers the extension's schemas, e.g. cron.schedule()
+            let rule = builtin.iter()
+                .find(|rule| rule.extensions.contains(extension))
+                .cloned()
+                .unwrap_or_else(|| SkipRule {
+                    extensions: vec![extension.clone()],
+                    ..Default::default()
+                });
+            profile_rules.push(SkipRule {
+                reason: format!("{} is not installed in this environment", extension),
+                when: SkipWhen::Always,
+                ..rule
+            });
+        }
+
+        let builtin = builtin.into_iter()
+            .filter(|rule| !rule.extensions.iter().any(|extension| extensions.contains_key(extension)));
+
+        let rules = profile_rules.into_iter()
+            .chain(builtin)
+            .chain(configured)
+            .filter(|rule| match rule.when {
+                SkipWhen::Always => true,
+                SkipWhen::TestMode => test_mode,
+                SkipWhen::Rds => is_rds,
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule that skips `sql`, if any. Statements that fail to parse
+    /// are never skipped, so the error surfaces when they are run.
+    pub fn matching(&self, sql: &str) -> Option<&SkipRule> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let targets = StatementTargets::from_sql(sql)?;
+        self.rules.iter().find(|rule| rule.matches(&targets))
+    }
+}
+
+fn builtin_rules() -> Vec<SkipRule> {
+    vec![
+        SkipRule {
+            reason: "pg_cron is not available in test mode".to_string(),
+            when: SkipWhen::TestMode,
+            extensions: vec!["pg_cron".to_string()],
+            schemas: vec!["cron".to_string()],
+            object_types: vec![ObjectType::CronJob],
+        },
+        SkipRule {
+            reason: "plpgsql_check is not available on AWS RDS".to_string(),
+            when: SkipWhen::Rds,
+            extensions: vec!["plpgsql_check".to_string()],
+            ..Default::default()
+        },
+    ]
+}
+
+/// What a statement touches, as far as skip rules are concerned
+#[derive(Debug, Default)]
+struct StatementTargets {
+    extensions: HashSet<String>,
+    schemas: HashSet<String>,
+    /// Unqualified names of the functions called
+    functions: HashSet<String>,
+    object_type: Option<ObjectType>,
+}
+
+impl StatementTargets {
+    fn from_sql(sql: &str) -> Option<Self> {
+        use pg_query::protobuf::ObjectType as PgObjectType;
+
+        let parsed = pg_query::parse(sql).ok()?;
+        let mut targets = Self::default();
+
+        for node in parsed.protobuf.stmts.iter().filter_map(|stmt| stmt.stmt.as_ref().and_then(|s| s.node.as_ref())) {
+            match node {
+                NodeEnum::CreateExtensionStmt(stmt) => {
+                    targets.extensions.insert(stmt.extname.clone());
+                }
+                NodeEnum::AlterExtensionStmt(stmt) => {
+                    targets.extensions.insert(stmt.extname.clone());
+                }
+                NodeEnum::AlterExtensionContentsStmt(stmt) => {
+                    targets.extensions.insert(stmt.extname.clone());
+                }
+                NodeEnum::DropStmt(stmt) if stmt.remove_type() == PgObjectType::ObjectExtension => {
+                    targets.extensions.extend(stmt.objects.iter().filter_map(string_value));
+                }
+                NodeEnum::DropStmt(stmt) if stmt.remove_type() == PgObjectType::ObjectSchema => {
+                    targets.schemas.extend(stmt.objects.iter().filter_map(string_value));
+                }
+                NodeEnum::CommentStmt(stmt) if stmt.objtype() == PgObjectType::ObjectExtension => {
+                    targets.extensions.extend(stmt.object.as_deref().and_then(string_value));
+                }
+                NodeEnum::CreateSchemaStmt(stmt) => {
+                    targets.schemas.insert(stmt.schemaname.clone());
+                }
+                _ => {}
+            }
+        }
+
+        if let Ok(dependencies) = extract_dependencies_from_parse_result_with_sql(&parsed.protobuf, Some(sql)) {
+            for ident in dependencies.relations.iter().chain(&dependencies.types) {
+                targets.schemas.extend(ident.schema.clone());
+            }
+            for ident in &dependencies.functions {
+                targets.schemas.extend(ident.schema.clone());
+                targets.functions.insert(ident.name.clone());
+            }
+        }
+
+        if let Ok(Some(object)) = identify_sql_object(sql) {
+            targets.schemas.extend(object.qualified_name.schema.clone());
+            targets.object_type = Some(object.object_type);
+        }
+
+        Some(targets)
+    }
+}
+
+fn string_value(node: &pg_query::protobuf::Node) -> Option<String> {
+    match &node.node {
+        Some(NodeEnum::String(s)) => Some(s.sval.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason<'a>(rules: &'a SkipRules, sql: &str) -> Option<&'a str> {
+        rules.matching(sql).map(|rule| rule.reason.as_str())
+    }
+
+    #[test]
+    fn test_builtin_pg_cron_rule() {
+        let rules = SkipRules::for_environment(vec![], true, false);
+
+        assert!(reason(&rules, "CREATE EXTENSION IF NOT EXISTS pg_cron").is_some());
+        assert!(reason(&rules, "COMMENT ON EXTENSION pg_cron IS 'Job scheduler'").is_some());
+        assert!(reason(&rules, "SELECT cron.schedule('vacuum', '0 3 * * *', 'VACUUM')").is_some());
+        assert!(reason(&rules, "SELECT cron.unschedule('vacuum')").is_some());
+
+        // Mentioning pg_cron is not using it
+        assert!(reason(&rules, "COMMENT ON TABLE jobs IS 'Cleaned up nightly by pg_cron'").is_none());
+        assert!(reason(&rules, "-- scheduled with cron.schedule in production\nCREATE TABLE jobs (id integer)").is_none());
+
+        // Only in test mode
+        let rules = SkipRules::for_environment(vec![], false, false);
+        assert!(rules.is_empty());
+        assert!(reason(&rules, "CREATE EXTENSION pg_cron").is_none());
+    }
+
+    #[test]
+    fn test_builtin_plpgsql_check_rule() {
+        let rules = SkipRules::for_environment(vec![], false, true);
+
+        assert_eq!(reason(&rules, "CREATE EXTENSION plpgsql_check"), Some("plpgsql_check is not available on AWS RDS"));
+        assert!(reason(&rules, "SELECT * FROM plpgsql_check_function_tb('process_order(integer)')").is_some());
+        assert!(reason(&rules, "SELECT cron.schedule('vacuum', '0 3 * * *', 'VACUUM')").is_none());
+        assert!(reason(&rules, "CREATE TABLE plpgsql_check_results (id integer)").is_none());
+    }
+
+    #[test]
+    fn test_configured_rules() {
+        let configured = vec![
+            SkipRule {
+                reason: "reporting is refreshed separately".to_string(),
+                schemas: vec!["reporting".to_string()],
+                ..Default::default()
+            },
+            SkipRule {
+                reason: "no materialized views in CI".to_string(),
+                when: SkipWhen::TestMode,
+                object_types: vec![ObjectType::MaterializedView],
+                ..Default::default()
+            },
+        ];
+        let rules = SkipRules::for_environment(configured.clone(), false, false);
+
+        assert!(reason(&rules, "CREATE VIEW reporting.daily_sales AS SELECT 1").is_some());
+        assert!(reason(&rules, "CREATE VIEW api.sales AS SELECT * FROM reporting.daily_sales").is_some());
+        assert!(reason(&rules, "CREATE MATERIALIZED VIEW sales_totals AS SELECT 1").is_none());
+
+        let rules = SkipRules::for_environment(configured, true, false);
+        assert_eq!(reason(&rules, "CREATE MATERIALIZED VIEW sales_totals AS SELECT 1"), Some("no materialized views in CI"));
+    }
+
+    #[test]
+    fn test_unparseable_statements_are_not_skipped() {
+        let rules = SkipRules::for_environment(vec![], true, true);
+        assert!(reason(&rules, "CREATE EXTENSION pg_cron WITH (").is_none());
+    }
+
+    #[test]
+    fn test_profile_extension_availability() {
+        let extensions = BTreeMap::from([
+            ("pg_cron".to_string(), true),
+            ("plpgsql_check".to_string(), false),
+            ("postgis".to_string(), false),
+        ]);
+
+        // pg_cron is installed, so it runs even in test mode
+        let rules = SkipRules::for_profile(vec![], true, false, &extensions);
+        assert!(reason(&rules, "SELECT cron.schedule('vacuum', '0 3 * * *', 'VACUUM')").is_none());
+
+        // plpgsql_check is missing, so it's skipped off RDS too
+        assert_eq!(reason(&rules, "CREATE EXTENSION plpgsql_check"), Some("plpgsql_check is not installed in this environment"));
+        assert!(reason(&rules, "CREATE EXTENSION postgis").is_some());
+        assert!(reason(&rules, "CREATE TABLE places (id integer)").is_none());
+    }
+}
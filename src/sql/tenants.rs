@@ -0,0 +1,84 @@
+//! Tenant-per-schema templating: code files that use a placeholder schema
+//! such as `__tenant__` are applied once for every tenant schema, with the
+//! placeholder replaced by the tenant's schema. Each tenant's copy of an
+//! object is named after its schema, so it has its own state row and is
+//! planned, applied and dropped on its own.
+
+use crate::sql::quote::quote_ident;
+
+pub const DEFAULT_TENANT_PLACEHOLDER: &str = "__tenant__";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantTemplate {
+    /// Replaced wherever it appears in a file, including in function bodies
+    /// and string literals
+    pub placeholder: String,
+    pub schemas: Vec<String>,
+    /// Returns more tenant schemas in its first column. Plan runs it and
+    /// adds the schemas to `schemas`.
+    pub query: Option<String>,
+}
+
+impl TenantTemplate {
+    /// Whether a file is templated
+    pub fn applies_to(&self, content: &str) -> bool {
+        !self.placeholder.is_empty() && content.contains(&self.placeholder)
+    }
+
+    /// A templated file's content once for each tenant schema, or the
+    /// content as it is if it doesn't use the placeholder
+    pub fn expand(&self, content: &str) -> Vec<String> {
+        if !self.applies_to(content) {
+            return vec![content.to_string()];
+        }
+        self.schemas.iter()
+            .map(|schema| content.replace(&self.placeholder, &quote_ident(schema)))
+            .collect()
+    }
+
+    /// Add schemas returned by the query, leaving out ones already listed
+    pub fn add_schemas(&mut self, schemas: impl IntoIterator<Item = String>) {
+        for schema in schemas {
+            if !self.schemas.contains(&schema) {
+                self.schemas.push(schema);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(schemas: &[&str]) -> TenantTemplate {
+        TenantTemplate {
+            placeholder: DEFAULT_TENANT_PLACEHOLDER.to_string(),
+            schemas: schemas.iter().map(|schema| schema.to_string()).collect(),
+            query: None,
+        }
+    }
+
+    #[test]
+    fn test_expand_templated_file() {
+        let content = "CREATE VIEW __tenant__.open_orders AS SELECT * FROM __tenant__.orders WHERE NOT closed;";
+
+        assert_eq!(template(&["acme", "Globex"]).expand(content), vec![
+            "CREATE VIEW acme.open_orders AS SELECT * FROM acme.orders WHERE NOT closed;",
+            "CREATE VIEW \"Globex\".open_orders AS SELECT * FROM \"Globex\".orders WHERE NOT closed;",
+        ]);
+        assert!(template(&[]).expand(content).is_empty());
+    }
+
+    #[test]
+    fn test_untemplated_file_is_kept() {
+        let content = "CREATE VIEW api.orders AS SELECT 1 AS id;";
+        assert_eq!(template(&["acme", "globex"]).expand(content), vec![content.to_string()]);
+    }
+
+    #[test]
+    fn test_add_schemas() {
+        let mut tenants = template(&["acme"]);
+        tenants.add_schemas(vec!["globex".to_string(), "acme".to_string()]);
+        assert_eq!(tenants.schemas, vec!["acme", "globex"]);
+    }
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
-use crate::sql::{splitter::split_sql_file, parser::analyze_statement, Dependencies, ObjectType};
+use crate::sql::{splitter::split_sql_file, parser::analyze_statement, Dependencies, ObjectType, QualifiedIdent};
 use crate::analysis::graph::ObjectRef;
 use crate::builtin_catalog::BuiltinCatalog;
+use tracing::warn;
 
 /// Represents a test file and its dependencies
 #[derive(Debug, Clone)]
@@ -35,14 +36,29 @@ impl TestDependencyMap {
         let mut affected_tests = std::collections::HashSet::new();
         
         for obj in objects {
-            if let Some(tests) = self.object_to_tests.get(obj) {
-                for test_path in tests {
-                    affected_tests.insert(test_path.clone());
+            // `users` and `public.users` name the same object
+            let alias_schema = match obj.qualified_name.schema.as_deref() {
+                None => Some(Some("public".to_string())),
+                Some("public") => Some(None),
+                Some(_) => None,
+            };
+            let alias = alias_schema.map(|schema| ObjectRef {
+                object_type: obj.object_type.clone(),
+                qualified_name: QualifiedIdent::new(schema, obj.qualified_name.name.clone()),
+            });
+            
+            for obj in std::iter::once(obj).chain(alias.as_ref()) {
+                if let Some(tests) = self.object_to_tests.get(obj) {
+                    for test_path in tests {
+                        affected_tests.insert(test_path.clone());
+                    }
                 }
             }
         }
         
-        affected_tests.into_iter().collect()
+        let mut affected_tests: Vec<PathBuf> = affected_tests.into_iter().collect();
+        affected_tests.sort();
+        affected_tests
     }
     
     /// Get dependencies for a specific test file
@@ -88,7 +104,7 @@ pub async fn analyze_test_file(
             }
             Err(e) => {
                 // Log warning but continue - some pgTAP functions might not parse correctly
-                eprintln!("Warning: Failed to analyze statement in test {}: {}", path.display(), e);
+                warn!(file = %path.display(), error = %e, "Failed to analyze statement in test");
             }
         }
     }
@@ -156,7 +172,7 @@ async fn scan_directory_recursive(
                 if file_name.ends_with(".test.sql") {
                     match analyze_test_file(&path, builtin_catalog).await {
                         Ok(test_file) => test_files.push(test_file),
-                        Err(e) => eprintln!("Warning: Failed to analyze test file {}: {}", path.display(), e),
+                        Err(e) => warn!(file = %path.display(), error = %e, "Failed to analyze test file"),
                     }
                 }
             }
@@ -252,7 +268,6 @@ pub fn build_test_dependency_map(tests: Vec<TestFile>) -> TestDependencyMap {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sql::QualifiedIdent;
     use tempfile::tempdir;
     
     #[tokio::test]
@@ -329,4 +344,25 @@ ROLLBACK;
         assert!(file_names.contains(&"products.test.sql".to_string()));
         assert!(file_names.contains(&"api.test.sql".to_string()));
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_find_tests_matches_public_schema() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("users.test.sql"), "BEGIN; SELECT * FROM users; ROLLBACK;").unwrap();
+        fs::write(temp_dir.path().join("orders.test.sql"), "BEGIN; SELECT * FROM public.orders; ROLLBACK;").unwrap();
+        fs::write(temp_dir.path().join("api.test.sql"), "BEGIN; SELECT api.get_user(1); ROLLBACK;").unwrap();
+        
+        let map = build_test_dependency_map(scan_test_files(temp_dir.path(), &BuiltinCatalog::new()).await.unwrap());
+        let find = |object_type: ObjectType, schema: Option<&str>, name: &str| {
+            map.find_tests_for_objects(&[ObjectRef {
+                object_type,
+                qualified_name: QualifiedIdent::new(schema.map(str::to_string), name.to_string()),
+            }])
+        };
+        
+        assert_eq!(find(ObjectType::Table, Some("public"), "users"), vec![temp_dir.path().join("users.test.sql")]);
+        assert_eq!(find(ObjectType::View, None, "orders"), vec![temp_dir.path().join("orders.test.sql")]);
+        assert_eq!(find(ObjectType::Function, Some("api"), "get_user"), vec![temp_dir.path().join("api.test.sql")]);
+        assert!(find(ObjectType::Function, None, "get_user").is_empty());
+    }
+}
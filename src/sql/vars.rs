@@ -0,0 +1,157 @@
+//! `${NAME}` variables in code files and migrations, for values that differ
+//! between environments such as foreign server hosts and role names. Values
+//! come from `[vars]` in pgmg.toml, the environment profile's `vars`, or else
+//! an environment variable of the same name.
+//!
+//! - `${NAME}` is replaced by the value as it is
+//! - `${'NAME'}` by the value quoted as a string literal
+//! - `${"NAME"}` by the value quoted as an identifier, if it needs quoting
+//! - `$${NAME}` is left as `${NAME}`, e.g. for template strings in plv8 bodies
+//!
+//! A variable right after a dollar-quote delimiter, as in `$$${NAME}$$` or
+//! `$body$${NAME}$body$`, is substituted: the delimiter's closing `$` isn't
+//! read as escaping it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::sql::quote::{quote_ident, quote_literal};
+
+static VARIABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\$?\$\{(?:([A-Za-z_][A-Za-z0-9_]*)|'([A-Za-z_][A-Za-z0-9_]*)'|"([A-Za-z_][A-Za-z0-9_]*)")\}"#).unwrap()
+});
+
+/// A dollar-quote tag, `$tag`, at the end of the text before its closing `$`
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$[A-Za-z_][A-Za-z0-9_]*$").unwrap());
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SqlVars {
+    /// Values set in pgmg.toml, taking precedence over environment variables
+    pub values: BTreeMap<String, String>,
+}
+
+impl SqlVars {
+    pub fn new(values: BTreeMap<String, String>) -> Self {
+        Self { values }
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        self.values.get(name).cloned().or_else(|| std::env::var(name).ok())
+    }
+
+    /// Replace every variable in `content`. Fails on the first one that has
+    /// no value, giving its line.
+    pub fn substitute(&self, content: &str) -> Result<String, String> {
+        let mut substituted = String::with_capacity(content.len());
+        let mut last = 0;
+
+        for captures in VARIABLE.captures_iter(content) {
+            let matched = captures.get(0).unwrap();
+            substituted.push_str(&content[last..matched.start()]);
+            last = matched.end();
+
+            // Of the run of `$` before the brace, a `$tag$` delimiter takes
+            // the first and `$$` delimiters take pairs. One left over starts
+            // the variable and two escape it.
+            let matched_dollars = matched.as_str().find('{').unwrap();
+            let before = content[..matched.start()].trim_end_matches('$');
+            let mut dollars = matched.start() - before.len() + matched_dollars;
+            if TAG.is_match(before) {
+                dollars -= 1;
+            }
+            if dollars == 0 {
+                // The `$` closes a delimiter, leaving only a brace
+                substituted.push_str(matched.as_str());
+                continue;
+            }
+            if dollars % 2 == 0 {
+                substituted.push_str(&matched.as_str()[1..]);
+                continue;
+            }
+            // A `$` in the match ahead of the variable's own closes a delimiter
+            substituted.push_str(&matched.as_str()[..matched_dollars - 1]);
+
+            let name = captures.get(1).or(captures.get(2)).or(captures.get(3)).unwrap().as_str();
+            let value = self.value(name).ok_or_else(|| format!(
+                "Undefined variable {} on line {}. Set it under [vars] in pgmg.toml or as an environment variable",
+                name,
+                content[..matched.start()].matches('\n').count() + 1
+            ))?;
+            if captures.get(2).is_some() {
+                substituted.push_str(&quote_literal(&value));
+            } else if captures.get(3).is_some() {
+                substituted.push_str(&quote_ident(&value));
+            } else {
+                substituted.push_str(&value);
+            }
+        }
+
+        substituted.push_str(&content[last..]);
+        Ok(substituted)
+    }
+}
+
+/// A file's content with its variables substituted, or as it is when no
+/// `[vars]` are configured. Errors name the file.
+pub fn substitute_vars(vars: Option<&SqlVars>, path: &Path, content: String) -> Result<String, String> {
+    match vars {
+        Some(vars) => vars.substitute(&content).map_err(|e| format!("{}: {}", path.display(), e)),
+        None => Ok(content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(values: &[(&str, &str)]) -> SqlVars {
+        SqlVars::new(values.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect())
+    }
+
+    #[test]
+    fn test_substitute() {
+        let vars = vars(&[("FDW_HOST", "db.internal"), ("APP_ROLE", "Web App"), ("OWNER", "o'brien")]);
+
+        assert_eq!(
+            vars.substitute("CREATE SERVER reporting FOREIGN DATA WRAPPER postgres_fdw OPTIONS (host '${FDW_HOST}');").unwrap(),
+            "CREATE SERVER reporting FOREIGN DATA WRAPPER postgres_fdw OPTIONS (host 'db.internal');"
+        );
+        assert_eq!(
+            vars.substitute("GRANT SELECT ON api.orders TO ${\"APP_ROLE\"};").unwrap(),
+            "GRANT SELECT ON api.orders TO \"Web App\";"
+        );
+        assert_eq!(
+            vars.substitute("COMMENT ON VIEW api.orders IS ${'OWNER'};").unwrap(),
+            "COMMENT ON VIEW api.orders IS 'o''brien';"
+        );
+    }
+
+    #[test]
+    fn test_escaped_variables_are_kept() {
+        let vars = vars(&[]);
+
+        assert_eq!(
+            vars.substitute("SELECT '$${PGMG_TEST_ESCAPED}'").unwrap(),
+            "SELECT '${PGMG_TEST_ESCAPED}'"
+        );
+        // Only variable names are substituted
+        let body = "CREATE FUNCTION f() RETURNS json AS $${\"a\": 1}$$ LANGUAGE sql";
+        assert_eq!(vars.substitute(body).unwrap(), body);
+    }
+
+    #[test]
+    fn test_variables_after_dollar_quote_delimiters() {
+        let vars = vars(&[("GREETING", "hello")]);
+
+        assert_eq!(vars.substitute("SELECT $$${GREETING}$$").unwrap(), "SELECT $$hello$$");
+        assert_eq!(vars.substitute("SELECT $msg$${GREETING}$msg$").unwrap(), "SELECT $msg$hello$msg$");
+        assert_eq!(vars.substitute("SELECT $$$${GREETING}$$").unwrap(), "SELECT $$${GREETING}$$");
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let err = vars(&[]).substitute("SELECT 1;\nSELECT ${PGMG_TEST_UNDEFINED};").unwrap_err();
+        assert!(err.contains("Undefined variable PGMG_TEST_UNDEFINED on line 2"), "{}", err);
+    }
+}
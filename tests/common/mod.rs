@@ -107,6 +107,8 @@ impl TestEnvironment {
         admin_client
             .execute(&format!("CREATE DATABASE {}", database_name), &[])
             .await?;
+        // Lets `pgmg test-db gc` find it if the test run is killed
+        pgmg::db::test_utils::mark_test_database(&admin_client, &database_name).await?;
         
         // Connect to test database
         let connection_string = format!(
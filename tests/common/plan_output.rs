@@ -39,7 +39,7 @@ impl Write for CaptureWriter {
 pub fn capture_plan_output(plan: &PlanResult) -> String {
     // For now, we'll just call print_plan_summary normally
     // In a real implementation, we'd modify the function to accept a writer
-    print_plan_summary(plan);
+    print_plan_summary(plan, true);
     
     // Return empty string as we can't easily capture stdout in tests
     // The actual tests will need to verify the plan structure instead
@@ -188,6 +188,16 @@ fn object_type_to_str(obj_type: &ObjectType) -> &'static str {
         ObjectType::CronJob => "cron_job",
         ObjectType::Aggregate => "aggregate",
         ObjectType::Operator => "operator",
+        ObjectType::SecurityLabel => "security_label",
+        ObjectType::Statistics => "statistics",
+        ObjectType::Extension => "extension",
+        ObjectType::Publication => "publication",
+        ObjectType::Collation => "collation",
+        ObjectType::TextSearchConfiguration => "text_search_configuration",
+        ObjectType::TextSearchDictionary => "text_search_dictionary",
+        ObjectType::OperatorClass => "operator_class",
+        ObjectType::OperatorFamily => "operator_family",
+        ObjectType::Cast => "cast",
     }
 }
 
@@ -1,25 +1,199 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use pgmg::CancellationToken;
 use pgmg::config::PgmgConfig;
-use pgmg::commands::apply::execute_apply;
+use pgmg::error::PgmgError;
+use pgmg::commands::apply::{execute_apply, execute_apply_with_cancellation, execute_apply_with_hooks, ApplyHooks, ApplyPause, ApplyPhase, ApplyResult};
 use pgmg::db::{AdvisoryLockManager, AdvisoryLockError, connect_with_url};
 use tempfile::TempDir;
 use testcontainers::clients::Cli;
 use testcontainers_modules::postgres::Postgres;
 use tokio::task::JoinHandle;
+use tokio::sync::{mpsc::UnboundedReceiver, oneshot};
 use tokio::time::sleep;
 
 mod common;
 
-/// Test that concurrent pgmg apply operations are properly serialized
-/// Note: This test is disabled due to Send trait issues with the execute_apply function
-/// The actual concurrency control is tested at the lock level below
+use common::TestEnvironment;
+
+async fn apply_with_hooks(
+    env: &TestEnvironment,
+    config: &PgmgConfig,
+    hooks: ApplyHooks,
+) -> Result<ApplyResult, String> {
+    execute_apply_with_hooks(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        config,
+        Duration::from_secs(30),
+        &hooks,
+    ).await.map_err(|e| e.to_string())
+}
+
+/// Resume an apply until it reaches `phase`, and hold it there
+async fn run_until(pauses: &mut UnboundedReceiver<ApplyPause>, phase: ApplyPhase) -> ApplyPause {
+    loop {
+        let pause = pauses.recv().await.expect("Apply finished early");
+        if pause.phase == phase {
+            return pause;
+        }
+        pause.resume();
+    }
+}
+
+async fn run_to_end(pauses: &mut UnboundedReceiver<ApplyPause>) {
+    while let Some(pause) = pauses.recv().await {
+        pause.resume();
+    }
+}
+
+/// Test that concurrent pgmg apply operations are properly serialized: the
+/// second apply waits for the lock, then finds nothing left to do
 #[tokio::test]
-#[ignore = "Disabled due to Send trait issues - concurrency tested via lock manager"]
 async fn test_concurrent_apply_operations_are_serialized() {
-    // This test is conceptually correct but can't run due to execute_apply
-    // returning non-Send error types. The lock management is tested separately.
+    let env = TestEnvironment::new().await.unwrap();
+    env.write_migration("001_users", "CREATE TABLE users (id serial PRIMARY KEY, email text);").await.unwrap();
+    env.write_sql_file("views/user_emails.sql", "CREATE VIEW user_emails AS SELECT email FROM users;").await.unwrap();
+    let config = PgmgConfig::default();
+
+    let (first_hooks, mut first_pauses) = ApplyHooks::channel();
+    let (second_hooks, mut second_pauses) = ApplyHooks::channel();
+    let (start_second, second_started) = oneshot::channel::<()>();
+
+    let first = apply_with_hooks(&env, &config, first_hooks);
+    let second = async {
+        second_started.await.unwrap();
+        apply_with_hooks(&env, &config, second_hooks).await
+    };
+    let control = async {
+        let held = run_until(&mut first_pauses, ApplyPhase::ChangesApplied).await;
+        start_second.send(()).unwrap();
+
+        let waiting = second_pauses.recv().await.unwrap();
+        assert_eq!(waiting.phase, ApplyPhase::WaitingForLock);
+        waiting.resume();
+
+        sleep(Duration::from_secs(2)).await;
+        assert!(second_pauses.try_recv().is_err(), "Second apply got past the lock while the first held it");
+
+        held.resume();
+        run_to_end(&mut first_pauses).await;
+        run_to_end(&mut second_pauses).await;
+    };
+    let (first, second, ()) = tokio::join!(first, second, control);
+
+    let first = first.unwrap();
+    assert_eq!(first.migrations_applied, vec!["001_users"]);
+    assert_eq!(first.objects_created.len(), 1);
+
+    let second = second.unwrap();
+    assert!(second.migrations_applied.is_empty());
+    assert!(second.objects_created.is_empty());
+    assert!(env.view_exists("user_emails").await.unwrap());
+}
+
+/// Test that an apply stopped before committing leaves nothing behind, and
+/// the next apply runs all of it
+#[tokio::test]
+async fn test_apply_aborted_before_commit_rolls_back() {
+    let env = TestEnvironment::new().await.unwrap();
+    let config = PgmgConfig::default();
+
+    // A first apply, so the next one runs in a transaction
+    env.write_migration("001_users", "CREATE TABLE users (id serial PRIMARY KEY, email text);").await.unwrap();
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config)
+        .await.unwrap();
+
+    env.write_migration("002_user_names", "ALTER TABLE users ADD COLUMN name text;").await.unwrap();
+    env.write_sql_file("views/user_names.sql", "CREATE VIEW user_names AS SELECT name FROM users;").await.unwrap();
+
+    let (hooks, mut pauses) = ApplyHooks::channel();
+    let control = async {
+        run_until(&mut pauses, ApplyPhase::ChangesApplied).await.abort();
+        run_to_end(&mut pauses).await;
+    };
+    let (aborted, ()) = tokio::join!(apply_with_hooks(&env, &config, hooks), control);
+
+    assert!(aborted.unwrap_err().contains("aborted"));
+    assert_eq!(env.get_applied_migrations().await.unwrap(), vec!["001_users"]);
+    assert!(!env.view_exists("user_names").await.unwrap());
+
+    let result = execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config)
+        .await.unwrap();
+    assert_eq!(result.migrations_applied, vec!["002_user_names"]);
+    assert!(env.view_exists("user_names").await.unwrap());
+}
+
+/// Test that cancelling an apply stops the running migration, rolls it back
+/// and frees the lock for the next apply
+#[tokio::test]
+async fn test_cancelled_apply_rolls_back_and_releases_lock() {
+    let env = TestEnvironment::new().await.unwrap();
+    let config = PgmgConfig::default();
+
+    env.write_migration("001_users", "CREATE TABLE users (id serial PRIMARY KEY, email text);").await.unwrap();
+    execute_apply(Some(env.migrations_dir.clone()), None, env.connection_string.clone(), &config)
+        .await.unwrap();
+
+    env.write_migration("002_slow", "CREATE TABLE orders (id serial PRIMARY KEY);\nSELECT pg_sleep(60);").await.unwrap();
+
+    let cancel = CancellationToken::new();
+    let on_timer = cancel.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_secs(1)).await;
+        on_timer.cancel();
+    });
+
+    let start_time = Instant::now();
+    let result = execute_apply_with_cancellation(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &config,
+        &cancel,
+    ).await;
+    assert!(matches!(result, Err(PgmgError::Cancelled)), "Expected Cancelled, got: {:?}", result);
+    assert!(start_time.elapsed() < Duration::from_secs(10), "Cancel should stop the running statement");
+
+    assert_eq!(env.get_applied_migrations().await.unwrap(), vec!["001_users"]);
+    assert!(!env.table_exists("orders").await.unwrap());
+
+    let (client, connection) = connect_with_url(&env.connection_string).await.unwrap();
+    connection.spawn();
+    let mut lock_manager = AdvisoryLockManager::new(&env.connection_string);
+    lock_manager.acquire_lock(&client, Duration::from_secs(1)).await.unwrap();
+    lock_manager.release_lock(&client).await.unwrap();
+}
+
+/// Test that cancelling while waiting for the lock gives up at once
+#[tokio::test]
+async fn test_lock_wait_cancelled() {
+    let env = TestEnvironment::new().await.unwrap();
+
+    let (client1, connection1) = connect_with_url(&env.connection_string).await.unwrap();
+    connection1.spawn();
+    let mut lock_manager1 = AdvisoryLockManager::new(&env.connection_string);
+    lock_manager1.acquire_lock(&client1, Duration::from_secs(5)).await.unwrap();
+
+    let (client2, connection2) = connect_with_url(&env.connection_string).await.unwrap();
+    connection2.spawn();
+    let mut lock_manager2 = AdvisoryLockManager::new(&env.connection_string);
+
+    let cancel = CancellationToken::new();
+    let on_timer = cancel.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(500)).await;
+        on_timer.cancel();
+    });
+
+    let start_time = Instant::now();
+    let result = lock_manager2.acquire_lock_unless_cancelled(&client2, Duration::from_secs(30), &cancel).await;
+    assert!(matches!(result, Err(AdvisoryLockError::Cancelled)), "Expected Cancelled, got: {:?}", result);
+    assert!(start_time.elapsed() < Duration::from_secs(5), "Should stop waiting once cancelled");
+
+    lock_manager1.release_lock(&client1).await.unwrap();
 }
 
 /// Test that lock acquisition times out when another process holds the lock
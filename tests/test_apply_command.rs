@@ -1,9 +1,11 @@
 mod common;
 
 use common::{TestEnvironment, assertions::*, fixtures};
-use pgmg::commands::execute_apply;
-use pgmg::config::PgmgConfig;
-use indoc::indoc;
+use pgmg::analysis::SearchPath;
+use pgmg::commands::{execute_apply, execute_plan_with_options, execute_refresh, ChangeOperation, MatviewRefreshStatus, PlanOptions};
+use pgmg::config::{PgmgConfig, LockRetryConfigSection, EnvironmentConfigSection, HookConfigSection, HooksConfigSection};
+use pgmg::error::PgmgError;
+use indoc::{formatdoc, indoc};
 
 #[tokio::test]
 async fn test_apply_new_migrations() -> Result<(), Box<dyn std::error::Error>> {
@@ -258,6 +260,8 @@ async fn test_apply_rollback_on_migration_error() -> Result<(), Box<dyn std::err
 #[tokio::test]
 async fn test_apply_rollback_on_object_error() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // The table is declared in the code directory, to be rolled back with the view
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
 
     // Pre-seed database with a successful migration so it's not a "fresh build"
     // This ensures transaction mode is used for rollback testing
@@ -266,7 +270,7 @@ async fn test_apply_rollback_on_object_error() -> Result<(), Box<dyn std::error:
         Some(env.migrations_dir.clone()),
         None,
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
 
     // Create valid and invalid SQL files
@@ -281,7 +285,7 @@ async fn test_apply_rollback_on_object_error() -> Result<(), Box<dyn std::error:
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await;
 
     // Should have failed
@@ -404,4 +408,1319 @@ async fn test_apply_twice_is_idempotent() -> Result<(), Box<dyn std::error::Erro
     assert_eq!(result2.objects_deleted.len(), 0);
     
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_apply_concurrent_index_outside_transaction() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, customer_id integer, status text);
+    "#}).await?;
+    env.write_sql_file("status_view.sql", indoc! {r#"
+        CREATE VIEW order_status AS SELECT id, status FROM orders;
+    "#}).await?;
+
+    // First apply is a fresh build and runs in auto-commit mode
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    // With state present, apply runs in a transaction, which CONCURRENTLY can't use
+    env.write_sql_file("orders_customer_idx.sql", indoc! {r#"
+        CREATE INDEX CONCURRENTLY orders_customer_idx ON orders (customer_id);
+    "#}).await?;
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    assert_apply_successful(&result);
+    assert!(result.objects_created.iter().any(|name| name == "orders_customer_idx"));
+
+    let valid: bool = env.query_scalar(
+        "SELECT indisvalid FROM pg_index WHERE indexrelid = 'orders_customer_idx'::regclass"
+    ).await?;
+    assert!(valid);
+
+    // Nothing left to do on the next run
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert!(result.objects_created.is_empty());
+    assert!(result.objects_updated.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_no_transaction_migration() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, customer_id integer);
+    "#}).await?;
+
+    // Fresh build runs in auto-commit mode; the next apply uses a transaction
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    env.write_migration("002_add_status", indoc! {r#"
+        ALTER TABLE orders ADD COLUMN status text;
+    "#}).await?;
+    env.write_migration("003_index_status", indoc! {r#"
+        -- Build without blocking writes
+        -- pgmg:no-transaction
+        CREATE INDEX CONCURRENTLY IF NOT EXISTS orders_status_idx ON orders (status);
+    "#}).await?;
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    assert_apply_successful(&result);
+    assert_migrations_applied(&result, &["002_add_status", "003_index_status"]);
+
+    let valid: bool = env.query_scalar(
+        "SELECT indisvalid FROM pg_index WHERE indexrelid = 'orders_status_idx'::regclass"
+    ).await?;
+    assert!(valid);
+
+    let applied = env.get_applied_migrations().await?;
+    assert!(applied.contains(&"003_index_status".to_string()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_leading_no_transaction_migration_after_pre_drop() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, status varchar(10));
+    "#}).await?;
+    env.write_sql_file("order_status.sql", indoc! {r#"
+        CREATE VIEW order_status AS SELECT id, status FROM orders;
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    // Retyping the column fails while the view reading it exists
+    env.write_migration("002_widen_status", indoc! {r#"
+        -- pgmg:no-transaction
+        ALTER TABLE orders ALTER COLUMN status TYPE text;
+    "#}).await?;
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    assert_apply_successful(&result);
+    assert_migrations_applied(&result, &["002_widen_status"]);
+    assert!(result.objects_updated.iter().any(|name| name == "order_status"));
+
+    let status_type: String = env.query_scalar(
+        "SELECT atttypid::regtype::text FROM pg_attribute WHERE attrelid = 'order_status'::regclass AND attname = 'status'"
+    ).await?;
+    assert_eq!(status_type, "text");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_objects_after_trailing_no_transaction_migration() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY);
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    env.write_migration("002_add_status", indoc! {r#"
+        ALTER TABLE orders ADD COLUMN status text;
+    "#}).await?;
+    env.write_migration("003_add_shipped_at", indoc! {r#"
+        -- pgmg:no-transaction
+        ALTER TABLE orders ADD COLUMN shipped_at timestamptz;
+    "#}).await?;
+    // Needs the column the trailing migration adds
+    env.write_sql_file("order_shipping.sql", indoc! {r#"
+        CREATE VIEW order_shipping AS SELECT id, status, shipped_at FROM orders;
+    "#}).await?;
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    assert_apply_successful(&result);
+    assert_migrations_applied(&result, &["002_add_status", "003_add_shipped_at"]);
+    assert!(env.view_exists("order_shipping").await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_refuses_interleaved_no_transaction_migration() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, status text);
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    env.write_migration("002_customers", indoc! {r#"
+        CREATE TABLE customers (id integer PRIMARY KEY);
+    "#}).await?;
+    env.write_migration("003_index_status", indoc! {r#"
+        -- pgmg:no-transaction
+        CREATE INDEX CONCURRENTLY IF NOT EXISTS orders_status_idx ON orders (status);
+    "#}).await?;
+    env.write_migration("004_add_customer", indoc! {r#"
+        ALTER TABLE orders ADD COLUMN customer_id integer REFERENCES customers (id);
+    "#}).await?;
+
+    let error = execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await.unwrap_err();
+    assert!(error.to_string().contains("003_index_status"), "{}", error);
+
+    // Refused before anything ran
+    assert!(!env.table_exists("customers").await?);
+    let applied = env.get_applied_migrations().await?;
+    assert!(!applied.contains(&"002_customers".to_string()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_retries_ddl_blocked_by_lock() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, status text);
+    "#}).await?;
+    env.write_sql_file("order_status.sql", indoc! {r#"
+        CREATE VIEW order_status AS SELECT id, status FROM orders;
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    env.write_sql_file("order_status.sql", indoc! {r#"
+        CREATE VIEW order_status AS SELECT id, upper(status) AS status FROM orders;
+    "#}).await?;
+
+    // Hold a lock on the view so dropping it has to wait
+    env.execute_sql("BEGIN; SELECT * FROM order_status;").await?;
+
+    let mut config = PgmgConfig::default();
+    config.lock_retry = Some(LockRetryConfigSection {
+        attempts: Some(20),
+        lock_timeout_ms: Some(100),
+        backoff_ms: Some(100),
+        max_backoff_ms: Some(200),
+    });
+
+    let (result, released) = tokio::join!(
+        execute_apply(
+            Some(env.migrations_dir.clone()),
+            Some(env.sql_dir.clone()),
+            env.connection_string.clone(),
+            &config,
+        ),
+        async {
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            env.execute_sql("COMMIT").await
+        }
+    );
+    released?;
+    let result = result?;
+
+    assert_apply_successful(&result);
+    assert!(result.objects_updated.iter().any(|name| name == "order_status"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_lock_retry_error_names_blocking_pid() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, status text);
+    "#}).await?;
+    env.write_sql_file("order_status.sql", indoc! {r#"
+        CREATE VIEW order_status AS SELECT id, status FROM orders;
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    env.write_sql_file("order_status.sql", indoc! {r#"
+        CREATE VIEW order_status AS SELECT id, upper(status) AS status FROM orders;
+    "#}).await?;
+
+    // Hold a lock on the view for longer than every try waits
+    let blocking_pid: i32 = env.query_scalar("SELECT pg_backend_pid()").await?;
+    env.execute_sql("BEGIN; SELECT * FROM order_status;").await?;
+
+    let mut config = PgmgConfig::default();
+    config.lock_retry = Some(LockRetryConfigSection {
+        attempts: Some(2),
+        lock_timeout_ms: Some(100),
+        backoff_ms: Some(100),
+        max_backoff_ms: Some(100),
+    });
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    ).await;
+    env.execute_sql("COMMIT").await?;
+
+    let error = result.expect_err("the view stays locked").to_string();
+    assert!(error.contains(&format!("blocked by pid {}", blocking_pid)), "{}", error);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_reapplies_object_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_users", indoc! {r#"
+        CREATE TABLE users (id integer PRIMARY KEY, email text);
+    "#}).await?;
+    env.write_sql_file("user_emails.sql", indoc! {r#"
+        CREATE OR REPLACE VIEW user_emails AS SELECT id, email FROM users;
+        ALTER VIEW user_emails SET (security_barrier = true);
+
+        CREATE OR REPLACE FUNCTION search_emails(query text) RETURNS SETOF text AS $$
+            SELECT email FROM user_emails WHERE email LIKE query
+        $$ LANGUAGE sql;
+        ALTER FUNCTION search_emails(text) SET work_mem = '64MB';
+    "#}).await?;
+
+    let config = PgmgConfig::default();
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    assert_apply_successful(&apply().await?);
+
+    let view_options = "SELECT array_to_string(reloptions, ',') FROM pg_class WHERE oid = 'user_emails'::regclass";
+    let function_config = "SELECT array_to_string(proconfig, ',') FROM pg_proc WHERE proname = 'search_emails'";
+    assert_eq!(env.query_scalar::<String>(view_options).await?, "security_barrier=true");
+    assert_eq!(env.query_scalar::<String>(function_config).await?, "work_mem=64MB");
+
+    // Replacing the definitions resets their options; apply sets them again
+    env.write_sql_file("user_emails.sql", indoc! {r#"
+        CREATE OR REPLACE VIEW user_emails AS SELECT id, email FROM users WHERE email IS NOT NULL;
+        ALTER VIEW user_emails SET (security_barrier = true);
+
+        CREATE OR REPLACE FUNCTION search_emails(query text) RETURNS SETOF text AS $$
+            SELECT email FROM user_emails WHERE email ILIKE query
+        $$ LANGUAGE sql;
+        ALTER FUNCTION search_emails(text) SET work_mem = '64MB';
+    "#}).await?;
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_objects_updated(&result, &["user_emails", "search_emails"]);
+    assert_eq!(env.query_scalar::<String>(view_options).await?, "security_barrier=true");
+    assert_eq!(env.query_scalar::<String>(function_config).await?, "work_mem=64MB");
+
+    // Changing only a setting updates the object
+    env.write_sql_file("user_emails.sql", indoc! {r#"
+        CREATE OR REPLACE VIEW user_emails AS SELECT id, email FROM users WHERE email IS NOT NULL;
+        ALTER VIEW user_emails SET (security_barrier = true);
+
+        CREATE OR REPLACE FUNCTION search_emails(query text) RETURNS SETOF text AS $$
+            SELECT email FROM user_emails WHERE email ILIKE query
+        $$ LANGUAGE sql;
+        ALTER FUNCTION search_emails(text) SET work_mem = '128MB';
+    "#}).await?;
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_objects_updated(&result, &["search_emails"]);
+    assert_eq!(env.query_scalar::<String>(function_config).await?, "work_mem=128MB");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_reports_skipped_statements() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_jobs", indoc! {r#"
+        CREATE TABLE jobs (id integer PRIMARY KEY);
+        SELECT cron.schedule('nightly-cleanup', '0 3 * * *', 'DELETE FROM jobs');
+    "#}).await?;
+
+    let result = pgmg::commands::apply::execute_apply_with_test_mode(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+        true,
+    ).await?;
+
+    assert_apply_successful(&result);
+    assert!(env.table_exists("jobs").await?);
+
+    assert_eq!(result.skipped.len(), 1);
+    let skipped = &result.skipped[0];
+    assert_eq!(skipped.file.as_deref(), Some(env.migrations_dir.join("001_jobs.sql").as_path()));
+    assert_eq!(skipped.line, Some(2));
+    assert!(skipped.statement.starts_with("SELECT cron.schedule"));
+    assert!(skipped.reason.contains("pg_cron"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_configured_skip_rules() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+        COMMENT ON TABLE orders IS 'Archived nightly by pg_cron';
+    "#}).await?;
+    env.write_sql_file("order_stats.sql", indoc! {r#"
+        CREATE VIEW order_count AS SELECT count(*) AS orders FROM orders;
+        CREATE MATERIALIZED VIEW order_totals AS SELECT sum(total) AS total FROM orders;
+    "#}).await?;
+
+    let config: PgmgConfig = toml::from_str(indoc! {r#"
+        [[skip_rules]]
+        reason = "materialized views are built by the warehouse job"
+        object_types = ["materialized_view"]
+    "#})?;
+
+    let result = pgmg::commands::apply::execute_apply_with_test_mode(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+        true,
+    ).await?;
+    assert_apply_successful(&result);
+
+    // Mentioning pg_cron in a comment doesn't get the statement skipped
+    let comment: String = env.query_scalar("SELECT obj_description('orders'::regclass, 'pg_class')").await?;
+    assert_eq!(comment, "Archived nightly by pg_cron");
+
+    assert_eq!(result.skipped.len(), 1);
+    assert!(result.skipped[0].statement.starts_with("CREATE MATERIALIZED VIEW order_totals"));
+    assert_eq!(result.skipped[0].reason, "materialized views are built by the warehouse job");
+    assert_eq!(result.skipped[0].line, Some(2));
+    let matviews: i64 = env.query_scalar("SELECT count(*) FROM pg_matviews WHERE matviewname = 'order_totals'").await?;
+    assert_eq!(matviews, 0);
+    assert!(env.view_exists("order_count").await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_drops_objects_with_quoted_names() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql(fixtures::sql::CREATE_USERS_TABLE).await?;
+
+    env.write_sql_file("odd_names.sql", indoc! {r#"
+        CREATE VIEW "UserNames" AS SELECT username FROM users;
+        CREATE VIEW "user" AS SELECT id FROM users;
+        CREATE FUNCTION "Greeting"(name text) RETURNS text AS $$ SELECT 'hi ' || name $$ LANGUAGE sql;
+        COMMENT ON VIEW "UserNames" IS 'Names only';
+    "#}).await?;
+
+    let config = PgmgConfig::default();
+    let apply = || execute_apply(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(env.view_exists("UserNames").await?);
+    assert!(env.view_exists("user").await?);
+
+    // Changing the view drops and recreates it under its quoted name
+    env.write_sql_file("odd_names.sql", indoc! {r#"
+        CREATE VIEW "UserNames" AS SELECT username, email FROM users;
+        CREATE VIEW "user" AS SELECT id FROM users;
+        CREATE FUNCTION "Greeting"(name text) RETURNS text AS $$ SELECT 'hi ' || name $$ LANGUAGE sql;
+    "#}).await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    let comment: Option<String> = env.query_scalar(r#"SELECT obj_description('"UserNames"'::regclass, 'pg_class')"#).await?;
+    assert_eq!(comment, None);
+
+    // Removing the file drops everything it defined
+    env.delete_sql_file("odd_names.sql").await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(!env.view_exists("UserNames").await?);
+    assert!(!env.view_exists("user").await?);
+    assert!(!env.function_exists("Greeting").await?);
+    assert!(env.get_tracked_objects().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_statistics_lifecycle() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, customer_id integer, region text, status text);
+    "#}).await?;
+    env.write_sql_file("orders_stats.sql", indoc! {r#"
+        CREATE STATISTICS orders_customer_region (dependencies) ON customer_id, region FROM orders;
+    "#}).await?;
+
+    let config = PgmgConfig::default();
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_created.iter().any(|name| name == "orders_customer_region"));
+    assert!(env.get_tracked_objects().await?.contains(&("statistics".to_string(), "orders_customer_region".to_string())));
+
+    let kinds: String = env.query_scalar(
+        "SELECT array_to_string(stxkind, ',') FROM pg_statistic_ext WHERE stxname = 'orders_customer_region'"
+    ).await?;
+    assert_eq!(kinds, "f");
+
+    // Changing the definition recreates it
+    env.write_sql_file("orders_stats.sql", indoc! {r#"
+        CREATE STATISTICS orders_customer_region (dependencies, ndistinct) ON customer_id, region, status FROM orders;
+    "#}).await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_updated.iter().any(|name| name == "orders_customer_region"));
+
+    let columns: i64 = env.query_scalar(
+        "SELECT array_length(stxkeys::int2[], 1)::bigint FROM pg_statistic_ext WHERE stxname = 'orders_customer_region'"
+    ).await?;
+    assert_eq!(columns, 3);
+
+    // Removing the file drops it
+    env.delete_sql_file("orders_stats.sql").await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+
+    let remaining: i64 = env.query_scalar(
+        "SELECT count(*) FROM pg_statistic_ext WHERE stxname = 'orders_customer_region'"
+    ).await?;
+    assert_eq!(remaining, 0);
+    assert!(env.get_tracked_objects().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_object_directives() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_sql_file("views.sql", indoc! {r#"
+        -- pgmg:no-drop
+        CREATE VIEW kept AS SELECT 1 AS one;
+
+        -- pgmg:skip-env ci
+        CREATE VIEW dev_only AS SELECT 2 AS two;
+    "#}).await?;
+
+    let config = PgmgConfig {
+        environment: Some("ci".to_string()),
+        environments: Some([("ci".to_string(), EnvironmentConfigSection::default())].into_iter().collect()),
+        ..PgmgConfig::default()
+    };
+    let apply = || execute_apply(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_created, vec!["kept".to_string()]);
+    assert!(!env.view_exists("dev_only").await?);
+
+    // Removing a no-drop object from code leaves it in the database
+    env.write_sql_file("views.sql", indoc! {r#"
+        -- pgmg:skip-env ci
+        CREATE VIEW dev_only AS SELECT 2 AS two;
+    "#}).await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_deleted.is_empty());
+    assert!(env.view_exists("kept").await?);
+    assert!(env.get_tracked_objects().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_keeps_declared_owner_across_recreation() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    // Roles are shared by every database in the cluster
+    let role = format!("{}_owner", env.database_name);
+    env.execute_sql(&format!("CREATE ROLE {}", role)).await?;
+
+    env.write_migration("001_users", "CREATE TABLE users (id serial PRIMARY KEY, email text);").await?;
+    env.write_sql_file("user_emails.sql", &formatdoc! {r#"
+        CREATE VIEW user_emails AS SELECT email FROM users;
+        ALTER VIEW user_emails OWNER TO {};
+    "#, role}).await?;
+    env.write_sql_file("count_users.sql", &formatdoc! {r#"
+        -- pgmg:owner {}
+        CREATE FUNCTION count_users() RETURNS bigint LANGUAGE sql AS $$ SELECT count(*) FROM users $$;
+    "#, role}).await?;
+
+    let config = PgmgConfig::default();
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+    let view_owner = || env.query_scalar::<String>("SELECT viewowner::text FROM pg_views WHERE viewname = 'user_emails'");
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(view_owner().await?, role);
+    assert_eq!(
+        env.query_scalar::<String>("SELECT pg_get_userbyid(proowner)::text FROM pg_proc WHERE proname = 'count_users'").await?,
+        role
+    );
+
+    // The view is dropped and recreated around the column change
+    env.write_migration("002_email_type", "ALTER TABLE users ALTER COLUMN email TYPE varchar(320);").await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_updated.iter().any(|name| name == "user_emails"));
+    assert_eq!(view_owner().await?, role);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_keeps_privileges_across_recreation() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let reader = format!("{}_reader", env.database_name);
+    let outsider = format!("{}_outsider", env.database_name);
+    env.execute_sql(&format!("CREATE ROLE {}; CREATE ROLE {}", reader, outsider)).await?;
+
+    env.write_migration("001_users", "CREATE TABLE users (id serial PRIMARY KEY, email text);").await?;
+    env.write_sql_file("user_emails.sql", "CREATE VIEW user_emails AS SELECT email FROM users;").await?;
+    env.write_sql_file("count_users.sql",
+        "CREATE FUNCTION count_users() RETURNS bigint LANGUAGE sql AS $$ SELECT count(*) FROM users $$;").await?;
+
+    let preserved = PgmgConfig::default();
+    let not_preserved = PgmgConfig { preserve_privileges: Some(false), ..PgmgConfig::default() };
+    let apply = |config| execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        config,
+    );
+    let can_select = |role: &str| format!("SELECT has_table_privilege('{}', 'user_emails', 'SELECT')", role);
+    let can_execute = |role: &str| format!("SELECT has_function_privilege('{}', 'count_users()', 'EXECUTE')", role);
+
+    assert_apply_successful(&apply(&preserved).await?);
+    env.execute_sql(&formatdoc! {"
+        GRANT SELECT ON user_emails TO {reader} WITH GRANT OPTION;
+        REVOKE EXECUTE ON FUNCTION count_users() FROM PUBLIC;
+        GRANT EXECUTE ON FUNCTION count_users() TO {reader};
+    ", reader = reader}).await?;
+
+    // With a migration pending, changed objects are dropped and recreated
+    // rather than replaced in place
+    env.write_migration("002_email_type", "ALTER TABLE users ALTER COLUMN email TYPE varchar(320);").await?;
+    env.write_sql_file("count_users.sql",
+        "CREATE FUNCTION count_users() RETURNS bigint LANGUAGE sql AS $$ SELECT count(email) FROM users $$;").await?;
+    let result = apply(&preserved).await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_updated.iter().any(|name| name == "user_emails"));
+    assert!(result.objects_updated.iter().any(|name| name == "count_users"));
+    assert!(env.query_scalar::<bool>(&can_select(&reader)).await?);
+    assert!(env.query_scalar::<bool>(&format!(
+        "SELECT has_table_privilege('{}', 'user_emails', 'SELECT WITH GRANT OPTION')", reader
+    )).await?);
+    assert!(!env.query_scalar::<bool>(&can_select(&outsider)).await?);
+    assert!(env.query_scalar::<bool>(&can_execute(&reader)).await?);
+    assert!(!env.query_scalar::<bool>(&can_execute(&outsider)).await?, "EXECUTE revoked from PUBLIC should stay revoked");
+
+    // Turned off, the recreated objects get their default privileges
+    env.write_migration("003_email_text", "ALTER TABLE users ALTER COLUMN email TYPE text;").await?;
+    let result = apply(&not_preserved).await?;
+    assert_apply_successful(&result);
+    assert!(!env.query_scalar::<bool>(&can_select(&reader)).await?);
+    assert!(env.query_scalar::<bool>(&can_execute(&outsider)).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_skips_formatting_only_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig {
+        ignore_formatting_changes: Some(true),
+        ..PgmgConfig::default()
+    };
+
+    env.write_sql_file("double_it.sql", indoc! {r#"
+        CREATE FUNCTION double_it(n integer) RETURNS integer LANGUAGE plpgsql AS $$
+        BEGIN
+            RETURN n * 2;
+        END;
+        $$;
+    "#}).await?;
+
+    let apply = || execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &config);
+    let plan = || execute_plan_with_options(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions::from_config(&config),
+    );
+
+    assert_apply_successful(&apply().await?);
+
+    env.write_sql_file("double_it.sql", indoc! {r#"
+        -- Doubles its argument
+        CREATE FUNCTION double_it(n integer)
+        RETURNS integer
+        LANGUAGE plpgsql
+        AS $$
+        BEGIN
+            /* no overflow check */
+            RETURN n   *   2;
+        END;
+        $$;
+    "#}).await?;
+
+    let plan_result = plan().await?;
+    assert!(plan_result.changes.is_empty());
+    assert_eq!(plan_result.formatting_only.len(), 1);
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_updated.is_empty());
+    assert!(plan().await?.formatting_only.is_empty());
+
+    // A real change is still applied
+    env.write_sql_file("double_it.sql", indoc! {r#"
+        CREATE FUNCTION double_it(n integer) RETURNS integer LANGUAGE plpgsql AS $$
+        BEGIN
+            RETURN n * 3;
+        END;
+        $$;
+    "#}).await?;
+    let result = apply().await?;
+    assert_eq!(result.objects_updated, vec!["double_it".to_string()]);
+    assert_eq!(env.query_scalar::<i32>("SELECT double_it(2)").await?, 6);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_migration_recreating_identity_sequence() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("next_order.sql", indoc! {r#"
+        CREATE VIEW next_order AS SELECT nextval('orders_id_seq'::regclass) AS id;
+    "#}).await?;
+
+    let config = PgmgConfig::default();
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+
+    // Dropping the identity drops its sequence, which the view depends on
+    env.write_migration("002_recreate_identity", indoc! {r#"
+        ALTER TABLE orders ALTER COLUMN id DROP IDENTITY;
+        ALTER TABLE orders ALTER COLUMN id ADD GENERATED ALWAYS AS IDENTITY;
+    "#}).await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(result.objects_updated.iter().any(|name| name == "next_order"));
+    assert!(env.view_exists("next_order").await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_rejects_unparseable_migration_before_running() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    // A fresh build runs migrations in auto-commit mode, so without the parse
+    // check the first migration would stay applied
+    env.write_migration("001_good", fixtures::sql::CREATE_USERS_TABLE).await?;
+    env.write_migration("002_broken", indoc! {r#"
+        CREATE TABLE test_table (id integer);
+        SELECT id FORM users;
+    "#}).await?;
+
+    let err = execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await.unwrap_err();
+
+    assert!(err.to_string().contains("Migration 002_broken does not parse, line 2"), "{}", err);
+    assert!(matches!(&err, PgmgError::MigrationParse { names, .. } if names == &["002_broken"]), "{:?}", err);
+    assert!(!env.table_exists("users").await?);
+    assert!(!env.table_exists("test_table").await?);
+    assert!(env.get_applied_migrations().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_failing_migration_error() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_users", fixtures::sql::CREATE_USERS_TABLE).await?;
+    env.write_migration("002_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY);
+
+        ALTER TABLE orders ADD COLUMN user_id integer REFERENCES accounts;
+    "#}).await?;
+
+    let err = execute_apply(
+        Some(env.migrations_dir.clone()),
+        None,
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await.unwrap_err();
+
+    match &err {
+        PgmgError::MigrationFailed { name, statement_index, line, pg_error, .. } => {
+            assert_eq!(name, "002_orders");
+            assert_eq!(*statement_index, 2);
+            assert_eq!(*line, Some(3));
+            assert_eq!(pg_error.code(), Some(&tokio_postgres::error::SqlState::UNDEFINED_TABLE));
+        }
+        _ => panic!("Expected MigrationFailed, got {:?}", err),
+    }
+    assert!(err.to_string().contains("relation \"accounts\" does not exist"), "{}", err);
+    assert!(!env.get_applied_migrations().await?.contains(&"002_orders".to_string()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_refreshes_recreated_matviews() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig {
+        matview_refresh: Some("refresh".to_string()),
+        ..PgmgConfig::default()
+    };
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+        INSERT INTO orders VALUES (1, 10), (2, 20);
+    "#}).await?;
+    env.write_sql_file("totals.sql", indoc! {r#"
+        CREATE MATERIALIZED VIEW order_totals AS SELECT sum(total) AS total FROM orders WITH NO DATA;
+        -- pgmg:refresh none
+        CREATE MATERIALIZED VIEW order_counts AS SELECT count(*) AS orders FROM orders WITH NO DATA;
+    "#}).await?;
+    assert_apply_successful(&apply().await?);
+
+    env.write_sql_file("totals.sql", indoc! {r#"
+        CREATE MATERIALIZED VIEW order_totals AS SELECT sum(total) AS total, max(total) AS largest FROM orders WITH NO DATA;
+        -- pgmg:refresh none
+        CREATE MATERIALIZED VIEW order_counts AS SELECT count(*) AS orders, count(total) AS with_total FROM orders WITH NO DATA;
+    "#}).await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+
+    let status = |name: &str| result.matview_refreshes.iter()
+        .find(|refresh| refresh.name == name)
+        .map(|refresh| refresh.status.clone());
+    assert_eq!(status("order_totals"), Some(MatviewRefreshStatus::Refreshed));
+    assert_eq!(status("order_counts"), Some(MatviewRefreshStatus::LeftEmpty));
+
+    assert_eq!(env.query_scalar::<String>("SELECT total::text FROM order_totals").await?, "30");
+    assert!(!env.query_scalar::<bool>("SELECT relispopulated FROM pg_class WHERE relname = 'order_counts'").await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refresh_follows_matview_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+        INSERT INTO orders VALUES (1, 10);
+    "#}).await?;
+    env.write_sql_file("totals.sql", indoc! {r#"
+        CREATE MATERIALIZED VIEW order_totals AS SELECT sum(total) AS total FROM orders;
+        CREATE VIEW order_totals_view AS SELECT total FROM order_totals;
+        CREATE MATERIALIZED VIEW a_rollup AS SELECT total * 2 AS doubled FROM order_totals_view;
+    "#}).await?;
+    let config = PgmgConfig::default();
+    assert_apply_successful(&execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    ).await?);
+
+    env.execute_sql("INSERT INTO orders VALUES (2, 20)").await?;
+
+    // a_rollup sorts first by name but reads order_totals, so it's refreshed second
+    let result = execute_refresh(env.connection_string.clone(), Vec::new(), true, &SearchPath::default()).await?;
+    assert!(result.failed.is_none());
+    let names: Vec<&str> = result.refreshed.iter().map(|view| view.name.as_str()).collect();
+    assert_eq!(names, vec!["order_totals", "a_rollup"]);
+    assert!(result.refreshed.iter().all(|view| !view.concurrently), "no view has a unique index");
+    assert_eq!(env.query_scalar::<String>("SELECT doubled::text FROM a_rollup").await?, "60");
+
+    let result = execute_refresh(env.connection_string.clone(), vec!["order_*".to_string()], false, &SearchPath::default()).await?;
+    assert_eq!(result.refreshed.len(), 1);
+    assert_eq!(result.refreshed[0].name, "order_totals");
+
+    assert!(execute_refresh(env.connection_string.clone(), vec!["missing".to_string()], false, &SearchPath::default()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_adds_enum_values_without_recreating_type() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+
+    env.write_sql_file("status.sql", "CREATE TYPE order_status AS ENUM ('pending', 'shipped');").await?;
+    env.write_sql_file("is_open.sql", indoc! {r#"
+        CREATE FUNCTION is_open(s order_status) RETURNS boolean LANGUAGE sql AS $$
+            SELECT s = 'pending'
+        $$;
+    "#}).await?;
+
+    let apply = || execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &config);
+    let plan = || execute_plan_with_options(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions::from_config(&config),
+    );
+
+    assert_apply_successful(&apply().await?);
+
+    // A column now uses the type, so it can't be dropped and recreated
+    env.execute_sql("CREATE TABLE orders (id int PRIMARY KEY, status order_status NOT NULL)").await?;
+    env.execute_sql("INSERT INTO orders VALUES (1, 'pending')").await?;
+
+    env.write_sql_file("status.sql", "CREATE TYPE order_status AS ENUM ('new', 'pending', 'packed', 'shipped', 'returned');").await?;
+
+    let plan_result = plan().await?;
+    assert!(plan_result.changes.is_empty(), "The type and its dependents aren't recreated");
+    assert_eq!(plan_result.enum_additions.len(), 1);
+    assert_eq!(plan_result.enum_additions[0].statements, vec![
+        "ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'new' BEFORE 'pending'",
+        "ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'packed' AFTER 'pending'",
+        "ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'returned'",
+    ]);
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_updated, vec!["order_status".to_string()]);
+
+    let values: Vec<String> = env.query_all(
+        "SELECT enumlabel::text FROM pg_enum WHERE enumtypid = 'order_status'::regtype ORDER BY enumsortorder"
+    ).await?;
+    assert_eq!(values, vec!["new", "pending", "packed", "shipped", "returned"]);
+    env.execute_sql("INSERT INTO orders VALUES (2, 'packed')").await?;
+    assert!(plan().await?.enum_additions.is_empty());
+
+    // Values can't be removed or reordered
+    env.write_sql_file("status.sql", "CREATE TYPE order_status AS ENUM ('new', 'pending', 'packed', 'shipped');").await?;
+    let error = plan().await.unwrap_err().to_string();
+    assert!(error.contains("no longer has 'returned'"), "{}", error);
+
+    env.write_sql_file("status.sql", "CREATE TYPE order_status AS ENUM ('pending', 'new', 'packed', 'shipped', 'returned');").await?;
+    let error = apply().await.unwrap_err().to_string();
+    assert!(error.contains("reorders its existing values"), "{}", error);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_manages_extensions() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+
+    // Sorts before the extension's file, so only the dependency orders them
+    env.write_sql_file("a_scores.sql", "CREATE VIEW word_scores AS SELECT similarity('word', 'words') AS score;").await?;
+    env.write_sql_file("extensions.sql", "CREATE EXTENSION IF NOT EXISTS pg_trgm VERSION '1.3';").await?;
+
+    let apply = || execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &config);
+    let installed_version = || env.query_scalar::<String>("SELECT extversion FROM pg_extension WHERE extname = 'pg_trgm'");
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_created, vec!["pg_trgm".to_string(), "word_scores".to_string()]);
+    assert_eq!(installed_version().await?, "1.3");
+    assert!(env.get_tracked_objects().await?.contains(&("extension".to_string(), "pg_trgm".to_string())));
+
+    // A new pinned version updates the extension in place
+    env.write_sql_file("extensions.sql", "CREATE EXTENSION IF NOT EXISTS pg_trgm VERSION '1.4';").await?;
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_updated, vec!["pg_trgm".to_string()]);
+    assert_eq!(installed_version().await?, "1.4");
+    assert!(env.view_exists("word_scores").await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_refuses_to_rewrite_relations_over_size_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig {
+        destructive_size_limit: Some("16kB".to_string()),
+        ..PgmgConfig::default()
+    };
+    let forced = PgmgConfig { force: Some(true), ..config.clone() };
+    let apply = |config| execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        config,
+    );
+
+    env.write_migration("001_events", indoc! {r#"
+        CREATE TABLE events (id integer PRIMARY KEY, payload text);
+        INSERT INTO events SELECT i, repeat('x', 100) FROM generate_series(1, 1000) i;
+    "#}).await?;
+    env.write_sql_file("counts.sql", "CREATE MATERIALIZED VIEW event_counts AS SELECT count(*) AS events FROM events;").await?;
+    assert_apply_successful(&apply(&config).await?);
+
+    env.write_migration("002_widen_ids", "ALTER TABLE events ALTER COLUMN id TYPE bigint;").await?;
+
+    let plan = execute_plan_with_options(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions::default(),
+    ).await?;
+    let events = plan.relation_sizes.iter()
+        .find(|size| size.relation == "events")
+        .expect("the rewritten table is sized");
+    assert_eq!(events.operation, "column type change (table rewrite) (migration 002_widen_ids)");
+    assert!(events.bytes > 16 * 1024);
+    assert!(plan.relation_sizes.iter().any(|size| size.relation == "event_counts"
+        && size.operation == "DROP and recreate MATERIALIZED VIEW"));
+
+    let error = apply(&config).await.unwrap_err().to_string();
+    assert!(error.contains("destructive_size_limit (16 kB)"), "{}", error);
+    assert!(error.contains("events"), "{}", error);
+    assert_eq!(
+        env.query_scalar::<String>("SELECT data_type::text FROM information_schema.columns WHERE table_name = 'events' AND column_name = 'id'").await?,
+        "integer"
+    );
+
+    assert_apply_successful(&apply(&forced).await?);
+    assert_eq!(
+        env.query_scalar::<String>("SELECT data_type::text FROM information_schema.columns WHERE table_name = 'events' AND column_name = 'id'").await?,
+        "bigint"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_alters_publication_tables_in_place() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+    let published = || env.query_all::<String>(
+        "SELECT tablename::text FROM pg_publication_tables WHERE pubname = 'analytics' ORDER BY tablename"
+    );
+    let publication_oid = || env.query_scalar::<tokio_postgres::types::Oid>(
+        "SELECT oid FROM pg_publication WHERE pubname = 'analytics'"
+    );
+
+    env.write_migration("001_tables", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+        CREATE TABLE events (id integer PRIMARY KEY);
+    "#}).await?;
+    env.write_sql_file("publications.sql", "CREATE PUBLICATION analytics FOR TABLE orders;").await?;
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_created, vec!["analytics".to_string()]);
+    assert_eq!(published().await?, vec!["orders"]);
+    let oid = publication_oid().await?;
+
+    // A table created by a pending migration can be added in the same apply
+    env.write_migration("002_invoices", "CREATE TABLE invoices (id integer PRIMARY KEY);").await?;
+    env.write_sql_file("publications.sql", "CREATE PUBLICATION analytics FOR TABLE events, invoices;").await?;
+
+    let plan = execute_plan_with_options(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions::from_config(&config),
+    ).await?;
+    assert!(plan.changes.iter().all(|change| matches!(change, ChangeOperation::ApplyMigration { .. })));
+    assert_eq!(plan.publication_changes.len(), 1);
+    assert_eq!(plan.publication_changes[0].statements, vec![
+        "ALTER PUBLICATION analytics DROP TABLE orders",
+        "ALTER PUBLICATION analytics ADD TABLE events",
+        "ALTER PUBLICATION analytics ADD TABLE invoices",
+    ]);
+
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_updated, vec!["analytics".to_string()]);
+    assert_eq!(published().await?, vec!["events", "invoices"]);
+    assert_eq!(publication_oid().await?, oid, "The publication isn't recreated");
+
+    // Changing its options recreates it
+    env.write_sql_file("publications.sql", "CREATE PUBLICATION analytics FOR TABLE events, invoices WITH (publish = 'insert');").await?;
+    assert_apply_successful(&apply().await?);
+    assert_eq!(published().await?, vec!["events", "invoices"]);
+    assert_ne!(publication_oid().await?, oid);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_runs_hooks() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let sql = |sql: &str| HookConfigSection { sql: Some(sql.to_string()), ..HookConfigSection::default() };
+    let log = |event: &str| sql(&format!("INSERT INTO hook_log (event) VALUES ('{}')", event));
+    let config = PgmgConfig {
+        hooks: Some(HooksConfigSection {
+            pre_apply: Some(vec![sql("CREATE TABLE IF NOT EXISTS hook_log (id serial, event text)"), log("pre_apply")]),
+            pre_migration: Some(vec![log("pre_migration")]),
+            post_migration: Some(vec![log("post_migration")]),
+            post_apply: Some(vec![
+                log("post_apply"),
+                // Changes are committed by now, so this only warns
+                HookConfigSection { run: Some("exit 1".to_string()), ..HookConfigSection::default() },
+            ]),
+            ..HooksConfigSection::default()
+        }),
+        ..PgmgConfig::default()
+    };
+    let apply = |config| execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        config,
+    );
+    let events = || env.query_all::<String>("SELECT event FROM hook_log ORDER BY id");
+
+    env.write_migration("001_orders", "CREATE TABLE orders (id integer PRIMARY KEY);").await?;
+    env.write_sql_file("views.sql", "CREATE VIEW order_ids AS SELECT id FROM orders;").await?;
+    assert_apply_successful(&apply(&config).await?);
+    assert_eq!(events().await?, vec!["pre_apply", "pre_migration", "post_migration", "post_apply"]);
+
+    // Nothing to apply, so no hooks run
+    assert_apply_successful(&apply(&config).await?);
+    assert_eq!(events().await?.len(), 4);
+
+    // A failing pre_apply hook stops the apply before anything changes
+    let failing = PgmgConfig {
+        hooks: Some(HooksConfigSection { pre_apply: Some(vec![sql("SELECT 1 / 0")]), ..HooksConfigSection::default() }),
+        ..PgmgConfig::default()
+    };
+    env.write_migration("002_invoices", "CREATE TABLE invoices (id integer PRIMARY KEY);").await?;
+    let error = apply(&failing).await.unwrap_err().to_string();
+    assert!(error.contains("pre_apply hook failed"), "{}", error);
+    assert!(!env.table_exists("invoices").await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_backs_up_dropped_tables() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let backup_dir = env.temp_dir.path().join("backups");
+    let config = PgmgConfig {
+        backup_before_destructive: Some(true),
+        backup_dir: Some(backup_dir.clone()),
+        // The table is dropped by removing its file
+        allow_tables_in_code_dir: Some(true),
+        ..PgmgConfig::default()
+    };
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    env.write_sql_file("audit_log.sql", "CREATE TABLE audit_log (id integer PRIMARY KEY, entry text);").await?;
+    env.write_sql_file("entries.sql", "CREATE VIEW entries AS SELECT entry FROM audit_log;").await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.backup_path, None, "nothing was dropped");
+
+    env.execute_sql("INSERT INTO audit_log VALUES (1, 'kept in the backup')").await?;
+    env.delete_sql_file("entries.sql").await?;
+    env.delete_sql_file("audit_log.sql").await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    assert!(!env.table_exists("audit_log").await?);
+
+    let backup_path = result.backup_path.expect("the dropped table is backed up");
+    assert!(backup_path.starts_with(&backup_dir));
+    assert!(std::fs::metadata(&backup_path)?.len() > 0);
+
+    let recorded: Option<String> = env.query_scalar("SELECT backup_path FROM pgmg.pgmg_runs ORDER BY id DESC LIMIT 1").await?;
+    assert_eq!(recorded, Some(backup_path.display().to_string()));
+
+    // An apply failing after its backup still records it, in a file of its
+    // own even within the same second
+    env.write_sql_file("audit_log.sql", "CREATE TABLE audit_log (id integer PRIMARY KEY, entry text);").await?;
+    assert_apply_successful(&apply().await?);
+    env.delete_sql_file("audit_log.sql").await?;
+    env.write_sql_file("broken.sql", "CREATE VIEW broken AS SELECT * FROM missing_table;").await?;
+    let failed = apply().await;
+    assert!(failed.as_ref().map_or(true, |result| !result.errors.is_empty()));
+
+    let recorded: Option<String> = env.query_scalar("SELECT backup_path FROM pgmg.pgmg_runs ORDER BY id DESC LIMIT 1").await?;
+    let recorded = recorded.expect("the failed run records its backup");
+    assert_ne!(recorded, backup_path.display().to_string());
+    assert!(std::path::Path::new(&recorded).exists());
+    assert!(backup_path.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_backs_up_only_the_dropped_relation() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let backup_dir = env.temp_dir.path().join("backups");
+    let config = PgmgConfig {
+        backup_before_destructive: Some(true),
+        backup_dir: Some(backup_dir.clone()),
+        allow_tables_in_code_dir: Some(true),
+        ..PgmgConfig::default()
+    };
+    let apply = || execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    );
+
+    // pg_dump would take an unqualified or unquoted name as a pattern matching
+    // these too
+    env.execute_sql(indoc! {r#"
+        CREATE SCHEMA archive;
+        CREATE TABLE archive.audit_log (id integer);
+        CREATE TABLE "audit*log" (id integer);
+    "#}).await?;
+    env.write_sql_file("audit_log.sql", "CREATE TABLE audit_log (id integer PRIMARY KEY);").await?;
+    assert_apply_successful(&apply().await?);
+
+    env.delete_sql_file("audit_log.sql").await?;
+    let result = apply().await?;
+    assert_apply_successful(&result);
+    let backup_path = result.backup_path.expect("the dropped table is backed up");
+
+    let listing = std::process::Command::new("pg_restore").arg("--list").arg(&backup_path).output()?;
+    let listing = String::from_utf8_lossy(&listing.stdout);
+    let tables: Vec<&str> = listing.lines().filter(|line| line.contains(" TABLE ") && !line.contains(" TABLE DATA ")).collect();
+    assert_eq!(tables.len(), 1, "{}", listing);
+    assert!(tables[0].contains("public audit_log"), "{}", listing);
+
+    Ok(())
+}
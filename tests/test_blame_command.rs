@@ -0,0 +1,70 @@
+mod common;
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use common::TestEnvironment;
+use pgmg::analysis::SearchPath;
+use pgmg::commands::{execute_apply, execute_blame};
+use pgmg::config::PgmgConfig;
+use indoc::indoc;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .args(["-c", "user.name=pgmg", "-c", "user.email=pgmg@example.com"])
+        .args(args)
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("git is installed");
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+#[tokio::test]
+async fn test_blame_reports_commit_and_run() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+    let repo = env.temp_dir.path();
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders;
+    "#}).await?;
+    git(repo, &["init", "-q"]);
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "Add orders"]);
+    let first_commit = git(repo, &["rev-parse", "HEAD"]);
+
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+
+    let blame = execute_blame("order_totals".to_string(), env.connection_string.clone(), &SearchPath::default()).await?;
+    assert_eq!(blame.objects.len(), 1);
+    let view = &blame.objects[0];
+    assert_eq!(view.git_commit.as_deref(), Some(first_commit.as_str()));
+    assert_eq!(view.git_dirty, Some(false));
+    let first_run = view.run.as_ref().expect("the apply run is found");
+    assert_eq!(first_run.git_commit.as_deref(), Some(first_commit.as_str()));
+
+    let migration_commit: Option<String> = env.query_scalar(
+        "SELECT git_commit FROM pgmg.pgmg_migrations WHERE name = '001_orders'"
+    ).await?;
+    assert_eq!(migration_commit.as_deref(), Some(first_commit.as_str()));
+
+    // Applying an edit that isn't committed yet marks the object dirty
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders WHERE total > 0;
+    "#}).await?;
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+
+    let blame = execute_blame("public.order_totals".to_string(), env.connection_string.clone(), &SearchPath::default()).await?;
+    let view = &blame.objects[0];
+    assert_eq!(view.git_commit.as_deref(), Some(first_commit.as_str()));
+    assert_eq!(view.git_dirty, Some(true));
+    assert!(view.run.as_ref().unwrap().id > first_run.id);
+
+    assert!(execute_blame("missing_view".to_string(), env.connection_string.clone(), &SearchPath::default()).await.is_err());
+
+    Ok(())
+}
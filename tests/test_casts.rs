@@ -0,0 +1,142 @@
+use indoc::indoc;
+use pgmg::sql::ObjectType;
+use pgmg::commands::{execute_plan, execute_apply};
+use pgmg::config::PgmgConfig;
+
+mod common;
+use common::TestEnvironment;
+use common::assertions::{assert_plan_contains_create, assert_plan_contains_update, assert_plan_contains_delete};
+
+const CAST_NAME: &str = "(api.money AS pg_catalog.numeric)";
+
+#[tokio::test]
+async fn test_cast_lifecycle() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql("CREATE SCHEMA api").await?;
+
+    // The cast's file sorts first, so only its dependencies put the type and
+    // function before it
+    env.write_sql_file("a_cast.sql", indoc! {r#"
+        CREATE CAST (api.money AS numeric)
+        WITH FUNCTION api.money_to_numeric(api.money)
+        AS ASSIGNMENT;
+    "#}).await?;
+    env.write_sql_file("b_money.sql", indoc! {r#"
+        CREATE TYPE api.money AS (cents bigint);
+    "#}).await?;
+    env.write_sql_file("c_func.sql", indoc! {r#"
+        CREATE FUNCTION api.money_to_numeric(api.money) RETURNS numeric
+        AS 'SELECT $1.cents / 100.0'
+        LANGUAGE sql IMMUTABLE;
+    "#}).await?;
+
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    assert_plan_contains_create(&plan, ObjectType::Cast, CAST_NAME);
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+    let amount: String = env.query_scalar("SELECT (ROW(250)::api.money)::numeric::text").await?;
+    assert_eq!(amount, "2.5000000000000000");
+
+    // Making the cast implicit recreates it
+    env.write_sql_file("a_cast.sql", indoc! {r#"
+        CREATE CAST (api.money AS numeric)
+        WITH FUNCTION api.money_to_numeric(api.money)
+        AS IMPLICIT;
+    "#}).await?;
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    assert_plan_contains_update(&plan, ObjectType::Cast, CAST_NAME);
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let context: String = env.query_scalar(
+        "SELECT castcontext::text FROM pg_cast WHERE castsource = 'api.money'::regtype AND casttarget = 'numeric'::regtype"
+    ).await?;
+    assert_eq!(context, "i");
+
+    // Removing the file drops it
+    env.delete_sql_file("a_cast.sql").await?;
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    assert_plan_contains_delete(&plan, ObjectType::Cast, CAST_NAME);
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let exists: bool = env.query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_cast WHERE castsource = 'api.money'::regtype)"
+    ).await?;
+    assert!(!exists);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unchanged_cast_is_not_replanned() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    // One dot in the name, which isn't a schema separator
+    env.write_sql_file("mytype.sql", indoc! {r#"
+        CREATE TYPE mytype AS (v integer);
+
+        CREATE FUNCTION mytype_to_int(mytype) RETURNS integer
+        AS 'SELECT $1.v'
+        LANGUAGE sql IMMUTABLE;
+
+        CREATE CAST (mytype AS integer)
+        WITH FUNCTION mytype_to_int(mytype);
+    "#}).await?;
+
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    assert_plan_contains_create(&plan, ObjectType::Cast, "(mytype AS pg_catalog.int4)");
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    assert!(!plan.has_changes(), "{:?}", plan.changes);
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::TestEnvironment;
+use indoc::indoc;
+use pgmg::commands::execute_check_files;
+use pgmg::config::PgmgConfig;
+use pgmg::plpgsql_check::CheckPolicy;
+
+#[tokio::test]
+async fn test_check_from_files_rolls_back() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    if env.execute_sql("CREATE EXTENSION IF NOT EXISTS plpgsql_check").await.is_err() {
+        println!("Skipping test - plpgsql_check extension not available");
+        return Ok(());
+    }
+
+    env.write_migration("001_users", "CREATE TABLE users (id integer PRIMARY KEY, name text);").await?;
+    env.write_sql_file("user_email.sql", indoc! {r#"
+        CREATE FUNCTION user_email(user_id integer)
+        RETURNS text AS $$
+        BEGIN
+            RETURN (SELECT email FROM users WHERE id = user_id);
+        END;
+        $$ LANGUAGE plpgsql;
+    "#}).await?;
+
+    let result = execute_check_files(
+        env.connection_string.clone(),
+        None,
+        None,
+        true,
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        &PgmgConfig::default(),
+        &CheckPolicy::default(),
+    ).await?;
+
+    // users has no email column
+    assert!(result.errors_found > 0);
+    assert_eq!(result.failures, result.errors_found);
+    let error = &result.check_errors[0];
+    assert!(error.function_name.starts_with("user_email"), "{}", error.function_name);
+    assert!(error.source_file.as_deref().is_some_and(|file| file.ends_with("user_email.sql")));
+
+    // Nothing was applied
+    assert!(!env.table_exists("users").await?);
+    assert!(!env.function_exists("user_email").await?);
+    assert!(env.get_applied_migrations().await.unwrap_or_default().is_empty());
+
+    Ok(())
+}
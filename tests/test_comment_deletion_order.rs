@@ -142,6 +142,7 @@ COMMENT ON COLUMN test_table_comments.users.email IS 'User email address';
     config.code_dir = Some(code_dir.clone());
     config.development_mode = Some(false);
     config.check_plpgsql = Some(false);
+    config.allow_tables_in_code_dir = Some(true);
 
     // Apply initial state
     let result = apply_migrations_with_options(&config, None, Some(code_dir.clone())).await.unwrap();
@@ -1,7 +1,8 @@
 mod common;
 
 use common::{TestEnvironment, plan_output::*};
-use pgmg::commands::plan::{execute_plan, ChangeOperation};
+use pgmg::commands::plan::{execute_plan, execute_plan_with_options, ChangeOperation, PlanOptions};
+use pgmg::config::PgmgConfig;
 use pgmg::sql::ObjectType;
 use indoc::indoc;
 
@@ -101,6 +102,8 @@ async fn test_comment_display_with_function() -> Result<(), Box<dyn std::error::
 #[tokio::test]
 async fn test_comment_display_with_table() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // The commented table is declared in the code directory
+    let options = PlanOptions { allow_tables_in_code_dir: true, ..PlanOptions::default() };
     
     env.write_sql_file("table.sql", indoc! {r#"
         CREATE TABLE users (
@@ -114,11 +117,12 @@ async fn test_comment_display_with_table() -> Result<(), Box<dyn std::error::Err
         COMMENT ON COLUMN users.created_at IS 'When the user account was created';
     "#}).await?;
     
-    let plan = execute_plan(
+    let plan = execute_plan_with_options(
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
         None,
+        &options,
     ).await?;
     
     // Should have table + 3 comments (1 table, 2 columns)
@@ -201,6 +205,8 @@ async fn test_comment_display_with_updates() -> Result<(), Box<dyn std::error::E
 async fn test_comment_display_multiple_objects() -> Result<(), Box<dyn std::error::Error>> {
     // Test with multiple objects to ensure comments are matched to correct parents
     let env = TestEnvironment::new().await?;
+    // The commented table is declared in the code directory
+    let options = PlanOptions { allow_tables_in_code_dir: true, ..PlanOptions::default() };
     
     env.write_sql_file("objects.sql", indoc! {r#"
         -- First function
@@ -220,11 +226,12 @@ async fn test_comment_display_multiple_objects() -> Result<(), Box<dyn std::erro
         COMMENT ON TYPE status_enum IS 'Status enumeration type';
     "#}).await?;
     
-    let plan = execute_plan(
+    let plan = execute_plan_with_options(
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
         None,
+        &options,
     ).await?;
     
     // Should have 8 changes (4 objects + 4 comments)
@@ -262,6 +269,8 @@ async fn test_comment_display_multiple_objects() -> Result<(), Box<dyn std::erro
 #[tokio::test]
 async fn test_comment_display_with_schema() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // The commented table is declared in the code directory
+    let options = PlanOptions { allow_tables_in_code_dir: true, ..PlanOptions::default() };
     
     // Create schema first
     env.execute_sql("CREATE SCHEMA myschema").await?;
@@ -286,11 +295,12 @@ async fn test_comment_display_with_schema() -> Result<(), Box<dyn std::error::Er
         COMMENT ON TABLE myschema.data_table IS 'Stores processed data';
     "#}).await?;
     
-    let plan = execute_plan(
+    let plan = execute_plan_with_options(
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
         None,
+        &options,
     ).await?;
     
     // Verify comments include schema in their identifiers
@@ -415,6 +425,8 @@ async fn test_comment_update_without_error() -> Result<(), Box<dyn std::error::E
 #[tokio::test]
 async fn test_comment_deletion_sets_to_null() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // The commented table is declared in the code directory
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Initialize state tracking
     let state_manager = pgmg::db::StateManager::new(&env.client);
@@ -435,7 +447,7 @@ async fn test_comment_deletion_sets_to_null() -> Result<(), Box<dyn std::error::
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &Default::default(),
+        &config,
     ).await?;
     
     assert_eq!(apply_result.errors.len(), 0, "Should apply without errors");
@@ -461,7 +473,7 @@ async fn test_comment_deletion_sets_to_null() -> Result<(), Box<dyn std::error::
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &Default::default(),
+        &config,
     ).await?;
     
     assert_eq!(delete_result.errors.len(), 0, "Should apply without errors");
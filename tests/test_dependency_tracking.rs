@@ -207,6 +207,8 @@ async fn test_basic_trigger_function_dependency() -> Result<(), Box<dyn std::err
 #[tokio::test]
 async fn test_complex_multi_level_dependencies() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // Tables are declared in the code directory here
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Create a complex dependency chain:
     // base_table -> summary_view -> mat_view -> process_func -> update_trigger
@@ -285,7 +287,7 @@ async fn test_complex_multi_level_dependencies() -> Result<(), Box<dyn std::erro
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result);
@@ -330,7 +332,7 @@ async fn test_complex_multi_level_dependencies() -> Result<(), Box<dyn std::erro
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result2);
@@ -351,6 +353,8 @@ async fn test_complex_multi_level_dependencies() -> Result<(), Box<dyn std::erro
 #[tokio::test]
 async fn test_cross_schema_dependencies() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // Tables are declared in the code directory here
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Create schemas
     env.execute_sql("CREATE SCHEMA schema_a").await?;
@@ -390,7 +394,7 @@ async fn test_cross_schema_dependencies() -> Result<(), Box<dyn std::error::Erro
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result);
@@ -416,7 +420,7 @@ async fn test_cross_schema_dependencies() -> Result<(), Box<dyn std::error::Erro
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result2);
@@ -586,6 +590,8 @@ async fn test_soft_vs_hard_dependencies() -> Result<(), Box<dyn std::error::Erro
 #[tokio::test]
 async fn test_type_and_domain_dependencies() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // Tables are declared in the code directory here
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Create custom type
     let type_sql = indoc! {r#"
@@ -626,7 +632,7 @@ async fn test_type_and_domain_dependencies() -> Result<(), Box<dyn std::error::E
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result);
@@ -652,7 +658,7 @@ async fn test_type_and_domain_dependencies() -> Result<(), Box<dyn std::error::E
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result2);
@@ -671,6 +677,8 @@ async fn test_type_and_domain_dependencies() -> Result<(), Box<dyn std::error::E
 #[tokio::test]
 async fn test_partial_deletion_preserves_remaining_dependencies() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // Tables are declared in the code directory here
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Create chain: table_a -> func_b -> func_c -> view_d
     let table_sql = indoc! {r#"
@@ -712,7 +720,7 @@ async fn test_partial_deletion_preserves_remaining_dependencies() -> Result<(),
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result);
@@ -726,7 +734,7 @@ async fn test_partial_deletion_preserves_remaining_dependencies() -> Result<(),
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await;
     
     // Should fail due to dependency
@@ -829,6 +837,8 @@ async fn test_dependency_persistence_across_sessions() -> Result<(), Box<dyn std
 #[tokio::test]
 async fn test_complex_mixed_operations() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // Tables are declared in the code directory here
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Initial setup: Create base objects
     let table1_sql = indoc! {r#"
@@ -864,7 +874,7 @@ async fn test_complex_mixed_operations() -> Result<(), Box<dyn std::error::Error
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result1);
@@ -915,7 +925,7 @@ async fn test_complex_mixed_operations() -> Result<(), Box<dyn std::error::Error
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     
     assert_apply_successful(&apply_result2);
@@ -942,6 +952,8 @@ async fn test_complex_mixed_operations() -> Result<(), Box<dyn std::error::Error
 #[tokio::test]
 async fn test_performance_with_many_dependencies() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
+    // Tables are declared in the code directory here
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
     
     // Create 50 interconnected objects
     let num_objects = 50;
@@ -986,7 +998,7 @@ async fn test_performance_with_many_dependencies() -> Result<(), Box<dyn std::er
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     let create_duration = start.elapsed();
     
@@ -1008,7 +1020,7 @@ async fn test_performance_with_many_dependencies() -> Result<(), Box<dyn std::er
         None,
         Some(env.sql_dir.clone()),
         env.connection_string.clone(),
-        &PgmgConfig::default(),
+        &config,
     ).await?;
     let delete_duration = start.elapsed();
     
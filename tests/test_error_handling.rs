@@ -1,3 +1,6 @@
+mod common;
+
+use common::TestEnvironment;
 use pgmg::error::{PgmgError, ErrorContext};
 use std::path::PathBuf;
 
@@ -43,10 +46,15 @@ fn test_error_suggestions() {
     assert!(suggestion.unwrap().contains("Check if the path is correct"));
     
     // Test circular dependency suggestions
-    let circ_err = PgmgError::CircularDependency("A -> B -> A".to_string());
+    let circ_err = PgmgError::DependencyCycle {
+        path: vec!["A".to_string(), "B".to_string()],
+        description: "Circular dependency detected: A -> B -> A".to_string(),
+    };
     let suggestion = suggest_fix(&circ_err);
     assert!(suggestion.is_some());
-    assert!(suggestion.unwrap().contains("Circular dependency detected"));
+    let suggestion = suggestion.unwrap();
+    assert!(suggestion.contains("Circular dependency detected"));
+    assert!(suggestion.contains("A -> B -> A"));
 }
 
 #[test]
@@ -79,7 +87,10 @@ fn test_file_errors() {
 
 #[test]
 fn test_dependency_errors() {
-    let err = PgmgError::CircularDependency("A -> B -> C -> A".to_string());
+    let err = PgmgError::DependencyCycle {
+        path: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+        description: "Circular dependency detected: A -> B -> C -> A".to_string(),
+    };
     assert!(err.to_string().contains("Circular dependency"));
     assert!(err.to_string().contains("A -> B -> C -> A"));
     
@@ -91,14 +102,48 @@ fn test_dependency_errors() {
     assert!(err.to_string().contains("table_b"));
 }
 
-#[test]
-fn test_migration_errors() {
+#[tokio::test]
+async fn test_migration_errors() -> Result<(), Box<dyn std::error::Error>> {
+    // A real server error, as PgmgError::MigrationFailed carries one
+    let env = TestEnvironment::new().await?;
+    let pg_error = env.client.batch_execute("SELECT * FORM users").await.unwrap_err();
+
     let err = PgmgError::MigrationFailed {
         name: "001_initial".to_string(),
-        statement: 3,
-        message: "syntax error".to_string(),
+        statement_index: 3,
+        file: PathBuf::from("migrations/001_initial.sql"),
+        line: Some(7),
+        sql: "SELECT * FORM users".to_string(),
+        pg_error,
     };
     assert!(err.to_string().contains("001_initial"));
     assert!(err.to_string().contains("statement 3"));
     assert!(err.to_string().contains("syntax error"));
+
+    Ok(())
+}
+
+#[test]
+fn test_migration_parse_errors() {
+    let err = PgmgError::MigrationParse {
+        names: vec!["001_initial".to_string()],
+        message: "Migration 001_initial does not parse, line 3: syntax error at or near \"FORM\"".to_string(),
+    };
+    assert!(err.to_string().contains("001_initial"));
+    assert!(err.to_string().contains("line 3"));
+    assert!(err.to_string().contains("syntax error"));
+}
+
+#[test]
+fn test_lock_timeout_errors() {
+    let err = PgmgError::LockTimeout {
+        timeout: std::time::Duration::from_secs(30),
+        holders: vec!["pid 4242 (pgmg, idle in transaction, connected 95s ago)".to_string()],
+    };
+    assert!(err.to_string().contains("Could not acquire lock for apply operation after 30 seconds"));
+    assert!(err.to_string().contains("The lock is held by pid 4242 (pgmg, idle in transaction, connected 95s ago)."));
+
+    let err = PgmgError::LockTimeout { timeout: std::time::Duration::ZERO, holders: Vec::new() };
+    assert!(err.to_string().contains("without waiting"));
+    assert!(!err.to_string().contains("held by"));
 }
\ No newline at end of file
@@ -319,4 +319,65 @@ async fn test_aggregate_parameter_change_cleanup() -> Result<(), Box<dyn std::er
     assert!((result - 7.0).abs() < 0.001);
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_overloads_across_schemas_dropped_in_one_apply() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql("CREATE SCHEMA api").await?;
+
+    env.write_sql_file("format.sql", indoc! {r#"
+        CREATE FUNCTION format_label(label text) RETURNS text
+        LANGUAGE sql AS $$ SELECT upper(label) $$;
+
+        CREATE FUNCTION api.format_label(label text) RETURNS text
+        LANGUAGE sql AS $$ SELECT lower(label) $$;
+
+        CREATE PROCEDURE api.log_label(label text)
+        LANGUAGE sql AS $$ SELECT 1 $$;
+    "#}).await?;
+
+    let apply_result = execute_apply(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert_apply_successful(&apply_result);
+
+    // An extra overload created outside of pgmg
+    env.execute_sql("CREATE FUNCTION api.format_label(label integer) RETURNS text LANGUAGE sql AS $$ SELECT label::text $$").await?;
+
+    // Change the arguments of api.format_label and api.log_label and remove
+    // the public function
+    env.write_sql_file("format.sql", indoc! {r#"
+        CREATE FUNCTION api.format_label(label text, prefix text) RETURNS text
+        LANGUAGE sql AS $$ SELECT prefix || lower(label) $$;
+
+        CREATE PROCEDURE api.log_label(label text, level integer)
+        LANGUAGE sql AS $$ SELECT 1 $$;
+    "#}).await?;
+
+    let apply_result = execute_apply(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert_apply_successful(&apply_result);
+    assert_objects_updated(&apply_result, &["api.format_label", "api.log_label"]);
+    assert_objects_deleted(&apply_result, &["format_label"]);
+
+    // Only the new definitions are left
+    let count = env.query_scalar::<i64>(
+        "SELECT COUNT(*) FROM pg_proc WHERE proname IN ('format_label', 'log_label')"
+    ).await?;
+    assert_eq!(count, 2);
+
+    let new_function = env.query_scalar::<bool>(
+        "SELECT to_regprocedure('api.format_label(text, text)') IS NOT NULL"
+    ).await?;
+    assert!(new_function);
+
+    Ok(())
+}
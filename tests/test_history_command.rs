@@ -0,0 +1,56 @@
+mod common;
+
+use common::TestEnvironment;
+use pgmg::commands::{execute_apply, execute_history};
+use pgmg::config::PgmgConfig;
+use indoc::indoc;
+
+#[tokio::test]
+async fn test_history_lists_apply_runs() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+
+    // No apply has run, and looking doesn't create pgmg_runs
+    let history = execute_history(env.connection_string.clone(), 20).await?;
+    assert!(history.runs.is_empty());
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders;
+    "#}).await?;
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+
+    // A run that changes nothing is still recorded
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+
+    env.write_migration("002_broken", indoc! {r#"
+        ALTER TABLE missing_table ADD COLUMN note text;
+    "#}).await?;
+    assert!(execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await.is_err());
+
+    let history = execute_history(env.connection_string.clone(), 20).await?;
+    assert_eq!(history.runs.len(), 3);
+
+    let (failed, unchanged, first) = (&history.runs[0], &history.runs[1], &history.runs[2]);
+    assert!(first.succeeded);
+    assert_eq!(first.changes.migrations_applied, vec!["001_orders".to_string()]);
+    assert_eq!(first.changes.objects_created.len(), 1);
+    assert_eq!(first.pgmg_version, env!("CARGO_PKG_VERSION"));
+    assert!(!first.db_user.is_empty());
+    assert!(first.finished_at >= first.started_at);
+
+    assert!(unchanged.succeeded);
+    assert!(unchanged.changes.migrations_applied.is_empty() && unchanged.changes.objects_created.is_empty());
+
+    assert!(!failed.succeeded);
+    assert!(!failed.errors.is_empty());
+    assert!(failed.changes.migrations_applied.is_empty());
+
+    let history = execute_history(env.connection_string.clone(), 1).await?;
+    assert_eq!(history.runs.len(), 1);
+    assert_eq!(history.runs[0].id, failed.id);
+
+    Ok(())
+}
@@ -1,7 +1,8 @@
 mod common;
 
 use common::{TestEnvironment, assertions::*, fixtures};
-use pgmg::{apply_migrations, apply_migrations_with_options, PgmgConfig};
+use pgmg::{apply_migrations, apply_migrations_with_options, Pgmg, PgmgConfig};
+use pgmg::embedded::{migrate, MigrateOptions};
 use indoc::indoc;
 use tracing_subscriber;
 use tracing::info;
@@ -29,11 +30,41 @@ async fn test_apply_migrations_library_api() -> Result<(), Box<dyn std::error::E
         migrations_dir: Some(env.migrations_dir.clone()),
         code_dir: Some(env.sql_dir.clone()),
         seed_dir: None,
+        seed_dirs: None,
         output_graph: None,
         development_mode: Some(false),
         emit_notify_events: Some(false),
         check_plpgsql: Some(false),
+        replication_safe: Some(false),
+        plain_output: None,
+        force: None,
         tls: None,
+        lock_retry: None,
+        apply_lock: None,
+        skip_rules: None,
+        environment: None,
+        environments: None,
+        integrations: None,
+        postgrest_reload: None,
+        webhooks: None,
+        plpgsql_check: None,
+        allow_tables_in_code_dir: None,
+        lint: None,
+        ignore_formatting_changes: None,
+        preserve_privileges: None,
+        matview_refresh: None,
+        partition_removal: None,
+        partitions: None,
+        destructive_size_limit: None,
+        backup_before_destructive: None,
+        backup_dir: None,
+        managed_schemas: None,
+        ignored_schemas: None,
+        search_path: None,
+        tenants: None,
+        vars: None,
+        hooks: None,
+        tags: None,
     };
     
     // Execute apply - all output goes through tracing
@@ -90,11 +121,41 @@ async fn test_apply_migrations_with_custom_directories() -> Result<(), Box<dyn s
         migrations_dir: None,
         code_dir: None,
         seed_dir: None,
+        seed_dirs: None,
         output_graph: None,
         development_mode: Some(false),
         emit_notify_events: Some(false),
         check_plpgsql: Some(false),
+        replication_safe: Some(false),
+        plain_output: None,
+        force: None,
         tls: None,
+        lock_retry: None,
+        apply_lock: None,
+        skip_rules: None,
+        environment: None,
+        environments: None,
+        integrations: None,
+        postgrest_reload: None,
+        webhooks: None,
+        plpgsql_check: None,
+        allow_tables_in_code_dir: None,
+        lint: None,
+        ignore_formatting_changes: None,
+        preserve_privileges: None,
+        matview_refresh: None,
+        partition_removal: None,
+        partitions: None,
+        destructive_size_limit: None,
+        backup_before_destructive: None,
+        backup_dir: None,
+        managed_schemas: None,
+        ignored_schemas: None,
+        search_path: None,
+        tenants: None,
+        vars: None,
+        hooks: None,
+        tags: None,
     };
     
     // Apply with custom directories
@@ -133,11 +194,41 @@ async fn test_apply_migrations_handles_errors() -> Result<(), Box<dyn std::error
         migrations_dir: Some(env.migrations_dir.clone()),
         code_dir: Some(env.sql_dir.clone()),
         seed_dir: None,
+        seed_dirs: None,
         output_graph: None,
         development_mode: Some(false),
         emit_notify_events: Some(false),
         check_plpgsql: Some(false),
+        replication_safe: Some(false),
+        plain_output: None,
+        force: None,
         tls: None,
+        lock_retry: None,
+        apply_lock: None,
+        skip_rules: None,
+        environment: None,
+        environments: None,
+        integrations: None,
+        postgrest_reload: None,
+        webhooks: None,
+        plpgsql_check: None,
+        allow_tables_in_code_dir: None,
+        lint: None,
+        ignore_formatting_changes: None,
+        preserve_privileges: None,
+        matview_refresh: None,
+        partition_removal: None,
+        partitions: None,
+        destructive_size_limit: None,
+        backup_before_destructive: None,
+        backup_dir: None,
+        managed_schemas: None,
+        ignored_schemas: None,
+        search_path: None,
+        tenants: None,
+        vars: None,
+        hooks: None,
+        tags: None,
     };
     
     // This should fail
@@ -190,11 +281,41 @@ async fn test_apply_migrations_with_plpgsql_check() -> Result<(), Box<dyn std::e
         migrations_dir: Some(env.migrations_dir.clone()),
         code_dir: Some(env.sql_dir.clone()),
         seed_dir: None,
+        seed_dirs: None,
         output_graph: None,
         development_mode: Some(true),  // Enable development mode
         emit_notify_events: Some(false),
         check_plpgsql: Some(true),      // Enable plpgsql_check
+        replication_safe: Some(false),
+        plain_output: None,
+        force: None,
         tls: None,
+        lock_retry: None,
+        apply_lock: None,
+        skip_rules: None,
+        environment: None,
+        environments: None,
+        integrations: None,
+        postgrest_reload: None,
+        webhooks: None,
+        plpgsql_check: None,
+        allow_tables_in_code_dir: None,
+        lint: None,
+        ignore_formatting_changes: None,
+        preserve_privileges: None,
+        matview_refresh: None,
+        partition_removal: None,
+        partitions: None,
+        destructive_size_limit: None,
+        backup_before_destructive: None,
+        backup_dir: None,
+        managed_schemas: None,
+        ignored_schemas: None,
+        search_path: None,
+        tenants: None,
+        vars: None,
+        hooks: None,
+        tags: None,
     };
     
     let result = apply_migrations(&config).await?;
@@ -231,11 +352,41 @@ async fn test_migration_result_details() -> Result<(), Box<dyn std::error::Error
         migrations_dir: Some(env.migrations_dir.clone()),
         code_dir: Some(env.sql_dir.clone()),
         seed_dir: None,
+        seed_dirs: None,
         output_graph: None,
         development_mode: Some(false),
         emit_notify_events: Some(false),
         check_plpgsql: Some(false),
+        replication_safe: Some(false),
+        plain_output: None,
+        force: None,
         tls: None,
+        lock_retry: None,
+        apply_lock: None,
+        skip_rules: None,
+        environment: None,
+        environments: None,
+        integrations: None,
+        postgrest_reload: None,
+        webhooks: None,
+        plpgsql_check: None,
+        allow_tables_in_code_dir: None,
+        lint: None,
+        ignore_formatting_changes: None,
+        preserve_privileges: None,
+        matview_refresh: None,
+        partition_removal: None,
+        partitions: None,
+        destructive_size_limit: None,
+        backup_before_destructive: None,
+        backup_dir: None,
+        managed_schemas: None,
+        ignored_schemas: None,
+        search_path: None,
+        tenants: None,
+        vars: None,
+        hooks: None,
+        tags: None,
     };
     
     let result = apply_migrations(&config).await?;
@@ -264,11 +415,41 @@ async fn test_migration_idempotency() -> Result<(), Box<dyn std::error::Error>>
         migrations_dir: Some(env.migrations_dir.clone()),
         code_dir: Some(env.sql_dir.clone()),
         seed_dir: None,
+        seed_dirs: None,
         output_graph: None,
         development_mode: Some(false),
         emit_notify_events: Some(false),
         check_plpgsql: Some(false),
+        replication_safe: Some(false),
+        plain_output: None,
+        force: None,
         tls: None,
+        lock_retry: None,
+        apply_lock: None,
+        skip_rules: None,
+        environment: None,
+        environments: None,
+        integrations: None,
+        postgrest_reload: None,
+        webhooks: None,
+        plpgsql_check: None,
+        allow_tables_in_code_dir: None,
+        lint: None,
+        ignore_formatting_changes: None,
+        preserve_privileges: None,
+        matview_refresh: None,
+        partition_removal: None,
+        partitions: None,
+        destructive_size_limit: None,
+        backup_before_destructive: None,
+        backup_dir: None,
+        managed_schemas: None,
+        ignored_schemas: None,
+        search_path: None,
+        tenants: None,
+        vars: None,
+        hooks: None,
+        tags: None,
     };
     
     // First application
@@ -282,4 +463,71 @@ async fn test_migration_idempotency() -> Result<(), Box<dyn std::error::Error>>
     assert_eq!(result2.objects_updated.len(), 0);
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_embedded_migrate_uses_host_client() -> Result<(), Box<dyn std::error::Error>> {
+    init_test_tracing();
+
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_embedded", indoc! {r#"
+        CREATE TABLE embedded_test (id integer);
+    "#}).await?;
+    env.write_sql_file("embedded_view.sql", indoc! {r#"
+        CREATE VIEW embedded_view AS SELECT id FROM embedded_test;
+    "#}).await?;
+
+    // The host application owns this connection
+    let (mut client, connection) = tokio_postgres::connect(&env.connection_string, tokio_postgres::NoTls).await?;
+    tokio::spawn(connection);
+
+    let options = MigrateOptions::new(env.migrations_dir.clone(), env.sql_dir.clone());
+
+    let result = migrate(&mut client, &options).await?;
+    assert_apply_successful(&result);
+    assert_migrations_applied(&result, &["001_embedded"]);
+    assert!(env.view_exists("embedded_view").await?);
+
+    // The host connection stays usable and a second run is a no-op
+    let result = migrate(&mut client, &options).await?;
+    assert!(result.migrations_applied.is_empty());
+    assert!(result.objects_created.is_empty());
+
+    let count: i64 = client.query_one("SELECT count(*) FROM embedded_view", &[]).await?.get(0);
+    assert_eq!(count, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pgmg_facade_plans_and_applies() -> Result<(), Box<dyn std::error::Error>> {
+    init_test_tracing();
+
+    let env = TestEnvironment::new().await?;
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders;
+    "#}).await?;
+
+    let pgmg = Pgmg::builder()
+        .connection(env.connection_string.clone())
+        .migrations_dir(env.migrations_dir.clone())
+        .code_dir(env.sql_dir.clone())
+        .build()?;
+
+    let plan = pgmg.plan().await?;
+    assert!(plan.has_changes());
+    assert_eq!(plan.new_migrations, vec!["001_orders".to_string()]);
+
+    let result = pgmg.apply().await?;
+    assert_apply_successful(&result);
+    assert_migrations_applied(&result, &["001_orders"]);
+    assert!(env.view_exists("order_totals").await?);
+
+    assert!(!pgmg.plan().await?.has_changes());
+
+    Ok(())
+}
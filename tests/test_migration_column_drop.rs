@@ -1,7 +1,7 @@
 mod common;
 
 use common::{TestEnvironment, assertions::*};
-use pgmg::commands::execute_apply;
+use pgmg::commands::{execute_apply, execute_plan, ChangeOperation};
 use pgmg::config::PgmgConfig;
 use indoc::indoc;
 
@@ -348,3 +348,66 @@ async fn test_column_drop_with_materialized_view() -> Result<(), Box<dyn std::er
 
     Ok(())
 }
+
+/// Test that an unchanged view reading a column a migration retypes is
+/// recreated around it, with the plan naming the column
+#[tokio::test]
+async fn test_column_type_change_recreates_unchanged_view() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_create_scores", indoc! {r#"
+        CREATE TABLE scores (
+            id INT PRIMARY KEY,
+            player TEXT NOT NULL,
+            score INT NOT NULL
+        );
+    "#}).await?;
+
+    env.write_sql_file("leaderboard.sql", indoc! {r#"
+        CREATE VIEW leaderboard AS
+        SELECT player, score
+        FROM public.scores
+        ORDER BY score DESC;
+    "#}).await?;
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert_apply_successful(&result);
+
+    // Retype the column without touching the view
+    env.write_migration("002_widen_score", "ALTER TABLE scores ALTER COLUMN score TYPE bigint;").await?;
+
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    let reasons: Vec<&str> = plan.changes.iter().filter_map(|change| match change {
+        ChangeOperation::UpdateObject { object, reason, .. } if object.qualified_name.name == "leaderboard" => Some(reason.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(reasons, vec!["Migration drops or changes the type of public.scores.score"]);
+
+    let result2 = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert_apply_successful(&result2);
+    assert_eq!(result2.migrations_applied.len(), 1);
+    assert_eq!(result2.objects_updated.len(), 1);
+
+    let score_type: String = env.query_scalar(
+        "SELECT data_type FROM information_schema.columns
+         WHERE table_name = 'leaderboard' AND column_name = 'score'"
+    ).await?;
+    assert_eq!(score_type, "bigint");
+
+    Ok(())
+}
@@ -407,4 +407,113 @@ async fn test_operator_with_schema() -> Result<(), Box<dyn std::error::Error>> {
     assert!(exists);
     
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_operator_created_after_its_function() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    // The operator's file sorts first, so only its FUNCTION dependency
+    // puts the function before it
+    env.write_sql_file("a_op.sql", indoc! {r#"
+        CREATE OPERATOR ~~~ (
+            LEFTARG = text,
+            RIGHTARG = text,
+            FUNCTION = z_similar,
+            COMMUTATOR = ~~~
+        );
+    "#}).await?;
+    env.write_sql_file("z_func.sql", indoc! {r#"
+        CREATE FUNCTION z_similar(text, text) RETURNS boolean
+        AS 'SELECT lower($1) = lower($2)'
+        LANGUAGE sql IMMUTABLE;
+    "#}).await?;
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+    let similar: bool = env.query_scalar("SELECT 'Hello'::text ~~~ 'HELLO'::text").await?;
+    assert!(similar);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_operator_drop_leaves_other_overloads() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    // An operator of the same name for other types, not managed by pgmg
+    env.execute_sql(indoc! {r#"
+        CREATE FUNCTION text_plus(text, text) RETURNS text AS 'SELECT $1 || $2' LANGUAGE sql;
+        CREATE OPERATOR +++ (LEFTARG = text, RIGHTARG = text, FUNCTION = text_plus);
+    "#}).await?;
+
+    env.write_sql_file("func.sql", indoc! {r#"
+        CREATE FUNCTION int_plus(int, int) RETURNS int AS 'SELECT $1 + $2' LANGUAGE sql;
+    "#}).await?;
+    env.write_sql_file("op.sql", indoc! {r#"
+        CREATE OPERATOR +++ (
+            LEFTARG = int,
+            RIGHTARG = int,
+            FUNCTION = int_plus
+        );
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let signature: Option<String> = env.query_scalar(
+        "SELECT operator_signature FROM pgmg.pgmg_state WHERE object_type = 'operator' AND object_name = '+++'"
+    ).await?;
+    assert_eq!(signature.as_deref(), Some("+++(pg_catalog.int4, pg_catalog.int4)"));
+
+    // Changing the argument types drops the old operator
+    env.write_sql_file("op.sql", indoc! {r#"
+        CREATE OPERATOR +++ (
+            RIGHTARG = int,
+            FUNCTION = abs
+        );
+    "#}).await?;
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+    assert_plan_contains_update(&plan, ObjectType::Operator, "+++");
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let overloads: Vec<String> = env.query_all(
+        "SELECT oprleft::regtype::text || ', ' || oprright::regtype::text FROM pg_operator WHERE oprname = '+++' ORDER BY 1"
+    ).await?;
+    assert_eq!(overloads, vec!["-, integer", "text, text"]);
+
+    // Deleting it leaves the unmanaged one
+    env.delete_sql_file("op.sql").await?;
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let overloads: Vec<String> = env.query_all(
+        "SELECT oprleft::regtype::text || ', ' || oprright::regtype::text FROM pg_operator WHERE oprname = '+++' ORDER BY 1"
+    ).await?;
+    assert_eq!(overloads, vec!["text, text"]);
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use indoc::indoc;
+use pgmg::sql::ObjectType;
+use pgmg::commands::{execute_plan_with_options, execute_apply, execute_partitions, ChangeOperation, MaintenanceSchedule, PlanOptions};
+use pgmg::config::PgmgConfig;
+
+mod common;
+use common::TestEnvironment;
+use common::assertions::assert_plan_contains_delete;
+
+async fn write_partitioned_events(env: &TestEnvironment) -> Result<(), Box<dyn std::error::Error>> {
+    env.execute_sql("CREATE SCHEMA api").await?;
+
+    // The partitions' files sort first, so only their parent dependency puts
+    // the partitioned table before them
+    env.write_sql_file("a_events_2026_10.sql", indoc! {r#"
+        CREATE TABLE api.events_2026_10 PARTITION OF api.events
+        FOR VALUES FROM ('2026-10-01') TO ('2026-11-01');
+    "#}).await?;
+    env.write_sql_file("a_events_2026_11.sql", indoc! {r#"
+        CREATE TABLE api.events_2026_11 PARTITION OF api.events
+        FOR VALUES FROM ('2026-11-01') TO ('2026-12-01');
+    "#}).await?;
+    env.write_sql_file("b_events.sql", indoc! {r#"
+        CREATE TABLE api.events (
+            id bigint NOT NULL,
+            occurred_at timestamptz NOT NULL
+        ) PARTITION BY RANGE (occurred_at);
+    "#}).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_removed_partition_is_detached() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    write_partitioned_events(&env).await?;
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
+    let options = PlanOptions::from_config(&config);
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    ).await?;
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+    env.execute_sql("INSERT INTO api.events VALUES (1, '2026-10-15'), (2, '2026-11-15')").await?;
+
+    env.delete_sql_file("a_events_2026_10.sql").await?;
+    let plan = execute_plan_with_options(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &options,
+    ).await?;
+    assert_plan_contains_delete(&plan, ObjectType::Table, "api.events_2026_10");
+    assert!(plan.changes.iter().any(|change| matches!(
+        change,
+        ChangeOperation::DeleteObject { detach_from: Some(parent), .. } if parent == "api.events"
+    )));
+    // Detaching keeps the rows, so it isn't sized as destructive
+    assert!(plan.relation_sizes.is_empty(), "{:?}", plan.relation_sizes);
+
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    ).await?;
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+    // The partition is a table of its own now, keeping its row
+    let kept: i64 = env.query_scalar("SELECT count(*) FROM api.events_2026_10").await?;
+    assert_eq!(kept, 1);
+    let remaining: i64 = env.query_scalar("SELECT count(*) FROM api.events").await?;
+    assert_eq!(remaining, 1);
+    let attached: bool = env.query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_inherits WHERE inhrelid = 'api.events_2026_10'::regclass)"
+    ).await?;
+    assert!(!attached);
+
+    // And it is no longer tracked
+    let plan = execute_plan_with_options(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &options,
+    ).await?;
+    assert!(plan.changes.is_empty(), "{:?}", plan.changes);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_removed_partition_is_dropped_when_configured() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    write_partitioned_events(&env).await?;
+    let config = PgmgConfig {
+        partition_removal: Some("drop".to_string()),
+        allow_tables_in_code_dir: Some(true),
+        ..PgmgConfig::default()
+    };
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    ).await?;
+
+    env.delete_sql_file("a_events_2026_10.sql").await?;
+    let result = execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &config,
+    ).await?;
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert!(result.objects_deleted.contains(&"api.events_2026_10".to_string()));
+
+    let exists: bool = env.query_scalar("SELECT to_regclass('api.events_2026_10') IS NOT NULL").await?;
+    assert!(!exists);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partition_maintenance() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql(indoc! {r#"
+        CREATE SCHEMA api;
+        CREATE TABLE api.metrics (
+            recorded_at timestamptz NOT NULL,
+            value numeric
+        ) PARTITION BY RANGE (recorded_at);
+        CREATE TABLE api.metrics_2000_01 PARTITION OF api.metrics
+        FOR VALUES FROM ('2000-01-01') TO ('2000-02-01');
+    "#}).await?;
+    let config: PgmgConfig = toml::from_str(indoc! {r#"
+        [[partitions.tables]]
+        table = "api.metrics"
+        interval = "month"
+        premake = 2
+        retain = 6
+        expired = "drop"
+    "#})?;
+
+    let result = execute_partitions(env.connection_string.clone(), &config, true).await?;
+    assert_eq!(result.changes.iter().filter(|change| change.action == "create").count(), 3);
+    assert!(result.changes.iter().any(|change| change.action == "drop" && change.partition == "api.metrics_2000_01"));
+    let exists: bool = env.query_scalar("SELECT to_regclass('api.metrics_2000_01') IS NOT NULL").await?;
+    assert!(exists, "a dry run changes nothing");
+
+    let result = execute_partitions(env.connection_string.clone(), &config, false).await?;
+    assert!(result.failed.is_none(), "{:?}", result.failed);
+    assert_eq!(result.changes.len(), 4);
+    assert_eq!(result.schedule, MaintenanceSchedule::NotScheduled);
+
+    let partitions: i64 = env.query_scalar(
+        "SELECT count(*) FROM pg_inherits WHERE inhparent = 'api.metrics'::regclass"
+    ).await?;
+    assert_eq!(partitions, 3);
+    env.execute_sql("INSERT INTO api.metrics VALUES (now(), 1)").await?;
+
+    // Running it again finds nothing to do
+    let result = execute_partitions(env.connection_string.clone(), &config, false).await?;
+    assert!(result.changes.is_empty(), "{:?}", result.changes);
+
+    Ok(())
+}
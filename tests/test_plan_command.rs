@@ -1,8 +1,10 @@
 mod common;
 
 use common::{TestEnvironment, assertions::*, fixtures};
-use pgmg::commands::execute_plan;
-use pgmg::sql::ObjectType;
+use pgmg::commands::{execute_plan, execute_plan_with_options, execute_plan_offline, execute_snapshot_export, execute_apply, find_edited_migrations, ChangeOperation, PlanOptions};
+use pgmg::config::PgmgConfig;
+use pgmg::db::StateSnapshot;
+use pgmg::sql::{ObjectType, QualifiedIdent, SchemaFilter};
 use indoc::indoc;
 
 #[tokio::test]
@@ -25,6 +27,7 @@ async fn test_plan_empty_database_with_migrations() -> Result<(), Box<dyn std::e
     assert_eq!(plan.new_migrations.len(), 2);
     assert_plan_contains_migration(&plan, "001_initial_schema");
     assert_plan_contains_migration(&plan, "002_add_users");
+    assert!(plan.has_changes());
     
     Ok(())
 }
@@ -134,6 +137,81 @@ async fn test_plan_detects_modified_objects() -> Result<(), Box<dyn std::error::
     Ok(())
 }
 
+#[tokio::test]
+async fn test_plan_update_carries_previous_ddl() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric, status text);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS
+        SELECT id, total
+        FROM orders;
+    "#}).await?;
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &PgmgConfig::default()).await?;
+
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS
+        SELECT id, total, status
+        FROM orders;
+    "#}).await?;
+    let plan = execute_plan(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), None).await?;
+
+    let (object, old_ddl) = plan.changes.iter().find_map(|change| match change {
+        ChangeOperation::UpdateObject { object, old_ddl, .. } => Some((object, old_ddl.as_deref())),
+        _ => None,
+    }).expect("the view is updated");
+    let diff = pgmg::diff::unified_diff(old_ddl.expect("the applied DDL is stored"), &object.ddl_source());
+    assert!(diff.contains("-SELECT id, total\n+SELECT id, total, status\n"), "{}", diff);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plan_offline_matches_plan_against_database() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", "CREATE TABLE orders (id serial PRIMARY KEY, total numeric);").await?;
+    env.write_sql_file("order_totals.sql", "CREATE VIEW order_totals AS SELECT id, total FROM orders;").await?;
+    env.write_sql_file("big_orders.sql", "CREATE VIEW big_orders AS SELECT id FROM order_totals WHERE total > 100;").await?;
+    env.write_sql_file("legacy.sql", "CREATE VIEW legacy AS SELECT 1 AS id;").await?;
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &PgmgConfig::default()).await?;
+
+    let snapshot_path = env.temp_dir.path().join("pgmg-snapshot.json");
+    execute_snapshot_export(env.connection_string.clone(), snapshot_path.clone(), &PgmgConfig::default()).await?;
+
+    env.write_migration("002_order_status", "ALTER TABLE orders ADD COLUMN status text;").await?;
+    env.write_sql_file("order_totals.sql", "CREATE VIEW order_totals AS SELECT id, total, 0 AS tax FROM orders;").await?;
+    env.delete_sql_file("legacy.sql").await?;
+
+    let online = execute_plan(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), None).await?;
+    let offline = execute_plan_offline(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        &StateSnapshot::load(&snapshot_path)?,
+        None,
+        &PlanOptions::default(),
+    ).await?;
+
+    let describe = |change: &ChangeOperation| match change {
+        ChangeOperation::CreateObject { object, .. } => format!("create {}", object.qualified_name.name),
+        ChangeOperation::UpdateObject { object, .. } => format!("update {}", object.qualified_name.name),
+        ChangeOperation::DeleteObject { object_name, .. } => format!("delete {}", object_name),
+        ChangeOperation::ApplyMigration { name, .. } => format!("migrate {}", name),
+    };
+    let mut offline_changes: Vec<String> = offline.changes.iter().map(describe).collect();
+    offline_changes.sort();
+    assert_eq!(offline_changes, vec!["delete legacy", "migrate 002_order_status", "update big_orders", "update order_totals"]);
+
+    let mut online_changes: Vec<String> = online.changes.iter().map(describe).collect();
+    online_changes.sort();
+    assert_eq!(offline_changes, online_changes);
+    assert_eq!(offline.apply_order, online.apply_order);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_plan_detects_deleted_objects() -> Result<(), Box<dyn std::error::Error>> {
     let env = TestEnvironment::new().await?;
@@ -243,6 +321,7 @@ async fn test_plan_with_no_changes() -> Result<(), Box<dyn std::error::Error>> {
     
     // Should have no changes
     assert_plan_empty(&plan);
+    assert!(!plan.has_changes());
     
     Ok(())
 }
@@ -332,4 +411,242 @@ async fn test_plan_with_mixed_changes() -> Result<(), Box<dyn std::error::Error>
     assert_plan_contains_delete(&plan, ObjectType::View, "deleted_view");
     
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_plan_view_depending_on_pending_migration_table() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_add_orders", indoc! {"
+        CREATE TABLE orders (
+            id bigserial PRIMARY KEY,
+            total numeric NOT NULL
+        );
+    "}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {"
+        CREATE VIEW order_totals AS
+        SELECT sum(total) AS total FROM orders;
+    "}).await?;
+
+    let plan = execute_plan(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+    ).await?;
+
+    assert_eq!(plan.migration_objects.len(), 1);
+    assert_eq!(plan.migration_objects[0].0, "001_add_orders");
+
+    // The view is ordered after the table created by the migration
+    let graph = plan.dependency_graph.as_ref().unwrap();
+    let order = graph.creation_order()?;
+    let table_pos = order.iter().position(|obj| obj.object_type == ObjectType::Table).unwrap();
+    let view_pos = order.iter().position(|obj| obj.object_type == ObjectType::View).unwrap();
+    assert!(table_pos < view_pos);
+
+    let waiting = pgmg::commands::migration_dependencies(&plan);
+    assert_eq!(waiting.len(), 1);
+    assert_eq!(waiting[0].2, "001_add_orders");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_code_dir_tables_are_refused_unless_allowed() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    
+    let plan = |options: PlanOptions| {
+        let env = &env;
+        async move {
+            execute_plan_with_options(None, Some(env.sql_dir.clone()), env.connection_string.clone(), None, &options).await
+        }
+    };
+    
+    env.write_sql_file("views/active_orders.sql", "CREATE VIEW active_orders AS SELECT 1 AS id;").await?;
+    plan(PlanOptions::default()).await?;
+    
+    env.write_sql_file("tables/orders.sql", "CREATE TABLE orders (id integer PRIMARY KEY);").await?;
+    let err = plan(PlanOptions::default()).await.unwrap_err();
+    assert!(matches!(err, pgmg::error::PgmgError::Configuration(_)), "{:?}", err);
+    let err = err.to_string();
+    assert!(err.contains("CREATE TABLE found in the code directory"));
+    assert!(err.contains("orders.sql:1"));
+    assert!(err.contains("allow_tables_in_code_dir"));
+    
+    // Applying is refused the same way
+    let err = execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &PgmgConfig::default()).await.unwrap_err();
+    assert!(err.to_string().contains("CREATE TABLE found in the code directory"));
+    assert!(!env.table_exists("orders").await?);
+    
+    let config = PgmgConfig { allow_tables_in_code_dir: Some(true), ..PgmgConfig::default() };
+    plan(PlanOptions::from_config(&config)).await?;
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plan_and_apply_filtered_by_tag() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_sql_file("billing.sql", indoc! {r#"
+        -- pgmg:tags billing, critical
+        CREATE VIEW invoices AS SELECT 1 AS id;
+    "#}).await?;
+    env.write_sql_file("search.sql", "CREATE VIEW search_terms AS SELECT 'term' AS term;").await?;
+
+    let config = PgmgConfig::default().with_tags(vec!["billing".to_string()]);
+    let result = execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_created, vec!["invoices".to_string()]);
+
+    let tags: Vec<String> = env.query_scalar("SELECT tags FROM pgmg.pgmg_state WHERE object_name = 'invoices'").await?;
+    assert_eq!(tags, vec!["billing", "critical"]);
+
+    // The untagged view is still pending without the filter
+    let plan = |tags: &[&str]| execute_plan_with_options(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions { tags: tags.iter().map(|tag| tag.to_string()).collect(), ..PlanOptions::default() },
+    );
+    assert!(plan(&["billing"]).await?.changes.is_empty());
+    assert_eq!(plan(&[]).await?.changes.len(), 1);
+
+    // Drops are filtered by the tags the object was applied with
+    env.delete_sql_file("billing.sql").await?;
+    let changes = plan(&["critical"]).await?.changes;
+    assert!(matches!(&changes[..], [ChangeOperation::DeleteObject { object_name, .. }] if object_name == "invoices"));
+    assert!(plan(&["search"]).await?.changes.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plan_and_apply_limited_to_managed_schemas() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql("CREATE SCHEMA api; CREATE SCHEMA analytics; CREATE TABLE analytics.events (id integer)").await?;
+
+    env.write_sql_file("api.sql", "CREATE VIEW api.orders AS SELECT 1 AS id;").await?;
+    env.write_sql_file("analytics.sql", indoc! {r#"
+        CREATE VIEW analytics.daily AS SELECT 1 AS id;
+        CREATE INDEX events_id_idx ON analytics.events (id);
+    "#}).await?;
+
+    let config = PgmgConfig {
+        ignored_schemas: Some(vec!["analytics".to_string()]),
+        ..PgmgConfig::default()
+    };
+    let result = execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_created, vec!["api.orders".to_string()]);
+
+    // The analytics objects are applied without the filter, then left alone
+    // by a plan that ignores the schema even once removed from code
+    let plan = |filter: SchemaFilter| execute_plan_with_options(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions { schemas: filter, ..PlanOptions::default() },
+    );
+    assert_eq!(plan(config.schema_filter()).await?.changes.len(), 0);
+    let result = execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &PgmgConfig::default()).await?;
+    assert_apply_successful(&result);
+    assert_eq!(result.objects_created.len(), 2);
+
+    env.delete_sql_file("analytics.sql").await?;
+    assert!(plan(SchemaFilter::new(vec!["api".to_string()], Vec::new())).await?.changes.is_empty());
+    assert_eq!(plan(SchemaFilter::default()).await?.changes.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unqualified_references_resolve_through_search_path() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql(&format!(
+        "CREATE SCHEMA api; CREATE SCHEMA sales; ALTER DATABASE {} SET search_path = sales, public",
+        env.database_name
+    )).await?;
+
+    env.write_sql_file("api.sql", "CREATE VIEW api.summary AS SELECT id FROM orders;").await?;
+    env.write_sql_file("orders.sql", indoc! {r#"
+        CREATE VIEW public.orders AS SELECT 1 AS id;
+        CREATE VIEW sales.orders AS SELECT 2 AS id;
+    "#}).await?;
+
+    let config = PgmgConfig {
+        search_path: Some(vec!["sales".to_string(), "public".to_string()]),
+        ..PgmgConfig::default()
+    };
+    let plan = execute_plan_with_options(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions::from_config(&config),
+    ).await?;
+    let graph = plan.dependency_graph.as_ref().unwrap();
+    let summary = graph.find_by_name(&QualifiedIdent::new(Some("api".to_string()), "summary".to_string()));
+    let dependencies: Vec<_> = graph.dependencies_of(&summary[0]).into_iter()
+        .map(|dependency| dependency.qualified_name.schema)
+        .collect();
+    assert_eq!(dependencies, vec![Some("sales".to_string())]);
+
+    // Created after sales.orders, though api sorts first
+    let result = execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+    assert_apply_successful(&result);
+    assert_eq!(env.query_scalar::<i32>("SELECT id FROM api.summary").await?, 2);
+
+    env.write_sql_file("orders.sql", indoc! {r#"
+        CREATE VIEW public.orders AS SELECT 1 AS id;
+        CREATE VIEW sales.orders AS SELECT 3 AS id, 'open' AS status;
+    "#}).await?;
+    let plan = execute_plan_with_options(
+        None,
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        None,
+        &PlanOptions::from_config(&config),
+    ).await?;
+    let mut updated: Vec<(Option<String>, String)> = plan.changes.iter()
+        .filter_map(|change| match change {
+            ChangeOperation::UpdateObject { object, .. } => Some((object.qualified_name.schema.clone(), object.qualified_name.name.clone())),
+            _ => None,
+        })
+        .collect();
+    updated.sort();
+    assert_eq!(updated, vec![
+        (Some("api".to_string()), "summary".to_string()),
+        (Some("sales".to_string()), "orders".to_string()),
+    ]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_edited_migrations() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.write_migration("001_users", "CREATE TABLE users (id integer PRIMARY KEY);").await?;
+    env.write_migration("002_orders", "CREATE TABLE orders (id integer PRIMARY KEY);").await?;
+
+    let result = execute_apply(Some(env.migrations_dir.clone()), None, env.connection_string.clone(), &PgmgConfig::default()).await?;
+    assert_apply_successful(&result);
+    assert!(find_edited_migrations(&env.client, &env.migrations_dir).await?.is_empty());
+
+    // Editing or removing an applied migration is noticed; a new one is just pending
+    env.write_migration("001_users", "CREATE TABLE users (id bigint PRIMARY KEY);").await?;
+    std::fs::remove_file(env.migrations_dir.join("002_orders.sql"))?;
+    env.write_migration("003_items", "CREATE TABLE items (id integer PRIMARY KEY);").await?;
+    assert_eq!(
+        find_edited_migrations(&env.client, &env.migrations_dir).await?,
+        vec!["001_users".to_string(), "002_orders".to_string()]
+    );
+
+    // Migrations applied without a recorded hash can't be checked
+    env.execute_sql("UPDATE pgmg.pgmg_migrations SET content_hash = NULL WHERE name = '001_users'").await?;
+    assert_eq!(find_edited_migrations(&env.client, &env.migrations_dir).await?, vec!["002_orders".to_string()]);
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use common::TestEnvironment;
+use pgmg::commands::{execute_seed, execute_seed_reset, SeedOptions, SeedResetOptions, SeedTransactionMode};
+use indoc::indoc;
+
+fn write_seed(env: &TestEnvironment, name: &str, content: &str) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let seed_dir = env.temp_dir.path().join("seeds");
+    std::fs::create_dir_all(&seed_dir)?;
+    std::fs::write(seed_dir.join(name), content)?;
+    Ok(seed_dir)
+}
+
+#[tokio::test]
+async fn test_seed_reports_rows_per_file() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql("CREATE TABLE users (id integer PRIMARY KEY, name text); CREATE TABLE orders (id integer PRIMARY KEY, user_id integer REFERENCES users)").await?;
+
+    // 10_orders.sql needs the users from 2_users.sql
+    write_seed(&env, "10_orders.sql", "INSERT INTO orders VALUES (1, 1), (2, 2), (3, 1);")?;
+    let seed_dir = write_seed(&env, "2_users.sql", indoc! {r#"
+        INSERT INTO users VALUES (1, 'alice');
+        INSERT INTO users VALUES (2, 'bob');
+    "#})?;
+    write_seed(&env, "3_demo_users.sql", "INSERT INTO users VALUES (3, 'demo');")?;
+
+    let options = SeedOptions {
+        skip: vec!["*demo*".to_string()],
+        ..Default::default()
+    };
+    let result = execute_seed(seed_dir, env.connection_string.clone(), &options).await?;
+
+    let processed: Vec<(&str, u64)> = result.files_processed.iter()
+        .map(|file| (file.file_name.as_str(), file.rows_affected))
+        .collect();
+    assert_eq!(processed, vec![("2_users.sql", 2), ("10_orders.sql", 3)]);
+    assert_eq!(result.files_skipped, vec!["3_demo_users.sql".to_string()]);
+
+    let users: i64 = env.query_scalar("SELECT count(*) FROM users").await?;
+    assert_eq!(users, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_seed_transaction_per_file_keeps_earlier_files() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql("CREATE TABLE users (id integer PRIMARY KEY, name text)").await?;
+
+    write_seed(&env, "1_users.sql", "INSERT INTO users VALUES (1, 'alice');")?;
+    let seed_dir = write_seed(&env, "2_broken.sql", indoc! {r#"
+        INSERT INTO users VALUES (2, 'bob');
+        INSERT INTO missing_table VALUES (1);
+    "#})?;
+
+    // In one transaction nothing is kept
+    assert!(execute_seed(seed_dir.clone(), env.connection_string.clone(), &SeedOptions::default()).await.is_err());
+    let users: i64 = env.query_scalar("SELECT count(*) FROM users").await?;
+    assert_eq!(users, 0);
+
+    // Per file, the files before the failing one stay committed
+    let options = SeedOptions {
+        transaction_mode: SeedTransactionMode::PerFile,
+        ..Default::default()
+    };
+    assert!(execute_seed(seed_dir, env.connection_string.clone(), &options).await.is_err());
+    let users: Vec<String> = env.query_all("SELECT name FROM users").await?;
+    assert_eq!(users, vec!["alice".to_string()]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tracked_seeds_only_rerun_when_changed() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql("CREATE TABLE settings (key text PRIMARY KEY, value text)").await?;
+
+    let seed_dir = write_seed(&env, "1_settings.sql", indoc! {r#"
+        INSERT INTO settings VALUES ('theme', 'dark')
+        ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value;
+    "#})?;
+    write_seed(&env, "2_flags.sql", "INSERT INTO settings VALUES ('beta', 'off') ON CONFLICT DO NOTHING;")?;
+
+    let options = SeedOptions {
+        track: true,
+        ..Default::default()
+    };
+
+    let result = execute_seed(seed_dir.clone(), env.connection_string.clone(), &options).await?;
+    assert_eq!(result.files_processed.len(), 2);
+
+    // Nothing changed, nothing runs
+    let result = execute_seed(seed_dir.clone(), env.connection_string.clone(), &options).await?;
+    assert!(result.files_processed.is_empty());
+    assert_eq!(result.files_unchanged, vec!["1_settings.sql".to_string(), "2_flags.sql".to_string()]);
+
+    // A changed file runs again
+    write_seed(&env, "1_settings.sql", indoc! {r#"
+        INSERT INTO settings VALUES ('theme', 'light')
+        ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value;
+    "#})?;
+    let result = execute_seed(seed_dir.clone(), env.connection_string.clone(), &options).await?;
+    let processed: Vec<&str> = result.files_processed.iter().map(|file| file.file_name.as_str()).collect();
+    assert_eq!(processed, vec!["1_settings.sql"]);
+    let theme: String = env.query_scalar("SELECT value FROM settings WHERE key = 'theme'").await?;
+    assert_eq!(theme, "light");
+
+    // --force runs everything
+    let options = SeedOptions {
+        track: true,
+        force: true,
+        ..Default::default()
+    };
+    let result = execute_seed(seed_dir, env.connection_string.clone(), &options).await?;
+    assert_eq!(result.files_processed.len(), 2);
+
+    let tracked: i64 = env.query_scalar("SELECT count(*) FROM pgmg.pgmg_seeds").await?;
+    assert_eq!(tracked, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_seed_loads_csv_files_with_copy() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql(indoc! {r#"
+        CREATE SCHEMA sales;
+        CREATE TABLE sales.customers (id integer PRIMARY KEY, name text, email text);
+    "#}).await?;
+
+    // Columns are matched by the header, not by position
+    let seed_dir = write_seed(&env, "1_sales.customers.csv", indoc! {r#"
+        email,id,name
+        alice@example.com,1,Alice
+        bob@example.com,2,"Bob, Jr."
+    "#})?;
+    write_seed(&env, "2_flag_vip.sql", "UPDATE sales.customers SET name = name || ' (VIP)' WHERE id = 1;")?;
+
+    let result = execute_seed(seed_dir.clone(), env.connection_string.clone(), &SeedOptions::default()).await?;
+    let processed: Vec<(&str, u64)> = result.files_processed.iter()
+        .map(|file| (file.file_name.as_str(), file.rows_affected))
+        .collect();
+    assert_eq!(processed, vec![("1_sales.customers.csv", 2), ("2_flag_vip.sql", 1)]);
+
+    let name: String = env.query_scalar("SELECT name FROM sales.customers WHERE id = 2").await?;
+    assert_eq!(name, "Bob, Jr.");
+
+    // Loading again fails on the primary key unless the table is emptied first
+    assert!(execute_seed(seed_dir.clone(), env.connection_string.clone(), &SeedOptions::default()).await.is_err());
+
+    let options = SeedOptions {
+        only: vec!["1_sales.customers".to_string()],
+        truncate_csv: true,
+        ..Default::default()
+    };
+    execute_seed(seed_dir, env.connection_string.clone(), &options).await?;
+    let customers: i64 = env.query_scalar("SELECT count(*) FROM sales.customers").await?;
+    assert_eq!(customers, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_seed_reset_empties_seeded_tables_in_foreign_key_order() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.execute_sql(indoc! {r#"
+        CREATE TABLE users (id serial PRIMARY KEY, name text);
+        CREATE TABLE orders (id integer PRIMARY KEY, user_id integer REFERENCES users);
+        CREATE TABLE settings (name text PRIMARY KEY, value text);
+        INSERT INTO settings VALUES ('theme', 'dark');
+    "#}).await?;
+
+    write_seed(&env, "1_users.sql", "INSERT INTO users (name) VALUES ('alice'), ('bob');")?;
+    let seed_dir = write_seed(&env, "2_orders.sql", indoc! {r#"
+        INSERT INTO orders VALUES (1, 1), (2, 2);
+        UPDATE settings SET value = 'light';
+    "#})?;
+    execute_seed(seed_dir.clone(), env.connection_string.clone(), &SeedOptions::default()).await?;
+
+    let dry_run = SeedResetOptions { dry_run: true, ..Default::default() };
+    let result = execute_seed_reset(seed_dir.clone(), env.connection_string.clone(), &dry_run).await?;
+    assert_eq!(result.tables, vec!["orders".to_string(), "users".to_string()]);
+    assert_eq!(result.statements, vec!["TRUNCATE orders, users".to_string()]);
+    let orders: i64 = env.query_scalar("SELECT count(*) FROM orders").await?;
+    assert_eq!(orders, 2);
+
+    let options = SeedResetOptions { restart_identity: true, delete: true, ..Default::default() };
+    let result = execute_seed_reset(seed_dir.clone(), env.connection_string.clone(), &options).await?;
+    assert_eq!(result.statements[..2], ["DELETE FROM orders".to_string(), "DELETE FROM users".to_string()]);
+    assert!(result.statements[2].starts_with("ALTER SEQUENCE users_id_seq RESTART"));
+
+    let users: i64 = env.query_scalar("SELECT count(*) FROM users").await?;
+    assert_eq!(users, 0);
+    // Only updated by the seeds, so left alone
+    let settings: i64 = env.query_scalar("SELECT count(*) FROM settings").await?;
+    assert_eq!(settings, 1);
+
+    // Reseeding starts the ids over
+    execute_seed(seed_dir.clone(), env.connection_string.clone(), &SeedOptions::default()).await?;
+    let max_id: i32 = env.query_scalar("SELECT max(id) FROM users").await?;
+    assert_eq!(max_id, 2);
+
+    // A table outside the seeds referencing a seeded one stops TRUNCATE
+    env.execute_sql("CREATE TABLE reviews (id integer PRIMARY KEY, user_id integer REFERENCES users)").await?;
+    let err = execute_seed_reset(seed_dir, env.connection_string.clone(), &SeedResetOptions::default()).await.unwrap_err();
+    assert!(err.to_string().contains("reviews references users"));
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::TestEnvironment;
+use pgmg::commands::{execute_apply, execute_show, ObjectStatus};
+use pgmg::analysis::SearchPath;
+use pgmg::config::PgmgConfig;
+use pgmg::sql::ObjectType;
+use indoc::indoc;
+
+#[tokio::test]
+async fn test_show_reports_state_and_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders;
+        CREATE VIEW big_orders AS SELECT id FROM order_totals WHERE total > 100;
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let result = execute_show("order_totals".to_string(), Some(env.sql_dir.clone()), env.connection_string.clone(), &SearchPath::default()).await?;
+    assert_eq!(result.objects.len(), 1);
+
+    let shown = &result.objects[0];
+    assert_eq!(shown.object_type, ObjectType::View);
+    assert_eq!(shown.status, ObjectStatus::UpToDate);
+    assert_eq!(shown.start_line, Some(1));
+    assert!(shown.last_applied.is_some());
+    assert!(shown.applied_ddl.as_deref().unwrap().starts_with("CREATE VIEW order_totals"));
+    assert!(shown.dependencies.iter().any(|dep| dep.name == "orders" && dep.is_hard));
+    assert!(shown.dependents.iter().any(|dep| dep.name == "big_orders"));
+
+    // Editing the file shows the object as modified
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders WHERE total IS NOT NULL;
+        CREATE VIEW big_orders AS SELECT id FROM order_totals WHERE total > 100;
+    "#}).await?;
+
+    let result = execute_show("public.order_totals".to_string(), Some(env.sql_dir.clone()), env.connection_string.clone(), &SearchPath::default()).await?;
+    assert_eq!(result.objects[0].status, ObjectStatus::Modified);
+    assert_ne!(result.objects[0].file_hash, result.objects[0].applied_hash);
+
+    // Unknown objects are an error
+    assert!(execute_show("missing_view".to_string(), None, env.connection_string.clone(), &SearchPath::default()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_show_compares_stored_state_with_files_and_catalog() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+        CREATE TABLE customers (id integer PRIMARY KEY);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", "CREATE VIEW order_totals AS SELECT id, total FROM orders;").await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let result = execute_show("order_totals".to_string(), Some(env.sql_dir.clone()), env.connection_string.clone(), &SearchPath::default()).await?;
+    let shown = &result.objects[0];
+    assert_eq!(shown.exists_in_database, Some(true));
+    assert_eq!(shown.dependency_drift(), Some((vec![], vec![])));
+
+    // The file now reads a different table, and the view was dropped by hand
+    env.write_sql_file("order_totals.sql", "CREATE VIEW order_totals AS SELECT id FROM customers;").await?;
+    env.execute_sql("DROP VIEW order_totals").await?;
+
+    let result = execute_show("order_totals".to_string(), Some(env.sql_dir.clone()), env.connection_string.clone(), &SearchPath::default()).await?;
+    let shown = &result.objects[0];
+    assert_eq!(shown.exists_in_database, Some(false));
+    assert_eq!(shown.dependency_drift(), Some((vec!["customers"], vec!["orders"])));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_show_resolves_unqualified_names_through_search_path() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE SCHEMA api;
+        CREATE TABLE api.orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW api.order_totals AS SELECT id, total FROM api.orders;
+        CREATE VIEW api.big_orders AS SELECT id FROM api.order_totals WHERE total > 100;
+    "#}).await?;
+
+    execute_apply(
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        env.connection_string.clone(),
+        &PgmgConfig::default(),
+    ).await?;
+
+    // Without api on the search path the unqualified name means public.order_totals
+    assert!(execute_show("order_totals".to_string(), Some(env.sql_dir.clone()), env.connection_string.clone(), &SearchPath::default()).await.is_err());
+
+    let search_path = SearchPath::new(vec!["api".to_string(), "public".to_string()]);
+    let result = execute_show("order_totals".to_string(), Some(env.sql_dir.clone()), env.connection_string.clone(), &search_path).await?;
+    assert_eq!(result.objects.len(), 1);
+    let shown = &result.objects[0];
+    assert_eq!(shown.status, ObjectStatus::UpToDate);
+    assert!(shown.dependencies.iter().any(|dep| dep.name == "api.orders"));
+    assert!(shown.dependents.iter().any(|dep| dep.name == "api.big_orders"));
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::TestEnvironment;
+use pgmg::commands::execute_apply;
+use pgmg::config::PgmgConfig;
+use pgmg::db::{StateManager, STATE_SCHEMA_VERSION};
+use pgmg::sql::{ObjectType, QualifiedIdent};
+
+#[tokio::test]
+async fn test_initialize_records_schema_version() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let state_manager = StateManager::new(&env.client);
+
+    assert_eq!(state_manager.schema_version().await?, 0);
+
+    state_manager.initialize().await?;
+    assert_eq!(state_manager.schema_version().await?, STATE_SCHEMA_VERSION);
+
+    // Running again is a no-op
+    state_manager.initialize().await?;
+    let upgrades: i64 = env.query_scalar("SELECT count(*) FROM pgmg.pgmg_meta").await?;
+    assert_eq!(upgrades, STATE_SCHEMA_VERSION as i64);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_upgrades_install_without_meta_table() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+
+    // State tables as created by pgmg versions before pgmg_meta
+    env.execute_sql(r#"
+        CREATE SCHEMA pgmg;
+        CREATE TABLE pgmg.pgmg_migrations (
+            name TEXT PRIMARY KEY,
+            applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        );
+        CREATE TABLE pgmg.pgmg_state (
+            object_type TEXT NOT NULL,
+            object_name TEXT NOT NULL,
+            ddl_hash TEXT NOT NULL,
+            last_applied TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (object_type, object_name)
+        );
+        CREATE TABLE pgmg.pgmg_dependencies (
+            dependent_type TEXT NOT NULL,
+            dependent_name TEXT NOT NULL,
+            dependency_type TEXT NOT NULL,
+            dependency_name TEXT NOT NULL,
+            dependency_kind TEXT NOT NULL,
+            PRIMARY KEY (dependent_type, dependent_name, dependency_type, dependency_name)
+        );
+        INSERT INTO pgmg.pgmg_migrations (name) VALUES ('001_initial');
+        INSERT INTO pgmg.pgmg_state (object_type, object_name, ddl_hash) VALUES ('view', 'active_users', 'abc123');
+    "#).await?;
+
+    let state_manager = StateManager::new(&env.client);
+    state_manager.initialize().await?;
+
+    assert_eq!(state_manager.schema_version().await?, STATE_SCHEMA_VERSION);
+    assert_eq!(env.get_applied_migrations().await?, vec!["001_initial".to_string()]);
+    assert_eq!(env.get_tracked_objects().await?.len(), 1);
+
+    // Objects applied before definitions were stored have none
+    let active_users = QualifiedIdent::from_name("active_users".to_string());
+    assert_eq!(state_manager.get_object_ddl(&ObjectType::View, &active_users).await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_newer_schema() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let state_manager = StateManager::new(&env.client);
+    state_manager.initialize().await?;
+
+    env.execute_sql(&format!(
+        "INSERT INTO pgmg.pgmg_meta (version, description) VALUES ({}, 'From the future')",
+        STATE_SCHEMA_VERSION + 1
+    )).await?;
+
+    let err = state_manager.initialize().await.unwrap_err();
+    assert!(err.to_string().contains("Upgrade pgmg"), "{}", err);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_stores_object_ddl() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.write_sql_file("active_users.sql", "CREATE VIEW active_users AS\n    SELECT 1 AS id;").await?;
+
+    execute_apply(None, Some(env.sql_dir.clone()), env.connection_string.clone(), &PgmgConfig::default()).await?;
+
+    let state_manager = StateManager::new(&env.client);
+    let active_users = QualifiedIdent::from_name("active_users".to_string());
+    assert_eq!(
+        state_manager.get_object_ddl(&ObjectType::View, &active_users).await?.as_deref(),
+        Some("CREATE VIEW active_users AS\nSELECT 1 AS id;")
+    );
+
+    let tracked = state_manager.get_tracked_objects().await?;
+    assert!(tracked[0].ddl_source.is_some());
+
+    Ok(())
+}
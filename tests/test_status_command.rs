@@ -0,0 +1,68 @@
+mod common;
+
+use common::TestEnvironment;
+use pgmg::commands::{execute_apply, execute_status};
+use pgmg::config::PgmgConfig;
+use pgmg::sql::ObjectType;
+use indoc::indoc;
+
+#[tokio::test]
+async fn test_status_before_first_apply_creates_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+
+    let status = execute_status(Some(env.migrations_dir.clone()), env.connection_string.clone(), &PgmgConfig::default()).await?;
+    assert!(!status.initialized);
+    assert!(status.applied_migrations.is_empty());
+    assert_eq!(status.pending_migrations, vec!["001_orders".to_string()]);
+    assert!(status.last_run.is_none());
+
+    let has_schema: bool = env.client.query_one(
+        "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = 'pgmg')",
+        &[],
+    ).await?.get(0);
+    assert!(!has_schema, "status must not create the pgmg schema");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_status_reports_migrations_objects_and_drift() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY, total numeric);
+    "#}).await?;
+    env.write_sql_file("order_totals.sql", indoc! {r#"
+        CREATE VIEW order_totals AS SELECT id, total FROM orders;
+    "#}).await?;
+    env.write_sql_file("big_orders.sql", indoc! {r#"
+        CREATE VIEW big_orders AS SELECT id FROM orders WHERE total > 100;
+    "#}).await?;
+    execute_apply(Some(env.migrations_dir.clone()), Some(env.sql_dir.clone()), env.connection_string.clone(), &config).await?;
+
+    env.write_migration("002_customers", indoc! {r#"
+        CREATE TABLE customers (id integer PRIMARY KEY);
+    "#}).await?;
+    // Dropped behind pgmg's back
+    env.execute_sql("DROP VIEW big_orders").await?;
+
+    let status = execute_status(Some(env.migrations_dir.clone()), env.connection_string.clone(), &config).await?;
+    assert!(status.initialized);
+    assert_eq!(status.schema_version, pgmg::db::STATE_SCHEMA_VERSION);
+    assert_eq!(status.applied_migrations.len(), 1);
+    assert_eq!(status.applied_migrations[0].name, "001_orders");
+    assert_eq!(status.pending_migrations, vec!["002_customers".to_string()]);
+    assert_eq!(status.object_counts, vec![(ObjectType::View, 2)]);
+    assert_eq!(status.missing_objects, vec![(ObjectType::View, "public.big_orders".to_string())]);
+    assert!(status.lock_holders.is_empty());
+
+    let last_run = status.last_run.expect("the apply was recorded");
+    assert!(last_run.succeeded);
+    assert_eq!(last_run.changes.migrations_applied, vec!["001_orders".to_string()]);
+
+    Ok(())
+}
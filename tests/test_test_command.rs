@@ -0,0 +1,135 @@
+mod common;
+
+use common::TestEnvironment;
+use pgmg::config::PgmgConfig;
+use std::time::Duration;
+use pgmg::db::{connect_with_url, gc_test_databases, TestDatabase};
+use pgmg::db::test_utils::{build_connection_string, parse_connection_string};
+
+#[tokio::test]
+async fn test_fresh_test_database_is_built_and_seeded() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.write_migration("001_users", "CREATE TABLE users (id integer PRIMARY KEY, name text);").await?;
+    env.write_sql_file("user_names.sql", "CREATE VIEW user_names AS SELECT name FROM users;").await?;
+
+    let seed_dir = env.temp_dir.path().join("seeds");
+    std::fs::create_dir_all(&seed_dir)?;
+    std::fs::write(seed_dir.join("1_users.sql"), "INSERT INTO users VALUES (1, 'alice'), (2, 'bob');")?;
+
+    let test_db = TestDatabase::new_fresh(
+        &env.connection_string,
+        Some(env.migrations_dir.clone()),
+        Some(env.sql_dir.clone()),
+        Some(seed_dir),
+        &PgmgConfig::default(),
+    ).await?;
+
+    let (client, connection) = connect_with_url(&test_db.connection_string).await?;
+    connection.spawn();
+    let names: i64 = client.query_one("SELECT count(*) FROM user_names", &[]).await?.get(0);
+    assert_eq!(names, 2);
+
+    // The database the tests ran against is untouched
+    assert!(!env.table_exists("users").await?);
+
+    test_db.cleanup().await?;
+    Ok(())
+}
+
+
+#[tokio::test]
+async fn test_fresh_template_is_reused_until_files_change() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    env.write_migration("001_users", "CREATE TABLE users (id integer PRIMARY KEY, name text);").await?;
+
+    let seed_dir = env.temp_dir.path().join("seeds");
+    std::fs::create_dir_all(&seed_dir)?;
+    std::fs::write(seed_dir.join("1_users.sql"), "INSERT INTO users VALUES (1, 'alice');")?;
+
+    let config = PgmgConfig::default();
+    let template_name = format!("{}_pgmg_seeded_template", env.database_name);
+    // Rebuilding drops and recreates the template, giving it a new oid
+    let template_oid = |client: &tokio_postgres::Client| {
+        let template_name = template_name.clone();
+        async move {
+            client.query_one("SELECT oid FROM pg_database WHERE datname = $1", &[&template_name]).await
+                .map(|row| row.get::<_, u32>(0))
+        }
+    };
+    let count_users = |conn_str: String| async move {
+        let (client, connection) = connect_with_url(&conn_str).await?;
+        connection.spawn();
+        let count: i64 = client.query_one("SELECT count(*) FROM users", &[]).await?.get(0);
+        Ok::<_, Box<dyn std::error::Error>>(count)
+    };
+
+    let first = TestDatabase::new_fresh_from_template(
+        &env.connection_string, Some(env.migrations_dir.clone()), None, Some(seed_dir.clone()), &config, false,
+    ).await?;
+    assert_eq!(count_users(first.connection_string.clone()).await?, 1);
+    first.cleanup().await?;
+    let oid = template_oid(&env.client).await?;
+
+    // Nothing changed, so the template is cloned as is
+    let second = TestDatabase::new_fresh_from_template(
+        &env.connection_string, Some(env.migrations_dir.clone()), None, Some(seed_dir.clone()), &config, false,
+    ).await?;
+    second.cleanup().await?;
+    assert_eq!(template_oid(&env.client).await?, oid);
+
+    // A new seed file rebuilds it
+    std::fs::write(seed_dir.join("2_more_users.sql"), "INSERT INTO users VALUES (2, 'bob');")?;
+    let third = TestDatabase::new_fresh_from_template(
+        &env.connection_string, Some(env.migrations_dir.clone()), None, Some(seed_dir), &config, false,
+    ).await?;
+    assert_eq!(count_users(third.connection_string.clone()).await?, 2);
+    third.cleanup().await?;
+    assert_ne!(template_oid(&env.client).await?, oid);
+
+    env.client.execute(&format!("DROP DATABASE IF EXISTS {}", template_name), &[]).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_gc_drops_stale_test_databases() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let stale = format!("gc_stale_{}", suffix);
+    let recent = format!("gc_recent_{}", suffix);
+    let unrelated = format!("gc_app_{}", suffix);
+
+    for name in [&stale, &recent, &unrelated] {
+        env.client.execute(&format!("CREATE DATABASE {}", name), &[]).await?;
+    }
+    // Created in 2001 by a killed test run
+    env.client.batch_execute(&format!("COMMENT ON DATABASE {} IS 'pgmg test database created_at=1000000000000'", stale)).await?;
+    pgmg::db::test_utils::mark_test_database(&env.client, &recent).await?;
+
+    let admin_conn_str = build_connection_string(&parse_connection_string(&env.connection_string)?, "postgres");
+    let database_exists = |name: String| {
+        let client = &env.client;
+        async move {
+            let count: i64 = client.query_one("SELECT count(*) FROM pg_database WHERE datname = $1", &[&name]).await?.get(0);
+            Ok::<_, Box<dyn std::error::Error>>(count == 1)
+        }
+    };
+
+    // A dry run only lists the stale database
+    let found = gc_test_databases(&admin_conn_str, Duration::from_secs(60 * 60), true).await?;
+    let names: Vec<&str> = found.iter().map(|database| database.name.as_str()).collect();
+    assert!(names.contains(&stale.as_str()));
+    assert!(!names.contains(&recent.as_str()));
+    assert!(!names.contains(&unrelated.as_str()));
+    assert!(database_exists(stale.clone()).await?);
+
+    let dropped = gc_test_databases(&admin_conn_str, Duration::from_secs(60 * 60), false).await?;
+    assert!(dropped.iter().any(|database| database.name == stale && database.dropped));
+    assert!(!database_exists(stale).await?);
+    assert!(database_exists(recent.clone()).await?);
+    assert!(database_exists(unrelated.clone()).await?);
+
+    for name in [recent, unrelated] {
+        env.client.execute(&format!("DROP DATABASE {}", name), &[]).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use std::time::Duration;
+use common::TestEnvironment;
+use pgmg::commands::{execute_apply, execute_wait_for_apply};
+use pgmg::config::PgmgConfig;
+use indoc::indoc;
+
+#[tokio::test]
+async fn test_wait_for_apply_returns_when_apply_finishes() -> Result<(), Box<dyn std::error::Error>> {
+    let env = TestEnvironment::new().await?;
+    let config = PgmgConfig::default();
+
+    env.write_migration("001_orders", indoc! {r#"
+        CREATE TABLE orders (id integer PRIMARY KEY);
+    "#}).await?;
+
+    let wait = execute_wait_for_apply(env.connection_string.clone(), Duration::from_secs(30), None);
+    let apply = async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        execute_apply(Some(env.migrations_dir.clone()), None, env.connection_string.clone(), &config).await
+    };
+    let (apply, result) = tokio::join!(wait, apply);
+    result?;
+    let apply = apply?;
+    assert_eq!(apply.migrations_applied, 1);
+    assert_eq!(apply.objects_changed, 0);
+    assert!(apply.waited < Duration::from_secs(30));
+
+    // That apply is now in the past: a new wait only accepts it with --since
+    let recent = execute_wait_for_apply(env.connection_string.clone(), Duration::from_secs(5), Some(Duration::from_secs(60))).await?;
+    assert_eq!(recent.id, apply.id);
+
+    let err = execute_wait_for_apply(env.connection_string.clone(), Duration::from_secs(1), None).await.unwrap_err();
+    assert!(err.to_string().contains("No apply finished within"), "{}", err);
+
+    Ok(())
+}